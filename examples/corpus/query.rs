@@ -0,0 +1,121 @@
+//! A SQL-like query language: `select name, age from users where age >= 18 and not banned
+//! order by age desc limit 10`.
+
+// Shared by `tests/corpus_test.rs` and `examples/corpus_bench.rs`, which each use a part of it.
+#![allow(dead_code)]
+
+use syn_grammar::grammar;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(String),
+    Int(i64),
+    Str(String),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    /// Empty for `select *`.
+    pub columns: Vec<String>,
+    pub table: String,
+    pub filter: Option<Expr>,
+    pub order: Vec<OrderKey>,
+    pub limit: Option<u64>,
+}
+
+grammar! {
+    grammar dsl {
+        use super::{Expr, OrderKey, Query};
+
+        keywords { select, from, order, by, limit, and, or, not, asc, desc }
+
+        #[complete]
+        pub rule query -> Query =
+            "select" => columns:columns "from" table:ident filter:filter? keys:order_by? max:row_limit? -> {
+                Query {
+                    columns,
+                    table: table.to_string(),
+                    filter,
+                    order: keys.unwrap_or_default(),
+                    limit: max,
+                }
+            }
+
+        rule columns -> Vec<String> =
+            "*" -> { Vec::new() }
+          | cs:separated(column, ",", min=1) -> { cs }
+
+        rule column -> String = c:ident -> { c.to_string() }
+
+        #[inline_rule]
+        rule filter -> Expr = "where" e:expr -> { e }
+
+        rule order_by -> Vec<OrderKey> =
+            "order" "by" keys:separated(order_key, ",", min=1) -> { keys }
+
+        rule order_key -> OrderKey =
+            c:ident d:direction? -> { OrderKey { column: c.to_string(), descending: d.unwrap_or(false) } }
+
+        rule direction -> bool = "asc" -> { false } | "desc" -> { true }
+
+        #[inline_rule]
+        rule row_limit -> u64 = "limit" n:u64 -> { n }
+
+        pub rule expr -> Expr = precedence(atom) {
+            left "or" => |l, op, r| { Expr::Binary(Box::new(l), op, Box::new(r)) };
+            left "and" => |l, op, r| { Expr::Binary(Box::new(l), op, Box::new(r)) };
+            prefix "not" => |_, x| { Expr::Not(Box::new(x)) };
+            left "=" "!=" "<=" ">=" "<" ">" => |l, op, r| { Expr::Binary(Box::new(l), op, Box::new(r)) };
+        }
+
+        rule atom -> Expr =
+            n:i64 -> { Expr::Int(n) }
+          | s:string -> { Expr::Str(s.value) }
+          | c:ident -> { Expr::Column(c.to_string()) }
+          | paren(e:expr) -> { e }
+    }
+}
+
+/// Queries that must parse.
+pub const VALID: &[&str] = &[
+    "select * from users",
+    "select name, age from users where age >= 18",
+    "select id from orders where status = \"open\" and not (total < 10 or total > 1000)",
+    "select name from users order by age desc, name limit 10",
+    "select a from t where a != 1 limit 0",
+];
+
+/// Queries that must fail, with a part of the expected error message.
+pub const INVALID: &[(&str, &str)] = &[
+    ("select from users", "expected at least 1 items"),
+    ("select * users", "expected `from`"),
+    (
+        "select * from users where",
+        "unexpected end of input, expected identifier",
+    ),
+    (
+        "select * from users limit -1",
+        "value -1 does not fit in u64",
+    ),
+    ("select * from users order age", "expected `by`"),
+    ("select * from users where a = 1 extra", "found `extra`"),
+];
+
+/// A query with `terms` conditions in its `where` clause, for benchmarks.
+pub fn fixture(terms: usize) -> String {
+    let conditions: Vec<String> = (0..terms)
+        .map(|i| format!("(c{} >= {} or not c{} = \"v{}\")", i, i, i, i))
+        .collect();
+    format!(
+        "select a, b, c from t where {} order by a desc, b limit 100",
+        conditions.join(" and ")
+    )
+}
@@ -0,0 +1,105 @@
+//! A routing language: `get /users/{id: u32} => users::show;`, with nested `scope /api { .. }`
+//! blocks that prefix the paths of their routes.
+
+// Shared by `tests/corpus_test.rs` and `examples/corpus_bench.rs`, which each use a part of it.
+#![allow(dead_code)]
+
+use syn_grammar::grammar;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Static(String),
+    /// A parameter with its type, e.g. `{id: u32}`.
+    Param(String, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub method: dsl::Method,
+    pub path: Vec<Segment>,
+    pub handler: String,
+}
+
+/// Flattens the routes of nested scopes, prefixing their paths.
+fn scoped(prefix: Vec<Segment>, routes: Vec<Vec<Route>>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .flatten()
+        .map(|mut route| {
+            route.path.splice(0..0, prefix.iter().cloned());
+            route
+        })
+        .collect()
+}
+
+grammar! {
+    grammar dsl {
+        use super::{scoped, Route, Segment};
+
+        keywords { scope }
+
+        #[complete]
+        pub rule routes -> Vec<Route> = items:item* -> { items.into_iter().flatten().collect() }
+
+        rule item -> Vec<Route> =
+            "scope" => prefix:path { items:item* } -> { scoped(prefix, items) }
+          | r:route -> { vec![r] }
+
+        rule route -> Route =
+            method:method => path:path "=>" handler:handler ";" -> Route { method, path, handler }
+
+        #[derive_enum]
+        pub rule method -> Method =
+            "get" -> {} | "post" -> {} | "put" -> {} | "patch" -> {} | "delete" -> {}
+
+        rule path -> Vec<Segment> =
+            "/" segments:separated(segment, "/") -> { segments }
+
+        rule segment -> Segment =
+            { name:ident ":" ty:rust_type } -> { Segment::Param(name.to_string(), quote::quote!(#ty).to_string()) }
+          | s:ident -> { Segment::Static(s.to_string()) }
+
+        rule handler -> String = parts:separated(ident, "::", min=1) -> {
+            parts.iter().map(|p| p.to_string()).collect::<Vec<_>>().join("::")
+        }
+    }
+}
+
+/// Route tables that must parse.
+pub const VALID: &[&str] = &[
+    "",
+    "get / => index;",
+    "get /users => users::list; post /users => users::create;",
+    "get /users/{id: u32} => users::show; delete /users/{id: u32} => users::delete;",
+    "scope /api { get /health => health; scope /v1 { put /items/{key: String} => items::put; } }",
+];
+
+/// Route tables that must fail, with a part of the expected error message.
+pub const INVALID: &[(&str, &str)] = &[
+    ("fetch /users => users::list;", "expected one of: route"),
+    (
+        "get /users/ => users::list;",
+        "expected item after separator",
+    ),
+    ("get /users users::list;", "expected `=>`"),
+    ("get /users => ;", "expected at least 1 items"),
+    ("get /users/{id} => users::show;", "expected `:`"),
+    ("scope /api { get /health => health }", "expected `;`"),
+    // `scope` is reserved, so it cannot name a handler
+    ("get /users => scope;", "Error in rule 'handler'"),
+];
+
+/// A route table with `resources` resources of five routes each in a scope, for benchmarks.
+pub fn fixture(resources: usize) -> String {
+    let routes: String = (0..resources)
+        .map(|i| {
+            format!(
+                "get /r{i} => r{i}::list; post /r{i} => r{i}::create; \
+                 get /r{i}/{{id: u64}} => r{i}::show; put /r{i}/{{id: u64}} => r{i}::update; \
+                 delete /r{i}/{{id: u64}} => r{i}::delete;\n",
+                i = i
+            )
+        })
+        .collect();
+    format!("scope /api/v1 {{ {} }}", routes)
+}
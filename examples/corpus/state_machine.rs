@@ -0,0 +1,135 @@
+//! A state machine language:
+//!
+//! ```text
+//! machine door {
+//!     initial closed;
+//!     state closed { on open => opened; on lock if has_key => locked; }
+//!     state opened { on close => closed; }
+//!     state locked { on unlock if has_key => closed; }
+//! }
+//! ```
+
+// Shared by `tests/corpus_test.rs` and `examples/corpus_bench.rs`, which each use a part of it.
+#![allow(dead_code)]
+
+use syn_grammar::grammar;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub event: String,
+    pub guard: Option<String>,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct State {
+    pub name: String,
+    pub transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Machine {
+    pub name: String,
+    pub initial: String,
+    pub states: Vec<State>,
+}
+
+impl Machine {
+    fn declares(&self, state: &str) -> bool {
+        self.states.iter().any(|s| s.name == state)
+    }
+
+    /// Returns true if the initial state and all transition targets are declared states.
+    pub fn is_closed(&self) -> bool {
+        self.declares(&self.initial)
+            && self
+                .states
+                .iter()
+                .flat_map(|s| &s.transitions)
+                .all(|t| self.declares(&t.target))
+    }
+}
+
+grammar! {
+    grammar dsl {
+        use super::{Machine, State, Transition};
+
+        keywords { machine, initial, state, on }
+
+        #[complete]
+        pub rule machine -> Machine =
+            "machine" => name:ident {
+                "initial" start:ident ";"
+                states:state*
+            }
+            where { states.iter().any(|s| s.name == start.to_string()) }
+            -> Machine { name: name.to_string(), initial: start.to_string(), states }
+
+        #[commit_after_first]
+        rule state -> State =
+            "state" name:ident { transitions:transition* }
+            -> State { name: name.to_string(), transitions }
+
+        #[commit_after_first]
+        rule transition -> Transition =
+            "on" event:ident guard:guard? "=>" target:ident ";" -> {
+                Transition { event: event.to_string(), guard, target: target.to_string() }
+            }
+
+        #[inline_rule]
+        rule guard -> String = "if" cond:condition -> { cond }
+
+        rule condition -> String =
+            "!" c:ident -> { format!("!{}", c) }
+          | c:ident -> { c.to_string() }
+    }
+}
+
+/// Machines that must parse.
+pub const VALID: &[&str] = &[
+    "machine idle { initial only; state only { } }",
+    "machine light { initial red; state red { on timer => green; } state green { on timer => red; } }",
+    "machine door {
+        initial closed;
+        state closed { on open => opened; on lock if has_key => locked; }
+        state opened { on close => closed; }
+        state locked { on unlock if !jammed => closed; }
+    }",
+];
+
+/// Machines that must fail, with a part of the expected error message.
+pub const INVALID: &[(&str, &str)] = &[
+    ("machine m { initial a; }", "does not hold"),
+    ("machine m { initial b; state a { } }", "does not hold"),
+    ("machine m { state a { } }", "expected `initial`"),
+    (
+        "machine m { initial a; state a { on => a; } }",
+        "expected identifier",
+    ),
+    (
+        "machine m { initial a; state a { on go a; } }",
+        "expected `=>`",
+    ),
+    (
+        "machine m { initial a; state a { on go => a } }",
+        "expected `;`",
+    ),
+    (
+        "machine state { initial a; state a { } }",
+        "found keyword `state`",
+    ),
+];
+
+/// A machine with `states` states in a ring, each with two transitions, for benchmarks.
+pub fn fixture(states: usize) -> String {
+    let body: String = (0..states)
+        .map(|i| {
+            format!(
+                "state s{} {{ on next => s{}; on reset if !locked => s0; }}\n",
+                i,
+                (i + 1) % states
+            )
+        })
+        .collect();
+    format!("machine ring {{ initial s0; {} }}", body)
+}
@@ -0,0 +1,107 @@
+//! A CSS-like style language: `h1, .title { font-size: 24px; color: rgb(0, 0, 0) !important }`.
+
+// Shared by `tests/corpus_test.rs` and `examples/corpus_bench.rs`, which each use a part of it.
+#![allow(dead_code)]
+
+use syn_grammar::grammar;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    Tag(String),
+    Class(String),
+    Id(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A number with its unit, e.g. `24px` or `0`.
+    Number(u64, String),
+    Str(String),
+    Keyword(String),
+    Function(String, Vec<Value>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub property: String,
+    pub values: Vec<Value>,
+    pub important: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleRule {
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<Declaration>,
+}
+
+grammar! {
+    grammar dsl {
+        use super::{Declaration, Selector, StyleRule, Value};
+
+        #[complete]
+        pub rule stylesheet -> Vec<StyleRule> = rules:repeated(style_rule) -> { rules }
+
+        rule style_rule -> StyleRule =
+            selectors:separated(selector, ",", min=1)
+            { declarations:terminated(declaration, ";", required=auto) }
+            -> StyleRule { selectors, declarations }
+
+        rule selector -> Selector =
+            "." c:name -> { Selector::Class(c) }
+          | "#" i:name -> { Selector::Id(i) }
+          | t:name -> { Selector::Tag(t) }
+
+        // Hyphenated names such as `font-size`
+        rule name -> String = parts:separated(ident, "-", min=1) -> {
+            parts.iter().map(|p| p.to_string()).collect::<Vec<_>>().join("-")
+        }
+
+        rule declaration -> Declaration =
+            property:name => ":" values:repeated(value, min=1) i:important? -> {
+                Declaration { property, values, important: i.is_some() }
+            }
+
+        rule important -> () = "!" "important" -> { () }
+
+        rule value -> Value =
+            n:lit_int -> { Value::Number(n.base10_parse().unwrap(), n.suffix().to_string()) }
+          | s:string -> { Value::Str(s.value) }
+          | f:name paren(args:separated(value, ",")) -> { Value::Function(f, args) }
+          | k:name -> { Value::Keyword(k) }
+    }
+}
+
+/// Stylesheets that must parse.
+pub const VALID: &[&str] = &[
+    "",
+    "h1 { }",
+    "h1, .title, #main { font-size: 24px; color: black }",
+    "p { margin: 0 auto; font-family: \"Fira Sans\"; }",
+    "a { color: rgb(255, 0, 0) !important; border: 1px solid dark-gray }",
+    ".a { } .b { width: 10em; }",
+];
+
+/// Stylesheets that must fail, with a part of the expected error message.
+pub const INVALID: &[(&str, &str)] = &[
+    ("h1 { color }", "expected `:`"),
+    ("h1 { color: }", "expected at least 1 items"),
+    (
+        "h1 { color: red blue; width 1px }",
+        "Error in rule 'declaration'",
+    ),
+    ("{ color: red }", "expected at least 1 items"),
+    ("h1 { color: rgb(1 2) }", "expected `)`"),
+    ("h1 { color: red } 42", "Error in rule 'style_rule'"),
+];
+
+/// A stylesheet with `rules` rules, for benchmarks.
+pub fn fixture(rules: usize) -> String {
+    (0..rules)
+        .map(|i| {
+            format!(
+                "h{i}, .item-a{i}, #id{i} {{ margin: {i}px auto; color: rgb({i}, 0, 0) !important; font-family: \"Sans\" }}\n",
+                i = i
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,128 @@
+//! Times the grammars of `examples/corpus` on their benchmark fixtures.
+//!
+//! ```text
+//! cargo run --release --example corpus_bench [iterations]
+//! ```
+//!
+//! Each grammar is first checked against its valid and invalid inputs, so a fixture that no
+//! longer parses fails the run instead of timing an error path.
+
+use std::time::{Duration, Instant};
+use syn::parse::{ParseStream, Parser};
+
+#[path = "corpus/query.rs"]
+mod query;
+#[path = "corpus/routes.rs"]
+mod routes;
+#[path = "corpus/state_machine.rs"]
+mod state_machine;
+#[path = "corpus/style.rs"]
+mod style;
+
+struct Corpus<T> {
+    name: &'static str,
+    parser: fn(ParseStream) -> syn::Result<T>,
+    valid: &'static [&'static str],
+    invalid: &'static [(&'static str, &'static str)],
+    fixture: String,
+}
+
+impl<T> Corpus<T> {
+    fn check(&self) {
+        for input in self.valid {
+            if let Err(e) = self.parser.parse_str(input) {
+                panic!("{}: {:?} failed to parse: {}", self.name, input, e);
+            }
+        }
+        for (input, message) in self.invalid {
+            match self.parser.parse_str(input) {
+                Ok(_) => panic!("{}: {:?} parsed", self.name, input),
+                Err(e) if !e.to_string().contains(message) => {
+                    panic!(
+                        "{}: {:?} failed with {:?}, expected {:?}",
+                        self.name,
+                        input,
+                        e.to_string(),
+                        message
+                    )
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    fn bench(&self, iterations: u32) {
+        // Tokenizing is not part of the measurement
+        let tokens: proc_macro2::TokenStream = self.fixture.parse().expect("fixture tokenizes");
+        let start = Instant::now();
+        for _ in 0..iterations {
+            if let Err(e) = self.parser.parse2(tokens.clone()) {
+                panic!("{}: fixture failed to parse: {}", self.name, e);
+            }
+        }
+        let per_parse = start.elapsed() / iterations;
+        let tokens_per_sec =
+            count_tokens(tokens) as f64 / per_parse.max(Duration::from_nanos(1)).as_secs_f64();
+        println!(
+            "{:<14} {:>10.1?} per parse {:>14.0} tokens/s",
+            self.name, per_parse, tokens_per_sec
+        );
+    }
+}
+
+fn count_tokens(tokens: proc_macro2::TokenStream) -> usize {
+    tokens
+        .into_iter()
+        .map(|tt| match tt {
+            proc_macro2::TokenTree::Group(g) => 1 + count_tokens(g.stream()),
+            _ => 1,
+        })
+        .sum()
+}
+
+fn main() {
+    let iterations = std::env::args()
+        .nth(1)
+        .map(|n| n.parse().expect("iterations must be a number"))
+        .unwrap_or(200);
+
+    let query = Corpus {
+        name: "query",
+        parser: query::dsl::parse_query,
+        valid: query::VALID,
+        invalid: query::INVALID,
+        fixture: query::fixture(200),
+    };
+    let style = Corpus {
+        name: "style",
+        parser: style::dsl::parse_stylesheet,
+        valid: style::VALID,
+        invalid: style::INVALID,
+        fixture: style::fixture(200),
+    };
+    let state_machine = Corpus {
+        name: "state_machine",
+        parser: state_machine::dsl::parse_machine,
+        valid: state_machine::VALID,
+        invalid: state_machine::INVALID,
+        fixture: state_machine::fixture(200),
+    };
+    let routes = Corpus {
+        name: "routes",
+        parser: routes::dsl::parse_routes,
+        valid: routes::VALID,
+        invalid: routes::INVALID,
+        fixture: routes::fixture(100),
+    };
+
+    query.check();
+    style.check();
+    state_machine.check();
+    routes.check();
+
+    println!("{} iterations", iterations);
+    query.bench(iterations);
+    style.bench(iterations);
+    state_machine.bench(iterations);
+    routes.bench(iterations);
+}
@@ -0,0 +1,124 @@
+//! Rendering of parse errors as annotated source snippets, for CLI tools and test output.
+
+use std::fmt::{self, Write};
+
+/// Writes every message of `err` as an annotated snippet of `source`: the message, its location
+/// and the source lines of its span with the offending tokens underlined.
+///
+/// Messages without location data (line 0, e.g. spans created inside a procedural macro) or
+/// pointing outside of `source` are written without a snippet.
+pub fn write_error(out: &mut impl Write, source: &str, err: &syn::Error) -> fmt::Result {
+    let lines: Vec<&str> = source.lines().collect();
+    for (i, message) in err.clone().into_iter().enumerate() {
+        if i > 0 {
+            out.write_char('\n')?;
+        }
+        write_message(out, &lines, &message)?;
+    }
+    Ok(())
+}
+
+/// Renders `err` with `write_error` into a string.
+pub fn render_error(source: &str, err: &syn::Error) -> String {
+    let mut out = String::new();
+    write_error(&mut out, source, err).expect("writing to a String cannot fail");
+    out
+}
+
+fn write_message(out: &mut impl Write, lines: &[&str], err: &syn::Error) -> fmt::Result {
+    let (start, end) = (err.span().start(), err.span().end());
+    write!(out, "error: {}", err)?;
+    if start.line == 0 || start.line > lines.len() {
+        return Ok(());
+    }
+
+    let last = end.line.clamp(start.line, lines.len());
+    let gutter = last.to_string().len();
+    write!(
+        out,
+        "\n{:w$}--> line {}:{}\n{:w$} |",
+        "",
+        start.line,
+        start.column + 1,
+        "",
+        w = gutter
+    )?;
+    for line_no in start.line..=last {
+        let line = lines[line_no - 1];
+        let from = if line_no == start.line {
+            start.column
+        } else {
+            0
+        };
+        let to = if line_no == end.line {
+            end.column
+        } else {
+            line.chars().count()
+        };
+        write!(
+            out,
+            "\n{:>w$} | {}\n{:w$} | {}{}",
+            line_no,
+            line,
+            "",
+            " ".repeat(from),
+            "^".repeat(to.saturating_sub(from).max(1)),
+            w = gutter
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse::Parser;
+
+    #[test]
+    fn test_render_single_line() {
+        let source = "let x = 1\nlet y = ;";
+        let err = (|input: syn::parse::ParseStream| {
+            while !input.peek(syn::Token![;]) {
+                input.parse::<proc_macro2::TokenTree>()?;
+            }
+            Err::<(), _>(input.error("expected an expression"))
+        })
+        .parse_str(source)
+        .unwrap_err();
+
+        assert_eq!(
+            render_error(source, &err),
+            "error: expected an expression\n\
+             \x20--> line 2:9\n\
+             \x20 |\n\
+             2 | let y = ;\n\
+             \x20 |         ^"
+        );
+    }
+
+    #[test]
+    fn test_render_multi_line_and_combined() {
+        let source = "fn a() {\n}\nfn b";
+        let tokens: proc_macro2::TokenStream = source.parse().unwrap();
+        let tts: Vec<_> = tokens.into_iter().collect();
+
+        let mut err = syn::Error::new_spanned(&tts[3], "unclosed body");
+        err.combine(syn::Error::new(tts[5].span(), "missing parameters"));
+
+        assert_eq!(
+            render_error(source, &err),
+            "error: unclosed body\n\
+             \x20--> line 1:8\n\
+             \x20 |\n\
+             1 | fn a() {\n\
+             \x20 |        ^\n\
+             2 | }\n\
+             \x20 | ^\n\
+             error: missing parameters\n\
+             \x20--> line 3:4\n\
+             \x20 |\n\
+             3 | fn b\n\
+             \x20 |    ^"
+        );
+    }
+}
@@ -2,43 +2,80 @@
 
 #[cfg(feature = "syn")]
 use proc_macro2::Span;
+#[cfg(feature = "syn")]
+pub use proc_macro2::TokenStream;
+#[cfg(feature = "syn")]
+use std::cell::RefCell;
 use std::collections::HashSet;
 #[cfg(feature = "syn")]
+use std::rc::Rc;
+#[cfg(feature = "syn")]
+use syn::buffer::Cursor;
+#[cfg(feature = "syn")]
 use syn::parse::discouraged::Speculative;
 #[cfg(feature = "syn")]
 use syn::parse::ParseStream;
 #[cfg(feature = "syn")]
 use syn::Result;
 
+#[cfg(feature = "syn")]
+pub mod diagnostics;
 #[cfg(feature = "testing")]
 pub mod testing;
 
 /// Generic symbol table that tracks variable definitions in nested scopes.
+///
+/// Speculative parses do not copy the table: while a [`ScopeCheckpoint`] is open, changes are
+/// recorded in an undo log, and rolling back undoes only what changed since the checkpoint.
 #[derive(Clone, Default)]
 pub struct ScopeStack {
     scopes: Vec<HashSet<String>>,
+    /// Changes made while checkpoints are open, oldest first.
+    log: Vec<ScopeChange>,
+    open_checkpoints: usize,
+}
+
+/// A change to a [`ScopeStack`], recorded to be undone on rollback.
+#[derive(Clone)]
+enum ScopeChange {
+    Entered,
+    Exited(HashSet<String>),
+    Defined(String),
 }
 
+/// A position in the undo log of a [`ScopeStack`], see [`ScopeStack::checkpoint`].
+#[must_use = "a checkpoint must be committed or rolled back"]
+pub struct ScopeCheckpoint(usize);
+
 impl ScopeStack {
     pub fn new() -> Self {
         Self {
             scopes: vec![HashSet::new()],
+            log: Vec::new(),
+            open_checkpoints: 0,
         }
     }
 
     pub fn enter_scope(&mut self) {
         self.scopes.push(HashSet::new());
+        self.record(ScopeChange::Entered);
     }
 
     pub fn exit_scope(&mut self) {
         if self.scopes.len() > 1 {
-            self.scopes.pop();
+            if let Some(scope) = self.scopes.pop() {
+                self.record(ScopeChange::Exited(scope));
+            }
         }
     }
 
     pub fn define(&mut self, name: impl Into<String>) {
+        let name = name.into();
         if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name.into());
+            if !scope.contains(&name) {
+                scope.insert(name.clone());
+                self.record(ScopeChange::Defined(name));
+            }
         }
     }
 
@@ -54,6 +91,47 @@ impl ScopeStack {
     pub fn scopes(&self) -> &Vec<HashSet<String>> {
         &self.scopes
     }
+
+    /// Starts recording changes, so that they can be undone with [`ScopeStack::rollback`].
+    /// Every checkpoint must be either committed or rolled back, innermost first.
+    pub fn checkpoint(&mut self) -> ScopeCheckpoint {
+        self.open_checkpoints += 1;
+        ScopeCheckpoint(self.log.len())
+    }
+
+    /// Keeps the changes made since the checkpoint.
+    pub fn commit(&mut self, checkpoint: ScopeCheckpoint) {
+        let _ = checkpoint;
+        self.open_checkpoints -= 1;
+        if self.open_checkpoints == 0 {
+            self.log.clear();
+        }
+    }
+
+    /// Undoes the changes made since the checkpoint, newest first.
+    pub fn rollback(&mut self, checkpoint: ScopeCheckpoint) {
+        while self.log.len() > checkpoint.0 {
+            match self.log.pop() {
+                Some(ScopeChange::Entered) => {
+                    self.scopes.pop();
+                }
+                Some(ScopeChange::Exited(scope)) => self.scopes.push(scope),
+                Some(ScopeChange::Defined(name)) => {
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.remove(&name);
+                    }
+                }
+                None => break,
+            }
+        }
+        self.open_checkpoints -= 1;
+    }
+
+    fn record(&mut self, change: ScopeChange) {
+        if self.open_checkpoints > 0 {
+            self.log.push(change);
+        }
+    }
 }
 
 #[cfg(all(feature = "rt", feature = "syn"))]
@@ -64,6 +142,16 @@ struct ErrorState {
     priority: u8,
 }
 
+/// Prints how the best error is tracked, if tracing is on (see [`trace`]).
+macro_rules! trace_errors {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "trace")]
+        if trace_enabled() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
 /// Holds the state for backtracking and error reporting.
 /// This must be passed mutably through the parsing chain.
 #[cfg(feature = "rt")]
@@ -74,9 +162,28 @@ pub struct ParseContext {
     best_error: Option<ErrorState>,
     pub scopes: ScopeStack,
     rule_stack: Vec<String>,
+    /// Call sites of the monomorphized generic rules being parsed, innermost last.
+    instantiations: Vec<&'static str>,
     #[cfg(feature = "syn")]
     pub last_span: Option<Span>,
     fail_triggered: bool,
+    /// Errors that `recover` and `#[recover]` skipped past, in input order.
+    #[cfg(feature = "syn")]
+    recovered: Vec<syn::Error>,
+    /// Non-fatal diagnostics reported with [`ParseContext::warn`].
+    #[cfg(feature = "syn")]
+    warnings: Vec<syn::Error>,
+    /// The nodes of the concrete syntax tree while one is recorded (see [`parse_cst`]): the
+    /// children of each rule being parsed, innermost last, below the finished root nodes.
+    #[cfg(feature = "syn")]
+    cst: Option<Vec<Vec<Node>>>,
+    /// Notified when rules are entered and left (see [`Listener`]).
+    #[cfg(feature = "syn")]
+    listener: Option<Rc<RefCell<dyn Listener>>>,
+    /// The columns of the open indentation levels of `layout = significant` grammars,
+    /// innermost last (see [`start_layout`]).
+    #[cfg(feature = "syn")]
+    layout: LayoutStack,
 }
 
 #[cfg(feature = "rt")]
@@ -88,12 +195,30 @@ impl ParseContext {
             best_error: None,
             scopes: ScopeStack::new(),
             rule_stack: Vec::new(),
+            instantiations: Vec::new(),
             #[cfg(feature = "syn")]
             last_span: None,
             fail_triggered: false,
+            #[cfg(feature = "syn")]
+            recovered: Vec::new(),
+            #[cfg(feature = "syn")]
+            warnings: Vec::new(),
+            #[cfg(feature = "syn")]
+            cst: None,
+            #[cfg(feature = "syn")]
+            listener: SCOPED_LISTENER.with(|l| l.borrow().clone()),
+            #[cfg(feature = "syn")]
+            layout: LayoutStack::default(),
         }
     }
 
+    /// Sets the listener notified when rules are entered and left, replacing the one of an
+    /// enclosing [`with_listener`].
+    #[cfg(feature = "syn")]
+    pub fn set_listener(&mut self, listener: Rc<RefCell<dyn Listener>>) {
+        self.listener = Some(listener);
+    }
+
     pub fn set_fatal(&mut self, fatal: bool) {
         self.is_fatal = fatal;
     }
@@ -107,42 +232,149 @@ impl ParseContext {
     }
 
     pub fn enter_rule(&mut self, name: &str) {
-        #[cfg(feature = "trace")]
-        eprintln!("[TRACE] enter_rule: {}", name);
         self.rule_stack.push(name.to_string());
     }
 
     pub fn exit_rule(&mut self) {
-        let _name = self.rule_stack.pop();
-        #[cfg(feature = "trace")]
-        if let Some(n) = _name {
-            eprintln!("[TRACE] exit_rule: {}", n);
-        }
+        self.rule_stack.pop();
+    }
+
+    /// Enters a rule instantiated from a generic rule at `call_site`.
+    /// Errors recorded until the matching `exit_instantiation` note the call site.
+    pub fn enter_instantiation(&mut self, call_site: &'static str) {
+        self.instantiations.push(call_site);
+    }
+
+    pub fn exit_instantiation(&mut self) {
+        self.instantiations.pop();
     }
 
     /// Records an error if it is "deeper" than the current best error.
     #[cfg(feature = "syn")]
     pub fn record_error(&mut self, err: syn::Error, start_span: Span) {
+        let err = self.annotate_error(err);
+        self.record_annotated_error(err, start_span);
+    }
+
+    /// The errors skipped past by recovery points so far, in input order. Recoveries made
+    /// inside an alternative that was later backtracked are not included.
+    #[cfg(feature = "syn")]
+    pub fn recovered_errors(&self) -> &[syn::Error] {
+        &self.recovered
+    }
+
+    #[cfg(feature = "syn")]
+    pub fn take_recovered_errors(&mut self) -> Vec<syn::Error> {
+        std::mem::take(&mut self.recovered)
+    }
+
+    /// Reports a non-fatal diagnostic, e.g. from an action that accepts deprecated syntax.
+    /// Parsing continues; warnings of alternatives that are backtracked are discarded.
+    #[cfg(feature = "syn")]
+    pub fn warn(&mut self, warning: syn::Error) {
+        self.warnings.push(warning);
+    }
+
+    #[cfg(feature = "syn")]
+    pub fn warnings(&self) -> &[syn::Error] {
+        &self.warnings
+    }
+
+    #[cfg(feature = "syn")]
+    pub fn take_warnings(&mut self) -> Vec<syn::Error> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Starts recording a concrete syntax tree from the rules of grammars with `cst = true`.
+    #[cfg(feature = "syn")]
+    pub fn enable_cst(&mut self) {
+        self.cst.get_or_insert_with(|| vec![Vec::new()]);
+    }
+
+    /// Starts the node of a rule, if a concrete syntax tree is recorded.
+    #[cfg(feature = "syn")]
+    pub fn cst_enter(&mut self) {
+        if let Some(frames) = &mut self.cst {
+            frames.push(Vec::new());
+        }
+    }
+
+    /// Ends the node of the rule `rule` that started at `start` and ended at `end`. A rule that
+    /// matched becomes a child of the rule that called it; the nodes of one that failed are
+    /// dropped.
+    #[cfg(feature = "syn")]
+    pub fn cst_exit(&mut self, rule: &'static str, start: Cursor, end: Cursor, matched: bool) {
+        let Some(frames) = &mut self.cst else {
+            return;
+        };
+        let Some(children) = frames.pop() else {
+            return;
+        };
+        if matched {
+            let node = Node::between(rule, start, end, children);
+            if let Some(parent) = frames.last_mut() {
+                parent.push(node);
+            }
+        }
+    }
+
+    /// Takes the finished root nodes of the concrete syntax tree.
+    #[cfg(feature = "syn")]
+    pub fn take_cst(&mut self) -> Vec<Node> {
+        match &mut self.cst {
+            Some(frames) => frames.first_mut().map(std::mem::take).unwrap_or_default(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Prefixes the error with the current rule and notes the generic instantiations.
+    #[cfg(feature = "syn")]
+    fn annotate_error(&self, err: syn::Error) -> syn::Error {
+        // Enrich error with rule name if available, unless it was recorded in the same rule
+        let err = match self.rule_stack.last() {
+            Some(rule_name) => {
+                let prefix = format!("Error in rule '{}': ", rule_name);
+                if err.to_string().starts_with(&prefix) {
+                    err
+                } else {
+                    syn::Error::new(err.span(), format!("{}{}", prefix, err))
+                }
+            }
+            None => err,
+        };
+        self.instantiations
+            .iter()
+            .rev()
+            .fold(err, |err, call_site| note_instantiation(err, call_site))
+    }
+
+    /// Records the error of a parse that a recovery point skips past and restores the state of
+    /// the snapshot. Besides competing for the best error, it is kept in
+    /// [`ParseContext::recovered_errors`].
+    #[cfg(feature = "syn")]
+    fn recover_from(&mut self, err: syn::Error, start_span: Span, snapshot: Snapshot) {
+        // Annotate before restoring state to capture inner rule context
+        let err = self.annotate_error(err);
+        self.record_annotated_error(err.clone(), start_span);
+        self.rollback(snapshot);
+        self.recovered.push(err);
+    }
+
+    #[cfg(feature = "syn")]
+    fn record_annotated_error(&mut self, err: syn::Error, start_span: Span) {
         // Heuristic: Compare the error location to the start of the attempt.
         let is_deep = err.span().start() != start_span.start();
 
         let priority = if self.fail_triggered { 1 } else { 0 };
         self.fail_triggered = false; // Reset after consuming
 
-        #[cfg(feature = "trace")]
-        eprintln!(
+        trace_errors!(
             "[TRACE] record_error: '{}', is_deep: {}, priority: {}",
-            err, is_deep, priority
+            err,
+            is_deep,
+            priority
         );
 
-        // Enrich error with rule name if available
-        let err = if let Some(rule_name) = self.rule_stack.last() {
-            let msg = format!("Error in rule '{}': {}", rule_name, err);
-            syn::Error::new(err.span(), msg)
-        } else {
-            err
-        };
-
         let new_error_state = ErrorState {
             err,
             is_deep,
@@ -151,15 +383,13 @@ impl ParseContext {
 
         match &mut self.best_error {
             None => {
-                #[cfg(feature = "trace")]
-                eprintln!("[TRACE] New best error (was None): {}", new_error_state.err);
+                trace_errors!("[TRACE] New best error (was None): {}", new_error_state.err);
                 self.best_error = Some(new_error_state);
             }
             Some(existing) => {
                 // Higher priority always wins.
                 if new_error_state.priority > existing.priority {
-                    #[cfg(feature = "trace")]
-                    eprintln!(
+                    trace_errors!(
                         "[TRACE] Overwriting due to higher priority: {}",
                         new_error_state.err
                     );
@@ -171,8 +401,7 @@ impl ParseContext {
                 if new_error_state.priority == existing.priority {
                     if !existing.is_deep {
                         // Existing is shallow. Always update (Deep beats Shallow, Last Shallow wins).
-                        #[cfg(feature = "trace")]
-                        eprintln!("[TRACE] Overwriting shallow error: {}", new_error_state.err);
+                        trace_errors!("[TRACE] Overwriting shallow error: {}", new_error_state.err);
                         self.best_error = Some(new_error_state);
                     } else if new_error_state.is_deep {
                         // Both are deep. Prefer the one that is logically "further" in the input.
@@ -184,8 +413,7 @@ impl ParseContext {
                                 && new_start.column > old_start.column);
 
                         if is_deeper {
-                            #[cfg(feature = "trace")]
-                            eprintln!(
+                            trace_errors!(
                                 "[TRACE] Overwriting deep error with deeper error: {}",
                                 new_error_state.err
                             );
@@ -200,20 +428,17 @@ impl ParseContext {
     #[cfg(feature = "syn")]
     pub fn take_best_error(&mut self) -> Option<syn::Error> {
         let err = self.best_error.take().map(|s| s.err);
-        #[cfg(feature = "trace")]
-        if let Some(e) = &err {
-            eprintln!("[TRACE] take_best_error: {}", e);
-        } else {
-            eprintln!("[TRACE] take_best_error: None");
-        }
+        trace_errors!(
+            "[TRACE] take_best_error: {}",
+            err.as_ref().map_or("None".to_string(), |e| e.to_string())
+        );
         err
     }
 
     #[cfg(feature = "syn")]
     pub fn is_best_error_deep(&self) -> bool {
         let is_deep = self.best_error.as_ref().map(|e| e.is_deep).unwrap_or(false);
-        #[cfg(feature = "trace")]
-        eprintln!("[TRACE] is_best_error_deep: {}", is_deep);
+        trace_errors!("[TRACE] is_best_error_deep: {}", is_deep);
         is_deep
     }
 
@@ -262,6 +487,159 @@ impl ParseContext {
     pub fn rule_stack(&self) -> &Vec<String> {
         &self.rule_stack
     }
+
+    /// Saves the state that carries over from parsed input to the input after it, i.e. the
+    /// symbol table, so that parsing can later resume there with [`ParseContext::restore`].
+    /// Must not be called during a speculative parse.
+    pub fn checkpoint(&self) -> ContextCheckpoint {
+        let mut scopes = self.scopes.clone();
+        scopes.log.clear();
+        scopes.open_checkpoints = 0;
+        ContextCheckpoint { scopes }
+    }
+
+    /// Resets the context to the state saved by [`ParseContext::checkpoint`]. Errors and the
+    /// rule stack are cleared, as for a fresh context.
+    pub fn restore(&mut self, checkpoint: &ContextCheckpoint) {
+        #[cfg(feature = "syn")]
+        let listener = self.listener.take();
+        *self = ParseContext::new();
+        self.scopes = checkpoint.scopes.clone();
+        #[cfg(feature = "syn")]
+        {
+            self.listener = listener;
+        }
+    }
+
+    /// Saves the state a failed speculative parse must restore. Instead of copying the scopes
+    /// and the rule stack, this records a scope checkpoint and the rule stack depth: the rules
+    /// entered by a parse are exited again before it returns, or are left on top of the stack.
+    #[cfg(feature = "syn")]
+    fn snapshot(&mut self) -> Snapshot {
+        Snapshot {
+            scopes: self.scopes.checkpoint(),
+            rule_depth: self.rule_stack.len(),
+            last_span: self.last_span,
+            recovered: self.recovered.len(),
+            warnings: self.warnings.len(),
+            cst: self
+                .cst
+                .as_ref()
+                .map(|frames| (frames.len(), frames.last().map_or(0, Vec::len))),
+            layout: self.layout.checkpoint(),
+        }
+    }
+
+    /// Keeps the state changes made since the snapshot.
+    #[cfg(feature = "syn")]
+    fn commit(&mut self, snapshot: Snapshot) {
+        self.scopes.commit(snapshot.scopes);
+        self.layout.commit();
+    }
+
+    /// Restores the state of the snapshot.
+    #[cfg(feature = "syn")]
+    fn rollback(&mut self, snapshot: Snapshot) {
+        self.scopes.rollback(snapshot.scopes);
+        self.rule_stack.truncate(snapshot.rule_depth);
+        self.last_span = snapshot.last_span;
+        self.recovered.truncate(snapshot.recovered);
+        self.warnings.truncate(snapshot.warnings);
+        self.layout.rollback(snapshot.layout);
+        if let (Some(frames), Some((depth, children))) = (&mut self.cst, snapshot.cst) {
+            frames.truncate(depth);
+            if let Some(frame) = frames.last_mut() {
+                frame.truncate(children);
+            }
+        }
+    }
+}
+
+/// State saved by [`ParseContext::snapshot`].
+#[cfg(all(feature = "rt", feature = "syn"))]
+struct Snapshot {
+    scopes: ScopeCheckpoint,
+    rule_depth: usize,
+    last_span: Option<Span>,
+    recovered: usize,
+    warnings: usize,
+    /// The number of open nodes and the children of the innermost one.
+    cst: Option<(usize, usize)>,
+    /// The position in the undo log of the indentation levels.
+    layout: usize,
+}
+
+/// The open indentation levels of a `layout = significant` grammar. Like [`ScopeStack`], it
+/// records changes in an undo log only while snapshots are open, so grammars without layout
+/// do not pay for speculative parses.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[derive(Clone, Default)]
+struct LayoutStack {
+    levels: Vec<usize>,
+    /// Changes made while snapshots are open, oldest first.
+    log: Vec<LayoutChange>,
+    open_checkpoints: usize,
+}
+
+/// A change to a [`LayoutStack`], recorded to be undone on rollback. Closing a level records
+/// its column, since the level cannot be restored from the depth alone.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[derive(Clone)]
+enum LayoutChange {
+    Pushed,
+    Popped(usize),
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl LayoutStack {
+    fn push(&mut self, column: usize) {
+        self.levels.push(column);
+        self.record(LayoutChange::Pushed);
+    }
+
+    fn pop(&mut self) {
+        if let Some(column) = self.levels.pop() {
+            self.record(LayoutChange::Popped(column));
+        }
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.open_checkpoints += 1;
+        self.log.len()
+    }
+
+    fn commit(&mut self) {
+        self.open_checkpoints -= 1;
+        if self.open_checkpoints == 0 {
+            self.log.clear();
+        }
+    }
+
+    fn rollback(&mut self, checkpoint: usize) {
+        while self.log.len() > checkpoint {
+            match self.log.pop() {
+                Some(LayoutChange::Pushed) => {
+                    self.levels.pop();
+                }
+                Some(LayoutChange::Popped(column)) => self.levels.push(column),
+                None => break,
+            }
+        }
+        self.open_checkpoints -= 1;
+    }
+
+    fn record(&mut self, change: LayoutChange) {
+        if self.open_checkpoints > 0 {
+            self.log.push(change);
+        }
+    }
+}
+
+/// State saved by [`ParseContext::checkpoint`].
+#[cfg(feature = "rt")]
+#[derive(Clone)]
+pub struct ContextCheckpoint {
+    scopes: ScopeStack,
 }
 
 #[cfg(feature = "rt")]
@@ -276,6 +654,42 @@ impl Default for ParseContext {
 #[cfg(all(feature = "rt", feature = "syn"))]
 #[inline]
 pub fn attempt<T, F>(input: ParseStream, ctx: &mut ParseContext, parser: F) -> Result<Option<T>>
+where
+    F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+{
+    attempt_at(input, ctx, input.span(), parser)
+}
+
+/// Safety net for the closures of [`attempt`] and the other helpers that parse on a fork: a
+/// closure that parses from a captured outer `input` instead of its parameter moves the outer
+/// stream, which no rollback can undo. A successful attempt then looks as if it matched nothing,
+/// and a failed one leaves the tokens it consumed behind. In debug builds, panics if the outer
+/// stream moved since `start` while `helper` ran its closure.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[inline]
+fn check_fork_use<T>(input: ParseStream, start: Cursor, helper: &str, res: &Result<T>) {
+    if cfg!(debug_assertions) && input.cursor() != start {
+        let outcome = match res {
+            Ok(_) => "succeeded without advancing its fork, but the outer input advanced",
+            Err(_) => "failed after advancing the outer input",
+        };
+        panic!(
+            "rt::{}: the closure {}; it must parse from the stream passed to it (name the parameter `input` to shadow the outer one)",
+            helper, outcome
+        );
+    }
+}
+
+/// Like [`attempt`], but failures are recorded as if the attempt had started at `start_span`,
+/// e.g. at the start of a prefix parsed once for several alternatives.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[inline]
+pub fn attempt_at<T, F>(
+    input: ParseStream,
+    ctx: &mut ParseContext,
+    start_span: Span,
+    parser: F,
+) -> Result<Option<T>>
 where
     F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
 {
@@ -283,31 +697,34 @@ where
     ctx.set_fatal(false);
 
     // Snapshot symbol table, rule stack, and last_span
-    let scopes_snapshot = ctx.scopes.clone();
-    let rule_stack_snapshot = ctx.rule_stack.clone();
-    let last_span_snapshot = ctx.last_span;
+    let snapshot = ctx.snapshot();
 
-    let start_span = input.span();
+    let start = input.cursor();
     let fork = input.fork();
 
     // Pass ctx into the closure
     let res = parser(&fork, ctx);
+    check_fork_use(input, start, "attempt", &res);
 
     let is_now_fatal = ctx.check_fatal();
 
+    match &res {
+        Ok(_) => trace(&fork, ctx, format_args!("attempt succeeded")),
+        Err(e) => trace(input, ctx, format_args!("attempt failed: {}", e)),
+    }
+
     match res {
         Ok(val) => {
             input.advance_to(&fork);
             ctx.set_fatal(was_fatal);
             // We KEEP the last_span updated by the successful attempt
+            ctx.commit(snapshot);
             Ok(Some(val))
         }
         Err(e) => {
             if is_now_fatal {
                 // Restore state
-                ctx.scopes = scopes_snapshot;
-                ctx.rule_stack = rule_stack_snapshot;
-                ctx.last_span = last_span_snapshot;
+                ctx.rollback(snapshot);
 
                 ctx.set_fatal(true);
                 Err(e)
@@ -317,9 +734,7 @@ where
                 ctx.record_error(e, start_span);
 
                 // Restore state
-                ctx.scopes = scopes_snapshot;
-                ctx.rule_stack = rule_stack_snapshot;
-                ctx.last_span = last_span_snapshot;
+                ctx.rollback(snapshot);
 
                 Ok(None)
             }
@@ -327,116 +742,1230 @@ where
     }
 }
 
-/// Executes a parser on a fork, returning the result but NEVER advancing the input.
-/// Restores ParseContext state (scopes, last_span) to what it was before.
+/// Prints `event` with the position of `input` and the next tokens to stderr, indented by
+/// the depth of the rule stack. Tracing is compiled in with the `trace` feature and turned on
+/// by setting the `SYN_GRAMMAR_TRACE` environment variable (to anything but `0`); otherwise
+/// this does nothing.
 #[cfg(all(feature = "rt", feature = "syn"))]
 #[inline]
-pub fn peek<T, F>(input: ParseStream, ctx: &mut ParseContext, parser: F) -> Result<T>
-where
-    F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
-{
-    let fork = input.fork();
-
-    // Snapshot state
-    let scopes_snapshot = ctx.scopes.clone();
-    let rule_stack_snapshot = ctx.rule_stack.clone();
-    let last_span_snapshot = ctx.last_span;
-
-    let res = parser(&fork, ctx);
-
-    // Always restore state because we are peeking (state side effects should not persist)
-    ctx.scopes = scopes_snapshot;
-    ctx.rule_stack = rule_stack_snapshot;
-    ctx.last_span = last_span_snapshot;
-
-    res
+pub fn trace(input: ParseStream, ctx: &ParseContext, event: std::fmt::Arguments) {
+    #[cfg(feature = "trace")]
+    if trace_enabled() {
+        eprintln!("{}", trace_line(input, ctx.rule_stack.len(), event));
+    }
+    #[cfg(not(feature = "trace"))]
+    let _ = (input, ctx, event);
 }
 
-/// Executes a parser on a fork.
-/// If it SUCCEEDS, returns Err("unexpected match").
-/// If it FAILS, returns Ok(()).
-/// Never advances input. Restores state.
+/// Traces entering the rule `name`, after [`ParseContext::enter_rule`], and notifies the
+/// listener of the context. Returns the position where the rule starts, for [`trace_exit`].
 #[cfg(all(feature = "rt", feature = "syn"))]
 #[inline]
-pub fn not_check<T, F>(input: ParseStream, ctx: &mut ParseContext, parser: F) -> Result<()>
-where
-    F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
-{
-    let fork = input.fork();
+pub fn trace_enter<'a>(input: ParseStream<'a>, ctx: &ParseContext, name: &str) -> Cursor<'a> {
+    trace(input, ctx, format_args!("enter {}", name));
+    if let Some(listener) = &ctx.listener {
+        // A listener parsing again from its callback is not notified of that parse
+        if let Ok(mut listener) = listener.try_borrow_mut() {
+            listener.on_enter(name, input.span());
+        }
+    }
+    input.cursor()
+}
 
-    // Snapshot state
-    let scopes_snapshot = ctx.scopes.clone();
-    let rule_stack_snapshot = ctx.rule_stack.clone();
-    let last_span_snapshot = ctx.last_span;
+/// Traces leaving the rule `name` that started at `start` with `res`, before
+/// [`ParseContext::exit_rule`], and notifies the listener of the context.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[inline]
+pub fn trace_exit<T>(
+    input: ParseStream,
+    ctx: &ParseContext,
+    name: &str,
+    start: Cursor,
+    res: &Result<T>,
+) {
+    match res {
+        Ok(_) => trace(input, ctx, format_args!("exit {}: ok", name)),
+        Err(e) => trace(input, ctx, format_args!("exit {}: {}", name, e)),
+    }
+    if let Some(listener) = &ctx.listener {
+        if let Ok(mut listener) = listener.try_borrow_mut() {
+            match res {
+                Ok(_) => listener.on_exit(name, span_between(start, input.cursor())),
+                Err(e) => listener.on_error(name, e),
+            }
+        }
+    }
+}
 
-    // Disable fatal errors for the check to allow backtracking/failure
-    let was_fatal = ctx.check_fatal();
-    ctx.set_fatal(false);
+/// Callbacks on entering and leaving rules, e.g. to profile a grammar, log the parse or build
+/// a symbol table without changing the grammar. All methods do nothing by default.
+///
+/// A listener is set for the parses in a closure with [`with_listener`], or for a context
+/// with [`ParseContext::set_listener`]. It also sees the rules of alternatives that are
+/// attempted and then backtracked, which fail with [`Listener::on_error`] or are left with
+/// [`Listener::on_exit`] before their alternative fails.
+#[cfg(feature = "syn")]
+pub trait Listener {
+    /// The rule `rule` is entered before the token at `span`.
+    fn on_enter(&mut self, rule: &str, span: Span) {
+        let _ = (rule, span);
+    }
 
-    let res = parser(&fork, ctx);
+    /// The rule `rule` matched the tokens spanned by `span`.
+    fn on_exit(&mut self, rule: &str, span: Span) {
+        let _ = (rule, span);
+    }
 
-    // Restore fatal flag
-    ctx.set_fatal(was_fatal);
+    /// The rule `rule` failed with `error`.
+    fn on_error(&mut self, rule: &str, error: &syn::Error) {
+        let _ = (rule, error);
+    }
+}
 
-    // Restore state
-    ctx.scopes = scopes_snapshot;
-    ctx.rule_stack = rule_stack_snapshot;
-    ctx.last_span = last_span_snapshot;
+#[cfg(feature = "syn")]
+std::thread_local! {
+    static SCOPED_LISTENER: RefCell<Option<Rc<RefCell<dyn Listener>>>> = const { RefCell::new(None) };
+}
 
-    match res {
-        Ok(_) => Err(syn::Error::new(input.span(), "unexpected match")),
-        Err(_) => Ok(()),
+/// Runs `f` with `listener` set for every [`ParseContext`] created in it on this thread, so
+/// that the entry points of the rules notify it. The listener stays accessible to the caller
+/// through the `Rc`.
+///
+/// ```rust,ignore
+/// let profile = Rc::new(RefCell::new(Profile::default()));
+/// let value = rt::with_listener(&profile, || calc::parse_expr.parse_str("1 + 2"));
+/// println!("{:?}", profile.borrow().calls);
+/// ```
+#[cfg(feature = "syn")]
+pub fn with_listener<L: Listener + 'static, R>(
+    listener: &Rc<RefCell<L>>,
+    f: impl FnOnce() -> R,
+) -> R {
+    struct Restore(Option<Rc<RefCell<dyn Listener>>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            SCOPED_LISTENER.with(|l| *l.borrow_mut() = previous);
+        }
     }
+
+    let listener: Rc<RefCell<dyn Listener>> = listener.clone();
+    let _restore = Restore(SCOPED_LISTENER.with(|l| l.borrow_mut().replace(listener)));
+    f()
 }
 
-/// Wrapper around attempt used specifically for recovery blocks.
+#[cfg(feature = "trace")]
+fn trace_enabled() -> bool {
+    static ENABLED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *ENABLED.get_or_init(|| {
+        std::env::var_os("SYN_GRAMMAR_TRACE").is_some_and(|v| !v.is_empty() && v != "0")
+    })
+}
+
+/// Formats a trace line, e.g. ``[TRACE]   enter expr at 1:4 before `a + b ...` ``.
 #[cfg(all(feature = "rt", feature = "syn"))]
-#[inline]
-pub fn attempt_recover<T, F>(
+#[cfg_attr(not(feature = "trace"), allow(dead_code))]
+fn trace_line(input: ParseStream, depth: usize, event: std::fmt::Arguments) -> String {
+    const SHOWN: usize = 3;
+
+    let position = if input.is_empty() {
+        "at end of input".to_string()
+    } else {
+        let start = input.span().start();
+        let tokens: Vec<proc_macro2::TokenTree> = input
+            .fork()
+            .parse::<TokenStream>()
+            .map(|t| t.into_iter().take(SHOWN + 1).collect())
+            .unwrap_or_default();
+        let shown: TokenStream = tokens.iter().take(SHOWN).cloned().collect();
+        let ellipsis = if tokens.len() > SHOWN { " ..." } else { "" };
+        format!(
+            "at {}:{} before `{}{}`",
+            start.line,
+            start.column + 1,
+            shown,
+            ellipsis
+        )
+    };
+    format!("[TRACE] {}{} {}", "  ".repeat(depth), event, position)
+}
+
+/// Runs `parser` on the input and, if it fails before consuming any input, reports
+/// "expected {label}" at the span of the error instead, for patterns and rules annotated with
+/// `#[expected("label")]`. Failures after the first token keep their more precise error.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn expected<T, F>(
     input: ParseStream,
     ctx: &mut ParseContext,
+    label: &str,
     parser: F,
-) -> Result<Option<T>>
+) -> Result<T>
 where
     F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
 {
-    let was_fatal = ctx.check_fatal();
-    ctx.set_fatal(false);
-
-    // Snapshot symbol table and rule stack
-    let scopes_snapshot = ctx.scopes.clone();
-    let rule_stack_snapshot = ctx.rule_stack.clone();
-    let last_span_snapshot = ctx.last_span;
-
-    let start_span = input.span();
-    let fork = input.fork();
+    let start = input.span().start();
+    parser(input, ctx).map_err(|err| {
+        if err.span().start() != start {
+            return err;
+        }
+        // The errors recorded inside describe the same position in terms of the label
+        if !ctx.is_best_error_deep() {
+            let _ = ctx.take_best_error();
+        }
+        syn::Error::new(err.span(), format!("expected {}", label))
+    })
+}
+
+/// Runs `parser` on the input and makes its failure fatal, for patterns committed with a `!`
+/// suffix: the enclosing alternatives do not backtrack, like after a cut.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn commit<T, F>(input: ParseStream, ctx: &mut ParseContext, parser: F) -> Result<T>
+where
+    F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+{
+    parser(input, ctx).inspect_err(|_| ctx.set_fatal(true))
+}
+
+/// Error of a bounded repetition that matched too few items: "expected {expected}" at the
+/// position of the missing item, e.g. "expected between 2 and 4 digits". The shallow error of
+/// the failed item describes the same position and is dropped.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn too_few(input: ParseStream, ctx: &mut ParseContext, expected: &str) -> syn::Error {
+    if !ctx.is_best_error_deep() {
+        let _ = ctx.take_best_error();
+    }
+    syn::Error::new(input.span(), format!("expected {}", expected))
+}
+
+/// Error of a variant whose `where { condition }` guard is false: "condition `..` does not
+/// hold" at the last token consumed so far (see [`ParseContext::record_span`]). Like `fail`, it
+/// is reported in preference to the syntax errors of other alternatives.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn guard_failed(input: ParseStream, ctx: &mut ParseContext, condition: &str) -> syn::Error {
+    ctx.trigger_fail();
+    let span = ctx.last_span.unwrap_or_else(|| input.span());
+    syn::Error::new(span, format!("condition `{}` does not hold", condition))
+}
+
+/// Error of a variant whose action in a rule with `throws Error` returned `error`: its message
+/// at the tokens consumed since `start`, the start of the rule. Like a failed guard, it is
+/// reported in preference to the syntax errors of other alternatives.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn action_failed<E: std::fmt::Display>(
+    input: ParseStream,
+    ctx: &mut ParseContext,
+    start: Cursor,
+    error: E,
+) -> syn::Error {
+    ctx.trigger_fail();
+    syn::Error::new(span_between(start, input.cursor()), error)
+}
+
+/// Attempts alternatives that share a prefix, parsing the prefix only once.
+///
+/// `alternatives` receives the prefix value, or `None` if the prefix failed, and the span
+/// where the prefix started, to attempt each alternative with [`attempt_at`]. The input only
+/// advances and the state changes are only kept if an alternative matches.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn attempt_hoisted<P, T, F, G>(
+    input: ParseStream,
+    ctx: &mut ParseContext,
+    prefix: F,
+    alternatives: G,
+) -> Result<Option<T>>
+where
+    F: FnOnce(ParseStream, &mut ParseContext) -> Result<P>,
+    G: FnOnce(ParseStream, &mut ParseContext, Option<P>, Span) -> Result<Option<T>>,
+{
+    let snapshot = ctx.snapshot();
+    let start_span = input.span();
+    let start = input.cursor();
+    let fork = input.fork();
+
+    let res =
+        attempt(&fork, ctx, prefix).and_then(|prefix| alternatives(&fork, ctx, prefix, start_span));
+    check_fork_use(input, start, "attempt_hoisted", &res);
+    match res {
+        Ok(Some(val)) => {
+            input.advance_to(&fork);
+            ctx.commit(snapshot);
+            Ok(Some(val))
+        }
+        Ok(None) => {
+            ctx.rollback(snapshot);
+            Ok(None)
+        }
+        Err(e) => {
+            ctx.rollback(snapshot);
+            Err(e)
+        }
+    }
+}
+
+/// Executes a parser on a fork, returning the result but NEVER advancing the input.
+/// Restores ParseContext state (scopes, last_span) to what it was before.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[inline]
+pub fn peek<T, F>(input: ParseStream, ctx: &mut ParseContext, parser: F) -> Result<T>
+where
+    F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+{
+    let start = input.cursor();
+    let fork = input.fork();
+
+    // Snapshot state
+    let snapshot = ctx.snapshot();
+
+    let res = parser(&fork, ctx);
+    check_fork_use(input, start, "peek", &res);
+
+    // Always restore state because we are peeking (state side effects should not persist)
+    ctx.rollback(snapshot);
+
+    res
+}
+
+/// Executes a parser on a fork.
+/// If it SUCCEEDS, returns Err("unexpected match").
+/// If it FAILS, returns Ok(()).
+/// Never advances input. Restores state.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[inline]
+pub fn not_check<T, F>(input: ParseStream, ctx: &mut ParseContext, parser: F) -> Result<()>
+where
+    F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+{
+    let start = input.cursor();
+    let fork = input.fork();
+
+    // Snapshot state
+    let snapshot = ctx.snapshot();
+
+    // Disable fatal errors for the check to allow backtracking/failure
+    let was_fatal = ctx.check_fatal();
+    ctx.set_fatal(false);
+
+    let res = parser(&fork, ctx);
+    check_fork_use(input, start, "not_check", &res);
+
+    // Restore fatal flag
+    ctx.set_fatal(was_fatal);
+
+    // Restore state
+    ctx.rollback(snapshot);
+
+    match res {
+        Ok(_) => Err(syn::Error::new(input.span(), "unexpected match")),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Lookbehind for `follows(pattern)`: succeeds without consuming input if the tokens from
+/// `start` up to the current position end with a match of `parser` and the next token starts
+/// right where they end, without whitespace in between.
+/// Tries the shortest suffix first. Restores state and keeps the best error of the caller.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn follows<T, F>(
+    start: Cursor,
+    input: ParseStream,
+    ctx: &mut ParseContext,
+    mut parser: F,
+) -> Result<()>
+where
+    F: FnMut(ParseStream, &mut ParseContext) -> Result<T>,
+{
+    let consumed: Vec<proc_macro2::TokenTree> =
+        tokens_between(start, input.cursor()).into_iter().collect();
+    let Some(last) = consumed.last() else {
+        return Err(syn::Error::new(input.span(), "expected a preceding token"));
+    };
+    if input.is_empty() {
+        return Err(syn::Error::new(input.span(), "unexpected end of input"));
+    }
+    if last.span().end() != input.span().start() {
+        return Err(syn::Error::new(
+            input.span(),
+            "unexpected whitespace before this token",
+        ));
+    }
+
+    let best_error = ctx.best_error.take();
+    let was_fatal = ctx.check_fatal();
+    ctx.set_fatal(false);
+    let mut matched = false;
+    for i in (0..consumed.len()).rev() {
+        let suffix: TokenStream = consumed[i..].iter().cloned().collect();
+        let snapshot = ctx.snapshot();
+        let res = syn::parse::Parser::parse2(|input: ParseStream| parser(input, ctx), suffix);
+        ctx.rollback(snapshot);
+        if res.is_ok() {
+            matched = true;
+            break;
+        }
+    }
+    ctx.set_fatal(was_fatal);
+    ctx.best_error = best_error;
+
+    if matched {
+        Ok(())
+    } else {
+        Err(syn::Error::new(
+            last.span(),
+            "the preceding tokens do not match the `follows` pattern",
+        ))
+    }
+}
+
+/// Wrapper around attempt used specifically for recovery blocks.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[inline]
+pub fn attempt_recover<T, F>(
+    input: ParseStream,
+    ctx: &mut ParseContext,
+    parser: F,
+) -> Result<Option<T>>
+where
+    F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+{
+    let was_fatal = ctx.check_fatal();
+    ctx.set_fatal(false);
+
+    // Snapshot symbol table and rule stack
+    let snapshot = ctx.snapshot();
+
+    let start_span = input.span();
+    let start = input.cursor();
+    let fork = input.fork();
 
     let res = parser(&fork, ctx);
+    check_fork_use(input, start, "attempt_recover", &res);
+
+    // Always restore fatal state, ignoring whatever happened inside.
+    ctx.set_fatal(was_fatal);
+
+    match res {
+        Ok(val) => {
+            input.advance_to(&fork);
+            // Keep last_span
+            ctx.commit(snapshot);
+            Ok(Some(val))
+        }
+        Err(e) => {
+            ctx.recover_from(e, start_span, snapshot);
+
+            Ok(None)
+        }
+    }
+}
+
+/// Runs a custom scanner on the raw token cursor of `input`.
+///
+/// The scanner returns the scanned value and the cursor after the consumed tokens, or `None`
+/// if the input does not match; `input` is then left untouched. This is the escape hatch for
+/// token-level matching that the grammar cannot express, e.g. adjacent puncts forming a
+/// custom operator.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn with_cursor<T, F>(input: ParseStream, scanner: F) -> Result<T>
+where
+    F: for<'c> FnOnce(Cursor<'c>) -> Option<(T, Cursor<'c>)>,
+{
+    input.step(|step| scanner(*step).ok_or_else(|| step.error("unexpected token")))
+}
+
+/// `with_cursor` for the `scan` built-in: fails with "expected {expected}" and records the span
+/// of the last consumed token.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn scan<T, F>(
+    input: ParseStream,
+    ctx: &mut ParseContext,
+    expected: &str,
+    scanner: F,
+) -> Result<T>
+where
+    F: for<'c> FnOnce(Cursor<'c>) -> Option<(T, Cursor<'c>)>,
+{
+    let mut last_span = None;
+    let value = input.step(|step| {
+        let start = *step;
+        let (value, rest) =
+            scanner(start).ok_or_else(|| step.error(format!("expected {}", expected)))?;
+        let mut cursor = start;
+        while cursor != rest {
+            let Some((tt, next)) = cursor.token_tree() else {
+                break;
+            };
+            last_span = Some(tt.span());
+            cursor = next;
+        }
+        Ok((value, rest))
+    })?;
+    if let Some(span) = last_span {
+        ctx.record_span(span);
+    }
+    Ok(value)
+}
+
+/// The `punct(..)` built-in: matches the first of `options`, punctuation such as `"+"` or
+/// `"**"`, by looking at the puncts at the cursor. The characters of a multi-character option
+/// must be joined without whitespace, so longer options must come first. Returns the matched
+/// option and its span, and fails with "expected one of ..." naming all options.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn punct(
+    input: ParseStream,
+    ctx: &mut ParseContext,
+    options: &[&'static str],
+) -> Result<(&'static str, Span)> {
+    let matched = input.step(|step| {
+        for option in options {
+            if let Some((first, last, rest)) = match_punct(*step, option) {
+                return Ok((Some((*option, first, last)), rest));
+            }
+        }
+        Ok((None, *step))
+    })?;
+    let Some((option, first, last)) = matched else {
+        let names: Vec<String> = options.iter().map(|o| format!("`{}`", o)).collect();
+        let message = match names.len() {
+            1 => format!("expected {}", names[0]),
+            _ => format!("expected one of {}", names.join(", ")),
+        };
+        return Err(syn::Error::new(input.span(), message));
+    };
+    ctx.record_span(last);
+    Ok((option, first.join(last).unwrap_or(first)))
+}
+
+/// Matches the characters of `option` against consecutive puncts, returning the spans of the
+/// first and last punct and the cursor after them.
+#[cfg(all(feature = "rt", feature = "syn"))]
+fn match_punct<'c>(mut cursor: Cursor<'c>, option: &str) -> Option<(Span, Span, Cursor<'c>)> {
+    let len = option.chars().count();
+    let mut spans = None;
+    for (i, ch) in option.chars().enumerate() {
+        let (punct, next) = cursor.punct()?;
+        if punct.as_char() != ch || (i + 1 < len && punct.spacing() != proc_macro2::Spacing::Joint)
+        {
+            return None;
+        }
+        let (first, _) = spans.unwrap_or((punct.span(), punct.span()));
+        spans = Some((first, punct.span()));
+        cursor = next;
+    }
+    spans.map(|(first, last)| (first, last, cursor))
+}
+
+/// A container that the list built-ins (`separated`, `repeated`, `delimited`, `terminated`)
+/// collect their items into, chosen with a generic argument such as `separated<VecDeque>(..)`.
+/// `min=` counts the items parsed, so a set can hold fewer items than `min` after duplicates.
+///
+/// Implement it for your own types to collect into them, e.g. an arena, or a map that
+/// extracts the key from each item:
+///
+/// ```ignore
+/// impl rt::Collect<Field> for IndexMap<String, Field> {
+///     fn empty() -> Self {
+///         IndexMap::new()
+///     }
+///
+///     fn push(&mut self, field: Field) {
+///         self.insert(field.name.clone(), field);
+///     }
+/// }
+/// ```
+#[cfg(feature = "rt")]
+pub trait Collect<T> {
+    /// The container before the first item.
+    fn empty() -> Self;
+    fn push(&mut self, item: T);
+}
+
+/// The separators of a list built-in, passed to its container after the item before them.
+/// Most containers drop them; [`syn::punctuated::Punctuated`] keeps them between its items.
+#[cfg(feature = "rt")]
+pub trait ListSeparator<P> {
+    fn push_separator(&mut self, separator: P);
+}
+
+macro_rules! list_containers {
+    ($($container:ty [$($bound:tt)*] => $push:ident),* $(,)?) => {
+        $(
+            #[cfg(feature = "rt")]
+            impl<T: $($bound)*> Collect<T> for $container {
+                fn empty() -> Self {
+                    Default::default()
+                }
+
+                fn push(&mut self, item: T) {
+                    self.$push(item);
+                }
+            }
+
+            #[cfg(feature = "rt")]
+            impl<T, P> ListSeparator<P> for $container {
+                fn push_separator(&mut self, _separator: P) {}
+            }
+        )*
+    };
+}
+
+list_containers! {
+    Vec<T> [] => push,
+    std::collections::VecDeque<T> [] => push_back,
+    std::collections::LinkedList<T> [] => push_back,
+    std::collections::BinaryHeap<T> [Ord] => push,
+    std::collections::BTreeSet<T> [Ord] => insert,
+    HashSet<T> [std::hash::Hash + Eq] => insert,
+}
+
+/// Maps collect `(key, value)` items; a later item replaces an earlier one with the same key.
+#[cfg(feature = "rt")]
+impl<K: std::hash::Hash + Eq, V> Collect<(K, V)> for std::collections::HashMap<K, V> {
+    fn empty() -> Self {
+        Self::new()
+    }
+
+    fn push(&mut self, (key, value): (K, V)) {
+        self.insert(key, value);
+    }
+}
+
+#[cfg(feature = "rt")]
+impl<K: Ord, V> Collect<(K, V)> for std::collections::BTreeMap<K, V> {
+    fn empty() -> Self {
+        Self::new()
+    }
+
+    fn push(&mut self, (key, value): (K, V)) {
+        self.insert(key, value);
+    }
+}
+
+#[cfg(feature = "rt")]
+impl<K, V, P> ListSeparator<P> for std::collections::HashMap<K, V> {
+    fn push_separator(&mut self, _separator: P) {}
+}
+
+#[cfg(feature = "rt")]
+impl<K, V, P> ListSeparator<P> for std::collections::BTreeMap<K, V> {
+    fn push_separator(&mut self, _separator: P) {}
+}
+
+/// Items are pushed with their separators; where a list has none, such as `repeated`, or
+/// a `terminated` item without its optional terminator, a default separator is inserted.
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T, P: Default> Collect<T> for syn::punctuated::Punctuated<T, P> {
+    fn empty() -> Self {
+        Self::new()
+    }
+
+    fn push(&mut self, item: T) {
+        self.push(item);
+    }
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T, P: Default> ListSeparator<P> for syn::punctuated::Punctuated<T, P> {
+    fn push_separator(&mut self, separator: P) {
+        self.push_punct(separator);
+    }
+}
+
+/// Result of the `delimited` built-in: the list items and the spans of both delimiters.
+#[cfg(feature = "syn")]
+#[derive(Debug, Clone)]
+pub struct Delimited<C> {
+    pub items: C,
+    pub open: Span,
+    pub close: Span,
+}
+
+/// Result of a rule marked `#[parsed]`: the value with the number of consumed token trees and
+/// the span of the last consumed token.
+#[cfg(feature = "syn")]
+#[derive(Debug, Clone)]
+pub struct Parsed<T> {
+    pub value: T,
+    /// Top-level token trees consumed by the rule; a delimited group counts as one.
+    pub consumed: usize,
+    /// The span of the last consumed token tree, or of the start position if none was consumed.
+    pub end_span: Span,
+}
+
+#[cfg(feature = "syn")]
+impl<T> Parsed<T> {
+    /// Wraps `value` with the metadata of the tokens from `start` up to `end`.
+    pub fn between(value: T, start: Cursor, end: Cursor) -> Self {
+        let mut consumed = 0;
+        let mut end_span = start.span();
+        let mut cursor = start;
+        while cursor != end {
+            let Some((tt, next)) = cursor.token_tree() else {
+                break;
+            };
+            consumed += 1;
+            end_span = tt.span();
+            cursor = next;
+        }
+        Parsed {
+            value,
+            consumed,
+            end_span,
+        }
+    }
+}
+
+/// A node of the concrete syntax tree recorded by [`parse_cst`]: a rule that matched, with the
+/// exact tokens it consumed and the nodes of the rules it called, in input order.
+///
+/// The tokens keep their spans, so a formatter or linter can recover the layout of the source
+/// from them; the tokens between the children are those of the rule itself.
+#[cfg(feature = "syn")]
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub rule: &'static str,
+    /// The span of the consumed tokens, or of the start position if none were consumed. Only
+    /// the first token is covered where the compiler cannot join spans.
+    pub span: Span,
+    pub tokens: TokenStream,
+    pub children: Vec<Node>,
+}
+
+#[cfg(feature = "syn")]
+impl Node {
+    /// The node of `rule` for the tokens from `start` up to `end`.
+    pub fn between(rule: &'static str, start: Cursor, end: Cursor, children: Vec<Node>) -> Self {
+        let tokens = tokens_between(start, end);
+        let span = match tokens.clone().into_iter().last() {
+            Some(last) => start.span().join(last.span()).unwrap_or(start.span()),
+            None => start.span(),
+        };
+        Node {
+            rule,
+            span,
+            tokens,
+            children,
+        }
+    }
+
+    /// Visits this node and its descendants, parents before their children.
+    pub fn walk(&self, f: &mut impl FnMut(&Node)) {
+        f(self);
+        for child in &self.children {
+            child.walk(f);
+        }
+    }
+}
+
+/// A public rule of a generated grammar that takes no parameters. The `rules` module of a
+/// grammar holds a marker type per such rule, e.g. `calc::rules::expr`.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub trait GrammarRule {
+    /// The value returned by the rule.
+    type Output;
+
+    fn parse_impl(input: ParseStream, ctx: &mut ParseContext) -> Result<Self::Output>;
+}
+
+/// Parses the rule `R` with a fresh [`ParseContext`] as a [`syn::parse::Parse`] type, so that
+/// generated rules can be used where syn helpers expect one: as field types of structs with a
+/// derived `Parse` implementation, in `Punctuated::parse_terminated`, or with `input.parse()`.
+///
+/// ```rust,ignore
+/// let args = Punctuated::<RuleParser<calc::rules::expr>, Token![,]>::parse_terminated(input)?;
+/// ```
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub struct RuleParser<R: GrammarRule>(pub R::Output);
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<R: GrammarRule> RuleParser<R> {
+    pub fn into_inner(self) -> R::Output {
+        self.0
+    }
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<R: GrammarRule> syn::parse::Parse for RuleParser<R> {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut ctx = ParseContext::new();
+        R::parse_impl(input, &mut ctx)
+            .map(RuleParser)
+            .map_err(|e| ctx.take_best_error().unwrap_or(e))
+    }
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<R: GrammarRule> std::ops::Deref for RuleParser<R> {
+    type Target = R::Output;
+
+    fn deref(&self) -> &R::Output {
+        &self.0
+    }
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<R: GrammarRule> std::fmt::Debug for RuleParser<R>
+where
+    R::Output: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("RuleParser").field(&self.0).finish()
+    }
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<R: GrammarRule> Clone for RuleParser<R>
+where
+    R::Output: Clone,
+{
+    fn clone(&self) -> Self {
+        RuleParser(self.0.clone())
+    }
+}
+
+/// The entry point of a public rule, returned by the methods of the generated parser struct of
+/// a grammar, e.g. `CalcParser::expr()`. It parses a whole input with the rule, which must
+/// consume all tokens.
+///
+/// ```rust,ignore
+/// let value = calc::CalcParser::expr().parse_str("1 + 2")?;
+/// ```
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub struct EntryPoint<T> {
+    parse: fn(ParseStream) -> Result<T>,
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T> EntryPoint<T> {
+    pub const fn new(parse: fn(ParseStream) -> Result<T>) -> Self {
+        EntryPoint { parse }
+    }
+
+    /// Parses the tokens of `source`.
+    pub fn parse_str(self, source: &str) -> Result<T> {
+        syn::parse::Parser::parse_str(self.parse, source)
+    }
+
+    /// Parses `tokens`, e.g. the input of a procedural macro.
+    pub fn parse2(self, tokens: TokenStream) -> Result<T> {
+        syn::parse::Parser::parse2(self.parse, tokens)
+    }
+
+    /// Parses the tokens of the file at `path`. A file that cannot be read is reported as an
+    /// error at the call site.
+    pub fn parse_file(self, path: impl AsRef<std::path::Path>) -> Result<T> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            syn::Error::new(
+                Span::call_site(),
+                format!("cannot read `{}`: {}", path.display(), e),
+            )
+        })?;
+        self.parse_str(&source)
+    }
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T> syn::parse::Parser for EntryPoint<T> {
+    type Output = T;
+
+    fn parse2(self, tokens: TokenStream) -> Result<T> {
+        syn::parse::Parser::parse2(self.parse, tokens)
+    }
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T> Clone for EntryPoint<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T> Copy for EntryPoint<T> {}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T> std::fmt::Debug for EntryPoint<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntryPoint").finish_non_exhaustive()
+    }
+}
+
+/// The result of an incremental parse, kept by an IDE host between edits: the items parsed
+/// from the input, each with its tokens and a [`ContextCheckpoint`] after it.
+///
+/// [`Incremental::parse`] compares the new input with the tokens of the cached items and parses
+/// again only from the first changed item; the item before it is also parsed again, since it may
+/// extend into the changed tokens. Lookahead of an item beyond the item after it is not
+/// tracked. Reused items keep the spans of the parse that produced them.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub struct Incremental<T> {
+    values: Vec<T>,
+    items: Vec<IncrementalItem>,
+    reused: usize,
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+struct IncrementalItem {
+    /// The top-level token trees of the item, compared by their text.
+    tokens: Vec<String>,
+    after: ContextCheckpoint,
+}
+
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T> Incremental<T> {
+    pub fn new() -> Self {
+        Self {
+            values: Vec::new(),
+            items: Vec::new(),
+            reused: 0,
+        }
+    }
+
+    /// The items of the last parse. After a failed parse, the items before the error.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// The number of items the last parse reused instead of parsing them again.
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+
+    /// Parses `input` as a sequence of items up to its end, reusing the cached items up to the
+    /// first changed token. Errors are the best errors of the failed item.
+    pub fn parse<F>(&mut self, input: ParseStream, mut item: F) -> Result<&[T]>
+    where
+        F: FnMut(ParseStream, &mut ParseContext) -> Result<T>,
+    {
+        // Items whose tokens are unchanged, minus the last one unless the input is unchanged
+        let mut cursor = input.cursor();
+        let mut matching = 0;
+        for cached in &self.items {
+            let Some(end) = match_tokens(cursor, &cached.tokens) else {
+                break;
+            };
+            cursor = end;
+            matching += 1;
+        }
+        let unchanged = matching == self.items.len() && cursor.eof();
+        let reused = if unchanged {
+            matching
+        } else {
+            matching.saturating_sub(1)
+        };
+        let skipped: usize = self.items[..reused].iter().map(|i| i.tokens.len()).sum();
+        self.values.truncate(reused);
+        self.items.truncate(reused);
+        self.reused = reused;
+
+        let mut ctx = ParseContext::new();
+        if let Some(last) = self.items.last() {
+            ctx.restore(&last.after);
+        }
+        input.step(|step| {
+            let mut rest = *step;
+            for _ in 0..skipped {
+                rest = rest.token_tree().map_or(rest, |(_, next)| next);
+            }
+            Ok(((), rest))
+        })?;
+
+        while !input.is_empty() {
+            let start = input.cursor();
+            let value = item(input, &mut ctx).map_err(|e| ctx.take_best_error().unwrap_or(e))?;
+            let tokens: Vec<String> = tokens_between(start, input.cursor())
+                .into_iter()
+                .map(|tt| tt.to_string())
+                .collect();
+            if tokens.is_empty() {
+                return Err(input.error("item matched no tokens"));
+            }
+            self.values.push(value);
+            self.items.push(IncrementalItem {
+                tokens,
+                after: ctx.checkpoint(),
+            });
+        }
+        Ok(&self.values)
+    }
+}
 
-    // Always restore fatal state, ignoring whatever happened inside.
-    ctx.set_fatal(was_fatal);
+#[cfg(all(feature = "rt", feature = "syn"))]
+impl<T> Default for Incremental<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    match res {
-        Ok(val) => {
-            input.advance_to(&fork);
-            // Keep last_span
-            Ok(Some(val))
+/// The cursor after `tokens` if the input at `cursor` starts with them.
+#[cfg(all(feature = "rt", feature = "syn"))]
+fn match_tokens<'c>(mut cursor: Cursor<'c>, tokens: &[String]) -> Option<Cursor<'c>> {
+    for token in tokens {
+        let (tt, next) = cursor.token_tree()?;
+        if tt.to_string() != *token {
+            return None;
         }
+        cursor = next;
+    }
+    Some(cursor)
+}
+
+// --- Stateless Helpers (No Context Needed) ---
+
+/// Appends an "instantiated from" note to every message of `err`, like rustc's macro backtraces.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn note_instantiation(err: syn::Error, call_site: &str) -> syn::Error {
+    err.into_iter()
+        .map(|e| {
+            syn::Error::new(
+                e.span(),
+                format!("{}\nnote: instantiated from {}", e, call_site),
+            )
+        })
+        .reduce(|mut acc, e| {
+            acc.combine(e);
+            acc
+        })
+        .expect("syn::Error holds at least one message")
+}
+
+/// Marks the code path calling it as unlikely, e.g. the action of an alternative marked
+/// `#[cold]`, which Rust does not accept on the statements generated for it.
+#[cfg(feature = "rt")]
+#[cold]
+#[inline]
+pub fn cold_path() {}
+
+/// Fails unless `input` is exhausted, for entry rules marked `#[complete]`.
+///
+/// The error reads "expected end of {expected}, found `..`", shows the first leftover tokens
+/// with the number of remaining tokens, and spans the whole remainder. If the best recorded
+/// error lies at or after the first leftover token, it explains why parsing stopped there and is
+/// reported instead.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn expect_end(input: ParseStream, ctx: &mut ParseContext, expected: &str) -> Result<()> {
+    const SHOWN: usize = 3;
+
+    if input.is_empty() {
+        return Ok(());
+    }
+    let here = input.span().start();
+    if let Some(best) = ctx.take_best_error() {
+        if best.span().start() >= here {
+            return Err(best);
+        }
+    }
+
+    let remainder: TokenStream = input.fork().parse()?;
+    let count = remainder.clone().into_iter().count();
+    let shown: TokenStream = remainder.clone().into_iter().take(SHOWN).collect();
+    let ellipsis = if count > SHOWN { " ..." } else { "" };
+    let plural = if count == 1 { "" } else { "s" };
+    let message = format!(
+        "expected end of {}, found `{}{}` ({} token{} remaining)",
+        expected, shown, ellipsis, count, plural
+    );
+    Err(syn::Error::new_spanned(remainder, message))
+}
+
+/// Runs `parser` with a fresh [`ParseContext`] and returns its value together with every error
+/// that a recovery point skipped past, in input order. If the parse fails, the value is `None`
+/// and the error that stopped it comes last.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn diagnose<T>(
+    input: ParseStream,
+    parser: impl FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+) -> (Option<T>, Vec<syn::Error>) {
+    let mut ctx = ParseContext::new();
+    let res = parser(input, &mut ctx);
+    let best = ctx.take_best_error();
+    let mut errors = ctx.take_recovered_errors();
+    match res {
+        Ok(val) => (Some(val), errors),
         Err(e) => {
-            // Record error BEFORE restoring state
-            ctx.record_error(e, start_span);
+            // The best error may be one that was already recovered from
+            let is_new = |err: &syn::Error, errors: &[syn::Error]| {
+                !errors.iter().any(|r| {
+                    r.span().start() == err.span().start() && r.to_string() == err.to_string()
+                })
+            };
+            let err = match best {
+                Some(best) if is_new(&best, &errors) => best,
+                _ => e,
+            };
+            if is_new(&err, &errors) {
+                errors.push(err);
+            }
+            (None, errors)
+        }
+    }
+}
 
-            // Restore state
-            ctx.scopes = scopes_snapshot;
-            ctx.rule_stack = rule_stack_snapshot;
-            ctx.last_span = last_span_snapshot;
+/// Runs `parser` with a fresh [`ParseContext`] that records a concrete syntax tree, and
+/// returns the value together with the node of the rule it parsed. Only grammars with
+/// `options { cst = true; }` record nodes; their `cst_<rule>` entry points call this.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn parse_cst<T>(
+    input: ParseStream,
+    parser: impl FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+) -> Result<(T, Node)> {
+    let mut ctx = ParseContext::new();
+    ctx.enable_cst();
+    let start = input.cursor();
+    match parser(input, &mut ctx) {
+        Ok(val) => {
+            let mut roots = ctx.take_cst();
+            // A parser that does not record nodes, or several rules parsed in sequence, are
+            // wrapped in a node of their own
+            let node = match roots.len() {
+                1 => roots.remove(0),
+                _ => Node::between("", start, input.cursor(), roots),
+            };
+            Ok((val, node))
+        }
+        Err(e) => Err(ctx.take_best_error().unwrap_or(e)),
+    }
+}
 
-            Ok(None)
+/// Runs `parser` with a fresh [`ParseContext`] for lenient grammars: a parse that succeeds,
+/// possibly by recovering from errors, returns its value together with the non-fatal
+/// diagnostics, i.e. the errors that recovery points skipped past and the warnings reported
+/// with [`ParseContext::warn`], sorted by position. A failed parse returns its error.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn parse_lenient<T>(
+    input: ParseStream,
+    parser: impl FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+) -> Result<(T, Vec<syn::Error>)> {
+    let mut ctx = ParseContext::new();
+    match parser(input, &mut ctx) {
+        Ok(val) => {
+            let mut diagnostics = ctx.take_recovered_errors();
+            diagnostics.extend(ctx.take_warnings());
+            diagnostics.sort_by_key(|d| {
+                let start = d.span().start();
+                (start.line, start.column)
+            });
+            Ok((val, diagnostics))
         }
+        Err(e) => Err(ctx.take_best_error().unwrap_or(e)),
     }
 }
 
-// --- Stateless Helpers (No Context Needed) ---
+/// Combines errors, e.g. those returned by [`diagnose`], into one [`syn::Error`] that reports
+/// all of their messages. Returns `None` if there are no errors.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn combine_errors(errors: impl IntoIterator<Item = syn::Error>) -> Option<syn::Error> {
+    errors.into_iter().reduce(|mut all, e| {
+        all.combine(e);
+        all
+    })
+}
+
+/// Parses several token streams as one input, e.g. the arguments of an attribute macro
+/// followed by the item it is applied to: `rt::parse_streams(dsl::parse_route, [args, item])`.
+///
+/// The tokens keep their spans, so errors point into the stream they come from. Errors at the
+/// end of the input, which syn places at the call site, point at the last token instead.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn parse_streams<P: syn::parse::Parser>(
+    parser: P,
+    streams: impl IntoIterator<Item = TokenStream>,
+) -> Result<P::Output> {
+    let tokens: TokenStream = streams.into_iter().flatten().collect();
+    let last = tokens.clone().into_iter().last().map(|tt| tt.span());
+    parser.parse2(tokens).map_err(|err| match last {
+        Some(last) => err
+            .into_iter()
+            .map(|e| {
+                let message = e.to_string();
+                if message.starts_with("unexpected end of input") {
+                    syn::Error::new(last, message)
+                } else {
+                    e
+                }
+            })
+            .reduce(|mut all, e| {
+                all.combine(e);
+                all
+            })
+            .expect("a syn::Error has at least one message"),
+        None => err,
+    })
+}
+
+/// The tokens from `start` up to `end`, e.g. the tokens consumed by a `#[lossless]` rule.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn tokens_between(start: Cursor, end: Cursor) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    let mut cursor = start;
+    while cursor != end {
+        let Some((tt, next)) = cursor.token_tree() else {
+            break;
+        };
+        tokens.extend(std::iter::once(tt));
+        cursor = next;
+    }
+    tokens
+}
+
+/// The span from the first to the last token between `start` and `end`, i.e. `$span` in the
+/// action of an alternative. Without any token in between, the span of the token at `start`.
+/// Falls back to the span of the first token where spans cannot be joined.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn span_between(start: Cursor, end: Cursor) -> Span {
+    let mut last = None;
+    let mut cursor = start;
+    while cursor != end {
+        let Some((tt, next)) = cursor.token_tree() else {
+            break;
+        };
+        last = Some(tt.span());
+        cursor = next;
+    }
+    match last {
+        Some(last) => start.span().join(last).unwrap_or(start.span()),
+        None => start.span(),
+    }
+}
+
+/// Declares the tokens consumed since `start`, e.g. the identifier matched by `declare(ident)`,
+/// in the innermost scope of the symbol table.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn declare_symbol(input: ParseStream, ctx: &mut ParseContext, start: Cursor) {
+    ctx.define(tokens_between(start, input.cursor()).to_string());
+}
+
+/// Checks that the tokens consumed since `start`, e.g. the identifier matched by
+/// `resolve(ident)`, were declared in an enclosing scope. Otherwise fails with "cannot find
+/// `name` in this scope" at the tokens, in preference to the syntax errors of other
+/// alternatives, like a failed guard.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn resolve_symbol(input: ParseStream, ctx: &mut ParseContext, start: Cursor) -> Result<()> {
+    let name = tokens_between(start, input.cursor()).to_string();
+    if ctx.is_defined(&name) {
+        return Ok(());
+    }
+    ctx.trigger_fail();
+    Err(syn::Error::new(
+        span_between(start, input.cursor()),
+        format!("cannot find `{}` in this scope", name),
+    ))
+}
+
+/// Error for a multi-token literal that failed to match: "expected {expected}", spanning from
+/// the first token of the literal to the token found instead.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn expected_tokens(input: ParseStream, start: Span, expected: &str) -> syn::Error {
+    let mut first = proc_macro2::Punct::new('#', proc_macro2::Spacing::Alone);
+    first.set_span(start);
+    let mut found = proc_macro2::Punct::new('#', proc_macro2::Spacing::Alone);
+    found.set_span(input.span());
+    let covered: proc_macro2::TokenStream = [first, found]
+        .into_iter()
+        .map(proc_macro2::TokenTree::from)
+        .collect();
+    syn::Error::new_spanned(covered, format!("expected {}", expected))
+}
 
 #[cfg(all(feature = "rt", feature = "syn"))]
 #[inline]
@@ -444,6 +1973,147 @@ pub fn parse_ident(input: ParseStream) -> Result<syn::Ident> {
     input.parse()
 }
 
+/// Parses the identifier `word`, which may also be a Rust keyword such as `match`. Used by the
+/// `keyword("word")` built-in.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn parse_keyword(input: ParseStream, word: &str) -> Result<syn::Ident> {
+    input.step(|cursor| match cursor.ident() {
+        Some((ident, rest)) if ident == word => Ok((ident, rest)),
+        _ => Err(cursor.error(format!("expected `{}`", word))),
+    })
+}
+
+/// The `position()` built-in: the span of the next token without consuming it, whose
+/// `start()` is its line and column. At the end of a delimited group, the span of the closing
+/// delimiter.
+#[cfg(all(feature = "rt", feature = "syn"))]
+#[inline]
+pub fn position(input: ParseStream) -> Span {
+    input.span()
+}
+
+/// Opens the outermost indentation level of a `layout = significant` grammar at the column of
+/// the next token, unless one is open. Called by every rule, so the level is that of the
+/// first token of the input.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn start_layout(input: ParseStream, ctx: &mut ParseContext) {
+    if ctx.layout.levels.is_empty() {
+        let column = if input.is_empty() {
+            0
+        } else {
+            input.span().start().column
+        };
+        ctx.layout.push(column);
+    }
+}
+
+/// The column of the next token if it starts a line, i.e. is on a later line than the last
+/// consumed token (see [`ParseContext::record_span`]). `None` at the end of the input or of a
+/// delimited group, and for tokens on the same line.
+#[cfg(all(feature = "rt", feature = "syn"))]
+fn line_start(input: ParseStream, ctx: &ParseContext) -> Option<usize> {
+    if input.is_empty() {
+        return None;
+    }
+    let next = input.span().start();
+    match ctx.last_span {
+        Some(last) if last.end().line >= next.line => None,
+        _ => Some(next.column),
+    }
+}
+
+/// The `newline` built-in of `layout = significant` grammars: the next token starts a line at
+/// the column of the current indentation level. Consumes nothing.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn layout_newline(input: ParseStream, ctx: &mut ParseContext) -> Result<()> {
+    match line_start(input, ctx) {
+        Some(column) if Some(&column) == ctx.layout.levels.last() => Ok(()),
+        _ => Err(syn::Error::new(input.span(), "expected a new line")),
+    }
+}
+
+/// The `indent` built-in of `layout = significant` grammars: the next token starts a line
+/// further right than the current indentation level, and opens a level at its column.
+/// Consumes nothing.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn layout_indent(input: ParseStream, ctx: &mut ParseContext) -> Result<()> {
+    match line_start(input, ctx) {
+        Some(column) if ctx.layout.levels.last().is_none_or(|&level| column > level) => {
+            ctx.layout.push(column);
+            Ok(())
+        }
+        _ => Err(syn::Error::new(input.span(), "expected an indented line")),
+    }
+}
+
+/// The `dedent` built-in of `layout = significant` grammars: closes the current indentation
+/// level if the next token starts a line further left, or at the end of the input or of a
+/// delimited group. Consumes nothing; a line that closes several levels matches one `dedent`
+/// for each. Fails with "unindent does not match any outer indentation level" if the column
+/// is between two levels.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn layout_dedent(input: ParseStream, ctx: &mut ParseContext) -> Result<()> {
+    if let [.., outer, level] = ctx.layout.levels[..] {
+        let column = line_start(input, ctx);
+        match column {
+            Some(column) if column < level && column > outer => {
+                ctx.trigger_fail();
+                return Err(syn::Error::new(
+                    input.span(),
+                    "unindent does not match any outer indentation level",
+                ));
+            }
+            Some(column) if column < level => {
+                ctx.layout.pop();
+                return Ok(());
+            }
+            None if input.is_empty() => {
+                ctx.layout.pop();
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+    Err(syn::Error::new(
+        input.span(),
+        "expected the end of the indented block",
+    ))
+}
+
+/// Parses an identifier that equals `word` ignoring ASCII case, such as `SELECT` for `select`.
+/// Used by case-insensitive literals (`"select"i`).
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn parse_ident_ci(input: ParseStream, word: &str) -> Result<syn::Ident> {
+    input.step(|cursor| match cursor.ident() {
+        Some((ident, rest)) if ident.to_string().eq_ignore_ascii_case(word) => Ok((ident, rest)),
+        _ => Err(cursor.error(format!("expected `{}`", word))),
+    })
+}
+
+/// Returns true if the next token is one of the `reserved` words, which the `ident` built-in
+/// does not match in rules with `keywords { .. }` declarations. Raw identifiers such as
+/// `r#from` are not reserved.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn peek_reserved(input: ParseStream, reserved: &[&str]) -> bool {
+    input
+        .cursor()
+        .ident()
+        .is_some_and(|(ident, _)| reserved.iter().any(|word| ident == word))
+}
+
+/// Fails with "expected identifier, found keyword `from`" if the next token is one of the
+/// `reserved` words (see [`peek_reserved`]).
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn reject_reserved(input: ParseStream, reserved: &[&str]) -> Result<()> {
+    match input.cursor().ident() {
+        Some((ident, _)) if peek_reserved(input, reserved) => Err(syn::Error::new(
+            ident.span(),
+            format!("expected identifier, found keyword `{}`", ident),
+        )),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(all(feature = "rt", feature = "syn"))]
 #[inline]
 pub fn parse_int<T: std::str::FromStr>(input: ParseStream) -> Result<T>
@@ -453,6 +2123,52 @@ where
     input.parse::<syn::LitInt>()?.base10_parse()
 }
 
+/// Parses the body of a rule marked `#[recover(sync = ";")]`. If the body fails after the first
+/// token, the error is recorded, the input is skipped up to and including the next token
+/// accepted by `sync`, and `None` is returned for the placeholder value. A failure at the first
+/// token or at the end of the input is returned as is, so that repetitions and alternatives
+/// calling the rule still end.
+#[cfg(all(feature = "rt", feature = "syn"))]
+pub fn recover_rule<T, F>(
+    input: ParseStream,
+    ctx: &mut ParseContext,
+    sync: impl Fn(ParseStream) -> bool,
+    parser: F,
+) -> Result<Option<T>>
+where
+    F: FnOnce(ParseStream, &mut ParseContext) -> Result<T>,
+{
+    let was_fatal = ctx.check_fatal();
+    let snapshot = ctx.snapshot();
+    let start_span = input.span();
+    let start = input.cursor();
+    let fork = input.fork();
+
+    let res = parser(&fork, ctx);
+    check_fork_use(input, start, "recover_rule", &res);
+    match res {
+        Ok(val) => {
+            input.advance_to(&fork);
+            ctx.commit(snapshot);
+            Ok(Some(val))
+        }
+        // Errors before the start were recorded by earlier attempts
+        Err(e) if input.is_empty() || e.span().start() <= start_span.start() => {
+            ctx.rollback(snapshot);
+            Err(e)
+        }
+        Err(e) => {
+            ctx.set_fatal(was_fatal);
+            ctx.recover_from(e, start_span, snapshot);
+            skip_until(input, &sync)?;
+            if !input.is_empty() {
+                input.parse::<proc_macro2::TokenTree>()?;
+            }
+            Ok(None)
+        }
+    }
+}
+
 #[cfg(all(feature = "rt", feature = "syn"))]
 pub fn skip_until(input: ParseStream, predicate: impl Fn(ParseStream) -> bool) -> Result<()> {
     while !input.is_empty() && !predicate(input) {
@@ -482,6 +2198,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_instantiation_note_in_error() {
+        let mut ctx = ParseContext::new();
+        ctx.enter_rule("list_1f");
+        ctx.enter_instantiation("`list` at line 3, column 5");
+
+        let err = syn::Error::new(Span::call_site(), "expected something");
+        ctx.record_error(err, Span::call_site());
+        ctx.exit_instantiation();
+
+        let final_err = ctx.take_best_error().unwrap();
+        assert_eq!(
+            final_err.to_string(),
+            "Error in rule 'list_1f': expected something\nnote: instantiated from `list` at line 3, column 5"
+        );
+    }
+
     #[test]
     fn test_nested_rule_name_in_error() {
         let mut ctx = ParseContext::new();
@@ -523,4 +2256,233 @@ mod tests {
         let err = ctx.take_best_error().expect("Error should be recorded");
         assert_eq!(err.to_string(), "Error in rule 'outer': parse failed");
     }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "rt::attempt: the closure failed after advancing the outer input")]
+    fn test_attempt_detects_captured_input_on_failure() {
+        use syn::parse::Parser;
+
+        let parser = |input: ParseStream| {
+            let mut ctx = ParseContext::new();
+            // The closure parses from the outer `input` instead of its fork
+            let _ = attempt(input, &mut ctx, |_fork, _ctx| -> Result<()> {
+                input.parse::<syn::Ident>()?;
+                Err(syn::Error::new(Span::call_site(), "fail"))
+            });
+            Ok(())
+        };
+        let _ = parser.parse_str("a");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "rt::attempt: the closure succeeded without advancing its fork")]
+    fn test_attempt_detects_captured_input_on_success() {
+        use syn::parse::Parser;
+
+        let parser = |input: ParseStream| {
+            let mut ctx = ParseContext::new();
+            let _ = attempt(input, &mut ctx, |_fork, _ctx| input.parse::<syn::Ident>());
+            Ok(())
+        };
+        let _ = parser.parse_str("a");
+    }
+
+    #[test]
+    fn test_attempt_with_fork_parameter() {
+        use syn::parse::Parser;
+
+        let parser = |input: ParseStream| {
+            let mut ctx = ParseContext::new();
+            let ident = attempt(input, &mut ctx, |input, _ctx| input.parse::<syn::Ident>())?;
+            let none = attempt(input, &mut ctx, |input, _ctx| input.parse::<syn::LitInt>())?;
+            Ok((ident.map(|i| i.to_string()), none.is_none()))
+        };
+        assert_eq!(
+            parser.parse_str("a").unwrap(),
+            (Some("a".to_string()), true)
+        );
+    }
+
+    #[test]
+    fn test_expect_end() {
+        use syn::parse::Parser;
+
+        let parser = |input: ParseStream| {
+            let mut ctx = ParseContext::new();
+            input.parse::<syn::Ident>()?;
+            // The reason parsing stopped at the leftover token
+            ctx.record_error(input.error("expected `;`"), Span::call_site());
+            expect_end(input, &mut ctx, "input")
+        };
+        let err = parser.parse_str("a b").unwrap_err();
+        assert_eq!(err.to_string(), "expected `;`");
+
+        let parser = |input: ParseStream| {
+            let mut ctx = ParseContext::new();
+            input.parse::<syn::Ident>()?;
+            expect_end(input, &mut ctx, "`stmt` input")
+        };
+        assert!(parser.parse_str("a").is_ok());
+        let err = parser.parse_str("a b").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected end of `stmt` input, found `b` (1 token remaining)"
+        );
+
+        let err = parser.parse_str("a b (c d) e f").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected end of `stmt` input, found `b (c d) e ...` (4 tokens remaining)"
+        );
+        // The error spans the whole remainder
+        assert_eq!(err.span().start().column, 2);
+        assert_eq!(err.span().end().column, 13);
+    }
+
+    #[test]
+    fn test_trace_line() {
+        use syn::parse::Parser;
+
+        let parser = |input: ParseStream| {
+            input.parse::<syn::Ident>()?;
+            let line = trace_line(input, 2, format_args!("enter {}", "expr"));
+            let _: TokenStream = input.parse()?;
+            Ok((line, trace_line(input, 0, format_args!("exit expr: ok"))))
+        };
+        let (middle, end) = parser.parse_str("x a + (b c) d").unwrap();
+        assert_eq!(
+            middle,
+            "[TRACE]     enter expr at 1:3 before `a + (b c) ...`"
+        );
+        assert_eq!(end, "[TRACE] exit expr: ok at end of input");
+    }
+
+    #[test]
+    fn test_attempt_rolls_back_scopes() {
+        use syn::parse::Parser;
+
+        let mut ctx = ParseContext::new();
+        ctx.define("a");
+
+        let parser = |input: ParseStream| {
+            // A successful attempt keeps its definitions, even inside a failed outer attempt
+            let _: Option<()> = attempt(input, &mut ctx, |input, ctx| {
+                ctx.define("b");
+                ctx.enter_scope();
+                ctx.define("c");
+                let _: Option<()> = attempt(input, ctx, |_, ctx| {
+                    ctx.exit_scope();
+                    ctx.define("d");
+                    ctx.enter_scope();
+                    Ok(())
+                })?;
+                assert!(ctx.is_defined("d") && !ctx.is_defined("c"));
+                ctx.enter_rule("inner");
+                Err(syn::Error::new(Span::call_site(), "fail"))
+            })?;
+            Ok(())
+        };
+        parser.parse_str("").unwrap();
+
+        assert!(ctx.is_defined("a"));
+        assert!(!ctx.is_defined("b") && !ctx.is_defined("c") && !ctx.is_defined("d"));
+        assert_eq!(ctx.scopes().len(), 1);
+        assert!(ctx.rule_stack().is_empty());
+
+        // Without open checkpoints, nothing is recorded
+        ctx.define("e");
+        assert!(ctx.scopes.log.is_empty());
+    }
+
+    #[test]
+    fn test_attempt_rolls_back_layout() {
+        use syn::parse::Parser;
+
+        let mut ctx = ParseContext::new();
+        ctx.layout.push(0);
+        ctx.layout.push(4);
+
+        let parser = |input: ParseStream| {
+            // A dedent followed by an indent to another column, undone by the failed attempt
+            let _: Option<()> = attempt(input, &mut ctx, |_, ctx| {
+                ctx.layout.pop();
+                ctx.layout.push(2);
+                let _: Option<()> = attempt(input, ctx, |_, ctx| {
+                    ctx.layout.pop();
+                    Ok(())
+                })?;
+                Err(syn::Error::new(Span::call_site(), "fail"))
+            })?;
+            Ok(())
+        };
+        parser.parse_str("").unwrap();
+        assert_eq!(ctx.layout.levels, [0, 4]);
+
+        // Without open snapshots, nothing is recorded
+        ctx.layout.pop();
+        assert!(ctx.layout.log.is_empty());
+    }
+
+    #[test]
+    fn test_incremental_reparse() {
+        use syn::parse::Parser;
+
+        // Items `let name = value;` or `use name;`, where `use` requires a defined name
+        let item = |input: ParseStream, ctx: &mut ParseContext| -> Result<String> {
+            if input.peek(syn::Token![let]) {
+                input.parse::<syn::Token![let]>()?;
+                let name: syn::Ident = input.parse()?;
+                input.parse::<syn::Token![=]>()?;
+                let value: syn::LitInt = input.parse()?;
+                input.parse::<syn::Token![;]>()?;
+                ctx.define(name.to_string());
+                Ok(format!("{}={}", name, value))
+            } else {
+                input.parse::<syn::Token![use]>()?;
+                let name: syn::Ident = input.parse()?;
+                input.parse::<syn::Token![;]>()?;
+                if !ctx.is_defined(&name.to_string()) {
+                    return Err(syn::Error::new(name.span(), "undefined"));
+                }
+                Ok(format!("use {}", name))
+            }
+        };
+
+        let mut state = Incremental::new();
+        let mut reparse = |source: &str| {
+            let mut parsed = 0;
+            let values = (|input: ParseStream| {
+                let res = state.parse(input, |input, ctx| {
+                    parsed += 1;
+                    item(input, ctx)
+                });
+                res.map(|values| values.to_vec())
+            })
+            .parse_str(source);
+            (values.map_err(|e| e.to_string()), parsed, state.reused())
+        };
+
+        let (values, parsed, reused) = reparse("let a = 1; let b = 2; let c = 3;");
+        assert_eq!(values.unwrap(), ["a=1", "b=2", "c=3"]);
+        assert_eq!((parsed, reused), (3, 0));
+
+        // The edited item and the one before it are parsed again
+        let (values, parsed, reused) = reparse("let a = 1; let b = 2; let c = 4; use a;");
+        assert_eq!(values.unwrap(), ["a=1", "b=2", "c=4", "use a"]);
+        assert_eq!((parsed, reused), (3, 1));
+
+        // Definitions of reused items are restored
+        let (values, parsed, reused) = reparse("let a = 1; let b = 2; let c = 4; use b;");
+        assert_eq!(values.unwrap(), ["a=1", "b=2", "c=4", "use b"]);
+        assert_eq!((parsed, reused), (2, 2));
+
+        let (values, parsed, reused) = reparse("let a = 1; let b = 2; let c = 4; use b;");
+        assert_eq!(values.unwrap().len(), 4);
+        assert_eq!((parsed, reused), (0, 4));
+
+        let (values, _, _) = reparse("let a = 1; use d;");
+        assert_eq!(values.unwrap_err(), "undefined");
+    }
 }
@@ -237,52 +237,42 @@ impl<T: Debug> Testable<T, syn::Error> for syn::Result<T> {
     }
 }
 
+/// Parses the text of every sample, e.g. from `syn_grammar::samples::generate_samples`,
+/// and panics with the sample and the rendered error at the first one `parser` rejects.
+/// Returns the number of samples parsed.
+///
+/// ```rust,ignore
+/// let samples = samples::generate_samples(&grammar, 42, 6).take(200);
+/// fuzz_roundtrip(calc::parse_expr, samples);
+/// ```
+#[cfg(feature = "syn")]
+pub fn fuzz_roundtrip<P, I>(parser: P, samples: I) -> usize
+where
+    P: syn::parse::Parser + Copy,
+    I: IntoIterator<Item = proc_macro2::TokenStream>,
+{
+    let mut count = 0;
+    for sample in samples {
+        let source = sample.to_string();
+        if let Err(err) = parser.parse_str(&source) {
+            panic!(
+                "sample {} of the grammar was rejected: `{}`\n{}",
+                count + 1,
+                source,
+                crate::diagnostics::render_error(&source, &err)
+            );
+        }
+        count += 1;
+    }
+    count
+}
+
 fn format_error_impl<E: Display + Debug + 'static>(err: &E, source: Option<&str>) -> String {
     #[cfg(feature = "syn")]
     if let Some(src) = source {
         if let Some(syn_err) = (err as &dyn Any).downcast_ref::<syn::Error>() {
-            return pretty_print_syn_error(syn_err, src);
+            return crate::diagnostics::render_error(src, syn_err);
         }
     }
     format!("{}", err)
 }
-
-#[cfg(feature = "syn")]
-fn pretty_print_syn_error(err: &syn::Error, source: &str) -> String {
-    let start = err.span().start();
-    let end = err.span().end();
-
-    if start.line == 0 {
-        return err.to_string();
-    }
-
-    let line_idx = start.line - 1;
-    let lines: Vec<&str> = source.lines().collect();
-
-    if line_idx >= lines.len() {
-        return err.to_string();
-    }
-
-    let line = lines[line_idx];
-    let col = start.column;
-
-    // Calculate width of the underline
-    // If start and end are on the same line, width is end.column - start.column
-    // Else just highlight until end of line or 1 char.
-    let width = if start.line == end.line {
-        end.column.saturating_sub(col).max(1)
-    } else {
-        1
-    };
-
-    format!(
-        "{}\n  --> line {}:{}\n   |\n {} | {}\n   | {}{}",
-        err,
-        start.line,
-        col,
-        start.line,
-        line,
-        " ".repeat(col),
-        "^".repeat(width)
-    )
-}
@@ -1,5 +1,5 @@
 use crate::rt::ParseContext;
-use proc_macro2::Span;
+use proc_macro2::{Delimiter, Span};
 use syn::parse::ParseStream;
 use syn::spanned::Spanned;
 use syn::Result;
@@ -59,62 +59,62 @@ impl<'a> CommonBuiltins for ParseStream<'a> {
 
     fn parse_i8(&mut self) -> Result<(i8, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_i16(&mut self) -> Result<(i16, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_i32(&mut self) -> Result<(i32, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_i64(&mut self) -> Result<(i64, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_i128(&mut self) -> Result<(i128, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_isize(&mut self) -> Result<(isize, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_u8(&mut self) -> Result<(u8, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_u16(&mut self) -> Result<(u16, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_u32(&mut self) -> Result<(u32, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_u64(&mut self) -> Result<(u64, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_u128(&mut self) -> Result<(u128, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_usize(&mut self) -> Result<(usize, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_f32(&mut self) -> Result<(f32, Span)> {
@@ -129,20 +129,107 @@ impl<'a> CommonBuiltins for ParseStream<'a> {
 
     fn parse_hex_literal(&mut self) -> Result<(u64, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_oct_literal(&mut self) -> Result<(u64, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 
     fn parse_bin_literal(&mut self) -> Result<(u64, Span)> {
         let lit = self.parse::<syn::LitInt>()?;
-        Ok((lit.base10_parse()?, lit.span()))
+        Ok((int_value(&lit, Overflow::Error)?, lit.span()))
     }
 }
 
+/// How integer built-ins handle literals outside the range of their type. `Error` is the
+/// default; `u8(wrapping=true)` and `u8(saturating=true)` select the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    Error,
+    Wrapping,
+    Saturating,
+}
+
+/// The primitive integer types produced by the integer built-ins.
+pub trait Integer: Copy + std::str::FromStr + std::fmt::Display {
+    const NAME: &'static str;
+    const MIN: Self;
+    const MAX: Self;
+
+    /// Truncates to the width of the type, as `value as Self` does.
+    fn wrap(value: u128) -> Self;
+}
+
+macro_rules! impl_integer {
+    ($($t:ident)*) => {
+        $(
+            impl Integer for $t {
+                const NAME: &'static str = stringify!($t);
+                const MIN: Self = $t::MIN;
+                const MAX: Self = $t::MAX;
+
+                fn wrap(value: u128) -> Self {
+                    value as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+/// Converts an integer literal to `N`. Values out of range are handled as `overflow` says;
+/// with `Overflow::Error` they are reported at the literal as
+/// "value 300 does not fit in u8 (0..=255)".
+pub fn int_value<N: Integer>(lit: &syn::LitInt, overflow: Overflow) -> Result<N> {
+    let digits = lit.base10_digits();
+    if let Ok(value) = digits.parse::<N>() {
+        return Ok(value);
+    }
+    // The digits are valid, so the value is out of range
+    let negative = digits.starts_with('-');
+    match overflow {
+        Overflow::Error => Err(syn::Error::new(
+            lit.span(),
+            format!(
+                "value {} does not fit in {} ({}..={})",
+                digits,
+                N::NAME,
+                N::MIN,
+                N::MAX
+            ),
+        )),
+        Overflow::Saturating => Ok(if negative { N::MIN } else { N::MAX }),
+        Overflow::Wrapping => {
+            let magnitude = digits
+                .trim_start_matches('-')
+                .bytes()
+                .fold(0u128, |acc, d| {
+                    acc.wrapping_mul(10).wrapping_add(u128::from(d - b'0'))
+                });
+            Ok(N::wrap(if negative {
+                magnitude.wrapping_neg()
+            } else {
+                magnitude
+            }))
+        }
+    }
+}
+
+/// Integer built-ins with a `wrapping=true` or `saturating=true` option.
+pub fn parse_integer_impl<N: Integer>(
+    input: &mut ParseStream,
+    ctx: &mut ParseContext,
+    overflow: Overflow,
+) -> Result<N> {
+    let lit: syn::LitInt = (*input).parse()?;
+    let value = int_value(&lit, overflow)?;
+    ctx.record_span(lit.span());
+    Ok(value)
+}
+
 pub fn parse_ident_impl<T: CommonBuiltins>(
     input: &mut T,
     ctx: &mut ParseContext,
@@ -495,8 +582,88 @@ pub fn parse_outer_attrs_impl(
     ctx: &mut ParseContext,
 ) -> Result<Vec<syn::Attribute>> {
     let attrs = syn::Attribute::parse_outer(input)?;
-    if let Some(last) = attrs.last() {
-        ctx.record_span(last.span());
+    if let (Some(first), Some(last)) = (attrs.first(), attrs.last()) {
+        ctx.record_span(first.span().join(last.span()).unwrap_or(last.span()));
     }
     Ok(attrs)
 }
+
+/// `token_tree`: any single token or delimited group, kept as it is to be parsed later.
+pub fn parse_token_tree_impl(
+    input: &mut ParseStream,
+    ctx: &mut ParseContext,
+) -> Result<proc_macro2::TokenTree> {
+    let t: proc_macro2::TokenTree = (*input).parse()?;
+    ctx.record_span(t.span());
+    Ok(t)
+}
+
+/// `balanced_group`: a group delimited by `( .. )`, `[ .. ]` or `{ .. }` with everything
+/// inside it, kept as it is to be parsed later.
+pub fn parse_balanced_group_impl(
+    input: &mut ParseStream,
+    ctx: &mut ParseContext,
+) -> Result<proc_macro2::Group> {
+    parse_group(input, ctx, None)
+}
+
+/// `balanced_group("{")`: like `balanced_group`, but only a group with the given opening
+/// delimiter.
+pub fn parse_balanced_group_matching_impl(
+    input: &mut ParseStream,
+    ctx: &mut ParseContext,
+    open: &str,
+) -> Result<proc_macro2::Group> {
+    let delimiter = match open {
+        "(" => Delimiter::Parenthesis,
+        "[" => Delimiter::Bracket,
+        _ => Delimiter::Brace,
+    };
+    parse_group(input, ctx, Some(delimiter))
+}
+
+fn parse_group(
+    input: &mut ParseStream,
+    ctx: &mut ParseContext,
+    delimiter: Option<Delimiter>,
+) -> Result<proc_macro2::Group> {
+    let group = input.step(|cursor| match cursor.token_tree() {
+        Some((proc_macro2::TokenTree::Group(group), rest))
+            if delimiter.map_or(group.delimiter() != Delimiter::None, |d| {
+                group.delimiter() == d
+            }) =>
+        {
+            Ok((group, rest))
+        }
+        _ => Err(cursor.error(match delimiter {
+            Some(Delimiter::Parenthesis) => "expected `( .. )`",
+            Some(Delimiter::Bracket) => "expected `[ .. ]`",
+            Some(_) => "expected `{ .. }`",
+            None => "expected a group in `( .. )`, `[ .. ]` or `{ .. }`",
+        })),
+    })?;
+    ctx.record_span(group.span());
+    Ok(group)
+}
+
+/// `outer_attrs("path")`: parses all outer attributes and returns those whose path is `path`
+/// (e.g. `"doc"` or `"serde::rename"`).
+pub fn parse_outer_attrs_matching_impl(
+    input: &mut ParseStream,
+    ctx: &mut ParseContext,
+    path: &str,
+) -> Result<Vec<syn::Attribute>> {
+    let attrs = parse_outer_attrs_impl(input, ctx)?;
+    Ok(attrs
+        .into_iter()
+        .filter(|attr| {
+            let segments: Vec<String> = attr
+                .path()
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            segments.join("::") == path
+        })
+        .collect())
+}
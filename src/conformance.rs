@@ -0,0 +1,323 @@
+//! Conformance checks for implementations of the portable built-ins.
+//!
+//! The syn backend is the reference for what a portable built-in accepts, what it returns and
+//! which source span it covers. Custom backends parse the same inputs with their own
+//! implementation and compare with [`check_builtin`]:
+//!
+//! ```rust
+//! use syn_grammar::rt::conformance::{check_builtin, Outcome};
+//!
+//! // Outcomes reported by a custom implementation of `u8`.
+//! check_builtin(
+//!     "u8",
+//!     &[
+//!         ("42", Outcome::accepted("42", 0..2)),
+//!         ("300", Outcome::Rejected),
+//!         ("x", Outcome::Rejected),
+//!     ],
+//! )
+//! .unwrap();
+//! ```
+
+use crate::builtins::*;
+use crate::rt::ParseContext;
+use crate::token_filter;
+use proc_macro2::{Span, TokenStream};
+use std::fmt::Write;
+use std::ops::Range;
+use syn::parse::{ParseStream, Parser};
+use syn::spanned::Spanned;
+use syn::Result;
+
+/// The portable built-ins with reference semantics. `spanned_*` built-ins share the semantics
+/// of the plain built-in.
+pub const PORTABLE_BUILTINS: &[&str] = &[
+    "ident",
+    "string",
+    "char",
+    "bool",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "f32",
+    "f64",
+    "hex_literal",
+    "oct_literal",
+    "bin_literal",
+    "alpha",
+    "digit",
+    "alphanumeric",
+    "hex_digit",
+    "oct_digit",
+];
+
+/// The result of running a built-in on one input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The built-in matched. `value` is the `Display` text of the returned value and `span`
+    /// the columns of the recorded span (inputs are a single line).
+    Accepted { value: String, span: Range<usize> },
+    /// The built-in failed. Error messages are backend-specific and are not compared.
+    Rejected,
+}
+
+impl Outcome {
+    pub fn accepted(value: impl Into<String>, span: Range<usize>) -> Self {
+        Outcome::Accepted {
+            value: value.into(),
+            span,
+        }
+    }
+}
+
+/// Runs the reference implementation of the built-in `name` on `input`. Tokens after the
+/// match are ignored. Returns `None` if `name` is not one of [`PORTABLE_BUILTINS`].
+pub fn reference(name: &str, input: &str) -> Option<Outcome> {
+    let name = name.strip_prefix("spanned_").unwrap_or(name);
+    if !PORTABLE_BUILTINS.contains(&name) {
+        return None;
+    }
+
+    let parser = |input: ParseStream| {
+        let mut ctx = ParseContext::new();
+        let outcome = parse_reference(name, input, &mut ctx);
+        input.parse::<TokenStream>()?;
+        Ok(outcome)
+    };
+    Some(match parser.parse_str(input) {
+        Ok(Ok((value, span))) => Outcome::accepted(value, columns(span)),
+        Ok(Err(_)) | Err(_) => Outcome::Rejected,
+    })
+}
+
+/// Checks the outcomes of a built-in implementation against the reference, one
+/// `(input, outcome)` pair per input. Returns a message listing every mismatch.
+pub fn check_builtin(name: &str, inputs: &[(&str, Outcome)]) -> std::result::Result<(), String> {
+    let mut mismatches = String::new();
+    for (input, outcome) in inputs {
+        let Some(expected) = reference(name, input) else {
+            return Err(format!("`{}` is not a portable built-in", name));
+        };
+        if *outcome != expected {
+            let _ = writeln!(
+                mismatches,
+                "  `{}`: expected {:?}, got {:?}",
+                input, expected, outcome
+            );
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "built-in `{}` does not conform:\n{}",
+            name, mismatches
+        ))
+    }
+}
+
+fn parse_reference(
+    name: &str,
+    mut input: ParseStream,
+    ctx: &mut ParseContext,
+) -> Result<(String, Span)> {
+    macro_rules! number {
+        ($f:ident) => {
+            $f(&mut input, ctx)?.to_string()
+        };
+    }
+
+    let value = match name {
+        "ident" => parse_ident_impl(&mut input, ctx)?.to_string(),
+        "string" => parse_string_impl(&mut input, ctx)?.to_string(),
+        "char" => parse_char_impl(&mut input, ctx)?.to_string(),
+        "bool" => parse_bool_impl(&mut input, ctx)?.to_string(),
+        "i8" => number!(parse_i8_impl),
+        "i16" => number!(parse_i16_impl),
+        "i32" => number!(parse_i32_impl),
+        "i64" => number!(parse_i64_impl),
+        "i128" => number!(parse_i128_impl),
+        "isize" => number!(parse_isize_impl),
+        "u8" => number!(parse_u8_impl),
+        "u16" => number!(parse_u16_impl),
+        "u32" => number!(parse_u32_impl),
+        "u64" => number!(parse_u64_impl),
+        "u128" => number!(parse_u128_impl),
+        "usize" => number!(parse_usize_impl),
+        "f32" => number!(parse_f32_impl),
+        "f64" => number!(parse_f64_impl),
+        "hex_literal" => number!(parse_hex_literal_impl),
+        "oct_literal" => number!(parse_oct_literal_impl),
+        "bin_literal" => number!(parse_bin_literal_impl),
+        "alpha" => record(ctx, token_filter::alpha(input)?),
+        "digit" => record(ctx, token_filter::digit(input)?),
+        "alphanumeric" => record(ctx, token_filter::alphanumeric(input)?),
+        "hex_digit" => record(ctx, token_filter::hex_digit(input)?),
+        "oct_digit" => record(ctx, token_filter::oct_digit(input)?),
+        _ => unreachable!("not a portable built-in: {}", name),
+    };
+
+    let span = ctx.last_span.unwrap_or_else(Span::call_site);
+    Ok((value, span))
+}
+
+fn record(ctx: &mut ParseContext, token: impl Spanned + ToString) -> String {
+    ctx.record_span(token.span());
+    token.to_string()
+}
+
+fn columns(span: Span) -> Range<usize> {
+    span.start().column..span.end().column
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One input of every kind of token syn distinguishes.
+    const TOKEN_KINDS: &[&str] = &[
+        "foo",
+        "r#foo",
+        "_",
+        "fn",
+        "true",
+        "'a",
+        "\"text\"",
+        "r\"raw\"",
+        "b\"bytes\"",
+        "c\"c string\"",
+        "'c'",
+        "b'b'",
+        "42",
+        "42u8",
+        "-7",
+        "300",
+        "0xff",
+        "0o17",
+        "0b101",
+        "1.5",
+        "1e3",
+        "2.5f32",
+        "+",
+        "(x)",
+        "",
+    ];
+
+    /// Checks `name` on all token kinds: the inputs in `accepted` must be accepted with the
+    /// given value, spanning the whole input, and all other inputs rejected.
+    fn check_kinds(name: &str, accepted: &[(&str, &str)]) {
+        for (input, _) in accepted {
+            assert!(
+                TOKEN_KINDS.contains(input),
+                "unknown token kind `{}`",
+                input
+            );
+        }
+        let cases: Vec<_> = TOKEN_KINDS
+            .iter()
+            .map(|input| {
+                let outcome = match accepted.iter().find(|(i, _)| i == input) {
+                    Some((_, value)) => Outcome::accepted(*value, 0..input.len()),
+                    None => Outcome::Rejected,
+                };
+                (*input, outcome)
+            })
+            .collect();
+        if let Err(e) = check_builtin(name, &cases) {
+            panic!("{}", e);
+        }
+    }
+
+    #[test]
+    fn test_text_builtins() {
+        check_kinds("ident", &[("foo", "foo"), ("r#foo", "r#foo")]);
+        check_kinds("string", &[("\"text\"", "text"), ("r\"raw\"", "raw")]);
+        check_kinds("char", &[("'c'", "c")]);
+        check_kinds("bool", &[("true", "true")]);
+        check_kinds("alpha", &[("foo", "foo")]);
+        check_kinds("alphanumeric", &[("foo", "foo")]);
+    }
+
+    #[test]
+    fn test_integer_builtins() {
+        let ints = [
+            ("42", "42"),
+            ("42u8", "42"),
+            ("-7", "-7"),
+            ("300", "300"),
+            ("0xff", "255"),
+            ("0o17", "15"),
+            ("0b101", "5"),
+        ];
+        let fits = |max: i128, min: i128| -> Vec<(&str, &str)> {
+            ints.iter()
+                .copied()
+                .filter(|(_, v)| (min..=max).contains(&v.parse::<i128>().unwrap()))
+                .collect()
+        };
+
+        check_kinds("i8", &fits(i8::MAX as i128, i8::MIN as i128));
+        check_kinds("u8", &fits(u8::MAX as i128, 0));
+        for name in ["i16", "i32", "i64", "i128", "isize"] {
+            check_kinds(name, &ints);
+        }
+        for name in ["u16", "u32", "u64", "u128", "usize"] {
+            check_kinds(name, &fits(i128::MAX, 0));
+        }
+        for name in ["hex_literal", "oct_literal", "bin_literal"] {
+            check_kinds(name, &fits(i128::MAX, 0));
+        }
+        check_kinds("spanned_u8", &fits(u8::MAX as i128, 0));
+    }
+
+    #[test]
+    fn test_float_builtins() {
+        for name in ["f32", "f64"] {
+            check_kinds(name, &[("1.5", "1.5"), ("1e3", "1000"), ("2.5f32", "2.5")]);
+        }
+    }
+
+    #[test]
+    fn test_digit_filters() {
+        // The filters check the decimal value of any integer literal, not its spelling
+        let ints = [
+            ("42", "42"),
+            ("42u8", "42u8"),
+            ("300", "300"),
+            ("0xff", "0xff"),
+            ("0o17", "0o17"),
+            ("0b101", "0b101"),
+        ];
+        check_kinds("digit", &ints);
+        check_kinds("hex_digit", &ints);
+        check_kinds("oct_digit", &ints);
+    }
+
+    #[test]
+    fn test_mismatches_are_reported() {
+        let err = check_builtin(
+            "u8",
+            &[
+                ("42", Outcome::accepted("42", 0..1)),
+                ("x", Outcome::accepted("x", 0..1)),
+                ("300", Outcome::Rejected),
+            ],
+        )
+        .unwrap_err();
+        assert!(err.contains("`42`: expected Accepted"), "{}", err);
+        assert!(err.contains("`x`: expected Rejected"), "{}", err);
+        assert!(!err.contains("`300`"), "{}", err);
+
+        assert!(check_builtin("rust_type", &[("x", Outcome::Rejected)]).is_err());
+    }
+}
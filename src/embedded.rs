@@ -0,0 +1,58 @@
+//! Support of `parse_in(string, rule)`, which parses the contents of a string literal with a
+//! rule of the grammar, e.g. the placeholders of a format string.
+
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use syn_grammar_model::model::types::StringLiteral;
+
+/// A string literal matched by the first pattern of `parse_in`, whose contents are parsed.
+pub trait EmbeddedSource {
+    /// The text between the quotes, with escapes resolved, and the span of the literal.
+    fn contents(&self) -> (String, Span);
+}
+
+impl<T: EmbeddedSource + ?Sized> EmbeddedSource for &T {
+    fn contents(&self) -> (String, Span) {
+        (**self).contents()
+    }
+}
+
+impl EmbeddedSource for syn::LitStr {
+    fn contents(&self) -> (String, Span) {
+        (self.value(), self.span())
+    }
+}
+
+impl EmbeddedSource for StringLiteral {
+    fn contents(&self) -> (String, Span) {
+        (self.value.clone(), self.span)
+    }
+}
+
+/// Splits the contents of a string literal into tokens. The tokens get the span of the
+/// literal, so errors inside the contents point at the string.
+pub fn tokens(source: &impl EmbeddedSource) -> syn::Result<TokenStream> {
+    let (text, span) = source.contents();
+    let tokens: TokenStream = text.parse().map_err(|err| {
+        syn::Error::new(
+            span,
+            format!("the contents of the string are not valid tokens: {}", err),
+        )
+    })?;
+    Ok(respan(tokens, span))
+}
+
+fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut token| {
+            if let TokenTree::Group(group) = &token {
+                let mut respanned = Group::new(group.delimiter(), respan(group.stream(), span));
+                respanned.set_span(span);
+                token = TokenTree::Group(respanned);
+            } else {
+                token.set_span(span);
+            }
+            token
+        })
+        .collect()
+}
@@ -6,7 +6,12 @@
 // The code generated by the macro refers to `syn_grammar::rt`.
 // Therefore, this module must be publicly available.
 pub mod rt {
+    pub use super::conformance;
+    pub use super::embedded;
     pub use super::token_filter;
+    pub use super::unparse;
+    #[cfg(feature = "bench")]
+    pub use criterion;
     pub use grammar_kit::*;
 }
 
@@ -16,13 +21,18 @@ pub use grammar_kit::testing;
 // 3. Re-export the macro
 // This allows the user to write: `use syn_grammar::grammar;`
 pub use syn_grammar_macros::grammar;
+pub use syn_grammar_macros::grammar_test;
 
 #[doc(hidden)]
 pub use syn_grammar_macros::include_grammar;
 
 #[doc(hidden)]
 pub mod builtins;
+pub mod conformance;
+pub mod embedded;
+pub mod samples;
 pub mod token_filter;
+pub mod unparse;
 
 // Export portable types
 pub mod types {
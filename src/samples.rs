@@ -0,0 +1,494 @@
+//! Random inputs of a grammar for property tests of the parser the syn backend generates.
+//!
+//! The samples are token streams, and built-ins produce typical tokens of the syn built-ins of
+//! the same name, e.g. `rust_type` a type and `lit_str` a string literal.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::collections::HashMap;
+use syn::Lit;
+use syn_grammar_model::analysis::{
+    collect_custom_keywords, pattern_height, rule_heights, sequence_height,
+};
+use syn_grammar_model::model::{Argument, GrammarDefinition, ModelPattern, Rule};
+
+/// Random inputs of the grammar, e.g. for property tests of the generated parser: an endless
+/// iterator of token streams matched by the entry rule, the first public rule (or the first
+/// rule if none is public). See [`Samples::rule`] to start from another rule.
+///
+/// Alternatives, optional patterns and repetitions are chosen at random from `seed`, so the
+/// same seed yields the same samples. Rule calls nest at most `depth` levels deep where the
+/// grammar allows it; beyond that the shallowest alternatives and the fewest repetitions are
+/// chosen. Built-ins produce typical tokens, e.g. `ident` a name that is not a keyword of the
+/// grammar and `i32` a small number. Lookahead, guards, `resolve` and built-ins without a
+/// typical input such as `scan` produce nothing, so samples of grammars that use them may
+/// not parse.
+pub fn generate_samples(grammar: &GrammarDefinition, seed: u64, depth: usize) -> Samples<'_> {
+    let start = grammar
+        .rules
+        .iter()
+        .find(|r| r.is_pub)
+        .or_else(|| grammar.rules.first())
+        .map(|r| r.name.to_string())
+        .unwrap_or_default();
+    let keywords = collect_custom_keywords(grammar);
+    let names = ["alpha", "beta", "gamma", "delta", "item", "x", "y2"]
+        .into_iter()
+        .filter(|name| !keywords.contains(*name))
+        .collect();
+    Samples {
+        rules: grammar
+            .rules
+            .iter()
+            .map(|r| (r.name.to_string(), r))
+            .collect(),
+        heights: rule_heights(grammar),
+        start,
+        state: seed,
+        depth,
+        names,
+    }
+}
+
+/// The iterator returned by [`generate_samples`].
+pub struct Samples<'a> {
+    rules: HashMap<String, &'a Rule>,
+    /// The fewest levels of rule calls each rule needs to match, see [`rule_heights`].
+    heights: HashMap<String, usize>,
+    start: String,
+    state: u64,
+    depth: usize,
+    names: Vec<&'static str>,
+}
+
+/// Grammar parameters of the rule being generated, bound to the argument patterns of the
+/// call and the parameters of the caller they refer to.
+#[derive(Default)]
+struct SampleParams<'a>(HashMap<String, (&'a ModelPattern, std::rc::Rc<SampleParams<'a>>)>);
+
+impl<'a> Samples<'a> {
+    /// Generates samples of `rule` instead of the entry rule.
+    pub fn rule(mut self, rule: &str) -> Self {
+        self.start = rule.to_string();
+        self
+    }
+
+    /// A pseudo-random number below `n` (SplitMix64).
+    fn below(&mut self, n: usize) -> usize {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z % n.max(1) as u64) as usize
+    }
+
+    fn name(&mut self) -> &'static str {
+        let i = self.below(self.names.len());
+        self.names[i]
+    }
+
+    fn gen_rule(
+        &mut self,
+        name: &str,
+        args: &'a [Argument],
+        caller: &std::rc::Rc<SampleParams<'a>>,
+        level: usize,
+        out: &mut TokenStream,
+    ) {
+        let Some(rule) = self.rules.get(name).copied() else {
+            return;
+        };
+        let mut params = SampleParams::default();
+        let grammar_params = rule.params.iter().filter(|(_, ty)| ty.is_none());
+        for ((param, _), arg) in grammar_params.zip(args) {
+            let (Argument::Positional(p) | Argument::Named(_, p)) = arg;
+            params.0.insert(param.to_string(), (p, caller.clone()));
+        }
+        let params = std::rc::Rc::new(params);
+
+        // Alternatives that fit in the remaining depth, or else the shallowest ones
+        let budget = self.depth.saturating_sub(level);
+        let heights: Vec<usize> = rule
+            .variants
+            .iter()
+            .map(|v| sequence_height(&v.pattern, &self.heights))
+            .collect();
+        let mut fitting: Vec<usize> = (0..heights.len())
+            .filter(|&i| heights[i] <= budget)
+            .collect();
+        if fitting.is_empty() {
+            let min = heights.iter().copied().min().unwrap_or(0);
+            fitting = (0..heights.len()).filter(|&i| heights[i] == min).collect();
+        }
+        if fitting.is_empty() {
+            return;
+        }
+        let variant = &rule.variants[fitting[self.below(fitting.len())]];
+        self.gen_sequence(&variant.pattern, &params, level + 1, out);
+    }
+
+    fn gen_sequence(
+        &mut self,
+        patterns: &'a [ModelPattern],
+        params: &std::rc::Rc<SampleParams<'a>>,
+        level: usize,
+        out: &mut TokenStream,
+    ) {
+        for pattern in patterns {
+            self.gen_pattern(pattern, params, level, out);
+        }
+    }
+
+    /// How often to repeat `inner`: between `min` and `min + 3` times if it fits in the
+    /// remaining depth, else `min` times.
+    fn repetitions(
+        &mut self,
+        inner: &ModelPattern,
+        min: usize,
+        max: Option<usize>,
+        level: usize,
+    ) -> usize {
+        let fits = pattern_height(inner, &self.heights) + level <= self.depth;
+        let max = max.unwrap_or(min + 3).min(min + 3);
+        if !fits || max <= min {
+            return min;
+        }
+        min + self.below(max - min + 1)
+    }
+
+    fn gen_pattern(
+        &mut self,
+        pattern: &'a ModelPattern,
+        params: &std::rc::Rc<SampleParams<'a>>,
+        level: usize,
+        out: &mut TokenStream,
+    ) {
+        match pattern {
+            ModelPattern::Lit { lit, .. } => match lit {
+                Lit::Str(s) => out.extend(s.value().parse::<TokenStream>().unwrap_or_default()),
+                other => out.extend(quote!(#other)),
+            },
+            ModelPattern::RuleCall {
+                rule_name, args, ..
+            } => {
+                let name = rule_name.to_string();
+                if let Some((arg, scope)) = params.0.get(&name) {
+                    let (arg, scope) = (*arg, scope.clone());
+                    self.gen_pattern(arg, &scope, level, out);
+                } else if self.rules.contains_key(&name) {
+                    self.gen_rule(&name, args, params, level, out);
+                } else {
+                    self.gen_builtin(&name, args, params, level, out);
+                }
+            }
+            ModelPattern::Group(alts, _) => {
+                let heights: Vec<usize> = alts
+                    .iter()
+                    .map(|(seq, _)| sequence_height(seq, &self.heights))
+                    .collect();
+                let budget = self.depth.saturating_sub(level);
+                let fitting: Vec<usize> =
+                    (0..alts.len()).filter(|&i| heights[i] <= budget).collect();
+                let chosen = match fitting.is_empty() {
+                    false => fitting[self.below(fitting.len())],
+                    true => (0..alts.len()).min_by_key(|&i| heights[i]).unwrap_or(0),
+                };
+                if let Some((seq, _)) = alts.get(chosen) {
+                    self.gen_sequence(seq, params, level, out);
+                }
+            }
+            ModelPattern::Bracketed(seq, _)
+            | ModelPattern::Braced(seq, _)
+            | ModelPattern::Parenthesized(seq, _) => {
+                let delimiter = match pattern {
+                    ModelPattern::Bracketed(..) => proc_macro2::Delimiter::Bracket,
+                    ModelPattern::Braced(..) => proc_macro2::Delimiter::Brace,
+                    _ => proc_macro2::Delimiter::Parenthesis,
+                };
+                let mut inner = TokenStream::new();
+                self.gen_sequence(seq, params, level, &mut inner);
+                out.extend([proc_macro2::TokenTree::Group(proc_macro2::Group::new(
+                    delimiter, inner,
+                ))]);
+            }
+            ModelPattern::Scope(seq, _) => self.gen_sequence(seq, params, level, out),
+            ModelPattern::Optional(inner, _) => {
+                for _ in 0..self.repetitions(inner, 0, Some(1), level) {
+                    self.gen_pattern(inner, params, level, out);
+                }
+            }
+            ModelPattern::Repeat(inner, _) | ModelPattern::Plus(inner, _) => {
+                let min = matches!(pattern, ModelPattern::Plus(..)) as usize;
+                for _ in 0..self.repetitions(inner, min, None, level) {
+                    self.gen_pattern(inner, params, level, out);
+                }
+            }
+            ModelPattern::Bounded {
+                pattern: inner,
+                min,
+                max,
+                ..
+            } => {
+                for _ in 0..self.repetitions(inner, *min, *max, level) {
+                    self.gen_pattern(inner, params, level, out);
+                }
+            }
+            ModelPattern::Fold { item, op, .. } => {
+                self.gen_pattern(item, params, level, out);
+                for _ in 0..self.repetitions(item, 0, None, level) {
+                    self.gen_pattern(op, params, level, out);
+                    self.gen_pattern(item, params, level, out);
+                }
+            }
+            ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Collect(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Recover { body: inner, .. } => {
+                self.gen_pattern(inner, params, level, out)
+            }
+            // Lookahead and `until` consume nothing of their own
+            ModelPattern::Cut(_)
+            | ModelPattern::Peek(_, _)
+            | ModelPattern::Not(_, _)
+            | ModelPattern::Follows(_, _)
+            | ModelPattern::Until { .. } => {}
+        }
+    }
+
+    fn gen_builtin(
+        &mut self,
+        name: &str,
+        args: &'a [Argument],
+        params: &std::rc::Rc<SampleParams<'a>>,
+        level: usize,
+        out: &mut TokenStream,
+    ) {
+        let positional: Vec<&'a ModelPattern> = args
+            .iter()
+            .filter_map(|a| match a {
+                Argument::Positional(p) => Some(p),
+                Argument::Named(..) => None,
+            })
+            .collect();
+        // `min = 2`, or a positional count, of `separated` and `repeated`
+        let min = args
+            .iter()
+            .find_map(|a| match a {
+                Argument::Positional(ModelPattern::Lit {
+                    lit: Lit::Int(i), ..
+                }) => i.base10_parse().ok(),
+                Argument::Named(
+                    id,
+                    ModelPattern::Lit {
+                        lit: Lit::Int(i), ..
+                    },
+                ) if id == "min" => i.base10_parse().ok(),
+                _ => None,
+            })
+            .unwrap_or(0);
+        let literal = |p: &ModelPattern| match p {
+            ModelPattern::Lit {
+                lit: Lit::Str(s), ..
+            } => Some(s.value()),
+            _ => None,
+        };
+
+        let text = match name {
+            "ident" => self.name().to_string(),
+            "string" | "lit_str" => format!("{:?}", self.name()),
+            "char" | "lit_char" | "spanned_char" => "'c'".to_string(),
+            "bool" | "lit_bool" | "spanned_bool" => ["true", "false"][self.below(2)].to_string(),
+            "f32" | "f64" | "lit_float" | "spanned_f32" | "spanned_f64" => {
+                format!("{}.5", self.below(100))
+            }
+            "hex_literal" => format!("0x{:x}", self.below(256)),
+            "oct_literal" => format!("0o{:o}", self.below(64)),
+            "bin_literal" => format!("0b{:b}", self.below(16)),
+            "rust_type" => ["i32", "String", "Vec<u8>"][self.below(3)].to_string(),
+            "rust_block" => "{}".to_string(),
+            "token_tree" => self.name().to_string(),
+            "balanced_group" => {
+                let (open, close) = match positional.first().and_then(|p| literal(p)).as_deref() {
+                    Some("[") => ("[", "]"),
+                    Some("{") => ("{", "}"),
+                    _ => ("(", ")"),
+                };
+                format!("{}{}{}", open, self.name(), close)
+            }
+            "lit_int" => self.below(100).to_string(),
+            _ if matches!(
+                name.trim_start_matches("spanned_"),
+                "i8" | "i16"
+                    | "i32"
+                    | "i64"
+                    | "i128"
+                    | "isize"
+                    | "u8"
+                    | "u16"
+                    | "u32"
+                    | "u64"
+                    | "u128"
+                    | "usize"
+            ) =>
+            {
+                self.below(100).to_string()
+            }
+            "keyword" => positional
+                .first()
+                .and_then(|p| literal(p))
+                .unwrap_or_default(),
+            "punct" | "oneof" => {
+                let options: Vec<String> = positional.iter().filter_map(|p| literal(p)).collect();
+                match options.len() {
+                    0 => String::new(),
+                    n => options[self.below(n)].clone(),
+                }
+            }
+            "skip" => {
+                if let Some(inner) = positional.first() {
+                    self.gen_pattern(inner, params, level, out);
+                }
+                return;
+            }
+            "separated" | "repeated" | "terminated" | "until_parse" => {
+                let Some(item) = positional.first().copied() else {
+                    return;
+                };
+                let separator = positional.get(1).filter(|_| name == "separated");
+                let terminator = positional.get(1).filter(|_| name == "terminated");
+                for i in 0..self.repetitions(item, min, None, level) {
+                    if let (true, Some(separator)) = (i > 0, separator) {
+                        self.gen_pattern(separator, params, level, out);
+                    }
+                    self.gen_pattern(item, params, level, out);
+                    if let Some(terminator) = terminator {
+                        self.gen_pattern(terminator, params, level, out);
+                    }
+                }
+                return;
+            }
+            "map" => {
+                let [key, assoc, value] = positional[..] else {
+                    return;
+                };
+                let separator = args.iter().find_map(|a| match a {
+                    Argument::Named(id, p) if id == "sep" => Some(p),
+                    _ => None,
+                });
+                for i in 0..self.repetitions(value, 0, None, level) {
+                    match (i > 0, separator) {
+                        (true, Some(separator)) => self.gen_pattern(separator, params, level, out),
+                        (true, None) => out.extend(quote!(,)),
+                        _ => {}
+                    }
+                    self.gen_pattern(key, params, level, out);
+                    self.gen_pattern(assoc, params, level, out);
+                    self.gen_pattern(value, params, level, out);
+                }
+                return;
+            }
+            "parse_in" => {
+                let Some(inner) = positional.get(1) else {
+                    return;
+                };
+                let mut contents = TokenStream::new();
+                self.gen_pattern(inner, params, level, &mut contents);
+                out.extend([proc_macro2::TokenTree::Literal(
+                    proc_macro2::Literal::string(&contents.to_string()),
+                )]);
+                return;
+            }
+            "delimited" => {
+                let [open, item, separator, close] = positional[..] else {
+                    return;
+                };
+                let delimiter = match literal(open).as_deref() {
+                    Some("(") => Some(proc_macro2::Delimiter::Parenthesis),
+                    Some("[") => Some(proc_macro2::Delimiter::Bracket),
+                    Some("{") => Some(proc_macro2::Delimiter::Brace),
+                    _ => None,
+                };
+                let mut items = TokenStream::new();
+                for i in 0..self.repetitions(item, 0, None, level) {
+                    if i > 0 {
+                        self.gen_pattern(separator, params, level, &mut items);
+                    }
+                    self.gen_pattern(item, params, level, &mut items);
+                }
+                match delimiter {
+                    Some(delimiter) => out.extend([proc_macro2::TokenTree::Group(
+                        proc_macro2::Group::new(delimiter, items),
+                    )]),
+                    None => {
+                        self.gen_pattern(open, params, level, out);
+                        out.extend(items);
+                        self.gen_pattern(close, params, level, out);
+                    }
+                }
+                return;
+            }
+            _ => String::new(),
+        };
+        out.extend(text.parse::<TokenStream>().unwrap_or_default());
+    }
+}
+
+impl Iterator for Samples<'_> {
+    type Item = TokenStream;
+
+    fn next(&mut self) -> Option<TokenStream> {
+        let mut out = TokenStream::new();
+        let start = self.start.clone();
+        self.gen_rule(&start, &[], &Default::default(), 0, &mut out);
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_generate_samples() {
+        let p: syn_grammar_model::parser::GrammarDefinition = parse_quote! {
+            grammar test {
+                keywords { alpha }
+                rule helper -> () = "x" -> { () }
+                pub rule list -> () = [ items:item* ] -> { () }
+                rule item -> () = list -> { () } | ident -> { () } | i32 "?"? -> { () }
+            }
+        };
+        let grammar = GrammarDefinition::from(p);
+
+        // The same seed gives the same samples, starting from the public rule
+        let first: Vec<String> = generate_samples(&grammar, 9, 4)
+            .take(20)
+            .map(|s| s.to_string())
+            .collect();
+        let again: Vec<String> = generate_samples(&grammar, 9, 4)
+            .take(20)
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(first, again);
+        assert!(first.iter().all(|s| s.starts_with('[')));
+        assert!(first.iter().any(|s| s.contains("[[")));
+        // Identifiers avoid the keywords of the grammar
+        assert!(first.iter().all(|s| !s.contains("alpha")));
+
+        // Without depth left, the list stays empty
+        let shallow: Vec<String> = generate_samples(&grammar, 9, 0)
+            .take(5)
+            .map(|s| s.to_string())
+            .collect();
+        assert!(shallow.iter().all(|s| s == "[]"));
+
+        let helper = generate_samples(&grammar, 9, 4).rule("helper").next();
+        assert_eq!(helper.unwrap().to_string(), "x");
+    }
+}
@@ -0,0 +1,126 @@
+//! Support of the generated `unparse_<rule>` functions, which print values back as tokens.
+//!
+//! Rules whose actions only construct their value from the bindings get an `unparse_<rule>`
+//! function next to `parse_<rule>`. It prints the literals of the matching alternative and
+//! each bound value through [`Unparse`], which is implemented for the values of the built-ins.
+
+use proc_macro2::{Group, Literal, TokenTree};
+use quote::ToTokens;
+use syn_grammar_model::model::types::{Identifier, SpannedValue, StringLiteral};
+
+pub use proc_macro2::{Delimiter, TokenStream};
+
+/// Prints a value bound by a built-in back as the tokens the built-in parses.
+pub trait Unparse {
+    fn unparse(&self, tokens: &mut TokenStream);
+}
+
+/// Appends the tokens of a literal of the grammar, e.g. `"+"` or `"fn"`.
+pub fn text(tokens: &mut TokenStream, text: &str) {
+    tokens.extend(text.parse::<TokenStream>().unwrap_or_default());
+}
+
+/// Appends a string literal, e.g. the contents printed for `parse_in(lit_str, rule)`.
+pub fn string(tokens: &mut TokenStream, text: &str) {
+    tokens.extend([TokenTree::Literal(Literal::string(text))]);
+}
+
+/// Appends a delimited group, e.g. the brackets of `[ items:item* ]`.
+pub fn group(tokens: &mut TokenStream, delimiter: Delimiter, inner: TokenStream) {
+    tokens.extend([TokenTree::Group(Group::new(delimiter, inner))]);
+}
+
+impl<T: Unparse + ?Sized> Unparse for &T {
+    fn unparse(&self, tokens: &mut TokenStream) {
+        (**self).unparse(tokens)
+    }
+}
+
+impl<T: Unparse + ?Sized> Unparse for Box<T> {
+    fn unparse(&self, tokens: &mut TokenStream) {
+        (**self).unparse(tokens)
+    }
+}
+
+impl<T: Unparse> Unparse for Vec<T> {
+    fn unparse(&self, tokens: &mut TokenStream) {
+        self.iter().for_each(|item| item.unparse(tokens));
+    }
+}
+
+impl<T: Unparse> Unparse for SpannedValue<T> {
+    fn unparse(&self, tokens: &mut TokenStream) {
+        self.value.unparse(tokens)
+    }
+}
+
+/// The text of `punct` and `oneof`.
+impl Unparse for String {
+    fn unparse(&self, tokens: &mut TokenStream) {
+        text(tokens, self)
+    }
+}
+
+impl Unparse for char {
+    fn unparse(&self, tokens: &mut TokenStream) {
+        tokens.extend([TokenTree::Literal(Literal::character(*self))]);
+    }
+}
+
+impl Unparse for bool {
+    fn unparse(&self, tokens: &mut TokenStream) {
+        text(tokens, if *self { "true" } else { "false" })
+    }
+}
+
+// Numbers are printed without a type suffix
+macro_rules! unparse_numbers {
+    ($($ty:ident => $literal:ident),* $(,)?) => {
+        $(
+            impl Unparse for $ty {
+                fn unparse(&self, tokens: &mut TokenStream) {
+                    tokens.extend([TokenTree::Literal(Literal::$literal(*self))]);
+                }
+            }
+        )*
+    };
+}
+
+unparse_numbers! {
+    i8 => i8_unsuffixed, i16 => i16_unsuffixed, i32 => i32_unsuffixed,
+    i64 => i64_unsuffixed, i128 => i128_unsuffixed, isize => isize_unsuffixed,
+    u8 => u8_unsuffixed, u16 => u16_unsuffixed, u32 => u32_unsuffixed,
+    u64 => u64_unsuffixed, u128 => u128_unsuffixed, usize => usize_unsuffixed,
+    f32 => f32_unsuffixed, f64 => f64_unsuffixed,
+}
+
+// Types that print themselves, such as the syn types of `rust_type` and `lit_str`
+macro_rules! unparse_to_tokens {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl Unparse for $ty {
+                fn unparse(&self, tokens: &mut TokenStream) {
+                    self.to_tokens(tokens)
+                }
+            }
+        )*
+    };
+}
+
+unparse_to_tokens! {
+    Identifier,
+    StringLiteral,
+    TokenStream,
+    TokenTree,
+    Group,
+    syn::Ident,
+    syn::Type,
+    syn::Block,
+    syn::Attribute,
+    syn::LitStr,
+    syn::LitInt,
+    syn::LitChar,
+    syn::LitBool,
+    syn::LitFloat,
+    syn::LitByte,
+}
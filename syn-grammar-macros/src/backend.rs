@@ -9,225 +9,330 @@ impl Backend for SynBackend {
             BuiltIn {
                 name: "ident",
                 return_type: "syn_grammar_model::model::types::Identifier",
+                peek: Some("syn::Ident"),
             },
             BuiltIn {
                 name: "string",
                 return_type: "syn_grammar_model::model::types::StringLiteral",
+                peek: Some("syn::LitStr"),
             },
             // Primitive Types (returning standard Rust types)
             BuiltIn {
                 name: "char",
                 return_type: "char",
+                peek: Some("syn::LitChar"),
             },
             BuiltIn {
                 name: "bool",
                 return_type: "bool",
+                peek: Some("syn::LitBool"),
             },
             // Integers
             BuiltIn {
                 name: "i8",
                 return_type: "i8",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "i16",
                 return_type: "i16",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "i32",
                 return_type: "i32",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "i64",
                 return_type: "i64",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "i128",
                 return_type: "i128",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "isize",
                 return_type: "isize",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "u8",
                 return_type: "u8",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "u16",
                 return_type: "u16",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "u32",
                 return_type: "u32",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "u64",
                 return_type: "u64",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "u128",
                 return_type: "u128",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "usize",
                 return_type: "usize",
+                peek: Some("syn::LitInt"),
             },
             // Floats
             BuiltIn {
                 name: "f32",
                 return_type: "f32",
+                peek: Some("syn::LitFloat"),
             },
             BuiltIn {
                 name: "f64",
                 return_type: "f64",
+                peek: Some("syn::LitFloat"),
             },
             // Alternative Bases
             BuiltIn {
                 name: "hex_literal",
                 return_type: "u64",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "oct_literal",
                 return_type: "u64",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "bin_literal",
                 return_type: "u64",
+                peek: Some("syn::LitInt"),
             },
             // Spanned Primitives (returning SpannedValue<T>)
             BuiltIn {
                 name: "spanned_char",
                 return_type: "syn_grammar_model::model::types::SpannedValue<char>",
+                peek: Some("syn::LitChar"),
             },
             BuiltIn {
                 name: "spanned_bool",
                 return_type: "syn_grammar_model::model::types::SpannedValue<bool>",
+                peek: Some("syn::LitBool"),
             },
             BuiltIn {
                 name: "spanned_i8",
                 return_type: "syn_grammar_model::model::types::SpannedValue<i8>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_i16",
                 return_type: "syn_grammar_model::model::types::SpannedValue<i16>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_i32",
                 return_type: "syn_grammar_model::model::types::SpannedValue<i32>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_i64",
                 return_type: "syn_grammar_model::model::types::SpannedValue<i64>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_i128",
                 return_type: "syn_grammar_model::model::types::SpannedValue<i128>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_isize",
                 return_type: "syn_grammar_model::model::types::SpannedValue<isize>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_u8",
                 return_type: "syn_grammar_model::model::types::SpannedValue<u8>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_u16",
                 return_type: "syn_grammar_model::model::types::SpannedValue<u16>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_u32",
                 return_type: "syn_grammar_model::model::types::SpannedValue<u32>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_u64",
                 return_type: "syn_grammar_model::model::types::SpannedValue<u64>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_u128",
                 return_type: "syn_grammar_model::model::types::SpannedValue<u128>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_usize",
                 return_type: "syn_grammar_model::model::types::SpannedValue<usize>",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "spanned_f32",
                 return_type: "syn_grammar_model::model::types::SpannedValue<f32>",
+                peek: Some("syn::LitFloat"),
             },
             BuiltIn {
                 name: "spanned_f64",
                 return_type: "syn_grammar_model::model::types::SpannedValue<f64>",
+                peek: Some("syn::LitFloat"),
             },
             // Low-level token filters (currently return syn types or ())
             BuiltIn {
                 name: "alpha",
                 return_type: "syn::Ident",
+                peek: None,
             },
             BuiltIn {
                 name: "digit",
                 return_type: "syn::Ident",
+                peek: None,
             },
             BuiltIn {
                 name: "alphanumeric",
                 return_type: "syn::Ident",
+                peek: None,
             },
             BuiltIn {
                 name: "hex_digit",
                 return_type: "syn::Ident",
+                peek: None,
             },
             BuiltIn {
                 name: "oct_digit",
                 return_type: "syn::Ident",
+                peek: None,
             },
             BuiltIn {
                 name: "any_byte",
                 return_type: "syn::LitByte",
+                peek: Some("syn::LitByte"),
             },
             BuiltIn {
                 name: "eof",
                 return_type: "()",
+                peek: None,
+            },
+            BuiltIn {
+                name: "position",
+                return_type: "proc_macro2::Span",
+                peek: None,
             },
             BuiltIn {
                 name: "fail",
                 return_type: "()",
+                peek: None,
+            },
+            BuiltIn {
+                name: "scan",
+                return_type: "_",
+                peek: None,
+            },
+            BuiltIn {
+                name: "keyword",
+                return_type: "syn::Ident",
+                peek: None,
             },
             BuiltIn {
                 name: "whitespace",
                 return_type: "()",
+                peek: None,
+            },
+            // Layout of `layout = significant` grammars
+            BuiltIn {
+                name: "newline",
+                return_type: "()",
+                peek: None,
+            },
+            BuiltIn {
+                name: "indent",
+                return_type: "()",
+                peek: None,
+            },
+            BuiltIn {
+                name: "dedent",
+                return_type: "()",
+                peek: None,
+            },
+            BuiltIn {
+                name: "punct",
+                return_type: "syn_grammar_model::model::types::SpannedValue<String>",
+                peek: None,
+            },
+            BuiltIn {
+                name: "oneof",
+                return_type: "syn_grammar_model::model::types::SpannedValue<String>",
+                peek: None,
             },
             // Syn-Specific Built-ins
             BuiltIn {
                 name: "rust_type",
                 return_type: "syn::Type",
+                peek: None,
             },
             BuiltIn {
                 name: "rust_block",
                 return_type: "syn::Block",
+                peek: Some("syn::token::Brace"),
             },
             BuiltIn {
                 name: "lit_str",
                 return_type: "syn::LitStr",
+                peek: Some("syn::LitStr"),
             },
             BuiltIn {
                 name: "lit_int",
                 return_type: "syn::LitInt",
+                peek: Some("syn::LitInt"),
             },
             BuiltIn {
                 name: "lit_char",
                 return_type: "syn::LitChar",
+                peek: Some("syn::LitChar"),
             },
             BuiltIn {
                 name: "lit_bool",
                 return_type: "syn::LitBool",
+                peek: Some("syn::LitBool"),
             },
             BuiltIn {
                 name: "lit_float",
                 return_type: "syn::LitFloat",
+                peek: Some("syn::LitFloat"),
             },
             BuiltIn {
                 name: "outer_attrs",
                 return_type: "Vec<syn::Attribute>",
+                peek: None,
+            },
+            BuiltIn {
+                name: "token_tree",
+                return_type: "proc_macro2::TokenTree",
+                peek: None,
+            },
+            BuiltIn {
+                name: "balanced_group",
+                return_type: "proc_macro2::Group",
+                peek: None,
             },
         ]
     }
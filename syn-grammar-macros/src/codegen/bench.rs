@@ -0,0 +1,98 @@
+use super::rule::cfg_attrs;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Result;
+use syn_grammar_model::model::*;
+
+/// Generates the `benches` module of the `bench` feature: a Criterion benchmark of the entry
+/// point of every public rule with `#[bench_input = ".."]` attributes, one per input. The
+/// attributes are checked without the feature too, so a grammar does not only fail to compile
+/// when benchmarked.
+pub fn generate_benches(grammar: &GrammarDefinition) -> Result<TokenStream> {
+    let mut benches = Vec::new();
+    for rule in &grammar.rules {
+        let inputs = bench_inputs(rule)?;
+        if inputs.is_empty() {
+            continue;
+        }
+        let fn_name = format_ident!("parse_{}", rule.name);
+        let group = format!("{}::{}", grammar.name, rule.name);
+        let cfg_attrs = cfg_attrs(rule);
+        // The tokens are split once and cloned outside of the measurement, so the benchmark
+        // times the parser and not the tokenizer
+        benches.push(quote! {
+            #(#cfg_attrs)*
+            {
+                let mut group = c.benchmark_group(#group);
+                for (i, input) in [#(#inputs),*].into_iter().enumerate() {
+                    let tokens: rt::TokenStream = input.parse().unwrap_or_else(|err| {
+                        panic!("bench input {} of `{}` is not valid tokens: {}", i, #group, err)
+                    });
+                    if let Err(err) = #fn_name.parse2(tokens.clone()) {
+                        panic!("bench input {} of `{}` does not parse: {}", i, #group, err);
+                    }
+                    group.bench_function(BenchmarkId::from_parameter(i), |b| {
+                        b.iter_batched(|| tokens.clone(), |tokens| #fn_name.parse2(tokens), BatchSize::SmallInput)
+                    });
+                }
+                group.finish();
+            }
+        });
+    }
+
+    if !cfg!(feature = "bench") {
+        return Ok(quote!());
+    }
+    Ok(quote! {
+        /// Criterion benchmarks of the rules with `#[bench_input = ".."]` inputs.
+        pub mod benches {
+            use super::*;
+            use rt::criterion::{BatchSize, BenchmarkId, Criterion};
+            use syn::parse::Parser;
+
+            /// Benchmarks every `#[bench_input]` of a rule with its entry point, in a group
+            /// named `grammar::rule` with the index of the input as parameter. Panics if an
+            /// input does not parse, so that failures are not measured.
+            pub fn bench(c: &mut Criterion) {
+                #(#benches)*
+            }
+        }
+    })
+}
+
+/// The inputs of the `#[bench_input = ".."]` attributes of a rule, which must be public and
+/// without parameters, as the benchmarks call its entry point.
+fn bench_inputs(rule: &Rule) -> Result<Vec<&syn::LitStr>> {
+    let attrs: Vec<&syn::Attribute> = rule
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("bench_input"))
+        .collect();
+    let Some(first) = attrs.first() else {
+        return Ok(Vec::new());
+    };
+    let is_public = rule.is_pub || rule.name == "main";
+    if !is_public || !rule.params.is_empty() || !rule.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            first,
+            "#[bench_input] needs a public rule without parameters or generics",
+        ));
+    }
+    attrs
+        .into_iter()
+        .map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(s),
+                        ..
+                    }),
+                ..
+            }) => Ok(s),
+            _ => Err(syn::Error::new_spanned(
+                attr,
+                "expected a string of input: #[bench_input = \"..\"]",
+            )),
+        })
+        .collect()
+}
@@ -1,35 +1,287 @@
+mod bench;
 mod pattern;
 mod rule;
+mod unparse;
 
+use crate::backend::SynBackend;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
+use std::collections::HashSet;
 use syn::Result;
-use syn_grammar_model::{analysis, model::*};
+use syn_grammar_model::{analysis, model::*, Backend};
 
-pub fn generate_rust(grammar: GrammarDefinition) -> Result<TokenStream> {
+/// Built-ins that match whenever their peek token is next. The others check more than the
+/// token type, such as the range of an integer or the prefix of a hex literal.
+const DECISIVE_PEEK_BUILTINS: &[&str] = &[
+    "ident",
+    "string",
+    "char",
+    "bool",
+    "any_byte",
+    "spanned_char",
+    "spanned_bool",
+    "lit_str",
+    "lit_int",
+    "lit_char",
+    "lit_bool",
+    "lit_float",
+];
+
+/// Grammar-wide information shared by the code generation of all rules, and the keywords as
+/// seen from the rule being generated.
+pub struct Context {
+    /// Custom keywords of the rule being generated (see `analysis::collect_rule_keywords`).
+    pub custom_keywords: HashSet<String>,
+    /// Words that the `ident` built-in does not match in the rule being generated.
+    pub reserved: Vec<String>,
+    /// Built-ins that are not shadowed by a rule of the grammar.
+    pub builtins: Vec<BuiltIn>,
+    /// Nullability and FIRST sets of the rules, used to derive peeks for rule calls.
+    pub analysis: analysis::GrammarAnalysis,
+    /// Names of the rules of the grammar, which take precedence over built-ins.
+    pub rule_names: HashSet<String>,
+    /// The grammar this one inherits from, whose rules `super` calls.
+    pub parent: Option<syn::Ident>,
+    /// Whether runs of variants starting with the same rule call parse it once (see
+    /// `analysis::common_prefix_runs`). Not in rules with `where` guards or `throws`, whose
+    /// lowered actions can fail after the value of the call has been handed over.
+    pub hoist_prefixes: bool,
+    /// Set by `options { cst = true; }`: the rules record concrete syntax tree nodes.
+    pub cst: bool,
+    /// Set by `options { layout = significant; }`: the `newline`, `indent` and `dedent`
+    /// built-ins match line breaks and indentation.
+    pub layout: bool,
+    /// Set by `options { diagnose = true; }`: entry rules get a `diagnose_<rule>` function.
+    pub diagnose: bool,
+    /// Set by `options { lenient = true; }`: entry rules get a `lenient_<rule>` function.
+    pub lenient: bool,
+    /// Set by `options { incremental = true; }`: entry rules get an `incremental_<rule>`
+    /// function.
+    pub incremental: bool,
+}
+
+impl Context {
+    pub fn new(grammar: &GrammarDefinition) -> Self {
+        let builtins = SynBackend::get_builtins()
+            .iter()
+            .filter(|b| !grammar.rules.iter().any(|r| r.name == b.name))
+            .cloned()
+            .collect();
+
+        Self {
+            custom_keywords: analysis::collect_custom_keywords(grammar),
+            reserved: Vec::new(),
+            builtins,
+            analysis: analysis::analyze_grammar(grammar),
+            rule_names: grammar.rules.iter().map(|r| r.name.to_string()).collect(),
+            parent: grammar.inherits.clone(),
+            hoist_prefixes: true,
+            cst: grammar.options.cst,
+            layout: grammar.options.layout,
+            diagnose: grammar.options.diagnose,
+            lenient: grammar.options.lenient,
+            incremental: grammar.options.incremental,
+        }
+    }
+
+    /// Sets the keywords and whether prefixes are hoisted for generating `rule`. Instances of
+    /// generic rules use the keyword declarations of their template.
+    pub fn enter_rule(&mut self, grammar: &GrammarDefinition, rule: &Rule) {
+        let name = match &rule.instantiation {
+            Some(instantiation) => instantiation.template.to_string(),
+            None => rule.name.to_string(),
+        };
+        self.custom_keywords = analysis::collect_rule_keywords(grammar, &name);
+        self.reserved = analysis::reserved_keywords(grammar, &name);
+        self.hoist_prefixes =
+            rule.throws.is_none() && rule.variants.iter().all(|v| v.guard.is_none());
+    }
+
+    /// Returns `input.peek(#peek)`. A peek for an identifier excludes the reserved words of the
+    /// rule, as `ident` does not match them.
+    pub fn peek_cond(&self, peek: &TokenStream) -> TokenStream {
+        let reserved = &self.reserved;
+        if reserved.is_empty() || peek.to_string() != "syn :: Ident" {
+            return quote!(input.peek(#peek));
+        }
+        quote!((input.peek(#peek) && !rt::peek_reserved(input, &[#(#reserved),*])))
+    }
+
+    /// Returns the token to peek for before attempting `pattern`, if it has a simple one.
+    pub fn simple_peek(&self, pattern: &ModelPattern) -> Result<Option<TokenStream>> {
+        analysis::get_simple_peek(pattern, &self.custom_keywords, &self.builtins)
+    }
+
+    /// Whether a match of [`Self::simple_peek`] guarantees that `pattern` matches too, so a
+    /// repetition may commit to it without `attempt`. A built-in only does so when it is called
+    /// directly and matches every token it peeks for (e.g. not `u8`, whose integer may not fit).
+    pub fn peek_decides(&self, pattern: &ModelPattern) -> bool {
+        match pattern {
+            ModelPattern::RuleCall {
+                rule_name, args, ..
+            } if args.is_empty() => {
+                DECISIVE_PEEK_BUILTINS.contains(&rule_name.to_string().as_str())
+                    && self.builtins.iter().any(|b| rule_name == b.name)
+            }
+            _ => analysis::get_simple_peek(pattern, &self.custom_keywords, &[])
+                .ok()
+                .flatten()
+                .is_some(),
+        }
+    }
+
+    /// Returns an `input.peek(..) || ..` condition derived from the FIRST set of a call to a
+    /// non-nullable user rule, if that set is small and can be decided by single-token peeks.
+    ///
+    /// With `follow` given, the condition is only returned if it is *decisive*: no token that
+    /// may start `follow` is accepted by it, so the call may be committed to without `attempt`.
+    /// `closed` marks `follow` as the end of a delimited group, where nothing else can follow.
+    pub fn first_set_peek(
+        &self,
+        pattern: &ModelPattern,
+        follow: Option<(&[ModelPattern], bool)>,
+    ) -> Option<TokenStream> {
+        let ModelPattern::RuleCall {
+            rule_name, args, ..
+        } = pattern
+        else {
+            return None;
+        };
+        let name = rule_name.to_string();
+        if !args.is_empty() || self.analysis.nullable_rules.contains(&name) {
+            return None;
+        }
+
+        let first_set = self.analysis.first_sets.get(&name)?;
+        if first_set.len() > analysis::MAX_FIRST_SET_PEEKS {
+            return None;
+        }
+        let types = self.peek_types(first_set)?;
+
+        if let Some((follow, closed)) = follow {
+            if !closed && self.analysis.is_sequence_nullable(follow) {
+                return None;
+            }
+            let follow_set = self.analysis.first_set_of(follow);
+            if !follow_set.is_empty() {
+                let follow_types = self.peek_types(&follow_set)?;
+                if analysis::peek_types_overlap(&types, &follow_types) {
+                    return None;
+                }
+            }
+        }
+
+        Some(quote!(#(input.peek(#types))||*))
+    }
+
+    fn peek_types(&self, first_set: &HashSet<String>) -> Option<Vec<syn::Type>> {
+        analysis::first_set_peek_types(first_set, &self.custom_keywords, &self.builtins)
+    }
+}
+
+/// Generates the `grammar_info` module, which lists properties of the grammar found by the
+/// analysis so that tests and build scripts can assert them, and the `GRAMMAR_DOCS` summary.
+pub fn generate_grammar_info(grammar: &GrammarDefinition) -> TokenStream {
+    let analysis = analysis::analyze_grammar(grammar);
+    // Without zero-width built-ins such as `position()`
+    let nullable = sorted_names(
+        analysis
+            .nullable_rules
+            .iter()
+            .filter(|n| grammar.rules.iter().any(|r| r.name == n)),
+    );
+    let unused = sorted_names(analysis.unused_rules.iter().filter(|n| !n.starts_with('_')));
+    let left_recursive = sorted_names(analysis.cycles.iter().flatten());
+    let docs = grammar.to_markdown();
+
+    quote! {
+        /// A Markdown summary of the rules as written in the grammar: the doc comments and the
+        /// syntax in EBNF of each rule.
+        pub const GRAMMAR_DOCS: &str = #docs;
+
+        /// Properties of the rules as written in the grammar.
+        pub mod grammar_info {
+            /// Rules that can match without consuming input.
+            pub const NULLABLE_RULES: &[&str] = &[#(#nullable),*];
+            /// Rules that cannot be reached from a public rule (or from the first rule if none
+            /// is public), except those whose name starts with `_`.
+            pub const UNUSED_RULES: &[&str] = &[#(#unused),*];
+            /// Rules that are left-recursive, directly or through other rules.
+            pub const LEFT_RECURSIVE_RULES: &[&str] = &[#(#left_recursive),*];
+        }
+    }
+}
+
+/// Appends the syntax in EBNF to the doc comments of the rules, which the generated `parse_*`
+/// functions carry. Runs before the rules are rewritten, so that it shows them as written.
+pub fn document_syntax(grammar: &mut GrammarDefinition) {
+    for rule in &mut grammar.rules {
+        if !rule.attrs.iter().any(|a| a.path().is_ident("doc")) {
+            let summary = format!("Parser for rule `{}`.", rule.name);
+            rule.attrs.push(syn::parse_quote!(#[doc = #summary]));
+        }
+        let syntax = format!("\n```text\n{}```", rule.to_ebnf());
+        rule.attrs.push(syn::parse_quote!(#[doc = #syntax]));
+    }
+}
+
+fn sorted_names<'a>(names: impl Iterator<Item = &'a String>) -> Vec<&'a String> {
+    let mut names: Vec<_> = names.collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+pub fn generate_rust(grammar: GrammarDefinition, grammar_info: TokenStream) -> Result<TokenStream> {
     let grammar_name = &grammar.name;
-    let custom_keywords = analysis::collect_custom_keywords(&grammar);
+    let mut cx = Context::new(&grammar);
 
-    let kw_defs = (!custom_keywords.is_empty()).then(|| {
-        let defs = custom_keywords.iter().map(|k| {
-            let ident = format_ident!("{}", k);
-            quote! { syn::custom_keyword!(#ident); }
-        });
-        quote! { pub mod kw { #(#defs)* } }
-    });
+    // A shared token pool replaces the generated `kw` module
+    let custom_keywords = analysis::collect_custom_keywords(&grammar);
+    let kw_defs = match &grammar.token_pool {
+        Some(pool) => Some(quote! { use #pool as kw; }),
+        None => (!custom_keywords.is_empty()).then(|| {
+            let defs = custom_keywords.iter().map(|k| {
+                let ident = format_ident!("{}", k);
+                quote! { syn::custom_keyword!(#ident); }
+            });
+            quote! { pub mod kw { #(#defs)* } }
+        }),
+    };
 
+    // Re-exported, so that a grammar inheriting from this one also sees the rules of its
+    // ancestors
     let inheritance = grammar.inherits.as_ref().map(|parent| {
-        quote! { use super::#parent::*; }
+        quote! { pub use super::#parent::*; }
     });
 
     let uses = &grammar.uses;
 
+    // Including the imported files makes the compiler rebuild the grammar when they change
+    let dependencies = grammar.dependencies.iter().map(|path| {
+        let path = path.to_string_lossy();
+        quote! { const _: &[u8] = include_bytes!(#path); }
+    });
+
     // Remove defined_rule_names collection as it's no longer needed for builtin logic
 
     let rules = grammar
         .rules
         .iter()
-        .map(|r| rule::generate_rule(r, &custom_keywords))
+        .map(|r| {
+            cx.enter_rule(&grammar, r);
+            rule::generate_rule(r, &cx)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let unparsers = unparse::generate_unparsers(&grammar);
+    let benches = bench::generate_benches(&grammar)?;
+    let markers = grammar.rules.iter().filter_map(rule::generate_rule_marker);
+    let parser_struct = rule::generate_parser_struct(&grammar);
+    let parse_impls = grammar
+        .rules
+        .iter()
+        .filter_map(|r| rule::generate_parse_impl(r).transpose())
         .collect::<Result<Vec<_>>>()?;
 
     // Capture the rules as a TokenStream to reuse for both code generation and string introspection
@@ -46,6 +298,10 @@ pub fn generate_rust(grammar: GrammarDefinition) -> Result<TokenStream> {
             /// The generated source code of the rules, used for testing verification.
             pub const GENERATED_SOURCE: &str = #rules_str;
 
+            #grammar_info
+
+            #(#dependencies)*
+
             use super::*;
             use syn::parse::{Parse, ParseStream};
             use syn::Result;
@@ -66,6 +322,22 @@ pub fn generate_rust(grammar: GrammarDefinition) -> Result<TokenStream> {
             #(#uses)*
 
             #rules_stream
+
+            #unparsers
+
+            #benches
+
+            #(#parse_impls)*
+
+            #parser_struct
+
+            /// Marker types of the public rules, to use them as `syn::parse::Parse` types
+            /// through `rt::RuleParser`.
+            pub mod rules {
+                use super::*;
+
+                #(#markers)*
+            }
         }
     })
 }
@@ -1,40 +1,344 @@
+use super::Context;
 use crate::backend::SynBackend;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
-use std::collections::HashSet;
 use syn::{Lit, Result};
 use syn_grammar_model::{analysis, model::*, Backend};
 
 pub fn generate_sequence(
     patterns: &[ModelPattern],
     action: &TokenStream,
-    kws: &HashSet<String>,
+    cx: &Context,
 ) -> Result<TokenStream> {
-    let steps = generate_sequence_steps(patterns, kws)?;
+    let steps = generate_sequence_steps(patterns, cx)?;
     Ok(quote! { { #steps Ok({ #action }) } })
 }
 
-pub fn generate_sequence_steps(
-    patterns: &[ModelPattern],
-    kws: &HashSet<String>,
-) -> Result<TokenStream> {
+pub fn generate_sequence_steps(patterns: &[ModelPattern], cx: &Context) -> Result<TokenStream> {
+    generate_steps(patterns, false, cx)
+}
+
+/// Generates the steps of a sequence. `closed` is set for the contents of a delimited group,
+/// whose end cannot be followed by any further token.
+fn generate_steps(patterns: &[ModelPattern], closed: bool, cx: &Context) -> Result<TokenStream> {
     let mut steps = Vec::new();
-    for p in patterns {
-        steps.push(generate_pattern_step(p, kws)?);
+    for (i, p) in patterns.iter().enumerate() {
+        let follow = Some((&patterns[i + 1..], closed));
+        let step = match p {
+            ModelPattern::Repeat(inner, _) => generate_repetition(inner, (0, None), follow, cx)?,
+            ModelPattern::Plus(inner, _) => generate_repetition(inner, (1, None), follow, cx)?,
+            ModelPattern::Bounded {
+                pattern, min, max, ..
+            } => generate_repetition(pattern, (*min, *max), follow, cx)?,
+            ModelPattern::RuleCall {
+                binding,
+                rule_name,
+                generics,
+                args,
+                ..
+            } if rule_name == "terminated" && !cx.rule_names.contains("terminated") => {
+                generate_terminated(binding.as_ref(), rule_name, generics, args, follow, cx)?
+            }
+            _ => generate_pattern_step(p, cx)?,
+        };
+        steps.push(step);
     }
     Ok(quote! { #(#steps)* })
 }
 
-fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Result<TokenStream> {
+/// Generates `inner*`, `inner+` or `inner{min,max}` for the given `(min, max)` bounds.
+///
+/// If the repeated item can be decided by peeking (see [`Context::peek_decides`], and
+/// [`Context::first_set_peek`] for how `follow` is used), the loop commits to each item without
+/// forking the input. A peek that does not decide the item only guards its attempt. Other
+/// bounds than those of `*` and `+` are counted, attempting each item.
+fn generate_repetition(
+    inner: &ModelPattern,
+    (min, max): (usize, Option<usize>),
+    follow: Option<(&[ModelPattern], bool)>,
+    cx: &Context,
+) -> Result<TokenStream> {
+    let at_least_one = min == 1;
+    let mut inner = inner.clone();
+    let mut collectors = Vec::new();
+    take_collectors(&mut inner, &mut collectors);
+    let inner = &inner;
+
+    let bindings = analysis::collect_bindings(std::slice::from_ref(inner));
+    let inner_logic = generate_pattern_step(inner, cx)?;
+
+    // Only use peek optimization if it's safe and unambiguous. A peek that does not decide
+    // the item still guards an attempt, so a failing item ends the loop
+    let simple_peek = if bindings.is_empty() {
+        None
+    } else {
+        cx.simple_peek(inner)
+            .ok()
+            .flatten()
+            .map(|peek| (cx.peek_cond(&peek), cx.peek_decides(inner)))
+    };
+    let peek_cond = simple_peek.or_else(|| {
+        follow
+            .and_then(|_| cx.first_set_peek(inner, follow))
+            .map(|cond| (cond, true))
+    });
+
+    // Use temporary names for vectors to avoid shadowing by inner bindings
+    let vec_names: Vec<_> = bindings
+        .iter()
+        .map(|b| format_ident!("_vec_{}", b))
+        .collect();
+
+    let collector_of = |b: &syn::Ident| collectors.iter().find(|(c, _)| c == b).map(|(_, ty)| ty);
+    let init_vecs: Vec<_> = vec_names
+        .iter()
+        .zip(bindings.iter())
+        .map(|(v, b)| match collector_of(b) {
+            Some(ty) => quote!(let mut #v: #ty = Default::default();),
+            None => quote!(let mut #v = Vec::new();),
+        })
+        .collect();
+    let push_vecs: Vec<_> = vec_names
+        .iter()
+        .zip(bindings.iter())
+        .map(|(v, b)| match collector_of(b).map(collector_kind) {
+            Some("Option") => quote!(#v = Some(#b);),
+            Some("String") => quote!(#v.push_str(&#b.to_string());),
+            Some(_) => quote!(Extend::extend(&mut #v, Some(#b));),
+            None => quote!(#v.push(#b);),
+        })
+        .collect();
+    let finalize_vecs: Vec<_> = bindings
+        .iter()
+        .zip(vec_names.iter())
+        .map(|(b, v)| quote!(let #b = #v;))
+        .collect();
+
+    // Without a peek, an iteration may succeed without consuming input (e.g. a nullable
+    // built-in the validator cannot see through), which would repeat forever
+    let progress_check = quote! {
+        if _rep_cursor == input.cursor() {
+            return Err(input.error("Repetition matched empty input (infinite loop detected)"));
+        }
+        _rep_cursor = input.cursor();
+    };
+
+    if min > 1 || max.is_some() {
+        let return_tuple = quote!(( #(#bindings),* ));
+        let limit = max.map(|max| quote!(if _rep_count == #max { break; }));
+        // Bounded by a maximum, a repetition of empty matches ends anyway
+        let (start_cursor, progress_check) = match max {
+            Some(_) => (None, None),
+            None => (
+                Some(quote!(let mut _rep_cursor = input.cursor();)),
+                Some(progress_check),
+            ),
+        };
+        let min_check = (min > 0).then(|| {
+            let expected = describe_count(inner, min, max);
+            quote! {
+                if _rep_count < #min {
+                    return Err(rt::too_few(input, ctx, #expected));
+                }
+            }
+        });
+        return Ok(quote! {
+            #(#init_vecs)*
+            let mut _rep_count: usize = 0;
+            #start_cursor
+            loop {
+                #limit
+                let Some(vals) = rt::attempt(input, ctx, |mut input, ctx| {
+                    #inner_logic
+                    Ok(#return_tuple)
+                })? else {
+                    break;
+                };
+                #progress_check
+                let #return_tuple = vals;
+                #(#push_vecs)*
+                _rep_count += 1;
+            }
+            #min_check
+            #(#finalize_vecs)*
+        });
+    }
+
+    let first = at_least_one.then(|| {
+        quote! {
+            {
+                #inner_logic
+                #(#push_vecs)*
+            }
+        }
+    });
+
+    if let Some((cond, true)) = &peek_cond {
+        return Ok(quote! {
+           #(#init_vecs)*
+           #first
+           while #cond {
+               {
+                   #inner_logic
+                   #(#push_vecs)*
+               }
+           }
+           #(#finalize_vecs)*
+        });
+    }
+
+    if bindings.is_empty() {
+        let first = at_least_one.then(|| quote!(#inner_logic));
+        return Ok(quote! {
+            #first
+            let mut _rep_cursor = input.cursor();
+            // Pass ctx to attempt
+            while let Some(_) = rt::attempt(input, ctx, |mut input, ctx| { #inner_logic Ok(()) })? {
+                #progress_check
+            }
+        });
+    }
+
+    let return_tuple = quote!(( #(#bindings),* ));
+    let tuple_pat = quote!(( #(#bindings),* ));
+
+    if let Some((cond, false)) = peek_cond {
+        return Ok(quote! {
+           #(#init_vecs)*
+           #first
+           let mut _rep_cursor = input.cursor();
+           while #cond {
+               // Pass ctx to attempt
+               let Some(vals) = rt::attempt(input, ctx, |mut input, ctx| {
+                   #inner_logic
+                   Ok(#return_tuple)
+               })? else {
+                   break;
+               };
+               #progress_check
+               let #tuple_pat = vals;
+               #(#push_vecs)*
+           }
+           #(#finalize_vecs)*
+        });
+    }
+
+    Ok(quote! {
+       #(#init_vecs)*
+       #first
+       let mut _rep_cursor = input.cursor();
+       // Pass ctx to attempt
+       while let Some(vals) = rt::attempt(input, ctx, |mut input, ctx| {
+           #inner_logic
+           Ok(#return_tuple)
+       })? {
+           #progress_check
+           let #tuple_pat = vals;
+           #(#push_vecs)*
+       }
+       #(#finalize_vecs)*
+    })
+}
+
+/// Describes how many items a bounded repetition expects, e.g. "between 2 and 4 digits" for
+/// `digit{2,4}`.
+fn describe_count(inner: &ModelPattern, min: usize, max: Option<usize>) -> String {
+    let item = |count: usize| match inner {
+        ModelPattern::RuleCall { rule_name, .. } if count == 1 => rule_name.to_string(),
+        ModelPattern::RuleCall { rule_name, .. } => {
+            let name = rule_name.to_string();
+            if ["s", "x", "z", "ch", "sh"]
+                .iter()
+                .any(|end| name.ends_with(end))
+            {
+                format!("{}es", name)
+            } else {
+                format!("{}s", name)
+            }
+        }
+        ModelPattern::Lit {
+            lit: Lit::Str(s), ..
+        } => format!("`{}`", s.value()),
+        _ if count == 1 => "item".to_string(),
+        _ => "items".to_string(),
+    };
+    match max {
+        Some(max) if max == min => format!("{} {}", min, item(min)),
+        Some(max) => format!("between {} and {} {}", min, max, item(max)),
+        None => format!("at least {} {}", min, item(min)),
+    }
+}
+
+/// Removes the `#[collect(Type)]` annotations of the bindings of a repeated pattern, recording
+/// the collector type of each binding. Annotations inside nested repetitions are left to them.
+fn take_collectors(pattern: &mut ModelPattern, out: &mut Vec<(syn::Ident, syn::Type)>) {
+    match pattern {
+        ModelPattern::Collect(inner, ty, _) => {
+            if let Some(binding) = analysis::collect_bindings(std::slice::from_ref(inner)).pop() {
+                out.push((binding, (**ty).clone()));
+            }
+            *pattern = (**inner).clone();
+            take_collectors(pattern, out);
+        }
+        ModelPattern::Group(alts, _) => {
+            for (seq, _) in alts {
+                seq.iter_mut().for_each(|p| take_collectors(p, out));
+            }
+        }
+        ModelPattern::Bracketed(seq, _)
+        | ModelPattern::Braced(seq, _)
+        | ModelPattern::Parenthesized(seq, _)
+        | ModelPattern::Scope(seq, _) => {
+            seq.iter_mut().for_each(|p| take_collectors(p, out));
+        }
+        ModelPattern::Optional(inner, _)
+        | ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Peek(inner, _)
+        | ModelPattern::Recover { body: inner, .. } => take_collectors(inner, out),
+        _ => {}
+    }
+}
+
+/// How values are added to a `#[collect(Type)]` collector, decided by the last segment of
+/// its type: `Option` keeps the last value, `String` appends the text of each value and any
+/// other type is extended with each value.
+fn collector_kind(ty: &syn::Type) -> &'static str {
+    let last = match ty {
+        syn::Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    };
+    match last.as_deref() {
+        Some("Option") => "Option",
+        Some("String") => "String",
+        _ => "Extend",
+    }
+}
+
+fn generate_pattern_step(pattern: &ModelPattern, cx: &Context) -> Result<TokenStream> {
     match pattern {
         ModelPattern::Cut(_) => Ok(quote!()),
         ModelPattern::Lit { binding, lit } => {
             if let Lit::Str(lit) = lit {
-                let token_types = analysis::resolve_token_types(lit, kws)?;
+                if analysis::is_case_insensitive_literal(lit) {
+                    return generate_case_insensitive_lit(binding.as_ref(), lit);
+                }
+                let token_types = analysis::resolve_token_types(lit, &cx.custom_keywords)?;
 
                 if token_types.len() <= 1 {
                     let parses = token_types.iter().map(|ty| {
-                        if let Some(bind) = binding {
+                        if let (Some(bind), true) = (binding, analysis::is_ident_literal(lit)) {
+                            let word = lit.value();
+                            quote! {
+                                let _t = input.parse::<#ty>()?;
+                                ctx.record_span(syn::spanned::Spanned::span(&_t));
+                                let #bind = syn::Ident::new(#word, syn::spanned::Spanned::span(&_t));
+                            }
+                        } else if let Some(bind) = binding {
                             quote! {
                                 let #bind = input.parse::<#ty>()?;
                                 ctx.record_span(syn::spanned::Spanned::span(&#bind));
@@ -51,11 +355,18 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                     let mut steps = Vec::new();
                     let mut checks = Vec::new();
                     let mut results = Vec::new();
+                    // Report the whole literal, not the token of it that failed to match
+                    let expected = format!("`{}`", lit.value());
 
+                    steps.push(quote! {
+                        let _start = input.span();
+                    });
                     for (i, ty) in token_types.iter().enumerate() {
                         let var = format_ident!("_t{}", i);
                         steps.push(quote! {
-                            let #var = input.parse::<#ty>()?;
+                            let #var = input
+                                .parse::<#ty>()
+                                .map_err(|_| rt::expected_tokens(input, _start, #expected))?;
                         });
                         results.push(var.clone());
 
@@ -81,19 +392,19 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                         }
                     }
 
-                    let bind_stmt = if let Some(bind) = binding {
-                        quote! { let #bind = ( #(#results),* ); }
-                    } else {
-                        quote! {}
-                    };
-
-                    Ok(quote! {
+                    let tokens = quote! {
                         {
                             #(#steps)*
                             #(#checks)*
-                            #bind_stmt
+                            ( #(#results),* )
                         }
-                    })
+                    };
+
+                    if let Some(bind) = binding {
+                        Ok(quote! { let #bind = #tokens; })
+                    } else {
+                        Ok(quote! { let _ = #tokens; })
+                    }
                 }
             } else {
                 Err(syn::Error::new(
@@ -107,6 +418,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
             rule_name,
             generics,
             args,
+            ..
         } => {
             let rule_name_str = rule_name.to_string();
             let builtins = SynBackend::get_builtins();
@@ -171,55 +483,19 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                     quote!(Vec)
                 };
 
-                // Inject binding if missing
-                let (rule_arg_with_binding, item_binding) = match rule_arg {
-                    ModelPattern::RuleCall {
-                        binding: None,
-                        rule_name,
-                        generics,
-                        args,
-                    } => {
-                        let temp = format_ident!("_item");
-                        let new_pat = ModelPattern::RuleCall {
-                            binding: Some(temp.clone()),
-                            rule_name: rule_name.clone(),
-                            generics: generics.clone(),
-                            args: args.clone(),
-                        };
-                        (new_pat, vec![temp])
-                    }
-                    ModelPattern::Lit { binding: None, lit } => {
-                        let temp = format_ident!("_item");
-                        let new_pat = ModelPattern::Lit {
-                            binding: Some(temp.clone()),
-                            lit: lit.clone(),
-                        };
-                        (new_pat, vec![temp])
-                    }
-                    _ => (
-                        rule_arg.clone(),
-                        analysis::collect_bindings(std::slice::from_ref(rule_arg)),
-                    ),
-                };
-
-                let rule_parser = generate_pattern_step(&rule_arg_with_binding, kws)?;
-                let sep_parser = generate_pattern_step(sep_arg, kws)?;
-                let sep_peek = analysis::get_simple_peek(sep_arg, kws).ok().flatten();
+                let (rule_arg_with_binding, item_binding, push_stmt) = bind_list_item(rule_arg);
 
-                let push_stmt = if item_binding.len() == 1 {
-                    let b = &item_binding[0];
-                    quote! { _items.push(#b); }
-                } else if item_binding.is_empty() {
-                    quote! { _items.push(()); }
-                } else {
-                    let b = &item_binding;
-                    quote! { _items.push((#(#b),*)); }
-                };
+                let rule_parser = generate_pattern_step(&rule_arg_with_binding, cx)?;
+                let (sep_with_binding, sep_binding) = bind_pattern(sep_arg, "_sep");
+                let sep_parser = generate_pattern_step(&sep_with_binding, cx)?;
+                let sep_peek = cx.simple_peek(sep_arg).ok().flatten();
 
+                // Containers such as `Punctuated` keep the separators
                 let sep_logic = if let Some(peek) = sep_peek {
                     quote! {
                         if input.peek(#peek) {
                             #sep_parser
+                            rt::ListSeparator::push_separator(&mut _items, (#(#sep_binding),*));
                             true
                         } else {
                             false
@@ -227,7 +503,11 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                     }
                 } else {
                     quote! {
-                        if rt::attempt(input, ctx, |mut input, ctx| { #sep_parser Ok(()) })?.is_some() {
+                        if let Some(_sep) = rt::attempt(input, ctx, |mut input, ctx| {
+                            #sep_parser
+                            Ok((#(#sep_binding),*))
+                        })? {
+                            rt::ListSeparator::push_separator(&mut _items, _sep);
                             true
                         } else {
                             false
@@ -236,7 +516,8 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                 };
 
                 let refined_loop = quote! {
-                    let mut _items = #container_ty::new();
+                    let mut _items = { use rt::Collect as _; #container_ty::empty() };
+                    let mut _count = 0usize;
                     let mut _first = true;
                     loop {
                         if !_first {
@@ -256,6 +537,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                         if let Some(val) = _item_res {
                             let (#(#item_binding),*) = val;
                             #push_stmt
+                            _count += 1;
                             _first = false;
                         } else {
                             if !_first && !#trailing {
@@ -266,7 +548,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                             break;
                         }
                     }
-                    if _items.len() < (#min as usize) {
+                    if _count < (#min as usize) {
                         // Clear best error because we want to report logic error
                         let _ = ctx.take_best_error();
                         return Err(input.error(concat!("expected at least ", #min, " items")));
@@ -323,59 +605,22 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                     quote!(Vec)
                 };
 
-                // Inject binding if missing
-                let (rule_arg_with_binding, item_binding) = match rule_arg {
-                    ModelPattern::RuleCall {
-                        binding: None,
-                        rule_name,
-                        generics,
-                        args,
-                    } => {
-                        let temp = format_ident!("_item");
-                        let new_pat = ModelPattern::RuleCall {
-                            binding: Some(temp.clone()),
-                            rule_name: rule_name.clone(),
-                            generics: generics.clone(),
-                            args: args.clone(),
-                        };
-                        (new_pat, vec![temp])
-                    }
-                    ModelPattern::Lit { binding: None, lit } => {
-                        let temp = format_ident!("_item");
-                        let new_pat = ModelPattern::Lit {
-                            binding: Some(temp.clone()),
-                            lit: lit.clone(),
-                        };
-                        (new_pat, vec![temp])
-                    }
-                    _ => (
-                        rule_arg.clone(),
-                        analysis::collect_bindings(std::slice::from_ref(rule_arg)),
-                    ),
-                };
-
-                let rule_parser = generate_pattern_step(&rule_arg_with_binding, kws)?;
+                let (rule_arg_with_binding, item_binding, push_stmt) = bind_list_item(rule_arg);
 
-                let push_stmt = if item_binding.len() == 1 {
-                    let b = &item_binding[0];
-                    quote! { _items.push(#b); }
-                } else if item_binding.is_empty() {
-                    quote! { _items.push(()); }
-                } else {
-                    let b = &item_binding;
-                    quote! { _items.push((#(#b),*)); }
-                };
+                let rule_parser = generate_pattern_step(&rule_arg_with_binding, cx)?;
 
                 let loop_logic = quote! {
-                    let mut _items = #container_ty::new();
+                    let mut _count = 0usize;
+                    let mut _items = { use rt::Collect as _; #container_ty::empty() };
                     while let Some(val) = rt::attempt(input, ctx, |mut input, ctx| {
                         #rule_parser
                         Ok( (#(#item_binding),*) )
                     })? {
                         let (#(#item_binding),*) = val;
                         #push_stmt
+                        _count += 1;
                     }
-                     if _items.len() < (#min as usize) {
+                    if _count < (#min as usize) {
                         // Clear best error
                         let _ = ctx.take_best_error();
                         return Err(input.error(concat!("expected at least ", #min, " items")));
@@ -388,6 +633,38 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                 } else {
                     Ok(quote! { let _ = { #loop_logic }; })
                 }
+            } else if rule_name_str == "delimited" {
+                generate_delimited(binding.as_ref(), rule_name, generics, args, cx)
+            } else if rule_name_str == "terminated" && !cx.rule_names.contains(&rule_name_str) {
+                generate_terminated(binding.as_ref(), rule_name, generics, args, None, cx)
+            } else if rule_name_str == "until_parse" && !cx.rule_names.contains(&rule_name_str) {
+                generate_until_parse(binding.as_ref(), rule_name, generics, args, cx)
+            } else if rule_name_str == "map" && !cx.rule_names.contains(&rule_name_str) {
+                generate_map(binding.as_ref(), rule_name, generics, args, cx)
+            } else if rule_name_str == "parse_in" && !cx.rule_names.contains(&rule_name_str) {
+                generate_parse_in(binding.as_ref(), rule_name, generics, args, cx)
+            } else if rule_name_str == "skip" && !cx.rule_names.contains(&rule_name_str) {
+                // `skip(pattern)`: parses the pattern and discards its value; bindings are
+                // rejected by the validator
+                match args.as_slice() {
+                    [Argument::Positional(inner)] => {
+                        let steps = generate_steps(std::slice::from_ref(inner), false, cx)?;
+                        Ok(quote! { { #steps } })
+                    }
+                    _ => Err(syn::Error::new(
+                        rule_name.span(),
+                        "skip expects exactly one pattern, e.g. skip(attrs)",
+                    )),
+                }
+            } else if rule_name_str == "punct" && !cx.rule_names.contains(&rule_name_str) {
+                generate_punct(binding.as_ref(), rule_name, args)
+            } else if rule_name_str == "oneof" && !cx.rule_names.contains(&rule_name_str) {
+                generate_oneof(binding.as_ref(), rule_name, args, cx)
+            } else if INTEGER_BUILTINS.contains(&rule_name_str.as_str())
+                && !args.is_empty()
+                && !cx.rule_names.contains(&rule_name_str)
+            {
+                generate_integer(binding.as_ref(), rule_name, args)
             } else if is_builtin {
                 // Generate a token-filtering expression for the primitive.
                 let expr = match rule_name_str.as_str() {
@@ -460,6 +737,92 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                             }
                         });
                     }
+                    "position" if !cx.rule_names.contains(&rule_name_str) => {
+                        if !args.is_empty() {
+                            return Err(syn::Error::new(
+                                rule_name.span(),
+                                "position takes no arguments: position()",
+                            ));
+                        }
+                        quote! { rt::position(input) }
+                    }
+                    "outer_attrs" if !args.is_empty() => {
+                        let path = match &args[..] {
+                            [Argument::Positional(ModelPattern::Lit {
+                                lit: syn::Lit::Str(s),
+                                ..
+                            })] => s,
+                            _ => return Err(syn::Error::new(
+                                rule_name.span(),
+                                "outer_attrs expects a single attribute path: outer_attrs(\"doc\")",
+                            )),
+                        };
+                        quote! { parse_outer_attrs_matching_impl(&mut input, ctx, #path)? }
+                    }
+                    "balanced_group" if !args.is_empty() => {
+                        let open = match &args[..] {
+                            [Argument::Positional(ModelPattern::Lit {
+                                lit: syn::Lit::Str(s),
+                                ..
+                            })] if matches!(s.value().as_str(), "(" | "[" | "{") => s,
+                            _ => return Err(syn::Error::new(
+                                rule_name.span(),
+                                "balanced_group expects an opening delimiter: balanced_group(\"{\")",
+                            )),
+                        };
+                        quote! { parse_balanced_group_matching_impl(&mut input, ctx, #open)? }
+                    }
+                    "keyword" if !cx.rule_names.contains(&rule_name_str) => {
+                        let word = match &args[..] {
+                            [Argument::Positional(ModelPattern::Lit {
+                                lit: syn::Lit::Str(s),
+                                ..
+                            })] if syn::parse::Parser::parse_str(
+                                <syn::Ident as syn::ext::IdentExt>::parse_any,
+                                &s.value(),
+                            )
+                            .is_ok() =>
+                            {
+                                s
+                            }
+                            _ => {
+                                return Err(syn::Error::new(
+                                    rule_name.span(),
+                                    "keyword expects a single word: keyword(\"match\")",
+                                ))
+                            }
+                        };
+                        quote! { rt::parse_keyword(input, #word)? }
+                    }
+                    "scan" if !cx.rule_names.contains(&rule_name_str) => {
+                        let scanner =
+                            match args.first() {
+                                Some(Argument::Positional(ModelPattern::RuleCall {
+                                    binding: None,
+                                    module: None,
+                                    rule_name: scanner,
+                                    args: scanner_args,
+                                    ..
+                                })) if scanner_args.is_empty() => scanner,
+                                _ => return Err(syn::Error::new(
+                                    rule_name.span(),
+                                    "scan expects the name of a scanner function: scan(my_scanner)",
+                                )),
+                            };
+                        let expected =
+                            match args.get(1) {
+                                Some(Argument::Positional(ModelPattern::Lit {
+                                    lit: syn::Lit::Str(s),
+                                    ..
+                                })) => s.value(),
+                                None => scanner.to_string(),
+                                Some(_) => return Err(syn::Error::new(
+                                    rule_name.span(),
+                                    "scan expects a string literal describing the expected input",
+                                )),
+                            };
+                        quote! { rt::scan(input, ctx, #expected, #scanner)? }
+                    }
                     "whitespace" => {
                         return Ok(quote! {
                             if !ctx.check_whitespace(input.span()) {
@@ -467,6 +830,36 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                             }
                         });
                     }
+                    "newline" | "indent" | "dedent" => {
+                        if !cx.layout {
+                            return Err(syn::Error::new(
+                                rule_name.span(),
+                                format!(
+                                    "`{}` requires `options {{ layout = significant; }}`",
+                                    rule_name
+                                ),
+                            ));
+                        }
+                        if !args.is_empty() {
+                            return Err(syn::Error::new(
+                                rule_name.span(),
+                                format!("{} takes no arguments", rule_name),
+                            ));
+                        }
+                        let f = format_ident!("layout_{}", rule_name);
+                        return Ok(quote! { rt::#f(input, ctx)?; });
+                    }
+                    // Words reserved with `keywords { .. }` are not identifiers in this rule
+                    "ident" if !cx.reserved.is_empty() => {
+                        let reserved = &cx.reserved;
+                        let func_call = generate_rule_call_expr(rule_name, args);
+                        quote! {
+                            {
+                                rt::reject_reserved(input, &[#(#reserved),*])?;
+                                #func_call
+                            }
+                        }
+                    }
                     // Defer to built-in rules for high-level primitives like "ident", "integer", "float"
                     _ => {
                         let func_call = generate_rule_call_expr(rule_name, args);
@@ -490,205 +883,49 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
             }
         }
 
-        ModelPattern::Repeat(inner, _) => {
-            let bindings = analysis::collect_bindings(std::slice::from_ref(inner));
-
-            if !bindings.is_empty() {
-                // Use temporary names for vectors to avoid shadowing by inner bindings
-                let vec_names: Vec<_> = bindings
-                    .iter()
-                    .map(|b| format_ident!("_vec_{}", b))
-                    .collect();
-
-                let init_vecs: Vec<_> = vec_names
-                    .iter()
-                    .map(|v| quote!(let mut #v = Vec::new();))
-                    .collect();
-                let push_vecs: Vec<_> = vec_names
-                    .iter()
-                    .zip(bindings.iter())
-                    .map(|(v, b)| quote!(#v.push(#b);))
-                    .collect();
-                let finalize_vecs: Vec<_> = bindings
-                    .iter()
-                    .zip(vec_names.iter())
-                    .map(|(b, v)| quote!(let #b = #v;))
-                    .collect();
-
-                let inner_logic = generate_pattern_step(inner, kws)?;
-
-                // Only use peek optimization if it's safe and unambiguous
-                let peek_opt = analysis::get_simple_peek(inner, kws).ok().flatten();
-
-                if let Some(peek) = peek_opt {
-                    Ok(quote! {
-                       #(#init_vecs)*
-                       while input.peek(#peek) {
-                           {
-                               #inner_logic
-                               #(#push_vecs)*
-                           }
-                       }
-                       #(#finalize_vecs)*
-                    })
-                } else {
-                    let return_tuple = quote!(( #(#bindings),* ));
-                    let tuple_pat = quote!(( #(#bindings),* ));
-
-                    Ok(quote! {
-                       #(#init_vecs)*
-                       // Pass ctx to attempt
-                       while let Some(vals) = rt::attempt(input, ctx, |mut input, ctx| {
-                           #inner_logic
-                           Ok(#return_tuple)
-                       })? {
-                           let #tuple_pat = vals;
-                           #(#push_vecs)*
-                       }
-                       #(#finalize_vecs)*
-                    })
-                }
-            } else {
-                let inner_logic = generate_pattern_step(inner, kws)?;
-                Ok(quote! {
-                    // Pass ctx to attempt
-                    while let Some(_) = rt::attempt(input, ctx, |mut input, ctx| { #inner_logic Ok(()) })? {}
-                })
-            }
-        }
-
-        ModelPattern::Plus(inner, _) => {
-            let bindings = analysis::collect_bindings(std::slice::from_ref(inner));
-
-            if !bindings.is_empty() {
-                // Use temporary names for vectors to avoid shadowing by inner bindings
-                let vec_names: Vec<_> = bindings
-                    .iter()
-                    .map(|b| format_ident!("_vec_{}", b))
-                    .collect();
-
-                let init_vecs: Vec<_> = vec_names
-                    .iter()
-                    .map(|v| quote!(let mut #v = Vec::new();))
-                    .collect();
-                let push_vecs: Vec<_> = vec_names
-                    .iter()
-                    .zip(bindings.iter())
-                    .map(|(v, b)| quote!(#v.push(#b);))
-                    .collect();
-                let finalize_vecs: Vec<_> = bindings
-                    .iter()
-                    .zip(vec_names.iter())
-                    .map(|(b, v)| quote!(let #b = #v;))
-                    .collect();
-
-                let inner_logic = generate_pattern_step(inner, kws)?;
-                let peek_opt = analysis::get_simple_peek(inner, kws).ok().flatten();
-
-                if let Some(peek) = peek_opt {
-                    Ok(quote! {
-                       #(#init_vecs)*
-                       {
-                           #inner_logic
-                           #(#push_vecs)*
-                       }
-                       while input.peek(#peek) {
-                           {
-                               #inner_logic
-                               #(#push_vecs)*
-                           }
-                       }
-                       #(#finalize_vecs)*
-                    })
-                } else {
-                    let return_tuple = quote!(( #(#bindings),* ));
-                    let tuple_pat = quote!(( #(#bindings),* ));
-
-                    Ok(quote! {
-                       #(#init_vecs)*
-                       {
-                           #inner_logic
-                           #(#push_vecs)*
-                       }
-                       // Pass ctx to attempt
-                       while let Some(vals) = rt::attempt(input, ctx, |mut input, ctx| {
-                           #inner_logic
-                           Ok(#return_tuple)
-                       })? {
-                           let #tuple_pat = vals;
-                           #(#push_vecs)*
-                       }
-                       #(#finalize_vecs)*
-                    })
-                }
-            } else {
-                let inner_logic = generate_pattern_step(inner, kws)?;
-                Ok(quote! {
-                    #inner_logic
-                    // Pass ctx to attempt
-                    while let Some(_) = rt::attempt(input, ctx, |mut input, ctx| { #inner_logic Ok(()) })? {}
-                })
-            }
-        }
+        ModelPattern::Repeat(inner, _) => generate_repetition(inner, (0, None), None, cx),
+        ModelPattern::Plus(inner, _) => generate_repetition(inner, (1, None), None, cx),
+        ModelPattern::Bounded {
+            pattern, min, max, ..
+        } => generate_repetition(pattern, (*min, *max), None, cx),
 
         ModelPattern::Optional(inner, _) => {
-            let inner_logic = generate_pattern_step(inner, kws)?;
-            let peek_opt = analysis::get_simple_peek(inner, kws).ok().flatten();
-            let is_nullable = analysis::is_nullable(inner);
+            let inner_logic = generate_pattern_step(inner, cx)?;
+            let peek_cond = match cx.simple_peek(inner).ok().flatten() {
+                Some(peek) if !analysis::is_nullable(inner) => Some(cx.peek_cond(&peek)),
+                _ => cx.first_set_peek(inner, None),
+            };
 
             let bindings = analysis::collect_bindings(std::slice::from_ref(inner));
+            let value = quote!((#(#bindings),*));
 
-            if let (Some(peek), false) = (peek_opt, is_nullable) {
-                if bindings.is_empty() {
-                    Ok(quote! {
-                        if input.peek(#peek) {
-                            // Pass ctx to attempt
-                            let _ = rt::attempt(input, ctx, |mut input, ctx| { #inner_logic Ok(()) })?;
-                        }
-                    })
-                } else {
-                    // For optional binding, we need to return Option<T>
-                    let vars: Vec<_> = bindings.iter().map(|b| quote!(#b)).collect();
-                    let some_vars: Vec<_> = bindings.iter().map(|b| quote!(Some(#b))).collect();
-                    let none_vars: Vec<_> = bindings.iter().map(|_| quote!(None)).collect();
+            // Parse the inner pattern once, producing a single `Option<(A, B, ..)>`
+            let attempt = quote! {
+                // Pass ctx to attempt
+                rt::attempt(input, ctx, |mut input, ctx| { #inner_logic Ok(#value) })?
+            };
+            let parsed = match peek_cond {
+                Some(cond) => quote!(if #cond { #attempt } else { None }),
+                None => attempt,
+            };
 
+            match bindings.len() {
+                0 => Ok(quote! { let _ = #parsed; }),
+                1 => {
+                    let bind = &bindings[0];
+                    Ok(quote! { let #bind = #parsed; })
+                }
+                _ => {
+                    // Destructure into one `Option` per binding for the action scope
+                    let some_vars = bindings.iter().map(|b| quote!(Some(#b)));
+                    let none_vars = bindings.iter().map(|_| quote!(None));
                     Ok(quote! {
-                        let (#(#vars),*) = if input.peek(#peek) {
-                            if let Some(vals) = rt::attempt(input, ctx, |mut input, ctx| {
-                                #inner_logic
-                                Ok((#(#vars),*))
-                            })? {
-                                let (#(#vars),*) = vals;
-                                (#(#some_vars),*)
-                            } else {
-                                (#(#none_vars),*)
-                            }
-                        } else {
-                            (#(#none_vars),*)
+                        let (#(#bindings),*) = match #parsed {
+                            Some(#value) => (#(#some_vars),*),
+                            None => (#(#none_vars),*),
                         };
                     })
                 }
-            } else if bindings.is_empty() {
-                Ok(quote! {
-                    // Pass ctx to attempt
-                    let _ = rt::attempt(input, ctx, |mut input, ctx| { #inner_logic Ok(()) })?;
-                })
-            } else {
-                let vars: Vec<_> = bindings.iter().map(|b| quote!(#b)).collect();
-                let some_vars: Vec<_> = bindings.iter().map(|b| quote!(Some(#b))).collect();
-                let none_vars: Vec<_> = bindings.iter().map(|_| quote!(None)).collect();
-
-                Ok(quote! {
-                    let (#(#vars),*) = if let Some(vals) = rt::attempt(input, ctx, |mut input, ctx| {
-                            #inner_logic
-                            Ok((#(#vars),*))
-                    })? {
-                        let (#(#vars),*) = vals;
-                        (#(#some_vars),*)
-                    } else {
-                        (#(#none_vars),*)
-                    };
-                })
             }
         }
         ModelPattern::Group(alts, _) => {
@@ -704,14 +941,16 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                         quote!(( #(#bindings),* ))
                     };
                     RuleVariant {
+                        attrs: Vec::new(),
                         pattern: pat_seq.clone(),
                         label: label.clone(), // Pass label
+                        guard: None,
                         action: quote!({ #action_expr }),
                     }
                 })
                 .collect::<Vec<_>>();
 
-            let variant_logic = generate_variants_internal(&temp_variants, false, kws)?;
+            let variant_logic = generate_variants_internal(&temp_variants, false, cx)?;
             let group_bindings = analysis::collect_bindings(std::slice::from_ref(pattern));
 
             let wrapped_logic = quote! {
@@ -739,7 +978,10 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                 _ => quote!(parenthesized),
             };
 
-            let inner_logic = generate_sequence_steps(s, kws)?;
+            let mut inner_logic = generate_steps(s, true, cx)?;
+            if analysis::uses_follows(s) {
+                inner_logic = quote! { let _lookbehind_start = input.cursor(); #inner_logic };
+            }
             let bindings = analysis::collect_bindings(s);
 
             if bindings.is_empty() {
@@ -782,6 +1024,11 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
             }
         }
 
+        ModelPattern::Collect(_, _, span) => Err(syn::Error::new(
+            *span,
+            "`#[collect]` only applies to bindings inside a repetition (`*` or `+`)",
+        )),
+
         ModelPattern::SpanBinding(inner, span_var, _) => {
             let (inner_pat, binding_name) = match &**inner {
                 ModelPattern::RuleCall {
@@ -789,6 +1036,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                     rule_name,
                     generics,
                     args,
+                    ..
                 } => {
                     if let Some(b) = binding {
                         (inner.clone(), b.clone())
@@ -796,6 +1044,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                         let temp = format_ident!("_val_{}", span_var);
                         let new_inner = ModelPattern::RuleCall {
                             binding: Some(temp.clone()),
+                            module: None,
                             rule_name: rule_name.clone(),
                             generics: generics.clone(),
                             args: args.clone(),
@@ -842,7 +1091,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                 }
             };
 
-            let inner_code = generate_pattern_step(&inner_pat, kws)?;
+            let inner_code = generate_pattern_step(&inner_pat, cx)?;
 
             Ok(quote! {
                 #inner_code
@@ -850,6 +1099,16 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
             })
         }
 
+        ModelPattern::Raw(inner, binding, _) => {
+            let inner_code = generate_pattern_step(inner, cx)?;
+            let start = format_ident!("_raw_start_{}", binding);
+            Ok(quote! {
+                let #start = input.cursor();
+                #inner_code
+                let #binding = rt::tokens_between(#start, input.cursor());
+            })
+        }
+
         ModelPattern::Recover {
             binding,
             body,
@@ -863,8 +1122,10 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                         rule_name,
                         generics,
                         args,
+                        ..
                     } => Box::new(ModelPattern::RuleCall {
                         binding: Some(bind.clone()),
+                        module: None,
                         rule_name: rule_name.clone(),
                         generics: generics.clone(),
                         args: args.clone(),
@@ -877,8 +1138,8 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                 body.clone()
             };
 
-            let inner_logic = generate_pattern_step(&effective_body, kws)?;
-            let sync_peek = analysis::get_simple_peek(sync, kws)?.ok_or_else(|| {
+            let inner_logic = generate_pattern_step(&effective_body, cx)?;
+            let sync_peek = cx.simple_peek(sync)?.ok_or_else(|| {
                 syn::Error::new(
                     sync.span(),
                     "Sync pattern in recover(...) must have a simple start token.",
@@ -894,7 +1155,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                 Ok(quote! {
                     // Pass ctx to attempt_recover
                     if rt::attempt_recover(input, ctx, |mut input, ctx| { #inner_logic Ok(()) })?.is_none() {
-                        rt::skip_until(input, |i| i.peek(#sync_peek))?;
+                        rt::skip_until(input, |input| input.peek(#sync_peek))?;
                     }
                 })
             } else {
@@ -912,7 +1173,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                                 Some(#(#bindings),*)
                             },
                             None => {
-                                rt::skip_until(input, |i| i.peek(#sync_peek))?;
+                                rt::skip_until(input, |input| input.peek(#sync_peek))?;
                                 None
                             }
                         };
@@ -929,7 +1190,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                                 (#(Some(#bindings)),*)
                             },
                             None => {
-                                rt::skip_until(input, |i| i.peek(#sync_peek))?;
+                                rt::skip_until(input, |input| input.peek(#sync_peek))?;
                                 (#(#none_exprs),*)
                             }
                         };
@@ -938,9 +1199,32 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
             }
         }
 
+        ModelPattern::Expected(inner, label, _) => {
+            let bindings = analysis::collect_bindings(std::slice::from_ref(inner));
+            let inner_logic = generate_pattern_step(inner, cx)?;
+            let label = label.value();
+            Ok(quote! {
+                let (#(#bindings),*) = rt::expected(input, ctx, #label, |mut input, ctx| {
+                    #inner_logic
+                    Ok((#(#bindings),*))
+                })?;
+            })
+        }
+
+        ModelPattern::Commit(inner, _) => {
+            let bindings = analysis::collect_bindings(std::slice::from_ref(inner));
+            let inner_logic = generate_pattern_step(inner, cx)?;
+            Ok(quote! {
+                let (#(#bindings),*) = rt::commit(input, ctx, |mut input, ctx| {
+                    #inner_logic
+                    Ok((#(#bindings),*))
+                })?;
+            })
+        }
+
         ModelPattern::Peek(inner, _) => {
             let bindings = analysis::collect_bindings(std::slice::from_ref(inner));
-            let inner_logic = generate_pattern_step(inner, kws)?;
+            let inner_logic = generate_pattern_step(inner, cx)?;
 
             if bindings.is_empty() {
                 Ok(quote! {
@@ -964,7 +1248,7 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
 
         ModelPattern::Not(inner, _) => {
             // Not does not export bindings.
-            let inner_logic = generate_pattern_step(inner, kws)?;
+            let inner_logic = generate_pattern_step(inner, cx)?;
             Ok(quote! {
                 rt::not_check(input, ctx, |mut input, ctx| {
                     #inner_logic
@@ -973,10 +1257,47 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
             })
         }
 
+        ModelPattern::Follows(inner, _) => {
+            // Follows does not export bindings. It checks the tokens consumed since the start
+            // of the rule or delimited group.
+            let inner_logic = generate_pattern_step(inner, cx)?;
+            Ok(quote! {
+                rt::follows(_lookbehind_start, input, ctx, |mut input, ctx| {
+                    #inner_logic
+                    Ok(())
+                })?;
+            })
+        }
+
+        ModelPattern::Declare(inner, _) | ModelPattern::Resolve(inner, _) => {
+            // The bindings of the pattern stay visible, so it is not wrapped in a block.
+            let inner_logic = generate_pattern_step(inner, cx)?;
+            let check = match pattern {
+                ModelPattern::Declare(..) => quote!(rt::declare_symbol(input, ctx, _symbol_start);),
+                _ => quote!(rt::resolve_symbol(input, ctx, _symbol_start)?;),
+            };
+            Ok(quote! {
+                let _symbol_start = input.cursor();
+                #inner_logic
+                #check
+            })
+        }
+
+        ModelPattern::Scope(seq, _) => {
+            // A scope left by an error is discarded when the enclosing attempt rolls back the
+            // symbol table, so only the successful path exits it.
+            let inner_logic = generate_steps(seq, false, cx)?;
+            Ok(quote! {
+                ctx.enter_scope();
+                #inner_logic
+                ctx.exit_scope();
+            })
+        }
+
         ModelPattern::Until {
             binding, pattern, ..
         } => {
-            let inner_logic = generate_pattern_step(pattern, kws)?;
+            let inner_logic = generate_pattern_step(pattern, cx)?;
 
             let loop_body = quote! {
                 let mut _tokens = Vec::new();
@@ -1002,6 +1323,931 @@ fn generate_pattern_step(pattern: &ModelPattern, kws: &HashSet<String>) -> Resul
                 Ok(quote! { let _ = { #loop_body }; })
             }
         }
+
+        ModelPattern::Fold {
+            binding,
+            fixity,
+            item,
+            op,
+            params,
+            action,
+            ..
+        } => generate_fold(binding.as_ref(), fixity, item, op, params, action, cx),
+    }
+}
+
+/// `fold_left(item, op, |l, op, r| ..)` and `fold_right(..)`: one or more items separated
+/// by operators, combined by the action from the left or from the right.
+///
+/// An operator is only parsed if it can be peeked or parsed speculatively, but once it has
+/// been parsed the item after it is required.
+fn generate_fold(
+    binding: Option<&syn::Ident>,
+    fixity: &Fixity,
+    item: &ModelPattern,
+    op: &ModelPattern,
+    params: &[syn::Pat],
+    action: &TokenStream,
+    cx: &Context,
+) -> Result<TokenStream> {
+    let (item_with_binding, item_bindings) = bind_pattern(item, "_item");
+    let (op_with_binding, op_bindings) = bind_pattern(op, "_op");
+    let item_parser = generate_pattern_step(&item_with_binding, cx)?;
+    let op_parser = generate_pattern_step(&op_with_binding, cx)?;
+    let item_value = quote! { { #item_parser (#(#item_bindings),*) } };
+    let op_value = quote! { (#(#op_bindings),*) };
+
+    let next_op = match cx.simple_peek(op).ok().flatten() {
+        Some(peek) if !analysis::is_nullable(op) => {
+            let cond = cx.peek_cond(&peek);
+            quote! {
+                if !(#cond) {
+                    break;
+                }
+                let _op = { #op_parser #op_value };
+            }
+        }
+        _ => quote! {
+            let Some(_op) = rt::attempt(input, ctx, |mut input, ctx| {
+                #op_parser
+                Ok(#op_value)
+            })? else {
+                break;
+            };
+        },
+    };
+
+    let (p_l, p_op, p_r) = (&params[0], &params[1], &params[2]);
+    let fold = match fixity {
+        Fixity::Right => quote! {
+            // Operands wait for the rest of the chain, which is combined first
+            let mut _pending = Vec::new();
+            let mut _acc = #item_value;
+            loop {
+                #next_op
+                let _rhs = #item_value;
+                _pending.push((_acc, _op));
+                _acc = _rhs;
+            }
+            while let Some((_lhs, _op)) = _pending.pop() {
+                _acc = {
+                    let #p_l = _lhs;
+                    let #p_op = _op;
+                    let #p_r = _acc;
+                    #action
+                };
+            }
+            _acc
+        },
+        _ => quote! {
+            let mut _acc = #item_value;
+            loop {
+                #next_op
+                let _rhs = #item_value;
+                _acc = {
+                    let #p_l = _acc;
+                    let #p_op = _op;
+                    let #p_r = _rhs;
+                    #action
+                };
+            }
+            _acc
+        },
+    };
+
+    match binding {
+        Some(bind) => Ok(quote! { let #bind = { #fold }; }),
+        None => Ok(quote! { let _ = { #fold }; }),
+    }
+}
+
+/// Injects the binding `temp` into `pattern` if it is a rule call or literal without one.
+/// Returns the pattern and its bindings.
+fn bind_pattern(pattern: &ModelPattern, temp: &str) -> (ModelPattern, Vec<syn::Ident>) {
+    match pattern {
+        ModelPattern::RuleCall {
+            binding: None,
+            rule_name,
+            generics,
+            args,
+            ..
+        } => {
+            let temp = format_ident!("{}", temp);
+            let new_pat = ModelPattern::RuleCall {
+                binding: Some(temp.clone()),
+                module: None,
+                rule_name: rule_name.clone(),
+                generics: generics.clone(),
+                args: args.clone(),
+            };
+            (new_pat, vec![temp])
+        }
+        ModelPattern::Lit { binding: None, lit } => {
+            let temp = format_ident!("{}", temp);
+            let new_pat = ModelPattern::Lit {
+                binding: Some(temp.clone()),
+                lit: lit.clone(),
+            };
+            (new_pat, vec![temp])
+        }
+        _ => (
+            pattern.clone(),
+            analysis::collect_bindings(std::slice::from_ref(pattern)),
+        ),
+    }
+}
+
+/// `"select"i`: an identifier spelled like the word in any case, bound as the `syn::Ident` of the
+/// input.
+fn generate_case_insensitive_lit(
+    binding: Option<&syn::Ident>,
+    lit: &syn::LitStr,
+) -> Result<TokenStream> {
+    let word = lit.value();
+    if word == "_"
+        || syn::parse::Parser::parse_str(<syn::Ident as syn::ext::IdentExt>::parse_any, &word)
+            .is_err()
+    {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!(
+                "Case-insensitive literal \"{}\"i must be a single word",
+                word
+            ),
+        ));
+    }
+    let bind = binding.cloned().unwrap_or_else(|| format_ident!("_t"));
+    Ok(quote! {
+        let #bind = rt::parse_ident_ci(input, #word)?;
+        ctx.record_span(#bind.span());
+    })
+}
+
+/// Injects a binding into the item pattern of a list built-in if it has none.
+/// Returns the pattern, its bindings and the statement pushing them onto `_items`.
+fn bind_list_item(rule_arg: &ModelPattern) -> (ModelPattern, Vec<syn::Ident>, TokenStream) {
+    let (rule_arg_with_binding, item_binding) = bind_pattern(rule_arg, "_item");
+
+    let b = &item_binding;
+    let push_stmt = quote! { rt::Collect::push(&mut _items, (#(#b),*)); };
+
+    (rule_arg_with_binding, item_binding, push_stmt)
+}
+
+/// `delimited(open, item, sep, close, recover=false)`: a possibly empty, separated list
+/// between two delimiters, allowing a trailing separator.
+///
+/// Bracket, paren and brace delimiters (`"["`, `"("`, `"{"`) parse a delimited group, any
+/// other delimiters are parsed as tokens. The result is an `rt::Delimited` holding the items
+/// and the spans of both delimiters. With `recover=true`, a malformed item is recorded as an
+/// error and the rest of the list is skipped up to the closing delimiter.
+fn generate_delimited(
+    binding: Option<&syn::Ident>,
+    rule_name: &syn::Ident,
+    generics: &[syn::Type],
+    args: &[Argument],
+    cx: &Context,
+) -> Result<TokenStream> {
+    let positional: Vec<&ModelPattern> = args
+        .iter()
+        .filter_map(|a| match a {
+            Argument::Positional(p) => Some(p),
+            Argument::Named(..) => None,
+        })
+        .collect();
+    let [open, item, sep, close] = positional[..] else {
+        return Err(syn::Error::new(
+            rule_name.span(),
+            "delimited requires 4 arguments: (open, item, separator, close)",
+        ));
+    };
+
+    let mut recover = false;
+    for arg in args {
+        if let Argument::Named(id, val) = arg {
+            match val {
+                ModelPattern::Lit {
+                    lit: Lit::Bool(b), ..
+                } if id == "recover" => recover = b.value,
+                _ => {
+                    return Err(syn::Error::new(
+                        id.span(),
+                        format!("Unknown argument '{}' for delimited", id),
+                    ))
+                }
+            }
+        }
+    }
+
+    let delimiter = |p: &ModelPattern| match p {
+        ModelPattern::Lit {
+            binding: None,
+            lit: Lit::Str(s),
+        } => Ok(s.clone()),
+        _ => Err(syn::Error::new(
+            p.span(),
+            "delimited expects string literals as delimiters",
+        )),
+    };
+    let (open_lit, close_lit) = (delimiter(open)?, delimiter(close)?);
+
+    let container_ty = match generics.first() {
+        Some(ty) => quote!(#ty),
+        None => quote!(Vec),
+    };
+
+    let (item_with_binding, _, push_stmt) = bind_list_item(item);
+    let item_parser = generate_pattern_step(&item_with_binding, cx)?;
+    let (sep_with_binding, sep_binding) = bind_pattern(sep, "_sep");
+    let sep_parser = generate_pattern_step(&sep_with_binding, cx)?;
+    let sep_peek = cx.simple_peek(sep)?.ok_or_else(|| {
+        syn::Error::new(
+            sep.span(),
+            "delimited expects a separator that can be peeked",
+        )
+    })?;
+
+    let group_macro = match (open_lit.value().as_str(), close_lit.value().as_str()) {
+        ("[", "]") => Some(quote!(bracketed)),
+        ("(", ")") => Some(quote!(parenthesized)),
+        ("{", "}") => Some(quote!(braced)),
+        (o, c) if matches!(o, "[" | "(" | "{") || matches!(c, "]" | ")" | "}") => {
+            return Err(syn::Error::new(
+                open.span(),
+                format!("Mismatched delimiters '{}' and '{}'", o, c),
+            ));
+        }
+        _ => None,
+    };
+
+    // The end of the list: the end of the group, or the closing token
+    let at_end = match group_macro {
+        Some(_) => quote!(input.is_empty()),
+        None => {
+            let close_peek = cx.simple_peek(close)?.ok_or_else(|| {
+                syn::Error::new(
+                    close.span(),
+                    "delimited expects a closing token that can be peeked",
+                )
+            })?;
+            quote!(input.peek(#close_peek))
+        }
+    };
+    let end_msg = match sep {
+        ModelPattern::Lit {
+            lit: Lit::Str(s), ..
+        } => format!("expected `{}` or `{}`", s.value(), close_lit.value()),
+        _ => format!("expected separator or `{}`", close_lit.value()),
+    };
+
+    let list_loop = quote! {
+        loop {
+            if #at_end {
+                break;
+            }
+            #item_parser
+            #push_stmt
+            if input.peek(#sep_peek) {
+                #sep_parser
+                rt::ListSeparator::push_separator(&mut _items, (#(#sep_binding),*));
+            } else {
+                break;
+            }
+        }
+        if !(#at_end) {
+            return Err(input.error(#end_msg));
+        }
+    };
+
+    let list = if recover {
+        quote! {
+            let mut _items = { use rt::Collect as _; #container_ty::empty() };
+            if rt::attempt_recover(input, ctx, |mut input, ctx| {
+                #list_loop
+                Ok(())
+            })?
+            .is_none()
+            {
+                rt::skip_until(input, |input| #at_end)?;
+            }
+        }
+    } else {
+        quote! {
+            let mut _items = { use rt::Collect as _; #container_ty::empty() };
+            #list_loop
+        }
+    };
+
+    let value = match group_macro {
+        Some(group_macro) => quote! {
+            {
+                let content;
+                let _delim = syn::#group_macro!(content in input);
+                let mut input = &content;
+                #list
+                rt::Delimited {
+                    items: _items,
+                    open: _delim.span.open(),
+                    close: _delim.span.close(),
+                }
+            }
+        },
+        None => {
+            let open_parser = generate_pattern_step(open, cx)?;
+            let close_parser = generate_pattern_step(close, cx)?;
+            quote! {
+                {
+                    let _open = input.span();
+                    #open_parser
+                    #list
+                    let _close = input.span();
+                    #close_parser
+                    rt::Delimited {
+                        items: _items,
+                        open: _open,
+                        close: _close,
+                    }
+                }
+            }
+        }
+    };
+
+    match binding {
+        Some(bind) => Ok(quote! { let #bind = #value; }),
+        None => Ok(quote! { let _ = #value; }),
+    }
+}
+
+/// `until_parse(item, stop)`: parses `item` repeatedly until `stop` matches or the input ends,
+/// collecting the values into a container like the list built-ins. `stop` is only peeked, not
+/// consumed. Every item up to it is required, so an item that fails reports its own error.
+fn generate_until_parse(
+    binding: Option<&syn::Ident>,
+    rule_name: &syn::Ident,
+    generics: &[syn::Type],
+    args: &[Argument],
+    cx: &Context,
+) -> Result<TokenStream> {
+    let [Argument::Positional(item), Argument::Positional(stop)] = args else {
+        return Err(syn::Error::new(
+            rule_name.span(),
+            "until_parse requires 2 arguments: (rule, stop), e.g. until_parse(stmt, \"end\")",
+        ));
+    };
+    let container_ty = match generics.first() {
+        Some(ty) => quote!(#ty),
+        None => quote!(Vec),
+    };
+
+    let (item_with_binding, item_binding, push_stmt) = bind_list_item(item);
+    let item_parser = generate_pattern_step(&item_with_binding, cx)?;
+    let stop_logic = generate_pattern_step(stop, cx)?;
+    let at_stop = match cx.simple_peek(stop).ok().flatten() {
+        Some(peek) if !analysis::is_nullable(stop) => cx.peek_cond(&peek),
+        _ => quote! {
+            rt::peek(input, ctx, |mut input, ctx| {
+                #stop_logic
+                Ok(())
+            })
+            .is_ok()
+        },
+    };
+
+    let list = quote! {
+        {
+            let mut _items = { use rt::Collect as _; #container_ty::empty() };
+            let mut _rep_cursor = input.cursor();
+            while !input.is_empty() && !(#at_stop) {
+                let (#(#item_binding),*) = {
+                    #item_parser
+                    (#(#item_binding),*)
+                };
+                #push_stmt
+                if _rep_cursor == input.cursor() {
+                    return Err(input.error("until_parse item matched empty input (infinite loop detected)"));
+                }
+                _rep_cursor = input.cursor();
+            }
+            _items
+        }
+    };
+
+    Ok(match binding {
+        Some(bind) => quote! { let #bind = #list; },
+        None => quote! { let _ = #list; },
+    })
+}
+
+/// `terminated(item, term, required=true)`: a possibly empty list of items, each followed by the
+/// terminator `term`.
+///
+/// `required=false` makes every terminator optional. `required=auto` only allows omitting the
+/// terminator of the last item before the end of the enclosing group or input, like the last
+/// statement of a block. If `follow` shows that the list is followed by patterns that must
+/// consume input, that end cannot be reached and the terminator is always required.
+fn generate_terminated(
+    binding: Option<&syn::Ident>,
+    rule_name: &syn::Ident,
+    generics: &[syn::Type],
+    args: &[Argument],
+    follow: Option<(&[ModelPattern], bool)>,
+    cx: &Context,
+) -> Result<TokenStream> {
+    let positional: Vec<&ModelPattern> = args
+        .iter()
+        .filter_map(|a| match a {
+            Argument::Positional(p) => Some(p),
+            Argument::Named(..) => None,
+        })
+        .collect();
+    let [item, term] = positional[..] else {
+        return Err(syn::Error::new(
+            rule_name.span(),
+            "terminated requires 2 arguments: (rule, terminator)",
+        ));
+    };
+
+    enum Required {
+        Always,
+        Never,
+        Auto,
+    }
+    let mut required = Required::Always;
+    for arg in args {
+        if let Argument::Named(id, val) = arg {
+            required = match val {
+                ModelPattern::Lit {
+                    lit: Lit::Bool(b), ..
+                } if id == "required" => match b.value {
+                    true => Required::Always,
+                    false => Required::Never,
+                },
+                ModelPattern::RuleCall {
+                    binding: None,
+                    module: None,
+                    rule_name: value,
+                    args,
+                    ..
+                } if id == "required" && value == "auto" && args.is_empty() => Required::Auto,
+                _ if id == "required" => {
+                    return Err(syn::Error::new(
+                        val.span(),
+                        "required expects `true`, `false` or `auto`",
+                    ))
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        id.span(),
+                        format!("Unknown argument '{}' for terminated", id),
+                    ))
+                }
+            };
+        }
+    }
+    // Followed by input that must be consumed, the list never ends at the end of the input
+    if let (Required::Auto, Some((rest, _))) = (&required, follow) {
+        if !cx.analysis.is_sequence_nullable(rest) {
+            required = Required::Always;
+        }
+    }
+
+    let container_ty = match generics.first() {
+        Some(ty) => quote!(#ty),
+        None => quote!(Vec),
+    };
+
+    let (item_with_binding, item_binding, push_stmt) = bind_list_item(item);
+    let item_parser = generate_pattern_step(&item_with_binding, cx)?;
+    let (term_with_binding, term_binding) = bind_pattern(term, "_term");
+    let term_parser = generate_pattern_step(&term_with_binding, cx)?;
+    let term_parser = quote! {
+        #term_parser
+        rt::ListSeparator::push_separator(&mut _items, (#(#term_binding),*));
+    };
+    let term_peek = cx.simple_peek(term)?.ok_or_else(|| {
+        syn::Error::new(
+            term.span(),
+            "terminated expects a terminator that can be peeked",
+        )
+    })?;
+
+    let term_logic = match required {
+        Required::Always => quote!(#term_parser),
+        Required::Never => quote! {
+            if input.peek(#term_peek) {
+                #term_parser
+            }
+        },
+        Required::Auto => quote! {
+            if !input.is_empty() {
+                #term_parser
+            }
+        },
+    };
+
+    let list = quote! {
+        {
+            let mut _items = { use rt::Collect as _; #container_ty::empty() };
+            while let Some(val) = rt::attempt(input, ctx, |mut input, ctx| {
+                #item_parser
+                Ok( (#(#item_binding),*) )
+            })? {
+                let (#(#item_binding),*) = val;
+                #push_stmt
+                #term_logic
+            }
+            _items
+        }
+    };
+
+    Ok(match binding {
+        Some(bind) => quote! { let #bind = #list; },
+        None => quote! { let _ = #list; },
+    })
+}
+
+/// `map(key, assoc, value, sep=",", trailing=false, unique=false)`: a possibly empty list of
+/// `key assoc value` entries separated by `sep`.
+///
+/// The result is a `Vec<(K, V)>`, or the map type given as generic argument
+/// (`map<HashMap>(...)`). With `unique=true`, a key equal to an earlier one fails the parse with
+/// an error at the duplicate that points back to the first definition.
+fn generate_map(
+    binding: Option<&syn::Ident>,
+    rule_name: &syn::Ident,
+    generics: &[syn::Type],
+    args: &[Argument],
+    cx: &Context,
+) -> Result<TokenStream> {
+    let positional: Vec<&ModelPattern> = args
+        .iter()
+        .filter_map(|a| match a {
+            Argument::Positional(p) => Some(p),
+            Argument::Named(..) => None,
+        })
+        .collect();
+    let [key, assoc, value] = positional[..] else {
+        return Err(syn::Error::new(
+            rule_name.span(),
+            "map requires 3 arguments: (key, separator, value)",
+        ));
+    };
+
+    let default_sep = ModelPattern::Lit {
+        binding: None,
+        lit: Lit::Str(syn::LitStr::new(",", rule_name.span())),
+    };
+    let mut sep = &default_sep;
+    let mut trailing = false;
+    let mut unique = false;
+    for arg in args {
+        if let Argument::Named(id, val) = arg {
+            match val {
+                _ if id == "sep" => sep = val,
+                ModelPattern::Lit {
+                    lit: Lit::Bool(b), ..
+                } if id == "trailing" => trailing = b.value,
+                ModelPattern::Lit {
+                    lit: Lit::Bool(b), ..
+                } if id == "unique" => unique = b.value,
+                _ => {
+                    return Err(syn::Error::new(
+                        id.span(),
+                        format!("Unknown argument '{}' for map", id),
+                    ))
+                }
+            }
+        }
+    }
+
+    let (key_with_binding, key_bindings) = bind_pattern(key, "_key");
+    let (value_with_binding, value_bindings) = bind_pattern(value, "_value");
+    let key_parser = generate_pattern_step(&key_with_binding, cx)?;
+    let assoc_parser = generate_pattern_step(assoc, cx)?;
+    let value_parser = generate_pattern_step(&value_with_binding, cx)?;
+    let sep_parser = generate_pattern_step(sep, cx)?;
+    let sep_peek = cx
+        .simple_peek(sep)?
+        .ok_or_else(|| syn::Error::new(sep.span(), "map expects a separator that can be peeked"))?;
+
+    let key_spans = if unique {
+        quote!(let mut _key_spans = Vec::new();)
+    } else {
+        quote!()
+    };
+    let unique_check = if unique {
+        quote! {
+            if let Some(_prev) = _entries.iter().position(|(k, _)| *k == _entry.0) {
+                ctx.trigger_fail();
+                let mut err = syn::Error::new(_key_span, "duplicate key");
+                err.combine(syn::Error::new(_key_spans[_prev], "first defined here"));
+                return Err(err);
+            }
+            _key_spans.push(_key_span);
+        }
+    } else {
+        quote!()
+    };
+
+    let result = match generics.first() {
+        Some(ty) => quote!(_entries.into_iter().collect::<#ty<_, _>>()),
+        None => quote!(_entries),
+    };
+
+    let value = quote! {
+        {
+            let mut _entries = Vec::new();
+            #key_spans
+            loop {
+                let _key_span = input.span();
+                let _entry = rt::attempt(input, ctx, |mut input, ctx| {
+                    #key_parser
+                    #assoc_parser
+                    #value_parser
+                    Ok(((#(#key_bindings),*), (#(#value_bindings),*)))
+                })?;
+                let Some(_entry) = _entry else {
+                    if !_entries.is_empty() && !#trailing {
+                        return Err(ctx
+                            .take_best_error()
+                            .unwrap_or_else(|| input.error("expected entry after separator")));
+                    }
+                    break;
+                };
+                #unique_check
+                _entries.push(_entry);
+                if input.peek(#sep_peek) {
+                    #sep_parser
+                } else {
+                    break;
+                }
+            }
+            #result
+        }
+    };
+
+    match binding {
+        Some(bind) => Ok(quote! { let #bind = #value; }),
+        None => Ok(quote! { let _ = #value; }),
+    }
+}
+
+/// `parse_in(string, pattern)`: matches `string`, a string literal such as `lit_str`, and
+/// parses its contents with `pattern`, which must consume all of them. The value is the value
+/// of `pattern`; errors inside the contents are reported at the string literal.
+fn generate_parse_in(
+    binding: Option<&syn::Ident>,
+    rule_name: &syn::Ident,
+    generics: &[syn::Type],
+    args: &[Argument],
+    cx: &Context,
+) -> Result<TokenStream> {
+    let (source, inner) =
+        match (generics, args) {
+            ([], [Argument::Positional(source), Argument::Positional(inner)]) => (source, inner),
+            _ => return Err(syn::Error::new(
+                rule_name.span(),
+                "parse_in expects a string literal and a pattern, e.g. parse_in(lit_str, format)",
+            )),
+        };
+    let (source_with_binding, source_bindings) = bind_pattern(source, "_source");
+    let [source_binding] = &source_bindings[..] else {
+        return Err(syn::Error::new(
+            source.span(),
+            "the first argument of parse_in must bind a single string literal, e.g. lit_str",
+        ));
+    };
+    let (inner_with_binding, inner_bindings) = bind_pattern(inner, "_value");
+    let source_parser = generate_pattern_step(&source_with_binding, cx)?;
+    let inner_parser = generate_pattern_step(&inner_with_binding, cx)?;
+
+    let value = quote! {
+        {
+            #source_parser
+            let _tokens = rt::embedded::tokens(&#source_binding)?;
+            syn::parse::Parser::parse2(
+                |mut input: syn::parse::ParseStream| -> syn::Result<_> {
+                    #inner_parser
+                    Ok((#(#inner_bindings),*))
+                },
+                _tokens,
+            )?
+        }
+    };
+
+    match binding {
+        Some(bind) => Ok(quote! { let #bind = #value; }),
+        None => Ok(quote! { let _ = #value; }),
+    }
+}
+
+/// Integer built-ins that accept the `wrapping=true` and `saturating=true` options.
+const INTEGER_BUILTINS: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+/// `u8(wrapping=true)` or `u8(saturating=true)`: an integer built-in that maps literals out of
+/// range into the type instead of failing.
+/// Generates `punct("+", "-", "**")`: matches one of the punctuation options, trying longer
+/// options first so that `**` is not taken for `*`, and binds an
+/// `SpannedValue<String>` of the matched option.
+fn generate_punct(
+    binding: Option<&syn::Ident>,
+    rule_name: &syn::Ident,
+    args: &[Argument],
+) -> Result<TokenStream> {
+    let mut options = Vec::new();
+    for arg in args {
+        match arg {
+            Argument::Positional(ModelPattern::Lit {
+                lit: Lit::Str(s), ..
+            }) if !s.value().is_empty() && s.value().chars().all(|c| c.is_ascii_punctuation()) => {
+                options.push(s.value());
+            }
+            Argument::Positional(p) | Argument::Named(_, p) => {
+                return Err(syn::Error::new(
+                    p.span(),
+                    "punct expects punctuation literals, e.g. punct(\"+\", \"-\", \"**\")",
+                ))
+            }
+        }
+    }
+    if options.is_empty() {
+        return Err(syn::Error::new(
+            rule_name.span(),
+            "punct expects at least one punctuation literal, e.g. punct(\"+\", \"-\")",
+        ));
+    }
+    options.sort_by_key(|o| std::cmp::Reverse(o.chars().count()));
+
+    let value = quote! {
+        {
+            let (_punct, _span) = rt::punct(input, ctx, &[#(#options),*])?;
+            syn_grammar::types::SpannedValue::new(_punct.to_string(), _span)
+        }
+    };
+    Ok(match binding {
+        Some(bind) => quote! { let #bind = #value; },
+        None => quote! { let _ = #value; },
+    })
+}
+
+/// Generates `oneof("+", "-", "fn")`: a chain of peeks, one per literal, binding an
+/// `SpannedValue<String>` of the matched literal. Literals of several tokens (e.g. `"->"`) are
+/// tried first and parsed in an `attempt()`, so that `-` does not match the start of `->` and
+/// tokens separated by whitespace fall through to the shorter literals.
+fn generate_oneof(
+    binding: Option<&syn::Ident>,
+    rule_name: &syn::Ident,
+    args: &[Argument],
+    cx: &Context,
+) -> Result<TokenStream> {
+    let mut options = Vec::new();
+    for arg in args {
+        match arg {
+            Argument::Positional(ModelPattern::Lit {
+                lit: Lit::Str(lit), ..
+            }) => {
+                let types = analysis::resolve_token_types(lit, &cx.custom_keywords)?;
+                options.push((lit.value(), types));
+            }
+            Argument::Positional(p) | Argument::Named(_, p) => {
+                return Err(syn::Error::new(
+                    p.span(),
+                    "oneof expects string literals, e.g. oneof(\"+\", \"-\", \"*\")",
+                ))
+            }
+        }
+    }
+    if options.is_empty() {
+        return Err(syn::Error::new(
+            rule_name.span(),
+            "oneof expects at least one literal, e.g. oneof(\"+\", \"-\")",
+        ));
+    }
+    options.sort_by_key(|(_, types)| std::cmp::Reverse(types.len()));
+
+    let branches = options.iter().map(|(text, types)| {
+        let peeks = types
+            .iter()
+            .take(3)
+            .zip([quote!(peek), quote!(peek2), quote!(peek3)]);
+        let guard = peeks.map(|(ty, peek)| quote! { input.#peek(#ty) });
+        if let [ty] = types.as_slice() {
+            return quote! {
+                if #(#guard)&&* {
+                    let _span = syn::spanned::Spanned::span(&input.parse::<#ty>()?);
+                    ctx.record_span(_span);
+                    break 'oneof syn_grammar::types::SpannedValue::new(#text.to_string(), _span);
+                }
+            };
+        }
+        let spaced = format!("expected '{}', found space between tokens", text);
+        let vars: Vec<_> = (0..types.len()).map(|i| format_ident!("_t{}", i)).collect();
+        let first = &vars[0];
+        let last = &vars[vars.len() - 1];
+        let adjacent = vars.windows(2).map(|w| {
+            let (prev, next) = (&w[0], &w[1]);
+            quote! { #prev.span().end() == #next.span().start() }
+        });
+        quote! {
+            if #(#guard)&&* {
+                let _matched = rt::attempt(input, ctx, |input, _ctx| {
+                    #(let #vars = syn::spanned::Spanned::span(&input.parse::<#types>()?);)*
+                    if #(#adjacent)&&* {
+                        Ok(#first.join(#last).unwrap_or(#first))
+                    } else {
+                        Err(syn::Error::new(#last, #spaced))
+                    }
+                })?;
+                if let Some(_span) = _matched {
+                    ctx.record_span(_span);
+                    break 'oneof syn_grammar::types::SpannedValue::new(#text.to_string(), _span);
+                }
+            }
+        }
+    });
+
+    let expected = match options.as_slice() {
+        [(text, _)] => format!("expected `{}`", text),
+        _ => format!(
+            "expected one of {}",
+            options
+                .iter()
+                .map(|(text, _)| format!("`{}`", text))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let value = quote! {
+        'oneof: {
+            #(#branches)*
+            return Err(syn::Error::new(input.span(), #expected));
+        }
+    };
+    Ok(match binding {
+        Some(bind) => quote! { let #bind = #value; },
+        None => quote! { let _ = #value; },
+    })
+}
+
+fn generate_integer(
+    binding: Option<&syn::Ident>,
+    rule_name: &syn::Ident,
+    args: &[Argument],
+) -> Result<TokenStream> {
+    let mut overflow = None;
+    for arg in args {
+        let (id, val) = match arg {
+            Argument::Named(id, val) if id == "wrapping" || id == "saturating" => (id, val),
+            Argument::Named(id, _) => {
+                return Err(syn::Error::new(
+                    id.span(),
+                    format!("Unknown argument '{}' for {}", id, rule_name),
+                ))
+            }
+            Argument::Positional(p) => {
+                return Err(syn::Error::new(
+                    p.span(),
+                    format!(
+                        "{} only takes `wrapping=true` or `saturating=true`",
+                        rule_name
+                    ),
+                ))
+            }
+        };
+        let ModelPattern::Lit {
+            lit: Lit::Bool(enabled),
+            ..
+        } = val
+        else {
+            return Err(syn::Error::new(
+                val.span(),
+                format!("{} expects `true` or `false`", id),
+            ));
+        };
+        if enabled.value {
+            if overflow.is_some() {
+                return Err(syn::Error::new(
+                    id.span(),
+                    "`wrapping` and `saturating` cannot be combined",
+                ));
+            }
+            overflow = Some(if id == "wrapping" {
+                quote!(Wrapping)
+            } else {
+                quote!(Saturating)
+            });
+        }
+    }
+
+    let overflow = overflow.unwrap_or(quote!(Error));
+    let value = quote! {
+        parse_integer_impl::<#rule_name>(&mut input, ctx, syn_grammar::builtins::Overflow::#overflow)?
+    };
+    match binding {
+        Some(bind) => Ok(quote! { let #bind = #value; }),
+        None => Ok(quote! { let _ = #value; }),
     }
 }
 
@@ -1,22 +1,93 @@
-use super::pattern;
+use super::{pattern, Context};
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
-use std::collections::{HashMap, HashSet};
-use syn::Result;
-use syn_grammar_model::{analysis, model::*};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use syn::{parse_quote, Result};
+use syn_grammar_model::{analysis, model::*, parser};
 
-pub fn generate_rule(rule: &Rule, custom_keywords: &HashSet<String>) -> Result<TokenStream> {
+/// Rule attributes that configure code generation and are not emitted.
+const CODEGEN_ATTRS: &[&str] = &[
+    "lossless",
+    "parsed",
+    "complete",
+    "iter",
+    "no_backtrack",
+    "commit_after_first",
+    "derive_enum",
+    "expected",
+    "recover",
+    "inline_rule",
+    "impl_parse",
+    "bench_input",
+];
+
+pub fn generate_rule(rule: &Rule, cx: &Context) -> Result<TokenStream> {
+    let derived_enum = generate_derived_enum(rule)?;
+    // Checked before `lower_variants` replaces `$span` in the actions
+    let uses_span = rule
+        .variants
+        .iter()
+        .any(|v| parser::uses_span_var(&v.action));
+    let rule = &*lower_variants(rule, cx);
     let name = &rule.name;
     let fn_name = format_ident!("parse_{}", name);
     let impl_name = format_ident!("parse_{}_impl", name);
     let ret_type = &rule.return_type;
-    let attrs = &rule.attrs;
+    // Attributes consumed here rather than passed through: `#[lossless]` also returns the
+    // consumed tokens, `#[parsed]` wraps the result in `rt::Parsed` with the consumed token
+    // count and end span, `#[complete]` (or `#[complete("a config file")]`, naming the input in
+    // the error) requires the end of input in the entry point, and
+    // `#[iter(";")]` adds an iterator over items separated by a sync token, `#[expected("a type")]`
+    // reports failures at the start of the rule as "expected a type", `#[recover(sync = ";")]`
+    // skips a failed rule to the sync token (see `recover_attr`). `#[no_backtrack]`
+    // is handled when generating the body, `#[commit_after_first]` by `lower_variants` and
+    // `#[derive_enum]` by `generate_derived_enum`
+    let lossless = rule.attrs.iter().any(|a| a.path().is_ident("lossless"));
+    let parsed = rule.attrs.iter().any(|a| a.path().is_ident("parsed"));
+    let complete = rule
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("complete"))
+        .map(|a| match &a.meta {
+            syn::Meta::Path(_) => Ok(format!("`{}` input", name)),
+            _ => a.parse_args::<syn::LitStr>().map(|s| s.value()),
+        })
+        .transpose()?;
+    let iter_sync = rule
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("iter"))
+        .map(|a| a.parse_args::<syn::LitStr>())
+        .transpose()?;
+    let expected = rule
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("expected"))
+        .map(|a| match &a.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(s),
+                        ..
+                    }),
+                ..
+            }) => Ok(s.value()),
+            _ => a.parse_args::<syn::LitStr>().map(|s| s.value()),
+        })
+        .transpose()?;
+    let attrs: Vec<&syn::Attribute> = rule
+        .attrs
+        .iter()
+        .filter(|a| !CODEGEN_ATTRS.iter().any(|c| a.path().is_ident(c)))
+        .collect();
+    let out_type = output_type(rule);
     let generics = &rule.generics; // Include where clause if present
 
     // Filter attributes for the implementation function
     // Structural & Lint attributes must be on both.
     // API & Doc attributes should only be on the wrapper.
-    let impl_attrs: Vec<&syn::Attribute> = attrs
+    let impl_attrs: Vec<&&syn::Attribute> = attrs
         .iter()
         .filter(|a| {
             let p = a.path();
@@ -51,16 +122,21 @@ pub fn generate_rule(rule: &Rule, custom_keywords: &HashSet<String>) -> Result<T
         .collect();
 
     let is_public = rule.is_pub || name == "main";
-    let vis = if is_public { quote!(pub) } else { quote!() };
+    let vis = entry_vis(rule);
 
     // Check for direct left recursion
     let (recursive_refs, base_refs) = analysis::split_left_recursive(name, &rule.variants);
 
-    // Add where clause from generics
-    let where_clause = &generics.where_clause;
+    // Lifetime parameters and the where clause go into every generated signature; type
+    // parameters have been removed by monomorphization
+    let (fn_generics, _, where_clause) = generics.split_for_impl();
 
-    let body = if recursive_refs.is_empty() {
-        generate_variants_internal(&rule.variants, true, custom_keywords)?
+    let body = if let Some(precedence) = &rule.precedence {
+        generate_precedence(rule, precedence, cx)?
+    } else if analysis::is_no_backtrack(rule) {
+        generate_committed_variants(rule, cx)?
+    } else if recursive_refs.is_empty() {
+        generate_variants_internal(&rule.variants, true, cx)?
     } else {
         if base_refs.is_empty() {
             return Err(syn::Error::new(
@@ -72,8 +148,8 @@ pub fn generate_rule(rule: &Rule, custom_keywords: &HashSet<String>) -> Result<T
         let base_owned: Vec<RuleVariant> = base_refs.into_iter().cloned().collect();
         let recursive_owned: Vec<RuleVariant> = recursive_refs.into_iter().cloned().collect();
 
-        let base_logic = generate_variants_internal(&base_owned, true, custom_keywords)?;
-        let loop_logic = generate_recursive_loop_body(&recursive_owned, custom_keywords)?;
+        let base_logic = generate_variants_internal(&base_owned, true, cx)?;
+        let loop_logic = generate_recursive_loop_body(&recursive_owned, cx)?;
 
         quote! {
             let mut lhs = {
@@ -90,13 +166,297 @@ pub fn generate_rule(rule: &Rule, custom_keywords: &HashSet<String>) -> Result<T
         }
     };
 
+    // `follows(..)` looks back at the tokens consumed since the start of the rule
+    let uses_follows = rule
+        .variants
+        .iter()
+        .any(|v| analysis::uses_follows(&v.pattern));
+    let body = if uses_follows {
+        quote! {
+            let _lookbehind_start = input.cursor();
+            #body
+        }
+    } else {
+        body
+    };
+
+    // `$span` in an action and errors thrown by an action span the tokens consumed since the
+    // start of the rule
+    let body = if uses_span || rule.throws.is_some() {
+        quote! {
+            let _span_start = input.cursor();
+            #body
+        }
+    } else {
+        body
+    };
+
+    // Errors escaping an instantiated generic rule note its call site
+    let (enter_instantiation, exit_instantiation) = match &rule.instantiation {
+        Some(inst) => {
+            let start = inst.call_site.start();
+            let call_site = format!(
+                "`{}` at line {}, column {}",
+                inst.template,
+                start.line,
+                start.column + 1
+            );
+            (
+                quote! {
+                    const INSTANTIATED_FROM: &str = #call_site;
+                    ctx.enter_instantiation(INSTANTIATED_FROM);
+                },
+                quote! {
+                    ctx.exit_instantiation();
+                    let res = res.map_err(|e| rt::note_instantiation(e, INSTANTIATED_FROM));
+                },
+            )
+        }
+        None => (quote!(), quote!()),
+    };
+
+    // `super` calls the parent grammar's version of this rule; the local function shadows the
+    // rule functions imported from the parent
+    let super_fn = match &cx.parent {
+        Some(parent) if analysis::calls_super(rule) => quote! {
+            fn parse_super_impl #fn_generics (input: ParseStream, ctx: &mut rt::ParseContext #(#params)*) -> Result<#ret_type> {
+                super::#parent::#impl_name(input, ctx #(#param_names)*)
+            }
+        },
+        _ => quote!(),
+    };
+
+    // Capture the consumed tokens by the cursor delta around the body
+    let (lossless_start, lossless_end) = if lossless {
+        (
+            quote! { let _lossless_start = input.cursor(); },
+            quote! { let res = res.map(|v| (v, rt::tokens_between(_lossless_start, input.cursor()))); },
+        )
+    } else {
+        (quote!(), quote!())
+    };
+
+    // The outermost indentation level is the column of the first token of the input
+    let layout_start = if cx.layout {
+        quote! { rt::start_layout(input, ctx); }
+    } else {
+        quote!()
+    };
+
+    // Concrete syntax tree nodes from the same cursor delta, kept only if the rule matched
+    let (cst_start, cst_end) = if cx.cst {
+        (
+            quote! {
+                let _cst_start = input.cursor();
+                ctx.cst_enter();
+            },
+            quote! { ctx.cst_exit(stringify!(#name), _cst_start, input.cursor(), res.is_ok()); },
+        )
+    } else {
+        (quote!(), quote!())
+    };
+
+    // Position metadata from the same cursor delta, wrapping the lossless pair if both are set
+    let (parsed_start, parsed_end) = if parsed {
+        (
+            quote! { let _parsed_start = input.cursor(); },
+            quote! { let res = res.map(|v| rt::Parsed::between(v, _parsed_start, input.cursor())); },
+        )
+    } else {
+        (quote!(), quote!())
+    };
+
+    let run_body = match &expected {
+        Some(label) => quote! {
+            rt::expected(input, ctx, #label, |mut input, ctx| -> syn::Result<#ret_type> {
+                #body
+            })
+        },
+        None => quote! {
+            (|| -> syn::Result<#ret_type> {
+                #body
+            })()
+        },
+    };
+    let run_body = match recover_attr(rule, cx)? {
+        Some((sync_ty, placeholder)) => quote! {
+            rt::recover_rule(
+                input,
+                ctx,
+                |input| input.peek(#sync_ty),
+                |mut input, ctx| #run_body,
+            )
+            .map(|v| v.unwrap_or_else(|| #placeholder))
+        },
+        None => run_body,
+    };
+
+    // Only the entry point checks for the end; calls from other rules are embedded
+    let complete_check = match &complete {
+        Some(expected) => quote! { rt::expect_end(input, &mut ctx, #expected)?; },
+        None => quote!(),
+    };
+
+    let iter_fn = match &iter_sync {
+        Some(sync) => {
+            let sync_ty = match &analysis::resolve_token_types(sync, &cx.custom_keywords)?[..] {
+                [ty] => ty.clone(),
+                _ => {
+                    return Err(syn::Error::new(
+                        sync.span(),
+                        "#[iter] expects a single sync token, e.g. #[iter(\";\")]",
+                    ))
+                }
+            };
+            if !params.is_empty() {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "#[iter] rules cannot take parameters",
+                ));
+            }
+            let iter_name = format_ident!("iter_{}", name);
+            let mut iter_generics = generics.clone();
+            iter_generics.params.insert(0, parse_quote!('__input));
+            let (iter_generics, _, _) = iter_generics.split_for_impl();
+            let doc = format!(
+                "Parses `{}` items one at a time, separated by `{}`. After a failed item, skips to the next `{}`.",
+                name,
+                sync.value(),
+                sync.value()
+            );
+            quote! {
+                #[doc = #doc]
+                #(#impl_attrs)*
+                #vis fn #iter_name #iter_generics (input: ParseStream<'__input>) -> impl Iterator<Item = Result<#out_type>> + '__input #where_clause {
+                    std::iter::from_fn(move || {
+                        if input.is_empty() {
+                            return None;
+                        }
+                        let mut ctx = rt::ParseContext::new();
+                        let res = #impl_name(input, &mut ctx).map_err(|e| ctx.take_best_error().unwrap_or(e));
+                        if res.is_err() {
+                            let _ = rt::skip_until(input, |input| input.peek(#sync_ty));
+                        }
+                        if input.peek(#sync_ty) {
+                            let _ = input.parse::<#sync_ty>();
+                        }
+                        Some(res)
+                    })
+                }
+            }
+        }
+        None => quote!(),
+    };
+
+    // Entry point for IDE hosts that parse the input as a sequence of items and reparse it
+    // after each edit, with `options { incremental = true; }`
+    let incremental_fn = if cx.incremental && is_public && params.is_empty() {
+        let incremental_name = format_ident!("incremental_{}", name);
+        let mut state_generics = generics.clone();
+        state_generics.params.insert(0, parse_quote!('__state));
+        let (state_generics, _, _) = state_generics.split_for_impl();
+        let doc = format!(
+            "Parses the input as a sequence of `{}` items, reusing the items in `state` that a previous parse produced from the same tokens.",
+            name
+        );
+        quote! {
+            #[doc = #doc]
+            #(#impl_attrs)*
+            #vis fn #incremental_name #state_generics (input: ParseStream, state: &'__state mut rt::Incremental<#out_type>) -> Result<&'__state [#out_type]> #where_clause {
+                state.parse(input, |input, ctx| #impl_name(input, ctx))
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    // Entry points that report every error skipped by a recovery point, not only the best one
+    // (with `options { diagnose = true; }`), and the value of a lenient parse together with its
+    // non-fatal diagnostics (with `options { lenient = true; }`)
+    let diagnostic_fns = if is_public && params.is_empty() {
+        let complete_check = match &complete {
+            Some(expected) => quote! { rt::expect_end(input, ctx, #expected)?; },
+            None => quote!(),
+        };
+        let parse = quote! {
+            |input, ctx| {
+                let val = #impl_name(input, ctx)?;
+                #complete_check
+                Ok(val)
+            }
+        };
+        let cst_fn = if cx.cst {
+            let cst_name = format_ident!("cst_{}", name);
+            let cst_doc = format!(
+                "Parses `{}` and returns its value with the concrete syntax tree of the rules it matched.",
+                name
+            );
+            quote! {
+                #[doc = #cst_doc]
+                #(#impl_attrs)*
+                #vis fn #cst_name #fn_generics (input: ParseStream) -> Result<(#out_type, rt::Node)> #where_clause {
+                    rt::parse_cst(input, #parse)
+                }
+            }
+        } else {
+            quote!()
+        };
+        let diagnose_fn = if cx.diagnose {
+            let diagnose_name = format_ident!("diagnose_{}", name);
+            let diagnose_doc = format!(
+                "Parses `{}` and returns its value, if any, with all errors that recovery points skipped past.",
+                name
+            );
+            quote! {
+                #[doc = #diagnose_doc]
+                #(#impl_attrs)*
+                #vis fn #diagnose_name #fn_generics (input: ParseStream) -> (Option<#out_type>, Vec<syn::Error>) #where_clause {
+                    rt::diagnose(input, #parse)
+                }
+            }
+        } else {
+            quote!()
+        };
+        let lenient_fn = if cx.lenient {
+            let lenient_name = format_ident!("lenient_{}", name);
+            let lenient_doc = format!(
+                "Parses `{}` and returns its value with the non-fatal diagnostics: errors that recovery points skipped past and warnings reported with `ctx.warn(..)`.",
+                name
+            );
+            quote! {
+                #[doc = #lenient_doc]
+                #(#impl_attrs)*
+                #vis fn #lenient_name #fn_generics (input: ParseStream) -> Result<(#out_type, Vec<syn::Error>)> #where_clause {
+                    rt::parse_lenient(input, #parse)
+                }
+            }
+        } else {
+            quote!()
+        };
+        quote! {
+            #cst_fn
+            #diagnose_fn
+            #lenient_fn
+        }
+    } else {
+        quote!()
+    };
+
     Ok(quote! {
+        #derived_enum
+        #iter_fn
+        #incremental_fn
+        #diagnostic_fns
+
         #(#attrs)*
         #default_doc
-        #vis fn #fn_name(input: ParseStream #(#params)*) -> Result<#ret_type> #where_clause {
+        #vis fn #fn_name #fn_generics (input: ParseStream #(#params)*) -> Result<#out_type> #where_clause {
             let mut ctx = rt::ParseContext::new();
             match #impl_name(input, &mut ctx #(#param_names)*) {
-                Ok(val) => Ok(val),
+                Ok(val) => {
+                    #complete_check
+                    Ok(val)
+                }
                 Err(e) => {
                     if let Some(best) = ctx.take_best_error() {
                         Err(best)
@@ -109,21 +469,850 @@ pub fn generate_rule(rule: &Rule, custom_keywords: &HashSet<String>) -> Result<T
 
         #[doc(hidden)]
         #(#impl_attrs)*
-        pub fn #impl_name(mut input: ParseStream, ctx: &mut rt::ParseContext #(#params)*) -> Result<#ret_type> #where_clause {
+        pub(crate) fn #impl_name #fn_generics (mut input: ParseStream, ctx: &mut rt::ParseContext #(#params)*) -> Result<#out_type> #where_clause {
+            #super_fn
             ctx.enter_rule(stringify!(#name));
-            let res = (|| -> syn::Result<#ret_type> {
-                #body
-            })();
+            let _rule_start = rt::trace_enter(input, ctx, stringify!(#name));
+            #enter_instantiation
+            #layout_start
+            #cst_start
+            #parsed_start
+            #lossless_start
+            let res = #run_body;
+            #lossless_end
+            #parsed_end
+            #cst_end
+            #exit_instantiation
+            rt::trace_exit(input, ctx, stringify!(#name), _rule_start, &res);
             ctx.exit_rule();
             res
         }
     })
 }
 
-fn generate_recursive_loop_body(
-    variants: &[RuleVariant],
-    kws: &HashSet<String>,
-) -> Result<TokenStream> {
+/// Parses `#[recover(sync = ";")]` or `#[recover(sync = ";", placeholder = Stmt::Error)]` of a
+/// rule into the sync token type and the value returned after recovering, which defaults to
+/// `Default::default()`.
+fn recover_attr(rule: &Rule, cx: &Context) -> Result<Option<(syn::Type, TokenStream)>> {
+    let Some(attr) = rule.attrs.iter().find(|a| a.path().is_ident("recover")) else {
+        return Ok(None);
+    };
+    let mut sync = None;
+    let mut placeholder = quote!(::std::default::Default::default());
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("sync") {
+            sync = Some(meta.value()?.parse::<syn::LitStr>()?);
+        } else if meta.path.is_ident("placeholder") {
+            let value: syn::Expr = meta.value()?.parse()?;
+            placeholder = quote!(#value);
+        } else {
+            return Err(meta.error("expected `sync = \"..\"` or `placeholder = expr`"));
+        }
+        Ok(())
+    })?;
+    let Some(sync) = sync else {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "#[recover] needs a sync token, e.g. #[recover(sync = \";\")]",
+        ));
+    };
+    match &analysis::resolve_token_types(&sync, &cx.custom_keywords)?[..] {
+        [ty] => Ok(Some((ty.clone(), placeholder))),
+        _ => Err(syn::Error::new(
+            sync.span(),
+            "#[recover] expects a single sync token, e.g. #[recover(sync = \";\")]",
+        )),
+    }
+}
+
+/// The type returned by the generated functions of a rule: the return type, paired with the
+/// consumed tokens for `#[lossless]` and wrapped in `rt::Parsed` for `#[parsed]`.
+fn output_type(rule: &Rule) -> TokenStream {
+    let ret_type = &rule.return_type;
+    let has_attr = |name: &str| rule.attrs.iter().any(|a| a.path().is_ident(name));
+    let out_type = if has_attr("lossless") {
+        quote!((#ret_type, rt::TokenStream))
+    } else {
+        quote!(#ret_type)
+    };
+    if has_attr("parsed") {
+        quote!(rt::Parsed<#out_type>)
+    } else {
+        out_type
+    }
+}
+
+/// The visibility of the entry points of a rule: its own, or `pub` for a rule named `main`.
+pub fn entry_vis(rule: &Rule) -> TokenStream {
+    match &rule.vis {
+        syn::Visibility::Inherited if rule.name == "main" => quote!(pub),
+        vis => quote!(#vis),
+    }
+}
+
+/// Generates `impl syn::parse::Parse` for the return type of a rule marked `#[impl_parse]`,
+/// delegating to its `parse_<rule>` entry point, so the type works with `parse_macro_input!`
+/// and other syn helpers without a wrapper.
+pub fn generate_parse_impl(rule: &Rule) -> Result<Option<TokenStream>> {
+    let Some(attr) = rule.attrs.iter().find(|a| a.path().is_ident("impl_parse")) else {
+        return Ok(None);
+    };
+    attr.meta.require_path_only()?;
+    if !rule.params.is_empty() || !rule.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "#[impl_parse] needs a rule without parameters or generics",
+        ));
+    }
+    if let Some(other) = rule
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("lossless") || a.path().is_ident("parsed"))
+    {
+        return Err(syn::Error::new_spanned(
+            other,
+            "#[impl_parse] implements `Parse` for the return type, which #[lossless] and #[parsed] wrap",
+        ));
+    }
+    let fn_name = format_ident!("parse_{}", rule.name);
+    let ret_type = &rule.return_type;
+    let cfg_attrs = cfg_attrs(rule);
+    Ok(Some(quote! {
+        #(#cfg_attrs)*
+        impl syn::parse::Parse for #ret_type {
+            fn parse(input: ParseStream) -> Result<Self> {
+                #fn_name(input)
+            }
+        }
+    }))
+}
+
+/// Generates the marker type of a public rule without parameters for `rt::RuleParser`, placed
+/// in the `rules` module of the grammar. The lifetimes of the rule become lifetimes of the
+/// marker.
+pub fn generate_rule_marker(rule: &Rule) -> Option<TokenStream> {
+    let name = &rule.name;
+    let is_public = rule.is_pub || name == "main";
+    if !is_public || !rule.params.is_empty() || rule.generics.type_params().next().is_some() {
+        return None;
+    }
+    let impl_name = format_ident!("parse_{}_impl", name);
+    let out_type = output_type(rule);
+    let cfg_attrs = cfg_attrs(rule);
+    // The markers are one module further down than the entry points
+    let vis = match &rule.vis {
+        syn::Visibility::Restricted(r) if r.path.is_ident("super") => quote!(pub(in super::super)),
+        _ => entry_vis(rule),
+    };
+    let doc = format!(
+        "The rule `{}`, to be parsed with [`rt::RuleParser`] by syn helpers.",
+        name
+    );
+    let (impl_generics, ty_generics, where_clause) = rule.generics.split_for_impl();
+    let lifetimes: Vec<_> = rule.generics.lifetimes().map(|l| &l.lifetime).collect();
+    let fields = if lifetimes.is_empty() {
+        quote!(;)
+    } else {
+        quote!((std::marker::PhantomData<(#(&#lifetimes ()),*)>);)
+    };
+    Some(quote! {
+        #[doc = #doc]
+        #(#cfg_attrs)*
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, Copy)]
+        #vis struct #name #impl_generics #fields
+
+        #(#cfg_attrs)*
+        impl #impl_generics rt::GrammarRule for #name #ty_generics #where_clause {
+            type Output = #out_type;
+
+            fn parse_impl(input: ParseStream, ctx: &mut rt::ParseContext) -> Result<Self::Output> {
+                super::#impl_name(input, ctx)
+            }
+        }
+    })
+}
+
+/// Generates the parser struct of the grammar, `CalcParser` for `calc` unless named with the
+/// `parser` option. It has a method returning the `rt::EntryPoint` of each public rule without
+/// parameters or generics, e.g. `CalcParser::expr().parse_str("1 + 2")`.
+pub fn generate_parser_struct(grammar: &GrammarDefinition) -> TokenStream {
+    let name = match &grammar.options.parser {
+        Some(name) => name.clone(),
+        None => format_ident!("{}Parser", enum_variant_name(&grammar.name.to_string())),
+    };
+    let methods = grammar.rules.iter().filter_map(|rule| {
+        let is_public = rule.is_pub || rule.name == "main";
+        if !is_public || !rule.params.is_empty() || !rule.generics.params.is_empty() {
+            return None;
+        }
+        let rule_name = &rule.name;
+        let fn_name = format_ident!("parse_{}", rule_name);
+        let out_type = output_type(rule);
+        let cfg_attrs = cfg_attrs(rule);
+        let vis = entry_vis(rule);
+        let doc = format!("The entry point of the rule `{}`.", rule_name);
+        Some(quote! {
+            #[doc = #doc]
+            #(#cfg_attrs)*
+            #vis fn #rule_name() -> rt::EntryPoint<#out_type> {
+                rt::EntryPoint::new(#fn_name)
+            }
+        })
+    });
+    let doc = format!(
+        "The entry points of the public rules of the grammar `{}`.",
+        grammar.name
+    );
+    quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct #name;
+
+        impl #name {
+            #(#methods)*
+        }
+    }
+}
+
+/// Generates the variants of a `#[no_backtrack]` rule: each one is chosen by peeking at its
+/// FIRST tokens and parsed without `attempt`, so its errors are returned directly.
+fn generate_committed_variants(rule: &Rule, cx: &Context) -> Result<TokenStream> {
+    let peeks = analysis::no_backtrack_peeks(rule, &cx.analysis, &cx.custom_keywords, &cx.builtins)
+        .map_err(|errors| {
+            errors
+                .into_iter()
+                .reduce(|mut a, b| {
+                    a.combine(b);
+                    a
+                })
+                .expect("no_backtrack_peeks fails with at least one error")
+        })?;
+
+    let mut expected = Vec::new();
+    let mut arms = Vec::new();
+    for (variant, peek) in rule.variants.iter().zip(&peeks) {
+        let logic = pattern::generate_sequence(&variant.pattern, &variant.action, cx)?;
+        let logic = with_variant_attrs(variant, quote! { return #logic; });
+        match peek {
+            Some(types) => {
+                let mut first: Vec<String> = cx
+                    .analysis
+                    .first_set_of(&variant.pattern)
+                    .iter()
+                    .map(|t| describe_first_token(t))
+                    .collect();
+                first.sort();
+                expected.extend(first);
+                arms.push(quote! {
+                    if #(input.peek(#types))||* {
+                        #logic
+                    }
+                });
+            }
+            None => arms.push(logic),
+        }
+    }
+
+    if peeks.last().is_some_and(|p| p.is_some()) {
+        expected.dedup();
+        let msg = format!("expected one of: {}", expected.join(", "));
+        arms.push(quote! { Err(input.error(#msg)) });
+    }
+    Ok(quote! { #(#arms)* })
+}
+
+/// Generates an operator precedence rule as a precedence climbing (Pratt) parser: `_pratt`
+/// parses an operand, optionally behind prefix operators, and then applies operators as long
+/// as they bind at least as tightly as `min_bp`. Level `i` has the binding power `2i + 1`;
+/// a left-associative operator parses its right operand with `2i + 2` and a right-associative
+/// one with `2i + 1`, so that the next operator of the same level is taken by the outer or the
+/// inner call respectively.
+fn generate_precedence(rule: &Rule, precedence: &Precedence, cx: &Context) -> Result<TokenStream> {
+    let ret_type = &rule.return_type;
+    let operand = pattern::generate_sequence(
+        &[ModelPattern::RuleCall {
+            binding: Some(format_ident!("_operand")),
+            module: None,
+            rule_name: precedence.operand.clone(),
+            generics: vec![],
+            args: vec![],
+        }],
+        &quote!(_operand),
+        cx,
+    )?;
+
+    // Longer operators first, so that `<=` is not taken for `<`
+    let mut operators: Vec<(u32, &PrecedenceLevel, &syn::LitStr)> = precedence
+        .levels
+        .iter()
+        .enumerate()
+        .flat_map(|(i, level)| {
+            level
+                .operators
+                .iter()
+                .map(move |op| (2 * i as u32 + 1, level, op))
+        })
+        .collect();
+    operators.sort_by_key(|(_, _, op)| std::cmp::Reverse(op.value().len()));
+
+    let mut prefix_arms = Vec::new();
+    let mut loop_arms = Vec::new();
+    for (bp, level, op) in operators {
+        let lit = ModelPattern::Lit {
+            binding: None,
+            lit: syn::Lit::Str(op.clone()),
+        };
+        let steps = pattern::generate_sequence_steps(std::slice::from_ref(&lit), cx)?;
+        let peek = cx.simple_peek(&lit)?;
+        let text = op.value();
+        let action = &level.action;
+        let params = &level.params;
+
+        match level.fixity {
+            Fixity::Prefix => {
+                let (p_op, p_x) = (&params[0], &params[1]);
+                let arm = quote! {
+                    if let Some(_operand) = rt::attempt(input, ctx, |mut input, ctx| {
+                        #steps
+                        _pratt(input, ctx, #bp)
+                    })? {
+                        let #p_op: &'static str = #text;
+                        let #p_x = _operand;
+                        break 'operand Ok({ #action });
+                    }
+                };
+                prefix_arms.push(match &peek {
+                    Some(peek) => quote! { if input.peek(#peek) { #arm } },
+                    None => arm,
+                });
+            }
+            fixity => {
+                let (parse, apply) = if fixity == Fixity::Postfix {
+                    let (p_x, p_op) = (&params[0], &params[1]);
+                    (
+                        quote! { #steps Ok(()) },
+                        quote! {
+                            let #p_x = lhs;
+                            let #p_op: &'static str = #text;
+                        },
+                    )
+                } else {
+                    let r_bp = if fixity == Fixity::Left { bp + 1 } else { bp };
+                    let (p_l, p_op, p_r) = (&params[0], &params[1], &params[2]);
+                    (
+                        quote! { #steps _pratt(input, ctx, #r_bp) },
+                        quote! {
+                            let #p_l = lhs;
+                            let #p_op: &'static str = #text;
+                            let #p_r = _operand;
+                        },
+                    )
+                };
+                let arm = quote! {
+                    if let Some(_operand) = rt::attempt(input, ctx, |mut input, ctx| { #parse })? {
+                        #apply
+                        lhs = { #action };
+                        continue;
+                    }
+                };
+                loop_arms.push(match &peek {
+                    Some(peek) => quote! {
+                        if input.peek(#peek) {
+                            if #bp < min_bp {
+                                break;
+                            }
+                            #arm
+                        }
+                    },
+                    None => quote! {
+                        if #bp >= min_bp {
+                            #arm
+                        }
+                    },
+                });
+            }
+        }
+    }
+
+    Ok(quote! {
+        fn _pratt(input: ParseStream, ctx: &mut rt::ParseContext, min_bp: u32) -> Result<#ret_type> {
+            let operand = |mut input: ParseStream, ctx: &mut rt::ParseContext| -> Result<#ret_type> {
+                #operand
+            };
+            let mut lhs = 'operand: {
+                #(#prefix_arms)*
+                operand(input, ctx)
+            }?;
+            loop {
+                #(#loop_arms)*
+                break;
+            }
+            Ok(lhs)
+        }
+        _pratt(input, ctx, 0)
+    })
+}
+
+/// Describes an entry of a FIRST set for "expected one of" errors.
+fn describe_first_token(entry: &str) -> String {
+    match entry {
+        "Paren" => "`(`".to_string(),
+        "Bracket" => "`[`".to_string(),
+        "Brace" => "`{`".to_string(),
+        s if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') => {
+            format!("`{}`", &s[1..s.len() - 1])
+        }
+        s => s.trim_start_matches('<').trim_end_matches('>').to_string(),
+    }
+}
+
+/// Rewrites the variants for attributes that change their patterns or actions:
+/// - `#[cold]` of a variant moves into its action as a call to `rt::cold_path()`, since Rust
+///   accepts `#[cold]` only on functions and not on the code generated for a variant.
+/// - `#[commit_after_first]` of the rule inserts a cut (`=>`) after the first pattern of every
+///   variant that has none, unless the pattern can match empty input or is a left-recursive call.
+/// - A `where { .. }` guard becomes a check at the start of the action, which fails the
+///   variant with `rt::guard_failed` so that the next one is attempted.
+/// - Bindings named `input` or `ctx` would shadow the parameters used by the generated code
+///   for the following patterns. They are renamed (see `rename_reserved_bindings`) and bound
+///   under their own name at the start of the action.
+/// - In a rule declaring `throws Error`, an action runs in a closure returning
+///   `Result<ReturnType, Error>`, and an error it returns fails the variant with
+///   `rt::action_failed`.
+/// - `$span` in an action becomes a variable bound at the start of the action to the span of
+///   the tokens consumed since the start of the rule (see `rt::span_between`).
+fn lower_variants<'a>(rule: &'a Rule, cx: &Context) -> Cow<'a, Rule> {
+    let is_cold = |a: &syn::Attribute| a.path().is_ident("cold");
+    let has_attr = |name: &str| rule.attrs.iter().any(|a| a.path().is_ident(name));
+    let commit = has_attr("commit_after_first");
+    let derive_enum = has_attr("derive_enum");
+    let reserved = rule
+        .variants
+        .iter()
+        .any(|v| !rename_reserved_bindings(&mut v.pattern.clone()).is_empty());
+    let guarded = rule.variants.iter().any(|v| v.guard.is_some());
+    let spanned = rule
+        .variants
+        .iter()
+        .any(|v| parser::uses_span_var(&v.action));
+    if !commit
+        && !derive_enum
+        && !reserved
+        && !guarded
+        && !spanned
+        && rule.throws.is_none()
+        && !rule.variants.iter().any(|v| v.attrs.iter().any(is_cold))
+    {
+        return Cow::Borrowed(rule);
+    }
+
+    let mut rule = rule.clone();
+    let enum_ty = rule.return_type.clone();
+    for variant in &mut rule.variants {
+        // Checked by `generate_derived_enum`
+        if derive_enum {
+            if let Some(word) = analysis::leading_keyword(variant) {
+                let name = enum_variant_name(&word);
+                variant.action = quote! { #enum_ty::#name };
+            }
+        }
+        // Inside the guard below, whose failure is a `syn::Error`
+        if let Some(error_ty) = &rule.throws {
+            let action = &variant.action;
+            variant.action = quote! {
+                let _thrown = (|| -> ::core::result::Result<#enum_ty, #error_ty> { Ok({ #action }) })();
+                match _thrown {
+                    Ok(value) => value,
+                    Err(error) => return Err(rt::action_failed(input, ctx, _span_start, error)),
+                }
+            };
+        }
+        if let Some(guard) = variant.guard.take() {
+            let condition = guard.to_string();
+            let action = &variant.action;
+            variant.action = quote! {
+                if !{ #guard } {
+                    return Err(rt::guard_failed(input, ctx, #condition));
+                }
+                #action
+            };
+        }
+        let renamed = rename_reserved_bindings(&mut variant.pattern);
+        if !renamed.is_empty() {
+            let rebinds = renamed.iter().map(|(name, internal)| {
+                quote! { let #name = #internal; }
+            });
+            let action = &variant.action;
+            variant.action = quote! { #(#rebinds)* #action };
+        }
+        // Ahead of the rebinds above, which may shadow `input`
+        if parser::uses_span_var(&variant.action) {
+            let var = format_ident!("_variant_span");
+            let action = parser::replace_span_var(&variant.action, &var);
+            variant.action = quote! {
+                let #var = rt::span_between(_span_start, input.cursor());
+                #action
+            };
+        }
+        if variant.attrs.iter().any(is_cold) {
+            variant.attrs.retain(|a| !is_cold(a));
+            let action = &variant.action;
+            variant.action = quote! { rt::cold_path(); #action };
+        }
+        if commit && variant.pattern.len() > 1 && analysis::find_cut(&variant.pattern).is_none() {
+            let first = &variant.pattern[0];
+            let left_recursive = matches!(first, ModelPattern::RuleCall { rule_name, .. } if *rule_name == rule.name);
+            if !left_recursive && !cx.analysis.is_sequence_nullable(&variant.pattern[..1]) {
+                let span = first.span();
+                variant.pattern.insert(1, ModelPattern::Cut(span));
+            }
+        }
+    }
+    Cow::Owned(rule)
+}
+
+/// Renames the bindings named `input` or `ctx` in `patterns`, including those in arguments of
+/// built-ins such as `delimited(x:item, ..)`, to `input_binding` and `ctx_binding`. Returns
+/// the original and the new name of each renamed binding, once per name.
+fn rename_reserved_bindings(patterns: &mut [ModelPattern]) -> Vec<(syn::Ident, syn::Ident)> {
+    fn visit(pattern: &mut ModelPattern, renamed: &mut Vec<(syn::Ident, syn::Ident)>) {
+        let mut rename = |ident: &mut syn::Ident| {
+            if ident == "input" || ident == "ctx" {
+                let internal = format_ident!("{}_binding", ident, span = ident.span());
+                if !renamed.iter().any(|(name, _)| name == ident) {
+                    renamed.push((ident.clone(), internal.clone()));
+                }
+                *ident = internal;
+            }
+        };
+        match pattern {
+            ModelPattern::Lit { binding, .. } => binding.iter_mut().for_each(rename),
+            ModelPattern::RuleCall { binding, args, .. } => {
+                binding.iter_mut().for_each(rename);
+                for arg in args {
+                    let (Argument::Positional(p) | Argument::Named(_, p)) = arg;
+                    visit(p, renamed);
+                }
+            }
+            ModelPattern::SpanBinding(inner, ident, _) | ModelPattern::Raw(inner, ident, _) => {
+                rename(ident);
+                visit(inner, renamed);
+            }
+            ModelPattern::Recover { binding, body, .. } => {
+                binding.iter_mut().for_each(rename);
+                visit(body, renamed);
+            }
+            ModelPattern::Until {
+                binding, pattern, ..
+            } => {
+                binding.iter_mut().for_each(rename);
+                visit(pattern, renamed);
+            }
+            ModelPattern::Fold { binding, .. } => binding.iter_mut().for_each(rename),
+            ModelPattern::Group(alts, _) => {
+                for (seq, _) in alts {
+                    seq.iter_mut().for_each(|p| visit(p, renamed));
+                }
+            }
+            ModelPattern::Bracketed(seq, _)
+            | ModelPattern::Braced(seq, _)
+            | ModelPattern::Parenthesized(seq, _)
+            | ModelPattern::Scope(seq, _) => {
+                seq.iter_mut().for_each(|p| visit(p, renamed));
+            }
+            ModelPattern::Optional(inner, _)
+            | ModelPattern::Repeat(inner, _)
+            | ModelPattern::Plus(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. }
+            | ModelPattern::Collect(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Peek(inner, _) => visit(inner, renamed),
+            ModelPattern::Cut(_) | ModelPattern::Not(_, _) | ModelPattern::Follows(_, _) => {}
+        }
+    }
+
+    let mut renamed = Vec::new();
+    patterns.iter_mut().for_each(|p| visit(p, &mut renamed));
+    renamed
+}
+
+/// Generates the enum returned by a rule marked `#[derive_enum]`, whose variants are keywords
+/// with empty actions (`"red" -> {}`): one unit variant per keyword, named by the keyword in
+/// UpperCamelCase, and `as_str` returning the keyword.
+fn generate_derived_enum(rule: &Rule) -> Result<Option<TokenStream>> {
+    if !rule.attrs.iter().any(|a| a.path().is_ident("derive_enum")) {
+        return Ok(None);
+    }
+    let enum_name = match &rule.return_type {
+        syn::Type::Path(p) if p.qself.is_none() => p.path.get_ident(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        syn::Error::new(
+            syn::spanned::Spanned::span(&rule.return_type),
+            "#[derive_enum] needs a plain type name as return type, e.g. `-> Color`",
+        )
+    })?;
+
+    let mut words = Vec::new();
+    for variant in &rule.variants {
+        match analysis::leading_keyword(variant) {
+            Some(word) if variant.pattern.len() == 1 && variant.action.is_empty() => {
+                words.push(word)
+            }
+            _ => {
+                let span = variant
+                    .pattern
+                    .first()
+                    .map_or(rule.name.span(), |p| p.span());
+                return Err(syn::Error::new(
+                    span,
+                    "#[derive_enum] rules must consist of keywords with empty actions, e.g. `\"red\" -> {}`",
+                ));
+            }
+        }
+    }
+
+    let names: Vec<_> = words.iter().map(|w| enum_variant_name(w)).collect();
+    let docs = words.iter().map(|w| format!("`{}`", w));
+    let vis = entry_vis(rule);
+    let doc = format!("The keywords of rule `{}`.", rule.name);
+    let cfg_attrs = cfg_attrs(rule);
+    Ok(Some(quote! {
+        #[doc = #doc]
+        #(#cfg_attrs)*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #vis enum #enum_name {
+            #(#[doc = #docs] #names,)*
+        }
+
+        #(#cfg_attrs)*
+        impl #enum_name {
+            /// The keyword of the variant.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    #(#enum_name::#names => #words,)*
+                }
+            }
+        }
+    }))
+}
+
+/// The `#[cfg(..)]` attributes of a rule, repeated on the items generated next to its
+/// functions, e.g. for test-only rules.
+pub fn cfg_attrs(rule: &Rule) -> Vec<&syn::Attribute> {
+    rule.attrs
+        .iter()
+        .filter(|a| a.path().is_ident("cfg"))
+        .collect()
+}
+
+/// The name of the enum variant for a keyword, e.g. `DarkRed` for `dark_red`.
+fn enum_variant_name(word: &str) -> syn::Ident {
+    let word = word.strip_prefix("r#").unwrap_or(word);
+    let name: String = word
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+    format_ident!("{}", name)
+}
+
+/// The attributes of a variant emitted on its code. Doc comments only document the grammar.
+fn emitted_attrs(variant: &RuleVariant) -> Vec<&syn::Attribute> {
+    variant
+        .attrs
+        .iter()
+        .filter(|a| !a.path().is_ident("doc"))
+        .collect()
+}
+
+/// Emits the attributes of a variant (e.g. `#[allow(..)]` or `#[cfg(..)]`) on its code.
+fn with_variant_attrs(variant: &RuleVariant, code: TokenStream) -> TokenStream {
+    let attrs = emitted_attrs(variant);
+    if attrs.is_empty() {
+        return code;
+    }
+    quote! {
+        #(#attrs)*
+        {
+            #code
+        }
+    }
+}
+
+/// Records the label of a failed variant for "expected one of" errors: its explicit label, or
+/// the rule name if the variant is a single rule call.
+fn failure_record(variant: &RuleVariant) -> TokenStream {
+    let label_str = if let Some(l) = &variant.label {
+        Some(l.clone())
+    } else if variant.pattern.len() == 1 {
+        if let ModelPattern::RuleCall { rule_name, .. } = &variant.pattern[0] {
+            Some(rule_name.to_string())
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    if let Some(l) = label_str {
+        quote! {
+            if !ctx.is_best_error_deep() {
+                _shallow_failures.push(#l);
+            }
+        }
+    } else {
+        quote! {}
+    }
+}
+
+/// Generates a run of variants that start with the same rule call (see
+/// [`analysis::common_prefix_runs`]): the call is parsed once, and each variant continues
+/// after it. Errors of the variants are recorded as if they started before the call.
+fn generate_hoisted_arm(run: &[RuleVariant], cx: &Context) -> Result<TokenStream> {
+    let ModelPattern::RuleCall { rule_name, .. } = &run[0].pattern[0] else {
+        unreachable!("hoisted variants start with a rule call");
+    };
+    let prefix = pattern::generate_sequence_steps(
+        &[ModelPattern::RuleCall {
+            binding: Some(format_ident!("_hoisted_value")),
+            module: None,
+            rule_name: rule_name.clone(),
+            generics: vec![],
+            args: vec![],
+        }],
+        cx,
+    )?;
+
+    let mut any_binding = false;
+    let alternatives = run
+        .iter()
+        .map(|variant| {
+            let steps = pattern::generate_sequence_steps(&variant.pattern[1..], cx)?;
+            // Handed over only once the variant has matched, so failed variants leave it
+            let take = match &variant.pattern[0] {
+                ModelPattern::RuleCall {
+                    binding: Some(b), ..
+                } => {
+                    any_binding = true;
+                    quote! { let #b = _hoisted.take().expect("hoisted prefix is taken once"); }
+                }
+                _ => quote!(),
+            };
+            let action = &variant.action;
+            let failure_rec = failure_record(variant);
+            Ok(with_variant_attrs(
+                variant,
+                quote! {
+                    if let Some(res) = rt::attempt_at(input, ctx, _hoist_start, |mut input, ctx| {
+                        #steps
+                        #take
+                        Ok({ #action })
+                    })? {
+                        return Ok(Some(res));
+                    }
+                    #failure_rec
+                },
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let hoisted = if any_binding {
+        quote! { let mut _hoisted = Some(_hoisted_value); }
+    } else {
+        quote! { let _ = _hoisted_value; }
+    };
+    let failure_recs = run.iter().map(failure_record);
+
+    Ok(quote! {
+        if let Some(res) = rt::attempt_hoisted(
+            input,
+            ctx,
+            |mut input, ctx| {
+                #prefix
+                Ok(_hoisted_value)
+            },
+            |input, ctx, _hoisted, _hoist_start| {
+                let Some(_hoisted_value) = _hoisted else {
+                    #(#failure_recs)*
+                    return Ok(None);
+                };
+                #hoisted
+                #(#alternatives)*
+                Ok(None)
+            },
+        )? {
+            return Ok(res);
+        }
+    })
+}
+
+/// Generates a run of variants starting with distinct keywords (see
+/// [`analysis::keyword_table_runs`]) as one `match` on the next identifier. Like a variant
+/// with a unique peek, the matching variant is committed to.
+fn generate_keyword_table(run: &[RuleVariant], cx: &Context) -> Result<TokenStream> {
+    let arms = run
+        .iter()
+        .map(|variant| {
+            let word =
+                analysis::leading_keyword(variant).expect("table variants start with a keyword");
+            let attrs = emitted_attrs(variant);
+            let logic = pattern::generate_sequence(&variant.pattern, &variant.action, cx)?;
+            Ok(quote! {
+                #(#attrs)*
+                #word => {
+                    let mut run = || -> syn::Result<_> {
+                        #logic
+                    };
+                    match run() {
+                        Ok(v) => return Ok(v),
+                        Err(e) => {
+                            ctx.set_fatal(true);
+                            return Err(e);
+                        }
+                    }
+                }
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Unlabeled keywords are expected by their text
+    let failure_recs = run.iter().map(|variant| match &variant.label {
+        Some(_) => failure_record(variant),
+        None => {
+            let expected = format!(
+                "`{}`",
+                analysis::leading_keyword(variant).unwrap_or_default()
+            );
+            quote! {
+                if !ctx.is_best_error_deep() {
+                    _shallow_failures.push(#expected);
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        if let Some((_keyword, _)) = input.cursor().ident() {
+            match _keyword.to_string().as_str() {
+                #(#arms)*
+                _ => {}
+            }
+        }
+        #(#failure_recs)*
+    })
+}
+
+fn generate_recursive_loop_body(variants: &[RuleVariant], cx: &Context) -> Result<TokenStream> {
     let arms = variants.iter().map(|variant| {
         let tail_pattern = &variant.pattern[1..];
 
@@ -138,15 +1327,16 @@ fn generate_recursive_loop_body(
             quote! {}
         };
 
-        let logic = pattern::generate_sequence(tail_pattern, &variant.action, kws)?;
+        let logic = pattern::generate_sequence(tail_pattern, &variant.action, cx)?;
 
         let peek_token_obj = tail_pattern.first()
-            .and_then(|f| analysis::get_simple_peek(f, kws).ok().flatten());
+            .and_then(|f| cx.simple_peek(f).ok().flatten())
+            .map(|peek| cx.peek_cond(&peek));
 
         match peek_token_obj {
             Some(token_code) => {
                 Ok(quote! {
-                    if input.peek(#token_code) {
+                    if #token_code {
                         let _start_cursor = input.cursor();
                         // Pass ctx to attempt
                         if let Some(new_val) = rt::attempt(input, ctx, |mut input, ctx| {
@@ -178,7 +1368,7 @@ fn generate_recursive_loop_body(
                     }
                 })
             }
-        }
+        }.map(|code| with_variant_attrs(variant, code))
     }).collect::<Result<Vec<_>>>()?;
 
     Ok(quote! { #(#arms)* })
@@ -187,7 +1377,7 @@ fn generate_recursive_loop_body(
 pub fn generate_variants_internal(
     variants: &[RuleVariant],
     is_top_level: bool,
-    _custom_keywords: &HashSet<String>,
+    cx: &Context,
 ) -> Result<TokenStream> {
     if variants.is_empty() {
         return Ok(quote! { Err(input.error("No variants defined")) });
@@ -203,171 +1393,164 @@ pub fn generate_variants_internal(
         }
     }
 
-    let arms = variants
-        .iter()
-        .map(|variant| {
-            // Label determination
-            let label_str = if let Some(l) = &variant.label {
-                 Some(l.clone())
-             } else if variant.pattern.len() == 1 {
-                 if let ModelPattern::RuleCall { rule_name, .. } = &variant.pattern[0] {
-                     Some(rule_name.to_string())
-                 } else {
-                     None
-                 }
-             } else {
-                 None
-             };
-
-             let failure_rec = if let Some(l) = label_str {
-                 quote! {
-                     if !ctx.is_best_error_deep() {
-                         // DEBUG: eprintln!("DEBUG: Pushing failure: {}", #l);
-                         _shallow_failures.push(#l);
-                     } else {
-                         // DEBUG: eprintln!("DEBUG: Skipping failure push due to deep error");
-                     }
-                 }
-             } else {
-                 quote! {}
-             };
-
-            let cut_info = analysis::find_cut(&variant.pattern);
-            let first_pat = variant.pattern.first();
-            let is_nullable = first_pat.is_none_or(analysis::is_nullable);
-
-            let peek_token_obj = if !is_nullable {
-                first_pat.and_then(|f| {
-                    analysis::get_simple_peek(f, _custom_keywords)
-                        .ok()
-                        .flatten()
-                })
-            } else {
-                None
-            };
+    let generate_arm = |variant: &RuleVariant| -> Result<TokenStream> {
+        let failure_rec = failure_record(variant);
 
-            let peek_str = if !is_nullable {
-                analysis::get_peek_token_string(&variant.pattern)
-            } else {
-                None
-            };
+        let cut_info = analysis::find_cut(&variant.pattern);
+        let first_pat = variant.pattern.first();
+        let is_nullable = first_pat.is_none_or(analysis::is_nullable);
 
-            let is_unique = if let (_, Some(token_key)) = (&peek_token_obj, &peek_str) {
-                token_counts
-                    .get(token_key)
-                    .map(|c| *c == 1)
-                    .unwrap_or(false)
-            } else {
-                false
-            };
+        let peek_token_obj = if !is_nullable {
+            first_pat
+                .and_then(|f| cx.simple_peek(f).ok().flatten())
+                .map(|peek| cx.peek_cond(&peek))
+        } else {
+            None
+        };
+
+        let peek_str = if !is_nullable {
+            analysis::get_peek_token_string(&variant.pattern)
+        } else {
+            None
+        };
 
-            let logic = if let Some(cut) = cut_info {
-                let pre_cut = cut.pre_cut;
-                let post_cut = cut.post_cut;
-
-                let pre_bindings = analysis::collect_bindings(pre_cut);
-                let pre_logic = pattern::generate_sequence_steps(pre_cut, _custom_keywords)?;
-                let post_logic = pattern::generate_sequence_steps(post_cut, _custom_keywords)?;
-                let action = &variant.action;
-
-                let logic_block = if is_unique {
-                    quote! {
-                        {
-                            let mut run = || -> syn::Result<_> {
-                                #pre_logic
-                                #post_logic
-                                Ok({ #action })
-                            };
-                            match run() {
-                                Ok(v) => return Ok(v),
-                                Err(e) => {
-                                    ctx.set_fatal(true); // Use ctx
-                                    return Err(e);
-                                }
+        let is_unique = if let (_, Some(token_key)) = (&peek_token_obj, &peek_str) {
+            token_counts
+                .get(token_key)
+                .map(|c| *c == 1)
+                .unwrap_or(false)
+        } else {
+            false
+        };
+
+        let logic = if let Some(cut) = cut_info {
+            let pre_cut = cut.pre_cut;
+            let post_cut = cut.post_cut;
+
+            let pre_bindings = analysis::collect_bindings(pre_cut);
+            let pre_logic = pattern::generate_sequence_steps(pre_cut, cx)?;
+            let post_logic = pattern::generate_sequence_steps(post_cut, cx)?;
+            let action = &variant.action;
+
+            let logic_block = if is_unique {
+                quote! {
+                    {
+                        let mut run = || -> syn::Result<_> {
+                            #pre_logic
+                            #post_logic
+                            Ok({ #action })
+                        };
+                        match run() {
+                            Ok(v) => return Ok(v),
+                            Err(e) => {
+                                ctx.set_fatal(true); // Use ctx
+                                return Err(e);
                             }
                         }
                     }
-                } else {
-                    quote! {
-                        // Pass ctx to attempt
-                        let pre_result = rt::attempt(input, ctx, |mut input, ctx| {
-                            #pre_logic
-                            Ok(( #(#pre_bindings),* ))
-                        })?;
-
-                        if let Some(( #(#pre_bindings),* )) = pre_result {
-                            let mut post_run = || -> syn::Result<_> {
-                                #post_logic
-                                Ok({ #action })
-                            };
-                            match post_run() {
-                                Ok(v) => return Ok(v),
-                                Err(e) => {
-                                    ctx.set_fatal(true); // Use ctx
-                                    return Err(e);
-                                }
+                }
+            } else {
+                quote! {
+                    // Pass ctx to attempt
+                    let pre_result = rt::attempt(input, ctx, |mut input, ctx| {
+                        #pre_logic
+                        Ok(( #(#pre_bindings),* ))
+                    })?;
+
+                    if let Some(( #(#pre_bindings),* )) = pre_result {
+                        let mut post_run = || -> syn::Result<_> {
+                            #post_logic
+                            Ok({ #action })
+                        };
+                        match post_run() {
+                            Ok(v) => return Ok(v),
+                            Err(e) => {
+                                ctx.set_fatal(true); // Use ctx
+                                return Err(e);
                             }
                         }
                     }
-                };
+                }
+            };
 
-                if let Some(token_code) = peek_token_obj {
-                     quote! {
-                        if input.peek(#token_code) {
-                            #logic_block
-                        }
+            if let Some(token_code) = peek_token_obj {
+                quote! {
+                    if #token_code {
+                        #logic_block
                     }
-                } else {
-                    logic_block
                 }
             } else {
-                let logic = pattern::generate_sequence(
-                    &variant.pattern,
-                    &variant.action,
-                    _custom_keywords,
-                )?;
-
-                if is_unique {
-                    let token_code = peek_token_obj.as_ref().unwrap();
-                     quote! {
-                        if input.peek(#token_code) {
-                            let mut run = || -> syn::Result<_> {
-                                #logic
-                            };
-                            match run() {
-                                Ok(v) => return Ok(v),
-                                Err(e) => {
-                                    ctx.set_fatal(true); // Use ctx
-                                    return Err(e);
-                                }
-                            }
-                        }
-                    }
-                } else if let Some(token_code) = peek_token_obj {
-                     quote! {
-                        if input.peek(#token_code) {
-                            // Pass ctx to attempt
-                            if let Some(res) = rt::attempt(input, ctx, |mut input, ctx| { #logic })? {
-                                return Ok(res);
+                logic_block
+            }
+        } else {
+            let logic = pattern::generate_sequence(&variant.pattern, &variant.action, cx)?;
+
+            if is_unique {
+                let token_code = peek_token_obj.as_ref().unwrap();
+                quote! {
+                    if #token_code {
+                        let mut run = || -> syn::Result<_> {
+                            #logic
+                        };
+                        match run() {
+                            Ok(v) => return Ok(v),
+                            Err(e) => {
+                                ctx.set_fatal(true); // Use ctx
+                                return Err(e);
                             }
                         }
                     }
-                } else {
-                     quote! {
+                }
+            } else if let Some(token_code) = peek_token_obj {
+                quote! {
+                    if #token_code {
                         // Pass ctx to attempt
                         if let Some(res) = rt::attempt(input, ctx, |mut input, ctx| { #logic })? {
                             return Ok(res);
                         }
                     }
                 }
-            };
+            } else {
+                quote! {
+                    // Pass ctx to attempt
+                    if let Some(res) = rt::attempt(input, ctx, |mut input, ctx| { #logic })? {
+                        return Ok(res);
+                    }
+                }
+            }
+        };
 
-            Ok(quote! {
+        Ok(with_variant_attrs(
+            variant,
+            quote! {
                 #logic
                 #failure_rec
-            })
-        })
-        .collect::<Result<Vec<_>>>()?;
+            },
+        ))
+    };
+
+    // Consecutive variants starting with the same rule call parse it once, and long runs of
+    // variants starting with distinct keywords are dispatched by a keyword table
+    let runs = if cx.hoist_prefixes {
+        analysis::common_prefix_runs(variants, &cx.rule_names)
+    } else {
+        Vec::new()
+    };
+    let tables = analysis::keyword_table_runs(variants);
+    let mut arms = Vec::new();
+    let mut i = 0;
+    while i < variants.len() {
+        if let Some(run) = runs.iter().find(|r| r.start == i) {
+            arms.push(generate_hoisted_arm(&variants[run.clone()], cx)?);
+            i = run.end;
+        } else if let Some(run) = tables.iter().find(|r| r.start == i) {
+            arms.push(generate_keyword_table(&variants[run.clone()], cx)?);
+            i = run.end;
+        } else {
+            arms.push(generate_arm(&variants[i])?);
+            i += 1;
+        }
+    }
 
     let error_msg = if is_top_level {
         "No matching rule variant found"
@@ -0,0 +1,549 @@
+//! `unparse_<rule>` functions, the inverse of the parsers of rules whose actions only construct
+//! their value from the bindings of the alternative.
+//!
+//! The action of each alternative is turned into a Rust pattern, e.g. `Expr::Add { l, r }`
+//! for `-> Expr::Add { l, r }`. The value is matched against the alternatives in order, and
+//! the first one whose bindings all print prints its literals and bound values in the order
+//! of the pattern. An action that is just a binding, like `t:term -> { t }`, passes the value
+//! on to the called rule; `paren(e:expr) -> { e }` thereby prints the parentheses a nested
+//! value needs.
+
+use super::rule;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use std::collections::{HashMap, HashSet};
+use syn_grammar_model::{analysis, model::*};
+
+/// Built-ins whose values print through `rt::unparse::Unparse`.
+const PRINTABLE_BUILTINS: &[&str] = &[
+    "ident",
+    "string",
+    "char",
+    "bool",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "f32",
+    "f64",
+    "alpha",
+    "digit",
+    "alphanumeric",
+    "hex_digit",
+    "oct_digit",
+    "any_byte",
+    "keyword",
+    "punct",
+    "oneof",
+    "rust_type",
+    "rust_block",
+    "lit_str",
+    "lit_int",
+    "lit_char",
+    "lit_bool",
+    "lit_float",
+    "outer_attrs",
+    "token_tree",
+    "balanced_group",
+];
+
+/// Built-ins that consume no tokens, so printing them prints nothing.
+const EMPTY_BUILTINS: &[&str] = &["eof", "whitespace", "position"];
+
+/// The rules that get an `unparse_<rule>` function and the patterns of their alternatives.
+struct Unparser<'a> {
+    rules: HashMap<String, &'a Rule>,
+    /// The rules whose alternatives can all be printed, narrowed down to a fixpoint.
+    printable: HashSet<String>,
+}
+
+/// Generates the `unparse_<rule>` functions of the rules whose alternatives all print.
+pub fn generate_unparsers(grammar: &GrammarDefinition) -> TokenStream {
+    let mut unparser = Unparser {
+        rules: grammar
+            .rules
+            .iter()
+            .map(|r| (r.name.to_string(), r))
+            .collect(),
+        printable: grammar
+            .rules
+            .iter()
+            .filter(|r| is_candidate(r))
+            .map(|r| r.name.to_string())
+            .collect(),
+    };
+
+    // Dropping a rule can make the rules calling it unprintable
+    loop {
+        let unprintable: Vec<String> = unparser
+            .printable
+            .iter()
+            .filter(|name| unparser.rule_arms(unparser.rules[*name]).is_none())
+            .cloned()
+            .collect();
+        if unprintable.is_empty() {
+            break;
+        }
+        for name in unprintable {
+            unparser.printable.remove(&name);
+        }
+    }
+
+    let functions = grammar
+        .rules
+        .iter()
+        .filter(|r| unparser.printable.contains(&r.name.to_string()))
+        .map(|r| unparser.generate_function(r));
+    quote!(#(#functions)*)
+}
+
+/// Rules that return their value as written, without parameters or generics.
+fn is_candidate(rule: &Rule) -> bool {
+    let has_attr = |name: &str| rule.attrs.iter().any(|a| a.path().is_ident(name));
+    rule.params.is_empty()
+        && rule.generics.params.is_empty()
+        && rule.precedence.is_none()
+        && !["lossless", "parsed", "derive_enum", "iter"]
+            .iter()
+            .any(|a| has_attr(a))
+}
+
+impl Unparser<'_> {
+    fn generate_function(&self, rule: &Rule) -> TokenStream {
+        let name = &rule.name;
+        let name_str = name.to_string();
+        let fn_name = format_ident!("unparse_{}", name);
+        let impl_name = format_ident!("__unparse_{}", name);
+        let ret_type = &rule.return_type;
+        let vis = rule::entry_vis(rule);
+        let cfg_attrs = rule::cfg_attrs(rule);
+        let doc = format!(
+            "Prints a value of rule `{}` back as tokens that parse to it, or `None` if no \
+             alternative of the rule constructs it.",
+            name
+        );
+        let arms = self.rule_arms(rule).unwrap_or_default();
+
+        // `_tried` holds the rules that were handed this value by alternatives that only pass
+        // it on, to stop at cycles such as `atom = paren(e:expr) -> { e }` for values that no
+        // alternative constructs
+        quote! {
+            #[doc = #doc]
+            #(#cfg_attrs)*
+            #vis fn #fn_name(_value: &#ret_type) -> Option<rt::TokenStream> {
+                #impl_name(_value, &mut Vec::new())
+            }
+
+            #(#cfg_attrs)*
+            #[allow(irrefutable_let_patterns, unreachable_patterns, unused_mut, clippy::all)]
+            fn #impl_name(_value: &#ret_type, _tried: &mut Vec<&'static str>) -> Option<rt::TokenStream> {
+                if _tried.contains(&#name_str) {
+                    return None;
+                }
+                _tried.push(#name_str);
+                let result = (|| {
+                    #(#arms)*
+                    None
+                })();
+                _tried.pop();
+                result
+            }
+        }
+    }
+
+    /// The match arms of the alternatives, or `None` if one of them cannot be printed.
+    fn rule_arms(&self, rule: &Rule) -> Option<Vec<TokenStream>> {
+        rule.variants
+            .iter()
+            .map(|variant| {
+                let action: syn::Expr = syn::parse2(variant.action.clone()).ok()?;
+                let bindings: HashSet<String> = analysis::collect_bindings(&variant.pattern)
+                    .iter()
+                    .map(|b| b.to_string())
+                    .collect();
+                let mut bound = Vec::new();
+                let pattern = action_pattern(&action, &bindings, &mut bound)?;
+                let mut unique = HashSet::new();
+                if !bound.iter().all(|b| unique.insert(b.clone())) {
+                    return None;
+                }
+                // `-> { t }` passes the value on, keeping track of the rules it went through
+                let passthrough = match &pattern {
+                    syn::Pat::Ident(p) => Some(p.ident.to_string()),
+                    _ => None,
+                };
+                let steps = self.sequence(&variant.pattern, &unique, passthrough.as_deref())?;
+                Some(quote! {
+                    if let #pattern = _value {
+                        if let Some(_tokens) = (|| {
+                            let mut _tokens = rt::TokenStream::new();
+                            #steps
+                            Some(_tokens)
+                        })() {
+                            return Some(_tokens);
+                        }
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn sequence(
+        &self,
+        patterns: &[ModelPattern],
+        bound: &HashSet<String>,
+        passthrough: Option<&str>,
+    ) -> Option<TokenStream> {
+        let steps = patterns
+            .iter()
+            .map(|p| self.pattern(p, bound, passthrough))
+            .collect::<Option<Vec<_>>>()?;
+        Some(quote!(#(#steps)*))
+    }
+
+    /// The statements printing a pattern into `_tokens`, or `None` if it cannot be printed
+    /// because a value it matched is not in the action.
+    fn pattern(
+        &self,
+        pattern: &ModelPattern,
+        bound: &HashSet<String>,
+        passthrough: Option<&str>,
+    ) -> Option<TokenStream> {
+        match pattern {
+            ModelPattern::Cut(_)
+            | ModelPattern::Peek(_, _)
+            | ModelPattern::Not(_, _)
+            | ModelPattern::Follows(_, _) => Some(quote!()),
+            ModelPattern::Lit {
+                lit: syn::Lit::Str(s),
+                ..
+            } => {
+                let text = s.value();
+                text.parse::<TokenStream>().ok()?;
+                Some(quote!(rt::unparse::text(&mut _tokens, #text);))
+            }
+            ModelPattern::Lit { .. } => None,
+            ModelPattern::RuleCall {
+                binding: Some(binding),
+                module: None,
+                rule_name,
+                generics,
+                args,
+            } => {
+                if !bound.contains(&binding.to_string()) {
+                    return None;
+                }
+                self.value(binding, rule_name, generics, args, passthrough)
+            }
+            ModelPattern::RuleCall {
+                binding: None,
+                module: None,
+                rule_name,
+                generics,
+                args,
+            } if generics.is_empty() && args.is_empty() => {
+                let name = rule_name.to_string();
+                match self.rules.get(&name) {
+                    // Only rules returning `()` can be printed without a value
+                    Some(rule) => {
+                        let unit =
+                            matches!(&rule.return_type, syn::Type::Tuple(t) if t.elems.is_empty());
+                        if !unit || !self.printable.contains(&name) {
+                            return None;
+                        }
+                        let impl_name = format_ident!("__unparse_{}", rule_name);
+                        Some(quote!(_tokens.extend(#impl_name(&(), &mut Vec::new())?);))
+                    }
+                    None if EMPTY_BUILTINS.contains(&name.as_str()) => Some(quote!()),
+                    None => None,
+                }
+            }
+            ModelPattern::RuleCall { .. } => None,
+            ModelPattern::Group(alts, _) => {
+                // Without bindings, any alternative parses back to the same value
+                if alts.len() > 1 && !bindings_of(pattern).is_empty() {
+                    return None;
+                }
+                alts.iter()
+                    .find_map(|(seq, _)| self.sequence(seq, bound, passthrough))
+            }
+            ModelPattern::Bracketed(seq, _)
+            | ModelPattern::Braced(seq, _)
+            | ModelPattern::Parenthesized(seq, _) => {
+                let delimiter = match pattern {
+                    ModelPattern::Bracketed(..) => quote!(Bracket),
+                    ModelPattern::Braced(..) => quote!(Brace),
+                    _ => quote!(Parenthesis),
+                };
+                let inner = self.sequence(seq, bound, passthrough)?;
+                Some(quote! {
+                    let _inner = {
+                        let mut _tokens = rt::TokenStream::new();
+                        #inner
+                        _tokens
+                    };
+                    rt::unparse::group(&mut _tokens, rt::unparse::Delimiter::#delimiter, _inner);
+                })
+            }
+            ModelPattern::Scope(seq, _) => self.sequence(seq, bound, passthrough),
+            ModelPattern::Optional(inner, _) => {
+                let bindings = bindings_of(inner);
+                if bindings.is_empty() {
+                    return Some(quote!());
+                }
+                if !bindings.iter().all(|b| bound.contains(&b.to_string())) {
+                    return None;
+                }
+                let inner = self.pattern(inner, bound, None)?;
+                Some(quote! {
+                    if let (#(Some(#bindings),)*) = (#(#bindings,)*) {
+                        #inner
+                    }
+                })
+            }
+            ModelPattern::Repeat(inner, _)
+            | ModelPattern::Plus(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. } => {
+                let min = match pattern {
+                    ModelPattern::Repeat(..) => 0,
+                    ModelPattern::Plus(..) => 1,
+                    ModelPattern::Bounded { min, .. } => *min,
+                    _ => unreachable!(),
+                };
+                match &bindings_of(inner)[..] {
+                    [] => {
+                        let inner = self.pattern(inner, bound, None)?;
+                        Some(quote!(for _ in 0..#min { #inner }))
+                    }
+                    [binding] if bound.contains(&binding.to_string()) => {
+                        let inner = self.pattern(inner, bound, None)?;
+                        Some(quote!(for #binding in #binding { #inner }))
+                    }
+                    _ => None,
+                }
+            }
+            ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _) => self.pattern(inner, bound, passthrough),
+            // The verbatim tokens print the inner pattern as parsed
+            ModelPattern::Raw(inner, binding, _) => {
+                if bound.contains(&binding.to_string()) {
+                    Some(quote!(rt::unparse::Unparse::unparse(#binding, &mut _tokens);))
+                } else {
+                    self.pattern(inner, bound, passthrough)
+                }
+            }
+            ModelPattern::Collect(..)
+            | ModelPattern::Until { .. }
+            | ModelPattern::Recover { .. }
+            | ModelPattern::Fold { .. } => None,
+        }
+    }
+
+    /// The statements printing the value of `binding`, bound by a call of `rule_name`.
+    fn value(
+        &self,
+        binding: &syn::Ident,
+        rule_name: &syn::Ident,
+        generics: &[syn::Type],
+        args: &[Argument],
+        passthrough: Option<&str>,
+    ) -> Option<TokenStream> {
+        let name = rule_name.to_string();
+        if self.rules.contains_key(&name) {
+            if !generics.is_empty() || !args.is_empty() || !self.printable.contains(&name) {
+                return None;
+            }
+            let impl_name = format_ident!("__unparse_{}", rule_name);
+            let tried = match passthrough {
+                Some(p) if binding == p => quote!(_tried),
+                _ => quote!(&mut Vec::new()),
+            };
+            return Some(quote!(_tokens.extend(#impl_name(#binding, #tried)?);));
+        }
+        if !generics.is_empty() {
+            return None;
+        }
+        let positional: Vec<&ModelPattern> = args
+            .iter()
+            .filter_map(|a| match a {
+                Argument::Positional(p) => Some(p),
+                Argument::Named(..) => None,
+            })
+            .collect();
+        match name.as_str() {
+            "separated" | "repeated" => {
+                // The items are printed through the value of `binding` from the item pattern
+                let item = match positional.first() {
+                    Some(ModelPattern::RuleCall {
+                        binding: None,
+                        module: None,
+                        rule_name,
+                        generics,
+                        args,
+                    }) => self.value(binding, rule_name, generics, args, None)?,
+                    _ => return None,
+                };
+                let separator = match (name.as_str(), positional.get(1)) {
+                    ("separated", Some(sep)) if bindings_of(sep).is_empty() => {
+                        self.pattern(sep, &HashSet::new(), None)?
+                    }
+                    ("separated", _) => return None,
+                    _ => quote!(),
+                };
+                Some(quote! {
+                    for (_i, #binding) in #binding.iter().enumerate() {
+                        if _i > 0 {
+                            #separator
+                        }
+                        #item
+                    }
+                })
+            }
+            "parse_in" => {
+                // The contents are printed through the value of `binding` and quoted
+                let contents = match positional[..] {
+                    [ModelPattern::RuleCall {
+                        binding: None,
+                        rule_name: source,
+                        args: source_args,
+                        ..
+                    }, ModelPattern::RuleCall {
+                        binding: None,
+                        module: None,
+                        rule_name,
+                        generics,
+                        args,
+                    }] if (source == "lit_str" || source == "string") && source_args.is_empty() => {
+                        self.value(binding, rule_name, generics, args, None)?
+                    }
+                    _ => return None,
+                };
+                Some(quote! {
+                    let _contents = {
+                        let mut _tokens = rt::TokenStream::new();
+                        #contents
+                        _tokens
+                    };
+                    rt::unparse::string(&mut _tokens, &_contents.to_string());
+                })
+            }
+            _ if PRINTABLE_BUILTINS.contains(&name.as_str())
+                || name
+                    .strip_prefix("spanned_")
+                    .is_some_and(|n| PRINTABLE_BUILTINS.contains(&n)) =>
+            {
+                if ["keyword", "punct", "oneof", "balanced_group"].contains(&name.as_str())
+                    || args.is_empty()
+                {
+                    Some(quote!(rt::unparse::Unparse::unparse(#binding, &mut _tokens);))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn bindings_of(pattern: &ModelPattern) -> Vec<syn::Ident> {
+    analysis::collect_bindings(std::slice::from_ref(pattern))
+}
+
+/// The Rust pattern matching the values an action constructs, with the `bindings` of the
+/// alternative as pattern variables collected into `bound`. `None` if the action does more
+/// than construct the value, e.g. calls a method or computes.
+fn action_pattern(
+    expr: &syn::Expr,
+    bindings: &HashSet<String>,
+    bound: &mut Vec<String>,
+) -> Option<syn::Pat> {
+    let path_pattern = |path: &syn::Path| -> Option<syn::Pat> {
+        // Structs, variants and constants by naming convention, not functions like
+        // `Vec::new` or variables, which a pattern would bind instead
+        let last = path.segments.last()?;
+        let is_constant =
+            last.arguments.is_empty() && last.ident.to_string().starts_with(char::is_uppercase);
+        is_constant.then(|| syn::parse_quote!(#path))
+    };
+    match expr {
+        syn::Expr::Path(p) if p.qself.is_none() => match p.path.get_ident() {
+            Some(ident) if bindings.contains(&ident.to_string()) => {
+                bound.push(ident.to_string());
+                Some(syn::parse_quote!(#ident))
+            }
+            _ => path_pattern(&p.path),
+        },
+        syn::Expr::Struct(s) if s.qself.is_none() => {
+            let path = &s.path;
+            let fields = s
+                .fields
+                .iter()
+                .map(|f| {
+                    let member = &f.member;
+                    let pat = action_pattern(&f.expr, bindings, bound)?;
+                    Some(match (&f.member, &pat) {
+                        (syn::Member::Named(m), syn::Pat::Ident(p)) if p.ident == *m => {
+                            quote!(#member)
+                        }
+                        _ => quote!(#member: #pat),
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            // `..Default::default()` of a spread action
+            let rest = s.rest.as_ref().map(|_| quote!(..));
+            Some(syn::parse_quote!(#path { #(#fields,)* #rest }))
+        }
+        syn::Expr::Call(c) => {
+            let syn::Expr::Path(func) = &*c.func else {
+                return None;
+            };
+            let path = &func.path;
+            // A boxed binding derefs to the value of the rule it was bound by
+            if path.segments.len() == 2 && path.segments[0].ident == "Box" {
+                return match (path.segments[1].ident == "new", c.args.first()) {
+                    (true, Some(arg @ syn::Expr::Path(_))) if c.args.len() == 1 => {
+                        action_pattern(arg, bindings, bound)
+                    }
+                    _ => None,
+                };
+            }
+            path_pattern(path)?;
+            let args = c
+                .args
+                .iter()
+                .map(|a| action_pattern(a, bindings, bound))
+                .collect::<Option<Vec<_>>>()?;
+            Some(syn::parse_quote!(#path(#(#args),*)))
+        }
+        syn::Expr::Tuple(t) => {
+            let elems = t
+                .elems
+                .iter()
+                .map(|e| action_pattern(e, bindings, bound))
+                .collect::<Option<Vec<_>>>()?;
+            Some(syn::parse_quote!((#(#elems,)*)))
+        }
+        // String literals would not match `String` values
+        syn::Expr::Lit(l) if !matches!(l.lit, syn::Lit::Str(_) | syn::Lit::ByteStr(_)) => {
+            let lit = &l.lit;
+            Some(syn::parse_quote!(#lit))
+        }
+        syn::Expr::Paren(p) => action_pattern(&p.expr, bindings, bound),
+        syn::Expr::Block(b) if b.block.stmts.len() == 1 => match &b.block.stmts[0] {
+            syn::Stmt::Expr(e, None) => action_pattern(e, bindings, bound),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,165 @@
+//! `grammar_test!`: table-driven tests of the rules of a grammar.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{braced, Expr, Ident, LitStr, Path, Result, Token};
+
+mod kw {
+    syn::custom_keyword!(accept);
+    syn::custom_keyword!(reject);
+    syn::custom_keyword!(expecting);
+}
+
+/// The cases of a `grammar_test!` invocation, grouped by the rule they parse.
+pub struct TestSuite {
+    rules: Vec<RuleCases>,
+}
+
+/// `grammar::rule { case; .. }`
+struct RuleCases {
+    grammar: Path,
+    rule: Ident,
+    cases: Vec<Case>,
+}
+
+enum Case {
+    /// `accept "input"`, or `accept "input" => value` to compare the parsed value.
+    Accept {
+        input: LitStr,
+        value: Option<Box<Expr>>,
+    },
+    /// `reject "input"`, or `reject "input" expecting "message"` to check the error.
+    Reject {
+        input: LitStr,
+        expecting: Option<LitStr>,
+    },
+}
+
+impl Parse for TestSuite {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut rules = Vec::new();
+        while !input.is_empty() {
+            rules.push(input.parse()?);
+        }
+        Ok(Self { rules })
+    }
+}
+
+impl Parse for RuleCases {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut grammar: Path = input.parse()?;
+        let rule = match grammar.segments.pop() {
+            Some(rule) if !grammar.segments.is_empty() => rule.into_value().ident,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &grammar,
+                    "expected the grammar and the rule to test, e.g. `calc::expr`",
+                ))
+            }
+        };
+        // `calc::expr` leaves the separator after `calc`
+        grammar.segments.pop_punct();
+
+        let content;
+        braced!(content in input);
+        let mut cases = Vec::new();
+        while !content.is_empty() {
+            cases.push(content.parse()?);
+            if !content.is_empty() {
+                content.parse::<Token![;]>()?;
+            }
+        }
+        Ok(Self {
+            grammar,
+            rule,
+            cases,
+        })
+    }
+}
+
+impl Parse for Case {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let lookahead = input.lookahead1();
+        if lookahead.peek(kw::accept) {
+            input.parse::<kw::accept>()?;
+            let text = input.parse()?;
+            let value = if input.peek(Token![=>]) {
+                input.parse::<Token![=>]>()?;
+                Some(input.parse()?)
+            } else {
+                None
+            };
+            Ok(Case::Accept { input: text, value })
+        } else if lookahead.peek(kw::reject) {
+            input.parse::<kw::reject>()?;
+            let text = input.parse()?;
+            let expecting = if input.peek(kw::expecting) {
+                input.parse::<kw::expecting>()?;
+                Some(input.parse()?)
+            } else {
+                None
+            };
+            Ok(Case::Reject {
+                input: text,
+                expecting,
+            })
+        } else {
+            Err(lookahead.error())
+        }
+    }
+}
+
+/// Generates a `#[test]` function per case, named after the rule, the kind of the case and
+/// its position among the cases of the rule, e.g. `expr_accept_1` and `expr_reject_2`.
+pub fn generate_tests(suite: &TestSuite) -> TokenStream {
+    let mut tests = Vec::new();
+    for RuleCases {
+        grammar,
+        rule,
+        cases,
+    } in &suite.rules
+    {
+        let parse_fn = format_ident!("parse_{}", rule);
+        for (i, case) in cases.iter().enumerate() {
+            let (kind, input, assertion) = match case {
+                Case::Accept { input, value } => {
+                    let assertion = match value {
+                        Some(value) => quote!(assert_success_is(#value)),
+                        None => quote!(assert_success()),
+                    };
+                    ("accept", input, assertion)
+                }
+                Case::Reject { input, expecting } => {
+                    let assertion = match expecting {
+                        Some(message) => quote!(assert_failure_contains(#message)),
+                        None => quote!(assert_failure()),
+                    };
+                    ("reject", input, assertion)
+                }
+            };
+            let name = format_ident!("{}_{}_{}", rule, kind, i + 1);
+            let context = format!(
+                "{}::{} should {} `{}`",
+                quote!(#grammar).to_string().replace(' ', ""),
+                rule,
+                kind,
+                input.value()
+            );
+            tests.push(quote! {
+                #[test]
+                fn #name() {
+                    use syn::parse::Parser as _;
+                    use syn_grammar::testing::Testable as _;
+                    #grammar::#parse_fn
+                        .parse_str(#input)
+                        .test()
+                        .with_source(#input)
+                        .with_context(#context)
+                        .#assertion;
+                }
+            });
+        }
+    }
+    quote!(#(#tests)*)
+}
@@ -0,0 +1,494 @@
+//! Inlines rules marked `#[inline_rule]` into their callers.
+//!
+//! A call to an inline rule is replaced by the rule's pattern, so it is parsed without a
+//! function call or an `attempt` of its own, and errors inside it are reported in the calling
+//! rule. The rule itself is still generated for calls that are not inlined and as an entry
+//! point.
+//!
+//! Calls are inlined as follows:
+//! - If the action of the inline rule returns one of its bindings, as in
+//!   `rule ty -> Type = ":" t:ty_expr -> { t }`, the call is replaced by the pattern with that
+//!   binding renamed to the binding of the call, wherever the call appears.
+//! - Otherwise, a call at the top level of an alternative is replaced by the pattern, and the
+//!   action of the inline rule is evaluated at the start of the action of the alternative.
+//!   Its bindings are renamed so they do not clash with those of the alternative.
+//! - Other calls, e.g. inside a repetition or whose binding a guard or a later argument
+//!   uses, keep calling the rule.
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use std::collections::HashMap;
+use syn::{Ident, Result};
+use syn_grammar_model::analysis;
+use syn_grammar_model::model::*;
+use syn_grammar_model::parser;
+
+/// Attributes that change what a call to a rule does, which an inlined pattern would lose.
+const CALL_ATTRS: &[&str] = &[
+    "lossless",
+    "parsed",
+    "recover",
+    "expected",
+    "commit_after_first",
+    "derive_enum",
+];
+
+/// An inline rule with its own inline calls expanded.
+struct InlineRule {
+    pattern: Vec<ModelPattern>,
+    action: TokenStream,
+    return_type: syn::Type,
+    /// The binding the action returns, if it consists of nothing else.
+    returned: Option<Ident>,
+}
+
+pub fn inline_rules(rules: &mut [Rule]) -> Result<()> {
+    let marked: Vec<&Rule> = rules.iter().filter(|r| is_inline(r)).collect();
+    if marked.is_empty() {
+        return Ok(());
+    }
+    for rule in &marked {
+        check_inline_rule(rule)?;
+    }
+
+    // Expand the inline rules in their dependency order, rejecting cycles between them
+    let templates: HashMap<String, &Rule> =
+        marked.iter().map(|r| (r.name.to_string(), *r)).collect();
+    let mut inliner = Inliner::default();
+    for rule in &marked {
+        inliner.resolve(&rule.name, &templates, &mut Vec::new())?;
+    }
+
+    for rule in rules.iter_mut().filter(|r| r.precedence.is_none()) {
+        for variant in &mut rule.variants {
+            inliner.inline_variant(variant);
+        }
+    }
+    Ok(())
+}
+
+fn is_inline(rule: &Rule) -> bool {
+    rule.attrs.iter().any(|a| a.path().is_ident("inline_rule"))
+}
+
+fn check_inline_rule(rule: &Rule) -> Result<()> {
+    let attr = rule
+        .attrs
+        .iter()
+        .find(|a| a.path().is_ident("inline_rule"))
+        .expect("checked by is_inline");
+    attr.meta.require_path_only()?;
+    let fail = |message: &str| {
+        Err(syn::Error::new(
+            rule.name.span(),
+            format!("#[inline_rule] rule '{}' {}", rule.name, message),
+        ))
+    };
+    if rule.precedence.is_some() {
+        return fail("cannot be an operator precedence rule");
+    }
+    if rule.instantiation.is_some() || !rule.generics.params.is_empty() || !rule.params.is_empty() {
+        return fail("cannot have parameters or generics");
+    }
+    let [variant] = &rule.variants[..] else {
+        return fail("must have a single alternative");
+    };
+    if variant.guard.is_some() {
+        return fail("cannot have a `where` guard");
+    }
+    if analysis::find_cut(&variant.pattern).is_some() {
+        return fail("cannot contain a cut (`=>`)");
+    }
+    if calls(&variant.pattern, "super") {
+        return fail("cannot call `super`");
+    }
+    if rule.throws.is_some() {
+        return fail("cannot declare `throws`");
+    }
+    if parser::uses_span_var(&variant.action) {
+        return fail("cannot use `$span` in its action");
+    }
+    if let Some(attr) = rule
+        .attrs
+        .iter()
+        .find(|a| CALL_ATTRS.iter().any(|c| a.path().is_ident(c)))
+    {
+        let name = attr.path().get_ident().expect("checked above");
+        return fail(&format!("cannot be combined with #[{}]", name));
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Inliner {
+    resolved: HashMap<String, InlineRule>,
+    /// Numbers the inlined calls, to give their bindings unique names.
+    count: usize,
+}
+
+impl Inliner {
+    fn resolve(
+        &mut self,
+        name: &Ident,
+        templates: &HashMap<String, &Rule>,
+        stack: &mut Vec<String>,
+    ) -> Result<()> {
+        let key = name.to_string();
+        if self.resolved.contains_key(&key) {
+            return Ok(());
+        }
+        if stack.contains(&key) {
+            return Err(syn::Error::new(
+                name.span(),
+                format!(
+                    "#[inline_rule] rules cannot be recursive: {} -> {}",
+                    stack.join(" -> "),
+                    key
+                ),
+            ));
+        }
+        let rule = templates[&key];
+        stack.push(key.clone());
+        for callee in templates.keys() {
+            if calls(&rule.variants[0].pattern, callee) {
+                let callee = &templates[callee].name;
+                self.resolve(callee, templates, stack)?;
+            }
+        }
+        stack.pop();
+
+        let mut variant = rule.variants[0].clone();
+        self.inline_variant(&mut variant);
+        let returned = returned_binding(&variant);
+        self.resolved.insert(
+            key,
+            InlineRule {
+                pattern: variant.pattern,
+                action: variant.action,
+                return_type: rule.return_type.clone(),
+                returned,
+            },
+        );
+        Ok(())
+    }
+
+    fn inline_variant(&mut self, variant: &mut RuleVariant) {
+        let mut prefix = Vec::new();
+        let mut i = 0;
+        while i < variant.pattern.len() {
+            if let Some((binding, name)) = inline_call(&variant.pattern[i], &self.resolved) {
+                let later = &variant.pattern[i + 1..];
+                let used_early = binding.as_ref().is_some_and(|b| {
+                    variant
+                        .guard
+                        .as_ref()
+                        .is_some_and(|g| mentions(g.clone(), b))
+                        || later.iter().any(|p| passes_binding(p, b))
+                });
+                let callee = &self.resolved[&name];
+                // A `return` would leave the calling rule instead of the inlined one
+                let returns = mentions(callee.action.clone(), &format_ident!("return"));
+                if callee.returned.is_some() || !(used_early || returns) {
+                    let (pattern, action) = self.expand(&name, binding.as_ref());
+                    if let Some(action) = action {
+                        prefix.push(action);
+                    }
+                    let len = pattern.len();
+                    variant.pattern.splice(i..=i, pattern);
+                    i += len;
+                    continue;
+                }
+            }
+            self.inline_nested(&mut variant.pattern[i]);
+            i += 1;
+        }
+        if !prefix.is_empty() {
+            let action = &variant.action;
+            variant.action = quote! { #(#prefix)* #action };
+        }
+    }
+
+    /// Inlines calls below the top level of an alternative, which must produce a single
+    /// pattern and cannot evaluate an action.
+    fn inline_nested(&mut self, pattern: &mut ModelPattern) {
+        if let Some((binding, name)) = inline_call(pattern, &self.resolved) {
+            if self.resolved[&name].returned.is_some() {
+                let span = pattern.span();
+                let (seq, _) = self.expand(&name, binding.as_ref());
+                *pattern = ModelPattern::Group(vec![(seq, None)], span);
+            }
+            return;
+        }
+        match pattern {
+            ModelPattern::Group(alts, _) => {
+                for (seq, _) in alts {
+                    seq.iter_mut().for_each(|p| self.inline_nested(p));
+                }
+            }
+            ModelPattern::Bracketed(seq, _)
+            | ModelPattern::Braced(seq, _)
+            | ModelPattern::Parenthesized(seq, _)
+            | ModelPattern::Scope(seq, _) => {
+                seq.iter_mut().for_each(|p| self.inline_nested(p));
+            }
+            ModelPattern::Optional(inner, _)
+            | ModelPattern::Repeat(inner, _)
+            | ModelPattern::Plus(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. }
+            | ModelPattern::Collect(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Peek(inner, _)
+            | ModelPattern::Not(inner, _)
+            | ModelPattern::Follows(inner, _)
+            | ModelPattern::Until { pattern: inner, .. } => self.inline_nested(inner),
+            ModelPattern::Recover { body, .. } => self.inline_nested(body),
+            ModelPattern::Fold { item, op, .. } => {
+                self.inline_nested(item);
+                self.inline_nested(op);
+            }
+            ModelPattern::Cut(_) | ModelPattern::Lit { .. } | ModelPattern::RuleCall { .. } => {}
+        }
+    }
+
+    /// Returns the pattern of the inline rule `name` for a call with `binding`, and the
+    /// statement that evaluates its action unless it returns a binding.
+    fn expand(
+        &mut self,
+        name: &str,
+        binding: Option<&Ident>,
+    ) -> (Vec<ModelPattern>, Option<TokenStream>) {
+        self.count += 1;
+        let callee = &self.resolved[name];
+        let mut renames = HashMap::new();
+        for original in analysis::collect_bindings(&callee.pattern) {
+            let renamed = match (&callee.returned, binding) {
+                (Some(returned), Some(binding)) if *returned == original => binding.clone(),
+                _ => format_ident!("inlined{}_{}", self.count, original, span = original.span()),
+            };
+            renames.insert(original.to_string(), renamed);
+        }
+        let mut pattern = callee.pattern.clone();
+        pattern
+            .iter_mut()
+            .for_each(|p| rename_bindings(p, &renames));
+
+        if callee.returned.is_some() {
+            return (pattern, None);
+        }
+        let rebinds = renames.iter().map(|(original, renamed)| {
+            let original = Ident::new(original, renamed.span());
+            quote! { let #original = #renamed; }
+        });
+        let action = &callee.action;
+        let return_type = &callee.return_type;
+        let value = quote! { { #(#rebinds)* { #action } } };
+        let statement = match binding {
+            Some(binding) => quote! { let #binding: #return_type = #value; },
+            None => quote! { let _: #return_type = #value; },
+        };
+        (pattern, Some(statement))
+    }
+}
+
+/// Returns the binding and the rule name of a plain call to a resolved inline rule.
+fn inline_call(
+    pattern: &ModelPattern,
+    resolved: &HashMap<String, InlineRule>,
+) -> Option<(Option<Ident>, String)> {
+    match pattern {
+        ModelPattern::RuleCall {
+            binding,
+            module: None,
+            rule_name,
+            generics,
+            args,
+        } if generics.is_empty() && args.is_empty() => {
+            let name = rule_name.to_string();
+            resolved
+                .contains_key(&name)
+                .then(|| (binding.clone(), name))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the binding an action consists of, e.g. `t` for `-> { t }`.
+fn returned_binding(variant: &RuleVariant) -> Option<Ident> {
+    let mut tokens: Vec<TokenTree> = variant.action.clone().into_iter().collect();
+    while let [TokenTree::Group(group)] = &tokens[..] {
+        if group.delimiter() != proc_macro2::Delimiter::Brace {
+            break;
+        }
+        tokens = group.stream().into_iter().collect();
+    }
+    match &tokens[..] {
+        // Only a top-level binding holds the value of a single match
+        [TokenTree::Ident(ident)] => variant.pattern.iter().find_map(|p| match p {
+            ModelPattern::Lit {
+                binding: Some(b), ..
+            }
+            | ModelPattern::RuleCall {
+                binding: Some(b), ..
+            } if b == ident => Some(b.clone()),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Returns true if the tokens contain the identifier.
+fn mentions(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(i) => i == *ident,
+        TokenTree::Group(group) => mentions(group.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Returns true if the pattern passes the binding as an argument to a rule.
+fn passes_binding(pattern: &ModelPattern, binding: &Ident) -> bool {
+    let mut found = false;
+    visit_calls(pattern, &mut |name, args| {
+        found |= args.iter().any(|arg| {
+            let (Argument::Positional(p) | Argument::Named(_, p)) = arg;
+            matches!(p, ModelPattern::RuleCall { rule_name, args, .. } if rule_name == binding && args.is_empty())
+        });
+        let _ = name;
+    });
+    found
+}
+
+/// Returns true if the patterns call the rule `name`.
+fn calls(patterns: &[ModelPattern], name: &str) -> bool {
+    let mut found = false;
+    for pattern in patterns {
+        visit_calls(pattern, &mut |rule_name, _| found |= rule_name == name);
+    }
+    found
+}
+
+fn visit_calls(pattern: &ModelPattern, f: &mut impl FnMut(&Ident, &[Argument])) {
+    match pattern {
+        ModelPattern::RuleCall {
+            rule_name, args, ..
+        } => {
+            f(rule_name, args);
+            for arg in args {
+                let (Argument::Positional(p) | Argument::Named(_, p)) = arg;
+                visit_calls(p, f);
+            }
+        }
+        ModelPattern::Group(alts, _) => {
+            for (seq, _) in alts {
+                seq.iter().for_each(|p| visit_calls(p, f));
+            }
+        }
+        ModelPattern::Bracketed(seq, _)
+        | ModelPattern::Braced(seq, _)
+        | ModelPattern::Parenthesized(seq, _)
+        | ModelPattern::Scope(seq, _) => seq.iter().for_each(|p| visit_calls(p, f)),
+        ModelPattern::Optional(inner, _)
+        | ModelPattern::Repeat(inner, _)
+        | ModelPattern::Plus(inner, _)
+        | ModelPattern::Bounded { pattern: inner, .. }
+        | ModelPattern::Collect(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Peek(inner, _)
+        | ModelPattern::Not(inner, _)
+        | ModelPattern::Follows(inner, _)
+        | ModelPattern::Until { pattern: inner, .. } => visit_calls(inner, f),
+        ModelPattern::Recover { body, sync, .. } => {
+            visit_calls(body, f);
+            visit_calls(sync, f);
+        }
+        ModelPattern::Fold { item, op, .. } => {
+            visit_calls(item, f);
+            visit_calls(op, f);
+        }
+        ModelPattern::Cut(_) | ModelPattern::Lit { .. } => {}
+    }
+}
+
+/// Renames the bindings of an inlined pattern, and the uses of them as rule arguments.
+fn rename_bindings(pattern: &mut ModelPattern, renames: &HashMap<String, Ident>) {
+    let rename = |ident: &mut Ident| {
+        if let Some(renamed) = renames.get(&ident.to_string()) {
+            *ident = renamed.clone();
+        }
+    };
+    match pattern {
+        ModelPattern::Lit { binding, .. } => binding.iter_mut().for_each(rename),
+        ModelPattern::RuleCall {
+            binding,
+            rule_name,
+            generics,
+            args,
+            ..
+        } => {
+            binding.iter_mut().for_each(rename);
+            // A binding passed as an argument is parsed as a call without arguments
+            if generics.is_empty() && args.is_empty() {
+                rename(rule_name);
+            }
+            for arg in args {
+                let (Argument::Positional(p) | Argument::Named(_, p)) = arg;
+                rename_bindings(p, renames);
+            }
+        }
+        ModelPattern::SpanBinding(inner, ident, _) | ModelPattern::Raw(inner, ident, _) => {
+            rename(ident);
+            rename_bindings(inner, renames);
+        }
+        ModelPattern::Recover { binding, body, .. } => {
+            binding.iter_mut().for_each(rename);
+            rename_bindings(body, renames);
+        }
+        ModelPattern::Until {
+            binding, pattern, ..
+        } => {
+            binding.iter_mut().for_each(rename);
+            rename_bindings(pattern, renames);
+        }
+        ModelPattern::Fold {
+            binding, item, op, ..
+        } => {
+            binding.iter_mut().for_each(rename);
+            rename_bindings(item, renames);
+            rename_bindings(op, renames);
+        }
+        ModelPattern::Group(alts, _) => {
+            for (seq, _) in alts {
+                seq.iter_mut().for_each(|p| rename_bindings(p, renames));
+            }
+        }
+        ModelPattern::Bracketed(seq, _)
+        | ModelPattern::Braced(seq, _)
+        | ModelPattern::Parenthesized(seq, _)
+        | ModelPattern::Scope(seq, _) => {
+            seq.iter_mut().for_each(|p| rename_bindings(p, renames));
+        }
+        ModelPattern::Optional(inner, _)
+        | ModelPattern::Repeat(inner, _)
+        | ModelPattern::Plus(inner, _)
+        | ModelPattern::Bounded { pattern: inner, .. }
+        | ModelPattern::Collect(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Peek(inner, _)
+        | ModelPattern::Not(inner, _)
+        | ModelPattern::Follows(inner, _) => rename_bindings(inner, renames),
+        ModelPattern::Cut(_) => {}
+    }
+}
@@ -4,11 +4,13 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn_grammar_model::parse_grammar;
+use syn_grammar_model::{analysis, parse_and_validate};
 
 // Include modules
 mod backend;
 mod codegen;
+mod grammar_test;
+mod inline;
 mod monomorphize;
 
 use backend::SynBackend;
@@ -32,22 +34,70 @@ use backend::SynBackend;
 pub fn grammar(input: TokenStream) -> TokenStream {
     // 1-3. Reusable pipeline: Parse, Transform, Validate
     // We convert proc_macro::TokenStream to proc_macro2::TokenStream via .into()
-    let mut m_ast = match parse_grammar::<SynBackend>(input.into()) {
+    let mut m_ast = match parse_and_validate::<SynBackend>(input.into()) {
         Ok(ast) => ast,
         Err(e) => return e.to_compile_error().into(),
     };
 
+    // Test-only rules are generated behind their `#[cfg(test)]`, so other rules must not call them
+    let test_only_calls = analysis::find_test_only_calls(&m_ast);
+    if let Some(err) = test_only_calls.into_iter().reduce(|mut acc, e| {
+        acc.combine(e);
+        acc
+    }) {
+        return err.to_compile_error().into();
+    }
+
+    // The `grammar_info` module describes the rules as written, before they are rewritten
+    let grammar_info = codegen::generate_grammar_info(&m_ast);
+    codegen::document_syntax(&mut m_ast);
+
+    // Indirect left recursion is rewritten as in `parse_grammar`
+    analysis::eliminate_indirect_left_recursion(&mut m_ast);
+
     // 3.5. Monomorphization
     let monomorphizer = monomorphize::Monomorphizer::new(m_ast.rules);
     m_ast.rules = monomorphizer.process();
 
+    // Calls to `#[inline_rule]` rules are replaced by their patterns
+    if let Err(e) = inline::inline_rules(&mut m_ast.rules) {
+        return e.to_compile_error().into();
+    }
+
     // 4. Code Generation: From model to finished Rust code (codegen.rs)
-    match codegen::generate_rust(m_ast) {
+    match codegen::generate_rust(m_ast, grammar_info) {
         Ok(stream) => stream.into(),           // Successful code
         Err(e) => e.to_compile_error().into(), // Emit generation error as compiler error
     }
 }
 
+/// Table-driven tests of the rules of a grammar.
+///
+/// Each `grammar::rule { .. }` block lists inputs the rule must accept, optionally with the
+/// value it must return, and inputs it must reject, optionally with a part of the error
+/// message. Every case becomes a `#[test]` function.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use syn_grammar::grammar_test;
+///
+/// grammar_test! {
+///     calc::expression {
+///         accept "1 + 2";
+///         accept "1 + 2 + 3" => 6;
+///         reject "1 + *" expecting "unexpected token";
+///     }
+/// }
+/// ```
+#[proc_macro]
+pub fn grammar_test(input: TokenStream) -> TokenStream {
+    match syn::parse::<grammar_test::TestSuite>(input) {
+        Ok(suite) => grammar_test::generate_tests(&suite).into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 #[doc(hidden)]
 #[proc_macro]
 pub fn include_grammar(_input: TokenStream) -> TokenStream {
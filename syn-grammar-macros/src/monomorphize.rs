@@ -4,7 +4,7 @@ use quote::format_ident;
 use std::collections::{hash_map::DefaultHasher, HashMap};
 use std::hash::{Hash, Hasher};
 use syn::visit_mut::VisitMut;
-use syn::{parse_quote, Ident, Type};
+use syn::{parse_quote, GenericParam, Ident, Type};
 use syn_grammar_model::model::*;
 use syn_grammar_model::Backend;
 
@@ -30,7 +30,13 @@ impl Monomorphizer {
         }
 
         for rule in rules {
-            let has_generics = !rule.generics.params.is_empty();
+            // Lifetime parameters are kept in the generated signatures, only type and const
+            // parameters make a rule a template
+            let has_generics = rule
+                .generics
+                .params
+                .iter()
+                .any(|p| !matches!(p, GenericParam::Lifetime(_)));
             let has_untyped_params = rule.params.iter().any(|(_, ty)| ty.is_none());
             let is_generic = has_generics || has_untyped_params;
 
@@ -81,9 +87,10 @@ impl Monomorphizer {
                 }
 
                 if let Some(template) = self.templates.get(rule_name).cloned() {
-                    let new_name = self.instantiate(&template, args);
+                    let call_site = rule_name.span();
+                    let (new_name, value_args) = self.instantiate(&template, args, call_site);
                     *rule_name = new_name;
-                    args.clear();
+                    *args = value_args;
                 }
             }
             ModelPattern::Group(alts, _) => {
@@ -95,7 +102,8 @@ impl Monomorphizer {
             }
             ModelPattern::Bracketed(p, _)
             | ModelPattern::Braced(p, _)
-            | ModelPattern::Parenthesized(p, _) => {
+            | ModelPattern::Parenthesized(p, _)
+            | ModelPattern::Scope(p, _) => {
                 for sub in p {
                     self.expand_pattern(sub);
                 }
@@ -103,28 +111,54 @@ impl Monomorphizer {
             ModelPattern::Optional(p, _)
             | ModelPattern::Repeat(p, _)
             | ModelPattern::Plus(p, _)
+            | ModelPattern::Bounded { pattern: p, .. }
             | ModelPattern::SpanBinding(p, _, _)
+            | ModelPattern::Raw(p, _, _)
+            | ModelPattern::Expected(p, _, _)
+            | ModelPattern::Commit(p, _)
+            | ModelPattern::Declare(p, _)
+            | ModelPattern::Resolve(p, _)
+            | ModelPattern::Collect(p, _, _)
             | ModelPattern::Peek(p, _)
-            | ModelPattern::Not(p, _) => {
+            | ModelPattern::Not(p, _)
+            | ModelPattern::Follows(p, _) => {
                 self.expand_pattern(p);
             }
             ModelPattern::Recover { body, sync, .. } => {
                 self.expand_pattern(body);
                 self.expand_pattern(sync);
             }
+            ModelPattern::Fold { item, op, .. } => {
+                self.expand_pattern(item);
+                self.expand_pattern(op);
+            }
             _ => {}
         }
     }
 
-    fn instantiate(&mut self, template: &Rule, args: &[Argument]) -> Ident {
-        // Extract ModelPatterns from Arguments (ignoring names for instantiation key)
-        let model_patterns: Vec<&ModelPattern> = args
-            .iter()
-            .map(|a| match a {
-                Argument::Positional(p) => p,
-                Argument::Named(_, p) => p, // Should we error? Or just use value? Using value is safer for key.
-            })
-            .collect();
+    /// Instantiates `template` for the grammar arguments among `args` (those passed to
+    /// untyped parameters), returning the new rule name and the remaining value arguments.
+    fn instantiate(
+        &mut self,
+        template: &Rule,
+        args: &[Argument],
+        call_site: Span,
+    ) -> (Ident, Vec<Argument>) {
+        // Split the arguments by parameter kind: patterns for untyped parameters are
+        // substituted into the rule, values for typed parameters are still passed at runtime
+        let mut model_patterns: Vec<&ModelPattern> = Vec::new();
+        let mut value_args = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            let is_value = template.params.get(i).is_some_and(|(_, ty)| ty.is_some());
+            if is_value {
+                value_args.push(arg.clone());
+            } else {
+                model_patterns.push(match arg {
+                    Argument::Positional(p) => p,
+                    Argument::Named(_, p) => p, // Should we error? Or just use value? Using value is safer for key.
+                });
+            }
+        }
 
         let args_repr = model_patterns
             .iter()
@@ -134,7 +168,7 @@ impl Monomorphizer {
         let key = (template.name.clone(), args_repr.clone());
 
         if let Some(name) = self.instantiations.get(&key) {
-            return name.clone();
+            return (name.clone(), value_args);
         }
 
         let mut hasher = DefaultHasher::new();
@@ -159,8 +193,17 @@ impl Monomorphizer {
 
         let mut new_rule = template.clone();
         new_rule.name = new_name.clone();
+        new_rule.instantiation = Some(Instantiation {
+            template: template.name.clone(),
+            call_site,
+        });
         let old_generics = new_rule.generics.clone();
-        new_rule.generics.params.clear();
+        new_rule.generics.params = old_generics
+            .params
+            .iter()
+            .filter(|p| matches!(p, GenericParam::Lifetime(_)))
+            .cloned()
+            .collect();
 
         new_rule.params.retain(|(_, ty)| ty.is_some());
 
@@ -179,7 +222,7 @@ impl Monomorphizer {
             .map(|tp| tp.ident.clone())
             .collect();
 
-        if generic_params.len() <= args.len() {
+        if generic_params.len() <= model_patterns.len() {
             for (i, gp) in generic_params.iter().enumerate() {
                 let arg = model_patterns[i];
                 if let Some(ty) = self.infer_type(arg) {
@@ -193,6 +236,9 @@ impl Monomorphizer {
         };
 
         type_substituter.visit_type_mut(&mut new_rule.return_type);
+        if let Some(throws) = &mut new_rule.throws {
+            type_substituter.visit_type_mut(throws);
+        }
 
         if let Some(where_clause) = &mut new_rule.generics.where_clause {
             type_substituter.visit_where_clause_mut(where_clause);
@@ -209,7 +255,7 @@ impl Monomorphizer {
             .insert(new_name.clone(), new_rule.return_type.clone());
         self.pending_rules.push(new_rule);
 
-        new_name
+        (new_name, value_args)
     }
 
     fn infer_type(&self, pattern: &ModelPattern) -> Option<Type> {
@@ -240,21 +286,27 @@ impl<'a> ParamSubstituter<'a> {
 
                     if let Some(b) = old_binding {
                         match pattern {
-                            ModelPattern::RuleCall {
+                            ModelPattern::Lit {
                                 binding: ref mut new_b,
                                 ..
-                            } => {
-                                if new_b.is_none() {
-                                    *new_b = Some(b);
-                                }
                             }
-                            ModelPattern::Recover {
+                            | ModelPattern::RuleCall {
                                 binding: ref mut new_b,
                                 ..
-                            } => {
-                                if new_b.is_none() {
-                                    *new_b = Some(b);
-                                }
+                            }
+                            | ModelPattern::Recover {
+                                binding: ref mut new_b,
+                                ..
+                            }
+                            | ModelPattern::Until {
+                                binding: ref mut new_b,
+                                ..
+                            }
+                            | ModelPattern::Fold {
+                                binding: ref mut new_b,
+                                ..
+                            } if new_b.is_none() => {
+                                *new_b = Some(b);
                             }
                             _ => {}
                         }
@@ -278,7 +330,8 @@ impl<'a> ParamSubstituter<'a> {
             }
             ModelPattern::Bracketed(p, _)
             | ModelPattern::Braced(p, _)
-            | ModelPattern::Parenthesized(p, _) => {
+            | ModelPattern::Parenthesized(p, _)
+            | ModelPattern::Scope(p, _) => {
                 for sub in p {
                     self.visit_pattern(sub);
                 }
@@ -286,15 +339,27 @@ impl<'a> ParamSubstituter<'a> {
             ModelPattern::Optional(p, _)
             | ModelPattern::Repeat(p, _)
             | ModelPattern::Plus(p, _)
+            | ModelPattern::Bounded { pattern: p, .. }
             | ModelPattern::SpanBinding(p, _, _)
+            | ModelPattern::Raw(p, _, _)
+            | ModelPattern::Expected(p, _, _)
+            | ModelPattern::Commit(p, _)
+            | ModelPattern::Declare(p, _)
+            | ModelPattern::Resolve(p, _)
+            | ModelPattern::Collect(p, _, _)
             | ModelPattern::Peek(p, _)
-            | ModelPattern::Not(p, _) => {
+            | ModelPattern::Not(p, _)
+            | ModelPattern::Follows(p, _) => {
                 self.visit_pattern(p);
             }
             ModelPattern::Recover { body, sync, .. } => {
                 self.visit_pattern(body);
                 self.visit_pattern(sync);
             }
+            ModelPattern::Fold { item, op, .. } => {
+                self.visit_pattern(item);
+                self.visit_pattern(op);
+            }
             _ => {}
         }
     }
@@ -2,19 +2,324 @@ use crate::model::*;
 use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 use std::collections::{HashMap, HashSet, VecDeque};
+use syn::ext::IdentExt;
 use syn::{parse_quote, Lit, Result};
 
-/// Collects all custom keywords from the grammar
+/// Words that syn provides as `Token![...]` although they are not reserved Rust keywords.
+pub const SYN_WEAK_KEYWORDS: &[&str] = &["auto", "default", "raw", "union"];
+
+/// Decides whether a word in a token literal is matched as a custom keyword (`kw::word`)
+/// instead of a syn token (`Token![word]`):
+///
+/// | Word | Matched as |
+/// |---|---|
+/// | declared with `keyword "word";` or `keywords { word }` | `kw::word` |
+/// | reserved Rust keyword (`fn`, `self`, ...) or `_` | `Token![word]` |
+/// | weak keyword known to syn (`auto`, `default`, `raw`, `union`) | `Token![word]` |
+/// | any other identifier (e.g. `macro_rules`) | `kw::word` |
+pub fn is_custom_keyword(word: &str, declared: &HashSet<String>) -> bool {
+    declared.contains(word)
+        || (word != "_"
+            && !SYN_WEAK_KEYWORDS.contains(&word)
+            && syn::parse_str::<syn::Ident>(word).is_ok())
+}
+
+/// Collects all custom keywords from the grammar, following `is_custom_keyword`. Words
+/// declared with `keywords(rule) { .. }` count as declared, so that the result holds the `kw`
+/// types of every rule.
 pub fn collect_custom_keywords(grammar: &GrammarDefinition) -> HashSet<String> {
-    let mut kws = HashSet::new();
+    collect_keywords(grammar, declared_keywords(grammar, None))
+}
+
+/// Collects the custom keywords as seen from `rule`: like [`collect_custom_keywords`], but
+/// words declared with `keywords(..) { .. }` for other rules only are matched as syn tokens
+/// if they have one, e.g. `Token![union]`.
+pub fn collect_rule_keywords(grammar: &GrammarDefinition, rule: &str) -> HashSet<String> {
+    collect_keywords(grammar, declared_keywords(grammar, Some(rule)))
+}
+
+/// The words reserved in `rule` by `keywords { .. }` declarations, either global or scoped to
+/// the rule with `keywords(rule, ..) { .. }`, and the words of `keyword("word")` calls anywhere
+/// in the grammar, sorted. The `ident` built-in does not match them in the rule.
+pub fn reserved_keywords(grammar: &GrammarDefinition, rule: &str) -> Vec<String> {
+    let mut words: Vec<String> = grammar
+        .keyword_sets
+        .iter()
+        .filter(|set| set.rules.is_empty() || set.rules.iter().any(|r| r == rule))
+        .flat_map(|set| set.words.iter().map(|w| w.to_string()))
+        .chain(keyword_call_words(grammar))
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+/// The words matched with the `keyword("word")` built-in, unless the grammar defines its own
+/// `keyword` rule.
+fn keyword_call_words(grammar: &GrammarDefinition) -> Vec<String> {
+    let mut words = Vec::new();
+    if grammar.rules.iter().any(|r| r.name == "keyword") {
+        return words;
+    }
+    for variant in grammar.rules.iter().flat_map(|r| &r.variants) {
+        visit_rule_calls(&variant.pattern, &mut |name, args| {
+            if name == "keyword" {
+                if let [Argument::Positional(ModelPattern::Lit {
+                    lit: Lit::Str(word),
+                    ..
+                })] = args
+                {
+                    words.push(word.value());
+                }
+            }
+        });
+    }
+    words
+}
+
+/// Words declared with `keyword "word";` and `keywords { .. }`, limited to the declarations in
+/// the scope of `rule` if given.
+fn declared_keywords(grammar: &GrammarDefinition, rule: Option<&str>) -> HashSet<String> {
+    let scoped = grammar
+        .keyword_sets
+        .iter()
+        .filter(|set| match rule {
+            Some(rule) => set.rules.is_empty() || set.rules.iter().any(|r| r == rule),
+            None => true,
+        })
+        .flat_map(|set| set.words.iter().map(|w| w.to_string()));
+    grammar
+        .keywords
+        .iter()
+        .map(|k| k.value())
+        .chain(scoped)
+        .collect()
+}
+
+fn collect_keywords(grammar: &GrammarDefinition, declared: HashSet<String>) -> HashSet<String> {
+    let mut kws = declared.clone();
     grammar
         .rules
         .iter()
         .flat_map(|r| &r.variants)
-        .for_each(|v| collect_from_patterns(&v.pattern, &mut kws));
+        .for_each(|v| collect_from_patterns(&v.pattern, &declared, &mut kws));
     kws
 }
 
+/// Finds bindings named like a custom keyword of the grammar, e.g. `value:expr` in a grammar
+/// that also matches `"value"` (`kw::value`), and rules named like a keyword that other rules
+/// match, e.g. a rule `value` next to `"value" v:value` in another rule. Such names compile,
+/// but make it hard to tell keyword types, rule functions and bindings apart. A rule that
+/// matches its own name, such as `rule state = "state" ..`, is not reported. Returns one
+/// diagnostic per rule and per binding name of an alternative, spanned at the name; the
+/// diagnostic of a rule carries a note at a literal of another rule matching the keyword.
+///
+/// Also finds words with a syn token, such as `union`, that `keywords(rule) { .. }` declares
+/// for some rules only: the literals of other rules match them as `Token![union]`, which
+/// `ident` does not see as reserved and whose bindings have a different type than
+/// `kw::union`. Their diagnostics are spanned at the first such literal of each rule, with a
+/// note at the declaration.
+pub fn find_keyword_shadowing(grammar: &GrammarDefinition) -> Vec<syn::Error> {
+    let kws = collect_custom_keywords(grammar);
+    let mut literals: Vec<(&Ident, syn::LitStr)> = Vec::new();
+    for rule in &grammar.rules {
+        for variant in &rule.variants {
+            visit_token_literals(&variant.pattern, &mut |lit| {
+                literals.push((&rule.name, lit.clone()))
+            });
+        }
+    }
+    // A literal of a rule other than `rule` matching `word`
+    let literal_elsewhere = |rule: &Ident, word: &str| {
+        literals
+            .iter()
+            .find(|(r, lit)| *r != rule && literal_words(lit).iter().any(|w| w == word))
+            .map(|(_, lit)| lit)
+    };
+
+    let mut diagnostics = Vec::new();
+    for rule in &grammar.rules {
+        let name = rule.name.to_string();
+        if let Some(lit) = literal_elsewhere(&rule.name, &name).filter(|_| kws.contains(&name)) {
+            let mut diagnostic = syn::Error::new(
+                rule.name.span(),
+                format!(
+                    "Rule '{}' has the same name as the keyword \"{}\" (kw::{}); consider renaming the rule, e.g. to '{}_'",
+                    name, name, name, name
+                ),
+            );
+            diagnostic.combine(syn::Error::new(
+                lit.span(),
+                format!("the keyword \"{}\" is matched here", name),
+            ));
+            diagnostics.push(diagnostic);
+        }
+        for variant in &rule.variants {
+            let mut seen = HashSet::new();
+            for binding in collect_bindings(&variant.pattern) {
+                let word = binding.to_string();
+                if kws.contains(&word) && seen.insert(word.clone()) {
+                    diagnostics.push(syn::Error::new(
+                        binding.span(),
+                        format!(
+                            "Binding '{}' in rule '{}' has the same name as the keyword \"{}\" (kw::{}); consider renaming the binding, e.g. to '{}_'",
+                            word, rule.name, word, word, word
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Words with a syn token that are custom keywords in some rules only
+    let everywhere: HashSet<String> = grammar
+        .keywords
+        .iter()
+        .map(|k| k.value())
+        .chain(
+            grammar
+                .keyword_sets
+                .iter()
+                .filter(|set| set.rules.is_empty())
+                .flat_map(|set| set.words.iter().map(|w| w.to_string())),
+        )
+        .collect();
+    let mut reported = HashSet::new();
+    for set in grammar
+        .keyword_sets
+        .iter()
+        .filter(|set| !set.rules.is_empty())
+    {
+        for word in &set.words {
+            let text = word.to_string();
+            if everywhere.contains(&text) || is_custom_keyword(&text, &HashSet::new()) {
+                continue;
+            }
+            for (rule, lit) in &literals {
+                let in_scope = grammar.keyword_sets.iter().any(|s| {
+                    s.rules.iter().any(|r| r == *rule) && s.words.iter().any(|w| *w == text)
+                });
+                if in_scope
+                    || !literal_words(lit).contains(&text)
+                    || !reported.insert((rule.to_string(), text.clone()))
+                {
+                    continue;
+                }
+                let mut diagnostic = syn::Error::new(
+                    lit.span(),
+                    format!(
+                        "\"{}\" is matched as Token![{}] in rule '{}', but as the keyword kw::{} in the rules it is declared for; declare it for rule '{}' as well or with `keyword \"{}\";` to match it the same way everywhere",
+                        text, text, rule, text, rule, text
+                    ),
+                );
+                diagnostic.combine(syn::Error::new(
+                    word.span(),
+                    format!("\"{}\" is declared as a keyword here", text),
+                ));
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Finds bindings of rule calls that neither the action, the guard nor a later argument of the
+/// alternative uses, such as `a:attrs` in `a:attrs "fn" n:ident -> { n }`. `skip(attrs)`
+/// states the intent to discard the value. Bindings starting with `_` are ignored. Returns
+/// one diagnostic per binding, spanned at the binding.
+pub fn find_unused_bindings(grammar: &GrammarDefinition) -> Vec<syn::Error> {
+    fn action_idents(tokens: TokenStream, out: &mut HashSet<String>) {
+        for token in tokens {
+            match token {
+                proc_macro2::TokenTree::Ident(ident) => {
+                    out.insert(ident.to_string());
+                }
+                proc_macro2::TokenTree::Group(group) => action_idents(group.stream(), out),
+                _ => {}
+            }
+        }
+    }
+
+    fn rule_call_bindings<'a>(patterns: &'a [ModelPattern], out: &mut Vec<(&'a Ident, &'a Ident)>) {
+        for p in patterns {
+            match p {
+                ModelPattern::RuleCall {
+                    binding,
+                    rule_name,
+                    args,
+                    ..
+                } => {
+                    if let Some(b) = binding {
+                        out.push((b, rule_name));
+                    }
+                    for arg in args {
+                        let (Argument::Positional(p) | Argument::Named(_, p)) = arg;
+                        rule_call_bindings(std::slice::from_ref(p), out);
+                    }
+                }
+                ModelPattern::Group(alts, _) => {
+                    for (alt, _) in alts {
+                        rule_call_bindings(alt, out);
+                    }
+                }
+                ModelPattern::Bracketed(seq, _)
+                | ModelPattern::Braced(seq, _)
+                | ModelPattern::Parenthesized(seq, _)
+                | ModelPattern::Scope(seq, _) => rule_call_bindings(seq, out),
+                ModelPattern::Optional(inner, _)
+                | ModelPattern::Repeat(inner, _)
+                | ModelPattern::Plus(inner, _)
+                | ModelPattern::Bounded { pattern: inner, .. }
+                | ModelPattern::SpanBinding(inner, _, _)
+                | ModelPattern::Raw(inner, _, _)
+                | ModelPattern::Expected(inner, _, _)
+                | ModelPattern::Commit(inner, _)
+                | ModelPattern::Declare(inner, _)
+                | ModelPattern::Resolve(inner, _)
+                | ModelPattern::Collect(inner, _, _)
+                | ModelPattern::Peek(inner, _)
+                | ModelPattern::Recover { body: inner, .. } => {
+                    rule_call_bindings(std::slice::from_ref(inner), out)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut diagnostics = Vec::new();
+    for rule in &grammar.rules {
+        for variant in &rule.variants {
+            let mut bindings = Vec::new();
+            rule_call_bindings(&variant.pattern, &mut bindings);
+            if bindings.is_empty() {
+                continue;
+            }
+            // Bindings passed to later rule calls appear as calls without arguments
+            let mut used = HashSet::new();
+            action_idents(variant.action.clone(), &mut used);
+            if let Some(guard) = &variant.guard {
+                action_idents(guard.clone(), &mut used);
+            }
+            visit_rule_calls(&variant.pattern, &mut |name, _| {
+                used.insert(name.to_string());
+            });
+            for (binding, rule_name) in bindings {
+                let name = binding.to_string();
+                if !name.starts_with('_') && !used.contains(&name) {
+                    diagnostics.push(syn::Error::new(
+                        binding.span(),
+                        format!(
+                            "Binding '{}' in rule '{}' is never used; use `skip({})` to parse and discard the value",
+                            name, rule.name, rule_name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
 /// Result of analyzing a pattern sequence for a Cut operator (`=>`)
 pub struct CutAnalysis<'a> {
     pub pre_cut: &'a [ModelPattern],
@@ -52,58 +357,209 @@ pub fn split_left_recursive<'a>(
     (recursive, base)
 }
 
-fn collect_from_patterns(patterns: &[ModelPattern], kws: &mut HashSet<String>) {
+/// Finds runs of at least two consecutive variants that start with the same call to a grammar
+/// rule in `rules`, e.g. `p:path "." f:ident | p:path "(" ")"`, so that the call can be parsed
+/// once for the whole run instead of once per variant. Calls with arguments, variants with a
+/// cut or a `where` guard and variants that pass the prefix binding to a later call are not
+/// hoisted: the value of the call is handed to one variant, which must not fail after that.
+pub fn common_prefix_runs(
+    variants: &[RuleVariant],
+    rules: &HashSet<String>,
+) -> Vec<std::ops::Range<usize>> {
+    let hoistable_prefix = |v: &RuleVariant| -> Option<String> {
+        let Some(ModelPattern::RuleCall {
+            binding,
+            rule_name,
+            generics,
+            args,
+            ..
+        }) = v.pattern.first()
+        else {
+            return None;
+        };
+        let name = rule_name.to_string();
+        if !rules.contains(&name)
+            || !generics.is_empty()
+            || !args.is_empty()
+            || find_cut(&v.pattern).is_some()
+            || v.guard.is_some()
+        {
+            return None;
+        }
+        // The value is only handed over once the rest has matched
+        if let Some(b) = binding {
+            let mut uses_binding = false;
+            collect_called_rules(&v.pattern[1..], &mut |called| {
+                uses_binding |= b == &called;
+            });
+            if uses_binding {
+                return None;
+            }
+        }
+        Some(name)
+    };
+
+    let prefixes: Vec<Option<String>> = variants.iter().map(hoistable_prefix).collect();
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start < prefixes.len() {
+        let mut end = start + 1;
+        if prefixes[start].is_some() {
+            while end < prefixes.len() && prefixes[end] == prefixes[start] {
+                end += 1;
+            }
+            if end - start > 1 {
+                runs.push(start..end);
+            }
+        }
+        start = end;
+    }
+    runs
+}
+
+/// Minimum number of consecutive keyword alternatives dispatched by [`keyword_table_runs`].
+pub const MIN_KEYWORD_TABLE: usize = 8;
+
+/// Returns the word of a variant that starts with a single-word literal such as `"let"`.
+pub fn leading_keyword(variant: &RuleVariant) -> Option<String> {
+    let Some(ModelPattern::Lit {
+        lit: Lit::Str(lit), ..
+    }) = variant.pattern.first()
+    else {
+        return None;
+    };
+    if is_case_insensitive_literal(lit) {
+        return None;
+    }
+    let word = lit.value();
+    let is_word = word != "_" && syn::parse::Parser::parse_str(Ident::parse_any, &word).is_ok();
+    is_word.then_some(word)
+}
+
+/// Finds runs of at least [`MIN_KEYWORD_TABLE`] consecutive variants that start with a keyword
+/// no other variant starts with, e.g. the statements of a language, so that they can be
+/// dispatched with one `match` on the next identifier instead of a peek per variant. Since the
+/// keywords are distinct, at most one variant of a run can apply. Variants with a cut or a
+/// `where` guard are not included. An alternation of at least two distinct keywords and nothing else, such as
+/// `"red" | "green" | "blue"`, is a single run regardless of its length.
+pub fn keyword_table_runs(variants: &[RuleVariant]) -> Vec<std::ops::Range<usize>> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for v in variants {
+        if let Some(key) = get_peek_token_string(&v.pattern) {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+    }
+    let dispatchable = |v: &RuleVariant| {
+        find_cut(&v.pattern).is_none()
+            && v.guard.is_none()
+            && leading_keyword(v).is_some_and(|w| counts[&w] == 1)
+    };
+
+    if variants.len() >= 2
+        && variants
+            .iter()
+            .all(|v| v.pattern.len() == 1 && dispatchable(v))
+    {
+        return std::iter::once(0..variants.len()).collect();
+    }
+
+    let mut runs = Vec::new();
+    let mut start = 0;
+    while start < variants.len() {
+        let len = variants[start..]
+            .iter()
+            .take_while(|v| dispatchable(v))
+            .count();
+        if len >= MIN_KEYWORD_TABLE {
+            runs.push(start..start + len);
+        }
+        start += len.max(1);
+    }
+    runs
+}
+
+fn collect_from_patterns(
+    patterns: &[ModelPattern],
+    declared: &HashSet<String>,
+    kws: &mut HashSet<String>,
+) {
+    visit_token_literals(patterns, &mut |lit| {
+        for word in literal_words(lit) {
+            if is_custom_keyword(&word, declared) {
+                kws.insert(word);
+            }
+        }
+    });
+}
+
+/// The identifiers of a token literal, e.g. `macro_rules` in `"macro_rules!"`.
+fn literal_words(lit: &syn::LitStr) -> Vec<String> {
+    syn::parse_str::<proc_macro2::TokenStream>(&lit.value())
+        .map(|ts| {
+            ts.into_iter()
+                .filter_map(|token| match token {
+                    proc_macro2::TokenTree::Ident(ident) => Some(ident.to_string()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Calls `cb` with the string literals matched as tokens, including those in the arguments
+/// of rule calls and in lookahead.
+fn visit_token_literals<F: FnMut(&syn::LitStr)>(patterns: &[ModelPattern], cb: &mut F) {
     for p in patterns {
         match p {
+            // Case-insensitive literals are matched by their text, not as `kw::word`
             ModelPattern::Lit {
                 lit: Lit::Str(lit), ..
-            } => {
-                let s = lit.value();
-                // Try to tokenize the string literal to find identifiers
-                if let Ok(ts) = syn::parse_str::<proc_macro2::TokenStream>(&s) {
-                    for token in ts {
-                        if let proc_macro2::TokenTree::Ident(ident) = token {
-                            let s = ident.to_string();
-                            // If syn accepts it as an Ident, it's a candidate.
-                            // We rely on syn::parse_str::<syn::Ident> to filter out reserved keywords.
-                            // We also exclude "_" because it cannot be a struct name for custom_keyword!.
-                            if s != "_" && syn::parse_str::<syn::Ident>(&s).is_ok() {
-                                kws.insert(s);
-                            }
-                        }
-                    }
-                }
-            }
+            } if is_case_insensitive_literal(lit) => {}
+            ModelPattern::Lit {
+                lit: Lit::Str(lit), ..
+            } => cb(lit),
             ModelPattern::RuleCall { args, .. } => {
                 for arg in args {
                     match arg {
                         Argument::Positional(p) | Argument::Named(_, p) => {
-                            collect_from_patterns(std::slice::from_ref(p), kws);
+                            visit_token_literals(std::slice::from_ref(p), cb);
                         }
                     }
                 }
             }
             ModelPattern::Group(alts, _) => alts
                 .iter()
-                .for_each(|(alt, _)| collect_from_patterns(alt, kws)),
+                .for_each(|(alt, _)| visit_token_literals(alt, cb)),
             ModelPattern::Bracketed(s, _)
             | ModelPattern::Braced(s, _)
-            | ModelPattern::Parenthesized(s, _) => collect_from_patterns(s, kws),
+            | ModelPattern::Parenthesized(s, _)
+            | ModelPattern::Scope(s, _) => visit_token_literals(s, cb),
             ModelPattern::Optional(i, _)
             | ModelPattern::Repeat(i, _)
-            | ModelPattern::Plus(i, _) => collect_from_patterns(std::slice::from_ref(i), kws),
-            ModelPattern::SpanBinding(i, _, _) => {
-                collect_from_patterns(std::slice::from_ref(i), kws)
+            | ModelPattern::Plus(i, _)
+            | ModelPattern::Bounded { pattern: i, .. } => {
+                visit_token_literals(std::slice::from_ref(i), cb)
             }
+            ModelPattern::SpanBinding(i, _, _)
+            | ModelPattern::Raw(i, _, _)
+            | ModelPattern::Expected(i, _, _)
+            | ModelPattern::Commit(i, _)
+            | ModelPattern::Declare(i, _)
+            | ModelPattern::Resolve(i, _)
+            | ModelPattern::Collect(i, _, _) => visit_token_literals(std::slice::from_ref(i), cb),
             ModelPattern::Recover { body, sync, .. } => {
-                collect_from_patterns(std::slice::from_ref(body), kws);
-                collect_from_patterns(std::slice::from_ref(sync), kws);
+                visit_token_literals(std::slice::from_ref(body), cb);
+                visit_token_literals(std::slice::from_ref(sync), cb);
             }
-            ModelPattern::Peek(i, _) | ModelPattern::Not(i, _) => {
-                collect_from_patterns(std::slice::from_ref(i), kws)
+            ModelPattern::Peek(i, _) | ModelPattern::Not(i, _) | ModelPattern::Follows(i, _) => {
+                visit_token_literals(std::slice::from_ref(i), cb)
             }
             ModelPattern::Until { pattern, .. } => {
-                collect_from_patterns(std::slice::from_ref(pattern), kws);
+                visit_token_literals(std::slice::from_ref(pattern), cb);
+            }
+            ModelPattern::Fold { item, op, .. } => {
+                visit_token_literals(std::slice::from_ref(item), cb);
+                visit_token_literals(std::slice::from_ref(op), cb);
             }
             _ => {}
         }
@@ -122,15 +578,17 @@ pub fn collect_bindings(patterns: &[ModelPattern]) -> Vec<Ident> {
             } => bindings.push(b.clone()),
             ModelPattern::Repeat(inner, _)
             | ModelPattern::Plus(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. }
             | ModelPattern::Optional(inner, _) => {
                 bindings.extend(collect_bindings(std::slice::from_ref(inner)));
             }
             ModelPattern::Parenthesized(s, _)
             | ModelPattern::Bracketed(s, _)
-            | ModelPattern::Braced(s, _) => {
+            | ModelPattern::Braced(s, _)
+            | ModelPattern::Scope(s, _) => {
                 bindings.extend(collect_bindings(s));
             }
-            ModelPattern::SpanBinding(inner, ident, _) => {
+            ModelPattern::SpanBinding(inner, ident, _) | ModelPattern::Raw(inner, ident, _) => {
                 bindings.push(ident.clone());
                 bindings.extend(collect_bindings(std::slice::from_ref(inner)));
             }
@@ -141,7 +599,12 @@ pub fn collect_bindings(patterns: &[ModelPattern]) -> Vec<Ident> {
                     bindings.extend(collect_bindings(std::slice::from_ref(body)));
                 }
             }
-            ModelPattern::Peek(inner, _) => {
+            ModelPattern::Peek(inner, _)
+            | ModelPattern::Collect(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _) => {
                 bindings.extend(collect_bindings(std::slice::from_ref(inner)));
             }
             ModelPattern::Group(alts, _) => {
@@ -152,6 +615,9 @@ pub fn collect_bindings(patterns: &[ModelPattern]) -> Vec<Ident> {
             ModelPattern::Not(_, _) => {
                 // Not(...) bindings are ignored/dropped because it only succeeds if inner fails.
             }
+            ModelPattern::Follows(_, _) => {
+                // Follows(...) only checks tokens that were already consumed and bound.
+            }
             ModelPattern::Until {
                 binding, pattern, ..
             } => {
@@ -160,12 +626,29 @@ pub fn collect_bindings(patterns: &[ModelPattern]) -> Vec<Ident> {
                 }
                 bindings.extend(collect_bindings(std::slice::from_ref(pattern)));
             }
+            // The bindings of the item and operator are only visible to the fold expression
+            ModelPattern::Fold {
+                binding: Some(b), ..
+            } => bindings.push(b.clone()),
             _ => {}
         }
     }
     bindings
 }
 
+/// Returns true for a literal with the `ident` suffix, such as `"self"ident`, which matches the
+/// word as an identifier and binds a `syn::Ident` instead of the keyword token.
+pub fn is_ident_literal(lit: &syn::LitStr) -> bool {
+    lit.suffix() == "ident"
+}
+
+/// Returns true for a literal with the `i` suffix, such as `"select"i`, which matches an
+/// identifier spelled like the word in any case, e.g. `SELECT` or `Select`. It has no token
+/// type: it is matched by comparing the text of the identifier, so it is never peeked.
+pub fn is_case_insensitive_literal(lit: &syn::LitStr) -> bool {
+    lit.suffix() == "i"
+}
+
 /// Returns the sequence of tokens for syn::parse::<Token>()
 pub fn resolve_token_types(
     lit: &syn::LitStr,
@@ -173,6 +656,39 @@ pub fn resolve_token_types(
 ) -> Result<Vec<syn::Type>> {
     let s = lit.value();
 
+    // `"self"ident` matches the word as an identifier. syn matches keyword tokens such as
+    // `Token![self]` by the text of the identifier, so the token type stays the same; the
+    // binding is a `syn::Ident` instead (see `is_ident_literal`).
+    match lit.suffix() {
+        "" => {}
+        "ident" => {
+            if s == "_" || syn::parse::Parser::parse_str(Ident::parse_any, &s).is_err() {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format!("Identifier literal \"{}\"ident must be a single word", s),
+                ));
+            }
+        }
+        "i" => {
+            return Err(syn::Error::new(
+                lit.span(),
+                format!(
+                    "Case-insensitive literal \"{}\"i has no token type; it can only be matched as a pattern",
+                    s
+                ),
+            ))
+        }
+        suffix => {
+            return Err(syn::Error::new(
+                lit.span(),
+                format!(
+                    "Unknown literal suffix `{}`; only `ident` (e.g. \"self\"ident) and `i` (e.g. \"select\"i) are supported",
+                    suffix
+                ),
+            ))
+        }
+    }
+
     if custom_keywords.contains(&s) {
         let ident = format_ident!("{}", s);
         return Ok(vec![parse_quote!(kw::#ident)]);
@@ -257,14 +773,18 @@ pub fn resolve_token_types(
 }
 
 /// Helper for UPO: Returns a TokenStream for input.peek(...)
+///
+/// `builtins` are the built-ins visible to the grammar (i.e. not shadowed by one of its rules);
+/// calls to them are peeked via their declared `peek` token type.
 pub fn get_simple_peek(
     pattern: &ModelPattern,
     kws: &HashSet<String>,
+    builtins: &[BuiltIn],
 ) -> Result<Option<TokenStream>> {
     match pattern {
         ModelPattern::Lit {
             lit: Lit::Str(lit), ..
-        } => {
+        } if !is_case_insensitive_literal(lit) => {
             let token_types = resolve_token_types(lit, kws)?;
             if let Some(first_type) = token_types.first() {
                 Ok(Some(quote!(#first_type)))
@@ -273,18 +793,29 @@ pub fn get_simple_peek(
             }
         }
         ModelPattern::Lit { .. } => Ok(None),
+        ModelPattern::RuleCall {
+            rule_name, args, ..
+        } if args.is_empty() => Ok(get_builtin_peek(rule_name, builtins)),
         ModelPattern::Bracketed(_, _) => Ok(Some(quote!(syn::token::Bracket))),
         ModelPattern::Braced(_, _) => Ok(Some(quote!(syn::token::Brace))),
         ModelPattern::Parenthesized(_, _) => Ok(Some(quote!(syn::token::Paren))),
         ModelPattern::Optional(inner, _)
         | ModelPattern::Repeat(inner, _)
-        | ModelPattern::Plus(inner, _) => get_simple_peek(inner, kws),
-        ModelPattern::SpanBinding(inner, _, _) => get_simple_peek(inner, kws),
-        ModelPattern::Recover { body, .. } => get_simple_peek(body, kws),
+        | ModelPattern::Plus(inner, _)
+        | ModelPattern::Bounded { pattern: inner, .. } => get_simple_peek(inner, kws, builtins),
+        ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Collect(inner, _, _) => get_simple_peek(inner, kws, builtins),
+        ModelPattern::Recover { body, .. } => get_simple_peek(body, kws, builtins),
+        ModelPattern::Fold { item, .. } => get_simple_peek(item, kws, builtins),
         ModelPattern::Group(alts, _) => {
             if alts.len() == 1 {
                 if let Some(first) = alts[0].0.first() {
-                    get_simple_peek(first, kws)
+                    get_simple_peek(first, kws, builtins)
                 } else {
                     Ok(None)
                 }
@@ -292,34 +823,195 @@ pub fn get_simple_peek(
                 Ok(None)
             }
         }
-        ModelPattern::Peek(inner, _) => get_simple_peek(inner, kws),
-        ModelPattern::Not(_, _) => Ok(None),
+        ModelPattern::Scope(seq, _) => match seq.first() {
+            Some(first) => get_simple_peek(first, kws, builtins),
+            None => Ok(None),
+        },
+        ModelPattern::Peek(inner, _) => get_simple_peek(inner, kws, builtins),
+        ModelPattern::Not(_, _) | ModelPattern::Follows(_, _) => Ok(None),
         ModelPattern::Until { .. } => Ok(None),
         _ => Ok(None),
     }
 }
 
+/// Returns the peekable token type of a built-in, if the backend declares one.
+fn get_builtin_peek(rule_name: &Ident, builtins: &[BuiltIn]) -> Option<TokenStream> {
+    let builtin = builtins.iter().find(|b| rule_name == b.name)?;
+    let ty: syn::Type = syn::parse_str(builtin.peek?).ok()?;
+    Some(quote!(#ty))
+}
+
+/// Maximum size of a FIRST set for which a multi-token peek is generated.
+pub const MAX_FIRST_SET_PEEKS: usize = 4;
+
+/// Maps a FIRST set (as computed by [`analyze_grammar`]) to the token types to peek for.
+///
+/// Returns `None` if the set is empty or contains an entry that cannot be decided by a
+/// single-token peek (e.g. non-string literals or built-ins without a `peek` hint).
+pub fn first_set_peek_types(
+    first_set: &HashSet<String>,
+    kws: &HashSet<String>,
+    builtins: &[BuiltIn],
+) -> Option<Vec<syn::Type>> {
+    if first_set.is_empty() {
+        return None;
+    }
+
+    let mut entries: Vec<&String> = first_set.iter().collect();
+    entries.sort();
+
+    let mut types: Vec<syn::Type> = Vec::new();
+    for entry in entries {
+        let ty: syn::Type = match entry.as_str() {
+            "Bracket" => parse_quote!(syn::token::Bracket),
+            "Brace" => parse_quote!(syn::token::Brace),
+            "Paren" => parse_quote!(syn::token::Paren),
+            "LIT" | "ANY" => return None,
+            s if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') => {
+                let lit = syn::LitStr::new(&s[1..s.len() - 1], proc_macro2::Span::call_site());
+                resolve_token_types(&lit, kws).ok()?.into_iter().next()?
+            }
+            s if s.starts_with('<') && s.ends_with('>') => {
+                let name = &s[1..s.len() - 1];
+                let builtin = builtins.iter().find(|b| b.name == name)?;
+                syn::parse_str(builtin.peek?).ok()?
+            }
+            _ => return None,
+        };
+        if !types.contains(&ty) {
+            types.push(ty);
+        }
+    }
+    Some(types)
+}
+
+/// Returns true if a token accepted by a peek for one of `a` may also be accepted by a peek
+/// for one of `b`. Custom keywords are treated as overlapping with `syn::Ident`.
+pub fn peek_types_overlap(a: &[syn::Type], b: &[syn::Type]) -> bool {
+    let ident: syn::Type = parse_quote!(syn::Ident);
+    let is_kw = |t: &syn::Type| match t {
+        syn::Type::Path(tp) => tp.path.segments.first().is_some_and(|s| s.ident == "kw"),
+        _ => false,
+    };
+    a.iter().any(|x| {
+        b.iter().any(|y| {
+            x == y || (*x == ident && (is_kw(y) || *y == ident)) || (*y == ident && is_kw(x))
+        })
+    })
+}
+
+/// Returns true if the rule is marked `#[no_backtrack]`.
+pub fn is_no_backtrack(rule: &Rule) -> bool {
+    rule.attrs.iter().any(|a| a.path().is_ident("no_backtrack"))
+}
+
+/// Decides the alternatives of a `#[no_backtrack]` rule by peeking at one token: returns the
+/// token types that select each alternative. The last alternative may be `None` if it has no
+/// such tokens (e.g. because it can match empty input); it is taken when no other one applies.
+///
+/// Fails with one error per alternative that cannot be decided this way, i.e. a left-recursive
+/// one, one without peekable FIRST tokens that is not the last, or one whose FIRST tokens
+/// overlap with those of an earlier alternative.
+pub fn no_backtrack_peeks(
+    rule: &Rule,
+    analysis: &GrammarAnalysis,
+    kws: &HashSet<String>,
+    builtins: &[BuiltIn],
+) -> std::result::Result<Vec<Option<Vec<syn::Type>>>, Vec<syn::Error>> {
+    let mut errors = Vec::new();
+    let mut peeks: Vec<Option<Vec<syn::Type>>> = Vec::new();
+    let last = rule.variants.len().saturating_sub(1);
+
+    for (i, variant) in rule.variants.iter().enumerate() {
+        let span = variant
+            .pattern
+            .first()
+            .map_or(rule.name.span(), |p| p.span());
+        let fail = |msg: String| {
+            syn::Error::new(
+                span,
+                format!(
+                    "Rule '{}' is #[no_backtrack], but alternative {} {}",
+                    rule.name,
+                    i + 1,
+                    msg
+                ),
+            )
+        };
+
+        if let Some(ModelPattern::RuleCall { rule_name, .. }) = variant.pattern.first() {
+            if *rule_name == rule.name {
+                errors.push(fail("is left-recursive".to_string()));
+                peeks.push(None);
+                continue;
+            }
+        }
+
+        let types = if analysis.is_sequence_nullable(&variant.pattern) {
+            None
+        } else {
+            first_set_peek_types(&analysis.first_set_of(&variant.pattern), kws, builtins)
+        };
+        match &types {
+            Some(types) => {
+                if let Some(j) = peeks
+                    .iter()
+                    .position(|p| p.as_ref().is_some_and(|p| peek_types_overlap(p, types)))
+                {
+                    errors.push(fail(format!(
+                        "can start with the same token as alternative {}",
+                        j + 1
+                    )));
+                }
+            }
+            None if i < last => errors.push(fail(
+                "cannot be chosen by peeking at one token; only the last alternative can be \
+                 taken without a peek"
+                    .to_string(),
+            )),
+            None => {}
+        }
+        peeks.push(types);
+    }
+
+    if errors.is_empty() {
+        Ok(peeks)
+    } else {
+        Err(errors)
+    }
+}
+
 /// Helper for UPO: Returns a unique string key for the start token
 pub fn get_peek_token_string(patterns: &[ModelPattern]) -> Option<String> {
     match patterns.first() {
         Some(ModelPattern::Lit {
             lit: Lit::Str(l), ..
-        }) => Some(l.value()),
+        }) if !is_case_insensitive_literal(l) => Some(l.value()),
         Some(ModelPattern::Lit { .. }) => None,
         Some(ModelPattern::Bracketed(_, _)) => Some("Bracket".to_string()),
         Some(ModelPattern::Braced(_, _)) => Some("Brace".to_string()),
         Some(ModelPattern::Parenthesized(_, _)) => Some("Paren".to_string()),
         Some(ModelPattern::Optional(inner, _))
         | Some(ModelPattern::Repeat(inner, _))
-        | Some(ModelPattern::Plus(inner, _)) => {
+        | Some(ModelPattern::Plus(inner, _))
+        | Some(ModelPattern::Bounded { pattern: inner, .. }) => {
             get_peek_token_string(std::slice::from_ref(&**inner))
         }
-        Some(ModelPattern::SpanBinding(inner, _, _)) => {
+        Some(ModelPattern::SpanBinding(inner, _, _))
+        | Some(ModelPattern::Raw(inner, _, _))
+        | Some(ModelPattern::Expected(inner, _, _))
+        | Some(ModelPattern::Commit(inner, _))
+        | Some(ModelPattern::Declare(inner, _))
+        | Some(ModelPattern::Resolve(inner, _))
+        | Some(ModelPattern::Collect(inner, _, _)) => {
             get_peek_token_string(std::slice::from_ref(&**inner))
         }
         Some(ModelPattern::Recover { body, .. }) => {
             get_peek_token_string(std::slice::from_ref(&**body))
         }
+        Some(ModelPattern::Fold { item, .. }) => {
+            get_peek_token_string(std::slice::from_ref(&**item))
+        }
         Some(ModelPattern::Group(alts, _)) => {
             if alts.len() == 1 {
                 get_peek_token_string(&alts[0].0)
@@ -327,8 +1019,9 @@ pub fn get_peek_token_string(patterns: &[ModelPattern]) -> Option<String> {
                 None
             }
         }
+        Some(ModelPattern::Scope(seq, _)) => get_peek_token_string(seq),
         Some(ModelPattern::Peek(inner, _)) => get_peek_token_string(std::slice::from_ref(&**inner)),
-        Some(ModelPattern::Not(_, _)) => None,
+        Some(ModelPattern::Not(_, _)) | Some(ModelPattern::Follows(_, _)) => None,
         Some(ModelPattern::Until { .. }) => None,
         _ => None,
     }
@@ -340,16 +1033,26 @@ pub fn is_nullable(pattern: &ModelPattern) -> bool {
         ModelPattern::Lit { .. } => false,
         ModelPattern::RuleCall { .. } => true,
         ModelPattern::Group(alts, _) => alts.iter().any(|(seq, _)| seq.iter().all(is_nullable)),
+        ModelPattern::Scope(seq, _) => seq.iter().all(is_nullable),
         ModelPattern::Bracketed(_, _)
         | ModelPattern::Braced(_, _)
         | ModelPattern::Parenthesized(_, _) => false,
         ModelPattern::Optional(_, _) => true,
         ModelPattern::Repeat(_, _) => true,
         ModelPattern::Plus(inner, _) => is_nullable(inner),
-        ModelPattern::SpanBinding(inner, _, _) => is_nullable(inner),
+        ModelPattern::Bounded { pattern, min, .. } => *min == 0 || is_nullable(pattern),
+        ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Collect(inner, _, _) => is_nullable(inner),
+        ModelPattern::Fold { item, .. } => is_nullable(item),
         ModelPattern::Recover { .. } => true,
         ModelPattern::Peek(_, _) => true,
         ModelPattern::Not(_, _) => true,
+        ModelPattern::Follows(_, _) => true,
         ModelPattern::Until { .. } => true,
     }
 }
@@ -364,12 +1067,79 @@ pub struct GrammarAnalysis {
     pub unused_rules: HashSet<String>,
     pub first_sets: HashMap<String, HashSet<String>>,
     pub errors: Vec<syn::Error>,
+    /// Complexity metrics per rule name.
+    pub metrics: HashMap<String, RuleMetrics>,
 }
 
-pub fn analyze_grammar(grammar: &GrammarDefinition) -> GrammarAnalysis {
-    let mut nullable_rules = HashSet::new();
+/// Complexity metrics of a rule, e.g. for dashboards or lint thresholds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMetrics {
+    /// Number of alternatives of the rule.
+    pub alternatives: usize,
+    /// Deepest nesting of compound patterns (groups, delimiters, repetitions, arguments, ...)
+    /// in any alternative; 0 for a flat sequence.
+    pub max_depth: usize,
+    /// Number of distinct rules called, including built-ins.
+    pub fan_out: usize,
+    /// True if an alternative starts with a call to the rule itself.
+    pub left_recursive: bool,
+}
 
+impl GrammarAnalysis {
+    /// Returns the FIRST set of a pattern sequence, based on the FIRST sets of the rules.
+    pub fn first_set_of(&self, patterns: &[ModelPattern]) -> HashSet<String> {
+        let mut acc = HashSet::new();
+        collect_first_from_sequence(patterns, &self.first_sets, &self.nullable_rules, &mut acc);
+        acc
+    }
+
+    /// Returns true if the pattern sequence can match without consuming input.
+    pub fn is_sequence_nullable(&self, patterns: &[ModelPattern]) -> bool {
+        is_sequence_nullable(patterns, &self.nullable_rules)
+    }
+}
+
+pub fn analyze_grammar(grammar: &GrammarDefinition) -> GrammarAnalysis {
     // 1. Compute Nullable Fixpoint
+    let nullable_rules = compute_nullable_rules(grammar);
+
+    // 2. Detect Cycles
+    let cycles = find_cycles(grammar, &nullable_rules);
+
+    // 3. Unused Rules
+    let unused_rules = find_unused_rules(grammar);
+
+    // 4. FIRST sets and Errors
+    let (first_sets, errors) = compute_first_sets_and_errors(grammar, &nullable_rules);
+
+    // 5. Metrics
+    let metrics = grammar
+        .rules
+        .iter()
+        .map(|rule| (rule.name.to_string(), rule_metrics(rule)))
+        .collect();
+
+    GrammarAnalysis {
+        nullable_rules,
+        cycles,
+        unused_rules,
+        first_sets,
+        errors,
+        metrics,
+    }
+}
+
+/// Built-ins that consume no tokens, such as `position()`, unless the grammar defines a rule of
+/// the same name. They are nullable and add nothing to FIRST sets.
+fn zero_width_builtins(grammar: &GrammarDefinition) -> impl Iterator<Item = String> + '_ {
+    ["position"]
+        .into_iter()
+        .filter(|name| !grammar.rules.iter().any(|r| r.name == name))
+        .map(str::to_string)
+}
+
+fn compute_nullable_rules(grammar: &GrammarDefinition) -> HashSet<String> {
+    let mut nullable_rules: HashSet<String> = zero_width_builtins(grammar).collect();
     let mut changed = true;
     while changed {
         changed = false;
@@ -393,22 +1163,130 @@ pub fn analyze_grammar(grammar: &GrammarDefinition) -> GrammarAnalysis {
             }
         }
     }
+    nullable_rules
+}
 
-    // 2. Detect Cycles
-    let cycles = find_cycles(grammar, &nullable_rules);
+/// Renders the rule dependency graph in Graphviz DOT format.
+///
+/// Edges lead from a rule to the grammar rules it calls. Calls to nullable rules are labeled
+/// `nullable`, left-recursive self calls `left-recursive`, and unused rules are drawn red.
+pub fn to_dot(grammar: &GrammarDefinition) -> String {
+    let analysis = analyze_grammar(grammar);
+    let defined: HashSet<String> = grammar.rules.iter().map(|r| r.name.to_string()).collect();
 
-    // 3. Unused Rules
-    let unused_rules = find_unused_rules(grammar);
+    let mut out = format!("digraph {} {{\n", grammar.name);
+    for rule in &grammar.rules {
+        let name = rule.name.to_string();
+        if analysis.unused_rules.contains(&name) {
+            out.push_str(&format!("    \"{}\" [color=red];\n", name));
+        } else {
+            out.push_str(&format!("    \"{}\";\n", name));
+        }
+    }
 
-    // 4. FIRST sets and Errors
-    let (first_sets, errors) = compute_first_sets_and_errors(grammar, &nullable_rules);
+    for rule in &grammar.rules {
+        let name = rule.name.to_string();
+        let left_recursive = analysis.metrics[&name].left_recursive;
 
-    GrammarAnalysis {
-        nullable_rules,
-        cycles,
-        unused_rules,
-        first_sets,
-        errors,
+        let mut callees: Vec<String> = Vec::new();
+        for variant in &rule.variants {
+            collect_called_rules(&variant.pattern, &mut |callee| {
+                if defined.contains(&callee) && !callees.contains(&callee) {
+                    callees.push(callee);
+                }
+            });
+        }
+
+        for callee in callees {
+            let mut labels = Vec::new();
+            if callee == name && left_recursive {
+                labels.push("left-recursive");
+            }
+            if analysis.nullable_rules.contains(&callee) {
+                labels.push("nullable");
+            }
+            if labels.is_empty() {
+                out.push_str(&format!("    \"{}\" -> \"{}\";\n", name, callee));
+            } else {
+                out.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                    name,
+                    callee,
+                    labels.join(", ")
+                ));
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn rule_metrics(rule: &Rule) -> RuleMetrics {
+    let mut callees = HashSet::new();
+    for variant in &rule.variants {
+        collect_called_rules(&variant.pattern, &mut |callee| {
+            callees.insert(callee);
+        });
+    }
+    let (recursive, _) = split_left_recursive(&rule.name, &rule.variants);
+
+    RuleMetrics {
+        alternatives: rule.variants.len(),
+        max_depth: rule
+            .variants
+            .iter()
+            .map(|v| sequence_depth(&v.pattern))
+            .max()
+            .unwrap_or(0),
+        fan_out: callees.len(),
+        left_recursive: !recursive.is_empty(),
+    }
+}
+
+fn sequence_depth(patterns: &[ModelPattern]) -> usize {
+    patterns.iter().map(pattern_depth).max().unwrap_or(0)
+}
+
+fn pattern_depth(pattern: &ModelPattern) -> usize {
+    match pattern {
+        ModelPattern::Cut(_) | ModelPattern::Lit { .. } => 0,
+        ModelPattern::RuleCall { args, .. } => args
+            .iter()
+            .map(|arg| match arg {
+                Argument::Positional(p) | Argument::Named(_, p) => 1 + pattern_depth(p),
+            })
+            .max()
+            .unwrap_or(0),
+        ModelPattern::Group(alts, _) => {
+            1 + alts
+                .iter()
+                .map(|(alt, _)| sequence_depth(alt))
+                .max()
+                .unwrap_or(0)
+        }
+        ModelPattern::Bracketed(inner, _)
+        | ModelPattern::Braced(inner, _)
+        | ModelPattern::Parenthesized(inner, _)
+        | ModelPattern::Scope(inner, _) => 1 + sequence_depth(inner),
+        ModelPattern::Optional(inner, _)
+        | ModelPattern::Repeat(inner, _)
+        | ModelPattern::Plus(inner, _)
+        | ModelPattern::Bounded { pattern: inner, .. }
+        | ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Peek(inner, _)
+        | ModelPattern::Not(inner, _)
+        | ModelPattern::Follows(inner, _)
+        | ModelPattern::Until { pattern: inner, .. } => 1 + pattern_depth(inner),
+        ModelPattern::Collect(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _) => pattern_depth(inner),
+        ModelPattern::Recover { body, sync, .. } => {
+            1 + pattern_depth(body).max(pattern_depth(sync))
+        }
+        ModelPattern::Fold { item, op, .. } => 1 + pattern_depth(item).max(pattern_depth(op)),
     }
 }
 
@@ -429,22 +1307,178 @@ fn is_pattern_nullable_precise(pattern: &ModelPattern, nullable_rules: &HashSet<
         ModelPattern::Group(alts, _) => alts
             .iter()
             .any(|(seq, _)| is_sequence_nullable(seq, nullable_rules)),
+        ModelPattern::Scope(seq, _) => is_sequence_nullable(seq, nullable_rules),
         ModelPattern::Optional(_, _)
         | ModelPattern::Repeat(_, _)
         | ModelPattern::Recover { .. }
         | ModelPattern::Peek(_, _)
         | ModelPattern::Not(_, _)
-        | ModelPattern::Until { .. } => true, // Peek/Not consume nothing
-        ModelPattern::Plus(inner, _) => is_pattern_nullable_precise(inner, nullable_rules),
-        ModelPattern::SpanBinding(inner, _, _) => {
+        | ModelPattern::Follows(_, _)
+        | ModelPattern::Until { .. } => true, // Peek/Not/Follows consume nothing
+        ModelPattern::Plus(inner, _) | ModelPattern::Fold { item: inner, .. } => {
             is_pattern_nullable_precise(inner, nullable_rules)
         }
+        ModelPattern::Bounded { pattern, min, .. } => {
+            *min == 0 || is_pattern_nullable_precise(pattern, nullable_rules)
+        }
+        ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Collect(inner, _, _) => is_pattern_nullable_precise(inner, nullable_rules),
         ModelPattern::Bracketed(_, _)
         | ModelPattern::Braced(_, _)
         | ModelPattern::Parenthesized(_, _) => false,
     }
 }
 
+/// Rewrites indirect left recursion (`a -> b -> a`) into direct left recursion, which code
+/// generation parses with a loop. For a cycle, the alternatives of one rule that start with a
+/// call to the next rule of the cycle are replaced by one alternative per alternative of the
+/// called rule, followed by the rest of the original alternative:
+///
+/// ```text
+/// rule expr -> Expr = b:binary -> { b } | a:atom -> { a }
+/// rule binary -> Expr = l:expr "+" r:atom -> { add(l, r) }
+///
+/// // `expr` becomes
+/// rule expr -> Expr = l:expr "+" r:atom -> { let b = { add(l, r) }; b } | a:atom -> { a }
+/// ```
+///
+/// The called rule is kept for its other callers. A call is only inlined if neither rule has
+/// parameters or generics, the called rule is not left-recursive itself and does not call
+/// `super`, and its bindings do not clash with the names the rest of the alternative uses.
+///
+/// Returns the cycles that could not be rewritten.
+pub fn eliminate_indirect_left_recursion(grammar: &mut GrammarDefinition) -> Vec<Vec<String>> {
+    // Inlining can create new cycles through the inlined alternatives, so bound the rewrites
+    let mut budget = grammar.rules.len() * grammar.rules.len();
+    loop {
+        let nullable_rules = compute_nullable_rules(grammar);
+        let cycles: Vec<Vec<String>> = find_cycles(grammar, &nullable_rules)
+            .into_iter()
+            .filter(|cycle| cycle.len() > 1)
+            .collect();
+        if cycles.is_empty() || budget == 0 {
+            return cycles;
+        }
+        budget -= 1;
+
+        let rewrite = cycles.iter().find_map(|cycle| {
+            (0..cycle.len()).find_map(|i| {
+                let next = &cycle[(i + 1) % cycle.len()];
+                inline_leading_calls(grammar, &cycle[i], next)
+            })
+        });
+        match rewrite {
+            Some((index, variants)) => grammar.rules[index].variants = variants,
+            None => return cycles,
+        }
+    }
+}
+
+/// Inlines the calls to `callee` at the start of the alternatives of `target`. Returns the index
+/// of `target` and its new variants, or `None` if no call can be inlined.
+fn inline_leading_calls(
+    grammar: &GrammarDefinition,
+    target: &str,
+    callee: &str,
+) -> Option<(usize, Vec<RuleVariant>)> {
+    let index = grammar.rules.iter().position(|r| r.name == target)?;
+    let rule = &grammar.rules[index];
+    let callee = grammar.rules.iter().find(|r| r.name == callee)?;
+    let has_params = |r: &Rule| !r.params.is_empty() || !r.generics.params.is_empty();
+    let (recursive, _) = split_left_recursive(&callee.name, &callee.variants);
+    if has_params(rule)
+        || has_params(callee)
+        || rule.precedence.is_some()
+        || !recursive.is_empty()
+        || calls_super(callee)
+    {
+        return None;
+    }
+
+    let mut inlined = false;
+    let mut variants = Vec::new();
+    for variant in &rule.variants {
+        match inline_leading_call(variant, callee) {
+            Some(expanded) => {
+                inlined = true;
+                variants.extend(expanded);
+            }
+            None => variants.push(variant.clone()),
+        }
+    }
+    inlined.then_some((index, variants))
+}
+
+fn inline_leading_call(variant: &RuleVariant, callee: &Rule) -> Option<Vec<RuleVariant>> {
+    let Some(ModelPattern::RuleCall {
+        binding,
+        rule_name,
+        generics,
+        args,
+        ..
+    }) = variant.pattern.first()
+    else {
+        return None;
+    };
+    // A guard refers to the bindings of its own alternative
+    if *rule_name != callee.name
+        || !generics.is_empty()
+        || !args.is_empty()
+        || variant.guard.is_some()
+        || callee.variants.iter().any(|v| v.guard.is_some())
+    {
+        return None;
+    }
+    let rest = &variant.pattern[1..];
+    let has_cut = find_cut(&variant.pattern).is_some();
+
+    // Names used by the rest of the alternative. The bindings of the callee are declared in the
+    // same scope, and the value of the call only exists once the whole sequence has matched.
+    let mut taken: HashSet<String> = collect_bindings(rest)
+        .iter()
+        .map(|b| b.to_string())
+        .collect();
+    collect_called_rules(rest, &mut |name| {
+        taken.insert(name);
+    });
+    if binding
+        .as_ref()
+        .is_some_and(|b| taken.contains(&b.to_string()))
+    {
+        return None;
+    }
+
+    let mut expanded = Vec::new();
+    for inner in &callee.variants {
+        let clashes = collect_bindings(&inner.pattern)
+            .iter()
+            .any(|b| taken.contains(&b.to_string()));
+        if clashes || (has_cut && find_cut(&inner.pattern).is_some()) {
+            return None;
+        }
+
+        let inner_action = &inner.action;
+        let outer_action = &variant.action;
+        let action = match binding {
+            Some(b) => quote! { let #b = { #inner_action }; #outer_action },
+            None => quote! { let _ = { #inner_action }; #outer_action },
+        };
+        expanded.push(RuleVariant {
+            attrs: inner.attrs.iter().chain(&variant.attrs).cloned().collect(),
+            pattern: inner.pattern.iter().chain(rest).cloned().collect(),
+            label: variant.label.clone().or_else(|| inner.label.clone()),
+            guard: None,
+            action,
+        });
+    }
+    Some(expanded)
+}
+
 fn find_cycles(grammar: &GrammarDefinition, nullable_rules: &HashSet<String>) -> Vec<Vec<String>> {
     let mut adj = HashMap::new();
     for rule in &grammar.rules {
@@ -518,6 +1552,12 @@ fn collect_nullable_deps(
                     return;
                 }
             }
+            ModelPattern::Scope(seq, _) => {
+                collect_nullable_deps(seq, nullable_rules, deps);
+                if !is_sequence_nullable(seq, nullable_rules) {
+                    return;
+                }
+            }
             ModelPattern::Group(alts, _) => {
                 let mut group_nullable = false;
                 for (alt, _) in alts {
@@ -533,21 +1573,35 @@ fn collect_nullable_deps(
             ModelPattern::Optional(inner, _) | ModelPattern::Repeat(inner, _) => {
                 collect_nullable_deps(std::slice::from_ref(inner), nullable_rules, deps);
             }
-            ModelPattern::Plus(inner, _) => {
+            ModelPattern::Plus(inner, _) | ModelPattern::Fold { item: inner, .. } => {
                 collect_nullable_deps(std::slice::from_ref(inner), nullable_rules, deps);
                 if !is_pattern_nullable_precise(inner, nullable_rules) {
                     return;
                 }
             }
-            ModelPattern::SpanBinding(inner, _, _) => {
+            ModelPattern::Bounded { pattern, min, .. } => {
+                collect_nullable_deps(std::slice::from_ref(pattern), nullable_rules, deps);
+                if *min > 0 && !is_pattern_nullable_precise(pattern, nullable_rules) {
+                    return;
+                }
+            }
+            ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Collect(inner, _, _) => {
                 collect_nullable_deps(std::slice::from_ref(inner), nullable_rules, deps);
                 if !is_pattern_nullable_precise(inner, nullable_rules) {
                     return;
                 }
             }
-            ModelPattern::Peek(inner, _) | ModelPattern::Not(inner, _) => {
+            ModelPattern::Peek(inner, _)
+            | ModelPattern::Not(inner, _)
+            | ModelPattern::Follows(inner, _) => {
                 collect_nullable_deps(std::slice::from_ref(inner), nullable_rules, deps);
-                // Peek/Not consume nothing, so we continue to next pattern
+                // Peek/Not/Follows consume nothing, so we continue to next pattern
             }
             ModelPattern::Recover { body, .. } => {
                 collect_nullable_deps(std::slice::from_ref(body), nullable_rules, deps);
@@ -609,43 +1663,160 @@ fn find_unused_rules(grammar: &GrammarDefinition) -> HashSet<String> {
         .collect()
 }
 
+/// Returns true if the rule is marked `#[cfg(test)]`, so that it only exists in test builds.
+pub fn is_test_only(rule: &Rule) -> bool {
+    rule.attrs
+        .iter()
+        .any(|a| a.path().is_ident("cfg") && a.parse_args::<Ident>().is_ok_and(|arg| arg == "test"))
+}
+
+/// Finds calls from rules that exist in every build to rules marked `#[cfg(test)]`, which
+/// would not compile outside of tests. The validator treats test-only rules as present since
+/// it does not know the cfg of the build, so backends check this when generating code. Returns
+/// one error per call, spanned at the called name.
+pub fn find_test_only_calls(grammar: &GrammarDefinition) -> Vec<syn::Error> {
+    let test_only: HashSet<String> = grammar
+        .rules
+        .iter()
+        .filter(|r| is_test_only(r))
+        .map(|r| r.name.to_string())
+        .collect();
+    let mut errors = Vec::new();
+    for rule in grammar.rules.iter().filter(|r| !is_test_only(r)) {
+        for variant in &rule.variants {
+            visit_rule_calls(&variant.pattern, &mut |callee, _| {
+                if test_only.contains(&callee.to_string()) {
+                    errors.push(syn::Error::new(
+                        callee.span(),
+                        format!(
+                            "Rule '{}' calls the test-only rule '{}', which does not exist outside of `#[cfg(test)]`; mark '{}' with `#[cfg(test)]` as well",
+                            rule.name, callee, rule.name
+                        ),
+                    ));
+                }
+            });
+        }
+    }
+    errors
+}
+
+/// Returns true if the rule calls its parent grammar's version with `super`.
+pub fn calls_super(rule: &Rule) -> bool {
+    let mut found = false;
+    for variant in &rule.variants {
+        collect_called_rules(&variant.pattern, &mut |name| found |= name == "super");
+    }
+    found
+}
+
+/// Returns true if the patterns contain a `follows(..)` lookbehind, which needs the position
+/// where the rule or delimited group started.
+pub fn uses_follows(patterns: &[ModelPattern]) -> bool {
+    patterns.iter().any(|pattern| match pattern {
+        ModelPattern::Follows(_, _) => true,
+        ModelPattern::Cut(_) | ModelPattern::Lit { .. } => false,
+        ModelPattern::RuleCall { args, .. } => args.iter().any(|arg| match arg {
+            Argument::Positional(p) | Argument::Named(_, p) => {
+                uses_follows(std::slice::from_ref(p))
+            }
+        }),
+        ModelPattern::Group(alts, _) => alts.iter().any(|(seq, _)| uses_follows(seq)),
+        ModelPattern::Bracketed(seq, _)
+        | ModelPattern::Braced(seq, _)
+        | ModelPattern::Parenthesized(seq, _)
+        | ModelPattern::Scope(seq, _) => uses_follows(seq),
+        ModelPattern::Optional(inner, _)
+        | ModelPattern::Repeat(inner, _)
+        | ModelPattern::Plus(inner, _)
+        | ModelPattern::Bounded { pattern: inner, .. }
+        | ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Collect(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Peek(inner, _)
+        | ModelPattern::Not(inner, _)
+        | ModelPattern::Until { pattern: inner, .. } => uses_follows(std::slice::from_ref(inner)),
+        ModelPattern::Recover {
+            body: first,
+            sync: second,
+            ..
+        }
+        | ModelPattern::Fold {
+            item: first,
+            op: second,
+            ..
+        } => {
+            uses_follows(std::slice::from_ref(first)) || uses_follows(std::slice::from_ref(second))
+        }
+    })
+}
+
+/// The names of the rules called in `patterns`, including calls in arguments.
+pub(crate) fn called_rules(patterns: &[ModelPattern]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    collect_called_rules(patterns, &mut |name| {
+        names.insert(name);
+    });
+    names
+}
+
 fn collect_called_rules<F: FnMut(String)>(patterns: &[ModelPattern], cb: &mut F) {
+    visit_rule_calls(patterns, &mut |name, _| cb(name.to_string()));
+}
+
+fn visit_rule_calls<F: FnMut(&Ident, &[Argument])>(patterns: &[ModelPattern], cb: &mut F) {
     for p in patterns {
         match p {
             ModelPattern::RuleCall {
                 rule_name, args, ..
             } => {
-                cb(rule_name.to_string());
+                cb(rule_name, args);
                 for arg in args {
                     match arg {
                         Argument::Positional(p) | Argument::Named(_, p) => {
-                            collect_called_rules(std::slice::from_ref(p), cb);
+                            visit_rule_calls(std::slice::from_ref(p), cb);
                         }
                     }
                 }
             }
             ModelPattern::Group(alts, _) => {
                 for (alt, _) in alts {
-                    collect_called_rules(alt, cb);
+                    visit_rule_calls(alt, cb);
                 }
             }
+            ModelPattern::Scope(seq, _) => visit_rule_calls(seq, cb),
             ModelPattern::Optional(inner, _)
             | ModelPattern::Repeat(inner, _)
             | ModelPattern::Plus(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. }
             | ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Collect(inner, _, _)
             | ModelPattern::Peek(inner, _)
             | ModelPattern::Not(inner, _)
+            | ModelPattern::Follows(inner, _)
             | ModelPattern::Until { pattern: inner, .. } => {
-                collect_called_rules(std::slice::from_ref(inner), cb);
+                visit_rule_calls(std::slice::from_ref(inner), cb);
             }
             ModelPattern::Recover { body, sync, .. } => {
-                collect_called_rules(std::slice::from_ref(body), cb);
-                collect_called_rules(std::slice::from_ref(sync), cb);
+                visit_rule_calls(std::slice::from_ref(body), cb);
+                visit_rule_calls(std::slice::from_ref(sync), cb);
+            }
+            ModelPattern::Fold { item, op, .. } => {
+                visit_rule_calls(std::slice::from_ref(item), cb);
+                visit_rule_calls(std::slice::from_ref(op), cb);
             }
             ModelPattern::Bracketed(inner, _)
             | ModelPattern::Braced(inner, _)
             | ModelPattern::Parenthesized(inner, _) => {
-                collect_called_rules(inner, cb);
+                visit_rule_calls(inner, cb);
             }
             _ => {}
         }
@@ -659,6 +1830,9 @@ fn compute_first_sets_and_errors(
     let mut first_sets: HashMap<String, HashSet<String>> = HashMap::new();
     let mut errors = Vec::new();
 
+    for name in zero_width_builtins(grammar) {
+        first_sets.insert(name, HashSet::new());
+    }
     for rule in &grammar.rules {
         first_sets.insert(rule.name.to_string(), HashSet::new());
     }
@@ -691,6 +1865,10 @@ fn compute_first_sets_and_errors(
     // 2. Generate Shadowing Errors (Exact Duplicate and Prefix Shadowing)
     for rule in &grammar.rules {
         for (i, v1) in rule.variants.iter().enumerate() {
+            // A guarded variant may fail after matching, so it shadows nothing
+            if v1.guard.is_some() {
+                continue;
+            }
             // Check against subsequent variants
             for (j, v2) in rule.variants.iter().enumerate().skip(i + 1) {
                 // Determine span for error reporting
@@ -742,7 +1920,7 @@ fn collect_first_from_sequence(
         match p {
             ModelPattern::Lit {
                 lit: Lit::Str(s), ..
-            } => {
+            } if !is_case_insensitive_literal(s) => {
                 acc.insert(format!("\"{}\"", s.value()));
                 return;
             }
@@ -761,6 +1939,12 @@ fn collect_first_from_sequence(
                     return;
                 }
             }
+            ModelPattern::Scope(seq, _) => {
+                collect_first_from_sequence(seq, first_sets, nullable_rules, acc);
+                if !is_sequence_nullable(seq, nullable_rules) {
+                    return;
+                }
+            }
             ModelPattern::Group(alts, _) => {
                 let mut group_nullable = false;
                 for (alt, _) in alts {
@@ -797,7 +1981,7 @@ fn collect_first_from_sequence(
                 );
                 continue;
             }
-            ModelPattern::Plus(inner, _) => {
+            ModelPattern::Plus(inner, _) | ModelPattern::Fold { item: inner, .. } => {
                 collect_first_from_sequence(
                     std::slice::from_ref(inner),
                     first_sets,
@@ -808,7 +1992,24 @@ fn collect_first_from_sequence(
                     return;
                 }
             }
-            ModelPattern::SpanBinding(inner, _, _) => {
+            ModelPattern::Bounded { pattern, min, .. } => {
+                collect_first_from_sequence(
+                    std::slice::from_ref(pattern),
+                    first_sets,
+                    nullable_rules,
+                    acc,
+                );
+                if *min > 0 && !is_pattern_nullable_precise(pattern, nullable_rules) {
+                    return;
+                }
+            }
+            ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Collect(inner, _, _) => {
                 collect_first_from_sequence(
                     std::slice::from_ref(inner),
                     first_sets,
@@ -819,7 +2020,7 @@ fn collect_first_from_sequence(
                     return;
                 }
             }
-            ModelPattern::Not(_inner, _) => {
+            ModelPattern::Not(_inner, _) | ModelPattern::Follows(_inner, _) => {
                 continue;
             }
             ModelPattern::Recover { body, .. } => {
@@ -829,6 +2030,8 @@ fn collect_first_from_sequence(
                     nullable_rules,
                     acc,
                 );
+                // Recovery skips arbitrary tokens
+                acc.insert("ANY".to_string());
             }
             ModelPattern::Until { pattern, .. } => {
                 collect_first_from_sequence(
@@ -837,6 +2040,7 @@ fn collect_first_from_sequence(
                     nullable_rules,
                     acc,
                 );
+                acc.insert("ANY".to_string());
             }
             _ => {}
         }
@@ -849,7 +2053,11 @@ fn collect_first_from_sequence(
 
 fn peel(p: &ModelPattern) -> &ModelPattern {
     match p {
-        ModelPattern::SpanBinding(inner, _, _) => peel(inner),
+        ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Collect(inner, _, _) => peel(inner),
         _ => p,
     }
 }
@@ -918,16 +2126,34 @@ fn pattern_structure_eq(p1: &ModelPattern, p2: &ModelPattern) -> bool {
         }
         (ModelPattern::Bracketed(inner1, _), ModelPattern::Bracketed(inner2, _))
         | (ModelPattern::Braced(inner1, _), ModelPattern::Braced(inner2, _))
-        | (ModelPattern::Parenthesized(inner1, _), ModelPattern::Parenthesized(inner2, _)) => {
+        | (ModelPattern::Parenthesized(inner1, _), ModelPattern::Parenthesized(inner2, _))
+        | (ModelPattern::Scope(inner1, _), ModelPattern::Scope(inner2, _)) => {
             sequence_structure_eq(inner1, inner2)
         }
         (ModelPattern::Optional(inner1, _), ModelPattern::Optional(inner2, _))
         | (ModelPattern::Repeat(inner1, _), ModelPattern::Repeat(inner2, _))
         | (ModelPattern::Plus(inner1, _), ModelPattern::Plus(inner2, _))
         | (ModelPattern::Peek(inner1, _), ModelPattern::Peek(inner2, _))
-        | (ModelPattern::Not(inner1, _), ModelPattern::Not(inner2, _)) => {
+        | (ModelPattern::Not(inner1, _), ModelPattern::Not(inner2, _))
+        | (ModelPattern::Follows(inner1, _), ModelPattern::Follows(inner2, _))
+        | (ModelPattern::Declare(inner1, _), ModelPattern::Declare(inner2, _))
+        | (ModelPattern::Resolve(inner1, _), ModelPattern::Resolve(inner2, _)) => {
             pattern_structure_eq(inner1, inner2)
         }
+        (
+            ModelPattern::Bounded {
+                pattern: p1,
+                min: min1,
+                max: max1,
+                ..
+            },
+            ModelPattern::Bounded {
+                pattern: p2,
+                min: min2,
+                max: max2,
+                ..
+            },
+        ) => min1 == min2 && max1 == max2 && pattern_structure_eq(p1, p2),
         (
             ModelPattern::Recover {
                 body: b1, sync: s1, ..
@@ -939,10 +2165,108 @@ fn pattern_structure_eq(p1: &ModelPattern, p2: &ModelPattern) -> bool {
         (ModelPattern::Until { pattern: p1, .. }, ModelPattern::Until { pattern: p2, .. }) => {
             pattern_structure_eq(p1, p2)
         }
+        (
+            ModelPattern::Fold {
+                fixity: f1,
+                item: i1,
+                op: o1,
+                action: a1,
+                ..
+            },
+            ModelPattern::Fold {
+                fixity: f2,
+                item: i2,
+                op: o2,
+                action: a2,
+                ..
+            },
+        ) => {
+            f1 == f2
+                && pattern_structure_eq(i1, i2)
+                && pattern_structure_eq(o1, o2)
+                && a1.to_string() == a2.to_string()
+        }
         _ => false,
     }
 }
 
+/// The fewest levels of rule calls each rule needs to match: 1 for a rule whose shallowest
+/// alternative only uses built-ins and literals, and `usize::MAX` for rules that cannot end.
+pub fn rule_heights(grammar: &GrammarDefinition) -> HashMap<String, usize> {
+    let mut heights: HashMap<String, usize> = grammar
+        .rules
+        .iter()
+        .map(|r| (r.name.to_string(), usize::MAX))
+        .collect();
+    loop {
+        let mut changed = false;
+        for rule in &grammar.rules {
+            let height = rule
+                .variants
+                .iter()
+                .map(|v| sequence_height(&v.pattern, &heights))
+                .min()
+                .unwrap_or(0)
+                .saturating_add(1);
+            let entry = heights.entry(rule.name.to_string()).or_insert(usize::MAX);
+            if height < *entry {
+                *entry = height;
+                changed = true;
+            }
+        }
+        if !changed {
+            return heights;
+        }
+    }
+}
+
+/// The fewest levels of rule calls the sequence needs to match, see [`rule_heights`].
+pub fn sequence_height(patterns: &[ModelPattern], heights: &HashMap<String, usize>) -> usize {
+    patterns
+        .iter()
+        .map(|p| pattern_height(p, heights))
+        .max()
+        .unwrap_or(0)
+}
+
+/// The fewest levels of rule calls the pattern needs to match, see [`rule_heights`].
+/// Calls of built-ins and grammar parameters count as none.
+pub fn pattern_height(pattern: &ModelPattern, heights: &HashMap<String, usize>) -> usize {
+    match pattern {
+        ModelPattern::RuleCall { rule_name, .. } => {
+            heights.get(&rule_name.to_string()).copied().unwrap_or(0)
+        }
+        ModelPattern::Group(alts, _) => alts
+            .iter()
+            .map(|(seq, _)| sequence_height(seq, heights))
+            .min()
+            .unwrap_or(0),
+        ModelPattern::Bracketed(seq, _)
+        | ModelPattern::Braced(seq, _)
+        | ModelPattern::Parenthesized(seq, _)
+        | ModelPattern::Scope(seq, _) => sequence_height(seq, heights),
+        ModelPattern::Optional(_, _) | ModelPattern::Repeat(_, _) => 0,
+        ModelPattern::Bounded { min: 0, .. } => 0,
+        ModelPattern::Plus(inner, _)
+        | ModelPattern::Bounded { pattern: inner, .. }
+        | ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Collect(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Recover { body: inner, .. }
+        | ModelPattern::Fold { item: inner, .. } => pattern_height(inner, heights),
+        ModelPattern::Cut(_)
+        | ModelPattern::Lit { .. }
+        | ModelPattern::Peek(_, _)
+        | ModelPattern::Not(_, _)
+        | ModelPattern::Follows(_, _)
+        | ModelPattern::Until { .. } => 0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -955,4 +2279,453 @@ mod tests {
         let types = resolve_token_types(&lit, &kws).unwrap();
         assert_eq!(types.len(), 1);
     }
+
+    #[test]
+    fn test_resolve_ident_literals() {
+        let kws = HashSet::new();
+        let lit: syn::LitStr = parse_quote!("self"ident);
+        assert!(is_ident_literal(&lit));
+        let types = resolve_token_types(&lit, &kws).unwrap();
+        assert_eq!(quote!(#(#types)*).to_string(), "Token ! [self]");
+
+        let err = |lit: syn::LitStr| resolve_token_types(&lit, &kws).unwrap_err().to_string();
+        assert_eq!(
+            err(parse_quote!("self ."ident)),
+            "Identifier literal \"self .\"ident must be a single word"
+        );
+        assert_eq!(
+            err(parse_quote!("self"kw)),
+            "Unknown literal suffix `kw`; only `ident` (e.g. \"self\"ident) and `i` (e.g. \"select\"i) are supported"
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_literals() {
+        let lit: syn::LitStr = parse_quote!("select"i);
+        assert!(is_case_insensitive_literal(&lit));
+
+        // Matched by text, so neither a custom keyword nor a peek or a FIRST set entry
+        let pattern = ModelPattern::Lit {
+            binding: None,
+            lit: Lit::Str(lit),
+        };
+        let mut kws = HashSet::new();
+        collect_from_patterns(std::slice::from_ref(&pattern), &HashSet::new(), &mut kws);
+        assert!(kws.is_empty());
+        assert!(get_simple_peek(&pattern, &kws, &[]).unwrap().is_none());
+        assert!(get_peek_token_string(std::slice::from_ref(&pattern)).is_none());
+    }
+
+    #[test]
+    fn test_zero_width_position() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar positions {
+                rule main -> () =
+                    p:position() "a" -> { () }
+                  | p:position() x:ident -> { () }
+                rule start -> () = p:position() -> { () }
+                rule left -> () = position() left "b" -> { () } | "c" -> { () }
+            }
+        })
+        .unwrap();
+        let analysis = analyze_grammar(&p_ast.into());
+
+        // `position()` consumes nothing: the alternatives start with what follows it
+        let rule = |name: &str| &analysis.first_sets[name];
+        assert_eq!(
+            rule("main"),
+            &HashSet::from(["\"a\"".to_string(), "<ident>".to_string()])
+        );
+        assert!(analysis.nullable_rules.contains("start"));
+        assert!(rule("start").is_empty());
+        assert_eq!(analysis.cycles, vec![vec!["left".to_string()]]);
+    }
+
+    #[test]
+    fn test_simple_peek_builtin() {
+        let kws = HashSet::new();
+        let builtins = [BuiltIn {
+            name: "ident",
+            return_type: "syn::Ident",
+            peek: Some("syn::Ident"),
+        }];
+        let call = |name: &str| ModelPattern::RuleCall {
+            binding: None,
+            module: None,
+            rule_name: format_ident!("{}", name),
+            generics: vec![],
+            args: vec![],
+        };
+
+        let repeat = ModelPattern::Repeat(Box::new(call("ident")), proc_macro2::Span::call_site());
+        let peek = get_simple_peek(&repeat, &kws, &builtins).unwrap().unwrap();
+        assert_eq!(peek.to_string(), "syn :: Ident");
+
+        // Shadowed built-ins are not passed in, and user rules have no simple peek.
+        assert!(get_simple_peek(&repeat, &kws, &[]).unwrap().is_none());
+        assert!(get_simple_peek(&call("item"), &kws, &builtins)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_first_set_peek_types() {
+        let kws: HashSet<String> = ["fn_kw".to_string()].into_iter().collect();
+        let builtins = [BuiltIn {
+            name: "ident",
+            return_type: "syn::Ident",
+            peek: Some("syn::Ident"),
+        }];
+        let set = |entries: &[&str]| -> HashSet<String> {
+            entries.iter().map(|e| e.to_string()).collect()
+        };
+
+        let types =
+            first_set_peek_types(&set(&["\"fn_kw\"", "\"->\"", "Bracket"]), &kws, &builtins)
+                .unwrap();
+        let strs: Vec<_> = types.iter().map(|t| quote!(#t).to_string()).collect();
+        assert_eq!(
+            strs,
+            ["Token ! [-]", "kw :: fn_kw", "syn :: token :: Bracket"]
+        );
+
+        assert!(first_set_peek_types(&set(&["LIT"]), &kws, &builtins).is_none());
+        assert!(first_set_peek_types(&set(&["<unknown>"]), &kws, &builtins).is_none());
+
+        let ident = first_set_peek_types(&set(&["<ident>"]), &kws, &builtins).unwrap();
+        let kw = first_set_peek_types(&set(&["\"fn_kw\""]), &kws, &builtins).unwrap();
+        let semi = first_set_peek_types(&set(&["\";\""]), &kws, &builtins).unwrap();
+        assert!(peek_types_overlap(&ident, &kw));
+        assert!(!peek_types_overlap(&ident, &semi));
+    }
+
+    #[test]
+    fn test_rule_metrics() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar metrics {
+                pub rule expr -> i32 =
+                    l:expr "+" r:term -> { l + r }
+                  | t:term -> { t }
+
+                rule term -> i32 =
+                    [ (v:i32 ",")* ] -> { 0 }
+                  | items:separated(atom, ",") -> { 0 }
+                  | i:i32 -> { i }
+                  | a:atom -> { a }
+
+                rule atom -> i32 = i:i32 -> { i }
+            }
+        })
+        .unwrap();
+        let analysis = analyze_grammar(&p_ast.into());
+
+        assert_eq!(
+            analysis.metrics["expr"],
+            RuleMetrics {
+                alternatives: 2,
+                max_depth: 0,
+                fan_out: 2,
+                left_recursive: true,
+            }
+        );
+        assert_eq!(
+            analysis.metrics["term"],
+            RuleMetrics {
+                alternatives: 4,
+                max_depth: 3,
+                fan_out: 3,
+                left_recursive: false,
+            }
+        );
+        assert_eq!(analysis.metrics["atom"].max_depth, 0);
+    }
+
+    #[test]
+    fn test_common_prefix_runs() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar hoist {
+                rule expr -> () =
+                    p:path "." f:ident -> { () }
+                  | p:path "?" -> { () }
+                  | path -> { () }
+                  | "-" path -> { () }
+                  | p:path "=" scaled(p) -> { () }
+                  | p:path "+" -> { () }
+                  | p:path => "*" -> { () }
+                  | i:ident -> { () }
+                  | i:ident "!" -> { () }
+                  | t:term "!" -> { () }
+                  | term -> { () }
+                  | p:path "." where { true } -> { () }
+                  | p:path "." -> { () }
+
+                rule path -> () = ident -> { () }
+                rule term -> () = "t" -> { () }
+                rule scaled(p: ()) -> () = "x" -> { () }
+            }
+        })
+        .unwrap();
+        let grammar: GrammarDefinition = p_ast.into();
+        let rules = grammar.rules.iter().map(|r| r.name.to_string()).collect();
+
+        // Only calls to grammar rules are hoisted, not built-ins such as `ident`
+        assert_eq!(
+            common_prefix_runs(&grammar.rules[0].variants, &rules),
+            [0..3, 9..11]
+        );
+    }
+
+    #[test]
+    fn test_find_keyword_shadowing() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar test {
+                rule main -> i32 = "value" value:value -> { value } | v:value -> { v }
+                rule value -> i32 = "=" v:i32 -> { v }
+                // Matching its own name is not confusing
+                rule state -> i32 = "state" v:i32 -> { v }
+            }
+        })
+        .unwrap();
+        let messages: Vec<String> = find_keyword_shadowing(&p_ast.into())
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Binding 'value' in rule 'main' has the same name as the keyword \"value\" (kw::value); consider renaming the binding, e.g. to 'value_'",
+                "Rule 'value' has the same name as the keyword \"value\" (kw::value); consider renaming the rule, e.g. to 'value_'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyword_shadowing_notes() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar test {
+                keywords(query) { union }
+                rule main -> () = "value" v:value -> { () } | "union" q:query -> { () }
+                rule value -> () = "=" -> { () }
+                rule query -> () = "union" -> { () }
+            }
+        })
+        .unwrap();
+        let messages: Vec<Vec<String>> = find_keyword_shadowing(&p_ast.into())
+            .into_iter()
+            .map(|e| e.into_iter().map(|m| m.to_string()).collect())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                vec![
+                    "Rule 'value' has the same name as the keyword \"value\" (kw::value); consider renaming the rule, e.g. to 'value_'",
+                    "the keyword \"value\" is matched here",
+                ],
+                // `query` matches `kw::union`, `main` the syn token
+                vec![
+                    "\"union\" is matched as Token![union] in rule 'main', but as the keyword kw::union in the rules it is declared for; declare it for rule 'main' as well or with `keyword \"union\";` to match it the same way everywhere",
+                    "\"union\" is declared as a keyword here",
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_unused_bindings() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar test {
+                rule main -> String =
+                    a:attrs "fn" n:ident _r:attrs -> { n.to_string() }
+                  | a:attrs k:i32 v:scaled(k) -> { format!("{:?}", (a, v)) }
+                  | skip(attrs) xs:attrs* -> { String::new() }
+                rule attrs -> () = "#" -> { () }
+                rule scaled(k: i32) -> i32 = v:i32 -> { v * k }
+            }
+        })
+        .unwrap();
+        let messages: Vec<String> = find_unused_bindings(&p_ast.into())
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Binding 'a' in rule 'main' is never used; use `skip(attrs)` to parse and discard the value",
+                "Binding 'xs' in rule 'main' is never used; use `skip(attrs)` to parse and discard the value",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyword_table_runs() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar stmts {
+                rule stmt -> () =
+                    "a" -> { () } | "b" -> { () } | "c" -> { () } | "d" -> { () }
+                  | "e" -> { () } | "f" -> { () } | "g" -> { () } | "h" -> { () }
+                  | x:ident -> { () }
+                  | "i" -> { () } | "j" -> { () } | "k" => "l" -> { () } | "m" -> { () }
+                  | "n" -> { () } | "o" -> { () } | "p" -> { () } | "q" -> { () }
+                  | "r" -> { () } | "s" -> { () } | "v" -> { () } | "t" -> { () } | "t" "u" -> { () }
+            }
+        })
+        .unwrap();
+        let grammar: GrammarDefinition = p_ast.into();
+
+        // The cut in `k` and the shared start of `t` end the runs
+        assert_eq!(
+            keyword_table_runs(&grammar.rules[0].variants),
+            [0..8, 12..20]
+        );
+
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar colors {
+                rule color -> () = "red" -> { () } | c:"green" -> { () } | "blue" -> { () }
+                rule short -> () = "red" -> { () } | "green" "x" -> { () }
+                rule repeated -> () = "red" -> { () } | "red" -> { () }
+            }
+        })
+        .unwrap();
+        let grammar: GrammarDefinition = p_ast.into();
+
+        // Short alternations of keywords alone form a table
+        assert_eq!(keyword_table_runs(&grammar.rules[0].variants), vec![(0..3)]);
+        assert!(keyword_table_runs(&grammar.rules[1].variants).is_empty());
+        assert!(keyword_table_runs(&grammar.rules[2].variants).is_empty());
+    }
+
+    #[test]
+    fn test_find_test_only_calls() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar dumps {
+                pub rule main -> i32 = e:expr -> { e } | d:dump -> { d }
+                rule expr -> i32 = i:i32 -> { i } | "(" d:dump ")" -> { d }
+
+                #[cfg(test)]
+                pub rule dump -> i32 = "dump" e:expr -> { e } | m:marker -> { m }
+
+                #[cfg(test)]
+                rule marker -> i32 = "@" -> { 0 }
+            }
+        })
+        .unwrap();
+        let messages: Vec<String> = find_test_only_calls(&p_ast.into())
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("Rule 'main' calls the test-only rule 'dump'"));
+        assert!(messages[1].starts_with("Rule 'expr' calls the test-only rule 'dump'"));
+    }
+
+    #[test]
+    fn test_eliminate_indirect_left_recursion() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar calc {
+                pub rule expr -> i32 =
+                    b:binary -> { b }
+                  | t:term -> { t }
+
+                rule binary -> i32 =
+                    l:expr "-" r:term -> { l - r }
+                  | l:expr "*" r:term -> { l * r }
+
+                rule term -> i32 = i:i32 -> { i }
+
+                // `a -> b -> a`, but `b` takes a parameter
+                rule a -> i32 = x:b(1) -> { x } | t:term -> { t }
+                rule b(n: i32) -> i32 = y:a "!" -> { y + n }
+            }
+        })
+        .unwrap();
+        let mut grammar: GrammarDefinition = p_ast.into();
+
+        let remaining = eliminate_indirect_left_recursion(&mut grammar);
+        assert_eq!(remaining, [vec!["a".to_string(), "b".to_string()]]);
+
+        let expr = &grammar.rules[0];
+        let (recursive, base) = split_left_recursive(&expr.name, &expr.variants);
+        assert_eq!(recursive.len(), 2);
+        assert_eq!(base.len(), 1);
+        assert_eq!(
+            recursive[0].action.to_string(),
+            quote! { let b = { l - r }; b }.to_string()
+        );
+        // The called rule is kept
+        assert_eq!(grammar.rules[1].variants.len(), 2);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(quote! {
+            grammar calc {
+                pub rule expr -> i32 =
+                    l:expr "+" r:term -> { l + r }
+                  | t:term -> { t }
+
+                rule term -> i32 = s:sign? i:i32 -> { i }
+
+                rule sign -> () = "-"? -> { () }
+
+                rule dead -> () = "x" -> { () }
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            to_dot(&p_ast.into()),
+            "digraph calc {\n\
+             \x20   \"expr\";\n\
+             \x20   \"term\";\n\
+             \x20   \"sign\";\n\
+             \x20   \"dead\" [color=red];\n\
+             \x20   \"expr\" -> \"expr\" [label=\"left-recursive\"];\n\
+             \x20   \"expr\" -> \"term\";\n\
+             \x20   \"term\" -> \"sign\" [label=\"nullable\"];\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_keyword_scopes() {
+        let p: crate::parser::GrammarDefinition = parse_quote! {
+            grammar test {
+                keywords { select }
+                keywords(query) { from, union }
+                rule query -> () = "select" "union" -> { () }
+                rule other -> () = "union" -> { () }
+            }
+        };
+        let grammar = GrammarDefinition::from(p);
+
+        assert_eq!(
+            reserved_keywords(&grammar, "query"),
+            ["from", "select", "union"]
+        );
+        assert_eq!(reserved_keywords(&grammar, "other"), ["select"]);
+
+        // `union` is a custom keyword only where it is declared, but has a `kw` type
+        assert!(collect_rule_keywords(&grammar, "query").contains("union"));
+        assert!(!collect_rule_keywords(&grammar, "other").contains("union"));
+        assert!(collect_custom_keywords(&grammar).contains("union"));
+        assert!(collect_custom_keywords(&grammar).contains("from"));
+    }
+
+    #[test]
+    fn test_keyword_decision_table() {
+        let none = HashSet::new();
+        let declared: HashSet<String> = ["union".to_string()].into_iter().collect();
+
+        assert!(is_custom_keyword("macro_rules", &none));
+        assert!(!is_custom_keyword("fn", &none));
+        assert!(!is_custom_keyword("_", &none));
+        assert!(!is_custom_keyword("union", &none));
+        assert!(is_custom_keyword("union", &declared));
+
+        let lit: syn::LitStr = parse_quote!("union");
+        let ty = &resolve_token_types(&lit, &none).unwrap()[0];
+        assert_eq!(quote!(#ty).to_string(), "Token ! [union]");
+        let ty = &resolve_token_types(&lit, &declared).unwrap()[0];
+        assert_eq!(quote!(#ty).to_string(), "kw :: union");
+    }
 }
@@ -0,0 +1,525 @@
+//! Resolution of `import "file" as alias;` declarations.
+//!
+//! Every imported file is parsed, validated on its own and merged into the importing grammar:
+//! the rule `token` of the grammar imported as `lex` becomes the rule `lex_token`, and the
+//! calls `lex::token` of the importer are rewritten to call it. Imported grammars may import
+//! other files themselves, with paths relative to their own directory; import cycles are
+//! reported at the import that closes them. Warnings about imported rules are printed once, by
+//! the validation of the merged grammar.
+
+use crate::model::{Argument, Backend, GrammarDefinition, GrammarImport, ModelPattern};
+use crate::{parser, validator};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use syn::{Ident, Result};
+
+/// The directory paths of imports in a `grammar!` invocation are relative to: the root of the
+/// crate being compiled, like the paths of `include_str!` after `env!("CARGO_MANIFEST_DIR")`.
+pub fn manifest_dir() -> PathBuf {
+    std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+/// Merges the rules of the grammars imported by `grammar`, with paths relative to `base_dir`,
+/// and resolves its qualified calls `alias::rule`. The files read are recorded in
+/// `grammar.dependencies`.
+pub fn resolve_imports<B: Backend>(grammar: &mut GrammarDefinition, base_dir: &Path) -> Result<()> {
+    let mut files = Files {
+        base_dir: base_dir.to_path_buf(),
+        dependencies: Vec::new(),
+    };
+    resolve::<B, _>(grammar, None, &mut files, &mut Vec::new())?;
+    grammar.dependencies = files.dependencies;
+    Ok(())
+}
+
+/// Like [`resolve_imports`], but takes the imported grammars from `grammars` instead of reading
+/// files: `import "lexer.grammar" as lex;` imports the grammar named `lexer`.
+pub fn resolve_imports_in<B: Backend>(
+    grammar: &mut GrammarDefinition,
+    grammars: &[GrammarDefinition],
+) -> Result<()> {
+    let key = grammar.name.to_string();
+    resolve::<B, _>(grammar, None, &mut Set { grammars }, &mut vec![key])
+}
+
+/// Where the grammars of imports come from.
+trait Source {
+    /// Identifies an imported grammar, to detect import cycles.
+    type Key: Clone + PartialEq;
+
+    /// The grammar `import` refers to, in the grammar identified by `importer` (`None` for the
+    /// grammar being resolved).
+    fn locate(&self, importer: Option<&Self::Key>, import: &GrammarImport) -> Result<Self::Key>;
+
+    /// The name of the grammar in messages.
+    fn display(key: &Self::Key) -> String;
+
+    /// The grammar, before resolving its own imports.
+    fn read(&mut self, key: &Self::Key) -> Result<GrammarDefinition>;
+}
+
+/// Grammar files, with paths relative to the importing file.
+struct Files {
+    base_dir: PathBuf,
+    dependencies: Vec<PathBuf>,
+}
+
+impl Source for Files {
+    type Key = PathBuf;
+
+    fn locate(&self, importer: Option<&PathBuf>, import: &GrammarImport) -> Result<PathBuf> {
+        let dir = importer
+            .and_then(|file| file.parent())
+            .unwrap_or(&self.base_dir);
+        let path = dir.join(import.path.value());
+        path.canonicalize().map_err(|e| {
+            syn::Error::new(
+                import.path.span(),
+                format!("cannot read `{}`: {}", path.display(), e),
+            )
+        })
+    }
+
+    fn display(key: &PathBuf) -> String {
+        key.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| key.display().to_string())
+    }
+
+    fn read(&mut self, key: &PathBuf) -> Result<GrammarDefinition> {
+        let source = std::fs::read_to_string(key).map_err(|e| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!("cannot read `{}`: {}", key.display(), e),
+            )
+        })?;
+        if !self.dependencies.contains(key) {
+            self.dependencies.push(key.clone());
+        }
+        let parsed: parser::GrammarDefinition = syn::parse_str(&source)?;
+        Ok(parsed.into())
+    }
+}
+
+/// Grammars validated together, found by the file stem of the import path.
+struct Set<'a> {
+    grammars: &'a [GrammarDefinition],
+}
+
+impl Source for Set<'_> {
+    type Key = String;
+
+    fn locate(&self, _importer: Option<&String>, import: &GrammarImport) -> Result<String> {
+        let path = import.path.value();
+        let stem = Path::new(&path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if self.grammars.iter().any(|g| g.name == stem) {
+            Ok(stem)
+        } else {
+            Err(syn::Error::new(
+                import.path.span(),
+                format!(
+                    "`{}` imports the grammar '{}', which is not in the set",
+                    path, stem
+                ),
+            ))
+        }
+    }
+
+    fn display(key: &String) -> String {
+        key.clone()
+    }
+
+    fn read(&mut self, key: &String) -> Result<GrammarDefinition> {
+        Ok(self
+            .grammars
+            .iter()
+            .find(|g| g.name == *key)
+            .cloned()
+            .expect("located grammars are in the set"))
+    }
+}
+
+/// The rules an alias refers to, under their names in the imported file.
+struct ImportedGrammar {
+    public: HashSet<String>,
+    rules: HashSet<String>,
+}
+
+fn resolve<B: Backend, S: Source>(
+    grammar: &mut GrammarDefinition,
+    importer: Option<&S::Key>,
+    source: &mut S,
+    stack: &mut Vec<S::Key>,
+) -> Result<()> {
+    let mut aliases: HashMap<String, ImportedGrammar> = HashMap::new();
+    let mut merged = Vec::new();
+
+    // Resolved grammars keep no imports, so resolving them again does nothing
+    for import in std::mem::take(&mut grammar.imports) {
+        let alias = import.alias.to_string();
+        if aliases.contains_key(&alias) {
+            return Err(syn::Error::new(
+                import.alias.span(),
+                format!("Duplicate import alias: '{}'", alias),
+            ));
+        }
+
+        let sub = load::<B, S>(&import, importer, source, stack)?;
+        aliases.insert(
+            alias.clone(),
+            ImportedGrammar {
+                public: sub
+                    .rules
+                    .iter()
+                    .filter(|r| r.is_pub)
+                    .map(|r| r.name.to_string())
+                    .collect(),
+                rules: sub.rules.iter().map(|r| r.name.to_string()).collect(),
+            },
+        );
+
+        for item in sub.uses {
+            let text = quote::quote!(#item).to_string();
+            if !grammar
+                .uses
+                .iter()
+                .any(|u| quote::quote!(#u).to_string() == text)
+            {
+                grammar.uses.push(item);
+            }
+        }
+        for kw in sub.keywords {
+            if !grammar.keywords.iter().any(|k| k.value() == kw.value()) {
+                grammar.keywords.push(kw);
+            }
+        }
+
+        let own: HashSet<String> = sub.rules.iter().map(|r| r.name.to_string()).collect();
+        // Keywords reserved in all rules of the imported grammar stay reserved in its rules only
+        for mut set in sub.keyword_sets {
+            if set.rules.is_empty() {
+                set.rules = sub.rules.iter().map(|r| r.name.clone()).collect();
+                if set.rules.is_empty() {
+                    continue;
+                }
+            }
+            for rule in &mut set.rules {
+                *rule = mangle(&import.alias, rule);
+            }
+            grammar.keyword_sets.push(set);
+        }
+        for mut rule in sub.rules {
+            let params: HashSet<String> = rule.params.iter().map(|(p, _)| p.to_string()).collect();
+            rule.name = mangle(&import.alias, &rule.name);
+            if let Some(precedence) = &mut rule.precedence {
+                if own.contains(&precedence.operand.to_string()) {
+                    precedence.operand = mangle(&import.alias, &precedence.operand);
+                }
+            }
+            for variant in &mut rule.variants {
+                visit_rule_calls_mut(&mut variant.pattern, &mut |_, rule_name| {
+                    let name = rule_name.to_string();
+                    if own.contains(&name) && !params.contains(&name) {
+                        *rule_name = mangle(&import.alias, rule_name);
+                    }
+                    Ok(())
+                })?;
+            }
+            merged.push(rule);
+        }
+    }
+
+    let defined: HashSet<String> = grammar.rules.iter().map(|r| r.name.to_string()).collect();
+    for rule in &merged {
+        if defined.contains(&rule.name.to_string()) {
+            return Err(syn::Error::new(
+                rule.name.span(),
+                format!(
+                    "Rule '{}' collides with the rule of the same name created for an imported rule",
+                    rule.name
+                ),
+            ));
+        }
+    }
+    let mangled: HashSet<String> = merged.iter().map(|r| r.name.to_string()).collect();
+
+    for rule in &mut grammar.rules {
+        for variant in &mut rule.variants {
+            visit_rule_calls_mut(&mut variant.pattern, &mut |module, rule_name| {
+                let Some(alias) = module.take() else {
+                    if mangled.contains(&rule_name.to_string()) {
+                        return Err(syn::Error::new(
+                            rule_name.span(),
+                            format!(
+                                "Rule '{}' is imported; call it by its qualified name",
+                                rule_name
+                            ),
+                        ));
+                    }
+                    return Ok(());
+                };
+                let Some(imported) = aliases.get(&alias.to_string()) else {
+                    return Err(syn::Error::new(
+                        alias.span(),
+                        format!("Unknown grammar: '{}' is not an import alias", alias),
+                    ));
+                };
+                let name = rule_name.to_string();
+                if !imported.rules.contains(&name) {
+                    return Err(syn::Error::new(
+                        rule_name.span(),
+                        format!("Grammar '{}' has no rule '{}'", alias, name),
+                    ));
+                }
+                if !imported.public.contains(&name) {
+                    return Err(syn::Error::new(
+                        rule_name.span(),
+                        format!(
+                            "Rule '{}::{}' is private; mark it `pub` to call it from another grammar",
+                            alias, name
+                        ),
+                    ));
+                }
+                *rule_name = mangle(&alias, rule_name);
+                Ok(())
+            })?;
+        }
+    }
+
+    grammar.rules.extend(merged);
+    Ok(())
+}
+
+/// Reads, resolves and validates the grammar of an import.
+fn load<B: Backend, S: Source>(
+    import: &GrammarImport,
+    importer: Option<&S::Key>,
+    source: &mut S,
+    stack: &mut Vec<S::Key>,
+) -> Result<GrammarDefinition> {
+    let err = |msg: String| syn::Error::new(import.path.span(), msg);
+
+    let key = source.locate(importer, import)?;
+    if let Some(start) = stack.iter().position(|k| *k == key) {
+        let cycle: Vec<String> = stack[start..]
+            .iter()
+            .chain(std::iter::once(&key))
+            .map(S::display)
+            .collect();
+        return Err(err(format!("import cycle: {}", cycle.join(" -> "))));
+    }
+
+    // Errors inside the imported file have spans that do not point into this file.
+    let in_file = |e: syn::Error| err(format!("in `{}`: {}", import.path.value(), e));
+
+    let mut sub = source.read(&key).map_err(in_file)?;
+    if sub.inherits.is_some() {
+        return Err(err(format!(
+            "imported grammar `{}` cannot inherit from another grammar",
+            import.path.value()
+        )));
+    }
+    if sub.token_pool.is_some() {
+        return Err(err(format!(
+            "imported grammar `{}` cannot declare `tokens from`",
+            import.path.value()
+        )));
+    }
+
+    stack.push(key.clone());
+    let resolved = resolve::<B, S>(&mut sub, Some(&key), source, stack);
+    stack.pop();
+    resolved.map_err(in_file)?;
+
+    validator::validate_quietly::<B>(&sub).map_err(in_file)?;
+    Ok(sub)
+}
+
+/// The name of the rule `rule` of the grammar imported as `alias` in the merged grammar.
+fn mangle(alias: &Ident, rule: &Ident) -> Ident {
+    Ident::new(&format!("{}_{}", alias, rule), rule.span())
+}
+
+fn visit_rule_calls_mut<F>(patterns: &mut [ModelPattern], cb: &mut F) -> Result<()>
+where
+    F: FnMut(&mut Option<Ident>, &mut Ident) -> Result<()>,
+{
+    for p in patterns {
+        match p {
+            ModelPattern::RuleCall {
+                module,
+                rule_name,
+                args,
+                ..
+            } => {
+                cb(module, rule_name)?;
+                for arg in args {
+                    match arg {
+                        Argument::Positional(p) | Argument::Named(_, p) => {
+                            visit_rule_calls_mut(std::slice::from_mut(p), cb)?;
+                        }
+                    }
+                }
+            }
+            ModelPattern::Group(alts, _) => {
+                for (alt, _) in alts {
+                    visit_rule_calls_mut(alt, cb)?;
+                }
+            }
+            ModelPattern::Optional(inner, _)
+            | ModelPattern::Repeat(inner, _)
+            | ModelPattern::Plus(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. }
+            | ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Collect(inner, _, _)
+            | ModelPattern::Peek(inner, _)
+            | ModelPattern::Not(inner, _)
+            | ModelPattern::Follows(inner, _)
+            | ModelPattern::Until { pattern: inner, .. } => {
+                visit_rule_calls_mut(std::slice::from_mut(inner), cb)?;
+            }
+            ModelPattern::Recover { body, sync, .. } => {
+                visit_rule_calls_mut(std::slice::from_mut(body), cb)?;
+                visit_rule_calls_mut(std::slice::from_mut(sync), cb)?;
+            }
+            ModelPattern::Fold { item, op, .. } => {
+                visit_rule_calls_mut(std::slice::from_mut(item), cb)?;
+                visit_rule_calls_mut(std::slice::from_mut(op), cb)?;
+            }
+            ModelPattern::Bracketed(inner, _)
+            | ModelPattern::Braced(inner, _)
+            | ModelPattern::Parenthesized(inner, _)
+            | ModelPattern::Scope(inner, _) => {
+                visit_rule_calls_mut(inner, cb)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BuiltIn;
+    use quote::quote;
+
+    struct TestBackend;
+    impl Backend for TestBackend {
+        fn get_builtins() -> &'static [BuiltIn] {
+            &[BuiltIn {
+                name: "ident",
+                return_type: "syn::Ident",
+                peek: Some("syn::Ident"),
+            }]
+        }
+    }
+
+    /// A fresh directory containing the given grammar files.
+    fn grammar_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "syn-grammar-imports-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, source) in files {
+            std::fs::write(dir.join(file), source).unwrap();
+        }
+        dir
+    }
+
+    fn resolve_in(dir: &Path, input: proc_macro2::TokenStream) -> Result<GrammarDefinition> {
+        let p_ast: parser::GrammarDefinition = syn::parse2(input).unwrap();
+        let mut grammar: GrammarDefinition = p_ast.into();
+        resolve_imports::<TestBackend>(&mut grammar, dir)?;
+        Ok(grammar)
+    }
+
+    #[test]
+    fn test_resolve_imports() {
+        let dir = grammar_dir(
+            "resolve",
+            &[(
+                "lexer.grammar",
+                "grammar lexer { pub rule name -> syn::Ident = i:word -> { i } rule word -> syn::Ident = i:ident -> { i } }",
+            )],
+        );
+        let mut grammar = resolve_in(
+            &dir,
+            quote! {
+                grammar main {
+                    import "lexer.grammar" as lex;
+                    pub rule main -> syn::Ident = n:lex::name -> { n }
+                }
+            },
+        )
+        .unwrap();
+
+        let names: Vec<String> = grammar.rules.iter().map(|r| r.name.to_string()).collect();
+        assert_eq!(names, ["main", "lex_name", "lex_word"]);
+        let mut calls = Vec::new();
+        for rule in &mut grammar.rules {
+            visit_rule_calls_mut(&mut rule.variants[0].pattern, &mut |_, name| {
+                calls.push(name.to_string());
+                Ok(())
+            })
+            .unwrap();
+        }
+        assert_eq!(calls, ["lex_name", "lex_word", "ident"]);
+        assert_eq!(
+            grammar.dependencies,
+            [dir.join("lexer.grammar").canonicalize().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let dir = grammar_dir(
+            "errors",
+            &[
+                (
+                    "a.grammar",
+                    "grammar a { import \"b.grammar\" as b; pub rule x -> () = \"x\" -> {} }",
+                ),
+                (
+                    "b.grammar",
+                    "grammar b { import \"a.grammar\" as a; pub rule y -> () = \"y\" -> {} }",
+                ),
+                ("c.grammar", "grammar c { rule hidden -> () = \"h\" -> {} }"),
+            ],
+        );
+        let err = |input| resolve_in(&dir, input).unwrap_err().to_string();
+
+        assert_eq!(
+            err(quote! { grammar g { import "a.grammar" as a; rule m -> () = "m" -> {} } }),
+            "in `a.grammar`: in `b.grammar`: import cycle: a.grammar -> b.grammar -> a.grammar"
+        );
+        assert_eq!(
+            err(quote! { grammar g { import "c.grammar" as c; rule m -> () = c::hidden -> {} } }),
+            "Rule 'c::hidden' is private; mark it `pub` to call it from another grammar"
+        );
+        assert_eq!(
+            err(quote! { grammar g { import "c.grammar" as c; rule m -> () = c::other -> {} } }),
+            "Grammar 'c' has no rule 'other'"
+        );
+        assert_eq!(
+            err(quote! { grammar g { rule m -> () = lex::name -> {} } }),
+            "Unknown grammar: 'lex' is not an import alias"
+        );
+        assert!(err(
+            quote! { grammar g { import "missing.grammar" as m; rule m -> () = "m" -> {} } }
+        )
+        .starts_with("cannot read"));
+    }
+}
@@ -8,33 +8,51 @@
 //!
 //! 1. **[parser]**: Parse input tokens into a syntactic AST.
 //! 2. **[model]**: Convert the AST into a semantic model (via `Into`).
-//! 3. **[validator]**: Validate the model for semantic correctness.
-//! 4. **[analysis]**: Extract information (keywords, recursion) for code generation.
+//! 3. **[imports]**: Merge the rules of imported grammar files.
+//! 4. **[validator]**: Validate the model for semantic correctness.
+//! 5. **[analysis]**: Rewrite indirect left recursion and extract information (keywords,
+//!    recursion) for code generation.
 
 use proc_macro2::TokenStream;
 use syn::Result;
 
 pub mod analysis;
+pub mod imports;
 pub mod model;
 pub mod parser;
 pub mod validator;
 
 pub use model::{Backend, BuiltIn};
 pub use proc_macro2::Span;
+pub use validator::validate_set;
 
 /// Reusable pipeline: Parses, transforms, and validates the grammar.
 ///
-/// This encapsulates the standard 3-step process used by all backends.
+/// This encapsulates the standard process used by all backends.
 ///
 /// This function uses the provided `Backend` to validate built-ins.
 pub fn parse_grammar<B: Backend>(input: TokenStream) -> Result<model::GrammarDefinition> {
+    let mut m_ast = parse_and_validate::<B>(input)?;
+
+    // 5. Rewriting: Turn indirect left recursion into direct left recursion
+    analysis::eliminate_indirect_left_recursion(&mut m_ast);
+
+    Ok(m_ast)
+}
+
+/// Like [`parse_grammar`], but returns the rules as written, without rewriting indirect left
+/// recursion. Useful to report properties of the grammar the user wrote.
+pub fn parse_and_validate<B: Backend>(input: TokenStream) -> Result<model::GrammarDefinition> {
     // 1. Parsing: From TokenStream to syntactic AST
     let p_ast: parser::GrammarDefinition = syn::parse2(input)?;
 
     // 2. Transformation: From syntactic AST to semantic model
-    let m_ast: model::GrammarDefinition = p_ast.into();
+    let mut m_ast: model::GrammarDefinition = p_ast.into();
+
+    // 3. Imports: Merge the rules of imported grammar files
+    imports::resolve_imports::<B>(&mut m_ast, &imports::manifest_dir())?;
 
-    // 3. Validation: Check for semantic errors
+    // 4. Validation: Check for semantic errors
     validator::validate::<B>(&m_ast)?;
 
     Ok(m_ast)
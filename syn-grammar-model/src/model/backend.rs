@@ -7,6 +7,10 @@ pub struct BuiltIn {
     /// This allows backends to declare portable types (e.g., "syn_grammar_model::model::types::Identifier")
     /// or backend-specific types (e.g., "syn::Ident").
     pub return_type: &'static str,
+    /// The token type that can be peeked to decide whether the built-in applies
+    /// (e.g., "syn::LitStr" for `string`), or `None` if no single token identifies it.
+    /// Analysis uses this to avoid speculative parsing of built-ins in loops and optionals.
+    pub peek: Option<&'static str>,
 }
 
 /// A trait that backends must implement to declare their capabilities.
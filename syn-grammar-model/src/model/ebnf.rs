@@ -0,0 +1,489 @@
+//! Export of grammars in EBNF and ABNF notation, e.g. for language references.
+
+use super::*;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Notation {
+    /// The EBNF of the W3C XML specification: `a ::= b c* | "x"`.
+    Ebnf,
+    /// ABNF as in RFC 5234: `a = b *c / "x"`.
+    Abnf,
+}
+
+impl GrammarDefinition {
+    /// Pretty-prints the grammar in the EBNF notation of the W3C XML specification.
+    ///
+    /// Bindings and actions are left out. Lookahead, `recover` blocks and other constructs
+    /// without an EBNF equivalent are kept as `/* .. */` comments, and doc comments of the
+    /// rules precede them. Calls with arguments are printed as `rule(arg, ..)`.
+    pub fn to_ebnf(&self) -> String {
+        print_grammar(self, Notation::Ebnf)
+    }
+
+    /// Pretty-prints the grammar in ABNF (RFC 5234). Rule names use `-` instead of `_`, and
+    /// constructs without an ABNF equivalent are described by `;` comments above the rule.
+    pub fn to_abnf(&self) -> String {
+        print_grammar(self, Notation::Abnf)
+    }
+
+    /// Summarizes the grammar in Markdown, e.g. for the documentation of a parser: a section
+    /// per rule with its doc comments and its syntax in the notation of
+    /// [`GrammarDefinition::to_ebnf`].
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Grammar `{}`\n", self.name);
+        for rule in &self.rules {
+            let _ = write!(out, "\n## `{}`\n\n", rule.name);
+            let docs: Vec<String> = rule.attrs.iter().filter_map(doc_line).collect();
+            if !docs.is_empty() {
+                let _ = write!(out, "{}\n\n", docs.join("\n"));
+            }
+            let _ = write!(out, "```text\n{}```\n", rule.to_ebnf());
+        }
+        out
+    }
+}
+
+impl Rule {
+    /// Pretty-prints the rule as [`GrammarDefinition::to_ebnf`] does, without its doc comments.
+    pub fn to_ebnf(&self) -> String {
+        print_rule(self, Notation::Ebnf, false)
+    }
+}
+
+fn print_grammar(grammar: &GrammarDefinition, notation: Notation) -> String {
+    let rules: Vec<String> = grammar
+        .rules
+        .iter()
+        .map(|rule| print_rule(rule, notation, true))
+        .collect();
+    rules.join("\n")
+}
+
+/// Prints a rule, preceded by comments about it, which include its doc comments if `docs`
+/// is set.
+fn print_rule(rule: &Rule, notation: Notation, docs: bool) -> String {
+    let mut out = String::new();
+    let (define, alternative) = match notation {
+        Notation::Ebnf => ("::=", "|"),
+        Notation::Abnf => ("=", "/"),
+    };
+
+    let mut printer = Printer {
+        notation,
+        notes: Vec::new(),
+    };
+    let alternatives: Vec<String> = rule
+        .variants
+        .iter()
+        .map(|v| printer.alternative(&v.pattern))
+        .collect();
+
+    let mut comments: Vec<String> = match docs {
+        true => rule.attrs.iter().filter_map(doc_line).collect(),
+        false => Vec::new(),
+    };
+    if !rule.params.is_empty() {
+        let params: Vec<String> = rule.params.iter().map(|(p, _)| p.to_string()).collect();
+        comments.push(format!("parameters: {}", params.join(", ")));
+    }
+    if let Some(precedence) = &rule.precedence {
+        comments.push(format!(
+            "operators from loosest to tightest: {}",
+            describe_precedence(precedence)
+        ));
+    }
+    let name = printer.rule_name(&rule.name);
+    comments.extend(printer.notes);
+    for comment in comments {
+        let _ = match notation {
+            Notation::Ebnf => writeln!(out, "/* {} */", comment),
+            Notation::Abnf => writeln!(out, "; {}", comment),
+        };
+    }
+
+    // Continuation lines align the alternatives with the first one
+    let indent = " ".repeat(name.len() + define.len() - 1);
+    for (j, alt) in alternatives.iter().enumerate() {
+        let _ = if j == 0 {
+            writeln!(out, "{} {} {}", name, define, alt)
+        } else {
+            writeln!(out, "{} {} {}", indent, alternative, alt)
+        };
+    }
+    out
+}
+
+fn doc_line(attr: &Attribute) -> Option<String> {
+    let syn::Meta::NameValue(nv) = &attr.meta else {
+        return None;
+    };
+    match &nv.value {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: Lit::Str(s), ..
+        }) if nv.path.is_ident("doc") => Some(s.value().trim().to_string()),
+        _ => None,
+    }
+}
+
+fn describe_precedence(precedence: &Precedence) -> String {
+    let levels: Vec<String> = precedence
+        .levels
+        .iter()
+        .map(|level| {
+            let fixity = match level.fixity {
+                Fixity::Left => "left",
+                Fixity::Right => "right",
+                Fixity::Prefix => "prefix",
+                Fixity::Postfix => "postfix",
+            };
+            let ops: Vec<String> = level
+                .operators
+                .iter()
+                .map(|op| format!("\"{}\"", op.value()))
+                .collect();
+            format!("{} {}", fixity, ops.join(" "))
+        })
+        .collect();
+    levels.join("; ")
+}
+
+struct Printer {
+    notation: Notation,
+    /// Comments about the rule collected while printing it in ABNF, which has no inline
+    /// comments.
+    notes: Vec<String>,
+}
+
+impl Printer {
+    fn rule_name(&self, name: &Ident) -> String {
+        match self.notation {
+            Notation::Ebnf => name.to_string(),
+            Notation::Abnf => name.to_string().replace('_', "-"),
+        }
+    }
+
+    fn sequence(&mut self, patterns: &[ModelPattern]) -> String {
+        let parts: Vec<String> = patterns
+            .iter()
+            .map(|p| self.pattern(p).0)
+            .filter(|p| !p.is_empty())
+            .collect();
+        parts.join(" ")
+    }
+
+    /// Prints an alternative, marking one that matches empty input.
+    fn alternative(&mut self, patterns: &[ModelPattern]) -> String {
+        let text = self.sequence(patterns);
+        match self.notation {
+            _ if !text.is_empty() => text,
+            Notation::Ebnf => "/* empty */".to_string(),
+            Notation::Abnf => "\"\"".to_string(),
+        }
+    }
+
+    /// Prints a sequence as an operand of a postfix operator or of an ABNF repetition.
+    fn operand(&mut self, pattern: &ModelPattern) -> String {
+        let (text, atomic) = self.pattern(pattern);
+        if atomic {
+            text
+        } else {
+            format!("({})", text)
+        }
+    }
+
+    /// Adds a comment to `text`: inline in EBNF, as a note above the rule in ABNF.
+    fn annotate(&mut self, text: String, note: String) -> String {
+        match self.notation {
+            Notation::Ebnf if text.is_empty() => format!("/* {} */", note),
+            Notation::Ebnf => format!("{} /* {} */", text, note),
+            Notation::Abnf => {
+                self.notes.push(note);
+                text
+            }
+        }
+    }
+
+    fn literal(&self, text: &str) -> String {
+        match self.notation {
+            Notation::Ebnf if text.contains('"') => format!("'{}'", text),
+            Notation::Ebnf => format!("\"{}\"", text),
+            // ABNF strings cannot contain `"`, which is written as %x22
+            Notation::Abnf => text
+                .split('"')
+                .map(|s| format!("\"{}\"", s))
+                .collect::<Vec<_>>()
+                .join(" %x22 ")
+                .replace("\"\" ", "")
+                .replace(" \"\"", ""),
+        }
+    }
+
+    /// Prints a pattern. The flag is set if the text can take a postfix operator without
+    /// parentheses.
+    fn pattern(&mut self, pattern: &ModelPattern) -> (String, bool) {
+        match pattern {
+            ModelPattern::Cut(_) => (String::new(), true),
+            ModelPattern::Lit { lit, .. } => {
+                let text = match lit {
+                    Lit::Str(s) => s.value(),
+                    other => quote!(#other).to_string(),
+                };
+                let atomic = self.notation == Notation::Ebnf || !text.contains('"');
+                (self.literal(&text), atomic)
+            }
+            ModelPattern::RuleCall {
+                rule_name, args, ..
+            } => {
+                let name = self.rule_name(rule_name);
+                if args.is_empty() {
+                    return (name, true);
+                }
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Argument::Positional(p) => self.pattern(p).0,
+                        Argument::Named(n, p) => format!("{} = {}", n, self.pattern(p).0),
+                    })
+                    .collect();
+                (format!("{}({})", name, args.join(", ")), true)
+            }
+            ModelPattern::Group(alts, _) => {
+                let alternative = match self.notation {
+                    Notation::Ebnf => " | ",
+                    Notation::Abnf => " / ",
+                };
+                let alts: Vec<String> = alts.iter().map(|(seq, _)| self.alternative(seq)).collect();
+                if alts.len() == 1 {
+                    let atomic = !alts[0].contains(' ');
+                    return (alts.into_iter().next().unwrap_or_default(), atomic);
+                }
+                (format!("({})", alts.join(alternative)), true)
+            }
+            ModelPattern::Bracketed(seq, _) => self.delimited("[", seq, "]"),
+            ModelPattern::Braced(seq, _) => self.delimited("{", seq, "}"),
+            ModelPattern::Parenthesized(seq, _) => self.delimited("(", seq, ")"),
+            ModelPattern::Scope(seq, _) => {
+                let text = self.alternative(seq);
+                let atomic = !text.contains(' ');
+                (text, atomic)
+            }
+            ModelPattern::Optional(inner, _) => match self.notation {
+                Notation::Ebnf => (format!("{}?", self.operand(inner)), true),
+                Notation::Abnf => (format!("[{}]", self.pattern(inner).0), true),
+            },
+            ModelPattern::Repeat(inner, _) => match self.notation {
+                Notation::Ebnf => (format!("{}*", self.operand(inner)), true),
+                Notation::Abnf => (format!("*{}", self.operand(inner)), true),
+            },
+            ModelPattern::Plus(inner, _) => match self.notation {
+                Notation::Ebnf => (format!("{}+", self.operand(inner)), true),
+                Notation::Abnf => (format!("1*{}", self.operand(inner)), true),
+            },
+            ModelPattern::Bounded {
+                pattern, min, max, ..
+            } => {
+                let operand = self.operand(pattern);
+                match self.notation {
+                    // W3C EBNF has no counts: `x{2,4}` is printed as `x x x? x?`
+                    Notation::Ebnf => {
+                        let mut items = vec![operand.clone(); *min];
+                        match max {
+                            Some(max) => items
+                                .extend(std::iter::repeat_n(format!("{}?", operand), max - min)),
+                            None => items.push(format!("{}*", operand)),
+                        }
+                        let atomic = items.len() == 1;
+                        (items.join(" "), atomic)
+                    }
+                    Notation::Abnf => match max {
+                        Some(max) if max == min => (format!("{}{}", min, operand), true),
+                        Some(max) => (format!("{}*{}{}", min, max, operand), true),
+                        None => (format!("{}*{}", min, operand), true),
+                    },
+                }
+            }
+            ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Collect(inner, _, _) => self.pattern(inner),
+            ModelPattern::Recover { body, sync, .. } => {
+                let (text, atomic) = self.pattern(body);
+                let note = format!(
+                    "recover: on errors in {}, skip to {}",
+                    text,
+                    self.pattern(sync).0
+                );
+                let annotated = self.annotate(text, note);
+                let atomic = atomic && self.notation == Notation::Abnf;
+                (annotated, atomic)
+            }
+            ModelPattern::Peek(inner, _) => {
+                let note = format!("followed by {}", self.pattern(inner).0);
+                let text = self.annotate(String::new(), note);
+                (text, false)
+            }
+            ModelPattern::Not(inner, _) => {
+                let note = format!("not followed by {}", self.pattern(inner).0);
+                let text = self.annotate(String::new(), note);
+                (text, false)
+            }
+            ModelPattern::Follows(inner, _) => {
+                let note = format!("directly after {}", self.pattern(inner).0);
+                let text = self.annotate(String::new(), note);
+                (text, false)
+            }
+            // The associativity only changes the value, not the matched language
+            ModelPattern::Fold { item, op, .. } => {
+                let item = self.operand(item);
+                let op = self.operand(op);
+                match self.notation {
+                    Notation::Ebnf => (format!("{} ({} {})*", item, op, item), false),
+                    Notation::Abnf => (format!("{} *({} {})", item, op, item), false),
+                }
+            }
+            ModelPattern::Until { pattern, .. } => {
+                let end = self.operand(pattern);
+                match self.notation {
+                    // W3C EBNF: `A - B` matches A but not B
+                    Notation::Ebnf => (format!("(token - {})*", end), true),
+                    Notation::Abnf => {
+                        self.notes
+                            .push(format!("token: any token tree other than {}", end));
+                        ("*token".to_string(), true)
+                    }
+                }
+            }
+        }
+    }
+
+    fn delimited(&mut self, open: &str, seq: &[ModelPattern], close: &str) -> (String, bool) {
+        let inner = self.sequence(seq);
+        let parts = [self.literal(open), inner, self.literal(close)];
+        let text = parts
+            .iter()
+            .filter(|p| !p.is_empty())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        (text, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grammar(input: TokenStream) -> GrammarDefinition {
+        let p_ast: crate::parser::GrammarDefinition = syn::parse2(input).unwrap();
+        p_ast.into()
+    }
+
+    #[test]
+    fn test_to_ebnf() {
+        let g = grammar(quote! {
+            grammar calc {
+                /// An addition or a single term
+                pub rule expr -> i32 =
+                    l:expr "+" r:term -> { l + r }
+                  | t:term -> { t }
+
+                rule term -> i32 =
+                    s:sign? d:digit_list -> { d }
+                  | paren(e:expr) -> { e }
+
+                rule digit_list -> i32 = (d:i32 ",")+ -> { 0 }
+                rule sign -> () = "-" => not("-") -> { () }
+                rule stmt -> () = recover(expr, ";") ";" -> { () } | -> { () }
+                rule code -> () = ("a" | "b"){2,3} i32{2,} -> { () }
+            }
+        });
+        assert_eq!(
+            g.to_ebnf(),
+            r#"/* An addition or a single term */
+expr ::= expr "+" term
+       | term
+
+term ::= sign? digit_list
+       | "(" expr ")"
+
+digit_list ::= (i32 ",")+
+
+sign ::= "-" /* not followed by "-" */
+
+stmt ::= expr /* recover: on errors in expr, skip to ";" */ ";"
+       | /* empty */
+
+code ::= ("a" | "b") ("a" | "b") ("a" | "b")? i32 i32 i32*
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let g = grammar(quote! {
+            grammar calc {
+                /// An addition
+                /// of two terms
+                pub rule expr -> i32 = l:term "+" r:term -> { l + r }
+
+                rule term -> i32 = i:i32 -> { i } | paren(e:expr) -> { e }
+            }
+        });
+        assert_eq!(
+            g.to_markdown(),
+            r#"# Grammar `calc`
+
+## `expr`
+
+An addition
+of two terms
+
+```text
+expr ::= term "+" term
+```
+
+## `term`
+
+```text
+term ::= i32
+       | "(" expr ")"
+```
+"#
+        );
+    }
+
+    #[test]
+    fn test_to_abnf() {
+        let g = grammar(quote! {
+            grammar calc {
+                pub rule expr -> i32 =
+                    l:expr "+" r:term -> { l + r }
+                  | t:term -> { t }
+
+                rule term -> i32 = xs:digit_list* q:"\"" -> { 0 }
+                rule digit_list -> i32 = (d:i32 ",")+ -> { 0 }
+                rule stmt -> () = recover(expr, ";") ";" -> { () }
+                rule code -> () = ("a" | "b"){2,3} i32{2,} i32{4} -> { () }
+            }
+        });
+        assert_eq!(
+            g.to_abnf(),
+            r#"expr = expr "+" term
+     / term
+
+term = *digit-list %x22
+
+digit-list = 1*(i32 ",")
+
+; recover: on errors in expr, skip to ";"
+stmt = expr ";"
+
+code = 2*3("a" / "b") 2*i32 4i32
+"#
+        );
+    }
+}
@@ -1,38 +1,83 @@
 // Moved from macros/src/model.rs
 pub mod backend;
+mod ebnf;
 pub mod types;
 
+pub use crate::parser::{
+    Fixity, GrammarImport, GrammarOptions, KeywordSet, Precedence, PrecedenceLevel,
+};
 pub use backend::*;
 pub use types::*;
 
 use crate::parser;
 use proc_macro2::{Span, TokenStream};
+use quote::quote;
 use syn::spanned::Spanned as _;
-use syn::{Attribute, Generics, Ident, ItemUse, Lit, Type};
+use syn::{Attribute, Generics, Ident, ItemUse, Lit, LitStr, Type};
 
 #[derive(Debug, Clone)]
 pub struct GrammarDefinition {
     pub name: Ident,
     pub inherits: Option<Ident>,
     pub uses: Vec<ItemUse>,
+    /// Words declared with `keyword "word";`, always matched as custom keywords.
+    pub keywords: Vec<LitStr>,
+    /// Words declared with `keywords { .. }`, which the `ident` built-in does not match in the
+    /// rules of their scope. See [`crate::analysis::reserved_keywords`].
+    pub keyword_sets: Vec<KeywordSet>,
+    /// Module declared with `tokens from path;` that provides the `kw` types instead of a
+    /// generated `kw` module, so that several grammars share the same keyword types.
+    pub token_pool: Option<syn::Path>,
+    /// Grammar files declared with `import "file" as alias;`. [`crate::imports::resolve_imports`]
+    /// merges their rules into `rules` and empties the list.
+    pub imports: Vec<GrammarImport>,
+    /// The files read to resolve the imports, including those imported by imported grammars,
+    /// so that backends can make the compiler track them.
+    pub dependencies: Vec<std::path::PathBuf>,
+    /// Settings declared with `options { .. }`. The parser has already applied them to the
+    /// rules; they are kept for backends.
+    pub options: GrammarOptions,
     pub rules: Vec<Rule>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Rule {
     pub attrs: Vec<Attribute>,
+    /// Set for rules with a visibility (`pub`, `pub(crate)` or `pub(super)`), which get entry
+    /// points.
     pub is_pub: bool,
+    pub vis: syn::Visibility,
     pub name: Ident,
     pub generics: Generics,
     pub params: Vec<(Ident, Option<Type>)>,
     pub return_type: Type,
+    /// The error type of the actions, declared with `throws Error`.
+    pub throws: Option<Type>,
     pub variants: Vec<RuleVariant>,
+    /// Set for a rule defined by `precedence(operand) { .. }`. Its `variants` then describe the
+    /// shapes of the expressions (the operand call, `op self`, `self op self` and `self op`)
+    /// for analysis and validation, and code generation parses it by precedence instead.
+    pub precedence: Option<Precedence>,
+    /// Set for rules created by monomorphizing a generic rule.
+    pub instantiation: Option<Instantiation>,
+}
+
+/// The generic rule a monomorphized rule was created from, and the call that created it.
+#[derive(Debug, Clone)]
+pub struct Instantiation {
+    pub template: Ident,
+    pub call_site: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct RuleVariant {
+    /// Attributes emitted on the generated code of the variant, e.g. `#[cold]` or `#[allow(..)]`.
+    pub attrs: Vec<Attribute>,
     pub pattern: Vec<ModelPattern>,
     pub label: Option<String>, // Added
+    /// The condition of a `where { .. }` guard, evaluated with the bindings of the pattern
+    /// before the action. If it is false, the variant fails like a pattern that did not match.
+    pub guard: Option<TokenStream>,
     pub action: TokenStream,
 }
 
@@ -51,6 +96,10 @@ pub enum ModelPattern {
     },
     RuleCall {
         binding: Option<Ident>,
+        /// The alias of the imported grammar of a call `alias::rule`. Calls are resolved to
+        /// the merged rules of the imported grammar while parsing, so this is always `None` in
+        /// a parsed grammar.
+        module: Option<Ident>,
         rule_name: Ident,
         generics: Vec<Type>,
         args: Vec<Argument>,
@@ -62,7 +111,26 @@ pub enum ModelPattern {
     Optional(Box<ModelPattern>, Span),
     Repeat(Box<ModelPattern>, Span),
     Plus(Box<ModelPattern>, Span),
+    /// `pattern{min,max}`: between `min` and `max` repetitions, or at least `min` if there is
+    /// no maximum.
+    Bounded {
+        pattern: Box<ModelPattern>,
+        min: usize,
+        max: Option<usize>,
+        span: Span,
+    },
     SpanBinding(Box<ModelPattern>, Ident, Span),
+    /// `name@(pattern)`: binds the verbatim tokens consumed by the pattern to `name`.
+    Raw(Box<ModelPattern>, Ident, Span),
+    /// A binding inside a repetition annotated with `#[collect(Type)]`, collecting its values
+    /// into `Type` instead of a `Vec`.
+    Collect(Box<ModelPattern>, Box<Type>, Span),
+    /// A pattern annotated with `#[expected("label")]`: failures before it consumed any input
+    /// are reported as "expected {label}" at the span of the original error.
+    Expected(Box<ModelPattern>, LitStr, Span),
+    /// A pattern with a `!` suffix: its failure is fatal, so the enclosing alternatives do not
+    /// backtrack, like after a cut.
+    Commit(Box<ModelPattern>, Span),
     Recover {
         binding: Option<Ident>,
         body: Box<ModelPattern>,
@@ -71,11 +139,36 @@ pub enum ModelPattern {
     },
     Peek(Box<ModelPattern>, Span),
     Not(Box<ModelPattern>, Span),
+    /// `follows(pattern)`: succeeds without consuming input if the tokens consumed so far in
+    /// the sequence end with a match of the pattern and the next token starts right after them.
+    Follows(Box<ModelPattern>, Span),
+    /// `declare(pattern)`: defines the text of the tokens matched by the pattern in the
+    /// innermost scope of the symbol table.
+    Declare(Box<ModelPattern>, Span),
+    /// `resolve(pattern)`: fails unless the text of the tokens matched by the pattern is
+    /// defined in an enclosing scope.
+    Resolve(Box<ModelPattern>, Span),
+    /// `scope { .. }`: a sequence parsed in a new scope of the symbol table. Its bindings are
+    /// visible after it, like those of a group.
+    Scope(Vec<ModelPattern>, Span),
     Until {
         binding: Option<Ident>,
         pattern: Box<ModelPattern>,
         span: Span,
     },
+    /// `fold_left(item, op, |l, op, r| expr)` or `fold_right(..)`: one or more items separated
+    /// by operators. The expression combines the operands with the value of the operator
+    /// between them, from the left (`(a + b) + c`) or, with `fixity: Right`, from the right
+    /// (`a ^ (b ^ c)`).
+    Fold {
+        binding: Option<Ident>,
+        fixity: Fixity,
+        item: Box<ModelPattern>,
+        op: Box<ModelPattern>,
+        params: Vec<syn::Pat>,
+        action: TokenStream,
+        span: Span,
+    },
 }
 
 impl From<parser::GrammarDefinition> for GrammarDefinition {
@@ -84,6 +177,12 @@ impl From<parser::GrammarDefinition> for GrammarDefinition {
             name: p.name,
             inherits: p.inherits.map(|spec| spec.name),
             uses: p.uses,
+            keywords: p.keywords,
+            keyword_sets: p.keyword_sets,
+            token_pool: p.token_pool,
+            imports: p.imports,
+            dependencies: Vec::new(),
+            options: p.options,
             rules: p.rules.into_iter().map(Into::into).collect(),
         }
     }
@@ -91,9 +190,14 @@ impl From<parser::GrammarDefinition> for GrammarDefinition {
 
 impl From<parser::Rule> for Rule {
     fn from(p: parser::Rule) -> Self {
+        let variants = match &p.precedence {
+            Some(precedence) => precedence_variants(&p.name, precedence),
+            None => p.variants.into_iter().map(Into::into).collect(),
+        };
         Self {
             attrs: p.attrs,
-            is_pub: p.is_pub.is_some(),
+            is_pub: !matches!(p.vis, syn::Visibility::Inherited),
+            vis: p.vis,
             name: p.name,
             generics: p.generics,
             params: p
@@ -102,17 +206,63 @@ impl From<parser::Rule> for Rule {
                 .map(|param| (param.name, param.ty))
                 .collect(),
             return_type: p.return_type,
-            variants: p.variants.into_iter().map(Into::into).collect(),
+            throws: p.throws,
+            variants,
+            precedence: p.precedence,
+            instantiation: None,
         }
     }
 }
 
+/// The alternatives an operator precedence rule is equivalent to, ignoring precedence.
+fn precedence_variants(name: &Ident, precedence: &Precedence) -> Vec<RuleVariant> {
+    let call = |rule_name: &Ident| ModelPattern::RuleCall {
+        binding: None,
+        module: None,
+        rule_name: rule_name.clone(),
+        generics: Vec::new(),
+        args: Vec::new(),
+    };
+    let variant = |pattern| RuleVariant {
+        attrs: Vec::new(),
+        pattern,
+        label: None,
+        guard: None,
+        action: quote!(unreachable!()),
+    };
+
+    let mut variants = vec![variant(vec![call(&precedence.operand)])];
+    for level in &precedence.levels {
+        for op in &level.operators {
+            let op = ModelPattern::Lit {
+                binding: None,
+                lit: Lit::Str(op.clone()),
+            };
+            variants.push(variant(match level.fixity {
+                Fixity::Left | Fixity::Right => vec![call(name), op, call(name)],
+                Fixity::Prefix => vec![op, call(name)],
+                Fixity::Postfix => vec![call(name), op],
+            }));
+        }
+    }
+    variants
+}
+
 impl From<parser::RuleVariant> for RuleVariant {
     fn from(p: parser::RuleVariant) -> Self {
+        let mut rebinds = Vec::new();
+        annotated_rebinds(&p.pattern, &mut rebinds);
+        let pattern: Vec<ModelPattern> = p.pattern.into_iter().map(Into::into).collect();
+        let action = match &p.struct_action {
+            Some(s) => s.to_expr(&crate::analysis::collect_bindings(&pattern)),
+            None => p.action,
+        };
         Self {
-            pattern: p.pattern.into_iter().map(Into::into).collect(),
+            attrs: p.attrs,
+            pattern,
             label: p.label, // Added
-            action: p.action,
+            guard: p.guard,
+            action: quote!(#(#rebinds)* #action),
         }
     }
 }
@@ -134,11 +284,13 @@ impl From<parser::Pattern> for ModelPattern {
             P::Lit { binding, lit } => ModelPattern::Lit { binding, lit },
             P::RuleCall {
                 binding,
+                module,
                 rule_name,
                 generics,
                 args,
             } => ModelPattern::RuleCall {
                 binding,
+                module,
                 rule_name,
                 generics,
                 args: args.into_iter().map(Argument::from).collect(),
@@ -168,9 +320,32 @@ impl From<parser::Pattern> for ModelPattern {
                 ModelPattern::Repeat(Box::new(ModelPattern::from(*p)), token.span())
             }
             P::Plus(p, token) => ModelPattern::Plus(Box::new(ModelPattern::from(*p)), token.span()),
+            P::Bounded {
+                pattern,
+                min,
+                max,
+                brace,
+            } => ModelPattern::Bounded {
+                pattern: Box::new(ModelPattern::from(*pattern)),
+                min,
+                max,
+                span: brace.span.join(),
+            },
             P::SpanBinding(p, ident, token) => {
                 ModelPattern::SpanBinding(Box::new(ModelPattern::from(*p)), ident, token.span)
             }
+            P::Raw(p, ident, token) => {
+                ModelPattern::Raw(Box::new(ModelPattern::from(*p)), ident, token.span)
+            }
+            P::Collect(p, ty, token) => {
+                ModelPattern::Collect(Box::new(ModelPattern::from(*p)), ty, token.span)
+            }
+            P::Expected(p, label, token) => {
+                ModelPattern::Expected(Box::new(ModelPattern::from(*p)), label, token.span)
+            }
+            P::Commit(p, token) => {
+                ModelPattern::Commit(Box::new(ModelPattern::from(*p)), token.span)
+            }
             P::Recover {
                 binding,
                 body,
@@ -184,6 +359,19 @@ impl From<parser::Pattern> for ModelPattern {
             },
             P::Peek(p, token) => ModelPattern::Peek(Box::new(ModelPattern::from(*p)), token.span()),
             P::Not(p, token) => ModelPattern::Not(Box::new(ModelPattern::from(*p)), token.span()),
+            P::Follows(p, token) => {
+                ModelPattern::Follows(Box::new(ModelPattern::from(*p)), token.span())
+            }
+            P::Declare(p, token) => {
+                ModelPattern::Declare(Box::new(ModelPattern::from(*p)), token.span())
+            }
+            P::Resolve(p, token) => {
+                ModelPattern::Resolve(Box::new(ModelPattern::from(*p)), token.span())
+            }
+            P::Scope(p, _, token) => ModelPattern::Scope(
+                p.into_iter().map(ModelPattern::from).collect(),
+                token.span.join(),
+            ),
             P::Until {
                 binding,
                 pattern,
@@ -193,6 +381,73 @@ impl From<parser::Pattern> for ModelPattern {
                 pattern: Box::new(ModelPattern::from(*pattern)),
                 span: kw_token.span(),
             },
+            P::Fold {
+                binding,
+                fixity,
+                item,
+                op,
+                params,
+                action,
+                kw_token,
+            } => ModelPattern::Fold {
+                binding,
+                fixity,
+                item: Box::new(ModelPattern::from(*item)),
+                op: Box::new(ModelPattern::from(*op)),
+                params,
+                action,
+                span: kw_token.span(),
+            },
+            // Annotations are applied to the action scope (see `From<parser::RuleVariant>`)
+            P::Annotated { pattern, .. } => ModelPattern::from(*pattern),
+        }
+    }
+}
+
+/// Rebinds `mut` and type-annotated bindings at the start of the action,
+/// e.g. `let mut xs: Vec<Item> = xs;`.
+fn annotated_rebinds(patterns: &[parser::Pattern], out: &mut Vec<TokenStream>) {
+    use parser::Pattern as P;
+    for p in patterns {
+        match p {
+            P::Annotated {
+                pattern,
+                binding,
+                mut_token,
+                ty,
+            } => {
+                let ty = ty.as_ref().map(|ty| quote!(: #ty));
+                out.push(quote!(let #mut_token #binding #ty = #binding;));
+                annotated_rebinds(std::slice::from_ref(pattern), out);
+            }
+            P::Group(alts, _) => {
+                for (seq, _) in alts {
+                    annotated_rebinds(seq, out);
+                }
+            }
+            P::Bracketed(seq, _)
+            | P::Braced(seq, _)
+            | P::Parenthesized(seq, _, _)
+            | P::Scope(seq, _, _) => {
+                annotated_rebinds(seq, out);
+            }
+            P::Optional(inner, _)
+            | P::Repeat(inner, _)
+            | P::Plus(inner, _)
+            | P::Bounded { pattern: inner, .. }
+            | P::SpanBinding(inner, _, _)
+            | P::Collect(inner, _, _)
+            | P::Expected(inner, _, _)
+            | P::Commit(inner, _)
+            | P::Raw(inner, _, _)
+            | P::Peek(inner, _)
+            | P::Declare(inner, _)
+            | P::Resolve(inner, _)
+            | P::Recover { body: inner, .. }
+            | P::Until { pattern: inner, .. } => {
+                annotated_rebinds(std::slice::from_ref(inner), out);
+            }
+            _ => {}
         }
     }
 }
@@ -206,14 +461,21 @@ impl ModelPattern {
             ModelPattern::Optional(_, s)
             | ModelPattern::Repeat(_, s)
             | ModelPattern::Plus(_, s) => *s,
-            ModelPattern::SpanBinding(_, _, s) => *s,
-            ModelPattern::Recover { span, .. } => *span,
+            ModelPattern::SpanBinding(_, _, s)
+            | ModelPattern::Raw(_, _, s)
+            | ModelPattern::Collect(_, _, s)
+            | ModelPattern::Expected(_, _, s) => *s,
+            ModelPattern::Recover { span, .. } | ModelPattern::Bounded { span, .. } => *span,
             ModelPattern::Group(_, s) => *s,
             ModelPattern::Bracketed(_, s)
             | ModelPattern::Braced(_, s)
-            | ModelPattern::Parenthesized(_, s) => *s,
-            ModelPattern::Peek(_, s) | ModelPattern::Not(_, s) => *s,
-            ModelPattern::Until { span, .. } => *span,
+            | ModelPattern::Parenthesized(_, s)
+            | ModelPattern::Scope(_, s) => *s,
+            ModelPattern::Peek(_, s) | ModelPattern::Not(_, s) | ModelPattern::Follows(_, s) => *s,
+            ModelPattern::Declare(_, s)
+            | ModelPattern::Resolve(_, s)
+            | ModelPattern::Commit(_, s) => *s,
+            ModelPattern::Until { span, .. } | ModelPattern::Fold { span, .. } => *span,
         }
     }
 }
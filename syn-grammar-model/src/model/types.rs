@@ -27,6 +27,18 @@ impl PartialEq for Identifier {
 
 impl Eq for Identifier {}
 
+impl PartialEq<str> for Identifier {
+    fn eq(&self, other: &str) -> bool {
+        self.text == other
+    }
+}
+
+impl PartialEq<&str> for Identifier {
+    fn eq(&self, other: &&str) -> bool {
+        self.text == *other
+    }
+}
+
 impl Hash for Identifier {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.text.hash(state);
@@ -1,7 +1,7 @@
 // Moved from macros/src/parser.rs
 use proc_macro2::TokenStream;
 use syn::parse::{Parse, ParseStream};
-use syn::{token, Attribute, Generics, Ident, ItemUse, Lit, Result, Token, Type};
+use syn::{token, Attribute, Generics, Ident, ItemUse, Lit, Result, Token, Type, Visibility};
 
 mod rt {
     use syn::ext::IdentExt;
@@ -35,18 +35,211 @@ pub mod kw {
     syn::custom_keyword!(recover);
     syn::custom_keyword!(peek);
     syn::custom_keyword!(not);
+    syn::custom_keyword!(follows);
     syn::custom_keyword!(until);
+    syn::custom_keyword!(fold_left);
+    syn::custom_keyword!(fold_right);
+    syn::custom_keyword!(keyword);
+    syn::custom_keyword!(keywords);
+    syn::custom_keyword!(collect);
+    syn::custom_keyword!(expected);
+    syn::custom_keyword!(tokens);
+    syn::custom_keyword!(from);
+    syn::custom_keyword!(precedence);
+    syn::custom_keyword!(left);
+    syn::custom_keyword!(right);
+    syn::custom_keyword!(prefix);
+    syn::custom_keyword!(postfix);
+    syn::custom_keyword!(import);
+    syn::custom_keyword!(options);
+    syn::custom_keyword!(throws);
+    syn::custom_keyword!(declare);
+    syn::custom_keyword!(resolve);
+    syn::custom_keyword!(scope);
 }
 
 pub struct GrammarDefinition {
     pub name: Ident,
     pub inherits: Option<InheritanceSpec>,
     pub uses: Vec<ItemUse>,
+    /// Words declared with `keyword "word";`, always matched as custom keywords.
+    pub keywords: Vec<syn::LitStr>,
+    /// Reserved words declared with `keywords { a, b }` or `keywords(rule) { a, b }`.
+    pub keyword_sets: Vec<KeywordSet>,
+    /// Module declared with `tokens from path;` that provides the `kw` types.
+    pub token_pool: Option<syn::Path>,
+    /// Grammar files declared with `import "file" as alias;`.
+    pub imports: Vec<GrammarImport>,
+    /// Settings declared with `options { .. }`, already applied to `rules`.
+    pub options: GrammarOptions,
     pub rules: Vec<Rule>,
 }
 
+/// `keywords(query, table) { select, from }`: words that the `ident` built-in does not match
+/// in the listed rules, or in all rules if the list is omitted (`keywords { select, from }`).
+#[derive(Debug, Clone)]
+pub struct KeywordSet {
+    /// The rules the words are reserved in; empty for all rules.
+    pub rules: Vec<Ident>,
+    pub words: Vec<Ident>,
+}
+
+impl Parse for KeywordSet {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let _ = input.parse::<kw::keywords>()?;
+        let rules = if input.peek(syn::token::Paren) {
+            let content;
+            let paren = syn::parenthesized!(content in input);
+            let rules = content.parse_terminated(rt::parse_ident, Token![,])?;
+            if rules.is_empty() {
+                return Err(syn::Error::new(
+                    paren.span.join(),
+                    "expected the rules the keywords are reserved in, e.g. `keywords(query) { select }`; omit the parentheses to reserve them in all rules",
+                ));
+            }
+            rules.into_iter().collect()
+        } else {
+            Vec::new()
+        };
+        let content;
+        let brace = syn::braced!(content in input);
+        let words = content.parse_terminated(rt::parse_ident, Token![,])?;
+        if words.is_empty() {
+            return Err(syn::Error::new(
+                brace.span.join(),
+                "expected at least one keyword, e.g. `keywords { select, from }`",
+            ));
+        }
+        Ok(KeywordSet {
+            rules,
+            words: words.into_iter().collect(),
+        })
+    }
+}
+
+/// `options { entry = program; no_backtrack = true; cst = true; }`: settings for the
+/// whole grammar, each of which can be given once.
+#[derive(Debug, Clone, Default)]
+pub struct GrammarOptions {
+    /// `entry = rule;`: the rule is public and `#[complete]`.
+    pub entry: Option<Ident>,
+    /// `no_backtrack = true;`: all rules are `#[no_backtrack]`, like `#![no_backtrack]`.
+    pub no_backtrack: bool,
+    /// `visibility = pub;`: all rules are public, as if declared with `pub rule`.
+    pub public_rules: bool,
+    /// `cst = true;`: the rules record a concrete syntax tree, returned by `cst_<rule>`.
+    pub cst: bool,
+    /// `parser = CalcParser;`: the name of the generated parser struct, which defaults to the
+    /// grammar name in upper camel case followed by `Parser`.
+    pub parser: Option<Ident>,
+    /// `layout = significant;`: line breaks and indentation are significant, matched by the
+    /// `newline`, `indent` and `dedent` built-ins.
+    pub layout: bool,
+    /// `warn_unused_bindings = true;`: the validator warns about bindings of rule calls that
+    /// are never used (see `analysis::find_unused_bindings`).
+    pub warn_unused_bindings: bool,
+    /// `diagnose = true;`: public rules without parameters get a `diagnose_<rule>` entry point
+    /// that returns every error skipped by recovery.
+    pub diagnose: bool,
+    /// `lenient = true;`: public rules without parameters get a `lenient_<rule>` entry point
+    /// that returns the value with the recovered errors and warnings.
+    pub lenient: bool,
+    /// `incremental = true;`: public rules without parameters get an `incremental_<rule>`
+    /// entry point that reparses a sequence of items, reusing the unchanged ones.
+    pub incremental: bool,
+}
+
+impl Parse for GrammarOptions {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let _ = input.parse::<kw::options>()?;
+        let content;
+        let _ = syn::braced!(content in input);
+        let mut options = GrammarOptions::default();
+        let mut seen: Vec<String> = Vec::new();
+        while !content.is_empty() {
+            let key = rt::parse_ident(&content)?;
+            if seen.contains(&key.to_string()) {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!("option `{}` is set more than once", key),
+                ));
+            }
+            seen.push(key.to_string());
+            let _ = content.parse::<Token![=]>()?;
+            if key == "entry" {
+                options.entry = Some(rt::parse_ident(&content)?);
+            } else if key == "no_backtrack" {
+                options.no_backtrack = content.parse::<syn::LitBool>()?.value;
+            } else if key == "cst" {
+                options.cst = content.parse::<syn::LitBool>()?.value;
+            } else if key == "parser" {
+                options.parser = Some(content.parse()?);
+            } else if key == "visibility" {
+                let _ = content.parse::<Token![pub]>()?;
+                options.public_rules = true;
+            } else if key == "layout" {
+                let value = rt::parse_ident(&content)?;
+                if value != "significant" {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        format!("unknown layout `{}`, expected `significant`", value),
+                    ));
+                }
+                options.layout = true;
+            } else if key == "diagnose" {
+                options.diagnose = content.parse::<syn::LitBool>()?.value;
+            } else if key == "incremental" {
+                options.incremental = content.parse::<syn::LitBool>()?.value;
+            } else if key == "lenient" {
+                options.lenient = content.parse::<syn::LitBool>()?.value;
+            } else if key == "warn_unused_bindings" {
+                options.warn_unused_bindings = content.parse::<syn::LitBool>()?.value;
+            } else {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!(
+                        "unknown option `{}`, expected `cst`, `diagnose`, `entry`, `incremental`, `layout`, `lenient`, `no_backtrack`, `parser`, `visibility` or `warn_unused_bindings`",
+                        key
+                    ),
+                ));
+            }
+            let _ = content.parse::<Token![;]>()?;
+        }
+        Ok(options)
+    }
+}
+
+/// `import "lexer.grammar" as lex;`: the rules of the grammar in the file are called as
+/// `lex::rule`.
+#[derive(Debug, Clone)]
+pub struct GrammarImport {
+    pub path: syn::LitStr,
+    pub alias: Ident,
+}
+
 impl Parse for GrammarDefinition {
     fn parse(input: ParseStream) -> Result<Self> {
+        // Dialects selected by the invocation: `#![dialect("strict")]`, and `#![no_backtrack]`
+        // marking all rules `#[no_backtrack]`
+        let mut dialects = Vec::new();
+        let mut no_backtrack = None;
+        for attr in Attribute::parse_inner(input)? {
+            if attr.path().is_ident("dialect") {
+                dialects.extend(parse_dialects(&attr)?);
+            } else if attr.path().is_ident("no_backtrack") {
+                attr.meta.require_path_only()?;
+                no_backtrack = Some(Attribute {
+                    style: syn::AttrStyle::Outer,
+                    ..attr
+                });
+            } else {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "unknown grammar attribute, expected `#![dialect(\"name\")]` or `#![no_backtrack]`",
+                ));
+            }
+        }
+
         let _ = input.parse::<kw::grammar>()?;
         let name = rt::parse_ident(input)?;
 
@@ -60,21 +253,160 @@ impl Parse for GrammarDefinition {
         let _ = syn::braced!(content in input);
 
         let mut uses = Vec::new();
-        while content.peek(Token![use]) {
-            uses.push(content.parse()?);
+        let mut keywords = Vec::new();
+        let mut keyword_sets = Vec::new();
+        let mut token_pool = None;
+        let mut imports = Vec::new();
+        let mut options = None;
+        loop {
+            if content.peek(Token![use]) {
+                uses.push(content.parse()?);
+            } else if content.peek(kw::keyword) && content.peek2(syn::LitStr) {
+                let _ = content.parse::<kw::keyword>()?;
+                keywords.push(content.parse()?);
+                let _ = content.parse::<Token![;]>()?;
+            } else if content.peek(kw::keywords)
+                && (content.peek2(syn::token::Brace) || content.peek2(syn::token::Paren))
+            {
+                keyword_sets.push(content.parse()?);
+            } else if content.peek(kw::tokens) && content.peek2(kw::from) {
+                let tokens = content.parse::<kw::tokens>()?;
+                let _ = content.parse::<kw::from>()?;
+                let path: syn::Path = content.parse()?;
+                let _ = content.parse::<Token![;]>()?;
+                if token_pool.is_some() {
+                    return Err(syn::Error::new(
+                        tokens.span,
+                        "a grammar can take its tokens from only one module",
+                    ));
+                }
+                token_pool = Some(path);
+            } else if content.peek(kw::import) && content.peek2(syn::LitStr) {
+                let _ = content.parse::<kw::import>()?;
+                let path = content.parse()?;
+                let _ = content.parse::<Token![as]>()?;
+                let alias = rt::parse_ident(&content)?;
+                let _ = content.parse::<Token![;]>()?;
+                imports.push(GrammarImport { path, alias });
+            } else if content.peek(kw::options) && content.peek2(syn::token::Brace) {
+                let span = content.span();
+                if options.is_some() {
+                    return Err(syn::Error::new(
+                        span,
+                        "a grammar can have only one `options` block",
+                    ));
+                }
+                options = Some(content.parse::<GrammarOptions>()?);
+            } else {
+                break;
+            }
         }
 
-        let rules = Rule::parse_all(&content)?;
+        let options = options.unwrap_or_default();
+        let mut rules = select_dialects(Rule::parse_all(&content)?, &dialects)?;
+        if options.no_backtrack && no_backtrack.is_none() {
+            no_backtrack = Some(syn::parse_quote!(#[no_backtrack]));
+        }
+        if let Some(attr) = no_backtrack {
+            // Operator precedence rules decide by their operators without backtracking anyway
+            for rule in rules.iter_mut().filter(|r| r.precedence.is_none()) {
+                if !rule.attrs.iter().any(|a| a.path().is_ident("no_backtrack")) {
+                    rule.attrs.push(attr.clone());
+                }
+            }
+        }
+
+        if options.public_rules {
+            for rule in &mut rules {
+                if let Visibility::Inherited = rule.vis {
+                    rule.vis = Visibility::Public(Token![pub](rule.name.span()));
+                }
+            }
+        }
+        if let Some(entry) = &options.entry {
+            let Some(rule) = rules.iter_mut().find(|r| r.name == *entry) else {
+                return Err(syn::Error::new(
+                    entry.span(),
+                    format!("the entry rule `{}` is not defined in this grammar", entry),
+                ));
+            };
+            if let Visibility::Inherited = rule.vis {
+                rule.vis = Visibility::Public(Token![pub](entry.span()));
+            }
+            if !rule.attrs.iter().any(|a| a.path().is_ident("complete")) {
+                rule.attrs.push(syn::parse_quote!(#[complete]));
+            }
+        }
 
         Ok(GrammarDefinition {
             name,
             inherits,
             uses,
+            keywords,
+            keyword_sets,
+            token_pool,
+            imports,
+            options,
             rules,
         })
     }
 }
 
+/// Parses the dialect names of a `#[dialect("a", "b")]` attribute.
+fn parse_dialects(attr: &Attribute) -> Result<Vec<syn::LitStr>> {
+    let names = attr
+        .parse_args_with(syn::punctuated::Punctuated::<syn::LitStr, Token![,]>::parse_terminated)?;
+    if names.is_empty() {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "expected at least one dialect name, e.g. `#[dialect(\"strict\")]`",
+        ));
+    }
+    Ok(names.into_iter().collect())
+}
+
+/// Returns true if an item tagged with `tags` belongs to the selected dialects: untagged
+/// items always do, tagged items if one of their dialects is selected.
+fn in_dialects(tags: &[syn::LitStr], selected: &[syn::LitStr]) -> bool {
+    tags.is_empty()
+        || tags
+            .iter()
+            .any(|t| selected.iter().any(|s| s.value() == t.value()))
+}
+
+/// Removes the rules and alternatives tagged with `#[dialect(..)]` that are not selected, and
+/// the `#[dialect]` attributes of the remaining rules.
+fn select_dialects(rules: Vec<Rule>, selected: &[syn::LitStr]) -> Result<Vec<Rule>> {
+    let mut kept = Vec::new();
+    for mut rule in rules {
+        let mut tags = Vec::new();
+        let mut attrs = Vec::new();
+        for attr in rule.attrs {
+            if attr.path().is_ident("dialect") {
+                tags.extend(parse_dialects(&attr)?);
+            } else {
+                attrs.push(attr);
+            }
+        }
+        if !in_dialects(&tags, selected) {
+            continue;
+        }
+        rule.attrs = attrs;
+        rule.variants.retain(|v| in_dialects(&v.dialects, selected));
+        if rule.variants.is_empty() && rule.precedence.is_none() {
+            return Err(syn::Error::new(
+                rule.name.span(),
+                format!(
+                    "Rule '{}' has no alternatives in the selected dialects",
+                    rule.name
+                ),
+            ));
+        }
+        kept.push(rule);
+    }
+    Ok(kept)
+}
+
 pub struct InheritanceSpec {
     pub name: Ident,
 }
@@ -107,87 +439,738 @@ impl Parse for RuleParameter {
 
 pub struct Rule {
     pub attrs: Vec<Attribute>,
-    pub is_pub: Option<Token![pub]>,
+    /// `pub`, `pub(crate)` or `pub(super)`; the generated entry points of the rule get the same
+    /// visibility.
+    pub vis: Visibility,
     pub name: Ident,
     pub generics: Generics,
     pub params: Vec<RuleParameter>,
     pub return_type: Type,
+    /// The error type declared with `throws Error` after the return type. The actions then
+    /// evaluate in a function returning `Result<ReturnType, Error>`, so they can use `?`.
+    pub throws: Option<Type>,
+    /// The alternatives of the rule; empty for an operator precedence rule.
     pub variants: Vec<RuleVariant>,
+    /// Set for a rule defined by `precedence(operand) { .. }`.
+    pub precedence: Option<Precedence>,
 }
 
 impl Parse for Rule {
     fn parse(input: ParseStream) -> Result<Self> {
         let attrs = Attribute::parse_outer(input)?;
 
-        let is_pub = if input.peek(Token![pub]) {
-            Some(input.parse()?)
-        } else {
-            None
-        };
+        let vis: Visibility = input.parse()?;
+        if let Visibility::Restricted(restricted) = &vis {
+            let path = &restricted.path;
+            if restricted.in_token.is_some() || !(path.is_ident("crate") || path.is_ident("super"))
+            {
+                return Err(syn::Error::new_spanned(
+                    &vis,
+                    "a rule can be `pub`, `pub(crate)` or `pub(super)`",
+                ));
+            }
+        }
 
+        if !input.peek(kw::rule) {
+            return Err(input.error("expected a rule definition: `rule name -> Type = ...`"));
+        }
         let _ = input.parse::<kw::rule>()?;
         let name = rt::parse_ident(input)?;
 
-        // Parse generics if present (e.g., <T, U>)
-        let generics: Generics = input.parse()?;
-
-        let params = if input.peek(token::Paren) {
-            let content;
-            syn::parenthesized!(content in input);
-            let mut params = Vec::new();
-            while !content.is_empty() {
-                params.push(content.parse()?);
-                if content.peek(Token![,]) {
-                    let _ = content.parse::<Token![,]>()?;
+        let (generics, params, return_type, throws, body) =
+            parse_rule_body(input).map_err(|e| with_rule_context(e, &name))?;
+        let (variants, precedence) = match body {
+            RuleDefinition::Variants(variants) => (variants, None),
+            RuleDefinition::Precedence(precedence) => {
+                if !params.is_empty() || !generics.params.is_empty() {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        format!(
+                            "Rule '{}': a `precedence` rule cannot take parameters or generics",
+                            name
+                        ),
+                    ));
                 }
+                if let Some(throws) = &throws {
+                    return Err(syn::Error::new_spanned(
+                        throws,
+                        format!(
+                            "Rule '{}': a `precedence` rule cannot declare `throws`",
+                            name
+                        ),
+                    ));
+                }
+                (Vec::new(), Some(precedence))
             }
-            params
-        } else {
-            Vec::new()
         };
-
-        let _ = input.parse::<Token![->]>()?;
-        let return_type = input.parse::<Type>()?;
-        let _ = input.parse::<Token![=]>()?;
-
-        let variants = RuleVariant::parse_list(input)?;
+        for (i, variant) in variants.iter().enumerate() {
+            variant.check_action(&name, i)?;
+        }
 
         Ok(Rule {
             attrs,
-            is_pub,
+            vis,
             name,
             generics,
             params,
             return_type,
+            throws,
             variants,
+            precedence,
+        })
+    }
+}
+
+/// What follows the `=` of a rule.
+enum RuleDefinition {
+    Variants(Vec<RuleVariant>),
+    Precedence(Precedence),
+}
+
+type RuleBody = (
+    Generics,
+    Vec<RuleParameter>,
+    Type,
+    Option<Type>,
+    RuleDefinition,
+);
+
+fn parse_rule_body(input: ParseStream) -> Result<RuleBody> {
+    // Parse generics if present (e.g., <T, U>)
+    let generics: Generics = input.parse()?;
+
+    let params = if input.peek(token::Paren) {
+        let content;
+        syn::parenthesized!(content in input);
+        let mut params = Vec::new();
+        while !content.is_empty() {
+            params.push(content.parse()?);
+            if content.peek(Token![,]) {
+                let _ = content.parse::<Token![,]>()?;
+            }
+        }
+        params
+    } else {
+        Vec::new()
+    };
+
+    if !input.peek(Token![->]) {
+        return Err(input.error("expected `->` followed by the return type"));
+    }
+    let _ = input.parse::<Token![->]>()?;
+    let return_type = input.parse::<Type>()?;
+    let throws = if input.peek(kw::throws) {
+        let _ = input.parse::<kw::throws>()?;
+        Some(input.parse::<Type>()?)
+    } else {
+        None
+    };
+    if !input.peek(Token![=]) {
+        return Err(input.error("expected `=` after the return type"));
+    }
+    let _ = input.parse::<Token![=]>()?;
+
+    let mut locals: Vec<LocalPattern> = Vec::new();
+    while input.peek(Token![let]) {
+        let mut local: LocalPattern = input.parse()?;
+        expand_sequence(&mut local.pattern, &locals)?;
+        locals.push(local);
+    }
+
+    let body = if input.peek(kw::precedence) && input.peek2(token::Paren) {
+        if let Some(local) = locals.first() {
+            return Err(syn::Error::new(
+                local.name.span(),
+                "a `precedence` rule cannot define local patterns",
+            ));
+        }
+        RuleDefinition::Precedence(input.parse()?)
+    } else {
+        let mut variants = RuleVariant::parse_list(input)?;
+        for variant in &mut variants {
+            expand_sequence(&mut variant.pattern, &locals)?;
+        }
+        RuleDefinition::Variants(variants)
+    };
+    Ok((generics, params, return_type, throws, body))
+}
+
+/// A named sub-pattern defined at the start of a rule body, e.g.
+/// `let sig = name:ident paren(params);`. The alternatives of the rule and the local patterns
+/// defined after it refer to it by name, and each reference is replaced by the pattern while
+/// parsing the grammar, so it is parsed without a call of its own. Like a Rust `let`, a later
+/// definition shadows an earlier one of the same name, and the name in its own pattern refers
+/// to the previous definition or to the rule of that name.
+struct LocalPattern {
+    name: Ident,
+    pattern: Vec<Pattern>,
+}
+
+impl Parse for LocalPattern {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let _ = input.parse::<Token![let]>()?;
+        let name = rt::parse_ident(input)?;
+        let _ = input.parse::<Token![=]>()?;
+        let mut pattern = Vec::new();
+        while !input.peek(Token![;]) {
+            if input.is_empty() || is_rule_start(input) || input.peek(Token![->]) {
+                return Err(input.error(format!("expected `;` after the local pattern `{}`", name)));
+            }
+            pattern.push(input.parse()?);
+        }
+        let semi = input.parse::<Token![;]>()?;
+        if pattern.is_empty() {
+            return Err(syn::Error::new(
+                semi.span,
+                format!("the local pattern `{}` is empty", name),
+            ));
+        }
+        Ok(Self { name, pattern })
+    }
+}
+
+/// Replaces the references to local patterns in a sequence. A reference in the sequence is
+/// replaced by the patterns of the local pattern.
+fn expand_sequence(sequence: &mut Vec<Pattern>, locals: &[LocalPattern]) -> Result<()> {
+    if locals.is_empty() {
+        return Ok(());
+    }
+    for mut pattern in std::mem::take(sequence) {
+        match local_reference(&pattern, locals)? {
+            Some(expansion) => sequence.extend(expansion),
+            None => {
+                expand_pattern(&mut pattern, locals)?;
+                sequence.push(pattern);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces a reference to a local pattern that is the operand of another pattern, e.g.
+/// `sig?`, by the pattern, or by a group if the local pattern is a sequence.
+fn expand_operand(pattern: &mut Pattern, locals: &[LocalPattern]) -> Result<()> {
+    let Some(mut expansion) = local_reference(pattern, locals)? else {
+        return expand_pattern(pattern, locals);
+    };
+    *pattern = if expansion.len() == 1 {
+        expansion.remove(0)
+    } else {
+        let span = match pattern {
+            Pattern::RuleCall { rule_name, .. } => rule_name.span(),
+            _ => unreachable!("only calls refer to local patterns"),
+        };
+        Pattern::Group(vec![(expansion, None)], token::Paren(span))
+    };
+    Ok(())
+}
+
+fn expand_pattern(pattern: &mut Pattern, locals: &[LocalPattern]) -> Result<()> {
+    match pattern {
+        Pattern::Cut(_) | Pattern::Lit { .. } => Ok(()),
+        Pattern::RuleCall { args, .. } => args.iter_mut().try_for_each(|arg| match arg {
+            Argument::Positional(p) | Argument::Named(_, p) => expand_operand(p, locals),
+        }),
+        Pattern::Group(alternatives, _) => alternatives
+            .iter_mut()
+            .try_for_each(|(sequence, _)| expand_sequence(sequence, locals)),
+        Pattern::Bracketed(sequence, _)
+        | Pattern::Braced(sequence, _)
+        | Pattern::Parenthesized(sequence, _, _)
+        | Pattern::Scope(sequence, _, _) => expand_sequence(sequence, locals),
+        Pattern::Optional(inner, _)
+        | Pattern::Repeat(inner, _)
+        | Pattern::Plus(inner, _)
+        | Pattern::Bounded { pattern: inner, .. }
+        | Pattern::SpanBinding(inner, _, _)
+        | Pattern::Peek(inner, _)
+        | Pattern::Not(inner, _)
+        | Pattern::Follows(inner, _)
+        | Pattern::Declare(inner, _)
+        | Pattern::Resolve(inner, _)
+        | Pattern::Until { pattern: inner, .. }
+        | Pattern::Annotated { pattern: inner, .. }
+        | Pattern::Raw(inner, _, _)
+        | Pattern::Collect(inner, _, _)
+        | Pattern::Expected(inner, _, _)
+        | Pattern::Commit(inner, _) => expand_operand(inner, locals),
+        Pattern::Recover { body, sync, .. } => {
+            expand_operand(body, locals)?;
+            expand_operand(sync, locals)
+        }
+        Pattern::Fold { item, op, .. } => {
+            expand_operand(item, locals)?;
+            expand_operand(op, locals)
+        }
+    }
+}
+
+/// The patterns a reference to a local pattern stands for, or `None` if the pattern is not a
+/// reference. A binding on the reference, as in `a:name`, binds the value of a local pattern
+/// consisting of a single literal or call without a binding.
+fn local_reference(pattern: &Pattern, locals: &[LocalPattern]) -> Result<Option<Vec<Pattern>>> {
+    let Pattern::RuleCall {
+        binding,
+        module: None,
+        rule_name,
+        generics,
+        args,
+    } = pattern
+    else {
+        return Ok(None);
+    };
+    let Some(local) = locals.iter().rev().find(|l| l.name == *rule_name) else {
+        return Ok(None);
+    };
+    if !generics.is_empty() || !args.is_empty() {
+        return Err(syn::Error::new(
+            rule_name.span(),
+            format!("the local pattern `{}` takes no arguments", rule_name),
+        ));
+    }
+    let mut expansion = local.pattern.clone();
+    if let Some(new_binding) = binding {
+        match &mut expansion[..] {
+            [Pattern::Lit { binding, .. }] | [Pattern::RuleCall { binding, .. }]
+                if binding.is_none() =>
+            {
+                *binding = Some(new_binding.clone());
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    new_binding.span(),
+                    format!(
+                        "only a local pattern of a single literal or call without a binding \
+                         can be bound; bind the values inside the local pattern `{}` instead",
+                        rule_name
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(Some(expansion))
+}
+
+/// An operator precedence rule:
+///
+/// ```text
+/// rule expr -> i32 = precedence(atom) {
+///     left "+" "-" => |l, op, r| { if op == "+" { l + r } else { l - r } };
+///     left "*" => |l, _, r| { l * r };
+///     prefix "-" => |_, x| { -x };
+/// }
+/// ```
+///
+/// Levels bind tighter the later they are listed. `left` and `right` levels hold binary
+/// operators, `prefix` and `postfix` levels unary ones. The action receives the operands and
+/// the text of the matched operator (a `&'static str`).
+#[derive(Debug, Clone)]
+pub struct Precedence {
+    /// The rule parsing the operands between the operators.
+    pub operand: Ident,
+    pub levels: Vec<PrecedenceLevel>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrecedenceLevel {
+    pub fixity: Fixity,
+    pub operators: Vec<syn::LitStr>,
+    /// The closure-like parameters of the action: `l, op, r` for binary operators, `op, x` for
+    /// prefix and `x, op` for postfix operators.
+    pub params: Vec<syn::Pat>,
+    pub action: TokenStream,
+}
+
+/// Parses the closure-like parameters of an action, e.g. `|l, op, r|`, which must match the
+/// number of parameters in `expected`.
+fn parse_action_params(input: ParseStream, expected: &str) -> Result<Vec<syn::Pat>> {
+    let bar = input.parse::<Token![|]>()?;
+    let mut params = Vec::new();
+    while !input.peek(Token![|]) {
+        params.push(syn::Pat::parse_single(input)?);
+        if !input.peek(Token![|]) {
+            let _ = input.parse::<Token![,]>()?;
+        }
+    }
+    let _ = input.parse::<Token![|]>()?;
+    if params.len() != expected.matches(',').count() + 1 {
+        return Err(syn::Error::new(
+            bar.span,
+            format!("expected the action parameters `{}`", expected),
+        ));
+    }
+    Ok(params)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fixity {
+    Left,
+    Right,
+    Prefix,
+    Postfix,
+}
+
+impl Parse for Precedence {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let _ = input.parse::<kw::precedence>()?;
+        let operand_content;
+        syn::parenthesized!(operand_content in input);
+        let operand = operand_content.call(rt::parse_ident)?;
+        if !operand_content.is_empty() {
+            return Err(operand_content.error("expected the name of the operand rule"));
+        }
+
+        let content;
+        syn::braced!(content in input);
+        let mut levels = Vec::new();
+        while !content.is_empty() {
+            levels.push(content.parse()?);
+        }
+        if levels.is_empty() {
+            return Err(content.error("a `precedence` rule needs at least one operator level"));
+        }
+        Ok(Precedence { operand, levels })
+    }
+}
+
+impl Parse for PrecedenceLevel {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let lookahead = input.lookahead1();
+        let fixity = if lookahead.peek(kw::left) {
+            let _ = input.parse::<kw::left>()?;
+            Fixity::Left
+        } else if lookahead.peek(kw::right) {
+            let _ = input.parse::<kw::right>()?;
+            Fixity::Right
+        } else if lookahead.peek(kw::prefix) {
+            let _ = input.parse::<kw::prefix>()?;
+            Fixity::Prefix
+        } else if lookahead.peek(kw::postfix) {
+            let _ = input.parse::<kw::postfix>()?;
+            Fixity::Postfix
+        } else {
+            return Err(lookahead.error());
+        };
+
+        let mut operators = Vec::new();
+        while input.peek(syn::LitStr) {
+            operators.push(input.parse()?);
+        }
+        if operators.is_empty() {
+            return Err(input.error("expected at least one operator, e.g. `left \"+\" \"-\"`"));
+        }
+
+        let _ = input.parse::<Token![=>]>()?;
+        let expected = match fixity {
+            Fixity::Left | Fixity::Right => "|l, op, r|",
+            Fixity::Prefix => "|op, x|",
+            Fixity::Postfix => "|x, op|",
+        };
+        let params = parse_action_params(input, expected)?;
+
+        let content;
+        let brace = syn::braced!(content in input);
+        let action = content.parse()?;
+        if uses_span_var(&action) {
+            return Err(syn::Error::new(
+                brace.span.join(),
+                "`$span` is only available in the actions of alternatives, not of operators",
+            ));
+        }
+        let _ = input.parse::<Token![;]>()?;
+
+        Ok(PrecedenceLevel {
+            fixity,
+            operators,
+            params,
+            action,
+        })
+    }
+}
+
+/// Prefixes every error of a rule definition with the name of the rule.
+fn with_rule_context(err: syn::Error, rule_name: &Ident) -> syn::Error {
+    err.into_iter()
+        .map(|e| syn::Error::new(e.span(), format!("Rule '{}': {}", rule_name, e)))
+        .reduce(|mut acc, e| {
+            acc.combine(e);
+            acc
         })
+        .expect("syn::Error holds at least one message")
+}
+
+/// Returns true if the input is at the start of a rule definition
+/// (`rule name`, `pub rule` or an attribute other than a pattern's `#[collect(..)]` or
+/// `#[expected(..)]`).
+fn is_rule_start(input: ParseStream) -> bool {
+    input.peek(Token![pub])
+        || (input.peek(Token![#]) && input.peek2(token::Bracket) && !is_pattern_attr(input))
+        || is_rule_keyword(input)
+}
+
+fn is_rule_keyword(input: ParseStream) -> bool {
+    use syn::ext::IdentExt;
+    input.peek(kw::rule) && input.peek2(Ident::peek_any)
+}
+
+/// Returns true if the input is at a `#[collect(..)]` or `#[expected(..)]` annotation of a
+/// pattern. `#[expected(..)]` in front of a rule definition is an attribute of the rule.
+fn is_pattern_attr(input: ParseStream) -> bool {
+    let fork = input.fork();
+    let Ok(attrs) = fork.call(syn::Attribute::parse_outer) else {
+        return false;
+    };
+    match attrs.first() {
+        Some(attr) if attr.path().is_ident("collect") => true,
+        Some(attr) if attr.path().is_ident("expected") => {
+            !fork.peek(Token![pub]) && !is_rule_keyword(&fork)
+        }
+        _ => false,
+    }
+}
+
+/// Parses the outer attributes of an alternative, up to a `#[collect(..)]` or `#[expected(..)]`
+/// annotation that starts its pattern.
+fn parse_variant_attrs(input: ParseStream) -> Result<Vec<Attribute>> {
+    let mut attrs = Vec::new();
+    while input.peek(Token![#]) && input.peek2(token::Bracket) && !is_pattern_attr(input) {
+        let pound_token = input.parse()?;
+        let content;
+        let bracket_token = syn::bracketed!(content in input);
+        attrs.push(Attribute {
+            pound_token,
+            style: syn::AttrStyle::Outer,
+            bracket_token,
+            meta: content.parse()?,
+        });
     }
+    Ok(attrs)
+}
+
+/// Returns true if the input is at a `# "label"` of an alternative.
+fn is_label_start(input: ParseStream) -> bool {
+    input.peek(Token![#]) && !input.peek2(token::Bracket)
 }
 
 impl Rule {
+    /// Parses all rules, recovering after a malformed rule at the start of the next one
+    /// so that the errors of all rules are reported together.
     pub fn parse_all(input: ParseStream) -> Result<Vec<Self>> {
         let mut rules = Vec::new();
+        let mut errors: Option<syn::Error> = None;
         while !input.is_empty() {
-            rules.push(input.parse()?);
+            let start = input.cursor();
+            match input.parse() {
+                Ok(rule) => rules.push(rule),
+                Err(e) => {
+                    match &mut errors {
+                        Some(errors) => errors.combine(e),
+                        None => errors = Some(e),
+                    }
+                    // Skip the rest of the malformed rule, making progress in any case
+                    if input.cursor() == start {
+                        let _ = input.parse::<proc_macro2::TokenTree>()?;
+                    }
+                    while !input.is_empty() && !is_rule_start(input) {
+                        let _ = input.parse::<proc_macro2::TokenTree>()?;
+                    }
+                }
+            }
+        }
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(rules),
         }
-        Ok(rules)
     }
 }
 
+const MISSING_ACTION: &str = "every variant needs an action block `-> { ... }`";
+
 pub struct RuleVariant {
+    /// Attributes of the variant other than `#[dialect(..)]`, emitted on its generated code.
+    pub attrs: Vec<Attribute>,
+    /// Dialects of a variant tagged with `#[dialect(..)]`; empty if it is part of all dialects.
+    pub dialects: Vec<syn::LitStr>,
     pub pattern: Vec<Pattern>,
     pub label: Option<String>,
+    /// The condition of a `where { .. }` guard after the pattern.
+    pub guard: Option<TokenStream>,
+    pub brace_token: token::Brace,
     pub action: TokenStream,
+    /// Set for a struct shorthand action `-> Path { .. }` or `->* Path`; `action` then holds the
+    /// struct expression without the bindings filled in by `..` or `*`.
+    pub struct_action: Option<StructAction>,
+}
+
+/// A struct shorthand action: `-> Item { name, value: v * 2, .. }` constructs `Item` from the
+/// listed fields, and with a trailing `..` from all other bindings of the alternative.
+///
+/// The spread form `->* Item` or `->* Item { value: v * 2 }` fills the fields from all bindings
+/// not starting with `_` and the remaining fields with `Default::default()`.
+pub struct StructAction {
+    pub path: syn::Path,
+    pub fields: syn::punctuated::Punctuated<syn::FieldValue, Token![,]>,
+    pub rest: Option<Token![..]>,
+    pub spread: Option<Token![*]>,
+}
+
+/// Returns true if the action refers to `$span`, the span of the tokens its alternative
+/// consumed.
+pub fn uses_span_var(action: &TokenStream) -> bool {
+    let mut tokens = action.clone().into_iter().peekable();
+    while let Some(tt) = tokens.next() {
+        match tt {
+            proc_macro2::TokenTree::Punct(p) if p.as_char() == '$' => {
+                if matches!(tokens.peek(), Some(proc_macro2::TokenTree::Ident(i)) if i == "span") {
+                    return true;
+                }
+            }
+            proc_macro2::TokenTree::Group(group) if uses_span_var(&group.stream()) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Replaces each `$span` in the action with the identifier `var`.
+pub fn replace_span_var(action: &TokenStream, var: &Ident) -> TokenStream {
+    let mut out = TokenStream::new();
+    let mut tokens = action.clone().into_iter().peekable();
+    while let Some(tt) = tokens.next() {
+        match tt {
+            proc_macro2::TokenTree::Punct(p)
+                if p.as_char() == '$'
+                    && matches!(tokens.peek(), Some(proc_macro2::TokenTree::Ident(i)) if i == "span") =>
+            {
+                tokens.next();
+                out.extend([proc_macro2::TokenTree::Ident(Ident::new(
+                    &var.to_string(),
+                    p.span(),
+                ))]);
+            }
+            proc_macro2::TokenTree::Group(group) => {
+                let mut replaced = proc_macro2::Group::new(
+                    group.delimiter(),
+                    replace_span_var(&group.stream(), var),
+                );
+                replaced.set_span(group.span());
+                out.extend([proc_macro2::TokenTree::Group(replaced)]);
+            }
+            tt => out.extend([tt]),
+        }
+    }
+    out
+}
+
+fn collect_idents(tokens: TokenStream, out: &mut Vec<Ident>) {
+    for tt in tokens {
+        match tt {
+            proc_macro2::TokenTree::Ident(ident) => out.push(ident),
+            proc_macro2::TokenTree::Group(group) => collect_idents(group.stream(), out),
+            _ => {}
+        }
+    }
+}
+
+impl StructAction {
+    fn parse_body(path: syn::Path, content: ParseStream) -> Result<Self> {
+        let mut fields = syn::punctuated::Punctuated::new();
+        let mut rest = None;
+        while !content.is_empty() {
+            if content.peek(Token![..]) {
+                rest = Some(content.parse()?);
+                if !content.is_empty() {
+                    return Err(content.error("`..` must be the last entry of a struct action"));
+                }
+                break;
+            }
+            fields.push_value(content.parse()?);
+            if content.is_empty() {
+                break;
+            }
+            fields.push_punct(content.parse()?);
+        }
+        Ok(StructAction {
+            path,
+            fields,
+            rest,
+            spread: None,
+        })
+    }
+
+    /// The struct expression. If the action ends with `..`, the `bindings` that are neither
+    /// listed as fields nor used by the listed fields fill the remaining fields. A spread
+    /// action does the same for bindings not starting with `_` and ends with
+    /// `..Default::default()`.
+    pub fn to_expr(&self, bindings: &[Ident]) -> TokenStream {
+        let path = &self.path;
+        let fields: Vec<&syn::FieldValue> = self.fields.iter().collect();
+        let mut used = Vec::new();
+        collect_idents(quote::quote!(#(#fields)*), &mut used);
+        let filled = bindings.iter().filter(|b| {
+            let fills = match self.spread {
+                Some(_) => !b.to_string().starts_with('_'),
+                None => self.rest.is_some(),
+            };
+            fills && !used.contains(b)
+        });
+        let default = self
+            .spread
+            .map(|_| quote::quote!(..::core::default::Default::default()));
+        quote::quote!(#path { #(#fields,)* #(#filled,)* #default })
+    }
 }
 
 impl RuleVariant {
     pub fn parse_list(input: ParseStream) -> Result<Vec<Self>> {
         let mut variants = Vec::new();
         loop {
+            let mut dialects = Vec::new();
+            let mut attrs = Vec::new();
+            for attr in parse_variant_attrs(input)? {
+                if attr.path().is_ident("dialect") {
+                    dialects.extend(parse_dialects(&attr)?);
+                } else {
+                    attrs.push(attr);
+                }
+            }
             let mut pattern = Vec::new();
-            while !input.peek(Token![->]) && !input.peek(Token![|]) && !input.peek(Token![#]) {
+            while !input.peek(Token![->])
+                && !input.peek(Token![|])
+                && !input.peek(Token![where])
+                && !is_label_start(input)
+            {
+                if input.is_empty() || is_rule_start(input) {
+                    return Err(input.error(MISSING_ACTION));
+                }
                 pattern.push(input.parse()?);
             }
+            if is_rule_start(input) {
+                return Err(input.error(MISSING_ACTION));
+            }
+
+            // `where { condition }`, checked once the pattern has matched
+            let guard = if input.peek(Token![where]) {
+                let where_token = input.parse::<Token![where]>()?;
+                if !input.peek(token::Brace) {
+                    return Err(syn::Error::new(
+                        where_token.span,
+                        "expected a condition in braces after `where`, e.g. `where { n > 0 }`",
+                    ));
+                }
+                let content;
+                let brace_token = syn::braced!(content in input);
+                let condition: TokenStream = content.parse()?;
+                if condition.is_empty() {
+                    return Err(syn::Error::new(
+                        brace_token.span.join(),
+                        "the condition of a `where` guard is empty",
+                    ));
+                }
+                Some(condition)
+            } else {
+                None
+            };
 
             let label = if input.peek(Token![#]) {
                 let _ = input.parse::<Token![#]>()?;
@@ -197,16 +1180,79 @@ impl RuleVariant {
                 None
             };
 
+            if !input.peek(Token![->]) {
+                return Err(input.error(MISSING_ACTION));
+            }
             let _ = input.parse::<Token![->]>()?;
 
-            let content;
-            syn::braced!(content in input);
-            let action = content.parse()?;
+            // `->* Path`, optionally with fields in braces, fills all fields from the bindings
+            let spread: Option<Token![*]> = input.parse()?;
+
+            // `-> { block }` or the struct shorthand `-> Path { fields }`
+            let path = if input.peek(token::Brace) && spread.is_none() {
+                None
+            } else if let Some(star) = spread {
+                let path = input.call(syn::Path::parse_mod_style).map_err(|_| {
+                    syn::Error::new(
+                        star.span,
+                        "expected a struct path after `->*`, e.g. `->* Item`",
+                    )
+                })?;
+                Some(path)
+            } else {
+                let path = input
+                    .call(syn::Path::parse_mod_style)
+                    .map_err(|_| input.error(MISSING_ACTION))?;
+                if !input.peek(token::Brace) {
+                    return Err(input.error(MISSING_ACTION));
+                }
+                Some(path)
+            };
+
+            let (brace_token, action, struct_action) = match path {
+                Some(path) if spread.is_some() && !input.peek(token::Brace) => {
+                    let struct_action = StructAction {
+                        path,
+                        fields: syn::punctuated::Punctuated::new(),
+                        rest: None,
+                        spread,
+                    };
+                    let span = syn::spanned::Spanned::span(&struct_action.path);
+                    (
+                        token::Brace(span),
+                        struct_action.to_expr(&[]),
+                        Some(struct_action),
+                    )
+                }
+                Some(path) => {
+                    let content;
+                    let brace_token = syn::braced!(content in input);
+                    let mut struct_action = StructAction::parse_body(path, &content)?;
+                    if let (Some(rest), Some(_)) = (struct_action.rest, spread) {
+                        return Err(syn::Error::new(
+                            rest.spans[0],
+                            "`->*` already fills the remaining fields; remove the `..`",
+                        ));
+                    }
+                    struct_action.spread = spread;
+                    (brace_token, struct_action.to_expr(&[]), Some(struct_action))
+                }
+                None => {
+                    let content;
+                    let brace_token = syn::braced!(content in input);
+                    (brace_token, content.parse()?, None)
+                }
+            };
 
             variants.push(RuleVariant {
+                attrs,
+                dialects,
                 pattern,
                 label,
+                guard,
+                brace_token,
                 action,
+                struct_action,
             });
 
             if input.peek(Token![|]) {
@@ -217,6 +1263,26 @@ impl RuleVariant {
         }
         Ok(variants)
     }
+
+    /// Checks that the action parses as a block, so that errors point at the
+    /// grammar instead of the generated code.
+    fn check_action(&self, rule_name: &Ident, index: usize) -> Result<()> {
+        let action = replace_span_var(&self.action, &quote::format_ident!("_variant_span"));
+        syn::parse2::<syn::Block>(quote::quote!({ #action })).map_err(|e| {
+            let variant = match &self.label {
+                Some(label) => format!("alternative \"{}\"", label),
+                None => format!("alternative {}", index + 1),
+            };
+            syn::Error::new(
+                self.brace_token.span.join(),
+                format!(
+                    "Rule '{}', {}: action is not a valid block: {}",
+                    rule_name, variant, e
+                ),
+            )
+        })?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -250,8 +1316,12 @@ pub enum Pattern {
         binding: Option<Ident>,
         lit: Lit,
     },
+    /// A call to a rule. `super` (`rule_name == "super"`) calls the parent grammar's version
+    /// of the enclosing rule, and `lex::token` (`module == Some(lex)`) a rule of the grammar
+    /// imported as `lex`.
     RuleCall {
         binding: Option<Ident>,
+        module: Option<Ident>,
         rule_name: Ident,
         generics: Vec<Type>, // Added generics support
         args: Vec<Argument>, // Changed from Vec<Pattern>
@@ -263,6 +1333,13 @@ pub enum Pattern {
     Optional(Box<Pattern>, Token![?]),
     Repeat(Box<Pattern>, Token![*]),
     Plus(Box<Pattern>, Token![+]),
+    /// A repetition with bounds, e.g. `digit{2,4}`, `digit{3}` or `digit{2,}` (no maximum).
+    Bounded {
+        pattern: Box<Pattern>,
+        min: usize,
+        max: Option<usize>,
+        brace: token::Brace,
+    },
     SpanBinding(Box<Pattern>, Ident, Token![@]),
     Recover {
         binding: Option<Ident>,
@@ -272,16 +1349,86 @@ pub enum Pattern {
     },
     Peek(Box<Pattern>, kw::peek),
     Not(Box<Pattern>, kw::not),
+    /// A lookbehind: the tokens consumed so far end with the pattern and are directly followed
+    /// by the next token, e.g. `follows(ident)`.
+    Follows(Box<Pattern>, kw::follows),
+    /// Adds the text of the tokens matched by the pattern to the innermost scope of the
+    /// symbol table, e.g. `declare(ident)`. A binding binds the value of the pattern.
+    Declare(Box<Pattern>, kw::declare),
+    /// Fails unless the text of the tokens matched by the pattern has been declared in an
+    /// enclosing scope, e.g. `resolve(ident)`.
+    Resolve(Box<Pattern>, kw::resolve),
+    /// A sequence parsed in a new scope of the symbol table, e.g. `scope { { stmt* } }`.
+    Scope(Vec<Pattern>, kw::scope, token::Brace),
     Until {
         binding: Option<Ident>,
         pattern: Box<Pattern>,
         kw_token: kw::until,
     },
+    /// `fold_left(item, op, |l, op, r| expr)` or `fold_right(..)`: items separated by
+    /// operators, combined pairwise by the expression from the left or from the right.
+    /// `fixity` is `Left` or `Right`.
+    Fold {
+        binding: Option<Ident>,
+        fixity: Fixity,
+        item: Box<Pattern>,
+        op: Box<Pattern>,
+        params: Vec<syn::Pat>,
+        action: TokenStream,
+        kw_token: Ident,
+    },
+    /// A pattern whose binding is declared `mut` and/or annotated with a type,
+    /// e.g. `xs:mut item*` or `x:ident: syn::Ident`.
+    Annotated {
+        pattern: Box<Pattern>,
+        binding: Ident,
+        mut_token: Option<Token![mut]>,
+        ty: Option<Box<Type>>,
+    },
+    /// The verbatim tokens consumed by a group, e.g. `raw@(a:ident "=" b:expr)`.
+    Raw(Box<Pattern>, Ident, Token![@]),
+    /// A binding inside a repetition that collects into the given type,
+    /// e.g. `#[collect(String)] parts:ident*`.
+    Collect(Box<Pattern>, Box<Type>, Token![#]),
+    /// A pattern whose failures before consuming input are reported as "expected {label}",
+    /// e.g. `#[expected("a type")] t:ty`.
+    Expected(Box<Pattern>, syn::LitStr, Token![#]),
+    /// A pattern whose failure is fatal instead of backtracking to the next alternative,
+    /// e.g. `")"!`.
+    Commit(Box<Pattern>, Token![!]),
 }
 
 impl Parse for Pattern {
     fn parse(input: ParseStream) -> Result<Self> {
-        let mut pat = parse_atom(input)?;
+        // 1. Check for `#[collect(Type)]` and `#[expected("label")]` annotations and a binding
+        let (collect, expected) = parse_pattern_attrs(input)?;
+        let binding = rt::attempt(input, |input| {
+            let id: Ident = input.parse()?;
+            // `alias::rule` is a call, not a binding
+            if input.peek(Token![::]) {
+                return Err(input.error("expected `:`"));
+            }
+            let _ = input.parse::<Token![:]>()?;
+            Ok(id)
+        })?;
+        if let (Some((pound, _)), None) = (&collect, &binding) {
+            return Err(syn::Error::new(
+                pound.span,
+                "`#[collect]` needs a binding, e.g. `#[collect(String)] parts:ident*`",
+            ));
+        }
+        let mut_token: Option<Token![mut]> = match binding {
+            Some(_) => input.parse()?,
+            None => None,
+        };
+
+        let mut pat = parse_atom(input, binding.clone())?;
+        if let Some((pound, label)) = expected {
+            pat = Pattern::Expected(Box::new(pat), label, pound);
+        }
+        if let Some((pound, ty)) = collect {
+            pat = Pattern::Collect(Box::new(pat), Box::new(ty), pound);
+        }
 
         loop {
             if input.peek(Token![*]) {
@@ -293,26 +1440,127 @@ impl Parse for Pattern {
             } else if input.peek(Token![?]) {
                 let token = input.parse::<Token![?]>()?;
                 pat = Pattern::Optional(Box::new(pat), token);
+            } else if peek_bounds(input) {
+                pat = parse_bounds(input, pat)?;
             } else if input.peek(Token![@]) {
                 let token = input.parse::<Token![@]>()?;
                 let ident = input.parse::<Ident>()?;
                 pat = Pattern::SpanBinding(Box::new(pat), ident, token);
+            } else if input.peek(Token![!]) {
+                let token = input.parse::<Token![!]>()?;
+                pat = Pattern::Commit(Box::new(pat), token);
             } else {
                 break;
             }
         }
+
+        // 2. Type annotation of the binding, as seen by the action: `xs:item*: Vec<Item>`
+        if let Some(binding) = binding {
+            let ty = if input.peek(Token![:]) {
+                let _ = input.parse::<Token![:]>()?;
+                Some(Box::new(input.parse::<Type>()?))
+            } else {
+                None
+            };
+            if mut_token.is_some() || ty.is_some() {
+                pat = Pattern::Annotated {
+                    pattern: Box::new(pat),
+                    binding,
+                    mut_token,
+                    ty,
+                };
+            }
+        }
         Ok(pat)
     }
 }
 
-fn parse_atom(input: ParseStream) -> Result<Pattern> {
-    // 1. Check for binding
-    let binding = rt::attempt(input, |input| {
-        let id: Ident = input.parse()?;
-        let _ = input.parse::<Token![:]>()?;
-        Ok(id)
-    })?;
+/// Returns true if the input starts with the bounds of a repetition, `{n}`, `{n,}` or `{n,m}`,
+/// rather than a braced pattern.
+fn peek_bounds(input: ParseStream) -> bool {
+    let fork = input.fork();
+    let starts_with_int = || -> Result<bool> {
+        let content;
+        syn::braced!(content in fork);
+        Ok(content.peek(syn::LitInt))
+    };
+    input.peek(token::Brace) && starts_with_int().unwrap_or(false)
+}
+
+fn parse_bounds(input: ParseStream, pattern: Pattern) -> Result<Pattern> {
+    let content;
+    let brace = syn::braced!(content in input);
+    let min = content.parse::<syn::LitInt>()?.base10_parse::<usize>()?;
+    let max = if content.is_empty() {
+        Some(min)
+    } else {
+        let _ = content.parse::<Token![,]>()?;
+        if content.is_empty() {
+            None
+        } else {
+            Some(content.parse::<syn::LitInt>()?.base10_parse::<usize>()?)
+        }
+    };
+    if !content.is_empty() {
+        return Err(content.error("expected `}`"));
+    }
+    match max {
+        Some(0) => Err(syn::Error::new(
+            brace.span.join(),
+            "A bounded repetition must allow at least one item",
+        )),
+        Some(max) if max < min => Err(syn::Error::new(
+            brace.span.join(),
+            format!(
+                "The maximum of a bounded repetition ({}) is less than its minimum ({})",
+                max, min
+            ),
+        )),
+        _ => Ok(Pattern::Bounded {
+            pattern: Box::new(pattern),
+            min,
+            max,
+            brace,
+        }),
+    }
+}
+
+type PatternAttrs = (Option<(Token![#], Type)>, Option<(Token![#], syn::LitStr)>);
+
+/// Parses the `#[collect(Type)]` and `#[expected("label")]` (or `#[expected = "label"]`)
+/// annotations in front of a pattern.
+fn parse_pattern_attrs(input: ParseStream) -> Result<PatternAttrs> {
+    let (mut collect, mut expected) = (None, None);
+    while input.peek(Token![#]) && input.peek2(token::Bracket) {
+        let pound = input.parse::<Token![#]>()?;
+        let content;
+        syn::bracketed!(content in input);
+        if content.peek(kw::collect) && collect.is_none() {
+            let _ = content.parse::<kw::collect>()?;
+            let args;
+            syn::parenthesized!(args in content);
+            collect = Some((pound, args.parse()?));
+        } else if content.peek(kw::expected) && expected.is_none() {
+            let _ = content.parse::<kw::expected>()?;
+            let label = if content.peek(Token![=]) {
+                let _ = content.parse::<Token![=]>()?;
+                content.parse()?
+            } else {
+                let args;
+                syn::parenthesized!(args in content);
+                args.parse()?
+            };
+            expected = Some((pound, label));
+        } else {
+            return Err(content.error(
+                "expected `collect(Type)` or `expected(\"label\")`, the only attributes of patterns",
+            ));
+        }
+    }
+    Ok((collect, expected))
+}
 
+fn parse_atom(input: ParseStream, binding: Option<Ident>) -> Result<Pattern> {
     if input.peek(Token![=>]) {
         if binding.is_some() {
             return Err(input.error("Cut operator cannot be bound."));
@@ -360,6 +1608,18 @@ fn parse_atom(input: ParseStream) -> Result<Pattern> {
         let content;
         let token = syn::parenthesized!(content in input);
         Ok(Pattern::Group(parse_group_content(&content)?, token))
+    } else if input.peek(Ident) && input.peek2(Token![@]) && input.peek3(token::Paren) {
+        if binding.is_some() {
+            return Err(input.error(
+                "Raw captures cannot be bound with `:`, the name before `@` binds the tokens.",
+            ));
+        }
+        let name: Ident = input.parse()?;
+        let at = input.parse::<Token![@]>()?;
+        let content;
+        let token = syn::parenthesized!(content in input);
+        let group = Pattern::Group(parse_group_content(&content)?, token);
+        Ok(Pattern::Raw(Box::new(group), name, at))
     } else if input.peek(kw::recover) {
         let kw_token = input.parse::<kw::recover>()?;
         let content;
@@ -391,6 +1651,60 @@ fn parse_atom(input: ParseStream) -> Result<Pattern> {
         syn::parenthesized!(content in input);
         let inner = content.parse()?;
         Ok(Pattern::Not(Box::new(inner), kw_token))
+    } else if input.peek(kw::follows) && input.peek2(token::Paren) {
+        if binding.is_some() {
+            return Err(input.error("Follows cannot be bound."));
+        }
+        let kw_token = input.parse::<kw::follows>()?;
+        let content;
+        syn::parenthesized!(content in input);
+        let inner = content.parse()?;
+        Ok(Pattern::Follows(Box::new(inner), kw_token))
+    } else if (input.peek(kw::declare) || input.peek(kw::resolve)) && input.peek2(token::Paren) {
+        let kw_token: Ident = input.parse()?;
+        let content;
+        syn::parenthesized!(content in input);
+        let mut inner: Pattern = content.parse()?;
+        if let Some(binding) = binding {
+            // The binding binds the value of the pattern, e.g. the `syn::Ident` of `ident`.
+            match &mut inner {
+                Pattern::RuleCall { binding: slot, .. } | Pattern::Lit { binding: slot, .. }
+                    if slot.is_none() =>
+                {
+                    *slot = Some(binding);
+                }
+                _ => {
+                    let msg = format!(
+                        "`{0}` can only be bound if its pattern is an unbound rule call or literal, e.g. `name:{0}(ident)`",
+                        kw_token
+                    );
+                    return Err(syn::Error::new(kw_token.span(), msg));
+                }
+            }
+        }
+        if kw_token == "declare" {
+            Ok(Pattern::Declare(
+                Box::new(inner),
+                kw::declare(kw_token.span()),
+            ))
+        } else {
+            Ok(Pattern::Resolve(
+                Box::new(inner),
+                kw::resolve(kw_token.span()),
+            ))
+        }
+    } else if input.peek(kw::scope) && input.peek2(token::Brace) {
+        if binding.is_some() {
+            return Err(input.error("Scopes cannot be bound directly."));
+        }
+        let kw_token = input.parse::<kw::scope>()?;
+        let content;
+        let token = syn::braced!(content in input);
+        Ok(Pattern::Scope(
+            parse_pattern_list(&content)?,
+            kw_token,
+            token,
+        ))
     } else if input.peek(kw::until) {
         // until returns a TokenStream, so it can be bound.
         let kw_token = input.parse::<kw::until>()?;
@@ -402,11 +1716,64 @@ fn parse_atom(input: ParseStream) -> Result<Pattern> {
             pattern: Box::new(pattern),
             kw_token,
         })
+    } else if (input.peek(kw::fold_left) || input.peek(kw::fold_right)) && input.peek2(token::Paren)
+    {
+        let kw_token: Ident = input.parse()?;
+        let fixity = if kw_token == "fold_left" {
+            Fixity::Left
+        } else {
+            Fixity::Right
+        };
+        let content;
+        syn::parenthesized!(content in input);
+        let item = content.parse()?;
+        let _ = content.parse::<Token![,]>()?;
+        let op = content.parse()?;
+        let _ = content.parse::<Token![,]>()?;
+        let params = parse_action_params(&content, "|l, op, r|")?;
+        let action: TokenStream = content.parse()?;
+        if action.is_empty() {
+            return Err(content.error("expected the expression combining the operands"));
+        }
+        Ok(Pattern::Fold {
+            binding,
+            fixity,
+            item: Box::new(item),
+            op: Box::new(op),
+            params,
+            action,
+            kw_token,
+        })
+    } else if input.peek(Token![super]) {
+        // The parent grammar's version of the enclosing rule, with the rule's arguments
+        let token = input.parse::<Token![super]>()?;
+        let args = if input.peek(token::Paren)
+            && spans_are_contiguous(token.span, input.cursor().span())
+        {
+            parse_args(input)?
+        } else {
+            Vec::new()
+        };
+        Ok(Pattern::RuleCall {
+            binding,
+            module: None,
+            rule_name: Ident::new("super", token.span),
+            generics: Vec::new(),
+            args,
+        })
     } else {
-        let rule_name: Ident = rt::parse_ident(input)?;
+        let mut rule_name: Ident = rt::parse_ident(input)?;
+
+        // `alias::rule` calls a rule of an imported grammar
+        let module = if input.peek(Token![::]) && input.peek3(Ident) {
+            let _ = input.parse::<Token![::]>()?;
+            Some(std::mem::replace(&mut rule_name, rt::parse_ident(input)?))
+        } else {
+            None
+        };
 
         // Check for aliases
-        let is_alias = get_alias(&rule_name.to_string()).is_some();
+        let is_alias = module.is_none() && get_alias(&rule_name.to_string()).is_some();
         if is_alias {
             // Check if it looks like a rule call (generics or contiguous parens)
             let has_generics = input.peek(Token![<]);
@@ -463,6 +1830,7 @@ fn parse_atom(input: ParseStream) -> Result<Pattern> {
 
         Ok(Pattern::RuleCall {
             binding,
+            module,
             rule_name,
             generics,
             args,
@@ -497,7 +1865,7 @@ fn parse_group_content(input: ParseStream) -> Result<Vec<(Vec<Pattern>, Option<S
     let mut alts = Vec::new();
     loop {
         let mut seq = Vec::new();
-        while !input.is_empty() && !input.peek(Token![|]) && !input.peek(Token![#]) {
+        while !input.is_empty() && !input.peek(Token![|]) && !is_label_start(input) {
             seq.push(input.parse()?);
         }
 
@@ -559,3 +1927,701 @@ fn get_alias(name: &str) -> Option<&'static str> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::quote;
+
+    #[test]
+    fn test_invalid_action_block() {
+        let input = quote! {
+            grammar test {
+                rule main -> () =
+                    "a" -> { () }
+                  | "b" -> { let x = ; }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an invalid action error");
+        assert!(err
+            .to_string()
+            .starts_with("Rule 'main', alternative 2: action is not a valid block:"));
+    }
+
+    #[test]
+    fn test_syntax_errors_are_reported_per_rule() {
+        let input = quote! {
+            grammar test {
+                rule a -> () = "a"
+                rule b () = "b" -> { () }
+                /// Doc comments start a new rule as well
+                rule c -> () = "c" -> { () } | "d"
+                #[doc = "last"]
+                pub rule d -> () = "d" -> { () }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected syntax errors");
+        let messages: Vec<_> = err.into_iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Rule 'a': every variant needs an action block `-> { ... }`",
+                "Rule 'b': expected `->` followed by the return type",
+                "Rule 'c': every variant needs an action block `-> { ... }`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_annotation() {
+        let input = quote! {
+            grammar test {
+                rule a -> String = (#[collect(String)] x:ident | "b" # "label")* -> { x }
+                #[doc = "still a rule attribute"]
+                rule c -> () = #[collect(String)] "c"* -> { () }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected a missing binding error");
+        assert_eq!(
+            err.to_string(),
+            "Rule 'c': `#[collect]` needs a binding, e.g. `#[collect(String)] parts:ident*`"
+        );
+    }
+
+    #[test]
+    fn test_expected_annotation() {
+        let input = quote! {
+            grammar test {
+                rule a -> String = #[expected("a name")] x:ident -> { x }
+                #[expected = "a type"]
+                rule b -> () = #[expected = "a word"] "b" -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        assert_eq!(grammar.rules.len(), 2);
+        assert!(matches!(
+            &grammar.rules[0].variants[0].pattern[0],
+            Pattern::Expected(inner, label, _)
+                if label.value() == "a name" && matches!(**inner, Pattern::RuleCall { .. })
+        ));
+        assert!(grammar.rules[1].attrs[0].path().is_ident("expected"));
+        assert!(matches!(
+            &grammar.rules[1].variants[0].pattern[0],
+            Pattern::Expected(_, label, _) if label.value() == "a word"
+        ));
+    }
+
+    #[test]
+    fn test_local_patterns() {
+        let input = quote! {
+            grammar test {
+                rule a -> () =
+                    let name = ident;
+                    let sig = n:name paren(args:name*);
+                    "fn" sig ";" -> { () }
+                  | sig? "=" -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let variants = &grammar.rules[0].variants;
+        // Spliced into the sequence, with the binding on the reference
+        assert_eq!(variants[0].pattern.len(), 4);
+        assert!(matches!(
+            &variants[0].pattern[1],
+            Pattern::RuleCall { binding: Some(b), rule_name, .. } if b == "n" && rule_name == "ident"
+        ));
+        assert!(matches!(
+            &variants[0].pattern[2],
+            Pattern::Parenthesized(inner, _, _)
+                if matches!(&inner[0], Pattern::Repeat(p, _)
+                    if matches!(&**p, Pattern::RuleCall { rule_name, .. } if rule_name == "ident"))
+        ));
+        // Inside another pattern, a sequence becomes a group
+        assert!(matches!(
+            &variants[1].pattern[0],
+            Pattern::Optional(inner, _)
+                if matches!(&**inner, Pattern::Group(alts, _) if alts.len() == 1 && alts[0].0.len() == 2)
+        ));
+    }
+
+    #[test]
+    fn test_local_pattern_errors() {
+        let error = |input: TokenStream| {
+            syn::parse2::<GrammarDefinition>(input)
+                .err()
+                .expect("the grammar should be rejected")
+                .to_string()
+        };
+        assert_eq!(
+            error(quote! { grammar g { rule a -> () = let p = "x" "y"; v:p -> { () } } }),
+            "Rule 'a': only a local pattern of a single literal or call without a binding can be \
+             bound; bind the values inside the local pattern `p` instead"
+        );
+        assert_eq!(
+            error(quote! { grammar g { rule a -> () = let p = ident; p(1) -> { () } } }),
+            "Rule 'a': the local pattern `p` takes no arguments"
+        );
+        assert_eq!(
+            error(quote! { grammar g { rule a -> () = let p = ; "x" -> { () } } }),
+            "Rule 'a': the local pattern `p` is empty"
+        );
+        assert_eq!(
+            error(quote! { grammar g { rule a -> () = let p = ident "x" -> { () } } }),
+            "Rule 'a': expected `;` after the local pattern `p`"
+        );
+    }
+
+    #[test]
+    fn test_commit_suffix() {
+        let input = quote! {
+            grammar test {
+                rule a -> i32 = "(" x:i32! ")"! -> { x }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let pattern = &grammar.rules[0].variants[0].pattern;
+        assert!(matches!(pattern[0], Pattern::Lit { .. }));
+        assert!(matches!(
+            &pattern[1],
+            Pattern::Commit(inner, _) if matches!(**inner, Pattern::RuleCall { .. })
+        ));
+        assert!(
+            matches!(&pattern[2], Pattern::Commit(inner, _) if matches!(**inner, Pattern::Lit { .. }))
+        );
+    }
+
+    #[test]
+    fn test_grammar_options() {
+        let input = quote! {
+            grammar test {
+                options { entry = main; no_backtrack = true; parser = TestParser; }
+                rule main -> () = "a" -> { () }
+                rule other -> () = "b" -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        assert_eq!(grammar.options.entry.as_ref().unwrap(), "main");
+        assert_eq!(grammar.options.parser.as_ref().unwrap(), "TestParser");
+        let main = &grammar.rules[0];
+        assert!(matches!(main.vis, Visibility::Public(_)));
+        assert!(main.attrs.iter().any(|a| a.path().is_ident("complete")));
+        assert!(matches!(grammar.rules[1].vis, Visibility::Inherited));
+        assert!(grammar
+            .rules
+            .iter()
+            .all(|r| r.attrs.iter().any(|a| a.path().is_ident("no_backtrack"))));
+        assert!(!grammar.options.layout);
+        assert!(!grammar.options.warn_unused_bindings);
+        assert!(!grammar.options.diagnose);
+        assert!(!grammar.options.lenient);
+        assert!(!grammar.options.incremental);
+
+        let input = quote! {
+            grammar test {
+                options {
+                    layout = significant;
+                    warn_unused_bindings = true;
+                    diagnose = true;
+                    lenient = true;
+                    incremental = true;
+                }
+                rule main -> () = "a" newline "b" -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        assert!(grammar.options.layout);
+        assert!(grammar.options.warn_unused_bindings);
+        assert!(grammar.options.diagnose);
+        assert!(grammar.options.lenient);
+        assert!(grammar.options.incremental);
+
+        let errors = [
+            (
+                quote!(options { whitespace = explicit; }),
+                "unknown option `whitespace`, expected `cst`, `diagnose`, `entry`, `incremental`, `layout`, `lenient`, `no_backtrack`, `parser`, `visibility` or `warn_unused_bindings`",
+            ),
+            (
+                quote!(options { layout = offside; }),
+                "unknown layout `offside`, expected `significant`",
+            ),
+            (
+                quote!(options { entry = main; entry = main; }),
+                "option `entry` is set more than once",
+            ),
+            (
+                quote!(options { entry = missing; }),
+                "the entry rule `missing` is not defined in this grammar",
+            ),
+            (
+                quote!(options {} options {}),
+                "a grammar can have only one `options` block",
+            ),
+        ];
+        for (options, message) in errors {
+            let input = quote! {
+                grammar test {
+                    #options
+                    rule main -> () = "a" -> { () }
+                }
+            };
+            let err = syn::parse2::<GrammarDefinition>(input)
+                .err()
+                .expect("expected an options error");
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        let input = quote! {
+            grammar test {
+                rule a -> i64 = n:fold_right(i64, "^", |l, _, r| l.pow(r as u32)) -> { n }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        assert!(matches!(
+            &grammar.rules[0].variants[0].pattern[0],
+            Pattern::Fold { binding: Some(b), fixity: Fixity::Right, params, .. }
+                if b == "n" && params.len() == 3
+        ));
+
+        let input = quote! {
+            grammar test {
+                rule a -> i64 = n:fold_left(i64, "+", |l, op, r|) -> { n }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected a missing action error");
+        assert!(err
+            .to_string()
+            .ends_with("expected the expression combining the operands"));
+    }
+
+    #[test]
+    fn test_bounded_repetition() {
+        let input = quote! {
+            grammar test {
+                rule a -> () = digit{2,4} ident{3} "x"{1,} {"y"} -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let bounds: Vec<_> = grammar.rules[0].variants[0]
+            .pattern
+            .iter()
+            .map(|p| match p {
+                Pattern::Bounded { min, max, .. } => Some((*min, *max)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            bounds,
+            [
+                Some((2, Some(4))),
+                Some((3, Some(3))),
+                Some((1, None)),
+                None
+            ]
+        );
+
+        for (input, message) in [
+            (
+                quote!(grammar t { rule a -> () = digit{4,2} -> { () } }),
+                "Rule 'a': The maximum of a bounded repetition (2) is less than its minimum (4)",
+            ),
+            (
+                quote!(grammar t { rule a -> () = digit{0} -> { () } }),
+                "Rule 'a': A bounded repetition must allow at least one item",
+            ),
+        ] {
+            let err = syn::parse2::<GrammarDefinition>(input)
+                .err()
+                .expect("expected invalid bounds");
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_where_guard() {
+        let input = quote! {
+            grammar test {
+                rule a -> i32 = n:i32 where { n > 0 } #"positive" -> { n } | n:i32 -> { -n }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let variants = &grammar.rules[0].variants;
+        assert_eq!(
+            variants[0].guard.as_ref().map(|g| g.to_string()),
+            Some("n > 0".to_string())
+        );
+        assert_eq!(variants[0].label.as_deref(), Some("positive"));
+        assert!(variants[1].guard.is_none());
+
+        let err = syn::parse2::<GrammarDefinition>(quote! {
+            grammar t { rule a -> i32 = n:i32 where {} -> { n } }
+        })
+        .err()
+        .expect("expected an empty guard to be rejected");
+        assert_eq!(
+            err.to_string(),
+            "Rule 'a': the condition of a `where` guard is empty"
+        );
+    }
+
+    #[test]
+    fn test_keyword_sets() {
+        let input = quote! {
+            grammar test {
+                keyword "union";
+                keywords { select, from }
+                keywords(query, table) { where_, limit }
+                rule query -> () = "select" -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let sets: Vec<(Vec<String>, Vec<String>)> = grammar
+            .keyword_sets
+            .iter()
+            .map(|set| {
+                let names = |idents: &[Ident]| idents.iter().map(|i| i.to_string()).collect();
+                (names(&set.rules), names(&set.words))
+            })
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                (vec![], vec!["select".to_string(), "from".to_string()]),
+                (
+                    vec!["query".to_string(), "table".to_string()],
+                    vec!["where_".to_string(), "limit".to_string()]
+                ),
+            ]
+        );
+        assert_eq!(grammar.keywords.len(), 1);
+
+        let err = syn::parse2::<GrammarDefinition>(quote! {
+            grammar t { keywords {} rule a -> () = "a" -> { () } }
+        })
+        .err()
+        .expect("expected an empty keyword set to be rejected");
+        assert_eq!(
+            err.to_string(),
+            "expected at least one keyword, e.g. `keywords { select, from }`"
+        );
+    }
+
+    #[test]
+    fn test_dialect_selection() {
+        let input = quote! {
+            #![dialect("b")]
+            grammar test {
+                #[dialect("a")]
+                rule only_a -> () = "a" -> { () }
+                #[dialect("a", "b")]
+                #[doc = "kept"]
+                rule a_or_b -> () =
+                    #[dialect("a")] "a" -> { () }
+                  | "x" -> { () }
+                  | #[dialect("b")] "b" -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        assert_eq!(grammar.rules.len(), 1);
+        let rule = &grammar.rules[0];
+        assert_eq!(rule.name, "a_or_b");
+        assert_eq!(rule.attrs.len(), 1);
+        assert_eq!(rule.variants.len(), 2);
+
+        let input = quote! {
+            grammar test {
+                rule main -> () = #[dialect("a")] "a" -> { () }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an error for a rule without alternatives");
+        assert_eq!(
+            err.to_string(),
+            "Rule 'main' has no alternatives in the selected dialects"
+        );
+    }
+
+    #[test]
+    fn test_token_pool() {
+        let input = quote! {
+            grammar test {
+                use std::fmt;
+                tokens from crate::dsl_tokens;
+                keyword "select";
+                rule main -> () = "select" -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let pool = grammar.token_pool.expect("expected a token pool");
+        assert_eq!(quote!(#pool).to_string(), "crate :: dsl_tokens");
+
+        let input = quote! {
+            grammar test {
+                tokens from a;
+                tokens from b;
+                rule main -> () = "x" -> { () }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an error for two token pools");
+        assert_eq!(
+            err.to_string(),
+            "a grammar can take its tokens from only one module"
+        );
+    }
+
+    #[test]
+    fn test_struct_action() {
+        let input = quote! {
+            grammar test {
+                rule main -> Item = 1 n:ident v:i32 -> Item { name: n.to_string(), .. }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let variant = &grammar.rules[0].variants[0];
+        let struct_action = variant.struct_action.as_ref().unwrap();
+        let bindings = [quote::format_ident!("n"), quote::format_ident!("v")];
+        assert_eq!(
+            struct_action.to_expr(&bindings).to_string(),
+            quote!(Item {
+                name: n.to_string(),
+                v,
+            })
+            .to_string()
+        );
+
+        let input = quote! {
+            grammar test {
+                rule main -> Item = 1 v:i32 -> Item { .., v }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an error for `..` before a field");
+        assert!(err
+            .to_string()
+            .contains("`..` must be the last entry of a struct action"));
+    }
+
+    #[test]
+    fn test_rule_visibility() {
+        let input = quote! {
+            grammar test {
+                pub(crate) rule a -> () = "a" -> {}
+                pub(super) rule b -> () = "b" -> {}
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        assert!(matches!(grammar.rules[0].vis, Visibility::Restricted(_)));
+
+        let input = quote! {
+            grammar test {
+                pub(in crate::x) rule a -> () = "a" -> {}
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an error for `pub(in ..)`");
+        assert!(err
+            .to_string()
+            .contains("a rule can be `pub`, `pub(crate)` or `pub(super)`"));
+    }
+
+    #[test]
+    fn test_spread_struct_action() {
+        let input = quote! {
+            grammar test {
+                rule main -> Item = n:ident _v:i32 -> * Item
+                rule other -> Item = n:ident v:i32 ->* Item { name: n.to_string() }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let bindings = [quote::format_ident!("n"), quote::format_ident!("_v")];
+        let expr = |i: usize, bindings: &[Ident]| {
+            let variant = &grammar.rules[i].variants[0];
+            let struct_action = variant.struct_action.as_ref().unwrap();
+            struct_action.to_expr(bindings).to_string()
+        };
+        // Bindings starting with `_` are not fields
+        assert_eq!(
+            expr(0, &bindings),
+            quote!(Item {
+                n,
+                ..::core::default::Default::default()
+            })
+            .to_string()
+        );
+        let bindings = [quote::format_ident!("n"), quote::format_ident!("v")];
+        assert_eq!(
+            expr(1, &bindings),
+            quote!(Item {
+                name: n.to_string(),
+                v,
+                ..::core::default::Default::default()
+            })
+            .to_string()
+        );
+
+        let input = quote! {
+            grammar test {
+                rule main -> Item = v:i32 ->* Item { .. }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an error for `..` in a spread action");
+        assert_eq!(
+            err.to_string(),
+            "Rule 'main': `->*` already fills the remaining fields; remove the `..`"
+        );
+    }
+
+    #[test]
+    fn test_span_var() {
+        let input = quote! {
+            grammar test {
+                rule main -> Span = "a" x:ident -> { let _ = x; $span }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let action = &grammar.rules[0].variants[0].action;
+        assert!(uses_span_var(action));
+        let replaced = replace_span_var(action, &quote::format_ident!("s"));
+        assert!(!uses_span_var(&replaced));
+        assert_eq!(replaced.to_string(), quote!(let _ = x; s).to_string());
+
+        let input = quote! {
+            grammar test {
+                rule expr -> i32 = precedence(atom) { left "+" => |l, _, r| { $span }; }
+                rule atom -> i32 = i:i32 -> { i }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an error for `$span` in an operator action");
+        assert!(
+            err.to_string().contains("`$span` is only available"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_rule_throws() {
+        let input = quote! {
+            grammar test {
+                rule main -> Vec<u8> throws Error = "a" -> { vec![] }
+                rule other -> u8 = "b" -> { 0 }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let throws = grammar.rules[0].throws.as_ref().unwrap();
+        assert_eq!(quote!(#throws).to_string(), "Error");
+        assert!(grammar.rules[1].throws.is_none());
+
+        let input = quote! {
+            grammar test {
+                rule expr -> i32 throws Error = precedence(atom) { left "+" => |l, _, r| { l + r }; }
+                rule atom -> i32 = i:i32 -> { i }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an error for `throws` on a precedence rule");
+        assert_eq!(
+            err.to_string(),
+            "Rule 'expr': a `precedence` rule cannot declare `throws`"
+        );
+    }
+
+    #[test]
+    fn test_symbol_patterns() {
+        let input = quote! {
+            grammar test {
+                rule block -> () = scope { "let" name:declare(ident) ";" resolve(ident) } -> { () }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let Pattern::Scope(seq, _, _) = &grammar.rules[0].variants[0].pattern[0] else {
+            panic!("expected a scope");
+        };
+        match &seq[1] {
+            Pattern::Declare(inner, _) => match &**inner {
+                Pattern::RuleCall { binding, .. } => {
+                    assert_eq!(binding.as_ref().unwrap(), "name")
+                }
+                _ => panic!("expected a rule call"),
+            },
+            _ => panic!("expected `declare`"),
+        }
+        assert!(matches!(seq[3], Pattern::Resolve(..)));
+
+        let input = quote! {
+            grammar test {
+                rule main -> () = x:declare(a:ident) -> { () }
+            }
+        };
+        let err = syn::parse2::<GrammarDefinition>(input)
+            .err()
+            .expect("expected an error for binding a bound pattern");
+        assert_eq!(
+            err.to_string(),
+            "Rule 'main': `declare` can only be bound if its pattern is an unbound rule call or literal, e.g. `name:declare(ident)`"
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let input = quote! {
+            grammar test {
+                rule expr -> i32 = precedence(atom) {
+                    left "+" "-" => |l, op, r| { l + r };
+                    prefix "-" => |_, x| { -x };
+                }
+                rule atom -> i32 = i:i32 -> { i }
+            }
+        };
+        let grammar = syn::parse2::<GrammarDefinition>(input).unwrap();
+        let precedence = grammar.rules[0].precedence.as_ref().unwrap();
+        assert_eq!(precedence.operand, "atom");
+        assert_eq!(precedence.levels.len(), 2);
+        assert_eq!(precedence.levels[0].fixity, Fixity::Left);
+        assert_eq!(precedence.levels[0].operators.len(), 2);
+        assert_eq!(precedence.levels[1].fixity, Fixity::Prefix);
+
+        let err = |input: TokenStream| {
+            syn::parse2::<GrammarDefinition>(input)
+                .err()
+                .expect("expected an error")
+                .to_string()
+        };
+        assert_eq!(
+            err(quote! {
+                grammar test {
+                    rule expr -> i32 = precedence(atom) { prefix "-" => |l, op, r| { l }; }
+                }
+            }),
+            "Rule 'expr': expected the action parameters `|op, x|`"
+        );
+        assert_eq!(
+            err(quote! {
+                grammar test {
+                    rule expr(n: i32) -> i32 = precedence(atom) { left "+" => |l, _, r| { l }; }
+                }
+            }),
+            "Rule 'expr': a `precedence` rule cannot take parameters or generics"
+        );
+    }
+}
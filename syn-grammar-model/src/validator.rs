@@ -2,65 +2,280 @@
 
 use crate::model::*;
 use std::collections::{HashMap, HashSet};
+use syn::ext::IdentExt;
 
+/// Validates the grammar, failing on the first error found.
+///
+/// Checks run in order (duplicate rules, undefined rules, argument counts, left
+/// recursion and nullable repetitions, `#[no_backtrack]` decisions, shadowing); only the
+/// shadowing errors are reported together.
 pub fn validate<B: Backend>(grammar: &GrammarDefinition) -> syn::Result<()> {
+    check::<B>(grammar, true, true)
+}
+
+/// Like [`validate`], but without printing warnings. Used for imported grammars, whose rules
+/// are reported as part of the grammar importing them.
+pub(crate) fn validate_quietly<B: Backend>(grammar: &GrammarDefinition) -> syn::Result<()> {
+    check::<B>(grammar, true, false)
+}
+
+/// Validates the grammar, reporting all errors found combined into one `syn::Error`.
+///
+/// Each error keeps its own span, so all of them can be fixed in one compile cycle.
+pub fn validate_all<B: Backend>(grammar: &GrammarDefinition) -> syn::Result<()> {
+    check::<B>(grammar, false, true)
+}
+
+/// Validates grammars that are used together, e.g. all grammar files of a project, reporting
+/// all errors combined into one `syn::Error`. Each message names its grammar.
+///
+/// Besides validating every grammar on its own (after resolving its imports against the set,
+/// see [`crate::imports::resolve_imports_in`]), it checks what a single grammar cannot:
+/// duplicate grammar names, parents of `grammar Child : Parent` that are missing from the set,
+/// inheritance cycles, and the rule calls and `super` calls of child grammars against the
+/// rules they inherit.
+pub fn validate_set<B: Backend>(grammars: &[GrammarDefinition]) -> syn::Result<()> {
+    let mut errors = Errors {
+        errors: Vec::new(),
+        fail_fast: false,
+    };
+    let in_grammar = |grammar: &GrammarDefinition, err: syn::Error| {
+        err.into_iter()
+            .map(|e| syn::Error::new(e.span(), format!("In grammar '{}': {}", grammar.name, e)))
+            .collect::<Vec<_>>()
+    };
+
+    let mut names = HashSet::new();
+    for grammar in grammars {
+        if !names.insert(grammar.name.to_string()) {
+            errors.errors.push(syn::Error::new(
+                grammar.name.span(),
+                format!("Duplicate grammar name: '{}'", grammar.name),
+            ));
+        }
+    }
+
+    // The grammars with their imports resolved, and whether that succeeded
+    let mut resolved = Vec::new();
+    for grammar in grammars {
+        let mut grammar = grammar.clone();
+        let ok = match crate::imports::resolve_imports_in::<B>(&mut grammar, grammars) {
+            Ok(()) => {
+                if let Err(err) = check::<B>(&grammar, false, true) {
+                    errors.errors.extend(in_grammar(&grammar, err));
+                }
+                true
+            }
+            Err(err) => {
+                errors.errors.extend(in_grammar(&grammar, err));
+                false
+            }
+        };
+        resolved.push((grammar, ok));
+    }
+
+    let builtin_names = B::get_builtins().iter().map(|b| b.name.to_string());
+    let builtin_names: HashSet<String> = builtin_names.collect();
+    for (grammar, ok) in &resolved {
+        if grammar.inherits.is_none() {
+            continue;
+        }
+        // The ancestors, nearest first
+        let mut ancestors: Vec<&GrammarDefinition> = Vec::new();
+        let mut current = grammar;
+        let mut complete = true;
+        while let Some(parent) = &current.inherits {
+            if parent == &grammar.name || ancestors.iter().any(|a| a.name == *parent) {
+                let cycle: Vec<String> = std::iter::once(grammar)
+                    .chain(ancestors.iter().copied())
+                    .map(|g| g.name.to_string())
+                    .chain(std::iter::once(parent.to_string()))
+                    .collect();
+                errors.errors.push(syn::Error::new(
+                    grammar.name.span(),
+                    format!("Inheritance cycle: {}", cycle.join(" -> ")),
+                ));
+                complete = false;
+                break;
+            }
+            let Some((next, _)) = resolved.iter().find(|(g, _)| g.name == *parent) else {
+                errors.errors.push(syn::Error::new(
+                    parent.span(),
+                    format!(
+                        "Grammar '{}' inherits from '{}', which is not in the set",
+                        current.name, parent
+                    ),
+                ));
+                complete = false;
+                break;
+            };
+            ancestors.push(next);
+            current = next;
+        }
+        // Calls of grammars whose imports failed to resolve are still qualified
+        if !complete || !ok {
+            continue;
+        }
+
+        // Calls in the child may name its own, inherited and built-in rules
+        let inherited: HashSet<String> = ancestors
+            .iter()
+            .flat_map(|a| &a.rules)
+            .map(|r| r.name.to_string())
+            .collect();
+        let all_defs: HashSet<String> = grammar
+            .rules
+            .iter()
+            .map(|r| r.name.to_string())
+            .chain(inherited.iter().cloned())
+            .chain(builtin_names.iter().cloned())
+            .collect();
+        let mut call_errors = Vec::new();
+        for rule in &grammar.rules {
+            // `super` is defined if an ancestor has the rule
+            if !inherited.contains(&rule.name.to_string()) && crate::analysis::calls_super(rule) {
+                call_errors.push(syn::Error::new(
+                    rule.name.span(),
+                    format!(
+                        "Rule '{}' calls `super`, but no parent grammar defines '{}'",
+                        rule.name, rule.name
+                    ),
+                ));
+            }
+            let mut defs = all_defs.clone();
+            defs.insert("super".to_string());
+            validate_rule(rule, &defs, &mut call_errors);
+        }
+        for err in call_errors {
+            errors.errors.extend(in_grammar(grammar, err));
+        }
+    }
+
+    errors.finish()
+}
+
+fn check<B: Backend>(grammar: &GrammarDefinition, fail_fast: bool, warn: bool) -> syn::Result<()> {
+    let mut errors = Errors {
+        errors: Vec::new(),
+        fail_fast,
+    };
+
     let builtins = B::get_builtins();
     let builtin_names: HashSet<String> = builtins.iter().map(|b| b.name.to_string()).collect();
 
     let mut defined_rules = HashSet::new();
     for rule in &grammar.rules {
         if !defined_rules.insert(rule.name.to_string()) {
-            return Err(syn::Error::new(
+            errors.errors.push(syn::Error::new(
                 rule.name.span(),
                 format!("Duplicate rule definition: '{}'", rule.name),
             ));
         }
     }
+    validate_keywords(grammar, &mut errors.errors);
+    errors.checkpoint()?;
 
-    let all_defs: HashSet<_> = grammar
+    let mut all_defs: HashSet<_> = grammar
         .rules
         .iter()
         .map(|r| r.name.to_string())
         .chain(builtin_names.iter().cloned())
         .collect();
 
-    let should_validate_rule_calls = grammar.inherits.is_none();
-
-    if should_validate_rule_calls {
+    // The rules of the ancestors are not known here: calls to undefined rules are assumed to
+    // be inherited and left to the compiler, while `validate_set` checks them against the
+    // ancestors. Everything else about the calls is still checked.
+    if grammar.inherits.is_some() {
         for rule in &grammar.rules {
-            validate_rule(rule, &all_defs)?;
+            for variant in &rule.variants {
+                let called = crate::analysis::called_rules(&variant.pattern);
+                all_defs.extend(
+                    called
+                        .into_iter()
+                        .filter(|name| !PORTABLE_BUILTINS.contains(&name.as_str())),
+                );
+            }
         }
+        all_defs.insert("super".to_string());
     }
 
-    validate_argument_counts(grammar)?;
+    for rule in &grammar.rules {
+        validate_rule(rule, &all_defs, &mut errors.errors);
+    }
+    errors.checkpoint()?;
+
+    validate_argument_counts(grammar, &mut errors.errors);
+    errors.checkpoint()?;
 
     // Perform advanced analysis
     let analysis = crate::analysis::analyze_grammar(grammar);
 
     // 1. Detect Infinite Recursion (Error)
-    for cycle in &analysis.cycles {
-        // We filter out self-loops (length 1) because the macro handles direct left recursion (e.g. A -> A b).
-        // We only report indirect recursion (A -> B -> A) or complex cycles that are not supported.
-        if cycle.len() > 1 {
-            let cycle_str = cycle
-                .iter()
-                .chain(std::iter::once(&cycle[0]))
-                .cloned()
-                .collect::<Vec<_>>()
-                .join(" -> ");
-            let msg = format!(
-                "Indirect left recursion detected (unsupported): {}",
-                cycle_str
-            );
+    // Self-loops (length 1) are fine because the macro handles direct left recursion (e.g. A -> A b),
+    // and most indirect recursion (A -> B -> A) is rewritten into it. We only report the cycles
+    // that cannot be rewritten.
+    let mut rewritten = grammar.clone();
+    for cycle in &crate::analysis::eliminate_indirect_left_recursion(&mut rewritten) {
+        let cycle_str = cycle
+            .iter()
+            .chain(std::iter::once(&cycle[0]))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        let msg = format!(
+            "Indirect left recursion detected (unsupported): {} (the cycle cannot be rewritten into direct left recursion)",
+            cycle_str
+        );
 
-            let rule_name = &cycle[0];
-            let rule = grammar.rules.iter().find(|r| r.name == *rule_name).unwrap();
-            return Err(syn::Error::new(rule.name.span(), msg));
+        let rule_name = &cycle[0];
+        let rule = grammar.rules.iter().find(|r| r.name == *rule_name).unwrap();
+        errors.errors.push(syn::Error::new(rule.name.span(), msg));
+    }
+    // Repetitions of patterns that can match nothing would loop forever, and declaring or
+    // resolving a pattern that can match nothing would use an empty name
+    for rule in &grammar.rules {
+        for variant in &rule.variants {
+            validate_repetitions(&variant.pattern, &analysis, &mut errors.errors);
+            validate_follows(&variant.pattern, true, &mut errors.errors);
+        }
+    }
+    errors.checkpoint()?;
+
+    // `#[no_backtrack]` rules must be decidable by peeking at one token
+    let kws = crate::analysis::collect_custom_keywords(grammar);
+    let visible_builtins: Vec<BuiltIn> = builtins
+        .iter()
+        .filter(|b| !defined_rules.contains(b.name))
+        .cloned()
+        .collect();
+    for rule in grammar
+        .rules
+        .iter()
+        .filter(|r| crate::analysis::is_no_backtrack(r))
+    {
+        if let Err(errs) =
+            crate::analysis::no_backtrack_peeks(rule, &analysis, &kws, &visible_builtins)
+        {
+            errors.errors.extend(errs);
         }
     }
+    errors.checkpoint()?;
 
-    // 2. Warn about Unused Rules
-    if should_validate_rule_calls {
+    // 2. Warnings
+    if warn {
+        print_warnings(grammar, &analysis);
+    }
+
+    // 3. Shadowing / Ambiguity Errors (always reported together)
+    errors.errors.extend(analysis.errors);
+
+    errors.finish()
+}
+
+/// Prints the warnings about `grammar` to stderr.
+fn print_warnings(grammar: &GrammarDefinition, analysis: &crate::analysis::GrammarAnalysis) {
+    // Unused rules; the rules of an inheriting grammar may be called by its ancestors
+    if grammar.inherits.is_none() {
         let mut unused: Vec<_> = analysis.unused_rules.iter().collect();
         unused.sort();
         for rule_name in unused {
@@ -68,46 +283,154 @@ pub fn validate<B: Backend>(grammar: &GrammarDefinition) -> syn::Result<()> {
                 eprintln!("warning: Unused rule: '{}'", rule_name);
             }
         }
+    }
 
-        // 3. Shadowing / Ambiguity Errors
-        if !analysis.errors.is_empty() {
-            let mut err = analysis.errors[0].clone();
-            for error in analysis.errors.iter().skip(1) {
-                err.combine(error.clone());
-            }
-            return Err(err);
+    // Rules and bindings named like custom keywords, and unused bindings of rule calls if
+    // the grammar asks for them
+    let unused_bindings = if grammar.options.warn_unused_bindings {
+        crate::analysis::find_unused_bindings(grammar)
+    } else {
+        Vec::new()
+    };
+    for warning in crate::analysis::find_keyword_shadowing(grammar)
+        .into_iter()
+        .chain(unused_bindings)
+    {
+        // Further messages of a warning are notes, e.g. pointing at a literal
+        for (i, message) in warning.into_iter().enumerate() {
+            let start = message.span().start();
+            eprintln!(
+                "{}: {} (line {}, column {})",
+                if i == 0 { "warning" } else { "note" },
+                message,
+                start.line,
+                start.column + 1
+            );
+        }
+    }
+}
+
+/// Errors collected by the validation checks.
+struct Errors {
+    errors: Vec<syn::Error>,
+    /// Stop at the first check that produced errors, reporting only its first error.
+    fail_fast: bool,
+}
+
+impl Errors {
+    fn checkpoint(&self) -> syn::Result<()> {
+        match self.errors.first() {
+            Some(first) if self.fail_fast => Err(first.clone()),
+            _ => Ok(()),
         }
     }
 
-    Ok(())
+    fn finish(self) -> syn::Result<()> {
+        let mut errors = self.errors.into_iter();
+        match errors.next() {
+            Some(mut err) => {
+                for error in errors {
+                    err.combine(error);
+                }
+                Err(err)
+            }
+            None => Ok(()),
+        }
+    }
 }
 
-fn validate_rule(rule: &Rule, all_defs: &HashSet<String>) -> syn::Result<()> {
+fn validate_rule(rule: &Rule, all_defs: &HashSet<String>, errors: &mut Vec<syn::Error>) {
     for variant in &rule.variants {
-        validate_pattern_sequence(&variant.pattern, all_defs, &rule.params)?;
+        validate_pattern_sequence(&variant.pattern, all_defs, &rule.params, errors);
     }
-    Ok(())
 }
 
 fn validate_pattern_sequence(
     patterns: &[ModelPattern],
     all_defs: &HashSet<String>,
     params: &[(syn::Ident, Option<syn::Type>)],
-) -> syn::Result<()> {
+    errors: &mut Vec<syn::Error>,
+) {
     for pattern in patterns {
-        validate_pattern(pattern, all_defs, params)?;
+        validate_pattern(pattern, all_defs, params, errors);
+    }
+}
+
+/// Declared keywords must be identifiers that `syn::custom_keyword!` accepts, and
+/// `keywords(rule) { .. }` must name rules of the grammar.
+fn validate_keywords(grammar: &GrammarDefinition, errors: &mut Vec<syn::Error>) {
+    let mut seen = HashSet::new();
+    for kw in &grammar.keywords {
+        let word = kw.value();
+        let message = if word != "_" && syn::parse_str::<syn::Ident>(&word).is_ok() {
+            if seen.insert(word.clone()) {
+                continue;
+            }
+            format!("Keyword '{}' is declared more than once", word)
+        } else if syn::parse::Parser::parse_str(syn::Ident::parse_any, &word).is_ok() {
+            format!(
+                "'{}' is a reserved Rust keyword and cannot be declared as a custom keyword",
+                word
+            )
+        } else {
+            format!("Keyword declaration '{}' is not a single identifier", word)
+        };
+        errors.push(syn::Error::new(kw.span(), message));
+    }
+
+    // Reserved words of `keywords { .. }` and the rules they are reserved in
+    for set in &grammar.keyword_sets {
+        let mut seen = HashSet::new();
+        for word in &set.words {
+            let text = word.to_string();
+            let message = if syn::parse_str::<syn::Ident>(&text).is_err() {
+                format!(
+                    "'{}' is a reserved Rust keyword and cannot be declared as a custom keyword",
+                    text
+                )
+            } else if !seen.insert(text.clone()) {
+                format!("Keyword '{}' is declared more than once", text)
+            } else {
+                continue;
+            };
+            errors.push(syn::Error::new(word.span(), message));
+        }
+        for rule in &set.rules {
+            if !grammar.rules.iter().any(|r| r.name == *rule) {
+                errors.push(syn::Error::new(
+                    rule.span(),
+                    format!("Keywords are reserved in undefined rule '{}'", rule),
+                ));
+            }
+        }
     }
-    Ok(())
 }
 
+/// The parametric built-ins every backend provides (ADR 004), which are not declared by
+/// `Backend::get_builtins`.
+const PORTABLE_BUILTINS: &[&str] = &[
+    "separated",
+    "repeated",
+    "delimited",
+    "terminated",
+    "until_parse",
+    "map",
+    "skip",
+    "parse_in",
+];
+
 fn validate_pattern(
     pattern: &ModelPattern,
     all_defs: &HashSet<String>,
     params: &[(syn::Ident, Option<syn::Type>)],
-) -> syn::Result<()> {
+    errors: &mut Vec<syn::Error>,
+) {
     match pattern {
         ModelPattern::RuleCall {
-            rule_name, args, ..
+            binding,
+            rule_name,
+            args,
+            ..
         } => {
             // Check if rule_name is in all_defs OR in params (as a grammar parameter)
             let is_param = params.iter().any(|(p_name, _)| p_name == rule_name);
@@ -119,78 +442,288 @@ fn validate_pattern(
 
             // Note: If 'separated' is not in all_defs, we might error.
             // The backend should probably export them or we hardcode them here?
-            // "separated" and "repeated" are portable built-ins.
-            let is_portable_builtin = rule_name == "separated" || rule_name == "repeated";
+            let is_portable_builtin = PORTABLE_BUILTINS.iter().any(|b| rule_name == b);
+
+            // `until_parse(item, stop)` only peeks `stop`
+            if rule_name == "until_parse" && !all_defs.contains("until_parse") {
+                if let [_, Argument::Positional(stop)] = &args[..] {
+                    validate_no_bindings(stop, "until_parse", errors);
+                }
+            }
 
-            if !all_defs.contains(&rule_name.to_string()) && !is_param && !is_portable_builtin {
-                return Err(syn::Error::new(
+            // `skip(pattern)` discards the value of its pattern
+            if rule_name == "skip" && !all_defs.contains("skip") {
+                if let Some(binding) = binding {
+                    errors.push(syn::Error::new(
+                        binding.span(),
+                        "`skip` discards the value of its pattern, so it cannot be bound",
+                    ));
+                }
+                for arg in args {
+                    let (Argument::Positional(p) | Argument::Named(_, p)) = arg;
+                    validate_no_bindings(p, "skip", errors);
+                }
+            }
+
+            // Only checked in grammars without a parent, or by `validate_set` with "super" in
+            // `all_defs` for rules a parent defines
+            if rule_name == "super" && !all_defs.contains("super") {
+                errors.push(syn::Error::new(
+                    rule_name.span(),
+                    "`super` calls the parent grammar's version of the rule, but this grammar \
+                     does not inherit from another grammar (`grammar Child : Parent`)",
+                ));
+            } else if rule_name != "super"
+                && !all_defs.contains(&rule_name.to_string())
+                && !is_param
+                && !is_portable_builtin
+            {
+                errors.push(syn::Error::new(
                     rule_name.span(),
                     format!("Undefined rule: '{}'", rule_name),
                 ));
             }
 
+            // The arguments of `scan` name a Rust function, not a rule.
+            let args: &[Argument] = if rule_name == "scan" { &[] } else { args };
             for arg in args {
                 match arg {
+                    // `required=auto` of `terminated` is an option, not a rule call
+                    Argument::Named(id, _) if rule_name == "terminated" && id == "required" => {}
                     Argument::Positional(p) | Argument::Named(_, p) => {
-                        validate_pattern(p, all_defs, params)?;
+                        validate_pattern(p, all_defs, params, errors);
                     }
                 }
             }
         }
         ModelPattern::Repeat(inner, _)
         | ModelPattern::Plus(inner, _)
+        | ModelPattern::Bounded { pattern: inner, .. }
         | ModelPattern::Optional(inner, _)
         | ModelPattern::SpanBinding(inner, _, _)
+        | ModelPattern::Raw(inner, _, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Collect(inner, _, _)
         | ModelPattern::Peek(inner, _) => {
-            validate_pattern(inner, all_defs, params)?;
+            validate_pattern(inner, all_defs, params, errors);
         }
-        ModelPattern::Not(inner, _) => {
-            validate_pattern(inner, all_defs, params)?;
+        ModelPattern::Not(inner, _) | ModelPattern::Follows(inner, _) => {
+            validate_pattern(inner, all_defs, params, errors);
         }
         ModelPattern::Group(variants, _) => {
             for (seq, _) in variants {
-                validate_pattern_sequence(seq, all_defs, params)?;
+                validate_pattern_sequence(seq, all_defs, params, errors);
             }
         }
         ModelPattern::Bracketed(seq, _)
         | ModelPattern::Braced(seq, _)
-        | ModelPattern::Parenthesized(seq, _) => {
-            validate_pattern_sequence(seq, all_defs, params)?;
+        | ModelPattern::Parenthesized(seq, _)
+        | ModelPattern::Scope(seq, _) => {
+            validate_pattern_sequence(seq, all_defs, params, errors);
         }
         ModelPattern::Recover { body, sync, .. } => {
-            validate_pattern(body, all_defs, params)?;
-            validate_pattern(sync, all_defs, params)?;
+            validate_pattern(body, all_defs, params, errors);
+            validate_pattern(sync, all_defs, params, errors);
         }
         ModelPattern::Until { pattern, .. } => {
-            validate_pattern(pattern, all_defs, params)?;
-            validate_no_bindings(pattern)?;
+            validate_pattern(pattern, all_defs, params, errors);
+            validate_no_bindings(pattern, "until", errors);
+        }
+        ModelPattern::Fold { item, op, .. } => {
+            validate_pattern(item, all_defs, params, errors);
+            validate_pattern(op, all_defs, params, errors);
         }
         _ => {}
     }
-    Ok(())
 }
 
-fn validate_no_bindings(pattern: &ModelPattern) -> syn::Result<()> {
+/// Reports `*` and `+` whose repeated pattern is nullable, at the span of the operator, and
+/// `declare` and `resolve` whose pattern is, at the span of the keyword.
+fn validate_repetitions(
+    patterns: &[ModelPattern],
+    analysis: &crate::analysis::GrammarAnalysis,
+    errors: &mut Vec<syn::Error>,
+) {
+    for pattern in patterns {
+        match pattern {
+            ModelPattern::Repeat(inner, span) | ModelPattern::Plus(inner, span) => {
+                if analysis.is_sequence_nullable(std::slice::from_ref(inner)) {
+                    errors.push(syn::Error::new(
+                        *span,
+                        "The repeated pattern can match without consuming input, so the repetition would never end",
+                    ));
+                }
+                validate_repetitions(std::slice::from_ref(inner), analysis, errors);
+            }
+            ModelPattern::Bounded {
+                pattern,
+                max: None,
+                span,
+                ..
+            } if analysis.is_sequence_nullable(std::slice::from_ref(pattern)) => {
+                errors.push(syn::Error::new(
+                    *span,
+                    "The repeated pattern can match without consuming input, so the repetition would never end",
+                ));
+                validate_repetitions(std::slice::from_ref(pattern), analysis, errors);
+            }
+            ModelPattern::Declare(inner, span) | ModelPattern::Resolve(inner, span) => {
+                if analysis.is_sequence_nullable(std::slice::from_ref(inner)) {
+                    let construct = match pattern {
+                        ModelPattern::Declare(..) => "declare",
+                        _ => "resolve",
+                    };
+                    errors.push(syn::Error::new(
+                        *span,
+                        format!(
+                            "The pattern of `{}` can match without consuming input, so it would have no name",
+                            construct
+                        ),
+                    ));
+                }
+                validate_repetitions(std::slice::from_ref(inner), analysis, errors);
+            }
+            ModelPattern::RuleCall { args, .. } => {
+                for arg in args {
+                    match arg {
+                        Argument::Positional(p) | Argument::Named(_, p) => {
+                            validate_repetitions(std::slice::from_ref(p), analysis, errors);
+                        }
+                    }
+                }
+            }
+            ModelPattern::Optional(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. }
+            | ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Collect(inner, _, _)
+            | ModelPattern::Peek(inner, _)
+            | ModelPattern::Not(inner, _)
+            | ModelPattern::Follows(inner, _)
+            | ModelPattern::Until { pattern: inner, .. } => {
+                validate_repetitions(std::slice::from_ref(inner), analysis, errors);
+            }
+            ModelPattern::Group(variants, _) => {
+                for (seq, _) in variants {
+                    validate_repetitions(seq, analysis, errors);
+                }
+            }
+            ModelPattern::Bracketed(seq, _)
+            | ModelPattern::Braced(seq, _)
+            | ModelPattern::Parenthesized(seq, _)
+            | ModelPattern::Scope(seq, _) => {
+                validate_repetitions(seq, analysis, errors);
+            }
+            ModelPattern::Recover { body, sync, .. } => {
+                validate_repetitions(std::slice::from_ref(body), analysis, errors);
+                validate_repetitions(std::slice::from_ref(sync), analysis, errors);
+            }
+            ModelPattern::Fold { item, op, span, .. } => {
+                if analysis.is_sequence_nullable(&[(**op).clone(), (**item).clone()]) {
+                    errors.push(syn::Error::new(
+                        *span,
+                        "The operator and item of the fold can match without consuming input, so the fold would never end",
+                    ));
+                }
+                validate_repetitions(std::slice::from_ref(item), analysis, errors);
+                validate_repetitions(std::slice::from_ref(op), analysis, errors);
+            }
+            ModelPattern::Cut(_) | ModelPattern::Lit { .. } => {}
+        }
+    }
+}
+
+/// Reports `follows(..)` where nothing can have been consumed yet: at the start of an
+/// alternative or of a delimited group, whose tokens the lookbehind cannot see.
+fn validate_follows(patterns: &[ModelPattern], at_start: bool, errors: &mut Vec<syn::Error>) {
+    for (i, pattern) in patterns.iter().enumerate() {
+        let at_start = at_start && i == 0;
+        match pattern {
+            ModelPattern::Follows(inner, span) => {
+                if at_start {
+                    errors.push(syn::Error::new(
+                        *span,
+                        "`follows` needs a preceding pattern in the same rule or delimited group",
+                    ));
+                }
+                validate_follows(std::slice::from_ref(inner), true, errors);
+            }
+            ModelPattern::RuleCall { args, .. } => {
+                for arg in args {
+                    match arg {
+                        Argument::Positional(p) | Argument::Named(_, p) => {
+                            validate_follows(std::slice::from_ref(p), at_start, errors);
+                        }
+                    }
+                }
+            }
+            ModelPattern::Optional(inner, _)
+            | ModelPattern::Repeat(inner, _)
+            | ModelPattern::Plus(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. }
+            | ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Collect(inner, _, _)
+            | ModelPattern::Peek(inner, _)
+            | ModelPattern::Not(inner, _)
+            | ModelPattern::Until { pattern: inner, .. } => {
+                validate_follows(std::slice::from_ref(inner), at_start, errors);
+            }
+            ModelPattern::Group(variants, _) => {
+                for (seq, _) in variants {
+                    validate_follows(seq, at_start, errors);
+                }
+            }
+            ModelPattern::Bracketed(seq, _)
+            | ModelPattern::Braced(seq, _)
+            | ModelPattern::Parenthesized(seq, _) => {
+                validate_follows(seq, true, errors);
+            }
+            ModelPattern::Scope(seq, _) => validate_follows(seq, at_start, errors),
+            ModelPattern::Recover { body, sync, .. } => {
+                validate_follows(std::slice::from_ref(body), at_start, errors);
+                validate_follows(std::slice::from_ref(sync), at_start, errors);
+            }
+            ModelPattern::Fold { item, op, .. } => {
+                validate_follows(std::slice::from_ref(item), at_start, errors);
+                validate_follows(std::slice::from_ref(op), false, errors);
+            }
+            ModelPattern::Cut(_) | ModelPattern::Lit { .. } => {}
+        }
+    }
+}
+
+/// Reports bindings inside the pattern of `construct` (`until` or `skip`), which discards them.
+fn validate_no_bindings(pattern: &ModelPattern, construct: &str, errors: &mut Vec<syn::Error>) {
     match pattern {
         ModelPattern::Lit { binding, .. } => {
             if binding.is_some() {
-                return Err(syn::Error::new(
+                errors.push(syn::Error::new(
                     binding.as_ref().unwrap().span(),
-                    "Bindings are not allowed inside 'until' patterns.",
+                    format!("Bindings are not allowed inside '{}' patterns.", construct),
                 ));
             }
         }
         ModelPattern::RuleCall { binding, args, .. } => {
             if binding.is_some() {
-                return Err(syn::Error::new(
+                errors.push(syn::Error::new(
                     binding.as_ref().unwrap().span(),
-                    "Bindings are not allowed inside 'until' patterns.",
+                    format!("Bindings are not allowed inside '{}' patterns.", construct),
                 ));
             }
             for arg in args {
                 match arg {
                     Argument::Positional(p) | Argument::Named(_, p) => {
-                        validate_no_bindings(p)?;
+                        validate_no_bindings(p, construct, errors);
                     }
                 }
             }
@@ -198,29 +731,49 @@ fn validate_no_bindings(pattern: &ModelPattern) -> syn::Result<()> {
         ModelPattern::Group(variants, _) => {
             for (seq, _) in variants {
                 for p in seq {
-                    validate_no_bindings(p)?;
+                    validate_no_bindings(p, construct, errors);
                 }
             }
         }
         ModelPattern::Bracketed(seq, _)
         | ModelPattern::Braced(seq, _)
-        | ModelPattern::Parenthesized(seq, _) => {
+        | ModelPattern::Parenthesized(seq, _)
+        | ModelPattern::Scope(seq, _) => {
             for p in seq {
-                validate_no_bindings(p)?;
+                validate_no_bindings(p, construct, errors);
             }
         }
         ModelPattern::Optional(inner, _)
         | ModelPattern::Repeat(inner, _)
         | ModelPattern::Plus(inner, _)
+        | ModelPattern::Bounded { pattern: inner, .. }
         | ModelPattern::Peek(inner, _)
         | ModelPattern::Not(inner, _)
+        | ModelPattern::Follows(inner, _)
+        | ModelPattern::Expected(inner, _, _)
+        | ModelPattern::Commit(inner, _)
+        | ModelPattern::Declare(inner, _)
+        | ModelPattern::Resolve(inner, _)
+        | ModelPattern::Collect(inner, _, _)
         | ModelPattern::Until { pattern: inner, .. } => {
-            validate_no_bindings(inner)?;
+            validate_no_bindings(inner, construct, errors);
         }
         ModelPattern::SpanBinding(_, ident, _) => {
-            return Err(syn::Error::new(
+            errors.push(syn::Error::new(
+                ident.span(),
+                format!(
+                    "Span bindings (@) are not allowed inside '{}' patterns.",
+                    construct
+                ),
+            ));
+        }
+        ModelPattern::Raw(_, ident, _) => {
+            errors.push(syn::Error::new(
                 ident.span(),
-                "Span bindings (@) are not allowed inside 'until' patterns.",
+                format!(
+                    "Raw captures (@) are not allowed inside '{}' patterns.",
+                    construct
+                ),
             ));
         }
         ModelPattern::Recover {
@@ -230,21 +783,32 @@ fn validate_no_bindings(pattern: &ModelPattern) -> syn::Result<()> {
             ..
         } => {
             if binding.is_some() {
-                return Err(syn::Error::new(
+                errors.push(syn::Error::new(
                     binding.as_ref().unwrap().span(),
-                    "Bindings are not allowed inside 'until' patterns.",
+                    format!("Bindings are not allowed inside '{}' patterns.", construct),
+                ));
+            }
+            validate_no_bindings(body, construct, errors);
+            validate_no_bindings(sync, construct, errors);
+        }
+        ModelPattern::Fold {
+            binding, item, op, ..
+        } => {
+            if let Some(binding) = binding {
+                errors.push(syn::Error::new(
+                    binding.span(),
+                    format!("Bindings are not allowed inside '{}' patterns.", construct),
                 ));
             }
-            validate_no_bindings(body)?;
-            validate_no_bindings(sync)?;
+            validate_no_bindings(item, construct, errors);
+            validate_no_bindings(op, construct, errors);
         }
         ModelPattern::Cut(_) => {}
     }
-    Ok(())
 }
 
 // Argument count validation
-fn validate_argument_counts(grammar: &GrammarDefinition) -> syn::Result<()> {
+fn validate_argument_counts(grammar: &GrammarDefinition, errors: &mut Vec<syn::Error>) {
     let rule_map: HashMap<_, _> = grammar
         .rules
         .iter()
@@ -254,16 +818,16 @@ fn validate_argument_counts(grammar: &GrammarDefinition) -> syn::Result<()> {
     for rule in &grammar.rules {
         for variant in &rule.variants {
             // Recursive validation of arguments
-            validate_args_recursive(&variant.pattern, &rule_map)?;
+            validate_args_recursive(&variant.pattern, &rule_map, errors);
         }
     }
-    Ok(())
 }
 
 fn validate_args_recursive(
     patterns: &[ModelPattern],
     rule_map: &HashMap<String, &Rule>,
-) -> syn::Result<()> {
+    errors: &mut Vec<syn::Error>,
+) {
     for pattern in patterns {
         match pattern {
             ModelPattern::RuleCall {
@@ -279,7 +843,7 @@ fn validate_args_recursive(
                     // Check if any args are named
                     for arg in args {
                         if let Argument::Named(n, _) = arg {
-                            return Err(syn::Error::new(
+                            errors.push(syn::Error::new(
                                 n.span(),
                                 "Named arguments are not supported for user-defined rules yet.",
                             ));
@@ -287,7 +851,7 @@ fn validate_args_recursive(
                     }
 
                     if target_rule.params.len() != args.len() {
-                        return Err(syn::Error::new(
+                        errors.push(syn::Error::new(
                             rule_name.span(),
                             format!(
                                 "Rule '{}' expects {} argument(s), but got {}.",
@@ -304,42 +868,53 @@ fn validate_args_recursive(
                 for arg in args {
                     match arg {
                         Argument::Positional(p) | Argument::Named(_, p) => {
-                            validate_args_recursive(std::slice::from_ref(p), rule_map)?;
+                            validate_args_recursive(std::slice::from_ref(p), rule_map, errors);
                         }
                     }
                 }
             }
             ModelPattern::Repeat(inner, _)
             | ModelPattern::Plus(inner, _)
+            | ModelPattern::Bounded { pattern: inner, .. }
             | ModelPattern::Optional(inner, _)
             | ModelPattern::SpanBinding(inner, _, _)
+            | ModelPattern::Raw(inner, _, _)
+            | ModelPattern::Expected(inner, _, _)
+            | ModelPattern::Commit(inner, _)
+            | ModelPattern::Declare(inner, _)
+            | ModelPattern::Resolve(inner, _)
+            | ModelPattern::Collect(inner, _, _)
             | ModelPattern::Peek(inner, _) => {
-                validate_args_recursive(std::slice::from_ref(inner), rule_map)?;
+                validate_args_recursive(std::slice::from_ref(inner), rule_map, errors);
             }
-            ModelPattern::Not(inner, _) => {
-                validate_args_recursive(std::slice::from_ref(inner), rule_map)?;
+            ModelPattern::Not(inner, _) | ModelPattern::Follows(inner, _) => {
+                validate_args_recursive(std::slice::from_ref(inner), rule_map, errors);
             }
             ModelPattern::Group(variants, _) => {
                 for (seq, _) in variants {
-                    validate_args_recursive(seq, rule_map)?;
+                    validate_args_recursive(seq, rule_map, errors);
                 }
             }
             ModelPattern::Bracketed(seq, _)
             | ModelPattern::Braced(seq, _)
-            | ModelPattern::Parenthesized(seq, _) => {
-                validate_args_recursive(seq, rule_map)?;
+            | ModelPattern::Parenthesized(seq, _)
+            | ModelPattern::Scope(seq, _) => {
+                validate_args_recursive(seq, rule_map, errors);
             }
             ModelPattern::Recover { body, sync, .. } => {
-                validate_args_recursive(std::slice::from_ref(body), rule_map)?;
-                validate_args_recursive(std::slice::from_ref(sync), rule_map)?;
+                validate_args_recursive(std::slice::from_ref(body), rule_map, errors);
+                validate_args_recursive(std::slice::from_ref(sync), rule_map, errors);
             }
             ModelPattern::Until { pattern, .. } => {
-                validate_args_recursive(std::slice::from_ref(pattern), rule_map)?;
+                validate_args_recursive(std::slice::from_ref(pattern), rule_map, errors);
+            }
+            ModelPattern::Fold { item, op, .. } => {
+                validate_args_recursive(std::slice::from_ref(item), rule_map, errors);
+                validate_args_recursive(std::slice::from_ref(op), rule_map, errors);
             }
             _ => {}
         }
     }
-    Ok(())
 }
 
 #[cfg(test)]
@@ -354,10 +929,17 @@ mod tests {
                 BuiltIn {
                     name: "ident",
                     return_type: "syn::Ident",
+                    peek: Some("syn::Ident"),
                 },
                 BuiltIn {
                     name: "string",
                     return_type: "String",
+                    peek: Some("syn::LitStr"),
+                },
+                BuiltIn {
+                    name: "position",
+                    return_type: "proc_macro2::Span",
+                    peek: None,
                 },
             ]
         }
@@ -396,6 +978,82 @@ mod tests {
         assert_eq!(err.to_string(), "Duplicate rule definition: 'main'");
     }
 
+    #[test]
+    fn test_indirect_left_recursion() {
+        let model = parse_model(quote! {
+            grammar test {
+                pub rule expr -> () = b:binary -> { b } | x:ident -> { () }
+                rule binary -> () = l:expr "+" r:ident -> { () }
+            }
+        });
+        validate::<TestBackend>(&model).unwrap();
+
+        // The rule in the middle of the cycle takes a parameter and cannot be inlined
+        let model = parse_model(quote! {
+            grammar test {
+                pub rule expr -> () = b:binary(1) -> { b } | x:ident -> { () }
+                rule binary(n: i32) -> () = l:expr "+" r:ident -> { () }
+            }
+        });
+        let err = validate::<TestBackend>(&model).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Indirect left recursion detected (unsupported): expr -> binary -> expr (the cycle cannot be rewritten into direct left recursion)"
+        );
+    }
+
+    #[test]
+    fn test_keyword_declarations() {
+        let input = quote! {
+            grammar test {
+                keyword "union";
+                keyword "fn";
+                keyword "a b";
+                keyword "union";
+                rule main -> () = "union" -> { () }
+            }
+        };
+        let model = parse_model(input);
+        let messages: Vec<String> = validate_all::<TestBackend>(&model)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "'fn' is a reserved Rust keyword and cannot be declared as a custom keyword",
+                "Keyword declaration 'a b' is not a single identifier",
+                "Keyword 'union' is declared more than once",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keyword_sets() {
+        let input = quote! {
+            grammar test {
+                keywords { select, fn, select }
+                keywords(main, missing) { from }
+                rule main -> () = "select" -> { () }
+            }
+        };
+        let model = parse_model(input);
+        let messages: Vec<String> = validate_all::<TestBackend>(&model)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "'fn' is a reserved Rust keyword and cannot be declared as a custom keyword",
+                "Keyword 'select' is declared more than once",
+                "Keywords are reserved in undefined rule 'missing'",
+            ]
+        );
+    }
+
     #[test]
     fn test_rule_args_mismatch() {
         let input = quote! {
@@ -461,6 +1119,76 @@ mod tests {
         validate::<TestBackend>(&model).unwrap();
     }
 
+    #[test]
+    fn test_no_backtrack() {
+        let input = quote! {
+            grammar test {
+                #[no_backtrack]
+                rule main -> () = "a" x -> { () } | x -> { () } | "b"? -> { () }
+                rule x -> () = "c" -> { () } | string -> { () }
+            }
+        };
+        validate::<TestBackend>(&parse_model(input)).unwrap();
+
+        let input = quote! {
+            #![no_backtrack]
+            grammar test {
+                rule main -> () = "a" x y -> { () } | x -> { () } | "b"? -> { () } | string -> { () }
+                rule x -> () = "d" -> { () } | ident -> { () }
+                rule y -> () = y "e" -> { () } | "f" -> { () }
+            }
+        };
+        let messages: Vec<String> = validate_all::<TestBackend>(&parse_model(input))
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Rule 'main' is #[no_backtrack], but alternative 2 can start with the same token as alternative 1",
+                "Rule 'main' is #[no_backtrack], but alternative 3 cannot be chosen by peeking at one token; only the last alternative can be taken without a peek",
+                "Rule 'x' is #[no_backtrack], but alternative 2 can start with the same token as alternative 1",
+                "Rule 'y' is #[no_backtrack], but alternative 1 is left-recursive",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_super_without_parent() {
+        let input = quote! {
+            grammar test {
+                rule main -> i32 = "neg" v:super -> { -v }
+            }
+        };
+        let err = validate::<TestBackend>(&parse_model(input)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("does not inherit from another grammar"));
+    }
+
+    #[test]
+    fn test_inheriting_grammar_calls() {
+        // `num` is assumed to be inherited, but the pattern it is called in is still checked
+        let input = quote! {
+            grammar test : base {
+                rule main -> i32 = n:num v:super until(x: "a") -> { n + v }
+            }
+        };
+        let err = validate::<TestBackend>(&parse_model(input)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bindings are not allowed inside 'until' patterns."
+        );
+
+        let input = quote! {
+            grammar test : base {
+                rule main -> i32 = n:num v:super -> { n + v }
+            }
+        };
+        validate::<TestBackend>(&parse_model(input)).unwrap();
+    }
+
     #[test]
     fn test_bug_typed_param() {
         let input = quote! {
@@ -486,5 +1214,219 @@ mod tests {
             err.to_string(),
             "Bindings are not allowed inside 'until' patterns."
         );
+
+        let input = quote! {
+            grammar test {
+                rule main -> () = items:until_parse(ident, x:"a") -> { () }
+            }
+        };
+        let err = validate::<TestBackend>(&parse_model(input)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Bindings are not allowed inside 'until_parse' patterns."
+        );
+    }
+
+    #[test]
+    fn test_skip_bindings() {
+        let input = quote! {
+            grammar test {
+                rule main -> () = a:skip(attrs) skip(b:attrs) -> { () }
+                rule attrs -> () = "#" -> { () }
+            }
+        };
+        let model = parse_model(input);
+        let err = validate_all::<TestBackend>(&model).unwrap_err();
+        let messages: Vec<String> = err.into_iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "`skip` discards the value of its pattern, so it cannot be bound",
+                "Bindings are not allowed inside 'skip' patterns.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_all_reports_every_error() {
+        let input = quote! {
+            grammar test {
+                rule main -> () = undefined_a sub(1) -> { () }
+                rule sub -> () = undefined_b -> { () }
+                rule sub -> () = "b" -> { () }
+            }
+        };
+        let model = parse_model(input);
+
+        // Fail-fast mode stops at the first failing check.
+        let err = validate::<TestBackend>(&model).unwrap_err();
+        assert_eq!(err.into_iter().count(), 1);
+
+        let messages: Vec<_> = validate_all::<TestBackend>(&model)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Duplicate rule definition: 'sub'",
+                "Undefined rule: 'undefined_a'",
+                "Undefined rule: 'undefined_b'",
+                "Rule 'sub' expects 0 argument(s), but got 1.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nullable_repetition() {
+        let input = quote! {
+            grammar test {
+                rule opt -> () = "a"? -> { () }
+                rule main -> () = "x" ("b"? opt)* ident+ -> { () }
+                rule lists -> () = (opt "c")* -> { () }
+                rule plus -> () = ("d"?)+ -> { () }
+                // `position()` is zero-width
+                rule pos -> () = position()* "e" -> { () }
+            }
+        };
+        let model = parse_model(input);
+        let messages: Vec<String> = validate_all::<TestBackend>(&model)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "The repeated pattern can match without consuming input, so the repetition would never end";
+                3
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nullable_symbol() {
+        let input = quote! {
+            grammar test {
+                rule main -> () = declare(ident?) resolve(ident) scope { declare(ident) } -> { () }
+            }
+        };
+        let model = parse_model(input);
+        let messages: Vec<String> = validate_all::<TestBackend>(&model)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec!["The pattern of `declare` can match without consuming input, so it would have no name"]
+        );
+    }
+
+    #[test]
+    fn test_follows_placement() {
+        let input = quote! {
+            grammar test {
+                rule main -> () = follows(ident) "?" -> { () }
+                    | "a" (follows("a") "?")? -> { () }
+                rule group -> () = "a" [follows("a") "?"] -> { () }
+            }
+        };
+        let model = parse_model(input);
+        let messages: Vec<String> = validate_all::<TestBackend>(&model)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            vec!["`follows` needs a preceding pattern in the same rule or delimited group"; 2]
+        );
+    }
+
+    #[test]
+    fn test_validate_set() {
+        let lexer = parse_model(quote! {
+            grammar lexer {
+                pub rule name -> syn::Ident = i:ident -> { i }
+                rule hidden -> () = "h" -> { () }
+            }
+        });
+        let base = parse_model(quote! {
+            grammar base {
+                pub rule main -> () = "a" -> { () }
+            }
+        });
+        let child = parse_model(quote! {
+            grammar child : base {
+                import "grammars/lexer.grammar" as lex;
+                pub rule main -> () = "b" n:lex::name -> { () } | v:super -> { v }
+            }
+        });
+        assert!(validate_set::<TestBackend>(&[lexer.clone(), base.clone(), child]).is_ok());
+
+        let broken = parse_model(quote! {
+            grammar child : base {
+                import "lexer.grammar" as lex;
+                pub rule other -> () = lex::hidden missing v:super -> { () }
+            }
+        });
+        let orphan = parse_model(quote! {
+            grammar orphan : nowhere {
+                rule main -> () = "x" -> { () }
+            }
+        });
+        let cycle_a = parse_model(quote! { grammar a : b { rule x -> () = "x" -> { () } } });
+        let cycle_b = parse_model(quote! { grammar b : a { rule y -> () = "y" -> { () } } });
+        let messages: Vec<String> = validate_set::<TestBackend>(&[
+            lexer.clone(),
+            lexer,
+            base,
+            broken,
+            orphan,
+            cycle_a,
+            cycle_b,
+        ])
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.to_string())
+        .collect();
+        assert_eq!(
+            messages,
+            [
+                "Duplicate grammar name: 'lexer'",
+                "In grammar 'child': Rule 'lex::hidden' is private; mark it `pub` to call it from another grammar",
+                "Grammar 'orphan' inherits from 'nowhere', which is not in the set",
+                "Inheritance cycle: a -> b -> a",
+                "Inheritance cycle: b -> a -> b",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_set_inherited_calls() {
+        let base = parse_model(quote! {
+            grammar base {
+                pub rule main -> () = "a" -> { () }
+            }
+        });
+        let child = parse_model(quote! {
+            grammar child : base {
+                pub rule other -> () = main missing v:super -> { () }
+            }
+        });
+        let messages: Vec<String> = validate_set::<TestBackend>(&[base, child])
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "In grammar 'child': Rule 'other' calls `super`, but no parent grammar defines 'other'",
+                "In grammar 'child': Undefined rule: 'missing'",
+            ]
+        );
     }
 }
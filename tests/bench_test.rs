@@ -0,0 +1,48 @@
+// Runs with `cargo test --features bench`.
+use std::time::Duration;
+use syn_grammar::grammar;
+use syn_grammar::rt::criterion::Criterion;
+
+grammar! {
+    grammar calc {
+        #[bench_input = "1 + 2 * 3"]
+        #[bench_input = "(1 + 2) * (3 + 4) * 5 + 6"]
+        pub rule expr -> i64 =
+            l:expr "+" r:term -> { l + r }
+          | t:term -> { t }
+
+        rule term -> i64 =
+            l:term "*" r:factor -> { l * r }
+          | f:factor -> { f }
+
+        rule factor -> i64 =
+            paren(e:expr) -> { e }
+          | i:i64 -> { i }
+    }
+}
+
+grammar! {
+    grammar broken {
+        #[bench_input = "1 2"]
+        pub rule pair -> i64 = a:i64 "," b:i64 -> { a + b }
+    }
+}
+
+fn quick() -> Criterion {
+    Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(1))
+        .measurement_time(Duration::from_millis(10))
+        .without_plots()
+}
+
+#[test]
+fn test_generated_benches() {
+    calc::benches::bench(&mut quick());
+}
+
+#[test]
+#[should_panic(expected = "bench input 0 of `broken::pair` does not parse")]
+fn test_bench_input_must_parse() {
+    broken::benches::bench(&mut quick());
+}
@@ -65,7 +65,7 @@ fn test_repetition() {
     repetition_test::parse_star
         .parse_str("")
         .test()
-        .assert_success_is(vec![]);
+        .assert_success_is(Vec::<i32>::new());
 
     repetition_test::parse_plus
         .parse_str("1 2")
@@ -88,6 +88,62 @@ fn test_repetition() {
         .assert_success_is(None);
 }
 
+#[test]
+fn test_bounded_repetition() {
+    grammar! {
+        grammar bounded_test {
+            pub rule range -> Vec<i32> = n:number{2,4} -> { n }
+            pub rule exact -> Vec<String> = words:ident{3} -> {
+                words.into_iter().map(|w| w.to_string()).collect()
+            }
+            pub rule at_least -> usize = ("x"){2,} -> { 0 }
+            pub rule pairs -> (Vec<i32>, Vec<i32>) = (a:i32 "=" b:i32){0,2} ";" -> { (a, b) }
+            rule number -> i32 = n:i32 -> { n }
+        }
+    }
+
+    bounded_test::parse_range
+        .parse_str("1 2 3")
+        .test()
+        .assert_success_is(vec![1, 2, 3]);
+    // Stops after the maximum
+    bounded_test::parse_range
+        .parse_str("1 2 3 4 5")
+        .test()
+        .assert_failure_contains("unexpected token");
+    bounded_test::parse_range
+        .parse_str("1")
+        .test()
+        .assert_failure_contains("expected between 2 and 4 numbers");
+
+    bounded_test::parse_exact
+        .parse_str("a b c")
+        .test()
+        .assert_success_is(vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+    bounded_test::parse_exact
+        .parse_str("a b")
+        .test()
+        .assert_failure_contains("expected 3 idents");
+
+    bounded_test::parse_at_least
+        .parse_str("x x x x")
+        .test()
+        .assert_success();
+    bounded_test::parse_at_least
+        .parse_str("x")
+        .test()
+        .assert_failure_contains("expected at least 2 items");
+
+    bounded_test::parse_pairs
+        .parse_str("1 = 2 3 = 4;")
+        .test()
+        .assert_success_is((vec![1, 3], vec![2, 4]));
+    bounded_test::parse_pairs
+        .parse_str(";")
+        .test()
+        .assert_success_is((vec![], vec![]));
+}
+
 // --- Test Nested Repetition & Complex Types ---
 #[test]
 fn test_nested_repetition_complex() {
@@ -181,6 +237,123 @@ fn test_left_recursion() {
         .assert_success_is(-4);
 }
 
+// --- Test Indirect Left Recursion ---
+#[test]
+fn test_indirect_left_recursion() {
+    grammar! {
+        grammar indirect_test {
+            pub rule expr -> i32 =
+                b:binary -> { b }
+              | t:term -> { t }
+
+            // `expr -> binary -> expr` is rewritten into direct left recursion of `expr`
+            rule binary -> i32 =
+                l:expr "-" r:term -> { l - r }
+              | l:expr "*" r:term -> { l * r }
+
+            rule term -> i32 =
+                i:i32 -> { i }
+              | paren(e:expr) -> { e }
+        }
+    }
+
+    // Left associative: (10 - 2) - 3
+    indirect_test::parse_expr
+        .parse_str("10 - 2 - 3")
+        .test()
+        .assert_success_is(5);
+
+    indirect_test::parse_expr
+        .parse_str("2 * (10 - 2 - 4)")
+        .test()
+        .assert_success_is(8);
+}
+
+// --- Test Operator Precedence ---
+#[test]
+fn test_precedence() {
+    grammar! {
+        grammar prec_test {
+            pub rule expr -> i64 = precedence(atom) {
+                prefix "not" => |_, x| { (x == 0) as i64 };
+                left "==" "<" => |l, op, r| { (if op == "==" { l == r } else { l < r }) as i64 };
+                left "+" "-" => |l, op, r| { if op == "+" { l + r } else { l - r } };
+                left "*" "/" => |l, op, r| { if op == "*" { l * r } else { l / r } };
+                right "^" => |l, _, r| { l.pow(r as u32) };
+                prefix "-" => |_, x| { -x };
+                postfix "!" => |x, _| { (1..=x).product() };
+            }
+
+            rule atom -> i64 =
+                i:i64 -> { i }
+              | paren(e:expr) -> { e }
+        }
+    }
+
+    let cases = [
+        ("1 + 2 * 3", 7),
+        ("10 - 2 - 3", 5),
+        ("2 ^ 3 ^ 2", 512),
+        ("-2 ^ 2", 4),
+        ("2 * -3", -6),
+        ("3! + 1", 7),
+        ("(1 + 2) * 3", 9),
+        ("1 + 1 == 2", 1),
+        ("not 1 < 2", 0),
+        ("1 == not 0", 1),
+        ("2 * 3 < 7", 1),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(
+            prec_test::parse_expr.parse_str(input).unwrap(),
+            expected,
+            "{}",
+            input
+        );
+    }
+
+    prec_test::parse_expr
+        .parse_str("1 +")
+        .test()
+        .assert_failure();
+}
+
+// --- Test Grammar Info ---
+#[test]
+fn test_grammar_info() {
+    grammar! {
+        grammar info_test {
+            pub rule expr -> i32 =
+                b:binary -> { b }
+              | s:sign? i:i32 -> { i }
+
+            rule binary -> i32 = l:expr "+" r:i32 -> { l + r }
+
+            rule sign -> () = "-" -> { () }
+
+            rule leftover -> () = "x" -> { () }
+        }
+    }
+
+    assert_eq!(info_test::grammar_info::NULLABLE_RULES, &[] as &[&str]);
+    assert_eq!(info_test::grammar_info::UNUSED_RULES, ["leftover"]);
+    // Reported for the rules as written, before the cycle is rewritten
+    assert_eq!(
+        info_test::grammar_info::LEFT_RECURSIVE_RULES,
+        ["binary", "expr"]
+    );
+
+    grammar! {
+        grammar nullable_info {
+            pub rule list -> () = item ";" -> { () }
+            rule item -> () = "x" -> { () } | opt -> { () }
+            rule opt -> () = "y"? -> { () }
+        }
+    }
+    assert_eq!(nullable_info::grammar_info::NULLABLE_RULES, ["item", "opt"]);
+    assert!(nullable_info::grammar_info::UNUSED_RULES.is_empty());
+}
+
 // --- Test Keywords vs Idents ---
 #[test]
 fn test_keywords_vs_idents() {
@@ -261,8 +434,8 @@ fn test_rule_arguments() {
 fn test_multiple_arguments() {
     grammar! {
         grammar multi_args {
-            pub rule main -> i32 = "calc" v:calc(2, 3) -> { v }
-            rule calc(mult: i32, base: i32) -> i32 = i:i32 -> { base + (i * mult) }
+            pub rule main -> i32 = "calc" v:scaled(2, 3) -> { v }
+            rule scaled(mult: i32, base: i32) -> i32 = i:i32 -> { base + (i * mult) }
         }
     }
 
@@ -319,6 +492,60 @@ fn test_cut_in_repetition() {
         .assert_failure_contains("expected integer");
 }
 
+// --- Test Committed Patterns ---
+#[test]
+fn test_committed_pattern() {
+    grammar! {
+        grammar commit {
+            pub rule main -> i32 =
+                // Once "+" matched, the integer is required
+                "+" i:i32! -> { i }
+              | "+" "+" i:i32 -> { i * 2 }
+              | i:i32 -> { -i }
+        }
+    }
+
+    commit::parse_main
+        .parse_str("+ 1")
+        .test()
+        .assert_success_is(1);
+
+    // Failures before the committed pattern still backtrack
+    commit::parse_main
+        .parse_str("1")
+        .test()
+        .assert_success_is(-1);
+
+    // The second arm is never tried
+    commit::parse_main
+        .parse_str("+ + 1")
+        .test()
+        .assert_failure_contains("expected integer");
+}
+
+// --- Test Local Patterns ---
+#[test]
+fn test_local_patterns() {
+    grammar! {
+        grammar locals {
+            pub rule item -> (String, usize, Option<i32>) =
+                let name = ident;
+                let sig = n:name paren(params:separated(name, ","));
+                "fn" sig ";" -> { (n.to_string(), params.len(), None) }
+              | "fn" sig "=" v:i32 ";" -> { (n.to_string(), params.len(), Some(v)) }
+        }
+    }
+
+    locals::parse_item
+        .parse_str("fn f(a, b);")
+        .test()
+        .assert_success_is(("f".to_string(), 2, None));
+    locals::parse_item
+        .parse_str("fn g() = 1;")
+        .test()
+        .assert_success_is(("g".to_string(), 0, Some(1)));
+}
+
 // --- Test Backtracking Priority ---
 #[test]
 fn test_backtracking_priority() {
@@ -401,6 +628,15 @@ fn test_multi_token_literals() {
         .parse_str("? .")
         .test()
         .assert_failure_contains("expected '?.', found space between tokens");
+
+    // A mismatch on the second token names the whole literal and spans what was found
+    let err = multi_tok::parse_main
+        .parse_str("?x")
+        .test()
+        .assert_failure();
+    assert!(err.to_string().contains("expected `?.`"), "{}", err);
+    let span = err.span();
+    assert_eq!((span.start().column, span.end().column), (0, 2));
 }
 
 // --- Test Extended Literals ---
@@ -583,3 +819,1822 @@ fn test_gap_detection() {
         .test()
         .assert_success_is("RuleB, Group".to_string());
 }
+
+// --- Test Built-in Peek Hints in Repetitions ---
+#[test]
+fn test_builtin_repetition_peek() {
+    grammar! {
+        grammar builtin_peek {
+            pub rule main -> (Vec<String>, Vec<i32>) =
+                names:ident* nums:i32* ";" -> {
+                    (names.into_iter().map(|n| n.text).collect(), nums)
+                }
+        }
+    }
+
+    builtin_peek::parse_main
+        .parse_str("a b 1 2 ;")
+        .test()
+        .assert_success_is((vec!["a".to_string(), "b".to_string()], vec![1, 2]));
+
+    builtin_peek::parse_main
+        .parse_str(";")
+        .test()
+        .assert_success_is((vec![], vec![]));
+
+    // Loops over built-ins stop at the first token of another kind, which is then reported as
+    // the token after the lists rather than as a failed item.
+    let err = builtin_peek::parse_main
+        .parse_str("a 1 x")
+        .test()
+        .assert_failure();
+    assert_eq!(err.to_string(), "Error in rule 'main': expected `;`");
+    assert_eq!(err.span().start().column, 4);
+
+    // A float is not an `i32` token, and an integer ends the identifiers
+    builtin_peek::parse_main
+        .parse_str("a 1.5 ;")
+        .test()
+        .assert_failure_contains("expected `;`");
+    builtin_peek::parse_main
+        .parse_str("1 a ;")
+        .test()
+        .assert_failure_contains("expected `;`");
+}
+
+#[test]
+fn test_builtin_peek_does_not_commit() {
+    grammar! {
+        grammar undecided_peek {
+            pub rule pairs -> (Vec<String>, String) =
+                (k:ident "=" v:ident)* last:ident -> {
+                    let pairs = k.iter().zip(&v).map(|(k, v)| format!("{}={}", k, v));
+                    (pairs.collect(), last.text)
+                }
+
+            pub rule bytes -> (Vec<u8>, i64) =
+                xs:u8* big:i64 -> { (xs, big) }
+        }
+    }
+
+    // An identifier starts a pair, but the loop ends where the pair does not match
+    undecided_peek::parse_pairs
+        .parse_str("a = b c")
+        .test()
+        .assert_success_is((vec!["a=b".to_string()], "c".to_string()));
+
+    // An integer literal that does not fit in `u8` ends the loop instead of failing it
+    undecided_peek::parse_bytes
+        .parse_str("1 2 300")
+        .test()
+        .assert_success_is((vec![1, 2], 300));
+}
+
+#[test]
+fn test_rule_repetition_first_set_peek() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use syn_grammar::rt;
+
+    grammar! {
+        grammar first_set_peek {
+            pub rule main -> (Vec<i32>, Option<i32>) =
+                items:item* last:tail? ";" -> { (items, last) }
+
+            pub rule pairs -> Vec<i32> =
+                ps:pair* "+" "-" -> { ps }
+
+            rule item -> i32 =
+                "+" v:i32 -> { v }
+              | "-" v:i32 -> { -v }
+
+            rule tail -> i32 = "*" v:i32 -> { v }
+
+            // Both alternatives start with `+`, so neither commits after the peek
+            rule pair -> i32 = "+" v:i32 -> { v } | "+" "*" -> { 0 }
+        }
+    }
+
+    first_set_peek::parse_main
+        .parse_str("+ 1 - 2 * 3 ;")
+        .test()
+        .assert_success_is((vec![1, -2], Some(3)));
+
+    first_set_peek::parse_main
+        .parse_str(";")
+        .test()
+        .assert_success_is((vec![], None));
+
+    // Calls to rules with small FIRST sets are guarded by peeks, so `item` and `tail` are not
+    // entered at tokens that cannot start them.
+    #[derive(Default)]
+    struct Calls(Vec<String>);
+
+    impl rt::Listener for Calls {
+        fn on_enter(&mut self, rule: &str, _span: proc_macro2::Span) {
+            self.0.push(format!("enter {}", rule));
+        }
+
+        fn on_error(&mut self, rule: &str, _error: &syn::Error) {
+            self.0.push(format!("error {}", rule));
+        }
+    }
+
+    let calls = Rc::new(RefCell::new(Calls::default()));
+    rt::with_listener(&calls, || first_set_peek::parse_main.parse_str("+ 1 * 2 ;")).unwrap();
+    assert_eq!(calls.borrow().0, ["enter main", "enter item", "enter tail"]);
+
+    // `"+"` may also follow `pair*`, so the loop attempts `pair` at the last `+` and backtracks.
+    calls.borrow_mut().0.clear();
+    let pairs = rt::with_listener(&calls, || first_set_peek::parse_pairs.parse_str("+ 1 + -"));
+    assert_eq!(pairs.unwrap(), [1]);
+    assert_eq!(
+        calls.borrow().0,
+        ["enter pairs", "enter pair", "enter pair", "error pair"]
+    );
+}
+
+#[test]
+fn test_optional_group_multiple_bindings() {
+    grammar! {
+        grammar opt_group {
+            pub rule main -> (Option<String>, Option<i32>) =
+                "let" (name:ident "=" value:i32)? ";" -> {
+                    (name.map(|n| n.text), value)
+                }
+        }
+    }
+
+    opt_group::parse_main
+        .parse_str("let x = 1 ;")
+        .test()
+        .assert_success_is((Some("x".to_string()), Some(1)));
+
+    opt_group::parse_main
+        .parse_str("let ;")
+        .test()
+        .assert_success_is((None, None));
+
+    // The group matches as a whole: a name without a value is not taken as `(Some, None)`, and
+    // the error points into the group where it failed.
+    opt_group::parse_main
+        .parse_str("let x ;")
+        .test()
+        .assert_failure_contains("expected `=`");
+    opt_group::parse_main
+        .parse_str("let x = ;")
+        .test()
+        .assert_failure_contains("expected integer literal");
+}
+
+#[test]
+fn test_mut_and_annotated_bindings() {
+    grammar! {
+        grammar annotated {
+            pub rule sorted -> Vec<i32> =
+                xs:mut i32* ";" -> { xs.sort(); xs }
+
+            pub rule count -> usize =
+                xs:separated<Vec>(i32, ","): Vec<i32> -> { xs.len() }
+
+            pub rule incremented -> i32 =
+                n:mut i32: i32 -> { n += 1; n }
+        }
+    }
+
+    annotated::parse_sorted
+        .parse_str("3 1 2 ;")
+        .test()
+        .assert_success_is(vec![1, 2, 3]);
+
+    annotated::parse_count
+        .parse_str("")
+        .test()
+        .assert_success_is(0);
+
+    annotated::parse_count
+        .parse_str("1, 2")
+        .test()
+        .assert_success_is(2);
+
+    annotated::parse_incremented
+        .parse_str("41")
+        .test()
+        .assert_success_is(42);
+}
+
+#[test]
+fn test_lossless_rule() {
+    grammar! {
+        grammar lossless {
+            pub rule list -> Vec<(i32, String)> =
+                items:separated(item, ",") -> {
+                    items.into_iter().map(|(v, tokens)| (v, tokens.to_string())).collect()
+                }
+
+            #[lossless]
+            pub rule item -> i32 =
+                "-" v:i32 -> { -v }
+              | paren(v:i32) -> { v }
+              | v:i32 -> { v }
+        }
+    }
+
+    lossless::parse_list
+        .parse_str("1, - 2, (3)")
+        .test()
+        .assert_success_is(vec![
+            (1, "1".to_string()),
+            (-2, "- 2".to_string()),
+            (3, "(3)".to_string()),
+        ]);
+}
+
+#[test]
+fn test_parsed_rule() {
+    grammar! {
+        grammar parsed {
+            // (value, consumed tokens, end column)
+            pub rule list -> Vec<(i32, usize, usize)> =
+                items:separated(item, ",") -> {
+                    items
+                        .into_iter()
+                        .map(|p| (p.value, p.consumed, p.end_span.end().column))
+                        .collect()
+                }
+
+            #[parsed]
+            pub rule item -> i32 =
+                "-" v:i32 -> { -v }
+              | paren(v:i32) -> { v }
+              | v:i32 -> { v }
+        }
+    }
+
+    parsed::parse_list
+        .parse_str("1, - 2, (3)")
+        .test()
+        .assert_success_is(vec![(1, 1, 1), (-2, 2, 6), (3, 1, 11)]);
+}
+
+#[test]
+fn test_raw_capture() {
+    grammar! {
+        grammar raw_capture {
+            // Validates `name = value` but re-emits the original tokens of the value
+            pub rule field -> (String, String, String) =
+                name:ident "=" value@("-"? v:i32) -> {
+                    (name.to_string(), value.to_string(), v.to_string())
+                }
+
+            pub rule op -> String = op@("+" | "-" | paren("*")) -> { op.to_string() }
+
+            pub rule fields -> Vec<String> =
+                fs@(field ";")* -> { fs.iter().map(|f| f.to_string()).collect() }
+        }
+    }
+
+    raw_capture::parse_field
+        .parse_str("x = - 1")
+        .test()
+        .assert_success_is(("x".to_string(), "- 1".to_string(), "1".to_string()));
+    raw_capture::parse_field
+        .parse_str("y = 2")
+        .test()
+        .assert_success_is(("y".to_string(), "2".to_string(), "2".to_string()));
+    raw_capture::parse_op
+        .parse_str("(*)")
+        .test()
+        .assert_success_is("(*)".to_string());
+    raw_capture::parse_fields
+        .parse_str("a = 1; b = 2;")
+        .test()
+        .assert_success_is(vec!["a = 1 ;".to_string(), "b = 2 ;".to_string()]);
+}
+
+#[test]
+fn test_complete_entry_rule() {
+    grammar! {
+        grammar complete {
+            #[complete]
+            pub rule nums -> Vec<i32> = n:i32* -> { n }
+
+            #[complete("a pair")]
+            pub rule pair -> (i32, i32) = a:i32 b:i32 -> { (a, b) }
+
+            // Calls from other rules do not require the end of input
+            pub rule pairs -> Vec<(i32, i32)> = ps:separated(pair, ";") -> { ps }
+        }
+    }
+
+    complete::parse_nums
+        .parse_str("1 2")
+        .test()
+        .assert_success_is(vec![1, 2]);
+
+    complete::parse_nums
+        .parse_str("1 2 x")
+        .test()
+        .assert_failure_contains("expected end of `nums` input, found `x` (1 token remaining)");
+
+    complete::parse_pair
+        .parse_str("1 2 3 4")
+        .test()
+        .assert_failure_contains("expected end of a pair, found `3 4` (2 tokens remaining)");
+
+    complete::parse_pairs
+        .parse_str("1 2; 3 4")
+        .test()
+        .assert_success_is(vec![(1, 2), (3, 4)]);
+}
+
+#[test]
+fn test_grammar_options() {
+    grammar! {
+        grammar with_options {
+            options {
+                entry = program;
+                visibility = pub;
+            }
+
+            rule program -> Vec<i32> = items:item* -> { items }
+
+            rule item -> i32 = n:i32 -> { n }
+        }
+    }
+
+    // The entry rule is public and complete, the other rules are public
+    with_options::parse_program
+        .parse_str("1 2")
+        .test()
+        .assert_success_is(vec![1, 2]);
+    with_options::parse_program
+        .parse_str("1 2 x")
+        .test()
+        .assert_failure_contains("expected one of: i32");
+    with_options::parse_item
+        .parse_str("3")
+        .test()
+        .assert_success_is(3);
+}
+
+#[test]
+fn test_declared_keywords() {
+    grammar! {
+        grammar declared_kw {
+            keyword "union";
+            keyword "record";
+
+            pub rule item -> String =
+                "union" name:ident -> { format!("union {}", name) }
+              | "default" name:ident -> { format!("default {}", name) }
+              | "macro_rules" name:ident -> { format!("macro {}", name) }
+        }
+    }
+
+    // Declared keywords get a `kw` type even if no literal uses them
+    let _: declared_kw::kw::record = Default::default();
+
+    declared_kw::parse_item
+        .parse_str("union Foo")
+        .test()
+        .assert_success_is("union Foo".to_string());
+    declared_kw::parse_item
+        .parse_str("default Foo")
+        .test()
+        .assert_success_is("default Foo".to_string());
+    declared_kw::parse_item
+        .parse_str("macro_rules foo")
+        .test()
+        .assert_success_is("macro foo".to_string());
+}
+
+#[test]
+fn test_reserved_keywords() {
+    grammar! {
+        grammar scoped_kw {
+            keywords { select }
+            keywords(query) { from, limit }
+
+            // Without reserving `from`, `ident+` would consume it
+            pub rule query -> (Vec<String>, String, Option<i32>) =
+                "select" cols:ident+ "from" t:ident n:limit_clause? -> {
+                    (cols.iter().map(|c| c.to_string()).collect(), t.to_string(), n)
+                }
+
+            rule limit_clause -> i32 = "limit" n:i32 -> { n }
+
+            pub rule pair -> (String, String) = a:ident b:ident -> { (a.to_string(), b.to_string()) }
+        }
+    }
+
+    scoped_kw::parse_query
+        .parse_str("select a b from t limit 5")
+        .test()
+        .assert_success_is((
+            vec!["a".to_string(), "b".to_string()],
+            "t".to_string(),
+            Some(5),
+        ));
+    scoped_kw::parse_query
+        .parse_str("select a from limit")
+        .test()
+        .assert_failure_contains("expected identifier, found keyword `limit`");
+
+    // `from` and `limit` are identifiers outside of `query`, `select` is reserved everywhere
+    scoped_kw::parse_pair
+        .parse_str("from limit")
+        .test()
+        .assert_success_is(("from".to_string(), "limit".to_string()));
+    scoped_kw::parse_pair
+        .parse_str("a select")
+        .test()
+        .assert_failure_contains("expected identifier, found keyword `select`");
+}
+
+#[test]
+fn test_keyword_builtin() {
+    grammar! {
+        grammar kw_builtin {
+            // `keyword` also matches Rust keywords such as `match`
+            pub rule stmt -> (String, String) =
+                kw:keyword("match") v:ident -> { (kw.to_string(), v.to_string()) }
+              | keyword("print") v:ident -> { ("print".to_string(), v.to_string()) }
+
+            // The words of `keyword(..)` are reserved in all rules, so `ident*` stops at `end`
+            pub rule names -> Vec<String> = n:ident* keyword("end") -> {
+                n.iter().map(|i| i.to_string()).collect()
+            }
+        }
+    }
+
+    kw_builtin::parse_stmt
+        .parse_str("match x")
+        .test()
+        .assert_success_is(("match".to_string(), "x".to_string()));
+    kw_builtin::parse_stmt
+        .parse_str("print y")
+        .test()
+        .assert_success_is(("print".to_string(), "y".to_string()));
+    kw_builtin::parse_stmt
+        .parse_str("print end")
+        .test()
+        .assert_failure_contains("expected identifier, found keyword `end`");
+
+    kw_builtin::parse_names
+        .parse_str("a b end")
+        .test()
+        .assert_success_is(vec!["a".to_string(), "b".to_string()]);
+    kw_builtin::parse_names
+        .parse_str("a b")
+        .test()
+        .assert_failure_contains("expected `end`");
+}
+
+#[test]
+fn test_case_insensitive_literals() {
+    grammar! {
+        grammar ci_sql {
+            pub rule query -> (String, Vec<String>, String) =
+                kw:"select"i cols:col+ "from"i t:ident -> {
+                    (kw.to_string(), cols, t.to_string())
+                }
+
+            rule col -> String = not("from"i) c:ident -> { c.to_string() }
+
+            pub rule order -> bool =
+                "asc"i -> { true }
+              | "desc"i -> { false }
+        }
+    }
+
+    // The binding holds the identifier as written
+    ci_sql::parse_query
+        .parse_str("SELECT a b FROM t")
+        .test()
+        .assert_success_is((
+            "SELECT".to_string(),
+            vec!["a".to_string(), "b".to_string()],
+            "t".to_string(),
+        ));
+    ci_sql::parse_query
+        .parse_str("select a From t")
+        .test()
+        .assert_success();
+    ci_sql::parse_query
+        .parse_str("selects a from t")
+        .test()
+        .assert_failure_contains("expected `select`");
+
+    ci_sql::parse_order
+        .parse_str("Desc")
+        .test()
+        .assert_success_is(false);
+    ci_sql::parse_order
+        .parse_str("ASC")
+        .test()
+        .assert_success_is(true);
+}
+
+// --- Test Struct Actions ---
+#[derive(Debug, PartialEq)]
+pub struct Setting {
+    pub name: String,
+    pub value: i32,
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Pair(pub i32, pub i32);
+
+#[test]
+fn test_struct_actions() {
+    grammar! {
+        grammar struct_action {
+            pub rule setting -> Setting =
+                "set" n:ident "=" value:i32 ("+" flags:flag)* -> Setting { name: n.to_string(), .. }
+              | "default" name:string -> Setting { name: name.value, value: 0, flags: vec![] }
+
+            pub rule pair -> Pair = a:i32 b:i32 -> Pair { 0: a, 1: b }
+
+            rule flag -> String = f:ident -> { f.to_string() }
+        }
+    }
+
+    struct_action::parse_setting
+        .parse_str("set width = 3 + a + b")
+        .test()
+        .assert_success_is(Setting {
+            name: "width".to_string(),
+            value: 3,
+            flags: vec!["a".to_string(), "b".to_string()],
+        });
+    struct_action::parse_setting
+        .parse_str("default \"x\"")
+        .test()
+        .assert_success_is(Setting {
+            name: "x".to_string(),
+            value: 0,
+            flags: vec![],
+        });
+    struct_action::parse_pair
+        .parse_str("1 2")
+        .test()
+        .assert_success_is(Pair(1, 2));
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub name: String,
+    pub width: i32,
+    pub verbose: bool,
+    pub tags: Vec<String>,
+}
+
+#[test]
+fn test_spread_struct_actions() {
+    grammar! {
+        grammar spread_action {
+            pub rule options -> Options =
+                "sized" name:string_value width:i32 ->* Options
+              | "quiet" name:string_value _ignored:i32? ->* Options { verbose: false }
+              | "loud" name:string_value ->* Options { verbose: true, tags: vec!["loud".into()] }
+
+            rule string_value -> String = s:string -> { s.value }
+        }
+    }
+
+    spread_action::parse_options
+        .parse_str("sized \"a\" 80")
+        .test()
+        .assert_success_is(Options {
+            name: "a".to_string(),
+            width: 80,
+            ..Default::default()
+        });
+    spread_action::parse_options
+        .parse_str("quiet \"b\" 1")
+        .test()
+        .assert_success_is(Options {
+            name: "b".to_string(),
+            ..Default::default()
+        });
+    spread_action::parse_options
+        .parse_str("loud \"c\"")
+        .test()
+        .assert_success_is(Options {
+            name: "c".to_string(),
+            verbose: true,
+            tags: vec!["loud".to_string()],
+            ..Default::default()
+        });
+}
+
+// --- Test Common Prefix Hoisting ---
+thread_local! {
+    static PATH_PARSES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[test]
+fn test_common_prefix_hoisting() {
+    grammar! {
+        grammar hoisted {
+            pub rule expr -> String =
+                p:path "." f:ident -> { format!("field {}.{}", p, f) }
+              | p:path paren() -> { format!("call {}", p) }
+              | path "!" -> { "macro".to_string() }
+              | p:path -> { format!("path {}", p) }
+              | i:i32 -> { format!("int {}", i) }
+
+            pub rule member -> String =
+                p:path "." f:ident -> { format!("{}.{}", p, f) }
+              | p:path "." "await" -> { format!("{}.await", p) }
+
+            rule path -> String =
+                first:ident ("::" rest:ident)* -> {
+                    PATH_PARSES.with(|n| n.set(n.get() + 1));
+                    std::iter::once(first).chain(rest).map(|s| s.to_string()).collect::<Vec<_>>().join("::")
+                }
+        }
+    }
+
+    let parse = |input: &str| {
+        PATH_PARSES.with(|n| n.set(0));
+        let res = hoisted::parse_expr.parse_str(input);
+        (
+            res.map_err(|e| e.to_string()),
+            PATH_PARSES.with(|n| n.get()),
+        )
+    };
+
+    // The shared `path` is parsed once, whichever alternative matches
+    assert_eq!(parse("a::b . c"), (Ok("field a::b.c".to_string()), 1));
+    assert_eq!(parse("a::b ()"), (Ok("call a::b".to_string()), 1));
+    assert_eq!(parse("a !"), (Ok("macro".to_string()), 1));
+    assert_eq!(parse("a::b"), (Ok("path a::b".to_string()), 1));
+    assert_eq!(parse("42"), (Ok("int 42".to_string()), 0));
+
+    // Errors after the prefix are reported as without hoisting
+    hoisted::parse_member
+        .parse_str("a::b . 1")
+        .test()
+        .assert_failure_contains("expected identifier");
+}
+
+#[derive(Debug)]
+struct Reserved;
+
+impl std::fmt::Display for Reserved {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "reserved path")
+    }
+}
+
+#[test]
+fn test_common_prefix_with_failing_actions() {
+    grammar! {
+        grammar guarded_prefix {
+            pub rule member -> String =
+                p:path "." where { p != "a" } -> { format!("member {}", p) }
+              | p:path "." -> { format!("fallback {}", p) }
+
+            pub rule access -> String throws Reserved =
+                p:path "." ","? -> { if p == "a" { return Err(Reserved); } format!("access {}", p) }
+              | p:path "." -> { format!("fallback {}", p) }
+
+            rule path -> String = i:ident -> { i.to_string() }
+        }
+    }
+
+    // The second alternative gets the shared prefix after the first one failed after it
+    guarded_prefix::parse_member
+        .parse_str("b .")
+        .test()
+        .assert_success_is("member b".to_string());
+    guarded_prefix::parse_member
+        .parse_str("a .")
+        .test()
+        .assert_success_is("fallback a".to_string());
+    guarded_prefix::parse_access
+        .parse_str("b .")
+        .test()
+        .assert_success_is("access b".to_string());
+    guarded_prefix::parse_access
+        .parse_str("a .")
+        .test()
+        .assert_success_is("fallback a".to_string());
+}
+
+#[test]
+fn test_no_backtrack() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use syn_grammar::rt;
+
+    grammar! {
+        grammar committed {
+            #[no_backtrack]
+            pub rule stmt -> String =
+                "let" n:ident "=" v:i32 -> { format!("let {} = {}", n, v) }
+              | "print" v:value -> { format!("print {}", v) }
+              | v:value -> { v.to_string() }
+
+            #[no_backtrack]
+            rule value -> i32 =
+                i:i32 -> { i }
+              | "-" i:i32 -> { -i }
+        }
+    }
+
+    committed::parse_stmt
+        .parse_str("let x = 1")
+        .test()
+        .assert_success_is("let x = 1");
+    committed::parse_stmt
+        .parse_str("print -2")
+        .test()
+        .assert_success_is("print -2");
+    committed::parse_stmt
+        .parse_str("3")
+        .test()
+        .assert_success_is("3");
+
+    // Errors come from the chosen alternative, without trying the others
+    committed::parse_stmt
+        .parse_str("let 1")
+        .test()
+        .assert_failure_contains("expected identifier");
+    committed::parse_stmt
+        .parse_str("print x")
+        .test()
+        .assert_failure_contains("expected one of: i32, `-`");
+
+    // No alternative is tried speculatively: a token that starts none of them fails without
+    // entering `value`, listing what each alternative starts with
+    #[derive(Default)]
+    struct Calls(Vec<String>);
+
+    impl rt::Listener for Calls {
+        fn on_enter(&mut self, rule: &str, _span: proc_macro2::Span) {
+            self.0.push(format!("enter {}", rule));
+        }
+
+        fn on_error(&mut self, rule: &str, _error: &syn::Error) {
+            self.0.push(format!("error {}", rule));
+        }
+    }
+
+    let calls = Rc::new(RefCell::new(Calls::default()));
+    let err = rt::with_listener(&calls, || committed::parse_stmt.parse_str("x")).unwrap_err();
+    assert_eq!(err.to_string(), "expected one of: `let`, `print`, `-`, i32");
+    assert_eq!(calls.borrow().0, ["enter stmt", "error stmt"]);
+}
+
+#[test]
+fn test_variant_attributes() {
+    grammar! {
+        grammar variant_attrs {
+            pub rule value -> i32 =
+                i:i32 -> { i }
+              | #[cold] "-" i:i32 -> { -i }
+              | #[cfg(any())] "+" i:i32 -> { i }
+              | #[allow(unused_mut)] "~" i:i32 -> { let mut v = !i; v }
+
+            pub rule sum -> i32 =
+                l:sum "+" r:value -> { l + r }
+              | #[cold] l:sum "-" r:value -> { l - r }
+              | v:value -> { v }
+        }
+    }
+
+    variant_attrs::parse_value
+        .parse_str("1")
+        .test()
+        .assert_success_is(1);
+    variant_attrs::parse_value
+        .parse_str("- 2")
+        .test()
+        .assert_success_is(-2);
+    variant_attrs::parse_value
+        .parse_str("~ 0")
+        .test()
+        .assert_success_is(-1);
+    assert!(variant_attrs::parse_value.parse_str("+ 3").is_err());
+    variant_attrs::parse_sum
+        .parse_str("1 + 2 - 4")
+        .test()
+        .assert_success_is(-1);
+    // A cold alternative still reports its errors; `#[allow(unused_mut)]` is checked by
+    // building the tests with warnings denied
+    variant_attrs::parse_value
+        .parse_str("- x")
+        .test()
+        .assert_failure_contains("expected integer literal");
+}
+
+#[test]
+fn test_keyword_table() {
+    grammar! {
+        grammar statements {
+            pub rule stmt -> String =
+                "let" n:ident "=" v:i32 -> { format!("let {} {}", n, v) }
+              | "const" n:ident -> { format!("const {}", n) }
+              | "static" n:ident -> { format!("static {}", n) }
+              | "fn" n:ident -> { format!("fn {}", n) }
+              | "use" n:ident -> { format!("use {}", n) }
+              | "print" v:i32 -> { format!("print {}", v) }
+              | "return" v:i32 -> { format!("return {}", v) }
+              | "loop" -> { "loop".to_string() }
+              | "break" -> { "break".to_string() }
+              | n:ident -> { format!("expr {}", n) }
+        }
+    }
+
+    let parse = |input: &str| {
+        statements::parse_stmt
+            .parse_str(input)
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(parse("let x = 1"), Ok("let x 1".to_string()));
+    assert_eq!(parse("print 2"), Ok("print 2".to_string()));
+    assert_eq!(parse("break"), Ok("break".to_string()));
+    assert_eq!(parse("foo"), Ok("expr foo".to_string()));
+    // Keywords are matched against the whole identifier
+    assert_eq!(parse("printx"), Ok("expr printx".to_string()));
+
+    // The keyword commits to its alternative
+    statements::parse_stmt
+        .parse_str("let 1")
+        .test()
+        .assert_failure_contains("expected identifier");
+    statements::parse_stmt
+        .parse_str("print x")
+        .test()
+        .assert_failure_contains("expected integer literal");
+}
+
+#[test]
+fn test_keyword_alternation() {
+    grammar! {
+        grammar colors {
+            pub rule color -> &'static str = "red" -> { "red" } | "green" -> { "green" }
+
+            #[derive_enum]
+            pub rule shade -> Shade = "light" -> {} | "dark_gray" -> {} | "fn" -> {}
+
+            pub rule shades -> Vec<Shade> = s:shade* -> { s }
+        }
+    }
+
+    colors::parse_color
+        .parse_str("green")
+        .test()
+        .assert_success_is("green");
+    colors::parse_color
+        .parse_str("blue")
+        .test()
+        .assert_failure_contains("expected one of: `green`, `red`");
+    // A single dispatch on the identifier: other tokens get the same error, and the keyword
+    // is matched as a whole
+    colors::parse_color
+        .parse_str("1")
+        .test()
+        .assert_failure_contains("expected one of: `green`, `red`");
+    colors::parse_color
+        .parse_str("redx")
+        .test()
+        .assert_failure_contains("expected one of: `green`, `red`");
+
+    colors::parse_shades
+        .parse_str("light dark_gray fn")
+        .test()
+        .assert_success_is(vec![
+            colors::Shade::Light,
+            colors::Shade::DarkGray,
+            colors::Shade::Fn,
+        ]);
+    assert_eq!(colors::Shade::DarkGray.as_str(), "dark_gray");
+}
+
+#[test]
+fn test_commit_after_first() {
+    grammar! {
+        grammar commands {
+            #[commit_after_first]
+            pub rule cmd -> String =
+                "show" n:ident -> { format!("show {}", n) }
+              | set_kw n:ident "=" v:i32 -> { format!("set {} {}", n, v) }
+              | n:ident -> { format!("call {}", n) }
+
+            rule set_kw -> () = "set" -> { () }
+        }
+    }
+
+    commands::parse_cmd
+        .parse_str("set x = 1")
+        .test()
+        .assert_success_is("set x 1");
+    commands::parse_cmd
+        .parse_str("reset")
+        .test()
+        .assert_success_is("call reset");
+
+    // Without the cut after `set_kw`, `n:ident` would match "set" and leave "1" unparsed
+    commands::parse_cmd
+        .parse_str("set 1")
+        .test()
+        .assert_failure_contains("expected identifier");
+    commands::parse_cmd
+        .parse_str("show")
+        .test()
+        .assert_failure_contains("expected identifier");
+}
+
+// --- Test `super` in Child Grammars ---
+grammar! {
+    grammar base_values {
+        pub rule value -> i32 =
+            i:i32 -> { i }
+          | "zero" -> { 0 }
+
+        pub rule scaled(factor: i32) -> i32 = i:i32 -> { i * factor }
+    }
+}
+
+grammar! {
+    grammar extended_values : base_values {
+        // Adds alternatives before and after the parent's
+        pub rule value -> i32 =
+            "neg" v:super -> { -v }
+          | v:super -> { v }
+          | "max" -> { i32::MAX }
+
+        pub rule scaled(factor: i32) -> i32 =
+            "twice" v:super(factor) -> { 2 * v }
+          | v:super(factor) -> { v }
+    }
+}
+
+#[test]
+fn test_super_delegation() {
+    let parse = |input: &str| {
+        extended_values::parse_value
+            .parse_str(input)
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(parse("neg 3"), Ok(-3));
+    assert_eq!(parse("neg zero"), Ok(0));
+    assert_eq!(parse("7"), Ok(7));
+    assert_eq!(parse("max"), Ok(i32::MAX));
+    assert!(parse("neg max").is_err());
+
+    let scaled = |input: &str| {
+        (|input: syn::parse::ParseStream| extended_values::parse_scaled(input, 10))
+            .parse_str(input)
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(scaled("twice 2"), Ok(40));
+    assert_eq!(scaled("2"), Ok(20));
+}
+
+// --- Test Inheritance over Several Levels ---
+grammar! {
+    grammar scaled_values : extended_values {
+        // `value` and `scaled` come from both ancestors
+        pub rule sum -> i32 = a:value "+" b:scaled(3) -> { a + b }
+
+        pub rule value -> i32 = "one" -> { 1 } | v:super -> { v }
+    }
+}
+
+#[test]
+fn test_multi_level_inheritance() {
+    let parse = |input: &str| {
+        scaled_values::parse_sum
+            .parse_str(input)
+            .map_err(|e| e.to_string())
+    };
+    assert_eq!(parse("one + 2"), Ok(7));
+    assert_eq!(parse("neg zero + twice 1"), Ok(6));
+
+    // The rules of the ancestors are re-exported
+    let scaled = (|input: syn::parse::ParseStream| scaled_values::parse_scaled(input, 2))
+        .parse_str("twice 1")
+        .unwrap();
+    assert_eq!(scaled, 4);
+    assert_eq!(base_values::parse_value.parse_str("one").ok(), None);
+}
+
+// --- Test Rule Visibility ---
+mod visibility {
+    pub mod inner {
+        use syn_grammar::grammar;
+
+        grammar! {
+            grammar scoped_vis {
+                pub(crate) rule item -> i32 = n:i32 -> { n }
+                pub(super) rule pair -> (i32, i32) = a:item b:item -> { (a, b) }
+                rule hidden -> i32 = "h" -> { 0 }
+            }
+        }
+
+        // `pub(super)` is relative to the grammar module, so `parse_pair` is visible here
+        pub fn pair(input: &str) -> syn::Result<(i32, i32)> {
+            syn::parse::Parser::parse_str(scoped_vis::parse_pair, input)
+        }
+    }
+}
+
+#[test]
+fn test_rule_visibility() {
+    assert_eq!(visibility::inner::pair("1 2").unwrap(), (1, 2));
+    assert_eq!(
+        visibility::inner::scoped_vis::parse_item
+            .parse_str("3")
+            .unwrap(),
+        3
+    );
+}
+
+// --- Test Shared Token Pools ---
+mod dsl_tokens {
+    syn::custom_keyword!(select);
+    syn::custom_keyword!(from);
+}
+
+#[test]
+fn test_shared_token_pool() {
+    grammar! {
+        grammar query_a {
+            tokens from crate::dsl_tokens;
+
+            pub rule select_stmt -> kw::select = k:"select" "*" -> { k }
+        }
+    }
+
+    grammar! {
+        grammar query_b {
+            tokens from crate::dsl_tokens;
+
+            pub rule select_stmt -> (kw::select, String) =
+                k:"select" "from" t:ident -> { (k, t.to_string()) }
+        }
+    }
+
+    // Both grammars produce the keyword types of the pool
+    let keywords: Vec<dsl_tokens::select> = vec![
+        query_a::parse_select_stmt.parse_str("select *").unwrap(),
+        query_b::parse_select_stmt
+            .parse_str("select from t")
+            .unwrap()
+            .0,
+    ];
+    assert_eq!(keywords.len(), 2);
+}
+
+// --- Test Dialects ---
+macro_rules! numbers_grammar {
+    ($($dialect:tt)*) => {
+        grammar! {
+            $($dialect)*
+            grammar numbers {
+                pub rule value -> i32 =
+                    v:i32 -> { v }
+                  | #[dialect("lenient")] "+" v:i32 -> { v }
+
+                #[dialect("lenient")]
+                pub rule values -> Vec<i32> = vs:value* -> { vs }
+            }
+        }
+    };
+}
+
+mod strict_numbers {
+    use super::*;
+    numbers_grammar!();
+}
+
+mod lenient_numbers {
+    use super::*;
+    numbers_grammar!(#![dialect("lenient")]);
+}
+
+#[test]
+fn test_dialects() {
+    strict_numbers::numbers::parse_value
+        .parse_str("+1")
+        .test()
+        .assert_failure();
+    lenient_numbers::numbers::parse_value
+        .parse_str("+1")
+        .test()
+        .assert_success_is(1);
+    lenient_numbers::numbers::parse_values
+        .parse_str("1 +2")
+        .test()
+        .assert_success_is(vec![1, 2]);
+
+    // Rules of other dialects are not part of the grammar
+    assert!(lenient_numbers::numbers::GRAMMAR_DOCS.contains("values ::= value*"));
+    assert!(!strict_numbers::numbers::GRAMMAR_DOCS.contains("values"));
+}
+
+// --- Test Rules as syn Parse Types ---
+#[test]
+fn test_rule_parser() {
+    use syn::parse::{Parse, ParseStream};
+    use syn::punctuated::Punctuated;
+    use syn::Token;
+    use syn_grammar::rt::RuleParser;
+
+    grammar! {
+        grammar assignments {
+            pub rule assign -> (String, i32) = name:ident "=" v:i32 -> { (name.to_string(), v) }
+
+            #[lossless]
+            pub rule value -> i32 = i:i32 -> { i }
+        }
+    }
+
+    // A type parsed by hand-written syn code, with a generated rule as a field
+    struct Let {
+        binding: RuleParser<assignments::rules::assign>,
+    }
+
+    impl Parse for Let {
+        fn parse(input: ParseStream) -> syn::Result<Self> {
+            input.parse::<Token![let]>()?;
+            Ok(Let {
+                binding: input.parse()?,
+            })
+        }
+    }
+
+    let parsed: Let = syn::parse_str("let x = 5").unwrap();
+    assert_eq!(*parsed.binding, ("x".to_string(), 5));
+
+    let parser = Punctuated::<RuleParser<assignments::rules::value>, Token![,]>::parse_terminated;
+    let values = parser.parse_str("1, 2, 3").unwrap();
+    let values: Vec<i32> = values.into_iter().map(|v| v.into_inner().0).collect();
+    assert_eq!(values, vec![1, 2, 3]);
+
+    // Errors are the best errors of the rule, as from the generated entry point
+    let err = syn::parse_str::<Let>("let x = y").err().unwrap();
+    assert!(err.to_string().contains("rule 'assign'"), "{}", err);
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: i32,
+}
+
+#[test]
+fn test_impl_parse() {
+    use syn::punctuated::Punctuated;
+    use syn::Token;
+
+    grammar! {
+        grammar assignment_parse {
+            use super::Assignment;
+
+            #[impl_parse]
+            rule assignment -> Assignment =
+                name:ident "=" value:i32 -> Assignment { name: name.to_string(), value }
+        }
+    }
+
+    // The return type parses like any syn type, without a wrapper
+    let assignment: Assignment = syn::parse_str("width = 80").unwrap();
+    assert_eq!(
+        assignment,
+        Assignment {
+            name: "width".to_string(),
+            value: 80
+        }
+    );
+
+    let list = Punctuated::<Assignment, Token![;]>::parse_terminated
+        .parse_str("a = 1; b = 2")
+        .unwrap();
+    let values: Vec<i32> = list.into_iter().map(|a| a.value).collect();
+    assert_eq!(values, vec![1, 2]);
+
+    let err = syn::parse_str::<Assignment>("width 80").err().unwrap();
+    assert!(err.to_string().contains("expected `=`"), "{}", err);
+}
+
+#[test]
+fn test_cst() {
+    grammar! {
+        grammar cst_sum {
+            options { cst = true; }
+
+            pub rule sum -> i32 = l:term "+" r:sum -> { l + r } | t:term -> { t }
+
+            rule term -> i32 = paren(s:sum) -> { s } | n:i32 -> { n }
+        }
+    }
+
+    fn rules(node: &syn_grammar::rt::Node) -> Vec<&'static str> {
+        let mut rules = Vec::new();
+        node.walk(&mut |n| rules.push(n.rule));
+        rules
+    }
+
+    let (value, root) = cst_sum::cst_sum.parse_str("1 + (2 + 3)").unwrap();
+    assert_eq!(value, 6);
+    assert_eq!(root.rule, "sum");
+    assert_eq!(root.tokens.to_string(), "1 + (2 + 3)");
+    assert_eq!(
+        rules(&root),
+        ["sum", "term", "sum", "term", "sum", "term", "sum", "term"]
+    );
+    // The children are in input order, with the exact tokens they consumed
+    let tokens: Vec<String> = root.children.iter().map(|c| c.tokens.to_string()).collect();
+    assert_eq!(tokens, ["1", "(2 + 3)"]);
+
+    // Nodes of backtracked alternatives are dropped
+    let (_, root) = cst_sum::cst_sum.parse_str("7").unwrap();
+    assert_eq!(rules(&root), ["sum", "term"]);
+
+    // The value parsers are unchanged
+    cst_sum::parse_sum
+        .parse_str("1 + 2")
+        .test()
+        .assert_success_is(3);
+}
+
+#[test]
+fn test_incremental_reparse() {
+    use syn::parse::ParseStream;
+    use syn_grammar::rt::Incremental;
+
+    grammar! {
+        grammar settings {
+            options { incremental = true; }
+
+            pub rule setting -> (String, i32) = name:ident "=" v:i32 ";" -> { (name.to_string(), v) }
+        }
+    }
+
+    fn reparse(
+        state: &mut Incremental<(String, i32)>,
+        source: &str,
+    ) -> syn::Result<Vec<(String, i32)>> {
+        (|input: ParseStream| settings::incremental_setting(input, state).map(|v| v.to_vec()))
+            .parse_str(source)
+    }
+
+    let mut state = Incremental::new();
+
+    let values = reparse(&mut state, "a = 1; b = 2; c = 3;").unwrap();
+    assert_eq!(values[2], ("c".to_string(), 3));
+
+    // Editing the last item reuses the first one
+    let values = reparse(&mut state, "a = 1; b = 2; c = 30; d = 4;").unwrap();
+    assert_eq!(values.len(), 4);
+    assert_eq!(values[2], ("c".to_string(), 30));
+    assert_eq!(state.reused(), 1);
+
+    let err = reparse(&mut state, "a = 1; b = ;").unwrap_err();
+    assert!(err.to_string().contains("expected"), "{}", err);
+    assert_eq!(state.values().len(), 1);
+}
+
+#[test]
+fn test_test_only_rules() {
+    grammar! {
+        grammar directives {
+            pub rule value -> i32 = i:i32 -> { i }
+
+            // Exists in the test build of the parser only
+            #[cfg(test)]
+            pub rule debug_value -> String = "dump" v:value -> { format!("value = {}", v) }
+        }
+    }
+
+    directives::parse_debug_value
+        .parse_str("dump 42")
+        .test()
+        .assert_success_is("value = 42".to_string());
+    let _: Option<directives::rules::debug_value> = None;
+}
+
+#[test]
+fn test_grammar_imports() {
+    grammar! {
+        grammar assignments {
+            import "tests/grammars/lexer.grammar" as lex;
+
+            pub rule assignment -> (String, i32) =
+                "let" n:lex::name "=" v:lex::number -> { (n, v) }
+
+            pub rule sum -> i32 = "sum" ns:lex::list -> { ns.iter().sum() }
+        }
+    }
+
+    assignments::parse_assignment
+        .parse_str("let x = -4")
+        .test()
+        .assert_success_is(("x".to_string(), -4));
+    assignments::parse_sum
+        .parse_str("sum [1 2 3]")
+        .test()
+        .assert_success_is(6);
+    // The imported public rules are generated as rules of the importer
+    assignments::parse_lex_number
+        .parse_str("7")
+        .test()
+        .assert_success_is(7);
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Call {
+    input: String,
+    ctx: Vec<i32>,
+}
+
+#[test]
+fn test_bindings_named_input_and_ctx() {
+    grammar! {
+        grammar reserved_names {
+            pub rule call -> Call =
+                input:ident paren(ctx:i32*) -> { Call { input: input.to_string(), ctx } }
+
+            pub rule mutated -> usize =
+                input:mut string ";" -> { input.value.push('!'); input.value.len() }
+        }
+    }
+
+    reserved_names::parse_call
+        .parse_str("f(1 2)")
+        .test()
+        .assert_success_is(Call {
+            input: "f".to_string(),
+            ctx: vec![1, 2],
+        });
+    reserved_names::parse_mutated
+        .parse_str("\"ab\";")
+        .test()
+        .assert_success_is(3);
+}
+
+#[test]
+fn test_skip() {
+    grammar! {
+        grammar skipping {
+            pub rule function -> String =
+                skip(attr*) "fn" n:ident skip(paren()) -> { n.to_string() }
+
+            rule attr -> () = "#" [ ident ] -> { () }
+        }
+    }
+
+    skipping::parse_function
+        .parse_str("#[inline] #[cold] fn run()")
+        .test()
+        .assert_success_is("run".to_string());
+    skipping::parse_function
+        .parse_str("fn main()")
+        .test()
+        .assert_success_is("main".to_string());
+}
+
+#[test]
+fn test_where_guards() {
+    grammar! {
+        grammar guarded {
+            pub rule modifier -> String =
+                i:ident where { i == "lazy" } -> { "lazy modifier".to_string() }
+              | n:i32 where { n % 2 == 0 } -> { format!("even {}", n) }
+              | i:ident -> { format!("name {}", i) }
+
+            pub rule small -> u8 = n:i32 where { (0..10).contains(&n) } -> { n as u8 }
+        }
+    }
+
+    guarded::parse_modifier
+        .parse_str("lazy")
+        .test()
+        .assert_success_is("lazy modifier".to_string());
+    // A false guard backtracks to the next alternative
+    guarded::parse_modifier
+        .parse_str("eager")
+        .test()
+        .assert_success_is("name eager".to_string());
+    guarded::parse_modifier
+        .parse_str("4")
+        .test()
+        .assert_success_is("even 4".to_string());
+    guarded::parse_modifier
+        .parse_str("3")
+        .test()
+        .assert_failure_contains("condition `n % 2 == 0` does not hold");
+
+    guarded::parse_small
+        .parse_str("12")
+        .test()
+        .assert_failure_contains("condition `(0..10).contains(&n)` does not hold");
+}
+
+#[test]
+fn test_parse_streams() {
+    use quote::quote;
+    use syn_grammar::rt;
+
+    grammar! {
+        grammar routes {
+            pub rule route -> (String, String, String) =
+                m:ident p:string "fn" f:ident ";" -> { (m.to_string(), p.value, f.to_string()) }
+        }
+    }
+
+    // e.g. `#[route(GET "/users")]` applied to `fn list;`
+    let args = quote!(GET "/users");
+    let item = quote!(fn list;);
+    let route = rt::parse_streams(routes::parse_route, [args.clone(), item]).unwrap();
+    assert_eq!(
+        route,
+        ("GET".to_string(), "/users".to_string(), "list".to_string())
+    );
+
+    let err = rt::parse_streams(routes::parse_route, [args, quote!(fn)]).unwrap_err();
+    assert!(
+        err.to_string().contains("end of input"),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn test_inline_rule() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use syn_grammar::rt;
+
+    grammar! {
+        grammar inlined {
+            pub rule field -> (String, String) =
+                name:ident t:type_annotation -> { (name.to_string(), t) }
+
+            pub rule values -> Vec<i32> = vs:value* -> { vs }
+
+            pub rule pair -> (i32, i32) = a:sum "," b:sum -> { (a, b) }
+
+            #[inline_rule]
+            rule type_annotation -> String = ":" t:ident -> { t.to_string() }
+
+            #[inline_rule]
+            rule value -> i32 = "=" v:i32 -> { v }
+
+            #[inline_rule]
+            rule sum -> i32 = a:i32 "+" b:i32 -> { a + b }
+        }
+    }
+
+    inlined::parse_field
+        .parse_str("x: u8")
+        .test()
+        .assert_success_is(("x".to_string(), "u8".to_string()));
+    inlined::parse_values
+        .parse_str("= 1 = 2")
+        .test()
+        .assert_success_is(vec![1, 2]);
+    inlined::parse_pair
+        .parse_str("1 + 2, 3 + 4")
+        .test()
+        .assert_success_is((3, 7));
+
+    // The inlined rules are parsed in their callers, whose errors they report
+    #[derive(Default)]
+    struct Calls(Vec<String>);
+
+    impl rt::Listener for Calls {
+        fn on_enter(&mut self, rule: &str, _span: proc_macro2::Span) {
+            self.0.push(format!("enter {}", rule));
+        }
+
+        fn on_error(&mut self, rule: &str, _error: &syn::Error) {
+            self.0.push(format!("error {}", rule));
+        }
+    }
+
+    let calls = Rc::new(RefCell::new(Calls::default()));
+    rt::with_listener(&calls, || {
+        inlined::parse_field.parse_str("x: u8").unwrap();
+        inlined::parse_values.parse_str("= 1 = 2").unwrap();
+    });
+    assert_eq!(calls.borrow().0, ["enter field", "enter values"]);
+
+    calls.borrow_mut().0.clear();
+    let err = rt::with_listener(&calls, || inlined::parse_pair.parse_str("1 + 2, 3")).unwrap_err();
+    assert!(err.to_string().contains("expected `+`"));
+    assert_eq!(calls.borrow().0, ["enter pair", "error pair"]);
+}
+
+#[test]
+fn test_variant_span() {
+    grammar! {
+        grammar variant_span {
+            use proc_macro2::Span;
+
+            pub rule pair -> (usize, usize) =
+                a:i32 "," [b:i32] -> {
+                    let _ = (a, b);
+                    ($span.start().column, $span.end().column)
+                }
+
+            // Left-recursive alternatives span their left operand, too
+            pub rule sum -> Span =
+                s:sum "+" n:i32 -> { let _ = (s, n); $span }
+              | n:i32 -> { let _ = n; $span }
+
+            // Bindings shadowing `input` do not change the span
+            pub rule named -> (String, usize) =
+                "let" input:ident -> { (input.to_string(), { $span }.end().column) }
+        }
+    }
+
+    variant_span::parse_pair
+        .parse_str(" 1 , [ 2 ]")
+        .test()
+        .assert_success_is((1, 10));
+    let span = variant_span::parse_sum
+        .parse_str("1 + 22 + 3")
+        .test()
+        .assert_success();
+    assert_eq!((span.start().column, span.end().column), (0, 10));
+    variant_span::parse_named
+        .parse_str("let x")
+        .test()
+        .assert_success_is(("x".to_string(), 5));
+}
+
+#[test]
+fn test_position_builtin() {
+    grammar! {
+        grammar positions {
+            // Records where the field begins, even if it starts with the optional `pub`
+            pub rule field -> (usize, usize, String) =
+                p:position() "pub"? name:ident -> {
+                    (p.start().line, p.start().column, name.to_string())
+                }
+
+            pub rule fields -> Vec<(usize, usize, String)> = fs:field* -> { fs }
+        }
+    }
+
+    positions::parse_field
+        .parse_str("  x")
+        .test()
+        .assert_success_is((1, 2, "x".to_string()));
+    positions::parse_fields
+        .parse_str("pub a\n  b")
+        .test()
+        .assert_success_is(vec![(1, 0, "a".to_string()), (2, 2, "b".to_string())]);
+}
+
+#[test]
+fn test_parser_struct() {
+    grammar! {
+        grammar entry_points {
+            pub rule sum -> i32 = a:i32 "+" b:i32 -> { a + b }
+            pub rule names -> Vec<String> = ns:ident* -> { ns.iter().map(|n| n.to_string()).collect() }
+            rule hidden -> () = "x" -> { () }
+        }
+    }
+
+    grammar! {
+        grammar renamed {
+            options { parser = Calculator; }
+            pub rule value -> i32 = v:i32 -> { v }
+        }
+    }
+
+    use entry_points::EntryPointsParser;
+    assert_eq!(EntryPointsParser::sum().parse_str("1 + 2").unwrap(), 3);
+    assert_eq!(
+        EntryPointsParser::names()
+            .parse2(quote::quote!(a b))
+            .unwrap(),
+        vec!["a".to_string(), "b".to_string()]
+    );
+    // The whole input must be consumed
+    let err = EntryPointsParser::sum().parse_str("1 + 2 3").unwrap_err();
+    assert!(err.to_string().contains("unexpected token"), "{}", err);
+
+    let path = std::env::temp_dir().join("syn_grammar_parser_struct_test.txt");
+    std::fs::write(&path, "40 + 2").unwrap();
+    assert_eq!(EntryPointsParser::sum().parse_file(&path).unwrap(), 42);
+    std::fs::remove_file(&path).unwrap();
+    let err = EntryPointsParser::sum().parse_file(&path).unwrap_err();
+    assert!(err.to_string().starts_with("cannot read"), "{}", err);
+
+    // An entry point works with syn helpers expecting a `syn::parse::Parser`
+    let parser = renamed::Calculator::value();
+    assert_eq!(syn::parse::Parser::parse_str(parser, "7").unwrap(), 7);
+}
+
+#[derive(Debug)]
+pub enum RangeError {
+    Reversed(i32, i32),
+    TooLarge(i64),
+}
+
+impl std::fmt::Display for RangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RangeError::Reversed(a, b) => write!(f, "the range {}..{} is reversed", a, b),
+            RangeError::TooLarge(n) => write!(f, "{} does not fit into a byte", n),
+        }
+    }
+}
+
+#[test]
+fn test_throwing_actions() {
+    grammar! {
+        grammar ranges {
+            use super::RangeError;
+
+            pub rule range -> (i32, i32) throws RangeError =
+                a:i32 ".." b:i32 -> {
+                    if a > b {
+                        Err(RangeError::Reversed(a, b))?
+                    }
+                    (a, b)
+                }
+
+            pub rule byte -> u8 throws RangeError =
+                n:i64 -> { u8::try_from(n).map_err(|_| RangeError::TooLarge(n))? }
+
+            pub rule ranges -> ((i32, i32), (i32, i32)) = a:range "," b:range -> { (a, b) }
+        }
+    }
+
+    ranges::parse_range
+        .parse_str("1 .. 3")
+        .test()
+        .assert_success_is((1, 3));
+    ranges::parse_byte
+        .parse_str("255")
+        .test()
+        .assert_success_is(255);
+    ranges::parse_byte
+        .parse_str("256")
+        .test()
+        .assert_failure_contains("256 does not fit into a byte");
+
+    // The error spans the tokens of the failed alternative
+    let err = ranges::parse_ranges
+        .parse_str("1 .. 2, 5 .. 4")
+        .test()
+        .assert_failure();
+    assert!(
+        err.to_string().ends_with("the range 5..4 is reversed"),
+        "{}",
+        err
+    );
+    let span = err.span();
+    assert_eq!((span.start().column, span.end().column), (8, 14));
+}
+
+#[test]
+fn test_listener_hooks() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use syn_grammar::rt;
+
+    grammar! {
+        grammar listened {
+            pub rule call -> (String, Vec<i32>) =
+                name:ident paren(args:separated(arg, ",")) -> { (name.to_string(), args) }
+
+            rule arg -> i32 = "-" n:i32 -> { -n } | n:i32 -> { n }
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl rt::Listener for Recorder {
+        fn on_enter(&mut self, rule: &str, span: proc_macro2::Span) {
+            self.events
+                .push(format!("enter {} at {}", rule, span.start().column));
+        }
+
+        fn on_exit(&mut self, rule: &str, span: proc_macro2::Span) {
+            let (start, end) = (span.start().column, span.end().column);
+            self.events
+                .push(format!("exit {} {}..{}", rule, start, end));
+        }
+
+        fn on_error(&mut self, rule: &str, _error: &syn::Error) {
+            self.events.push(format!("error {}", rule));
+        }
+    }
+
+    let recorder = Rc::new(RefCell::new(Recorder::default()));
+    let value = rt::with_listener(&recorder, || {
+        listened::parse_call.parse_str("f(1, -2)").unwrap()
+    });
+    assert_eq!(value, ("f".to_string(), vec![1, -2]));
+    assert_eq!(
+        recorder.borrow().events,
+        vec![
+            "enter call at 0",
+            "enter arg at 2",
+            "exit arg 2..3",
+            "enter arg at 5",
+            "exit arg 5..7",
+            "exit call 0..8",
+        ]
+    );
+
+    // Failures are reported, and parses outside of `with_listener` are not
+    recorder.borrow_mut().events.clear();
+    rt::with_listener(&recorder, || listened::parse_call.parse_str("f 1")).unwrap_err();
+    listened::parse_call.parse_str("g(1)").unwrap();
+    assert_eq!(
+        recorder.borrow().events,
+        vec!["enter call at 0", "error call"]
+    );
+}
+
+#[test]
+fn test_symbol_table_patterns() {
+    grammar! {
+        grammar scoped {
+            pub rule block -> Vec<String> =
+                scope { { items:item* } } -> { items.into_iter().flatten().collect() }
+
+            rule item -> Vec<String> =
+                "let" declare(ident) ";" -> { vec![] }
+              | "use" declare(ident) "!" ";" -> { vec![] }
+              | "use" name:resolve(ident) ";" -> { vec![name.to_string()] }
+              | b:block -> { b }
+        }
+    }
+
+    assert_eq!(
+        scoped::parse_block
+            .parse_str("{ let x; use x; { let y; use y; use x; } }")
+            .unwrap(),
+        vec!["x", "y", "x"]
+    );
+
+    // Declarations end with their scope
+    let err = scoped::parse_block
+        .parse_str("{ { let y; } use y; }")
+        .unwrap_err();
+    assert!(
+        err.to_string().ends_with("cannot find `y` in this scope"),
+        "{}",
+        err
+    );
+    assert_eq!(err.span().start().column, 17);
+
+    // The declaration of an alternative that failed is undone
+    let err = scoped::parse_block.parse_str("{ use y; }").unwrap_err();
+    assert!(
+        err.to_string().ends_with("cannot find `y` in this scope"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_grammar_docs() {
+    grammar! {
+        grammar documented {
+            /// A sum of numbers.
+            pub rule sum -> i32 = l:num "+" r:num -> { l + r }
+
+            rule num -> i32 = "-" n:i32 -> { -n } | n:i32 -> { n }
+        }
+    }
+
+    assert_eq!(documented::parse_sum.parse_str("1 + -2").unwrap(), -1);
+    assert_eq!(
+        documented::GRAMMAR_DOCS,
+        "# Grammar `documented`
+
+## `sum`
+
+A sum of numbers.
+
+```text
+sum ::= num \"+\" num
+```
+
+## `num`
+
+```text
+num ::= \"-\" i32
+      | i32
+```
+"
+    );
+}
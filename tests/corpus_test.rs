@@ -0,0 +1,179 @@
+//! Real-world grammars from `examples/corpus`, parsed end to end with valid and invalid input.
+
+use syn::parse::Parser;
+use syn_grammar::testing::Testable;
+
+#[path = "../examples/corpus/query.rs"]
+mod query;
+#[path = "../examples/corpus/routes.rs"]
+mod routes;
+#[path = "../examples/corpus/state_machine.rs"]
+mod state_machine;
+#[path = "../examples/corpus/style.rs"]
+mod style;
+
+#[test]
+fn test_query_corpus() {
+    use query::{Expr, OrderKey, Query};
+
+    for input in query::VALID {
+        query::dsl::parse_query
+            .parse_str(input)
+            .test()
+            .assert_success();
+    }
+    for (input, message) in query::INVALID {
+        query::dsl::parse_query
+            .parse_str(input)
+            .test()
+            .assert_failure_contains(message);
+    }
+
+    let column = |c: &str| Box::new(Expr::Column(c.to_string()));
+    query::dsl::parse_query
+        .parse_str("select name from users where not a = 1 and b < 2 order by name desc limit 5")
+        .test()
+        .assert_success_is(Query {
+            columns: vec!["name".to_string()],
+            table: "users".to_string(),
+            filter: Some(Expr::Binary(
+                Box::new(Expr::Not(Box::new(Expr::Binary(
+                    column("a"),
+                    "=",
+                    Box::new(Expr::Int(1)),
+                )))),
+                "and",
+                Box::new(Expr::Binary(column("b"), "<", Box::new(Expr::Int(2)))),
+            )),
+            order: vec![OrderKey {
+                column: "name".to_string(),
+                descending: true,
+            }],
+            limit: Some(5),
+        });
+
+    let query = query::dsl::parse_query
+        .parse_str(&query::fixture(50))
+        .test()
+        .assert_success();
+    assert_eq!(query.limit, Some(100));
+}
+
+#[test]
+fn test_style_corpus() {
+    use style::{Declaration, Selector, StyleRule, Value};
+
+    for input in style::VALID {
+        style::dsl::parse_stylesheet
+            .parse_str(input)
+            .test()
+            .assert_success();
+    }
+    for (input, message) in style::INVALID {
+        style::dsl::parse_stylesheet
+            .parse_str(input)
+            .test()
+            .assert_failure_contains(message);
+    }
+
+    style::dsl::parse_stylesheet
+        .parse_str(".nav-bar, #top { border-width: 2px !important }")
+        .test()
+        .assert_success_is(vec![StyleRule {
+            selectors: vec![
+                Selector::Class("nav-bar".to_string()),
+                Selector::Id("top".to_string()),
+            ],
+            declarations: vec![Declaration {
+                property: "border-width".to_string(),
+                values: vec![Value::Number(2, "px".to_string())],
+                important: true,
+            }],
+        }]);
+
+    let sheet = style::dsl::parse_stylesheet
+        .parse_str(&style::fixture(50))
+        .test()
+        .assert_success();
+    assert_eq!(sheet.len(), 50);
+}
+
+#[test]
+fn test_state_machine_corpus() {
+    use state_machine::Transition;
+
+    for input in state_machine::VALID {
+        let machine = state_machine::dsl::parse_machine
+            .parse_str(input)
+            .test()
+            .assert_success();
+        assert!(machine.is_closed(), "{:?}", machine);
+    }
+    for (input, message) in state_machine::INVALID {
+        state_machine::dsl::parse_machine
+            .parse_str(input)
+            .test()
+            .assert_failure_contains(message);
+    }
+
+    let machine = state_machine::dsl::parse_machine
+        .parse_str(state_machine::VALID[2])
+        .test()
+        .assert_success();
+    assert_eq!(
+        machine.states[2].transitions,
+        vec![Transition {
+            event: "unlock".to_string(),
+            guard: Some("!jammed".to_string()),
+            target: "closed".to_string(),
+        }]
+    );
+
+    let machine = state_machine::dsl::parse_machine
+        .parse_str(&state_machine::fixture(50))
+        .test()
+        .assert_success();
+    assert_eq!(machine.states.len(), 50);
+    assert!(machine.is_closed());
+}
+
+#[test]
+fn test_routes_corpus() {
+    use routes::dsl::Method;
+    use routes::{Route, Segment};
+
+    for input in routes::VALID {
+        routes::dsl::parse_routes
+            .parse_str(input)
+            .test()
+            .assert_success();
+    }
+    for (input, message) in routes::INVALID {
+        routes::dsl::parse_routes
+            .parse_str(input)
+            .test()
+            .assert_failure_contains(message);
+    }
+
+    // Scopes prefix the paths of their routes
+    routes::dsl::parse_routes
+        .parse_str("scope /api { scope /v1 { put /items/{key: String} => items::put; } }")
+        .test()
+        .assert_success_is(vec![Route {
+            method: Method::Put,
+            path: vec![
+                Segment::Static("api".to_string()),
+                Segment::Static("v1".to_string()),
+                Segment::Static("items".to_string()),
+                Segment::Param("key".to_string(), "String".to_string()),
+            ],
+            handler: "items::put".to_string(),
+        }]);
+
+    let table = routes::dsl::parse_routes
+        .parse_str(&routes::fixture(20))
+        .test()
+        .assert_success();
+    assert_eq!(table.len(), 100);
+    assert!(table.iter().all(|r| r.path.len() >= 3));
+}
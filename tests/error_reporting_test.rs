@@ -120,3 +120,66 @@ fn test_rule_name_in_error_message() {
         .test()
         .assert_failure_contains("Error in rule 'inner'");
 }
+
+#[test]
+fn test_repetition_without_progress() {
+    grammar! {
+        grammar no_progress {
+            // `outer_attrs` succeeds without consuming input, which the validator cannot see
+            pub rule main -> usize = groups:outer_attrs* "end" -> { groups.len() }
+        }
+    }
+
+    no_progress::parse_main
+        .parse_str("end")
+        .test()
+        .assert_failure_contains("infinite loop detected");
+}
+
+#[test]
+fn test_expected_labels() {
+    grammar! {
+        grammar labeled {
+            pub rule field -> (String, String) =
+                n:ident ":" #[expected("a type expression")] t:type_expr -> { (n.to_string(), t) }
+
+            pub rule annotated -> String = "as" t:checked_type -> { t }
+
+            #[expected = "a checked type"]
+            rule checked_type -> String = t:type_expr -> { t }
+
+            rule type_expr -> String =
+                "fn" "<" ">" -> { "fn<>".to_string() }
+              | i:ident -> { i.to_string() }
+        }
+    }
+
+    labeled::parse_field
+        .parse_str("x: u8")
+        .test()
+        .assert_success_is(("x".to_string(), "u8".to_string()));
+
+    // Failing at the start of the pattern reports the label at the failing token
+    let err = labeled::parse_field
+        .parse_str("x: 42")
+        .test()
+        .assert_failure();
+    assert!(
+        err.to_string().contains("expected a type expression"),
+        "{}",
+        err
+    );
+    assert!(!err.to_string().contains("one of"), "{}", err);
+    assert_eq!(err.span().start().column, 3);
+
+    // Failures after the first token keep the precise error
+    labeled::parse_field
+        .parse_str("x: fn < ;")
+        .test()
+        .assert_failure_contains("expected `>`");
+
+    labeled::parse_annotated
+        .parse_str("as 1")
+        .test()
+        .assert_failure_contains("expected a checked type");
+}
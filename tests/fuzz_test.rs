@@ -0,0 +1,72 @@
+use syn_grammar::grammar;
+use syn_grammar::samples::generate_samples;
+use syn_grammar::testing::fuzz_roundtrip;
+use syn_grammar_model::model::GrammarDefinition;
+use syn_grammar_model::parser;
+
+/// Expands the grammar with `grammar!` and keeps its tokens for `generate_samples`.
+macro_rules! fuzzed_grammar {
+    ($($grammar:tt)*) => {
+        grammar! { $($grammar)* }
+
+        fn model() -> GrammarDefinition {
+            syn::parse2::<parser::GrammarDefinition>(quote::quote!($($grammar)*))
+                .unwrap()
+                .into()
+        }
+    };
+}
+
+fuzzed_grammar! {
+    grammar calc {
+        pub rule expr -> i64 =
+            l:expr "+" r:term -> { l.wrapping_add(r) }
+          | t:term -> { t }
+
+        rule term -> i64 =
+            l:term "*" r:atom -> { l.wrapping_mul(r) }
+          | a:atom -> { a }
+
+        rule atom -> i64 =
+            "-" i:i64 -> { -i }
+          | i:i64 -> { i }
+          | "max" paren(args:separated(expr, ",", min = 1)) -> {
+                args.into_iter().max().unwrap()
+            }
+          | "let" _name:ident "=" v:expr "in" [b:expr] -> { v.wrapping_add(b) }
+          | "eval" e:parse_in(lit_str, expr) -> { e }
+          | paren(e:expr) -> { e }
+    }
+}
+
+#[test]
+fn test_generated_samples_parse() {
+    let grammar = model();
+    let samples = generate_samples(&grammar, 7, 6).take(200);
+    assert_eq!(fuzz_roundtrip(calc::parse_expr, samples), 200);
+
+    // Samples of another rule
+    let samples = generate_samples(&grammar, 7, 3).rule("atom").take(50);
+    assert_eq!(fuzz_roundtrip(calc::parse_expr, samples), 50);
+}
+
+#[test]
+fn test_samples_cover_the_grammar() {
+    let grammar = model();
+    let text: String = generate_samples(&grammar, 1, 6)
+        .take(200)
+        .map(|sample| sample.to_string() + " ")
+        .collect();
+    for token in ["+", "*", "-", "max", ",", "let", "in", "[", "(", "eval \""] {
+        assert!(text.contains(token), "no sample contains `{}`", token);
+    }
+}
+
+#[test]
+#[should_panic(expected = "was rejected")]
+fn test_rejected_sample() {
+    let grammar = model();
+    let samples = generate_samples(&grammar, 3, 4).rule("atom").take(50);
+    // A trailing `+` misses its right operand
+    fuzz_roundtrip(calc::parse_expr, samples.map(|s| quote::quote!(#s + )));
+}
@@ -50,3 +50,98 @@ fn test_generic_map() {
         .test()
         .assert_success_is(expected);
 }
+
+#[test]
+fn test_literal_arguments_as_patterns() {
+    grammar! {
+        grammar literal_args {
+            pub rule main -> (i32, i32) =
+                a:wrapped("<", ">") b:wrapped("|", "|") -> { (a, b) }
+
+            // Literal arguments are matched as tokens; bindings carry over to them
+            rule wrapped(open, close) -> i32 =
+                o:open t:i32 close -> { let _: proc_macro2::Span = o.span; t }
+        }
+    }
+
+    literal_args::parse_main
+        .parse_str("< 1 > | 2 |")
+        .test()
+        .assert_success_is((1, 2));
+}
+
+#[test]
+fn test_literal_arguments_with_value_parameters() {
+    grammar! {
+        grammar mixed_args {
+            pub rule main -> i32 =
+                v:scaled("<", 10) -> { v }
+
+            rule scaled(open, factor: i32) -> i32 =
+                open t:i32 -> { t * factor }
+        }
+    }
+
+    mixed_args::parse_main
+        .parse_str("< 4")
+        .test()
+        .assert_success_is(40);
+}
+
+#[test]
+fn test_instantiation_call_site_in_errors() {
+    grammar! {
+        grammar call_site {
+            pub rule main -> i32 =
+                v:wrapped("<", ">") -> { v }
+
+            rule wrapped(open, close) -> i32 =
+                open t:i32 close -> { t }
+        }
+    }
+
+    call_site::parse_main
+        .parse_str("< 1 ;")
+        .test()
+        .assert_failure_contains("note: instantiated from `wrapped` at line ");
+}
+
+#[test]
+fn test_lifetime_generics() {
+    use syn::parse::ParseStream;
+    use syn_grammar::rt::RuleParser;
+
+    grammar! {
+        grammar lifetimes {
+            pub rule pick<'a>(words: &'a [&'a str]) -> &'a str =
+                i:usize -> { words[i] }
+
+            pub rule pair<'a>(words: &'a [&'a str]) -> (&'a str, &'a str) =
+                a:pick(words) "," b:pick(words) -> { (a, b) }
+
+            pub rule keyword<'a> -> &'a str =
+                "let" -> { "let" }
+
+            // Lifetimes are kept in instantiations of generic rules
+            rule wrapped<'a, T: ?Sized + 'a>(item) -> T =
+                paren(v:item) -> { v }
+
+            pub rule main<'a> -> &'a str =
+                k:wrapped(keyword) -> { k }
+        }
+    }
+
+    let words = ["zero", "one", "two"];
+    (|input: ParseStream| lifetimes::parse_pair(input, &words))
+        .parse_str("2, 0")
+        .test()
+        .assert_success_is(("two", "zero"));
+
+    lifetimes::parse_main
+        .parse_str("(let)")
+        .test()
+        .assert_success_is("let");
+
+    let keyword: RuleParser<lifetimes::rules::keyword<'static>> = syn::parse_str("let").unwrap();
+    assert_eq!(*keyword, "let");
+}
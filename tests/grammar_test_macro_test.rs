@@ -0,0 +1,42 @@
+use syn_grammar::{grammar, grammar_test};
+
+grammar! {
+    grammar calc {
+        pub rule expression -> i32 =
+            l:expression "+" r:term -> { l + r }
+          | t:term -> { t }
+
+        rule term -> i32 = i:i32 -> { i } | paren(e:expression) -> { e }
+
+        pub rule list -> Vec<i32> = [items:separated(term, ",")] -> { items }
+    }
+}
+
+mod nested {
+    syn_grammar::grammar! {
+        grammar words {
+            pub rule word -> String = i:ident -> { i.to_string() }
+        }
+    }
+}
+
+grammar_test! {
+    calc::expression {
+        accept "1";
+        accept "1 + 2 + 3" => 6;
+        accept "(1 + 2) + 3" => 6;
+        reject "1 + *" expecting "unexpected token";
+        reject "";
+    }
+
+    calc::list {
+        accept "[1, (2 + 3)]" => vec![1, 5];
+        accept "[]" => Vec::<i32>::new();
+        reject "[1 2]"
+    }
+
+    nested::words::word {
+        accept "hello" => "hello";
+        reject "42" expecting "expected identifier"
+    }
+}
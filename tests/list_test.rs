@@ -138,3 +138,395 @@ fn test_explicit_container() {
         .test()
         .assert_success_is(vec!["a".to_string(), "b".to_string()]);
 }
+
+#[test]
+fn test_punctuated_container() {
+    use std::collections::{BTreeSet, VecDeque};
+
+    grammar! {
+        grammar list_punctuated {
+            use syn::punctuated::Punctuated;
+            use std::collections::{BTreeSet, VecDeque};
+
+            pub rule args -> Punctuated<i32, Token![,]> =
+                items:separated<Punctuated>(i32, ",", trailing=true) -> { items }
+
+            pub rule stmts -> Punctuated<i32, Token![;]> =
+                items:terminated<Punctuated>(i32, ";", required=auto) -> { items }
+
+            pub rule list -> Punctuated<i32, Token![,]> =
+                l:delimited<Punctuated>("[", i32, ",", "]") -> { l.items }
+
+            pub rule queue -> VecDeque<i32> = items:repeated<VecDeque>(i32) -> { items }
+
+            pub rule set -> BTreeSet<i32> = items:separated<BTreeSet>(i32, ",") -> { items }
+        }
+    }
+
+    // The separator tokens are kept with their spans, including a trailing one
+    let args = list_punctuated::parse_args.parse_str("1, 2,").unwrap();
+    let values: Vec<i32> = args.iter().copied().collect();
+    assert_eq!(values, vec![1, 2]);
+    assert!(args.trailing_punct());
+    let commas: Vec<usize> = args
+        .pairs()
+        .filter_map(|p| p.punct().map(|c| c.span.start().column))
+        .collect();
+    assert_eq!(commas, vec![1, 4]);
+
+    let stmts = list_punctuated::parse_stmts.parse_str("1; 2").unwrap();
+    assert_eq!(stmts.len(), 2);
+    assert!(!stmts.trailing_punct());
+
+    let list = list_punctuated::parse_list.parse_str("[1, 2, 3]").unwrap();
+    assert_eq!(list.pairs().filter(|p| p.punct().is_some()).count(), 2);
+
+    let queue = list_punctuated::parse_queue.parse_str("1 2 3").unwrap();
+    assert_eq!(queue, VecDeque::from(vec![1, 2, 3]));
+
+    let set = list_punctuated::parse_set.parse_str("3, 1, 3").unwrap();
+    assert_eq!(set, BTreeSet::from([1, 3]));
+}
+
+/// An arena without `Default` or `len`, which only implements `Collect`.
+#[derive(Debug)]
+pub struct Arena {
+    names: Vec<String>,
+}
+
+impl syn_grammar::rt::Collect<String> for Arena {
+    fn empty() -> Self {
+        Arena {
+            names: Vec::with_capacity(8),
+        }
+    }
+
+    fn push(&mut self, item: String) {
+        self.names.push(item);
+    }
+}
+
+impl<P> syn_grammar::rt::ListSeparator<P> for Arena {
+    fn push_separator(&mut self, _separator: P) {}
+}
+
+#[test]
+fn test_custom_collect() {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    grammar! {
+        grammar list_collect {
+            use super::Arena;
+            use std::collections::{BTreeMap, BTreeSet};
+
+            pub rule names -> Arena = items:separated<Arena>(name, ",", min=2) -> { items }
+
+            rule name -> String = i:ident -> { i.to_string() }
+
+            pub rule map -> BTreeMap<String, i32> = items:separated<BTreeMap>(entry, ",") -> { items }
+
+            rule entry -> (String, i32) = k:ident ":" v:i32 -> { (k.to_string(), v) }
+
+            pub rule set -> BTreeSet<i32> = items:repeated<BTreeSet>(i32, min=3) -> { items }
+        }
+    }
+
+    let arena = list_collect::parse_names.parse_str("a, b, c").unwrap();
+    assert_eq!(arena.names, vec!["a", "b", "c"]);
+    let err = list_collect::parse_names.parse_str("a").unwrap_err();
+    assert!(err.to_string().contains("expected at least 2 items"));
+
+    let map = list_collect::parse_map
+        .parse_str("b: 2, a: 1, b: 3")
+        .unwrap();
+    assert_eq!(
+        map,
+        BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 3)])
+    );
+
+    // `min` counts the items parsed, not the ones the set keeps
+    let set = list_collect::parse_set.parse_str("1 1 1").unwrap();
+    assert_eq!(set, BTreeSet::from([1]));
+}
+
+#[test]
+fn test_delimited() {
+    grammar! {
+        grammar delimited_test {
+            pub rule bracketed -> Vec<i32> =
+                l:delimited("[", i32, ",", "]") -> { l.items }
+
+            pub rule angled -> Vec<i32> =
+                l:delimited("<", i32, ",", ">") -> { l.items }
+
+            pub rule spans -> bool =
+                l:delimited("(", i32, ",", ")") -> {
+                    l.open.start().column < l.close.start().column
+                }
+
+            pub rule recovered -> (Vec<i32>, i32) =
+                l:delimited("[", i32, ",", "]", recover=true) n:i32 -> { (l.items, n) }
+        }
+    }
+
+    delimited_test::parse_bracketed
+        .parse_str("[1, 2, 3]")
+        .test()
+        .assert_success_is(vec![1, 2, 3]);
+
+    delimited_test::parse_bracketed
+        .parse_str("[]")
+        .test()
+        .assert_success_is(Vec::<i32>::new());
+
+    delimited_test::parse_bracketed
+        .parse_str("[1, 2,]")
+        .test()
+        .assert_success_is(vec![1, 2]);
+
+    delimited_test::parse_bracketed
+        .parse_str("[1 2]")
+        .test()
+        .assert_failure_contains("expected `,` or `]`");
+
+    delimited_test::parse_angled
+        .parse_str("<1, 2,>")
+        .test()
+        .assert_success_is(vec![1, 2]);
+
+    delimited_test::parse_angled
+        .parse_str("<>")
+        .test()
+        .assert_success_is(Vec::<i32>::new());
+
+    delimited_test::parse_spans
+        .parse_str("(1, 2)")
+        .test()
+        .assert_success_is(true);
+
+    // A malformed item skips the rest of the list, keeping the items parsed before it
+    delimited_test::parse_recovered
+        .parse_str("[1, x, 3] 4")
+        .test()
+        .assert_success_is((vec![1], 4));
+}
+
+#[test]
+fn test_map() {
+    grammar! {
+        grammar map_test {
+            pub rule entries -> Vec<(String, i32)> =
+                m:map(ident, ":", i32) -> {
+                    m.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+                }
+
+            pub rule config -> std::collections::BTreeMap<String, i32> =
+                m:map<std::collections::BTreeMap>(key, "=", i32, sep=";", trailing=true, unique=true) -> { m }
+
+            rule key -> String = k:ident -> { k.to_string() }
+        }
+    }
+
+    map_test::parse_entries
+        .parse_str("a: 1, b: 2")
+        .test()
+        .assert_success_is(vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+    map_test::parse_entries
+        .parse_str("")
+        .test()
+        .assert_success_is(vec![]);
+
+    // Duplicates are kept unless `unique=true`
+    map_test::parse_entries
+        .parse_str("a: 1, a: 2")
+        .test()
+        .assert_success_is(vec![("a".to_string(), 1), ("a".to_string(), 2)]);
+
+    map_test::parse_entries
+        .parse_str("a: 1, b 2")
+        .test()
+        .assert_failure_contains("expected `:`");
+
+    map_test::parse_config
+        .parse_str("x = 1; y = 2;")
+        .test()
+        .assert_success_is(
+            [("x".to_string(), 1), ("y".to_string(), 2)]
+                .into_iter()
+                .collect(),
+        );
+
+    map_test::parse_config
+        .parse_str("x = 1; y = 2; x = 3")
+        .test()
+        .assert_failure_contains("duplicate key");
+}
+
+#[test]
+fn test_collect_annotations() {
+    grammar! {
+        grammar collect_test {
+            // Concatenates the text of the identifiers
+            pub rule path -> String = #[collect(String)] parts:ident+ -> { parts }
+
+            // Keeps the last value
+            pub rule last -> Option<i32> = #[collect(Option<i32>)] v:i32* -> { v }
+
+            // Collects into any `Default + Extend` type; other bindings stay `Vec`s
+            pub rule pairs -> (std::collections::BTreeSet<i32>, Vec<String>) =
+                (#[collect(std::collections::BTreeSet<i32>)] k:i32 "=" v:ident)* -> {
+                    (k, v.into_iter().map(|v| v.to_string()).collect())
+                }
+        }
+    }
+
+    collect_test::parse_path
+        .parse_str("foo bar baz")
+        .test()
+        .assert_success_is("foobarbaz".to_string());
+
+    collect_test::parse_last
+        .parse_str("1 2 3")
+        .test()
+        .assert_success_is(Some(3));
+    collect_test::parse_last
+        .parse_str("")
+        .test()
+        .assert_success_is(None);
+
+    let (keys, values) = collect_test::parse_pairs
+        .parse_str("2 = b 1 = a 2 = c")
+        .unwrap();
+    assert_eq!(keys.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(values, vec!["b", "a", "c"]);
+}
+
+#[test]
+fn test_terminated() {
+    grammar! {
+        grammar terminated_test {
+            rule stmt -> i32 = "let" v:i32 -> { v }
+
+            pub rule strict -> Vec<i32> = items:terminated(stmt, ";") -> { items }
+
+            pub rule lenient -> Vec<i32> = items:terminated(stmt, ";", required=false) -> { items }
+
+            // The last terminator may be omitted before the closing brace or the end of input
+            pub rule block -> Vec<i32> = {
+                items:terminated(stmt, ";", required=auto)
+            } -> { items }
+            pub rule program -> Vec<i32> = items:terminated(stmt, ";", required=auto) -> { items }
+
+            // Followed by "end", the list never reaches the end of the input
+            pub rule ended -> Vec<i32> =
+                items:terminated(stmt, ";", required=auto) "end" -> { items }
+        }
+    }
+
+    terminated_test::parse_strict
+        .parse_str("let 1; let 2;")
+        .test()
+        .assert_success_is(vec![1, 2]);
+    terminated_test::parse_strict
+        .parse_str("let 1; let 2")
+        .test()
+        .assert_failure_contains("expected `;`");
+
+    terminated_test::parse_lenient
+        .parse_str("let 1 let 2; let 3")
+        .test()
+        .assert_success_is(vec![1, 2, 3]);
+
+    terminated_test::parse_block
+        .parse_str("{ let 1; let 2 }")
+        .test()
+        .assert_success_is(vec![1, 2]);
+    terminated_test::parse_block
+        .parse_str("{ let 1; let 2; }")
+        .test()
+        .assert_success_is(vec![1, 2]);
+    terminated_test::parse_block
+        .parse_str("{ let 1 let 2 }")
+        .test()
+        .assert_failure_contains("expected `;`");
+    terminated_test::parse_program
+        .parse_str("let 1; let 2")
+        .test()
+        .assert_success_is(vec![1, 2]);
+
+    terminated_test::parse_ended
+        .parse_str("let 1; end")
+        .test()
+        .assert_success_is(vec![1]);
+    terminated_test::parse_ended
+        .parse_str("let 1 end")
+        .test()
+        .assert_failure_contains("expected `;`");
+}
+
+#[test]
+fn test_fold_left() {
+    grammar! {
+        grammar fold_left_test {
+            pub rule sum -> i64 =
+                n:fold_left(i64, op:oneof("+", "-"), |l, op, r| {
+                    if op.value == "+" { l + r } else { l - r }
+                }) -> { n }
+
+            // Without a peekable operator, each operator is parsed speculatively
+            pub rule path -> String =
+                p:fold_left(ident_text, sep, |l, _, r| format!("({}.{})", l, r)) -> { p }
+
+            rule ident_text -> String = i:ident -> { i.to_string() }
+            rule sep -> () = "." -> { () } | "::" -> { () }
+        }
+    }
+
+    fold_left_test::parse_sum
+        .parse_str("10 - 3 - 2 + 1")
+        .test()
+        .assert_success_is(6);
+    fold_left_test::parse_sum
+        .parse_str("7")
+        .test()
+        .assert_success_is(7);
+    fold_left_test::parse_sum
+        .parse_str("1 +")
+        .test()
+        .assert_failure_contains("expected integer literal");
+
+    fold_left_test::parse_path
+        .parse_str("a.b::c")
+        .test()
+        .assert_success_is("((a.b).c)".to_string());
+}
+
+#[test]
+fn test_fold_right() {
+    grammar! {
+        grammar fold_right_test {
+            pub rule power -> u64 = n:fold_right(u64, "^", |l, _, r| l.pow(r as u32)) -> { n }
+
+            pub rule cons -> String =
+                s:fold_right(ident_text, "::", |l, _, r| format!("({} {})", l, r)) -> { s }
+
+            rule ident_text -> String = i:ident -> { i.to_string() }
+        }
+    }
+
+    // 2 ^ (3 ^ 2), not (2 ^ 3) ^ 2
+    fold_right_test::parse_power
+        .parse_str("2 ^ 3 ^ 2")
+        .test()
+        .assert_success_is(512);
+    fold_right_test::parse_power
+        .parse_str("5")
+        .test()
+        .assert_success_is(5);
+
+    fold_right_test::parse_cons
+        .parse_str("a :: b :: c")
+        .test()
+        .assert_success_is("(a (b c))".to_string());
+}
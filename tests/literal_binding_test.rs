@@ -54,3 +54,49 @@ fn test_literal_span_binding() {
     // It returns the span
     assert_eq!(res.start().line, 1);
 }
+
+grammar! {
+    grammar contextual {
+        pub rule path -> Vec<String> =
+            first:segment rest:segment_tail* -> {
+                std::iter::once(first).chain(rest).collect()
+            }
+
+        rule segment_tail -> String = "::" s:segment -> { s }
+
+        rule segment -> String =
+            s:"self"ident -> { s.to_string() }
+          | c:"crate"ident -> { c.to_string() }
+          | i:ident -> { i.to_string() }
+    }
+}
+
+#[test]
+fn test_ident_literal_binding() {
+    let res = contextual::parse_path.parse_str("crate::a::self").unwrap();
+    assert_eq!(res, ["crate", "a", "self"]);
+
+    // The binding is a `syn::Ident`
+    let ident: syn::Ident = {
+        grammar! {
+            grammar self_ident {
+                pub rule main -> syn::Ident = s:"self"ident -> { s }
+            }
+        }
+        self_ident::parse_main.parse_str("self").unwrap()
+    };
+    assert_eq!(ident, "self");
+    assert_eq!(ident.span().start().column, 0);
+}
+
+#[test]
+fn test_multi_token_literal_binding() {
+    grammar! {
+        grammar arrow {
+            // `->` is parsed as two tokens, bound as a tuple
+            pub rule main -> proc_macro2::Span = a:"->" -> { a.0.span }
+        }
+    }
+    let span = arrow::parse_main.parse_str("->").unwrap();
+    assert_eq!(span.start().column, 0);
+}
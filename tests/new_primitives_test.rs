@@ -69,6 +69,53 @@ fn test_numeric_primitives() {
         .assert_success_is(10u64);
 }
 
+#[test]
+fn test_integer_overflow() {
+    grammar! {
+        grammar overflow_test {
+            pub rule strict -> u8 = v:u8 -> { v }
+            pub rule wrapping -> i8 = v:i8(wrapping=true) -> { v }
+            pub rule saturating -> u8 = v:u8(saturating=true) -> { v }
+            pub rule huge -> u128 = v:u128(saturating=true) -> { v }
+        }
+    }
+
+    overflow_test::parse_strict
+        .parse_str("300")
+        .test()
+        .assert_failure_contains("value 300 does not fit in u8 (0..=255)");
+    overflow_test::parse_strict
+        .parse_str("-1")
+        .test()
+        .assert_failure_contains("value -1 does not fit in u8 (0..=255)");
+
+    overflow_test::parse_wrapping
+        .parse_str("200")
+        .test()
+        .assert_success_is(-56i8);
+    overflow_test::parse_wrapping
+        .parse_str("-129")
+        .test()
+        .assert_success_is(127i8);
+
+    overflow_test::parse_saturating
+        .parse_str("300")
+        .test()
+        .assert_success_is(255u8);
+    overflow_test::parse_saturating
+        .parse_str("-5")
+        .test()
+        .assert_success_is(0u8);
+    overflow_test::parse_saturating
+        .parse_str("42")
+        .test()
+        .assert_success_is(42u8);
+    overflow_test::parse_huge
+        .parse_str("999999999999999999999999999999999999999999")
+        .test()
+        .assert_success_is(u128::MAX);
+}
+
 // --- Test Whitespace Primitive ---
 #[test]
 fn test_whitespace_primitive() {
@@ -140,3 +187,293 @@ fn test_whitespace_between_rules() {
         .test()
         .assert_success();
 }
+
+/// Scans `|>` written without whitespace between the puncts.
+fn pipe_op(cursor: syn::buffer::Cursor) -> Option<(String, syn::buffer::Cursor)> {
+    let (first, rest) = cursor.punct()?;
+    if first.as_char() != '|' || first.spacing() != proc_macro2::Spacing::Joint {
+        return None;
+    }
+    let (second, rest) = rest.punct()?;
+    (second.as_char() == '>').then(|| ("|>".to_string(), rest))
+}
+
+#[test]
+fn test_scan_primitive() {
+    grammar! {
+        grammar scan_test {
+            pub rule pipe -> (String, String, String) =
+                a:ident op:scan(pipe_op, "`|>`") b:ident -> {
+                    (a.to_string(), op, b.to_string())
+                }
+
+            pub rule default_message -> String = op:scan(pipe_op) -> { op }
+        }
+    }
+
+    scan_test::parse_pipe
+        .parse_str("a |> b")
+        .test()
+        .assert_success_is(("a".to_string(), "|>".to_string(), "b".to_string()));
+
+    scan_test::parse_pipe
+        .parse_str("a | > b")
+        .test()
+        .assert_failure_contains("expected `|>`");
+
+    scan_test::parse_default_message
+        .parse_str("+")
+        .test()
+        .assert_failure_contains("expected pipe_op");
+}
+
+#[test]
+fn test_punct_primitive() {
+    grammar! {
+        grammar punct_test {
+            pub rule op -> String = p:punct("+", "-", "*", "**") -> { p.value }
+
+            pub rule binary -> (i32, String, i32) =
+                a:i32 op:punct("+", "-", "**", "*") b:i32 -> { (a, op.value, b) }
+        }
+    }
+
+    punct_test::parse_op
+        .parse_str("**")
+        .test()
+        .assert_success_is("**".to_string());
+
+    punct_test::parse_op
+        .parse_str("-")
+        .test()
+        .assert_success_is("-".to_string());
+
+    punct_test::parse_binary
+        .parse_str("2 ** 3")
+        .test()
+        .assert_success_is((2, "**".to_string(), 3));
+
+    // Separated by whitespace, `* *` is not `**`
+    punct_test::parse_binary
+        .parse_str("2 * *3")
+        .test()
+        .assert_failure();
+
+    punct_test::parse_op
+        .parse_str("/")
+        .test()
+        .assert_failure_contains("expected one of `**`, `+`, `-`, `*`");
+}
+
+#[test]
+fn test_oneof_primitive() {
+    grammar! {
+        grammar oneof_test {
+            pub rule op -> String = o:oneof("+", "-", "->", "fn") -> { o.value }
+
+            pub rule arrow_or_minus -> (String, String) =
+                a:oneof("-", "->") b:ident -> { (a.value, b.to_string()) }
+        }
+    }
+
+    oneof_test::parse_op
+        .parse_str("+")
+        .test()
+        .assert_success_is("+".to_string());
+
+    oneof_test::parse_op
+        .parse_str("fn")
+        .test()
+        .assert_success_is("fn".to_string());
+
+    // The longer literal wins, regardless of its position in the list
+    oneof_test::parse_arrow_or_minus
+        .parse_str("-> x")
+        .test()
+        .assert_success_is(("->".to_string(), "x".to_string()));
+
+    oneof_test::parse_arrow_or_minus
+        .parse_str("- x")
+        .test()
+        .assert_success_is(("-".to_string(), "x".to_string()));
+
+    oneof_test::parse_op
+        .parse_str("*")
+        .test()
+        .assert_failure_contains("expected one of `->`, `+`, `-`, `fn`");
+}
+
+#[test]
+fn test_outer_attrs_filter() {
+    grammar! {
+        grammar attrs_test {
+            pub rule all -> usize = a:outer_attrs "struct" -> { a.len() }
+
+            pub rule docs -> Vec<String> =
+                a:outer_attrs("doc") "struct" -> {
+                    a.iter().map(|a| a.path().get_ident().unwrap().to_string()).collect()
+                }
+
+            pub rule renames -> usize = a:outer_attrs("serde::rename") "struct" -> { a.len() }
+        }
+    }
+
+    let input = r#"#[doc = "a"] #[derive(Debug)] #[doc = "b"] #[serde::rename = "x"] struct"#;
+
+    attrs_test::parse_all
+        .parse_str(input)
+        .test()
+        .assert_success_is(4);
+    attrs_test::parse_docs
+        .parse_str(input)
+        .test()
+        .assert_success_is(vec!["doc".to_string(), "doc".to_string()]);
+    attrs_test::parse_renames
+        .parse_str(input)
+        .test()
+        .assert_success_is(1);
+    attrs_test::parse_docs
+        .parse_str("struct")
+        .test()
+        .assert_success_is(Vec::<String>::new());
+}
+
+#[test]
+fn test_parse_in_primitive() {
+    grammar! {
+        grammar embedded {
+            // The placeholders of a format string, e.g. "{name}: {value}"
+            pub rule format -> Vec<String> = names:parse_in(lit_str, pieces) -> { names }
+
+            rule pieces -> Vec<String> = ps:piece* -> { ps.into_iter().flatten().collect() }
+
+            rule piece -> Option<String> =
+                { n:ident } -> { Some(n.to_string()) }
+              | _text:ident -> { None }
+              | ":" -> { None }
+
+            pub rule call -> i32 = "sum" s:parse_in(string, addition) -> { s }
+
+            rule addition -> i32 = l:i32 "+" r:i32 -> { l + r }
+        }
+    }
+
+    embedded::parse_format
+        .parse_str(r#""{name}: {value} and more""#)
+        .test()
+        .assert_success_is(vec!["name".to_string(), "value".to_string()]);
+    embedded::parse_call
+        .parse_str(r#"sum "1 + 2""#)
+        .test()
+        .assert_success_is(3);
+
+    // The contents must be parsed completely
+    embedded::parse_call
+        .parse_str(r#"sum "1 + 2 + 3""#)
+        .test()
+        .assert_failure_contains("unexpected token");
+    // Errors inside the contents are reported at the string
+    embedded::parse_format
+        .parse_str(r#""{1}""#)
+        .test()
+        .assert_failure_contains("expected identifier");
+    let err = embedded::parse_call
+        .parse_str(r#"sum "1 + x""#)
+        .unwrap_err();
+    assert!(err.to_string().contains("expected integer"), "{}", err);
+    assert_eq!(err.span().start().column, 4);
+    embedded::parse_call
+        .parse_str(r#"sum "1 + (""#)
+        .test()
+        .assert_failure_contains("the contents of the string are not valid tokens");
+}
+
+#[test]
+fn test_token_tree_primitives() {
+    grammar! {
+        grammar deferred {
+            pub rule macro_call -> (String, proc_macro2::Group) =
+                name:ident "!" body:balanced_group -> { (name.to_string(), body) }
+
+            pub rule attr -> String =
+                "#" [ tts:token_tree* ] -> { tts.into_iter().collect::<proc_macro2::TokenStream>().to_string() }
+
+            pub rule block -> String = b:balanced_group("{") -> { b.stream().to_string() }
+        }
+    }
+
+    let (name, body) = deferred::parse_macro_call
+        .parse_str("vec! [1, (2, 3)]")
+        .unwrap();
+    assert_eq!(name, "vec");
+    assert_eq!(body.delimiter(), proc_macro2::Delimiter::Bracket);
+    assert_eq!(body.stream().to_string(), "1 , (2 , 3)");
+    deferred::parse_macro_call
+        .parse_str("vec! x")
+        .test()
+        .assert_failure_contains("expected a group");
+
+    deferred::parse_attr
+        .parse_str("#[derive(Debug, Clone)]")
+        .test()
+        .assert_success_is("derive (Debug , Clone)".to_string());
+
+    deferred::parse_block
+        .parse_str("{ let x = 1; }")
+        .test()
+        .assert_success_is("let x = 1 ;".to_string());
+    deferred::parse_block
+        .parse_str("(x)")
+        .test()
+        .assert_failure_contains("expected `{ .. }`");
+}
+
+#[test]
+fn test_layout_primitives() {
+    grammar! {
+        grammar indented {
+            options { layout = significant; }
+
+            pub rule module -> Vec<String> =
+                first:stmt rest:next_stmt* -> { std::iter::once(first).chain(rest).collect() }
+
+            rule next_stmt -> String = newline s:stmt -> { s }
+
+            rule stmt -> String =
+                "if" c:ident ":" b:block -> { format!("if {} {{ {} }}", c, b.join("; ")) }
+              | "pass" -> { "pass".to_string() }
+              | name:ident "=" v:i32 -> { format!("{} = {}", name, v) }
+
+            rule block -> Vec<String> = indent s:module dedent -> { s }
+        }
+    }
+
+    let source = "x = 1\nif a:\n    y = 2\n    if b:\n        pass\nz = 3";
+    indented::parse_module
+        .parse_str(source)
+        .test()
+        .assert_success_is(vec![
+            "x = 1".to_string(),
+            "if a { y = 2; if b { pass } }".to_string(),
+            "z = 3".to_string(),
+        ]);
+    // The outermost level is the column of the first token, and blocks end with the input
+    indented::parse_module
+        .parse_str("  if a:\n    if b:\n      pass")
+        .test()
+        .assert_success_is(vec!["if a { if b { pass } }".to_string()]);
+
+    // Statements on one line are not separated
+    indented::parse_module
+        .parse_str("x = 1 y = 2")
+        .test()
+        .assert_failure();
+    indented::parse_module
+        .parse_str("if a:\npass")
+        .test()
+        .assert_failure_contains("expected an indented line");
+    indented::parse_module
+        .parse_str("if a:\n    x = 1\n  y = 2")
+        .test()
+        .assert_failure_contains("unindent does not match any outer indentation level");
+}
@@ -86,3 +86,40 @@ fn test_not_complex() {
         .test()
         .assert_failure_contains("unexpected match");
 }
+
+// --- Test Follows (Lookbehind) ---
+#[test]
+fn test_follows() {
+    grammar! {
+        grammar follows_test {
+            // A `?` suffix directly after an identifier, e.g. `x?` but not `x ?` or `1?`
+            pub rule main -> String = t:target follows(ident) "?" -> { t }
+            rule target -> String = i:ident -> { i.to_string() } | n:i32 -> { n.to_string() }
+
+            pub rule in_brackets -> String = [i:ident follows(ident) "?"] -> { i.to_string() }
+        }
+    }
+
+    follows_test::parse_main
+        .parse_str("x?")
+        .test()
+        .assert_success_is("x".to_string());
+    follows_test::parse_main
+        .parse_str("x ?")
+        .test()
+        .assert_failure_contains("unexpected whitespace before this token");
+    follows_test::parse_main
+        .parse_str("1?")
+        .test()
+        .assert_failure_contains("the preceding tokens do not match the `follows` pattern");
+
+    // The lookbehind starts at the opening bracket
+    follows_test::parse_in_brackets
+        .parse_str("[x?]")
+        .test()
+        .assert_success_is("x".to_string());
+    follows_test::parse_in_brackets
+        .parse_str("[x ?]")
+        .test()
+        .assert_failure_contains("unexpected whitespace before this token");
+}
@@ -103,3 +103,246 @@ fn test_attempt_recover_behavior() {
         .test()
         .assert_success_is("recovered".to_string());
 }
+
+#[test]
+fn test_iter_items_with_lazy_errors() {
+    use syn::parse::{ParseStream, Parser};
+
+    grammar! {
+        grammar stmts {
+            #[iter(";")]
+            pub rule stmt -> (String, i32) = name:ident "=" v:i32 -> { (name.to_string(), v) }
+        }
+    }
+
+    let items = (|input: ParseStream| {
+        Ok(stmts::iter_stmt(input)
+            .map(|item| item.map_err(|e| e.to_string()))
+            .collect::<Vec<_>>())
+    })
+    .parse_str("a = 1; b = x y; c = 3;")
+    .unwrap();
+
+    assert_eq!(items.len(), 3);
+    assert_eq!(items[0], Ok(("a".to_string(), 1)));
+    assert!(items[1]
+        .as_ref()
+        .unwrap_err()
+        .contains("expected integer literal"));
+    assert_eq!(items[2], Ok(("c".to_string(), 3)));
+
+    // Items are parsed lazily: the first one is available before the broken one is reached
+    let first = (|input: ParseStream| {
+        let first = stmts::iter_stmt(input).next();
+        while !input.is_empty() {
+            input.parse::<proc_macro2::TokenTree>()?;
+        }
+        Ok(first)
+    })
+    .parse_str("a = 1; b = ;")
+    .unwrap();
+    assert_eq!(first.unwrap().unwrap(), ("a".to_string(), 1));
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Let(String, i32),
+    Error,
+}
+
+#[test]
+fn test_rule_level_recovery() {
+    grammar! {
+        grammar recovery_rule {
+            use super::Item;
+
+            pub rule block -> Vec<Item> = { items:item* } -> { items }
+
+            #[recover(sync = ";", placeholder = Item::Error)]
+            rule item -> Item =
+                "let" n:ident "=" v:i32 ";" -> { Item::Let(n.to_string(), v) }
+
+            pub rule values -> Vec<i32> = vs:value* -> { vs }
+
+            // Recovered values fall back to `Default::default()`
+            #[recover(sync = ",")]
+            rule value -> i32 = "+" v:i32 "," -> { v }
+        }
+    }
+
+    recovery_rule::parse_block
+        .parse_str("{ let a = 1; let b = ; let c = 3; }")
+        .test()
+        .assert_success_is(vec![
+            Item::Let("a".to_string(), 1),
+            Item::Error,
+            Item::Let("c".to_string(), 3),
+        ]);
+
+    // The failed item is skipped including its sync token, even at the end of the input
+    recovery_rule::parse_block
+        .parse_str("{ let a = 1; let = 2 }")
+        .test()
+        .assert_success_is(vec![Item::Let("a".to_string(), 1), Item::Error]);
+
+    // A failure at the first token is not recovered, so the repetition ends
+    recovery_rule::parse_block
+        .parse_str("{ let a = 1; oops; }")
+        .test()
+        .assert_failure();
+
+    recovery_rule::parse_values
+        .parse_str("+1, + x,")
+        .test()
+        .assert_success_is(vec![1, 0]);
+    recovery_rule::parse_values
+        .parse_str("+1, + x, +3,")
+        .test()
+        .assert_success_is(vec![1, 0, 3]);
+}
+
+#[test]
+fn test_diagnose_collects_recovered_errors() {
+    grammar! {
+        grammar recovery_diag {
+            use super::Item;
+
+            options { diagnose = true; }
+
+            #[complete]
+            pub rule items -> Vec<Item> = items:item* -> { items }
+
+            #[recover(sync = ";", placeholder = Item::Error)]
+            rule item -> Item =
+                "let" n:ident "=" v:i32 ";" -> { Item::Let(n.to_string(), v) }
+
+            // The first alternative recovers, then fails and is backtracked
+            pub rule choice -> i32 =
+                "a" _v:recover(num, ",") "," "x" -> { 1 }
+              | "a" "b" "," "y" -> { 2 }
+
+            rule num -> i32 = v:i32 -> { v }
+        }
+    }
+
+    fn diagnose<T>(
+        f: fn(syn::parse::ParseStream) -> (Option<T>, Vec<syn::Error>),
+        src: &str,
+    ) -> (Option<T>, Vec<String>) {
+        let (val, errors) = (|input: syn::parse::ParseStream| {
+            let res = f(input);
+            // A failed parse leaves tokens behind
+            input.parse::<proc_macro2::TokenStream>()?;
+            Ok(res)
+        })
+        .parse_str(src)
+        .unwrap();
+        (val, errors.iter().map(|e| e.to_string()).collect())
+    }
+
+    let (items, errors) = diagnose(
+        recovery_diag::diagnose_items,
+        "let a = ; let b = 2; let = 3;",
+    );
+    assert_eq!(
+        items,
+        Some(vec![
+            Item::Error,
+            Item::Let("b".to_string(), 2),
+            Item::Error
+        ])
+    );
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(
+        errors[0].contains("expected integer literal"),
+        "{:?}",
+        errors
+    );
+    assert!(errors[1].contains("expected identifier"), "{:?}", errors);
+
+    // Without errors, nothing is reported
+    let (items, errors) = diagnose(recovery_diag::diagnose_items, "let a = 1;");
+    assert_eq!(items, Some(vec![Item::Let("a".to_string(), 1)]));
+    assert!(errors.is_empty());
+
+    // A failed parse reports the recovered errors and the one that stopped it
+    let (items, errors) = diagnose(recovery_diag::diagnose_items, "let a = ; oops;");
+    assert_eq!(items, None);
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(
+        errors[0].contains("expected integer literal"),
+        "{:?}",
+        errors
+    );
+    assert!(errors[1].contains("expected end of"), "{:?}", errors);
+
+    // Recoveries inside backtracked alternatives are discarded
+    let (value, errors) = diagnose(recovery_diag::diagnose_choice, "a b , y");
+    assert_eq!(value, Some(2));
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    let combined = syn_grammar::rt::combine_errors(
+        diagnose(recovery_diag::diagnose_items, "let a = ; let = 3;")
+            .1
+            .into_iter()
+            .map(|e| syn::Error::new(proc_macro2::Span::call_site(), e)),
+    )
+    .unwrap();
+    assert_eq!(combined.into_iter().count(), 2);
+}
+
+#[test]
+fn test_lenient_parse_with_warnings() {
+    grammar! {
+        grammar lenient {
+            use super::Item;
+
+            options { lenient = true; }
+
+            #[complete]
+            pub rule items -> Vec<Item> = items:item* -> { items }
+
+            #[recover(sync = ";", placeholder = Item::Error)]
+            rule item -> Item =
+                "let" n:ident "=" v:i32 ";" -> { Item::Let(n.to_string(), v) }
+              | old_let n:ident "=" v:i32 ";" -> { Item::Let(n.to_string(), v) }
+              | "var" n:ident ":=" v:i32 ";" -> { Item::Let(n.to_string(), v) }
+
+            // Accepted, but reported as a warning
+            rule old_let -> () = k:"var" -> {
+                ctx.warn(syn::Error::new(k.span, "`var` is deprecated, use `let`"));
+            }
+        }
+    }
+
+    fn lenient(src: &str) -> syn::Result<(Vec<Item>, Vec<String>)> {
+        let (items, diagnostics) = lenient::lenient_items.parse_str(src)?;
+        Ok((items, diagnostics.iter().map(|e| e.to_string()).collect()))
+    }
+
+    // Warnings and recovered errors are reported in input order
+    let (items, diagnostics) = lenient("let a = ; var b = 2; let c = 3;").unwrap();
+    assert_eq!(
+        items,
+        vec![
+            Item::Error,
+            Item::Let("b".to_string(), 2),
+            Item::Let("c".to_string(), 3)
+        ]
+    );
+    assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    assert!(
+        diagnostics[0].contains("expected integer literal"),
+        "{:?}",
+        diagnostics
+    );
+    assert_eq!(diagnostics[1], "`var` is deprecated, use `let`");
+
+    // The warning of the backtracked alternative is discarded
+    let (items, diagnostics) = lenient("var x := 1;").unwrap();
+    assert_eq!(items, vec![Item::Let("x".to_string(), 1)]);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    // A fatal error fails the parse
+    assert!(lenient("let a = 1; oops").is_err());
+}
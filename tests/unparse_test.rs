@@ -0,0 +1,110 @@
+use syn::parse::Parser;
+use syn_grammar::{grammar, Identifier};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(u64),
+    Var(Identifier),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Call { name: Identifier, args: Vec<Expr> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Let {
+        name: Identifier,
+        ty: Option<Identifier>,
+        value: Expr,
+    },
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub stmts: Vec<Stmt>,
+}
+
+grammar! {
+    grammar lang {
+        pub rule program -> Program = stmts:stmt* -> Program { stmts }
+
+        rule stmt -> Stmt =
+            "let" name:ident (":" ty:ident)? "=" value:expr ";" -> Stmt::Let { name, ty, value }
+          | e:expr ";" -> { Stmt::Expr(e) }
+
+        pub rule expr -> Expr =
+            l:expr "+" r:term -> { Expr::Add(Box::new(l), Box::new(r)) }
+          | t:term -> { t }
+
+        rule term -> Expr =
+            l:term "*" r:atom -> { Expr::Mul(Box::new(l), Box::new(r)) }
+          | a:atom -> { a }
+
+        rule atom -> Expr =
+            n:u64 -> { Expr::Num(n) }
+          | name:ident paren(args:separated(expr, ",")) -> Expr::Call { name, args }
+          | v:ident -> { Expr::Var(v) }
+          | paren(e:expr) -> { e }
+
+        // The expression is written in a string
+        pub rule quoted -> Stmt = "quote" e:parse_in(lit_str, expr) ";" -> { Stmt::Expr(e) }
+
+        pub rule number -> Expr = n:u64 -> { Expr::Num(n) }
+
+        // Computes its value, so it cannot be printed
+        pub rule difference -> u64 = a:u64 "-" b:u64 -> { a - b }
+    }
+}
+
+fn roundtrip(input: &str) -> String {
+    let program = lang::parse_program.parse_str(input).unwrap();
+    let tokens = lang::unparse_program(&program).unwrap();
+    let reparsed = lang::parse_program.parse2(tokens.clone()).unwrap();
+    assert_eq!(reparsed, program, "`{}` printed as `{}`", input, tokens);
+    tokens.to_string()
+}
+
+#[test]
+fn test_unparse_roundtrip() {
+    assert_eq!(roundtrip("1 + 2 * x;"), "1 + 2 * x ;");
+    assert_eq!(
+        roundtrip("let y: T = max(1, (2 + 3)) * 4;"),
+        "let y : T = max (1 , 2 + 3) * 4 ;"
+    );
+    assert_eq!(roundtrip("let y = f(); g(a);"), "let y = f () ; g (a) ;");
+    assert_eq!(roundtrip(""), "");
+}
+
+#[test]
+fn test_unparse_adds_parentheses() {
+    // `(1 + 2) * 3`, built without parsing
+    let sum = Expr::Add(Box::new(Expr::Num(1)), Box::new(Expr::Num(2)));
+    let product = Expr::Mul(Box::new(sum), Box::new(Expr::Num(3)));
+    let tokens = lang::unparse_expr(&product).unwrap();
+    assert_eq!(tokens.to_string(), "(1 + 2) * 3");
+    assert_eq!(lang::parse_expr.parse2(tokens).unwrap(), product);
+}
+
+#[test]
+fn test_unparse_string_contents() {
+    let stmt = lang::parse_quoted
+        .parse_str(r#"quote "f(1) * x";"#)
+        .unwrap();
+    let tokens = lang::unparse_quoted(&stmt).unwrap();
+    assert_eq!(tokens.to_string(), r#"quote "f (1) * x" ;"#);
+    assert_eq!(lang::parse_quoted.parse2(tokens).unwrap(), stmt);
+}
+
+#[test]
+fn test_unparse_unknown_value() {
+    assert_eq!(
+        lang::unparse_number(&Expr::Num(7)).unwrap().to_string(),
+        "7"
+    );
+    assert!(lang::unparse_number(&Expr::Var(Identifier::new(
+        "x",
+        proc_macro2::Span::call_site()
+    )))
+    .is_none());
+}
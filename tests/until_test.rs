@@ -82,3 +82,50 @@ fn test_until_group() {
     let res = parse_until_group_wrapper(input).unwrap();
     assert!(res.ends_with("b"));
 }
+
+grammar! {
+    grammar until_parse_test {
+        use std::collections::VecDeque;
+
+        pub rule block -> Vec<(String, i32)> =
+            "begin" stmts:until_parse(stmt, "end") "end" -> { stmts }
+
+        rule stmt -> (String, i32) = n:ident "=" v:i32 ";" -> { (n.to_string(), v) }
+
+        // Any list container works, and the stop pattern can be a rule
+        pub rule words -> VecDeque<String> =
+            w:until_parse<VecDeque>(word, stop) stop -> { w }
+
+        rule word -> String = i:ident -> { i.to_string() }
+
+        rule stop -> () = "." -> {}
+    }
+}
+
+#[test]
+fn test_until_parse() {
+    let block = until_parse_test::parse_block
+        .parse_str("begin a = 1; b = 2; end")
+        .unwrap();
+    assert_eq!(block, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+    assert_eq!(
+        until_parse_test::parse_block
+            .parse_str("begin end")
+            .unwrap(),
+        vec![]
+    );
+
+    // A statement that does not parse reports its own error instead of stopping the list
+    let err = until_parse_test::parse_block
+        .parse_str("begin a = 1; b 2; end")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Error in rule 'stmt': expected `=`");
+    // Without the stop pattern, the list ends with the input
+    let err = until_parse_test::parse_block
+        .parse_str("begin a = 1;")
+        .unwrap_err();
+    assert_eq!(err.to_string(), "unexpected end of input, expected `end`");
+
+    let words = until_parse_test::parse_words.parse_str("x y z .").unwrap();
+    assert_eq!(words, ["x", "y", "z"]);
+}
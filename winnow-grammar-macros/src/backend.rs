@@ -0,0 +1,147 @@
+use syn_grammar_model::{Backend, BuiltIn};
+
+/// The built-ins of the winnow backend: the portable built-ins with the return types of the syn
+/// backend where they are portable, and `eof`. Peeks are token types of the syn backend, so none are declared.
+pub struct WinnowBackend;
+
+impl Backend for WinnowBackend {
+    fn get_builtins() -> &'static [BuiltIn] {
+        &[
+            BuiltIn {
+                name: "ident",
+                return_type: "syn_grammar_model::model::types::Identifier",
+                peek: None,
+            },
+            BuiltIn {
+                name: "string",
+                return_type: "syn_grammar_model::model::types::StringLiteral",
+                peek: None,
+            },
+            BuiltIn {
+                name: "char",
+                return_type: "char",
+                peek: None,
+            },
+            BuiltIn {
+                name: "bool",
+                return_type: "bool",
+                peek: None,
+            },
+            BuiltIn {
+                name: "i8",
+                return_type: "i8",
+                peek: None,
+            },
+            BuiltIn {
+                name: "i16",
+                return_type: "i16",
+                peek: None,
+            },
+            BuiltIn {
+                name: "i32",
+                return_type: "i32",
+                peek: None,
+            },
+            BuiltIn {
+                name: "i64",
+                return_type: "i64",
+                peek: None,
+            },
+            BuiltIn {
+                name: "i128",
+                return_type: "i128",
+                peek: None,
+            },
+            BuiltIn {
+                name: "isize",
+                return_type: "isize",
+                peek: None,
+            },
+            BuiltIn {
+                name: "u8",
+                return_type: "u8",
+                peek: None,
+            },
+            BuiltIn {
+                name: "u16",
+                return_type: "u16",
+                peek: None,
+            },
+            BuiltIn {
+                name: "u32",
+                return_type: "u32",
+                peek: None,
+            },
+            BuiltIn {
+                name: "u64",
+                return_type: "u64",
+                peek: None,
+            },
+            BuiltIn {
+                name: "u128",
+                return_type: "u128",
+                peek: None,
+            },
+            BuiltIn {
+                name: "usize",
+                return_type: "usize",
+                peek: None,
+            },
+            BuiltIn {
+                name: "f32",
+                return_type: "f32",
+                peek: None,
+            },
+            BuiltIn {
+                name: "f64",
+                return_type: "f64",
+                peek: None,
+            },
+            BuiltIn {
+                name: "hex_literal",
+                return_type: "u64",
+                peek: None,
+            },
+            BuiltIn {
+                name: "oct_literal",
+                return_type: "u64",
+                peek: None,
+            },
+            BuiltIn {
+                name: "bin_literal",
+                return_type: "u64",
+                peek: None,
+            },
+            BuiltIn {
+                name: "alpha",
+                return_type: "String",
+                peek: None,
+            },
+            BuiltIn {
+                name: "digit",
+                return_type: "String",
+                peek: None,
+            },
+            BuiltIn {
+                name: "alphanumeric",
+                return_type: "String",
+                peek: None,
+            },
+            BuiltIn {
+                name: "hex_digit",
+                return_type: "String",
+                peek: None,
+            },
+            BuiltIn {
+                name: "oct_digit",
+                return_type: "String",
+                peek: None,
+            },
+            BuiltIn {
+                name: "eof",
+                return_type: "()",
+                peek: None,
+            },
+        ]
+    }
+}
@@ -0,0 +1,780 @@
+use crate::backend::WinnowBackend;
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use std::collections::HashSet;
+use syn::spanned::Spanned as _;
+use syn::{Lit, Result};
+use syn_grammar_model::{analysis, model::*, parser, Backend};
+
+/// Rule attributes passed through to the generated function.
+const FORWARDED_ATTRS: &[&str] = &["cfg", "doc", "allow", "expect", "warn", "deny"];
+
+/// Rule attributes that do not change a winnow parser: rules always backtrack only as far as
+/// needed, inlining is left to the compiler, and `#[complete]` is the default of `rt::parse`.
+const IGNORED_ATTRS: &[&str] = &["no_backtrack", "inline_rule", "complete"];
+
+/// The parametric built-ins of ADR 004 implemented by this backend.
+const LIST_BUILTINS: &[&str] = &[
+    "separated",
+    "repeated",
+    "delimited",
+    "terminated",
+    "until_parse",
+    "map",
+    "skip",
+    "parse_in",
+];
+
+struct Context {
+    /// Names of the rules of the grammar, which take precedence over built-ins.
+    rule_names: HashSet<String>,
+}
+
+impl Context {
+    fn is_builtin(&self, name: &str, builtins: &[&str]) -> bool {
+        !self.rule_names.contains(name) && builtins.contains(&name)
+    }
+}
+
+fn unsupported(span: Span, what: &str) -> syn::Error {
+    syn::Error::new(
+        span,
+        format!("{} is not supported by the winnow backend", what),
+    )
+}
+
+/// Generates the module of a grammar: a function `parse_<rule>` for every rule, taking the
+/// input and the parameters of the rule.
+pub fn generate(grammar: &GrammarDefinition) -> Result<TokenStream> {
+    if let Some(parent) = &grammar.inherits {
+        return Err(unsupported(parent.span(), "grammar inheritance"));
+    }
+    if grammar.options.cst {
+        return Err(unsupported(grammar.name.span(), "`cst = true`"));
+    }
+    if grammar.options.layout {
+        return Err(unsupported(grammar.name.span(), "`layout = significant`"));
+    }
+    // The entry points of these options are built on the recovery and state of the syn runtime
+    let entry_points = [
+        (grammar.options.diagnose, "`diagnose = true`"),
+        (grammar.options.lenient, "`lenient = true`"),
+        (grammar.options.incremental, "`incremental = true`"),
+    ];
+    if let Some((_, option)) = entry_points.iter().find(|(set, _)| *set) {
+        return Err(unsupported(grammar.name.span(), option));
+    }
+
+    let cx = Context {
+        rule_names: grammar.rules.iter().map(|r| r.name.to_string()).collect(),
+    };
+    let rules = grammar
+        .rules
+        .iter()
+        .map(|rule| generate_rule(rule, &cx))
+        .collect::<Result<Vec<_>>>()?;
+    let name = &grammar.name;
+    let uses = &grammar.uses;
+    // Makes the compiler rebuild the grammar when an imported file changes
+    let dependencies = grammar.dependencies.iter().map(|path| {
+        let path = path.to_string_lossy();
+        quote! { const _: &[u8] = include_bytes!(#path); }
+    });
+
+    Ok(quote! {
+        pub mod #name {
+            #![allow(unused_imports, unused_variables, unused_mut, dead_code, unused_braces, unused_parens)]
+            #![allow(private_interfaces, clippy::all)]
+            use super::*;
+            use winnow_grammar::rt;
+            #(#uses)*
+            #(#dependencies)*
+            #(#rules)*
+        }
+    })
+}
+
+fn generate_rule(rule: &Rule, cx: &Context) -> Result<TokenStream> {
+    let name = &rule.name;
+    if rule.precedence.is_some() {
+        return Err(unsupported(name.span(), "`precedence`"));
+    }
+    if !rule.generics.params.is_empty() {
+        return Err(unsupported(rule.generics.span(), "a generic rule"));
+    }
+    let mut attrs = Vec::new();
+    for attr in &rule.attrs {
+        if FORWARDED_ATTRS.iter().any(|a| attr.path().is_ident(a)) {
+            attrs.push(attr);
+        } else if !IGNORED_ATTRS.iter().any(|a| attr.path().is_ident(a)) {
+            return Err(unsupported(attr.span(), "this attribute"));
+        }
+    }
+    let params = rule
+        .params
+        .iter()
+        .map(|(param, ty)| match ty {
+            Some(ty) => Ok(quote!(#param: #ty)),
+            None => Err(unsupported(param.span(), "a parameter without type")),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let vis = match &rule.vis {
+        syn::Visibility::Inherited if name == "main" => quote!(pub),
+        vis => quote!(#vis),
+    };
+    let fn_name = format_ident!("parse_{}", name);
+    let ret_type = &rule.return_type;
+    let body = generate_rule_body(rule, cx)?;
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis fn #fn_name(input: &mut rt::Input<'_>, #(#params),*) -> rt::PResult<#ret_type> {
+            #body
+        }
+    })
+}
+
+/// The body of a rule. Direct left recursion (`expr = l:expr "+" r:term | t:term`) parses a
+/// base variant and then extends it with the recursive variants as long as one matches,
+/// binding the value so far to the binding of the recursive call.
+fn generate_rule_body(rule: &Rule, cx: &Context) -> Result<TokenStream> {
+    let (recursive, base) = analysis::split_left_recursive(&rule.name, &rule.variants);
+    if recursive.is_empty() {
+        return generate_alternatives(rule, &base, cx);
+    }
+    if base.is_empty() {
+        return Err(syn::Error::new(
+            rule.name.span(),
+            "a left-recursive rule needs a variant that does not start with the rule",
+        ));
+    }
+
+    let base_logic = generate_alternatives(rule, &base, cx)?;
+    let attempts = recursive
+        .iter()
+        .map(|variant| {
+            let (first, rest) = variant.pattern.split_first().expect("recursive call");
+            let bind = match first {
+                ModelPattern::RuleCall {
+                    binding: Some(b), ..
+                } => quote!(let #b = lhs.clone();),
+                _ => quote!(),
+            };
+            let logic = generate_variant(rule, variant, rest, cx)?;
+            Ok(quote! {
+                if let Some(_value) = _alts.attempt(input, |input| { #bind #logic })? {
+                    lhs = _value;
+                    if rt::offset(input) == _before {
+                        break;
+                    }
+                    continue;
+                }
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        let mut lhs = rt::group(input, |input| { #base_logic })?;
+        loop {
+            let _before = rt::offset(input);
+            let mut _alts = rt::Alternatives::new();
+            #(#attempts)*
+            break;
+        }
+        Ok(lhs)
+    })
+}
+
+/// Attempts the variants in order, returning the value of the first that matches.
+fn generate_alternatives(
+    rule: &Rule,
+    variants: &[&RuleVariant],
+    cx: &Context,
+) -> Result<TokenStream> {
+    if let [variant] = variants {
+        return generate_variant(rule, variant, &variant.pattern, cx);
+    }
+    let attempts = variants
+        .iter()
+        .map(|variant| {
+            let logic = generate_variant(rule, variant, &variant.pattern, cx)?;
+            Ok(quote! {
+                if let Some(_value) = _alts.attempt(input, |input| { #logic })? {
+                    return Ok(_value);
+                }
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(quote! {
+        let mut _alts = rt::Alternatives::new();
+        #(#attempts)*
+        _alts.fail(input)
+    })
+}
+
+/// The steps of a variant, its guard and its action, evaluating to the result of the variant.
+/// `$span` in the action is the byte range of the input the variant consumed.
+fn generate_variant(
+    rule: &Rule,
+    variant: &RuleVariant,
+    patterns: &[ModelPattern],
+    cx: &Context,
+) -> Result<TokenStream> {
+    let steps = generate_sequence(patterns, cx)?;
+    let (span_start, span_end) = match parser::uses_span_var(&variant.action) {
+        true => (
+            quote! { rt::skip_whitespace(input); let _span_start = rt::offset(input); },
+            quote! { let _span = _span_start..rt::offset(input); },
+        ),
+        false => (quote!(), quote!()),
+    };
+    let guard = variant.guard.as_ref().map(|guard| {
+        let condition = guard.to_string();
+        quote! {
+            if !{ #guard } {
+                return Err(rt::guard_failed(input, #condition));
+            }
+        }
+    });
+    let action = replace_span_var(variant.action.clone());
+    let value = match &rule.throws {
+        Some(error_ty) => {
+            let ret_type = &rule.return_type;
+            quote! {
+                match (|| -> ::core::result::Result<#ret_type, #error_ty> { Ok({ #action }) })() {
+                    Ok(_value) => Ok(_value),
+                    Err(_error) => Err(rt::action_failed(input, _error)),
+                }
+            }
+        }
+        None => quote!(Ok({ #action })),
+    };
+    Ok(quote! {
+        #span_start
+        #steps
+        #span_end
+        #guard
+        #value
+    })
+}
+
+/// Replaces `$span` with the variable holding the span of the variant.
+fn replace_span_var(tokens: TokenStream) -> TokenStream {
+    let mut out = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(tt) = tokens.next() {
+        match tt {
+            TokenTree::Punct(p) if p.as_char() == '$' => match tokens.peek() {
+                Some(TokenTree::Ident(i)) if i == "span" => {
+                    let span = i.span();
+                    tokens.next();
+                    out.push(TokenTree::Ident(syn::Ident::new("_span", span)));
+                }
+                _ => out.push(TokenTree::Punct(p)),
+            },
+            TokenTree::Group(g) => {
+                let mut group =
+                    proc_macro2::Group::new(g.delimiter(), replace_span_var(g.stream()));
+                group.set_span(g.span());
+                out.push(TokenTree::Group(group));
+            }
+            tt => out.push(tt),
+        }
+    }
+    out.into_iter().collect()
+}
+
+/// The steps of a sequence, binding the values of its patterns. The patterns after a cut are
+/// committed: their failure does not backtrack to the other alternatives.
+fn generate_sequence(patterns: &[ModelPattern], cx: &Context) -> Result<TokenStream> {
+    if let Some(cut) = analysis::find_cut(patterns) {
+        let pre = generate_sequence(cut.pre_cut, cx)?;
+        let post = generate_sequence(cut.post_cut, cx)?;
+        let bindings = analysis::collect_bindings(cut.post_cut);
+        return Ok(quote! {
+            #pre
+            let (#(#bindings),*) = rt::commit(input, |input| {
+                #post
+                Ok((#(#bindings),*))
+            })?;
+        });
+    }
+    let steps = patterns
+        .iter()
+        .map(|p| generate_pattern(p, cx))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(quote! { #(#steps)* })
+}
+
+/// `let` statements binding the value of `value`, a combinator returning the bindings of a
+/// pattern, to the bindings.
+fn bind(bindings: &[syn::Ident], value: TokenStream) -> TokenStream {
+    quote! { let (#(#bindings),*) = #value?; }
+}
+
+/// A closure parsing `inner` and returning its bindings, for the combinators of `rt`.
+fn closure(inner: &[ModelPattern], cx: &Context) -> Result<TokenStream> {
+    let steps = generate_sequence(inner, cx)?;
+    let bindings = analysis::collect_bindings(inner);
+    Ok(quote! {
+        |input| {
+            #steps
+            Ok((#(#bindings),*))
+        }
+    })
+}
+
+/// Like [`closure`], but returning the value of a rule call or literal without binding.
+fn value_closure(pattern: &ModelPattern, cx: &Context) -> Result<TokenStream> {
+    closure(&[bind_value(pattern, "_value")], cx)
+}
+
+/// Injects the binding `name` into `pattern` if it is a rule call or literal without one.
+fn bind_value(pattern: &ModelPattern, name: &str) -> ModelPattern {
+    let binding = Some(format_ident!("{}", name));
+    match pattern {
+        ModelPattern::RuleCall {
+            binding: None,
+            module,
+            rule_name,
+            generics,
+            args,
+        } => ModelPattern::RuleCall {
+            binding,
+            module: module.clone(),
+            rule_name: rule_name.clone(),
+            generics: generics.clone(),
+            args: args.clone(),
+        },
+        ModelPattern::Lit { binding: None, lit } => ModelPattern::Lit {
+            binding,
+            lit: lit.clone(),
+        },
+        _ => pattern.clone(),
+    }
+}
+
+fn generate_pattern(pattern: &ModelPattern, cx: &Context) -> Result<TokenStream> {
+    match pattern {
+        ModelPattern::Cut(_) => Ok(quote!()),
+        ModelPattern::Lit { binding, lit } => {
+            let value = generate_literal(lit)?;
+            Ok(match binding {
+                Some(b) => quote! { let #b = #value?; },
+                None => quote! { let _ = #value?; },
+            })
+        }
+        ModelPattern::RuleCall {
+            binding,
+            rule_name,
+            generics,
+            args,
+            ..
+        } => {
+            let name = rule_name.to_string();
+            let value = if cx.is_builtin(&name, LIST_BUILTINS) {
+                generate_list_builtin(rule_name, generics, args, cx)?
+            } else {
+                if !generics.is_empty() {
+                    return Err(unsupported(rule_name.span(), "a generic rule call"));
+                }
+                let builtins: Vec<&str> = WinnowBackend::get_builtins()
+                    .iter()
+                    .map(|b| b.name)
+                    .collect();
+                if cx.is_builtin(&name, &builtins) && args.is_empty() {
+                    quote!(rt::#rule_name(input))
+                } else {
+                    let f = format_ident!("parse_{}", rule_name);
+                    let args = args
+                        .iter()
+                        .map(|arg| match arg {
+                            Argument::Positional(p) | Argument::Named(_, p) => match p {
+                                ModelPattern::Lit { lit, .. } => Ok(quote!(#lit)),
+                                ModelPattern::RuleCall {
+                                    rule_name, args, ..
+                                } if args.is_empty() => Ok(quote!(#rule_name)),
+                                _ => Err(unsupported(p.span(), "a pattern as argument")),
+                            },
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    quote!(#f(input, #(#args),*))
+                }
+            };
+            Ok(match binding {
+                Some(b) => quote! { let #b = #value?; },
+                None => quote! { let _ = #value?; },
+            })
+        }
+        ModelPattern::Group(alts, _) => {
+            if let [(seq, _)] = alts.as_slice() {
+                return generate_sequence(seq, cx);
+            }
+            let attempts = alts
+                .iter()
+                .map(|(seq, _)| {
+                    let steps = generate_sequence(seq, cx)?;
+                    let bindings = analysis::collect_bindings(seq);
+                    Ok(quote! {
+                        if let Some(_value) = _alts.attempt(input, |input| {
+                            #steps
+                            Ok((#(#bindings),*))
+                        })? {
+                            return Ok(_value);
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let bindings = analysis::collect_bindings(std::slice::from_ref(pattern));
+            Ok(bind(
+                &bindings,
+                quote! {
+                    rt::group(input, |input| {
+                        let mut _alts = rt::Alternatives::new();
+                        #(#attempts)*
+                        _alts.fail(input)
+                    })
+                },
+            ))
+        }
+        ModelPattern::Parenthesized(seq, _)
+        | ModelPattern::Bracketed(seq, _)
+        | ModelPattern::Braced(seq, _) => {
+            let (open, close) = match pattern {
+                ModelPattern::Parenthesized(..) => ("(", ")"),
+                ModelPattern::Bracketed(..) => ("[", "]"),
+                _ => ("{", "}"),
+            };
+            let steps = generate_sequence(seq, cx)?;
+            Ok(quote! {
+                rt::literal(input, #open)?;
+                #steps
+                rt::literal(input, #close)?;
+            })
+        }
+        ModelPattern::Optional(inner, _) => {
+            let bindings = analysis::collect_bindings(std::slice::from_ref(&**inner));
+            let closure = closure(std::slice::from_ref(&**inner), cx)?;
+            let parsed = quote!(rt::opt(input, #closure)?);
+            let nones = bindings.iter().map(|_| quote!(None));
+            Ok(match bindings.as_slice() {
+                [] => quote! { let _ = #parsed; },
+                [b] => quote! { let #b = #parsed; },
+                _ => quote! {
+                    let (#(#bindings),*) = match #parsed {
+                        Some((#(#bindings),*)) => (#(Some(#bindings)),*),
+                        None => (#(#nones),*),
+                    };
+                },
+            })
+        }
+        ModelPattern::Repeat(inner, _) => generate_repetition(inner, 0, None, cx),
+        ModelPattern::Plus(inner, _) => generate_repetition(inner, 1, None, cx),
+        ModelPattern::Bounded {
+            pattern, min, max, ..
+        } => generate_repetition(pattern, *min, *max, cx),
+        ModelPattern::SpanBinding(inner, name, _) => {
+            let bindings = analysis::collect_bindings(std::slice::from_ref(&**inner));
+            let closure = closure(std::slice::from_ref(&**inner), cx)?;
+            Ok(quote! {
+                let ((#(#bindings),*), #name) = rt::spanned(input, #closure)?;
+            })
+        }
+        ModelPattern::Raw(inner, name, _) => {
+            let bindings = analysis::collect_bindings(std::slice::from_ref(&**inner));
+            let closure = closure(std::slice::from_ref(&**inner), cx)?;
+            Ok(quote! {
+                let ((#(#bindings),*), #name) = rt::recognize(input, #closure)?;
+            })
+        }
+        ModelPattern::Commit(inner, _) => {
+            let bindings = analysis::collect_bindings(std::slice::from_ref(&**inner));
+            let closure = closure(std::slice::from_ref(&**inner), cx)?;
+            Ok(bind(&bindings, quote!(rt::commit(input, #closure))))
+        }
+        ModelPattern::Expected(inner, label, _) => {
+            let bindings = analysis::collect_bindings(std::slice::from_ref(&**inner));
+            let closure = closure(std::slice::from_ref(&**inner), cx)?;
+            Ok(bind(
+                &bindings,
+                quote!(rt::expected(input, #label, #closure)),
+            ))
+        }
+        ModelPattern::Peek(inner, _) => {
+            let bindings = analysis::collect_bindings(std::slice::from_ref(&**inner));
+            let closure = closure(std::slice::from_ref(&**inner), cx)?;
+            Ok(bind(&bindings, quote!(rt::peek(input, #closure))))
+        }
+        ModelPattern::Not(inner, _) => {
+            let steps = generate_sequence(std::slice::from_ref(&**inner), cx)?;
+            Ok(quote! {
+                rt::not(input, |input| {
+                    #steps
+                    Ok(())
+                })?;
+            })
+        }
+        ModelPattern::Collect(_, _, span) => Err(unsupported(*span, "`#[collect]`")),
+        ModelPattern::Recover { span, .. } => Err(unsupported(*span, "`recover`")),
+        ModelPattern::Follows(_, span) => Err(unsupported(*span, "`follows`")),
+        ModelPattern::Declare(_, span) | ModelPattern::Resolve(_, span) => {
+            Err(unsupported(*span, "the symbol table"))
+        }
+        ModelPattern::Scope(_, span) => Err(unsupported(*span, "`scope`")),
+        ModelPattern::Until { span, .. } => Err(unsupported(*span, "`until`")),
+        ModelPattern::Fold { span, .. } => Err(unsupported(*span, "`fold_left` and `fold_right`")),
+    }
+}
+
+/// The value of a literal matcher: the literal for `"->"`, or the word as written in the input
+/// for case-insensitive literals (`"select"i`) and literals matched as identifiers
+/// (`"self"ident`).
+fn generate_literal(lit: &Lit) -> Result<TokenStream> {
+    match lit {
+        Lit::Str(s) if analysis::is_case_insensitive_literal(s) => {
+            let word = s.value();
+            Ok(quote!(rt::literal_ci(input, #word)))
+        }
+        Lit::Str(s) if analysis::is_ident_literal(s) => {
+            let word = s.value();
+            Ok(quote!(rt::literal(input, #word).map(str::to_string)))
+        }
+        Lit::Str(s) => {
+            let text = s.value();
+            Ok(quote!(rt::literal(input, #text)))
+        }
+        Lit::Char(c) => {
+            let text = c.value().to_string();
+            Ok(quote!(rt::literal(input, #text)))
+        }
+        _ => Err(syn::Error::new(
+            lit.span(),
+            "Non-string literals are not supported as matchers.",
+        )),
+    }
+}
+
+/// `inner*`, `inner+` or `inner{min,max}`. Each binding of `inner` becomes a `Vec` of its
+/// values.
+fn generate_repetition(
+    inner: &ModelPattern,
+    min: usize,
+    max: Option<usize>,
+    cx: &Context,
+) -> Result<TokenStream> {
+    let bindings = analysis::collect_bindings(std::slice::from_ref(inner));
+    let closure = closure(std::slice::from_ref(inner), cx)?;
+    let max = match max {
+        Some(max) => quote!(Some(#max)),
+        None => quote!(None),
+    };
+    let items = quote!(rt::repeat(input, #min, #max, #closure)?);
+    Ok(match bindings.as_slice() {
+        [] => quote! { let _ = #items; },
+        [b] => quote! { let #b = #items; },
+        _ => {
+            let temps: Vec<_> = (0..bindings.len())
+                .map(|i| format_ident!("_item{}", i))
+                .collect();
+            quote! {
+                let _items = #items;
+                #(let mut #bindings = Vec::with_capacity(_items.len());)*
+                for (#(#temps),*) in _items {
+                    #(#bindings.push(#temps);)*
+                }
+            }
+        }
+    })
+}
+
+/// An integer argument of a list built-in, e.g. `min=1`.
+fn int_arg(value: &ModelPattern) -> Result<usize> {
+    match value {
+        ModelPattern::Lit {
+            lit: Lit::Int(i), ..
+        } => i.base10_parse(),
+        _ => Err(syn::Error::new(value.span(), "expected an integer")),
+    }
+}
+
+/// A boolean argument of a list built-in, e.g. `trailing=true`.
+fn bool_arg(value: &ModelPattern) -> Result<bool> {
+    match value {
+        ModelPattern::Lit {
+            lit: Lit::Bool(b), ..
+        } => Ok(b.value),
+        _ => Err(syn::Error::new(value.span(), "expected `true` or `false`")),
+    }
+}
+
+/// The value of one of [`LIST_BUILTINS`], e.g. `separated(item, ",", min=1)`.
+fn generate_list_builtin(
+    rule_name: &syn::Ident,
+    generics: &[syn::Type],
+    args: &[Argument],
+    cx: &Context,
+) -> Result<TokenStream> {
+    let name = rule_name.to_string();
+    let positional: Vec<&ModelPattern> = args
+        .iter()
+        .filter_map(|a| match a {
+            Argument::Positional(p) => Some(p),
+            Argument::Named(..) => None,
+        })
+        .collect();
+    let named = |key: &str| {
+        args.iter().find_map(|a| match a {
+            Argument::Named(id, p) if id == key => Some(p),
+            _ => None,
+        })
+    };
+    for arg in args {
+        if let Argument::Named(id, _) = arg {
+            let known: &[&str] = match name.as_str() {
+                "separated" => &["min", "trailing"],
+                "repeated" => &["min"],
+                "terminated" => &["required"],
+                "map" => &["sep", "trailing", "unique"],
+                _ => &[],
+            };
+            if !known.iter().any(|k| id == k) {
+                return Err(syn::Error::new(
+                    id.span(),
+                    format!("Unknown argument '{}' for {}", id, name),
+                ));
+            }
+        }
+    }
+    let expect_args = |count: usize, usage: &str| {
+        if positional.len() == count {
+            Ok(())
+        } else {
+            Err(syn::Error::new(
+                rule_name.span(),
+                format!("{} requires {} arguments: {}", name, count, usage),
+            ))
+        }
+    };
+    // Containers other than `Vec`, e.g. `separated<HashSet>(..)`
+    let collect = |value: TokenStream| match generics.first() {
+        Some(ty) if name == "map" => quote!(#value.map(|v| v.into_iter().collect::<#ty<_, _>>())),
+        Some(ty) => quote!(#value.map(|v| v.into_iter().collect::<#ty<_>>())),
+        None => value,
+    };
+
+    Ok(match name.as_str() {
+        "separated" => {
+            expect_args(2, "(rule, separator)")?;
+            let min = named("min").map(int_arg).transpose()?.unwrap_or(0);
+            let trailing = named("trailing")
+                .map(bool_arg)
+                .transpose()?
+                .unwrap_or(false);
+            let item = value_closure(positional[0], cx)?;
+            let sep = value_closure(positional[1], cx)?;
+            collect(quote!(rt::separated(input, #min, #trailing, #item, #sep)))
+        }
+        "repeated" => {
+            expect_args(1, "(rule)")?;
+            let min = named("min").map(int_arg).transpose()?.unwrap_or(0);
+            let item = value_closure(positional[0], cx)?;
+            collect(quote!(rt::repeated(input, #min, #item)))
+        }
+        "terminated" => {
+            expect_args(2, "(rule, terminator)")?;
+            let required = match named("required") {
+                Some(ModelPattern::RuleCall { rule_name, .. }) if rule_name == "auto" => {
+                    return Err(unsupported(rule_name.span(), "`required=auto`"));
+                }
+                Some(value) => bool_arg(value)?,
+                None => true,
+            };
+            let item = value_closure(positional[0], cx)?;
+            let term = value_closure(positional[1], cx)?;
+            collect(quote!(rt::terminated(input, #required, #item, #term)))
+        }
+        "until_parse" => {
+            expect_args(2, "(rule, stop)")?;
+            let item = value_closure(positional[0], cx)?;
+            let stop = closure(std::slice::from_ref(positional[1]), cx)?;
+            collect(quote!(rt::until_parse(input, #item, #stop)))
+        }
+        "delimited" => {
+            expect_args(4, "(open, item, separator, close)")?;
+            if let Some(ty) = generics.first() {
+                return Err(unsupported(ty.span(), "a container for delimited"));
+            }
+            let delimiter = |p: &ModelPattern| match p {
+                ModelPattern::Lit {
+                    binding: None,
+                    lit: Lit::Str(s),
+                } => Ok(s.value()),
+                _ => Err(syn::Error::new(
+                    p.span(),
+                    "delimited expects string literals as delimiters",
+                )),
+            };
+            let open = delimiter(positional[0])?;
+            let close = delimiter(positional[3])?;
+            let item = value_closure(positional[1], cx)?;
+            let sep = value_closure(positional[2], cx)?;
+            quote!(rt::delimited(input, (#open, #close), #item, #sep))
+        }
+        "map" => {
+            expect_args(3, "(key, separator, value)")?;
+            let trailing = named("trailing")
+                .map(bool_arg)
+                .transpose()?
+                .unwrap_or(false);
+            let unique = named("unique").map(bool_arg).transpose()?.unwrap_or(false);
+            let default_sep = ModelPattern::Lit {
+                binding: None,
+                lit: Lit::Str(syn::LitStr::new(",", rule_name.span())),
+            };
+            let sep = value_closure(named("sep").unwrap_or(&default_sep), cx)?;
+            let key = bind_value(positional[0], "_key");
+            let value = bind_value(positional[2], "_value");
+            let key_bindings = analysis::collect_bindings(std::slice::from_ref(&key));
+            let value_bindings = analysis::collect_bindings(std::slice::from_ref(&value));
+            let entry = generate_sequence(&[key, positional[1].clone(), value], cx)?;
+            collect(quote! {
+                rt::entries(input, #trailing, #unique, |input| {
+                    #entry
+                    Ok(((#(#key_bindings),*), (#(#value_bindings),*)))
+                }, #sep)
+            })
+        }
+        "skip" => {
+            expect_args(1, "(pattern)")?;
+            let steps = generate_sequence(std::slice::from_ref(positional[0]), cx)?;
+            quote!(rt::group(input, |input| {
+                #steps
+                Ok(())
+            }))
+        }
+        "parse_in" => {
+            expect_args(2, "(string, pattern)")?;
+            let source = bind_value(positional[0], "_source");
+            let [source_binding] = &analysis::collect_bindings(std::slice::from_ref(&source))[..]
+            else {
+                return Err(syn::Error::new(
+                    positional[0].span(),
+                    "the first argument of parse_in must bind a single string, e.g. string",
+                ));
+            };
+            let source_steps = generate_pattern(&source, cx)?;
+            let inner = value_closure(positional[1], cx)?;
+            quote! {
+                rt::group(input, |input| {
+                    let (_source, _source_span) = rt::spanned(input, |input| {
+                        #source_steps
+                        Ok(#source_binding)
+                    })?;
+                    rt::parse_in(&_source, _source_span.start, #inner)
+                })
+            }
+        }
+        _ => unreachable!("not a list built-in: {}", name),
+    })
+}
@@ -0,0 +1,28 @@
+//! The `grammar!` macro of `winnow-grammar`. Use it through the `winnow-grammar` crate, which
+//! provides the runtime the generated code refers to.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use syn_grammar_model::parse_grammar;
+
+mod backend;
+mod codegen;
+
+use backend::WinnowBackend;
+
+/// Compiles a grammar into a module of winnow parsers, one `parse_<rule>` function per rule.
+///
+/// The grammar language is the one of `syn_grammar::grammar!`; see the crate-level
+/// documentation of `winnow-grammar` for what the winnow backend supports.
+#[proc_macro]
+pub fn grammar(input: TokenStream) -> TokenStream {
+    let grammar = match parse_grammar::<WinnowBackend>(input.into()) {
+        Ok(grammar) => grammar,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    match codegen::generate(&grammar) {
+        Ok(stream) => stream.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,383 @@
+//! The portable built-ins, lexing Rust tokens from text with the semantics of the syn
+//! backend (see `syn_grammar::rt::conformance`).
+
+use crate::rt::{backtrack, remaining, skip_whitespace, Input, PResult};
+use crate::types::{Identifier, StringLiteral};
+use proc_macro2::Span;
+use std::str::FromStr;
+use winnow::stream::Stream;
+
+/// Words that are not identifiers unless written as raw identifiers (`r#fn`).
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "Self", "self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// The length of the identifier or keyword at the start of `s`.
+fn ident_len(s: &str) -> Option<usize> {
+    let first = s.chars().next()?;
+    if first != '_' && !first.is_alphabetic() {
+        return None;
+    }
+    let rest = &s[first.len_utf8()..];
+    let len = rest
+        .find(|c: char| c != '_' && !c.is_alphanumeric())
+        .unwrap_or(rest.len());
+    Some(first.len_utf8() + len)
+}
+
+/// Lexes a word at the start of `s`: an identifier, a keyword or a raw identifier. Returns its
+/// length and whether it is raw. Prefixes of literals such as `b"bytes"` are not words.
+fn word(s: &str) -> Option<(usize, bool)> {
+    if let Some(len) = s.strip_prefix("r#").and_then(ident_len) {
+        return Some((len + 2, true));
+    }
+    let len = ident_len(s)?;
+    let next = &s[len..];
+    if next.starts_with(['"', '\''])
+        || (matches!(&s[..len], "r" | "br" | "cr") && next.starts_with('#'))
+    {
+        return None;
+    }
+    Some((len, false))
+}
+
+/// Consumes the next token if `lex` recognizes it at the start of the input, returning its
+/// text and the value found by `lex`.
+fn token<'i, T>(
+    input: &mut Input<'i>,
+    what: &str,
+    lex: impl FnOnce(&'i str) -> Option<(usize, T)>,
+) -> PResult<(&'i str, T)> {
+    skip_whitespace(input);
+    match lex(remaining(input)) {
+        Some((len, value)) => Ok((input.next_slice(len), value)),
+        None => backtrack(input, what),
+    }
+}
+
+/// An identifier that is not a keyword or `_`, e.g. `foo` or `r#fn`. Positions in the text are
+/// byte offsets, which a `Span` cannot hold, so the span of the value is `Span::call_site()`.
+pub fn ident(input: &mut Input<'_>) -> PResult<Identifier> {
+    let (text, ()) = token(input, "identifier", |s| match word(s)? {
+        (len, true) => Some((len, ())),
+        (len, false) if s[..len] != *"_" && !KEYWORDS.contains(&&s[..len]) => Some((len, ())),
+        _ => None,
+    })?;
+    Ok(Identifier::new(text, Span::call_site()))
+}
+
+/// `true` or `false`.
+pub fn bool(input: &mut Input<'_>) -> PResult<bool> {
+    let (_, value) = token(input, "`true` or `false`", |s| match word(s)? {
+        (len, false) if &s[..len] == "true" => Some((len, true)),
+        (len, false) if &s[..len] == "false" => Some((len, false)),
+        _ => None,
+    })?;
+    Ok(value)
+}
+
+/// Lexes the contents of a string or character literal after the opening quote, up to and
+/// with the closing `quote`. Returns the length and the value with escapes processed.
+fn quoted(body: &str, quote: char) -> Option<(usize, String)> {
+    let mut value = String::new();
+    let mut chars = body.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return Some((i + 1, value));
+        }
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()?.1 {
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            '0' => value.push('\0'),
+            c @ ('\\' | '\'' | '"') => value.push(c),
+            'x' => {
+                let hex: String = [chars.next()?.1, chars.next()?.1].iter().collect();
+                let code = u8::from_str_radix(&hex, 16).ok().filter(u8::is_ascii)?;
+                value.push(char::from(code));
+            }
+            'u' => {
+                if chars.next()?.1 != '{' {
+                    return None;
+                }
+                let mut hex = String::new();
+                loop {
+                    match chars.next()?.1 {
+                        '}' => break,
+                        '_' => {}
+                        c => hex.push(c),
+                    }
+                }
+                value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            // A line continuation skips the whitespace at the start of the next line
+            '\n' => while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {},
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// A string literal, `"text"` or the raw `r"text"` and `r#"text"#`, returning its value with the
+/// span `Span::call_site()`, as for [`ident`].
+pub fn string(input: &mut Input<'_>) -> PResult<StringLiteral> {
+    let (_, value) = token(input, "string literal", |s| {
+        if let Some(body) = s.strip_prefix('"') {
+            let (len, value) = quoted(body, '"')?;
+            return Some((len + 1, value));
+        }
+        let raw = s.strip_prefix('r')?;
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let body = raw[hashes..].strip_prefix('"')?;
+        let end = body.find(&format!("\"{}", "#".repeat(hashes)))?;
+        Some((1 + hashes + 1 + end + 1 + hashes, body[..end].to_string()))
+    })?;
+    Ok(StringLiteral::new(value, Span::call_site()))
+}
+
+/// A character literal such as `'c'` or `'\n'`.
+pub fn char(input: &mut Input<'_>) -> PResult<char> {
+    let (_, value) = token(input, "character literal", |s| {
+        let (len, value) = quoted(s.strip_prefix('\'')?, '\'')?;
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some((len + 1, c)),
+            _ => None,
+        }
+    })?;
+    Ok(value)
+}
+
+/// A number literal: an integer such as `42`, `-7`, `0xff` or `42u8`, or a float such as
+/// `1.5`, `1e3` or `2.5f32`.
+#[derive(Debug, Clone)]
+struct Number {
+    /// The value in decimal, with a `-` if negative, for integers. The digits, fraction and
+    /// exponent without `_` for floats.
+    digits: String,
+    is_float: bool,
+}
+
+/// Lexes the number literal at the start of `s`, with a leading `-`.
+fn number(s: &str) -> Option<(usize, Number)> {
+    let (negative, start) = match s.strip_prefix('-') {
+        Some(rest) => (true, s.len() - rest.trim_start().len()),
+        None => (false, 0),
+    };
+    let body = &s[start..];
+    if !body.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let (radix, prefix) = match body.get(..2) {
+        Some("0x") => (16, 2),
+        Some("0o") => (8, 2),
+        Some("0b") => (2, 2),
+        _ => (10, 0),
+    };
+    let bytes = body.as_bytes();
+    let is_digit = |b: &u8| match radix {
+        16 => b.is_ascii_hexdigit(),
+        _ => b.is_ascii_digit(),
+    };
+    let digits_end = |from: usize| {
+        from + bytes[from..]
+            .iter()
+            .take_while(|b| is_digit(b) || **b == b'_')
+            .count()
+    };
+    let mut end = digits_end(prefix);
+    let mut is_float = false;
+    if radix == 10 {
+        // `1.5` and `1.`, but not the range `1..2` or the field access `1.foo`
+        let after_dot = bytes.get(end + 1);
+        if bytes.get(end) == Some(&b'.')
+            && !after_dot.is_some_and(|b| *b == b'.' || *b == b'_' || b.is_ascii_alphabetic())
+        {
+            is_float = true;
+            end = digits_end(end + 1);
+        }
+        if matches!(bytes.get(end), Some(b'e' | b'E')) {
+            let mut exponent = end + 1;
+            if matches!(bytes.get(exponent), Some(b'+' | b'-')) {
+                exponent += 1;
+            }
+            let exponent_end = digits_end(exponent);
+            if body[exponent..exponent_end].contains(|c: char| c.is_ascii_digit()) {
+                is_float = true;
+                end = exponent_end;
+            }
+        }
+    }
+    let literal = body[prefix..end].replace('_', "");
+    let suffix_len = ident_len(&body[end..]).unwrap_or(0);
+    let suffix = &body[end..end + suffix_len];
+    if matches!(suffix, "f32" | "f64") {
+        if radix != 10 {
+            return None;
+        }
+        is_float = true;
+    }
+
+    let digits = if is_float {
+        literal
+    } else {
+        to_decimal(&literal, radix)?
+    };
+    let sign = if negative { "-" } else { "" };
+    let number = Number {
+        digits: format!("{}{}", sign, digits),
+        is_float,
+    };
+    Some((start + end + suffix_len, number))
+}
+
+/// Converts the digits of an integer literal in `radix` to decimal, without limiting the
+/// size, so that a literal too large for any type is still a literal.
+fn to_decimal(digits: &str, radix: u32) -> Option<String> {
+    const BASE: u64 = 1_000_000_000;
+    if digits.is_empty() {
+        return None;
+    }
+    // Little-endian limbs in base 10^9
+    let mut limbs: Vec<u64> = vec![0];
+    for c in digits.chars() {
+        let mut carry = u64::from(c.to_digit(radix)?);
+        for limb in &mut limbs {
+            let value = *limb * u64::from(radix) + carry;
+            *limb = value % BASE;
+            carry = value / BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    }
+    let mut decimal = limbs.pop().unwrap_or(0).to_string();
+    for limb in limbs.iter().rev() {
+        decimal.push_str(&format!("{:09}", limb));
+    }
+    Some(decimal)
+}
+
+/// An integer literal. Returns its text as written and its value in decimal.
+fn integer<'i>(input: &mut Input<'i>, what: &str) -> PResult<(&'i str, String)> {
+    token(input, what, |s| match number(s)? {
+        (len, number) if !number.is_float => Some((len, number.digits)),
+        _ => None,
+    })
+}
+
+/// An integer literal with a value of type `T`.
+fn parse_int<T: FromStr>(input: &mut Input<'_>, what: &str) -> PResult<T> {
+    let start = input.checkpoint();
+    let (_, digits) = integer(input, what)?;
+    match digits.parse() {
+        Ok(value) => Ok(value),
+        Err(_) => {
+            input.reset(&start);
+            backtrack(input, what)
+        }
+    }
+}
+
+/// A float literal with a value of type `T`. Integer literals are not floats.
+fn parse_float<T: FromStr>(input: &mut Input<'_>, what: &str) -> PResult<T> {
+    let (_, value) = token(input, what, |s| match number(s)? {
+        (len, number) if number.is_float => Some((len, number.digits.parse().ok()?)),
+        _ => None,
+    })?;
+    Ok(value)
+}
+
+macro_rules! integer_builtins {
+    ($($name:ident),*) => {
+        $(
+            #[doc = concat!("An integer literal with a value that fits in `", stringify!($name), "`.")]
+            pub fn $name(input: &mut Input<'_>) -> PResult<$name> {
+                parse_int(input, concat!("`", stringify!($name), "` literal"))
+            }
+        )*
+    };
+}
+
+integer_builtins!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// A float literal with an `f32` value.
+pub fn f32(input: &mut Input<'_>) -> PResult<f32> {
+    parse_float(input, "float literal")
+}
+
+/// A float literal with an `f64` value.
+pub fn f64(input: &mut Input<'_>) -> PResult<f64> {
+    parse_float(input, "float literal")
+}
+
+/// An integer literal in any base, e.g. `0xff`, as `u64`.
+pub fn hex_literal(input: &mut Input<'_>) -> PResult<u64> {
+    parse_int(input, "hexadecimal literal")
+}
+
+/// An integer literal in any base, e.g. `0o17`, as `u64`.
+pub fn oct_literal(input: &mut Input<'_>) -> PResult<u64> {
+    parse_int(input, "octal literal")
+}
+
+/// An integer literal in any base, e.g. `0b101`, as `u64`.
+pub fn bin_literal(input: &mut Input<'_>) -> PResult<u64> {
+    parse_int(input, "binary literal")
+}
+
+/// An identifier whose characters all satisfy `filter`.
+fn filtered_ident(input: &mut Input<'_>, what: &str, filter: fn(char) -> bool) -> PResult<String> {
+    let start = input.checkpoint();
+    let ident = ident(input)?.text;
+    if !ident.chars().all(filter) {
+        input.reset(&start);
+        return backtrack(input, what);
+    }
+    Ok(ident)
+}
+
+/// An integer literal whose decimal value only has digits that satisfy `filter`. Returns the
+/// literal as written.
+fn filtered_int(input: &mut Input<'_>, what: &str, filter: fn(char) -> bool) -> PResult<String> {
+    let start = input.checkpoint();
+    let (text, digits) = integer(input, what)?;
+    if !digits.chars().all(filter) {
+        input.reset(&start);
+        return backtrack(input, what);
+    }
+    Ok(text.to_string())
+}
+
+/// An identifier of letters only.
+pub fn alpha(input: &mut Input<'_>) -> PResult<String> {
+    filtered_ident(input, "alphabetic identifier", char::is_alphabetic)
+}
+
+/// An identifier of letters and digits only.
+pub fn alphanumeric(input: &mut Input<'_>) -> PResult<String> {
+    filtered_ident(input, "alphanumeric identifier", char::is_alphanumeric)
+}
+
+/// A non-negative integer literal whose decimal value only has digits.
+pub fn digit(input: &mut Input<'_>) -> PResult<String> {
+    filtered_int(input, "digits", |c| c.is_ascii_digit())
+}
+
+/// A non-negative integer literal whose decimal value only has hexadecimal digits.
+pub fn hex_digit(input: &mut Input<'_>) -> PResult<String> {
+    filtered_int(input, "hexadecimal digits", |c| c.is_ascii_hexdigit())
+}
+
+/// A non-negative integer literal whose decimal value only has octal digits.
+pub fn oct_digit(input: &mut Input<'_>) -> PResult<String> {
+    filtered_int(input, "octal digits", |c| matches!(c, '0'..='7'))
+}
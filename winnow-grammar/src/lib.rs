@@ -0,0 +1,15 @@
+#![doc = include_str!("../README.md")]
+
+// The code generated by the macro refers to `winnow_grammar::rt`
+#[doc(hidden)]
+pub mod builtins;
+pub mod rt;
+
+// The portable types returned by `ident` and `string`, shared with the syn backend
+pub mod types {
+    pub use syn_grammar_model::model::types::*;
+}
+pub use types::{Identifier, StringLiteral};
+
+pub use winnow;
+pub use winnow_grammar_macros::grammar;
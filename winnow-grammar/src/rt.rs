@@ -0,0 +1,657 @@
+//! Runtime of the parsers generated by [`grammar!`](crate::grammar): the input and error types,
+//! whitespace handling and the combinators the generated code is built from.
+//!
+//! Generated rules are plain winnow parsers: functions from `&mut Input` to [`PResult`].
+//! Whitespace and comments are skipped before every token, so a rule never consumes the
+//! whitespace after its last token.
+
+use crate::types::StringLiteral;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Range;
+use winnow::error::{ErrMode, ParserError};
+use winnow::stream::{Location, Stream};
+
+pub use crate::builtins::*;
+pub use winnow::stream::{LocatingSlice, Stateful};
+
+/// The input of generated parsers: the text, the byte offset of the position in it and the
+/// [`State`] of the parse.
+pub type Input<'i> = Stateful<LocatingSlice<&'i str>, &'i State>;
+
+/// The state of a parse: the error of the furthest pattern that failed and was backtracked
+/// from, e.g. the item after `1 +` in `(t:term ("+" term)*)`. If the parse fails, that error
+/// is reported instead of an error closer to the start, so that `1 + *` fails at `*` rather
+/// than at `+`.
+#[derive(Debug, Default)]
+pub struct State {
+    furthest: RefCell<Option<Error>>,
+}
+
+/// Records an error that is backtracked from in the [`State`].
+fn record(input: &Input<'_>, error: Error) {
+    let mut furthest = input.state.furthest.borrow_mut();
+    *furthest = Some(match furthest.take() {
+        Some(previous) => previous.merge(error),
+        None => error,
+    });
+}
+
+/// The result of generated parsers. Failures are `ErrMode::Backtrack`, so that alternatives
+/// are attempted, unless they occur after a cut or in a pattern with `!`, which makes them
+/// `ErrMode::Cut`.
+pub type PResult<T> = winnow::ModalResult<T, Error>;
+
+/// A parse error at a byte offset of the input.
+///
+/// When alternatives fail, the error that got furthest wins, and errors at the same offset
+/// combine what they expected ("expected `+` or `-`").
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    offset: usize,
+    expected: Vec<String>,
+    message: Option<String>,
+}
+
+impl Error {
+    /// An error at `offset` expecting `what`, e.g. "identifier" or "`+`".
+    pub fn expected(offset: usize, what: impl Into<String>) -> Self {
+        Error {
+            offset,
+            expected: vec![what.into()],
+            message: None,
+        }
+    }
+
+    /// An error at `offset` with a message, which is not combined with other errors.
+    pub fn message(offset: usize, message: impl Into<String>) -> Self {
+        Error {
+            offset,
+            expected: Vec::new(),
+            message: Some(message.into()),
+        }
+    }
+
+    /// The byte offset of the error in the input.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The line and column of the error in `text`, the parsed input, both starting at 1.
+    /// Columns count characters.
+    pub fn line_column(&self, text: &str) -> (usize, usize) {
+        let before = &text[..self.offset.min(text.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (line, before[line_start..].chars().count() + 1)
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        match self.offset.cmp(&other.offset) {
+            Ordering::Greater => self,
+            Ordering::Less => other,
+            Ordering::Equal if self.message.is_some() => self,
+            Ordering::Equal if other.message.is_some() => other,
+            Ordering::Equal => {
+                for what in other.expected {
+                    if !self.expected.contains(&what) {
+                        self.expected.push(what);
+                    }
+                }
+                self
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.message, self.expected.as_slice()) {
+            (Some(message), _) => f.write_str(message),
+            (None, []) => f.write_str("unexpected input"),
+            (None, expected) => write!(f, "expected {}", expected.join(" or ")),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl<'i> ParserError<Input<'i>> for Error {
+    type Inner = Self;
+
+    fn from_input(input: &Input<'i>) -> Self {
+        Error {
+            offset: offset(input),
+            expected: Vec::new(),
+            message: None,
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        self.merge(other)
+    }
+
+    fn into_inner(self) -> Result<Self, Self> {
+        Ok(self)
+    }
+}
+
+/// Parses all of `text` with `parser`, a rule of a grammar such as `calc::parse_expr`.
+/// Whitespace after the last token is allowed.
+pub fn parse<T>(text: &str, parser: impl FnOnce(&mut Input<'_>) -> PResult<T>) -> Result<T, Error> {
+    let state = State::default();
+    let mut input = Stateful {
+        input: LocatingSlice::new(text),
+        state: &state,
+    };
+    let error = match parser(&mut input) {
+        Ok(value) => {
+            skip_whitespace(&mut input);
+            if input.is_empty() {
+                return Ok(value);
+            }
+            Error::expected(offset(&input), "end of input")
+        }
+        Err(ErrMode::Backtrack(e) | ErrMode::Cut(e)) => e,
+        Err(ErrMode::Incomplete(_)) => Error::message(text.len(), "incomplete input"),
+    };
+    Err(match state.furthest.take() {
+        Some(furthest) => furthest.merge(error),
+        None => error,
+    })
+}
+
+/// The byte offset of the position in the input.
+pub fn offset(input: &Input<'_>) -> usize {
+    input.current_token_start()
+}
+
+/// The rest of the input.
+pub fn remaining<'i>(input: &Input<'i>) -> &'i str {
+    *input.input
+}
+
+/// Skips whitespace and comments (`// ..` to the end of the line and `/* .. */`), which may
+/// appear before every token.
+pub fn skip_whitespace(input: &mut Input<'_>) {
+    loop {
+        let rest = remaining(input);
+        let trimmed = rest.trim_start();
+        let mut skip = rest.len() - trimmed.len();
+        if trimmed.starts_with("//") {
+            skip += trimmed.find('\n').unwrap_or(trimmed.len());
+        } else if let Some(comment) = trimmed.strip_prefix("/*") {
+            // An unterminated comment is left for the next token to fail on
+            if let Some(end) = comment.find("*/") {
+                skip += end + 4;
+            }
+        }
+        if skip == 0 {
+            return;
+        }
+        input.next_slice(skip);
+    }
+}
+
+/// Fails at the next token, expecting `what`.
+pub fn backtrack<T>(input: &mut Input<'_>, what: &str) -> PResult<T> {
+    skip_whitespace(input);
+    Err(ErrMode::Backtrack(Error::expected(offset(input), what)))
+}
+
+/// The error of a `where { .. }` guard that does not hold after the pattern matched.
+pub fn guard_failed(input: &Input<'_>, condition: &str) -> ErrMode<Error> {
+    ErrMode::Backtrack(Error::message(
+        offset(input),
+        format!("condition `{}` does not hold", condition),
+    ))
+}
+
+/// The error of an action in a rule with `throws Error` that returned `error`. Like a failed
+/// guard, the variant fails with the message of the error.
+pub fn action_failed(input: &Input<'_>, error: impl fmt::Display) -> ErrMode<Error> {
+    ErrMode::Backtrack(Error::message(offset(input), error.to_string()))
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Matches one word or punctuation of a literal. A word must not be followed by more
+/// characters of an identifier, so `"in"` does not match the start of `input`.
+fn literal_part(input: &mut Input<'_>, part: &str, ignore_case: bool) -> bool {
+    skip_whitespace(input);
+    let rest = remaining(input);
+    let Some(prefix) = rest.get(..part.len()) else {
+        return false;
+    };
+    let matches = match ignore_case {
+        true => prefix.eq_ignore_ascii_case(part),
+        false => prefix == part,
+    };
+    let is_word = part.ends_with(is_ident_continue);
+    if !matches || (is_word && rest[part.len()..].starts_with(is_ident_continue)) {
+        return false;
+    }
+    input.next_slice(part.len());
+    true
+}
+
+/// Matches the literal `text` of a grammar, e.g. `"fn"` or `"->"`. The parts of a literal
+/// with spaces, e.g. `"else if"`, may be separated by any whitespace.
+pub fn literal(input: &mut Input<'_>, text: &'static str) -> PResult<&'static str> {
+    for part in text.split_whitespace() {
+        if !literal_part(input, part, false) {
+            return backtrack(input, &format!("`{}`", text));
+        }
+    }
+    Ok(text)
+}
+
+/// Matches a case-insensitive literal such as `"select"i` and returns the word as spelled in
+/// the input.
+pub fn literal_ci(input: &mut Input<'_>, word: &'static str) -> PResult<String> {
+    skip_whitespace(input);
+    let rest = remaining(input);
+    if !literal_part(input, word, true) {
+        return backtrack(input, &format!("`{}`", word));
+    }
+    Ok(rest[..word.len()].to_string())
+}
+
+/// Succeeds at the end of the input, after whitespace.
+pub fn eof(input: &mut Input<'_>) -> PResult<()> {
+    skip_whitespace(input);
+    if !input.is_empty() {
+        return backtrack(input, "end of input");
+    }
+    Ok(())
+}
+
+/// Runs the sequence of a group, whose alternatives return from the closure.
+pub fn group<'i, T>(
+    input: &mut Input<'i>,
+    f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+) -> PResult<T> {
+    f(input)
+}
+
+/// The alternatives of a rule or group, attempted in order. A failed alternative resets the
+/// input, and its error is kept for [`Alternatives::fail`] unless a later one got further.
+#[derive(Debug, Default)]
+pub struct Alternatives {
+    error: Option<Error>,
+}
+
+impl Alternatives {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attempts one alternative. Returns `None` if it backtracked.
+    pub fn attempt<'i, T>(
+        &mut self,
+        input: &mut Input<'i>,
+        f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+    ) -> PResult<Option<T>> {
+        let start = input.checkpoint();
+        match f(input) {
+            Ok(value) => Ok(Some(value)),
+            Err(ErrMode::Backtrack(e)) => {
+                input.reset(&start);
+                record(input, e.clone());
+                self.error = Some(match self.error.take() {
+                    Some(error) => error.merge(e),
+                    None => e,
+                });
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The error after all alternatives failed.
+    pub fn fail<T>(self, input: &mut Input<'_>) -> PResult<T> {
+        match self.error {
+            Some(error) => Err(ErrMode::Backtrack(error)),
+            None => backtrack(input, "a matching alternative"),
+        }
+    }
+}
+
+/// `pattern?`: `None` if the pattern backtracks, with the input reset.
+pub fn opt<'i, T>(
+    input: &mut Input<'i>,
+    f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+) -> PResult<Option<T>> {
+    let start = input.checkpoint();
+    match f(input) {
+        Ok(value) => Ok(Some(value)),
+        Err(ErrMode::Backtrack(e)) => {
+            input.reset(&start);
+            record(input, e);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// `pattern*`, `pattern+` and `pattern{min,max}`: repeats the pattern until it backtracks or
+/// `max` items matched. An item that matches without consuming input ends the repetition.
+pub fn repeat<'i, T>(
+    input: &mut Input<'i>,
+    min: usize,
+    max: Option<usize>,
+    mut f: impl FnMut(&mut Input<'i>) -> PResult<T>,
+) -> PResult<Vec<T>> {
+    let mut items = Vec::new();
+    while max.is_none_or(|max| items.len() < max) {
+        let start = input.checkpoint();
+        let before = offset(input);
+        match f(input) {
+            Ok(item) => {
+                items.push(item);
+                if offset(input) == before {
+                    break;
+                }
+            }
+            Err(ErrMode::Backtrack(e)) => {
+                input.reset(&start);
+                if items.len() < min {
+                    return Err(ErrMode::Backtrack(e));
+                }
+                record(input, e);
+                break;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(items)
+}
+
+/// Fails unless at least `min` items matched.
+fn check_min<T>(input: &mut Input<'_>, items: Vec<T>, min: usize) -> PResult<Vec<T>> {
+    if items.len() < min {
+        skip_whitespace(input);
+        return Err(ErrMode::Backtrack(Error::message(
+            offset(input),
+            format!("expected at least {} items", min),
+        )));
+    }
+    Ok(items)
+}
+
+/// `separated(item, sep, min=0, trailing=false)`: a possibly empty list of items separated by
+/// `sep`. A separator must be followed by an item unless `trailing` allows it to end the
+/// list.
+pub fn separated<'i, T, S>(
+    input: &mut Input<'i>,
+    min: usize,
+    trailing: bool,
+    mut item: impl FnMut(&mut Input<'i>) -> PResult<T>,
+    mut sep: impl FnMut(&mut Input<'i>) -> PResult<S>,
+) -> PResult<Vec<T>> {
+    let mut items = Vec::new();
+    if let Some(first) = opt(input, &mut item)? {
+        items.push(first);
+        while opt(input, &mut sep)?.is_some() {
+            let start = input.checkpoint();
+            match item(input) {
+                Ok(next) => items.push(next),
+                Err(ErrMode::Backtrack(e)) if trailing => {
+                    input.reset(&start);
+                    record(input, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    check_min(input, items, min)
+}
+
+/// `repeated(item, min=0)`: as many items as match.
+pub fn repeated<'i, T>(
+    input: &mut Input<'i>,
+    min: usize,
+    item: impl FnMut(&mut Input<'i>) -> PResult<T>,
+) -> PResult<Vec<T>> {
+    let items = repeat(input, 0, None, item)?;
+    check_min(input, items, min)
+}
+
+/// `terminated(item, term, required=true)`: a possibly empty list of items, each followed by
+/// `term`, which is optional unless `required`.
+pub fn terminated<'i, T, S>(
+    input: &mut Input<'i>,
+    required: bool,
+    mut item: impl FnMut(&mut Input<'i>) -> PResult<T>,
+    mut term: impl FnMut(&mut Input<'i>) -> PResult<S>,
+) -> PResult<Vec<T>> {
+    let mut items = Vec::new();
+    while let Some(next) = opt(input, &mut item)? {
+        items.push(next);
+        if required {
+            term(input)?;
+        } else {
+            opt(input, &mut term)?;
+        }
+    }
+    Ok(items)
+}
+
+/// `until_parse(item, stop)`: items up to the end of the input or a match of `stop`, which
+/// is only peeked. Every item up to it is required.
+pub fn until_parse<'i, T, S>(
+    input: &mut Input<'i>,
+    mut item: impl FnMut(&mut Input<'i>) -> PResult<T>,
+    mut stop: impl FnMut(&mut Input<'i>) -> PResult<S>,
+) -> PResult<Vec<T>> {
+    let mut items = Vec::new();
+    loop {
+        skip_whitespace(input);
+        if input.is_empty() || peek(input, &mut stop).is_ok() {
+            return Ok(items);
+        }
+        let before = offset(input);
+        items.push(item(input)?);
+        if offset(input) == before {
+            return Err(ErrMode::Cut(Error::message(
+                before,
+                "until_parse item matched empty input",
+            )));
+        }
+    }
+}
+
+/// The value of `delimited(open, item, sep, close)`: the items and the byte ranges of the
+/// delimiters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delimited<T> {
+    pub items: Vec<T>,
+    pub open: Range<usize>,
+    pub close: Range<usize>,
+}
+
+/// `delimited(open, item, sep, close)`: a possibly empty list of items separated by `sep`
+/// between the literals `open` and `close`, allowing a trailing separator.
+pub fn delimited<'i, T, S>(
+    input: &mut Input<'i>,
+    (open, close): (&'static str, &'static str),
+    mut item: impl FnMut(&mut Input<'i>) -> PResult<T>,
+    mut sep: impl FnMut(&mut Input<'i>) -> PResult<S>,
+) -> PResult<Delimited<T>> {
+    let ((), open_span) = spanned(input, |input| literal(input, open).map(|_| ()))?;
+    let mut items = Vec::new();
+    let at_close = |input: &mut Input<'i>| peek(input, |input| literal(input, close)).is_ok();
+    while !at_close(input) {
+        items.push(item(input)?);
+        if opt(input, &mut sep)?.is_none() {
+            break;
+        }
+    }
+    if !at_close(input) {
+        skip_whitespace(input);
+        return Err(ErrMode::Backtrack(Error::message(
+            offset(input),
+            format!("expected separator or `{}`", close),
+        )));
+    }
+    let ((), close_span) = spanned(input, |input| literal(input, close).map(|_| ()))?;
+    Ok(Delimited {
+        items,
+        open: open_span,
+        close: close_span,
+    })
+}
+
+/// `map(key, assoc, value, sep=",", trailing=false, unique=false)`: a possibly empty list of
+/// entries, each parsed by `entry`, separated by `sep`. With `unique`, a key equal to an
+/// earlier one fails with "duplicate key".
+pub fn entries<'i, K: PartialEq, V, S>(
+    input: &mut Input<'i>,
+    trailing: bool,
+    unique: bool,
+    mut entry: impl FnMut(&mut Input<'i>) -> PResult<(K, V)>,
+    mut sep: impl FnMut(&mut Input<'i>) -> PResult<S>,
+) -> PResult<Vec<(K, V)>> {
+    let mut entries: Vec<(K, V)> = Vec::new();
+    loop {
+        skip_whitespace(input);
+        let start = offset(input);
+        let next = if entries.is_empty() || trailing {
+            opt(input, &mut entry)?
+        } else {
+            Some(entry(input)?)
+        };
+        let Some(next) = next else {
+            break;
+        };
+        if unique && entries.iter().any(|(key, _)| *key == next.0) {
+            return Err(ErrMode::Cut(Error::message(start, "duplicate key")));
+        }
+        entries.push(next);
+        if opt(input, &mut sep)?.is_none() {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+/// A string matched by the first pattern of `parse_in`, whose contents are parsed.
+pub trait EmbeddedSource {
+    /// The text between the quotes, with escapes resolved.
+    fn contents(&self) -> &str;
+}
+
+impl EmbeddedSource for str {
+    fn contents(&self) -> &str {
+        self
+    }
+}
+
+impl EmbeddedSource for String {
+    fn contents(&self) -> &str {
+        self
+    }
+}
+
+impl EmbeddedSource for StringLiteral {
+    fn contents(&self) -> &str {
+        &self.value
+    }
+}
+
+/// `parse_in(string, pattern)`: parses `source`, the value of a string matched at `offset`,
+/// with `f`, which must consume all of it. Errors inside the string are reported at its
+/// start.
+pub fn parse_in<T>(
+    source: &(impl EmbeddedSource + ?Sized),
+    offset: usize,
+    f: impl FnOnce(&mut Input<'_>) -> PResult<T>,
+) -> PResult<T> {
+    parse(source.contents(), f).map_err(|e| {
+        ErrMode::Backtrack(Error::message(
+            offset,
+            format!("in string at {}: {}", e.offset(), e),
+        ))
+    })
+}
+
+/// `pattern!` and the patterns after a cut: failures can no longer backtrack.
+pub fn commit<'i, T>(
+    input: &mut Input<'i>,
+    f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+) -> PResult<T> {
+    f(input).map_err(ErrMode::cut)
+}
+
+/// `peek(pattern)`: matches the pattern without consuming input.
+pub fn peek<'i, T>(
+    input: &mut Input<'i>,
+    f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+) -> PResult<T> {
+    let start = input.checkpoint();
+    let result = f(input);
+    input.reset(&start);
+    result
+}
+
+/// `not(pattern)`: succeeds without consuming input if the pattern does not match.
+pub fn not<'i, T>(
+    input: &mut Input<'i>,
+    f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+) -> PResult<()> {
+    skip_whitespace(input);
+    match peek(input, f) {
+        Ok(_) => Err(ErrMode::Backtrack(Error::message(
+            offset(input),
+            "unexpected input",
+        ))),
+        Err(ErrMode::Backtrack(_)) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// `#[expected("label")]`: a failure of the pattern before it consumed any input is reported
+/// as "expected label".
+pub fn expected<'i, T>(
+    input: &mut Input<'i>,
+    label: &str,
+    f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+) -> PResult<T> {
+    skip_whitespace(input);
+    let start = offset(input);
+    f(input).map_err(|e| {
+        e.map(|e| match e.offset == start {
+            true => Error::expected(start, label),
+            false => e,
+        })
+    })
+}
+
+/// `span:(pattern)` and the spans of built-ins: the value of `f` and the byte range of the
+/// input it consumed, without whitespace before it.
+pub fn spanned<'i, T>(
+    input: &mut Input<'i>,
+    f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+) -> PResult<(T, Range<usize>)> {
+    skip_whitespace(input);
+    let start = offset(input);
+    let value = f(input)?;
+    Ok((value, start..offset(input)))
+}
+
+/// `name@(pattern)`: the value of `f` and the text of the input it consumed, without
+/// whitespace before it.
+pub fn recognize<'i, T>(
+    input: &mut Input<'i>,
+    f: impl FnOnce(&mut Input<'i>) -> PResult<T>,
+) -> PResult<(T, &'i str)> {
+    skip_whitespace(input);
+    let rest = remaining(input);
+    let start = offset(input);
+    let value = f(input)?;
+    Ok((value, &rest[..offset(input) - start]))
+}
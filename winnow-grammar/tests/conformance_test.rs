@@ -0,0 +1,111 @@
+use syn_grammar::rt::conformance::{check_builtin, Outcome, PORTABLE_BUILTINS};
+use winnow::stream::Stream;
+use winnow_grammar::rt;
+
+/// The inputs of the syn backend's conformance tests, and some more.
+const INPUTS: &[&str] = &[
+    "foo",
+    "r#foo",
+    "_",
+    "fn",
+    "true",
+    "'a",
+    "\"text\"",
+    "r\"raw\"",
+    "b\"bytes\"",
+    "c\"c string\"",
+    "'c'",
+    "b'b'",
+    "42",
+    "42u8",
+    "-7",
+    "300",
+    "0xff",
+    "0o17",
+    "0b101",
+    "1.5",
+    "1e3",
+    "2.5f32",
+    "+",
+    "(x)",
+    "",
+    "  42 rest",
+    "/* comment */ foo",
+    "foo_bar9 baz",
+    "r#fn",
+    "false",
+    "'\\n'",
+    "'\\u{1F600}'",
+    "\"a\\tb\\\"c\\x41\"",
+    "r#\"hash \" raw\"#",
+    "1_000",
+    "-1.5",
+    "1.0e-2",
+    "0o18",
+    "255u8",
+    "-128",
+    "99999999999999999999999999999999999999999",
+    "123abc",
+];
+
+/// Runs the built-in `name` of this crate on `input`, ignoring the rest of the input.
+fn outcome(name: &str, input: &str) -> Outcome {
+    macro_rules! run {
+        ($f:path) => {
+            rt::parse(input, |input| {
+                let (value, span) = rt::spanned(input, $f)?;
+                input.finish();
+                Ok((value.to_string(), span))
+            })
+        };
+    }
+
+    let result = match name {
+        "ident" => run!(rt::ident),
+        "string" => run!(rt::string),
+        "char" => run!(rt::char),
+        "bool" => run!(rt::bool),
+        "i8" => run!(rt::i8),
+        "i16" => run!(rt::i16),
+        "i32" => run!(rt::i32),
+        "i64" => run!(rt::i64),
+        "i128" => run!(rt::i128),
+        "isize" => run!(rt::isize),
+        "u8" => run!(rt::u8),
+        "u16" => run!(rt::u16),
+        "u32" => run!(rt::u32),
+        "u64" => run!(rt::u64),
+        "u128" => run!(rt::u128),
+        "usize" => run!(rt::usize),
+        "f32" => run!(rt::f32),
+        "f64" => run!(rt::f64),
+        "hex_literal" => run!(rt::hex_literal),
+        "oct_literal" => run!(rt::oct_literal),
+        "bin_literal" => run!(rt::bin_literal),
+        "alpha" => run!(rt::alpha),
+        "digit" => run!(rt::digit),
+        "alphanumeric" => run!(rt::alphanumeric),
+        "hex_digit" => run!(rt::hex_digit),
+        "oct_digit" => run!(rt::oct_digit),
+        _ => panic!("portable built-in `{}` is not implemented", name),
+    };
+    match result {
+        Ok((value, span)) => Outcome::accepted(value, span),
+        Err(_) => Outcome::Rejected,
+    }
+}
+
+#[test]
+fn test_portable_builtins_conform() {
+    let mut failures = Vec::new();
+    for name in PORTABLE_BUILTINS {
+        let cases: Vec<_> = INPUTS
+            .iter()
+            .map(|input| (*input, outcome(name, input)))
+            .collect();
+        if let Err(e) = check_builtin(name, &cases) {
+            failures.push(e);
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
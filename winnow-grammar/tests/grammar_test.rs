@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use winnow_grammar::{grammar, rt};
+
+grammar! {
+    grammar calc {
+        pub rule expr -> i64 =
+            l:expr "+" r:term -> { l + r }
+          | l:expr "-" r:term -> { l - r }
+          | t:term -> { t }
+
+        rule term -> i64 =
+            l:term "*" r:factor -> { l * r }
+          | f:factor -> { f }
+
+        rule factor -> i64 =
+            paren(e:expr) -> { e }
+          | "-" f:factor -> { -f }
+          | i:i64 -> { i }
+    }
+}
+
+#[test]
+fn test_left_recursion() {
+    assert_eq!(rt::parse("1 + 2 * 3", calc::parse_expr), Ok(7));
+    assert_eq!(rt::parse("10 - 2 - 3", calc::parse_expr), Ok(5));
+    assert_eq!(rt::parse("(1 + 2) * -(3)", calc::parse_expr), Ok(-9));
+    assert_eq!(
+        rt::parse(" 1 /* one */ +\n 2 // two\n", calc::parse_expr),
+        Ok(3)
+    );
+}
+
+#[test]
+fn test_errors_report_the_furthest_failure() {
+    let err = rt::parse("1 + * 2", calc::parse_expr).unwrap_err();
+    assert_eq!(err.offset(), 4);
+    assert_eq!(err.to_string(), "expected `(` or `-` or `i64` literal");
+
+    let err = rt::parse("1 2", calc::parse_expr).unwrap_err();
+    assert_eq!(err.offset(), 2);
+    assert!(err.to_string().contains("end of input"), "{}", err);
+
+    let err = rt::parse("(1\n+ 2", calc::parse_expr).unwrap_err();
+    assert_eq!(err.line_column("(1\n+ 2"), (2, 4));
+    assert!(err.to_string().contains("`)`"), "{}", err);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+    Let(String, Option<String>, i64),
+    Call(String, Vec<i64>),
+    Block(Vec<Stmt>),
+}
+
+grammar! {
+    grammar lang {
+        pub rule stmt -> Stmt =
+            "let" => n:ident (":" t:ident)? "=" v:i64 ";" -> { Stmt::Let(n.text, t.map(|t| t.text), v) }
+          | f:ident paren(a:args) ";" -> { Stmt::Call(f.text, a) }
+          | "{" s:stmt* "}" -> { Stmt::Block(s) }
+
+        rule args -> Vec<i64> = a:separated(i64, ",", trailing = true) -> { a }
+
+        pub rule keyword -> String = k:"select"i -> { k }
+
+        pub rule word -> &'static str = w:"in" -> { w }
+    }
+}
+
+#[test]
+fn test_sequences_optionals_and_repetitions() {
+    assert_eq!(
+        rt::parse("let x = 1;", lang::parse_stmt),
+        Ok(Stmt::Let("x".into(), None, 1))
+    );
+    assert_eq!(
+        rt::parse("let x: u8 = 1;", lang::parse_stmt),
+        Ok(Stmt::Let("x".into(), Some("u8".into()), 1))
+    );
+    assert_eq!(
+        rt::parse("{ f(1, 2,); { g(); } }", lang::parse_stmt),
+        Ok(Stmt::Block(vec![
+            Stmt::Call("f".into(), vec![1, 2]),
+            Stmt::Block(vec![Stmt::Call("g".into(), vec![])]),
+        ]))
+    );
+}
+
+#[test]
+fn test_literals_match_whole_words() {
+    // `letx` is an identifier, not `let` followed by `x`
+    let err = rt::parse("letx = 1;", lang::parse_stmt).unwrap_err();
+    assert_eq!(err.offset(), 5);
+    assert_eq!(rt::parse("in", lang::parse_word), Ok("in"));
+    assert!(rt::parse("input", lang::parse_word).is_err());
+    assert_eq!(
+        rt::parse("SeLeCt", lang::parse_keyword),
+        Ok("SeLeCt".to_string())
+    );
+}
+
+#[test]
+fn test_cut_does_not_backtrack() {
+    // After `let =>`, the failure is reported at the `=` instead of trying the call
+    let err = rt::parse("let (1);", lang::parse_stmt).unwrap_err();
+    assert_eq!(err.offset(), 4);
+    assert_eq!(err.to_string(), "expected identifier");
+}
+
+#[derive(Debug)]
+struct Overflow;
+
+impl std::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("too many items")
+    }
+}
+
+grammar! {
+    grammar checks {
+        pub rule even -> u32 =
+            n:u32 where { n % 2 == 0 } -> { n }
+
+        pub rule small -> Vec<u32> throws Overflow =
+            "[" items:u32* "]" -> {
+                if items.len() > 2 {
+                    return Err(Overflow);
+                }
+                items
+            }
+
+        pub rule spans -> (std::ops::Range<usize>, std::ops::Range<usize>) =
+            "x" ("a" "b") @ s -> { (s, $span) }
+
+        pub rule raw -> (&'static str, String) =
+            r@("a" "-" "b") -> { ("raw", r.to_string()) }
+
+        pub rule scaled(factor: u32) -> u32 = n:u32 -> { n * factor }
+
+        pub rule triple -> u32 = n:scaled(3) -> { n }
+
+        pub rule bounded -> Vec<char> = c:char{2,3} -> { c }
+
+        pub rule lookahead -> String =
+            peek("a") i:ident -> { i.text }
+          | not("b") i:ident -> { format!("not b: {}", i) }
+
+        pub rule labeled -> u32 = #[expected("a count")] n:u32 -> { n }
+
+        pub rule committed -> u32 =
+            "#" n:u32! -> { n }
+          | "#" "x" -> { 0 }
+
+        pub rule pairs -> (Vec<String>, Vec<u32>) =
+            (k:ident "=" v:u32)* -> { (k.into_iter().map(|k| k.text).collect(), v) }
+    }
+}
+
+#[test]
+fn test_guards_and_throws() {
+    assert_eq!(rt::parse("4", checks::parse_even), Ok(4));
+    let err = rt::parse("3", checks::parse_even).unwrap_err();
+    assert_eq!(err.to_string(), "condition `n % 2 == 0` does not hold");
+
+    assert_eq!(rt::parse("[1 2]", checks::parse_small), Ok(vec![1, 2]));
+    let err = rt::parse("[1 2 3]", checks::parse_small).unwrap_err();
+    assert_eq!(err.to_string(), "too many items");
+}
+
+#[test]
+fn test_spans_and_raw_text() {
+    assert_eq!(rt::parse("x  a b", checks::parse_spans), Ok((3..6, 0..6)));
+    assert_eq!(
+        rt::parse(" a -  b", checks::parse_raw),
+        Ok(("raw", "a -  b".to_string()))
+    );
+}
+
+#[test]
+fn test_rule_parameters_and_bounds() {
+    assert_eq!(rt::parse("5", checks::parse_triple), Ok(15));
+    assert_eq!(
+        rt::parse("'a' 'b'", checks::parse_bounded),
+        Ok(vec!['a', 'b'])
+    );
+    assert!(rt::parse("'a'", checks::parse_bounded).is_err());
+    assert!(rt::parse("'a' 'b' 'c' 'd'", checks::parse_bounded).is_err());
+}
+
+#[test]
+fn test_lookahead() {
+    assert_eq!(rt::parse("a", checks::parse_lookahead), Ok("a".to_string()));
+    assert_eq!(
+        rt::parse("c", checks::parse_lookahead),
+        Ok("not b: c".to_string())
+    );
+    assert!(rt::parse("b", checks::parse_lookahead).is_err());
+}
+
+#[test]
+fn test_labels_and_commit() {
+    let err = rt::parse("x", checks::parse_labeled).unwrap_err();
+    assert_eq!(err.to_string(), "expected a count");
+
+    // `u32!` does not backtrack to `"#" "x"`
+    let err = rt::parse("# x", checks::parse_committed).unwrap_err();
+    assert_eq!(err.offset(), 2);
+}
+
+#[test]
+fn test_bindings_in_repetitions() {
+    assert_eq!(
+        rt::parse("a = 1 b = 2", checks::parse_pairs),
+        Ok((vec!["a".to_string(), "b".to_string()], vec![1, 2]))
+    );
+}
+
+grammar! {
+    grammar lists {
+        pub rule list -> rt::Delimited<u8> = d:delimited("[", u8, ",", "]") -> { d }
+
+        pub rule at_least_two -> Vec<u8> = v:separated(u8, ",", min = 2) -> { v }
+
+        pub rule config -> HashMap<String, u32> =
+            m:map<HashMap>(ident, "=", u32, sep = ";", trailing = true, unique = true) -> {
+                m.into_iter().map(|(k, v)| (k.text, v)).collect()
+            }
+
+        pub rule stmts -> Vec<String> = s:terminated(ident, ";") -> {
+            s.into_iter().map(|s| s.text).collect()
+        }
+
+        pub rule until_end -> Vec<u8> = v:until_parse(u8, "end") "end" -> { v }
+
+        pub rule embedded -> i64 = v:parse_in(string, sum) -> { v }
+
+        rule sum -> i64 = a:i64 "+" b:i64 -> { a + b }
+
+        pub rule skipped -> u8 = skip("!") n:u8 -> { n }
+
+        pub rule many -> Vec<u8> = v:repeated(u8, min = 1) -> { v }
+    }
+}
+
+#[test]
+fn test_list_builtins() {
+    let list = rt::parse("[1, 2, 3,]", lists::parse_list).unwrap();
+    assert_eq!(list.items, vec![1, 2, 3]);
+    assert_eq!((list.open, list.close), (0..1, 9..10));
+    assert!(rt::parse("[1 2]", lists::parse_list).is_err());
+
+    assert_eq!(rt::parse("1, 2", lists::parse_at_least_two), Ok(vec![1, 2]));
+    let err = rt::parse("1", lists::parse_at_least_two).unwrap_err();
+    assert_eq!(err.to_string(), "expected at least 2 items");
+
+    let config = rt::parse("a = 1; b = 2;", lists::parse_config).unwrap();
+    assert_eq!(config.get("b"), Some(&2));
+    let err = rt::parse("a = 1; a = 2", lists::parse_config).unwrap_err();
+    assert_eq!(
+        (err.offset(), err.to_string()),
+        (7, "duplicate key".to_string())
+    );
+
+    assert_eq!(
+        rt::parse("a; b;", lists::parse_stmts),
+        Ok(vec!["a".to_string(), "b".to_string()])
+    );
+    assert!(rt::parse("a; b", lists::parse_stmts).is_err());
+
+    assert_eq!(rt::parse("1 2 end", lists::parse_until_end), Ok(vec![1, 2]));
+    assert_eq!(rt::parse("! 7", lists::parse_skipped), Ok(7));
+    assert!(rt::parse("", lists::parse_many).is_err());
+}
+
+#[test]
+fn test_parse_in() {
+    assert_eq!(rt::parse(r#""1 + 2""#, lists::parse_embedded), Ok(3));
+    let err = rt::parse(r#"  "1 +""#, lists::parse_embedded).unwrap_err();
+    assert_eq!(err.offset(), 2);
+}
+
+// The same grammar for the syn backend: actions that use the fields of the portable types
+// compile with both
+syn_grammar::grammar! {
+    grammar syn_portable {
+        pub rule entry -> (String, String) = k:ident "=" v:string -> { (k.text, v.value) }
+    }
+}
+
+grammar! {
+    grammar portable {
+        pub rule entry -> (String, String) = k:ident "=" v:string -> { (k.text, v.value) }
+    }
+}
+
+#[test]
+fn test_portable_values() {
+    use syn::parse::Parser;
+
+    let expected = ("key".to_string(), "value".to_string());
+    assert_eq!(
+        syn_portable::parse_entry.parse_str(r#"key = "value""#).ok(),
+        Some(expected.clone())
+    );
+    assert_eq!(
+        rt::parse(r#"key = "value""#, portable::parse_entry),
+        Ok(expected)
+    );
+}